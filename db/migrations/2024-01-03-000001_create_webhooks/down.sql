@@ -0,0 +1,3 @@
+-- Drop tables
+DROP TRIGGER IF EXISTS update_webhooks_updated_at ON webhooks;
+DROP TABLE IF EXISTS webhooks;
\ No newline at end of file
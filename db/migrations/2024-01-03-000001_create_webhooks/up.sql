@@ -0,0 +1,16 @@
+-- Create webhooks table for per-account notification endpoints
+CREATE TABLE webhooks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_id TEXT NOT NULL,
+    url TEXT NOT NULL,
+    event_types TEXT[] NOT NULL,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Create index
+CREATE INDEX idx_webhooks_account_id ON webhooks(account_id);
+
+CREATE TRIGGER update_webhooks_updated_at BEFORE UPDATE
+    ON webhooks FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Index payloads for containment (@>) searches from the admin API
+CREATE INDEX idx_transaction_queue_transaction_data ON transaction_queue USING GIN (transaction_data jsonb_path_ops);
@@ -0,0 +1,3 @@
+-- Drop tables
+DROP TRIGGER IF EXISTS update_limit_change_requests_updated_at ON limit_change_requests;
+DROP TABLE IF EXISTS limit_change_requests;
\ No newline at end of file
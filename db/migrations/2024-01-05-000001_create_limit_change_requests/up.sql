@@ -0,0 +1,21 @@
+-- Create limit_change_requests table for account-requested submit limit changes
+CREATE TABLE limit_change_requests (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_id TEXT NOT NULL,
+    requested_max_requests INTEGER NOT NULL,
+    justification TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    decided_by TEXT,
+    decided_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Create indexes
+CREATE INDEX idx_limit_change_requests_account_id ON limit_change_requests(account_id);
+-- At most one open request per account
+CREATE UNIQUE INDEX idx_limit_change_requests_one_pending
+    ON limit_change_requests(account_id) WHERE status = 'pending';
+
+CREATE TRIGGER update_limit_change_requests_updated_at BEFORE UPDATE
+    ON limit_change_requests FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,8 @@
+-- Drop indexes and columns
+DROP INDEX IF EXISTS idx_audit_log_account_id_created_at_id;
+DROP INDEX IF EXISTS idx_audit_log_event_type_created_at_id;
+DROP INDEX IF EXISTS idx_audit_log_created_at_id;
+CREATE INDEX idx_audit_log_created_at ON audit_log(created_at);
+
+ALTER TABLE audit_log DROP COLUMN account_id;
+ALTER TABLE audit_log DROP COLUMN event_type;
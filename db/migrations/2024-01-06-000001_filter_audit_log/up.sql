@@ -0,0 +1,9 @@
+-- Record what each admin call did and which account it targeted
+ALTER TABLE audit_log ADD COLUMN event_type TEXT;
+ALTER TABLE audit_log ADD COLUMN account_id TEXT;
+
+-- Keyset pagination walks (created_at, id), alone or under a filter
+DROP INDEX IF EXISTS idx_audit_log_created_at;
+CREATE INDEX idx_audit_log_created_at_id ON audit_log(created_at, id);
+CREATE INDEX idx_audit_log_event_type_created_at_id ON audit_log(event_type, created_at, id);
+CREATE INDEX idx_audit_log_account_id_created_at_id ON audit_log(account_id, created_at, id);
@@ -0,0 +1,2 @@
+-- Drop result column
+ALTER TABLE transaction_queue DROP COLUMN result;
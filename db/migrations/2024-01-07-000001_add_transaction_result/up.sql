@@ -0,0 +1,2 @@
+-- Outcome of a completed transaction, e.g. the created account address
+ALTER TABLE transaction_queue ADD COLUMN result JSONB;
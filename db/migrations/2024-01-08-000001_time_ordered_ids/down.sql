@@ -0,0 +1,2 @@
+-- Drop the id comment
+COMMENT ON COLUMN transaction_queue.id IS NULL;
@@ -0,0 +1,4 @@
+-- Ids are generated by the application, which now hands out time-ordered
+-- UUIDv7 ids instead of random v4 ones. Existing rows keep their ids and the
+-- column type is unchanged, so this only records the switch.
+COMMENT ON COLUMN transaction_queue.id IS 'UUIDv7 from the application; rows created before 2024-01-08 have v4 ids';
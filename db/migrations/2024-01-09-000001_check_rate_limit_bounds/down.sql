@@ -0,0 +1,4 @@
+-- Drop the rate limit bounds
+ALTER TABLE rate_limits
+    DROP CONSTRAINT rate_limits_max_requests_check,
+    DROP CONSTRAINT rate_limits_window_seconds_check;
@@ -0,0 +1,7 @@
+-- Keep limits the limiter can enforce out of reach of bad admin input: a
+-- window of 1 second to 30 days, and a max_requests of 0 (block everything)
+-- or more. NOT VALID leaves existing rows alone; the API clamps those when
+-- it reads them.
+ALTER TABLE rate_limits
+    ADD CONSTRAINT rate_limits_max_requests_check CHECK (max_requests >= 0) NOT VALID,
+    ADD CONSTRAINT rate_limits_window_seconds_check CHECK (window_seconds BETWEEN 1 AND 2592000) NOT VALID;
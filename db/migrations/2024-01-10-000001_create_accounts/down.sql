@@ -0,0 +1,3 @@
+-- Drop tables
+DROP TRIGGER IF EXISTS update_accounts_updated_at ON accounts;
+DROP TABLE IF EXISTS accounts;
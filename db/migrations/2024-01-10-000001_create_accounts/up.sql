@@ -0,0 +1,13 @@
+-- Create accounts table recording each account's tier. The limits a tier
+-- implies live in rate_limits, rewritten from the tier's template whenever
+-- the tier changes.
+CREATE TABLE accounts (
+    account_id TEXT PRIMARY KEY,
+    tier TEXT NOT NULL CHECK (tier IN ('basic', 'premium', 'enterprise')),
+    default_priority INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_accounts_updated_at BEFORE UPDATE
+    ON accounts FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,3 @@
+-- Drop tables
+DROP TRIGGER IF EXISTS update_payload_schemas_updated_at ON payload_schemas;
+DROP TABLE IF EXISTS payload_schemas;
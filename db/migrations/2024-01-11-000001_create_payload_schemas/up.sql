@@ -0,0 +1,13 @@
+-- Create payload_schemas table holding the JSON Schema an account's
+-- transaction_data must match. Submit only checks it while enabled, so a
+-- schema can be registered before it is enforced.
+CREATE TABLE payload_schemas (
+    account_id TEXT PRIMARY KEY,
+    schema JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_payload_schemas_updated_at BEFORE UPDATE
+    ON payload_schemas FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,2 @@
+-- Drop index
+DROP INDEX IF EXISTS idx_transaction_queue_account_status;
@@ -0,0 +1,4 @@
+-- Index rows by account and status so an account's pending rows can be
+-- counted without visiting its finished ones, as submit does when it
+-- positions a transaction without Redis
+CREATE INDEX idx_transaction_queue_account_status ON transaction_queue(account_id, status);
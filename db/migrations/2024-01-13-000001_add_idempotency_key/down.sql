@@ -0,0 +1,3 @@
+-- Drop column
+DROP INDEX IF EXISTS idx_transaction_queue_idempotency_key;
+ALTER TABLE transaction_queue DROP COLUMN IF EXISTS idempotency_key;
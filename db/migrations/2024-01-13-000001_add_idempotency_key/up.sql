@@ -0,0 +1,6 @@
+-- Idempotency-Key a client submitted the transaction with. Unique per
+-- account, so a retry that outlives its Redis claim still cannot store a
+-- second row.
+ALTER TABLE transaction_queue ADD COLUMN idempotency_key TEXT;
+CREATE UNIQUE INDEX idx_transaction_queue_idempotency_key ON transaction_queue(account_id, idempotency_key)
+    WHERE idempotency_key IS NOT NULL;
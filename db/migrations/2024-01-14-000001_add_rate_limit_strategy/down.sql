@@ -0,0 +1,2 @@
+-- Drop column
+ALTER TABLE rate_limits DROP COLUMN IF EXISTS strategy;
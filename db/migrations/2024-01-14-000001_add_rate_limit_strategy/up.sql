@@ -0,0 +1,5 @@
+-- How the limiter counts requests against the row: a sliding window of
+-- max_requests per window_seconds, or a token bucket holding max_requests
+-- and refilled over each window
+ALTER TABLE rate_limits ADD COLUMN strategy TEXT NOT NULL DEFAULT 'sliding_window'
+    CONSTRAINT rate_limits_strategy_check CHECK (strategy IN ('sliding_window', 'token_bucket'));
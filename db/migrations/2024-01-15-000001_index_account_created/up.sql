@@ -0,0 +1,3 @@
+-- Index an account's rows in creation order so GET /v1/transactions can page
+-- through them by (created_at, id) without sorting or skipping rows
+CREATE INDEX idx_transaction_queue_account_created ON transaction_queue(account_id, created_at DESC, id DESC);
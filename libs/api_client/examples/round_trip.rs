@@ -0,0 +1,40 @@
+//! Submit a transaction, read it back and cancel it.
+//!
+//! Run against a local API with `cargo run -p api_client --example round_trip`;
+//! set API_BASE_URL to use another one. Cancelling needs the Postgres backed
+//! API, so run it against `just run` rather than `just run-memory`.
+
+use api_client::types::{CancelAllRequest, SubmitTransactionRequest};
+use api_client::{ApiClient, ClientError};
+use serde_json::json;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), ClientError> {
+    let base_url = std::env::var("API_BASE_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    let client = ApiClient::builder(base_url)
+        .timeout(Duration::from_secs(10))
+        .retry_rate_limited(3)
+        .build()?;
+    let account_id = format!("example_{}", uuid::Uuid::new_v4().simple());
+
+    let limits = client.rate_limit_status(&account_id).await?;
+    println!("submit limit: {:?}", limits.rate_limits.first());
+
+    let submitted = client
+        .submit(&SubmitTransactionRequest {
+            account_id: account_id.clone(),
+            transaction_data: json!({ "type": "transfer", "amount": 10 }),
+            priority: Some(5),
+            idempotency_key: None,
+        })
+        .await?;
+    println!("submitted {} at position {:?}", submitted.transaction_id, submitted.queue_position);
+
+    let status = client.get_status(submitted.transaction_id).await?;
+    println!("status: {}", status.status);
+
+    let cancelled = client.cancel_all(&account_id, &CancelAllRequest::default()).await?;
+    println!("cancelled {} pending transactions", cancelled.cancelled);
+    Ok(())
+}
@@ -0,0 +1,301 @@
+//! Typed async client for the transaction queue API.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), api_client::ClientError> {
+//! use api_client::{types::SubmitTransactionRequest, ApiClient};
+//!
+//! let client = ApiClient::builder("http://localhost:3000").retry_rate_limited(3).build()?;
+//! let submitted = client
+//!     .submit(&SubmitTransactionRequest {
+//!         account_id: "acct_1".to_string(),
+//!         transaction_data: serde_json::json!({ "amount": 10 }),
+//!         priority: None,
+//!         idempotency_key: None,
+//!     })
+//!     .await?;
+//! let status = client.get_status(submitted.transaction_id).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Request and response bodies live in `types`, which the server uses for
+//! its handlers, so the two cannot drift apart.
+
+pub mod types;
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use types::{
+    CancelAllRequest, CancelAllResponse, ErrorBody, ErrorResponse, LimitsResponse, ListTransactionsQuery,
+    ListTransactionsResponse, SubmitStreamResult, SubmitTransactionRequest, SubmitTransactionResponse,
+    TransactionStatusResponse,
+};
+use uuid::Uuid;
+
+/// Content type of submit-stream request and response bodies
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Longest a rate limited request waits before its retry unless configured
+pub const DEFAULT_MAX_RETRY_WAIT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Invalid response body: {0}")]
+    Decode(#[from] serde_json::Error),
+
+    /// The API answered with an error status
+    #[error("API error {status}: {}", error.message)]
+    Api {
+        status: StatusCode,
+        /// Boxed, so results carrying the error stay small
+        error: Box<ErrorBody>,
+        /// How long the server asked the client to wait, on 429s and 503s
+        retry_after: Option<Duration>,
+    },
+}
+
+impl ClientError {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Http(e) => e.status(),
+            Self::Decode(_) => None,
+            Self::Api { status, .. } => Some(*status),
+        }
+    }
+
+    /// The error's machine readable code, e.g. "pending_limit_exceeded"
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::Api { error, .. } => error.code.as_deref(),
+            Self::Http(_) | Self::Decode(_) => None,
+        }
+    }
+}
+
+pub struct ApiClientBuilder {
+    base_url: String,
+    api_key: Option<String>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    rate_limit_retries: u32,
+    max_retry_wait: Duration,
+}
+
+impl ApiClientBuilder {
+    /// Sent as a bearer token, for deployments behind an authenticating gateway
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Limit on each request, from connecting to reading the whole response
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Retry a request answered 429 up to `retries` times, waiting as long
+    /// as the response asks. Off by default.
+    pub fn retry_rate_limited(mut self, retries: u32) -> Self {
+        self.rate_limit_retries = retries;
+        self
+    }
+
+    /// Give up retrying a 429 that asks for a longer wait than this
+    pub fn max_retry_wait(mut self, wait: Duration) -> Self {
+        self.max_retry_wait = wait;
+        self
+    }
+
+    pub fn build(self) -> Result<ApiClient, ClientError> {
+        let mut http = reqwest::Client::builder();
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            http = http.connect_timeout(timeout);
+        }
+        Ok(ApiClient {
+            http: http.build()?,
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            api_key: self.api_key,
+            rate_limit_retries: self.rate_limit_retries,
+            max_retry_wait: self.max_retry_wait,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    rate_limit_retries: u32,
+    max_retry_wait: Duration,
+}
+
+impl ApiClient {
+    /// Builder for a client of the API at `base_url`, e.g. "http://localhost:3000"
+    pub fn builder(base_url: impl Into<String>) -> ApiClientBuilder {
+        ApiClientBuilder {
+            base_url: base_url.into(),
+            api_key: None,
+            timeout: None,
+            connect_timeout: None,
+            rate_limit_retries: 0,
+            max_retry_wait: DEFAULT_MAX_RETRY_WAIT,
+        }
+    }
+
+    pub async fn submit<D: Serialize>(
+        &self,
+        request: &SubmitTransactionRequest<D>,
+    ) -> Result<SubmitTransactionResponse, ClientError> {
+        self.send(|| self.http.post(self.url("/v1/transactions/submit")).json(request)).await
+    }
+
+    /// Submit many transactions in one request, one NDJSON line each. Each
+    /// result carries its own status, so a rejected line leaves the others
+    /// submitted. The body is built in memory; stream from another HTTP
+    /// client for uploads too large for that.
+    pub async fn submit_stream<'a, D: Serialize + 'a>(
+        &self,
+        requests: impl IntoIterator<Item = &'a SubmitTransactionRequest<D>>,
+    ) -> Result<Vec<SubmitStreamResult>, ClientError> {
+        let mut body = Vec::new();
+        for request in requests {
+            serde_json::to_writer(&mut body, request)?;
+            body.push(b'\n');
+        }
+        let response = self
+            .execute(|| {
+                self.http
+                    .post(self.url("/v1/transactions/submit-stream"))
+                    .header(CONTENT_TYPE, NDJSON_CONTENT_TYPE)
+                    .body(body.clone())
+            })
+            .await?;
+        let text = response.text().await?;
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    pub async fn get_status(&self, transaction_id: Uuid) -> Result<TransactionStatusResponse, ClientError> {
+        self.send(|| self.http.get(self.url(&format!("/v1/transactions/{}", transaction_id)))).await
+    }
+
+    /// One page of an account's transactions, newest first. Pass the
+    /// response's `next_cursor` back as `cursor` for the next page.
+    pub async fn list_transactions(
+        &self,
+        query: &ListTransactionsQuery,
+    ) -> Result<ListTransactionsResponse, ClientError> {
+        self.send(|| self.http.get(self.url("/v1/transactions")).query(query)).await
+    }
+
+    /// Cancel a transaction that is still pending. One a worker already took
+    /// up is refused with a 409 carrying its status.
+    pub async fn cancel(&self, transaction_id: Uuid) -> Result<TransactionStatusResponse, ClientError> {
+        self.send(|| self.http.delete(self.url(&format!("/v1/transactions/{}", transaction_id)))).await
+    }
+
+    /// Cancel the account's pending transactions matching `request`
+    pub async fn cancel_all(
+        &self,
+        account_id: &str,
+        request: &CancelAllRequest,
+    ) -> Result<CancelAllResponse, ClientError> {
+        let path = format!("/v1/accounts/{}/transactions/cancel-all", account_id);
+        self.send(|| self.http.post(self.url(&path)).json(request)).await
+    }
+
+    /// The limits the API currently enforces on the account
+    pub async fn rate_limit_status(&self, account_id: &str) -> Result<LimitsResponse, ClientError> {
+        self.send(|| self.http.get(self.url("/v1/limits")).query(&[("account_id", account_id)])).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, build: impl Fn() -> RequestBuilder) -> Result<T, ClientError> {
+        Ok(self.execute(build).await?.json().await?)
+    }
+
+    /// Send the request `build` makes, again after each 429 the client is
+    /// configured to retry, and return the first successful response
+    async fn execute(&self, build: impl Fn() -> RequestBuilder) -> Result<Response, ClientError> {
+        let mut retries = 0;
+        loop {
+            let mut request = build();
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+            let response = request.send().await?;
+            if response.status().is_success() {
+                return Ok(response);
+            }
+
+            let error = api_error(response).await?;
+            if let ClientError::Api {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                retry_after: Some(wait),
+                ..
+            } = &error
+            {
+                if retries < self.rate_limit_retries && *wait <= self.max_retry_wait {
+                    retries += 1;
+                    tokio::time::sleep(*wait).await;
+                    continue;
+                }
+            }
+            return Err(error);
+        }
+    }
+}
+
+async fn api_error(response: Response) -> Result<ClientError, ClientError> {
+    let status = response.status();
+    let retry_after = retry_after(response.headers());
+    let text = response.text().await?;
+    let error = match serde_json::from_str::<ErrorResponse>(&text) {
+        Ok(body) => body.error,
+        // Errors from outside the API's handlers, e.g. a proxy in front of it
+        Err(_) => ErrorBody {
+            message: text,
+            status: status.as_u16(),
+            code: None,
+            details: None,
+            retry: None,
+        },
+    };
+    Ok(ClientError::Api {
+        status,
+        error: Box::new(error),
+        retry_after,
+    })
+}
+
+/// Wait asked for by Retry-After in seconds, or else until X-RateLimit-Reset
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+    if let Some(seconds) = header(RETRY_AFTER.as_str()) {
+        return Some(Duration::from_secs(seconds));
+    }
+    let reset_at = header("x-ratelimit-reset")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(reset_at.saturating_sub(now)))
+}
@@ -0,0 +1,314 @@
+//! Request and response bodies of the public API.
+//!
+//! The server's handlers take and return these same types, so a field added
+//! or renamed on one side is added or renamed on the other. Everything here
+//! is plain data; how the server computes the values stays in the server.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Body of every error response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub message: String,
+    pub status: u16,
+    /// Machine readable reason, for errors clients are expected to branch on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Structured detail, such as the limit a 429 was checked against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    /// When a request rejected by a rate limit can be retried, on 429s
+    /// from a rate limit window
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryInfo>,
+}
+
+/// The rate limit headers of a 429, in the error body so clients need not
+/// parse headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryInfo {
+    /// Seconds until the window has room again, rounded up; also sent as
+    /// Retry-After
+    pub retry_after_seconds: u64,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Unix epoch seconds, as in X-RateLimit-Reset
+    pub reset_at: u64,
+}
+
+/// POST /v1/transactions/submit. The server reads `transaction_data` as
+/// the JSON text it arrived as; clients send any serializable value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitTransactionRequest<D = serde_json::Value> {
+    pub account_id: String,
+    pub transaction_data: D,
+    /// Higher is processed first; left out means 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Retries with the same key get the first submit's response instead of
+    /// a second transaction; also accepted as the Idempotency-Key header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitTransactionResponse {
+    pub transaction_id: Uuid,
+    /// `None` when the handler ran out of budget before looking it up
+    pub queue_position: Option<i64>,
+    /// Position among the account's own queued items, so other accounts'
+    /// traffic does not inflate it. Processing follows one order across all
+    /// accounts, so the estimate is still based on `queue_position`.
+    #[serde(default)]
+    pub account_position: Option<i64>,
+    pub estimated_processing_time_seconds: i64,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<RateLimitWarning>,
+    /// How far behind the queue is and how long to wait before the next submit
+    pub backpressure: Backpressure,
+}
+
+/// One line of a POST /v1/transactions/submit-stream response, answering
+/// the request line numbered `line`. Exactly one of `result` and `error` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitStreamResult {
+    /// 1-indexed line of the request body; blank lines get no result
+    pub line: u64,
+    /// What POST /v1/transactions/submit would have answered for the line
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<SubmitTransactionResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
+}
+
+/// Sent while an account is past its soft limit but still under the hard limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitWarning {
+    pub code: String,
+    pub message: String,
+    pub used: u32,
+    pub limit: u32,
+    pub threshold_pct: u32,
+    pub reset_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackpressureLevel {
+    None,
+    Elevated,
+    High,
+}
+
+impl BackpressureLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Elevated => "elevated",
+            Self::High => "high",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Backpressure {
+    pub level: BackpressureLevel,
+    pub suggested_delay_ms: u64,
+}
+
+impl Backpressure {
+    pub const NONE: Self = Self {
+        level: BackpressureLevel::None,
+        suggested_delay_ms: 0,
+    };
+}
+
+/// GET /v1/transactions/:id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionStatusResponse {
+    pub id: Uuid,
+    pub account_id: String,
+    pub status: String,
+    pub priority: i32,
+    pub retry_count: i32,
+    pub created_at: DateTime<Utc>,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub error_message: Option<String>,
+    /// What processing produced, once the transaction has completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    /// Why workers are currently skipping this transaction, while it is pending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processing_hold: Option<ProcessingHoldResponse>,
+    /// Seconds from submission until processing finished, or until now
+    pub elapsed_wait_seconds: i64,
+    /// Where a pending transaction stands now. Read back, this is `Some`
+    /// with every field `None` for a transaction that is not pending.
+    #[serde(flatten)]
+    pub wait: Option<WaitEstimate>,
+}
+
+/// A pending transaction's place in the queue, recomputed on every read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaitEstimate {
+    pub queue_position: Option<i64>,
+    /// `None` when no honest estimate can be made, with the reason beside it
+    pub estimated_processing_time_seconds: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate_unavailable_reason: Option<EstimateUnavailable>,
+}
+
+/// Why a queued item has no estimate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateUnavailable {
+    /// No worker has sent a heartbeat recently, so nothing is draining the queue
+    NoLiveWorkers,
+    /// The item is not in the queue, e.g. a worker popped it a moment ago
+    NotQueued,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessingHoldResponse {
+    /// e.g. "account_paused" or "concurrency_cap"
+    pub reason: String,
+    pub since: DateTime<Utc>,
+}
+
+/// GET /v1/transactions. Which of an account's transactions to list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListTransactionsQuery {
+    pub account_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Lowest priority listed, inclusive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_priority: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListTransactionsResponse {
+    /// Newest first
+    pub items: Vec<TransactionStatusResponse>,
+    /// Pass as `cursor` with the same filters to get the next page; absent
+    /// on the last page
+    pub next_cursor: Option<String>,
+    /// The account's transactions in each status, whatever the other filters
+    pub status_counts: BTreeMap<String, i64>,
+}
+
+/// POST /v1/accounts/:account_id/transactions/cancel-all. Which pending
+/// transactions to cancel; an empty body cancels them all.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CancelAllRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelAllResponse {
+    pub cancelled: u64,
+    /// Queue entries removed. Lower than `cancelled` when a worker popped an
+    /// entry first, or when Redis could not be reached; either way the
+    /// worker skips cancelled rows.
+    pub removed_from_queue: u64,
+    /// The first cancelled transactions; see `has_more`
+    pub transaction_ids: Vec<Uuid>,
+    pub has_more: bool,
+}
+
+/// Where the limit applied to an account came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitSource {
+    /// The account's own rate_limits row
+    AccountConfig,
+    /// The SUBMIT_TIER_LIMITS entry matching the account id
+    TierDefault,
+    /// The service-wide default
+    GlobalDefault,
+}
+
+impl LimitSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AccountConfig => "account_config",
+            Self::TierDefault => "tier_default",
+            Self::GlobalDefault => "global_default",
+        }
+    }
+}
+
+/// A limit as the server applies it, sent as the details of a 429 and
+/// listed by GET /v1/limits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitPolicy {
+    pub limit_type: Cow<'static, str>,
+    pub source: LimitSource,
+    pub max_requests: u32,
+    /// `None` for limits that are not per window, such as the pending cap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_seconds: Option<u64>,
+}
+
+impl RateLimitPolicy {
+    /// Value of the X-RateLimit-Policy header:
+    /// "<max>;w=<window>;type=<limit_type>;source=<source>"
+    pub fn header_value(&self) -> String {
+        let window = self.window_seconds.map(|window| format!(";w={}", window)).unwrap_or_default();
+        format!(
+            "{}{};type={};source={}",
+            self.max_requests,
+            window,
+            self.limit_type,
+            self.source.as_str()
+        )
+    }
+}
+
+/// GET /v1/limits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimitsResponse {
+    pub account_id: String,
+    /// Per window limits, in the shape 429 details report them
+    pub rate_limits: Vec<RateLimitPolicy>,
+    /// Most transactions the account may have waiting or in flight
+    pub max_pending: RateLimitPolicy,
+    /// Share of the submit limit after which submits carry a warning
+    pub soft_limit_pct: u32,
+    pub priority: PriorityRange,
+    pub max_body_bytes: usize,
+    pub max_transaction_data_bytes: usize,
+    pub supported_content_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriorityRange {
+    pub min: i32,
+    pub max: i32,
+    /// Priority of a submit that leaves it out
+    pub default: i32,
+}
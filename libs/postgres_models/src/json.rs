@@ -0,0 +1,23 @@
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::serialize::{IsNull, Output, ToSql};
+use diesel::sql_types::Jsonb;
+use serde_json::value::RawValue;
+use std::io::Write;
+
+/// Binary format version prefix Postgres expects on jsonb values
+const JSONB_VERSION: u8 = 1;
+
+/// Binds already-serialized JSON text to a jsonb column without parsing it
+/// into a `serde_json::Value` first. Postgres validates the text on insert.
+#[derive(Debug, Clone, Copy, AsExpression)]
+#[diesel(sql_type = Jsonb)]
+pub struct RawJsonb<'a>(pub &'a RawValue);
+
+impl ToSql<Jsonb, Pg> for RawJsonb<'_> {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        out.write_all(&[JSONB_VERSION])?;
+        out.write_all(self.0.get().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
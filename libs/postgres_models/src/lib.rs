@@ -1,11 +1,18 @@
+pub mod json;
 pub mod models;
+pub mod processing;
 pub mod schema;
+pub mod sources;
+pub mod transaction_data;
+pub mod truncate;
 
 use bb8::Pool;
-use diesel_async::pooled_connection::AsyncDieselConnectionManager;
-use diesel_async::AsyncPgConnection;
+use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig};
+use diesel_async::{AsyncConnection, AsyncPgConnection};
 use std::time::Duration;
 
+pub use service_config::PoolConfig;
+
 pub type DbPool = Pool<AsyncDieselConnectionManager<AsyncPgConnection>>;
 pub type DbConnection = bb8::PooledConnection<'static, AsyncDieselConnectionManager<AsyncPgConnection>>;
 
@@ -19,18 +26,75 @@ pub enum DbError {
     
     #[error("Connection error: {0}")]
     Connection(String),
+
+    #[error("Batch insert failed in chunk {chunk_index}: {source}")]
+    BatchChunk {
+        chunk_index: usize,
+        #[source]
+        source: diesel::result::Error,
+    },
+
+    #[error("Invalid status transition from {from} to {to}")]
+    InvalidTransition { from: &'static str, to: &'static str },
+
+    #[error("Unknown transaction status {0:?}")]
+    UnknownStatus(String),
 }
 
+/// Idle connections the pool keeps open unless configured otherwise
+pub const MIN_IDLE_CONNECTIONS: u32 = 5;
+/// Connections the pool opens at most unless configured otherwise
+pub const MAX_CONNECTIONS: u32 = 20;
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
 pub async fn create_pool(database_url: &str) -> Result<DbPool, DbError> {
-    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(database_url);
-    
-    Pool::builder()
-        .max_size(20)
-        .min_idle(Some(5))
-        .connection_timeout(Duration::from_secs(30))
-        .idle_timeout(Some(Duration::from_secs(600)))
-        .test_on_check_out(true)
-        .build(config)
+    create_pool_with_config(database_url, &PoolConfig::default()).await
+}
+
+/// A pool sized and timed by `config`, with the defaults above for whatever
+/// it leaves unset
+pub async fn create_pool_with_config(database_url: &str, config: &PoolConfig) -> Result<DbPool, DbError> {
+    pool_builder(config)
+        .min_idle(Some(config.min_idle.unwrap_or(MIN_IDLE_CONNECTIONS)))
+        .build(manager(database_url, config))
         .await
         .map_err(|e| DbError::Connection(e.to_string()))
-}
\ No newline at end of file
+}
+
+/// A pool that opens no connection until one is borrowed, for processes and
+/// tests that may never touch the database
+pub fn create_lazy_pool(database_url: &str) -> DbPool {
+    create_lazy_pool_with_config(database_url, &PoolConfig::default())
+}
+
+pub fn create_lazy_pool_with_config(database_url: &str, config: &PoolConfig) -> DbPool {
+    pool_builder(config).build_unchecked(manager(database_url, config))
+}
+
+fn pool_builder(config: &PoolConfig) -> bb8::Builder<AsyncDieselConnectionManager<AsyncPgConnection>> {
+    // bb8 has one timeout for a checkout, covering both the wait for a free
+    // connection and opening a new one; the connect timeout bounds the latter
+    Pool::builder()
+        .max_size(config.max_size.unwrap_or(MAX_CONNECTIONS))
+        .connection_timeout(config.wait_timeout.unwrap_or(WAIT_TIMEOUT))
+        .idle_timeout(Some(config.idle_timeout.unwrap_or(IDLE_TIMEOUT)))
+        .test_on_check_out(true)
+}
+
+fn manager(database_url: &str, config: &PoolConfig) -> AsyncDieselConnectionManager<AsyncPgConnection> {
+    let Some(connect_timeout) = config.connect_timeout else {
+        return AsyncDieselConnectionManager::new(database_url);
+    };
+    let mut manager_config = ManagerConfig::default();
+    manager_config.custom_setup = Box::new(move |url| {
+        Box::pin(async move {
+            tokio::time::timeout(connect_timeout, AsyncPgConnection::establish(url))
+                .await
+                .map_err(|_| {
+                    diesel::ConnectionError::BadConnection(format!("Timed out connecting after {:?}", connect_timeout))
+                })?
+        })
+    });
+    AsyncDieselConnectionManager::new_with_config(database_url, manager_config)
+}
@@ -0,0 +1,238 @@
+use crate::models::{NewRateLimit, RateLimit};
+use crate::schema::{accounts, rate_limits};
+use crate::DbError;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = accounts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Account {
+    pub account_id: String,
+    pub tier: String,
+    /// Default priority of the account's tier
+    pub default_priority: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = accounts)]
+pub struct NewAccount {
+    pub account_id: String,
+    pub tier: String,
+    pub default_priority: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountTier {
+    Basic,
+    Premium,
+    Enterprise,
+}
+
+/// A rate_limits row a tier implies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateLimit {
+    pub limit_type: &'static str,
+    pub max_requests: i32,
+    pub window_seconds: i32,
+}
+
+/// Everything an account's tier decides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierTemplate {
+    pub default_priority: i32,
+    pub limits: &'static [TemplateLimit],
+}
+
+const fn limit(limit_type: &'static str, max_requests: i32, window_seconds: i32) -> TemplateLimit {
+    TemplateLimit {
+        limit_type,
+        max_requests,
+        window_seconds,
+    }
+}
+
+/// Submits per minute, pending cap and daily quota. The first two use the
+/// limit types the API enforces; the pending cap has no window, and 60
+/// satisfies the table's bounds.
+const BASIC_LIMITS: &[TemplateLimit] = &[
+    limit("submit", 100, 60),
+    limit("max_pending", 1_000, 60),
+    limit("daily_quota", 50_000, 86_400),
+];
+const PREMIUM_LIMITS: &[TemplateLimit] = &[
+    limit("submit", 500, 60),
+    limit("max_pending", 5_000, 60),
+    limit("daily_quota", 250_000, 86_400),
+];
+const ENTERPRISE_LIMITS: &[TemplateLimit] = &[
+    limit("submit", 2_000, 60),
+    limit("max_pending", 20_000, 60),
+    limit("daily_quota", 1_000_000, 86_400),
+];
+
+impl AccountTier {
+    pub const ALL: [Self; 3] = [Self::Basic, Self::Premium, Self::Enterprise];
+
+    /// The tier stored as `value`, if any
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|tier| tier.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Basic => "basic",
+            Self::Premium => "premium",
+            Self::Enterprise => "enterprise",
+        }
+    }
+
+    pub fn template(&self) -> TierTemplate {
+        match self {
+            Self::Basic => TierTemplate {
+                default_priority: 0,
+                limits: BASIC_LIMITS,
+            },
+            Self::Premium => TierTemplate {
+                default_priority: 2,
+                limits: PREMIUM_LIMITS,
+            },
+            Self::Enterprise => TierTemplate {
+                default_priority: 5,
+                limits: ENTERPRISE_LIMITS,
+            },
+        }
+    }
+}
+
+/// One rate_limits row rewritten by a tier change; `previous_*` are `None`
+/// when the account had no row of this type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimitChange {
+    pub limit_type: String,
+    pub previous_max_requests: Option<i32>,
+    pub previous_window_seconds: Option<i32>,
+    pub max_requests: i32,
+    pub window_seconds: i32,
+}
+
+/// What `upgrade_account_tier` changed; `previous_*` are `None` when the
+/// account had no accounts row yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierChange {
+    pub account_id: String,
+    pub previous_tier: Option<String>,
+    pub tier: String,
+    pub previous_default_priority: Option<i32>,
+    pub default_priority: i32,
+    pub limits: Vec<LimitChange>,
+}
+
+impl Account {
+    pub async fn find(conn: &mut AsyncPgConnection, account_id: &str) -> Result<Option<Account>, DbError> {
+        let account = accounts::table
+            .filter(accounts::account_id.eq(account_id))
+            .select(Account::as_select())
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(account)
+    }
+}
+
+/// Move an account to `new_tier`, creating its accounts row if it has none,
+/// and rewrite every rate_limits row the tier's template covers. Limit types
+/// outside the template, such as a soft limit, are left alone.
+///
+/// Runs in one transaction with the account row locked, so concurrent changes
+/// to the same account apply one after the other and submits never read a
+/// mix of two tiers' limits. Callers drop cached limits once it returns.
+pub async fn upgrade_account_tier(
+    conn: &mut AsyncPgConnection,
+    account_id: &str,
+    new_tier: AccountTier,
+) -> Result<TierChange, DbError> {
+    let template = new_tier.template();
+    conn.transaction::<_, DbError, _>(|conn| {
+        async move {
+            let previous = accounts::table
+                .filter(accounts::account_id.eq(account_id))
+                .select(Account::as_select())
+                .for_update()
+                .first(conn)
+                .await
+                .optional()?;
+
+            let account = diesel::insert_into(accounts::table)
+                .values(&NewAccount {
+                    account_id: account_id.to_string(),
+                    tier: new_tier.as_str().to_string(),
+                    default_priority: template.default_priority,
+                })
+                .on_conflict(accounts::account_id)
+                .do_update()
+                .set((
+                    accounts::tier.eq(excluded(accounts::tier)),
+                    accounts::default_priority.eq(excluded(accounts::default_priority)),
+                ))
+                .returning(Account::as_returning())
+                .get_result(conn)
+                .await?;
+
+            let limit_types: Vec<&str> = template.limits.iter().map(|limit| limit.limit_type).collect();
+            let previous_limits = rate_limits::table
+                .filter(rate_limits::account_id.eq(account_id))
+                .filter(rate_limits::limit_type.eq_any(&limit_types))
+                .select(RateLimit::as_select())
+                .for_update()
+                .load(conn)
+                .await?;
+
+            let mut limits = Vec::with_capacity(template.limits.len());
+            for template_limit in template.limits {
+                let limit = diesel::insert_into(rate_limits::table)
+                    .values(&NewRateLimit::new(
+                        account_id.to_string(),
+                        template_limit.limit_type.to_string(),
+                        template_limit.max_requests,
+                        template_limit.window_seconds,
+                    ))
+                    .on_conflict((rate_limits::account_id, rate_limits::limit_type))
+                    .do_update()
+                    .set((
+                        rate_limits::max_requests.eq(excluded(rate_limits::max_requests)),
+                        rate_limits::window_seconds.eq(excluded(rate_limits::window_seconds)),
+                    ))
+                    .returning(RateLimit::as_returning())
+                    .get_result(conn)
+                    .await?;
+
+                let previous = previous_limits.iter().find(|row| row.limit_type == limit.limit_type);
+                limits.push(LimitChange {
+                    limit_type: limit.limit_type,
+                    previous_max_requests: previous.map(|row| row.max_requests),
+                    previous_window_seconds: previous.map(|row| row.window_seconds),
+                    max_requests: limit.max_requests,
+                    window_seconds: limit.window_seconds,
+                });
+            }
+
+            Ok(TierChange {
+                account_id: account.account_id,
+                previous_tier: previous.as_ref().map(|account| account.tier.clone()),
+                tier: account.tier,
+                previous_default_priority: previous.map(|account| account.default_priority),
+                default_priority: account.default_priority,
+                limits,
+            })
+        }
+        .scope_boxed()
+    })
+    .await
+}
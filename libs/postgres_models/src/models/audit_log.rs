@@ -0,0 +1,152 @@
+use crate::schema::audit_log;
+use crate::DbError;
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Timestamptz, Uuid as SqlUuid};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AuditLog {
+    pub id: Uuid,
+    pub actor: String,
+    pub method: String,
+    pub path: String,
+    pub status_code: i32,
+    pub created_at: DateTime<Utc>,
+    /// Method and route pattern of the call, e.g. "PUT /v1/admin/accounts/:account_id/pause".
+    /// Absent for calls that matched no route and for entries written before it was recorded.
+    pub event_type: Option<String>,
+    /// Account named in the call's path, if any
+    pub account_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditLog {
+    pub id: Uuid,
+    pub actor: String,
+    pub method: String,
+    pub path: String,
+    pub status_code: i32,
+    pub event_type: Option<String>,
+    pub account_id: Option<String>,
+}
+
+impl NewAuditLog {
+    pub fn new(actor: String, method: String, path: String, status_code: i32) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            actor,
+            method,
+            path,
+            status_code,
+            event_type: None,
+            account_id: None,
+        }
+    }
+
+    pub fn with_event_type(mut self, event_type: Option<String>) -> Self {
+        self.event_type = event_type;
+        self
+    }
+
+    pub fn with_account_id(mut self, account_id: Option<String>) -> Self {
+        self.account_id = account_id;
+        self
+    }
+}
+
+/// Filters of an audit log listing; any left unset match every entry
+#[derive(Debug, Clone, Default)]
+pub struct AuditLogFilter {
+    /// Entries created at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Entries created before this time
+    pub until: Option<DateTime<Utc>>,
+    pub event_type: Option<String>,
+    pub account_id: Option<String>,
+}
+
+/// Position of the last entry of a page; the next page starts just after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl AuditCursor {
+    pub fn after(entry: &AuditLog) -> Self {
+        Self {
+            created_at: entry.created_at,
+            id: entry.id,
+        }
+    }
+
+    /// Opaque text form handed to clients as `next_cursor`
+    pub fn encode(&self) -> String {
+        format!(
+            "{}_{}",
+            self.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.id.simple()
+        )
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let (created_at, id) = cursor.rsplit_once('_')?;
+        Some(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at).ok()?.with_timezone(&Utc),
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+impl AuditLog {
+    /// One page of entries, newest first, in a single query.
+    ///
+    /// Pages are keyed on `(created_at, id)` rather than an offset, so
+    /// entries written while a client pages through never shift or repeat
+    /// the rows it has yet to see. Each filter combination is served by an
+    /// index ending in `(created_at, id)`.
+    pub async fn list(
+        conn: &mut AsyncPgConnection,
+        filter: &AuditLogFilter,
+        after: Option<AuditCursor>,
+        limit: i64,
+    ) -> Result<Vec<AuditLog>, DbError> {
+        let mut query = audit_log::table.select(AuditLog::as_select()).into_boxed();
+        if let Some(since) = filter.since {
+            query = query.filter(audit_log::created_at.ge(since));
+        }
+        if let Some(until) = filter.until {
+            query = query.filter(audit_log::created_at.lt(until));
+        }
+        if let Some(event_type) = &filter.event_type {
+            query = query.filter(audit_log::event_type.eq(event_type));
+        }
+        if let Some(account_id) = &filter.account_id {
+            query = query.filter(audit_log::account_id.eq(account_id));
+        }
+        if let Some(after) = after {
+            // A row comparison, which Postgres answers with one index range scan
+            query = query.filter(
+                sql::<Bool>("(audit_log.created_at, audit_log.id) < (")
+                    .bind::<Timestamptz, _>(after.created_at)
+                    .sql(", ")
+                    .bind::<SqlUuid, _>(after.id)
+                    .sql(")"),
+            );
+        }
+
+        let rows = query
+            .order((audit_log::created_at.desc(), audit_log::id.desc()))
+            .limit(limit)
+            .load(conn)
+            .await?;
+        Ok(rows)
+    }
+}
@@ -0,0 +1,173 @@
+use crate::models::{NewRateLimit, RateLimit};
+use crate::schema::{limit_change_requests, rate_limits};
+use crate::DbError;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = limit_change_requests)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct LimitChangeRequest {
+    pub id: Uuid,
+    pub account_id: String,
+    pub requested_max_requests: i32,
+    pub justification: String,
+    pub status: String,
+    /// Admin who approved or denied the request
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = limit_change_requests)]
+pub struct NewLimitChangeRequest {
+    pub id: Uuid,
+    pub account_id: String,
+    pub requested_max_requests: i32,
+    pub justification: String,
+    pub status: String,
+}
+
+impl NewLimitChangeRequest {
+    pub fn new(account_id: String, requested_max_requests: i32, justification: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            account_id,
+            requested_max_requests,
+            justification,
+            status: LimitRequestStatus::Pending.as_str().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimitRequestStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl LimitRequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Denied => "denied",
+        }
+    }
+}
+
+impl LimitChangeRequest {
+    pub async fn find(conn: &mut AsyncPgConnection, id: Uuid) -> Result<Option<LimitChangeRequest>, DbError> {
+        let request = limit_change_requests::table
+            .filter(limit_change_requests::id.eq(id))
+            .select(LimitChangeRequest::as_select())
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(request)
+    }
+
+    /// An account's requests, newest first
+    pub async fn list_for_account(
+        conn: &mut AsyncPgConnection,
+        account_id: &str,
+    ) -> Result<Vec<LimitChangeRequest>, DbError> {
+        let requests = limit_change_requests::table
+            .filter(limit_change_requests::account_id.eq(account_id))
+            .order(limit_change_requests::created_at.desc())
+            .select(LimitChangeRequest::as_select())
+            .load(conn)
+            .await?;
+        Ok(requests)
+    }
+
+    /// Every request still waiting for a decision, oldest first
+    pub async fn list_pending(conn: &mut AsyncPgConnection) -> Result<Vec<LimitChangeRequest>, DbError> {
+        let requests = limit_change_requests::table
+            .filter(limit_change_requests::status.eq(LimitRequestStatus::Pending.as_str()))
+            .order(limit_change_requests::created_at.asc())
+            .select(LimitChangeRequest::as_select())
+            .load(conn)
+            .await?;
+        Ok(requests)
+    }
+
+    /// Approve a pending request and set the account's `limit_type` limit to
+    /// the requested maximum, in one transaction. An existing limit keeps its
+    /// window; a new one gets `window_seconds`.
+    ///
+    /// Returns `None`, changing nothing, when no pending request has this id.
+    pub async fn approve(
+        conn: &mut AsyncPgConnection,
+        id: Uuid,
+        decided_by: &str,
+        limit_type: &str,
+        window_seconds: i32,
+    ) -> Result<Option<(LimitChangeRequest, RateLimit)>, DbError> {
+        conn.transaction::<_, DbError, _>(|conn| {
+            async move {
+                let Some(request) = Self::decide(conn, id, LimitRequestStatus::Approved, decided_by).await? else {
+                    return Ok(None);
+                };
+
+                let limit = diesel::insert_into(rate_limits::table)
+                    .values(&NewRateLimit::new(
+                        request.account_id.clone(),
+                        limit_type.to_string(),
+                        request.requested_max_requests,
+                        window_seconds,
+                    ))
+                    .on_conflict((rate_limits::account_id, rate_limits::limit_type))
+                    .do_update()
+                    .set(rate_limits::max_requests.eq(excluded(rate_limits::max_requests)))
+                    .returning(RateLimit::as_returning())
+                    .get_result(conn)
+                    .await?;
+                Ok(Some((request, limit)))
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// Deny a pending request. Returns `None` when no pending request has this id.
+    pub async fn deny(
+        conn: &mut AsyncPgConnection,
+        id: Uuid,
+        decided_by: &str,
+    ) -> Result<Option<LimitChangeRequest>, DbError> {
+        Self::decide(conn, id, LimitRequestStatus::Denied, decided_by).await
+    }
+
+    /// Move a request out of pending, only if it is still pending
+    async fn decide(
+        conn: &mut AsyncPgConnection,
+        id: Uuid,
+        decision: LimitRequestStatus,
+        decided_by: &str,
+    ) -> Result<Option<LimitChangeRequest>, DbError> {
+        let request = diesel::update(
+            limit_change_requests::table
+                .filter(limit_change_requests::id.eq(id))
+                .filter(limit_change_requests::status.eq(LimitRequestStatus::Pending.as_str())),
+        )
+        .set((
+            limit_change_requests::status.eq(decision.as_str()),
+            limit_change_requests::decided_by.eq(decided_by),
+            limit_change_requests::decided_at.eq(Utc::now()),
+        ))
+        .returning(LimitChangeRequest::as_returning())
+        .get_result(conn)
+        .await
+        .optional()?;
+        Ok(request)
+    }
+}
@@ -1,5 +1,15 @@
 pub mod transaction_queue;
 pub mod rate_limits;
+pub mod audit_log;
+pub mod webhooks;
+pub mod limit_change_requests;
+pub mod accounts;
+pub mod payload_schemas;
 
 pub use transaction_queue::*;
-pub use rate_limits::*;
\ No newline at end of file
+pub use rate_limits::*;
+pub use audit_log::*;
+pub use webhooks::*;
+pub use limit_change_requests::*;
+pub use accounts::*;
+pub use payload_schemas::*;
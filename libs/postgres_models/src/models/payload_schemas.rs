@@ -0,0 +1,79 @@
+use crate::schema::payload_schemas;
+use crate::DbError;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = payload_schemas)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PayloadSchema {
+    pub account_id: String,
+    /// JSON Schema the account's transaction_data must match
+    pub schema: serde_json::Value,
+    /// Whether submit checks payloads against the schema
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = payload_schemas)]
+pub struct NewPayloadSchema {
+    pub account_id: String,
+    pub schema: serde_json::Value,
+    pub enabled: bool,
+}
+
+impl PayloadSchema {
+    pub async fn find(conn: &mut AsyncPgConnection, account_id: &str) -> Result<Option<PayloadSchema>, DbError> {
+        let schema = payload_schemas::table
+            .filter(payload_schemas::account_id.eq(account_id))
+            .select(PayloadSchema::as_select())
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(schema)
+    }
+
+    /// The account's schema if it is enabled
+    pub async fn find_enabled(
+        conn: &mut AsyncPgConnection,
+        account_id: &str,
+    ) -> Result<Option<serde_json::Value>, DbError> {
+        let schema = payload_schemas::table
+            .filter(payload_schemas::account_id.eq(account_id))
+            .filter(payload_schemas::enabled.eq(true))
+            .select(payload_schemas::schema)
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(schema)
+    }
+
+    /// Register or replace an account's schema
+    pub async fn upsert(conn: &mut AsyncPgConnection, schema: &NewPayloadSchema) -> Result<PayloadSchema, DbError> {
+        let schema = diesel::insert_into(payload_schemas::table)
+            .values(schema)
+            .on_conflict(payload_schemas::account_id)
+            .do_update()
+            .set((
+                payload_schemas::schema.eq(excluded(payload_schemas::schema)),
+                payload_schemas::enabled.eq(excluded(payload_schemas::enabled)),
+            ))
+            .returning(PayloadSchema::as_returning())
+            .get_result(conn)
+            .await?;
+        Ok(schema)
+    }
+
+    /// Remove an account's schema. Returns false if it had none.
+    pub async fn delete(conn: &mut AsyncPgConnection, account_id: &str) -> Result<bool, DbError> {
+        let deleted = diesel::delete(payload_schemas::table.filter(payload_schemas::account_id.eq(account_id)))
+            .execute(conn)
+            .await?;
+        Ok(deleted > 0)
+    }
+}
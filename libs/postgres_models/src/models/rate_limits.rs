@@ -4,6 +4,9 @@ use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Strategy of rows that do not name one
+pub const DEFAULT_LIMIT_STRATEGY: &str = "sliding_window";
+
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
 #[diesel(table_name = rate_limits)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -15,6 +18,8 @@ pub struct RateLimit {
     pub window_seconds: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// `sliding_window` or `token_bucket`
+    pub strategy: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
@@ -25,6 +30,7 @@ pub struct NewRateLimit {
     pub limit_type: String,
     pub max_requests: i32,
     pub window_seconds: i32,
+    pub strategy: String,
 }
 
 impl NewRateLimit {
@@ -35,6 +41,12 @@ impl NewRateLimit {
             limit_type,
             max_requests,
             window_seconds,
+            strategy: DEFAULT_LIMIT_STRATEGY.to_string(),
         }
     }
+
+    pub fn with_strategy(mut self, strategy: impl Into<String>) -> Self {
+        self.strategy = strategy.into();
+        self
+    }
 }
\ No newline at end of file
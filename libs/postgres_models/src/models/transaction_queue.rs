@@ -1,9 +1,24 @@
+use crate::json::RawJsonb;
 use crate::schema::transaction_queue;
-use chrono::{DateTime, Utc};
+use crate::sources::{Clock, IdGenerator};
+use crate::{truncate, DbError};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use diesel::dsl::sql;
 use diesel::prelude::*;
+use diesel::sql_types::{Bool, Timestamptz, Uuid as SqlUuid};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::str::FromStr;
 use uuid::Uuid;
 
+/// Postgres rejects statements with more bind parameters than this
+pub const POSTGRES_MAX_BIND_PARAMS: usize = 65535;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
 #[diesel(table_name = transaction_queue)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -20,6 +35,556 @@ pub struct TransactionQueue {
     pub scheduled_at: Option<DateTime<Utc>>,
     pub processed_at: Option<DateTime<Utc>>,
     pub error_message: Option<String>,
+    /// What processing produced, set when the row completes
+    pub result: Option<serde_json::Value>,
+}
+
+/// Filters for `TransactionQueue::search`; `None` fields match every row
+#[derive(Debug, Clone, Default)]
+pub struct TransactionSearch {
+    pub account_id: Option<String>,
+    pub status: Option<TransactionStatus>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Rows whose transaction_data contains this document (jsonb `@>`)
+    pub payload: Option<serde_json::Value>,
+}
+
+/// Filters for `TransactionQueue::cancel_pending`; `None` fields match every row
+#[derive(Debug, Clone, Default)]
+pub struct CancelFilter {
+    /// Lowest priority cancelled, inclusive
+    pub min_priority: Option<i32>,
+    /// Highest priority cancelled, inclusive
+    pub max_priority: Option<i32>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+/// Columns a status transition writes besides the status; `None` fields
+/// are left as they are
+#[derive(AsChangeset)]
+#[diesel(table_name = transaction_queue)]
+struct StatusChange<'a> {
+    status: &'a str,
+    processed_at: Option<Option<DateTime<Utc>>>,
+    error_message: Option<&'a str>,
+    scheduled_at: Option<DateTime<Utc>>,
+}
+
+/// A row `TransactionQueue::cancel_pending` cancelled
+#[derive(Debug, Clone, PartialEq, Queryable, Selectable)]
+#[diesel(table_name = transaction_queue)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct CancelledTransaction {
+    pub id: Uuid,
+    pub priority: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters of `TransactionQueue::list`, within one account; `None` fields
+/// match every row
+#[derive(Debug, Clone)]
+pub struct TransactionListFilter {
+    pub account_id: String,
+    pub status: Option<TransactionStatus>,
+    /// Lowest priority listed, inclusive
+    pub min_priority: Option<i32>,
+    /// Rows created at or after this time
+    pub created_after: Option<DateTime<Utc>>,
+}
+
+/// Position of the last row of a page; the next page starts just after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl TransactionCursor {
+    pub fn after(transaction: &TransactionQueue) -> Self {
+        Self {
+            created_at: transaction.created_at,
+            id: transaction.id,
+        }
+    }
+
+    /// Opaque token handed to clients as `next_cursor`: the row's creation
+    /// time, to the microsecond Postgres stores, and id, base64 encoded
+    pub fn encode(&self) -> String {
+        let position = format!(
+            "{}_{}",
+            self.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.id.simple()
+        );
+        URL_SAFE_NO_PAD.encode(position)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let position = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let (created_at, id) = position.rsplit_once('_')?;
+        Some(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at).ok()?.with_timezone(&Utc),
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+impl TransactionSearch {
+    /// Whether an index narrows the search down. Status and the created
+    /// range alone match too large a share of the table.
+    pub fn is_selective(&self) -> bool {
+        self.account_id.is_some() || self.payload.is_some()
+    }
+}
+
+impl TransactionQueue {
+    pub async fn find(conn: &mut AsyncPgConnection, id: Uuid) -> Result<Option<TransactionQueue>, DbError> {
+        let row = transaction_queue::table
+            .find(id)
+            .select(TransactionQueue::as_select())
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(row)
+    }
+
+    /// Rows matching `search`, newest first
+    pub async fn search(
+        conn: &mut AsyncPgConnection,
+        search: &TransactionSearch,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<TransactionQueue>, DbError> {
+        let mut query = transaction_queue::table
+            .select(TransactionQueue::as_select())
+            .into_boxed();
+        if let Some(account_id) = &search.account_id {
+            query = query.filter(transaction_queue::account_id.eq(account_id));
+        }
+        if let Some(status) = search.status {
+            query = query.filter(transaction_queue::status.eq(status.as_str()));
+        }
+        if let Some(after) = search.created_after {
+            query = query.filter(transaction_queue::created_at.ge(after));
+        }
+        if let Some(before) = search.created_before {
+            query = query.filter(transaction_queue::created_at.lt(before));
+        }
+        if let Some(payload) = &search.payload {
+            query = query.filter(transaction_queue::transaction_data.contains(payload));
+        }
+
+        let rows = query
+            .order((transaction_queue::created_at.desc(), transaction_queue::id.desc()))
+            .limit(limit)
+            .offset(offset)
+            .load(conn)
+            .await?;
+        Ok(rows)
+    }
+
+    /// One page of the account's rows matching `filter`, newest first.
+    ///
+    /// Pages are keyed on `(created_at, id)` rather than an offset, so a
+    /// page deep into a large account costs the same as the first, and rows
+    /// submitted while a client pages through never shift or repeat the rows
+    /// it has yet to see. The account's `(account_id, created_at, id)` index
+    /// serves the order.
+    pub async fn list(
+        conn: &mut AsyncPgConnection,
+        filter: &TransactionListFilter,
+        after: Option<TransactionCursor>,
+        limit: i64,
+    ) -> Result<Vec<TransactionQueue>, DbError> {
+        let mut query = transaction_queue::table
+            .select(TransactionQueue::as_select())
+            .filter(transaction_queue::account_id.eq(&filter.account_id))
+            .into_boxed();
+        if let Some(status) = filter.status {
+            query = query.filter(transaction_queue::status.eq(status.as_str()));
+        }
+        if let Some(min_priority) = filter.min_priority {
+            query = query.filter(transaction_queue::priority.ge(min_priority));
+        }
+        if let Some(after) = filter.created_after {
+            query = query.filter(transaction_queue::created_at.ge(after));
+        }
+        if let Some(after) = after {
+            // A row comparison, which Postgres answers with one index range scan
+            query = query.filter(
+                sql::<Bool>("(transaction_queue.created_at, transaction_queue.id) < (")
+                    .bind::<Timestamptz, _>(after.created_at)
+                    .sql(", ")
+                    .bind::<SqlUuid, _>(after.id)
+                    .sql(")"),
+            );
+        }
+
+        let rows = query
+            .order((transaction_queue::created_at.desc(), transaction_queue::id.desc()))
+            .limit(limit)
+            .load(conn)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Rows that have sat in "processing" without an update for longer than
+    /// `older_than`, oldest first
+    pub async fn find_stale_processing(
+        conn: &mut AsyncPgConnection,
+        older_than: std::time::Duration,
+    ) -> Result<Vec<TransactionQueue>, DbError> {
+        let cutoff = Utc::now() - TimeDelta::from_std(older_than).unwrap_or(TimeDelta::MAX);
+
+        let rows = transaction_queue::table
+            .filter(transaction_queue::status.eq(TransactionStatus::Processing.as_str()))
+            .filter(transaction_queue::updated_at.lt(cutoff))
+            .order(transaction_queue::updated_at.asc())
+            .select(TransactionQueue::as_select())
+            .load(conn)
+            .await?;
+        Ok(rows)
+    }
+
+    /// Rows not yet in a terminal status, per account. Accounts without any
+    /// are absent from the map.
+    pub async fn count_pending_by_account(
+        conn: &mut AsyncPgConnection,
+    ) -> Result<HashMap<String, i64>, DbError> {
+        let terminal = [
+            TransactionStatus::Completed.as_str(),
+            TransactionStatus::Failed.as_str(),
+            TransactionStatus::Cancelled.as_str(),
+        ];
+        let counts = transaction_queue::table
+            .filter(transaction_queue::status.ne_all(terminal))
+            .group_by(transaction_queue::account_id)
+            .select((transaction_queue::account_id, diesel::dsl::count_star()))
+            .load::<(String, i64)>(conn)
+            .await?;
+        Ok(counts.into_iter().collect())
+    }
+
+    /// A page of the account's rows in `statuses`, highest priority first
+    /// and oldest first within a priority
+    pub async fn list_for_account(
+        conn: &mut AsyncPgConnection,
+        account_id: &str,
+        statuses: &[TransactionStatus],
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<TransactionQueue>, DbError> {
+        let statuses: Vec<&str> = statuses.iter().map(TransactionStatus::as_str).collect();
+        let rows = transaction_queue::table
+            .filter(transaction_queue::account_id.eq(account_id))
+            .filter(transaction_queue::status.eq_any(statuses))
+            .order((
+                transaction_queue::priority.desc(),
+                transaction_queue::created_at.asc(),
+                transaction_queue::id.asc(),
+            ))
+            .limit(limit)
+            .offset(offset)
+            .select(TransactionQueue::as_select())
+            .load(conn)
+            .await?;
+        Ok(rows)
+    }
+
+    /// The account's rows per status, for the statuses in `statuses` it has
+    /// any rows in
+    pub async fn count_for_account_by_status(
+        conn: &mut AsyncPgConnection,
+        account_id: &str,
+        statuses: &[TransactionStatus],
+    ) -> Result<HashMap<String, i64>, DbError> {
+        let statuses: Vec<&str> = statuses.iter().map(TransactionStatus::as_str).collect();
+        let counts = transaction_queue::table
+            .filter(transaction_queue::account_id.eq(account_id))
+            .filter(transaction_queue::status.eq_any(statuses))
+            .group_by(transaction_queue::status)
+            .select((transaction_queue::status, diesel::dsl::count_star()))
+            .load::<(String, i64)>(conn)
+            .await?;
+        Ok(counts.into_iter().collect())
+    }
+
+    /// The account's transaction submitted with `idempotency_key`, if any
+    pub async fn find_by_idempotency_key(
+        conn: &mut AsyncPgConnection,
+        account_id: &str,
+        idempotency_key: &str,
+    ) -> Result<Option<TransactionQueue>, DbError> {
+        let transaction = transaction_queue::table
+            .filter(transaction_queue::account_id.eq(account_id))
+            .filter(transaction_queue::idempotency_key.eq(idempotency_key))
+            .select(TransactionQueue::as_select())
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(transaction)
+    }
+
+    /// Rows of the account still waiting to be picked up, counted on the
+    /// (account_id, status) index
+    pub async fn count_queued_for_account(conn: &mut AsyncPgConnection, account_id: &str) -> Result<i64, DbError> {
+        let count = transaction_queue::table
+            .filter(transaction_queue::account_id.eq(account_id))
+            .filter(transaction_queue::status.eq(TransactionStatus::Pending.as_str()))
+            .count()
+            .get_result(conn)
+            .await?;
+        Ok(count)
+    }
+
+    /// Move a row from `from` to `to`, only if it is still in `from`.
+    ///
+    /// Returns false when the row does not exist, another writer already
+    /// moved it on or, moving to `Retry`, it has no retries left. Moving to
+    /// `Retry` counts as a retry.
+    pub async fn transition_status(
+        conn: &mut AsyncPgConnection,
+        id: Uuid,
+        from: TransactionStatus,
+        to: TransactionStatus,
+    ) -> Result<bool, DbError> {
+        Ok(Self::transition(conn, id, from, to, None).await?.is_some())
+    }
+
+    /// Move a row from `from` to `to` in one UPDATE guarded by the row still
+    /// being in `from`, storing `error_message` when given, and return it as
+    /// updated.
+    ///
+    /// Returns `None` when the row does not exist, another writer already
+    /// moved it on or, moving to retry, it has no retries left.
+    /// Moving to `Retry` counts as a retry; finishing stamps `processed_at`
+    /// and retrying clears it.
+    pub async fn transition(
+        conn: &mut AsyncPgConnection,
+        id: Uuid,
+        from: TransactionStatus,
+        to: TransactionStatus,
+        error_message: Option<&str>,
+    ) -> Result<Option<TransactionQueue>, DbError> {
+        if !from.can_transition_to(to) {
+            return Err(DbError::InvalidTransition {
+                from: from.as_str(),
+                to: to.as_str(),
+            });
+        }
+
+        let stored = error_message.map(|message| stored_error_message(id, message));
+        let processed_at = match to {
+            TransactionStatus::Completed | TransactionStatus::Failed => Some(Some(Utc::now())),
+            TransactionStatus::Retry => Some(None),
+            _ => None,
+        };
+        let retry_increment = if to == TransactionStatus::Retry { 1 } else { 0 };
+        // A retry spends one of the row's retries, so it needs one left
+        let retries_unbounded = to != TransactionStatus::Retry;
+        let row = diesel::update(
+            transaction_queue::table
+                .filter(transaction_queue::id.eq(id))
+                .filter(transaction_queue::status.eq(from.as_str()))
+                .filter(
+                    transaction_queue::retry_count
+                        .lt(transaction_queue::max_retries)
+                        .or(retries_unbounded.into_sql::<diesel::sql_types::Bool>()),
+                ),
+        )
+        .set((
+            StatusChange {
+                status: to.as_str(),
+                processed_at,
+                error_message: stored.as_deref(),
+                scheduled_at: None,
+            },
+            transaction_queue::retry_count.eq(transaction_queue::retry_count + retry_increment),
+        ))
+        .returning(TransactionQueue::as_returning())
+        .get_result(conn)
+        .await
+        .optional()?;
+        Ok(row)
+    }
+
+    /// Claim a row for processing: move it to "processing" if it is pending,
+    /// or in retry and due. Returns it as updated, or `None` when it does not
+    /// exist, is in any other status or is a retry scheduled for later, so
+    /// of two workers claiming the same row only one succeeds.
+    pub async fn claim(conn: &mut AsyncPgConnection, id: Uuid) -> Result<Option<TransactionQueue>, DbError> {
+        let due = transaction_queue::scheduled_at
+            .is_null()
+            .or(transaction_queue::scheduled_at.le(diesel::dsl::now));
+        let row = diesel::update(
+            transaction_queue::table.filter(transaction_queue::id.eq(id)).filter(
+                transaction_queue::status
+                    .eq(TransactionStatus::Pending.as_str())
+                    .or(transaction_queue::status.eq(TransactionStatus::Retry.as_str()).and(due)),
+            ),
+        )
+        .set(transaction_queue::status.eq(TransactionStatus::Processing.as_str()))
+        .returning(TransactionQueue::as_returning())
+        .get_result(conn)
+        .await
+        .optional()?;
+        Ok(row)
+    }
+
+    /// Settle a processing row whose attempt failed with `error_message`:
+    /// back to "retry", due at `retry_at`, while it has retries left, and
+    /// "failed" once it has none. Returns the row as updated, or `None`
+    /// when it was no longer processing.
+    pub async fn fail_attempt(
+        conn: &mut AsyncPgConnection,
+        id: Uuid,
+        error_message: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<Option<TransactionQueue>, DbError> {
+        let stored = stored_error_message(id, error_message);
+        let retried = diesel::update(
+            transaction_queue::table
+                .filter(transaction_queue::id.eq(id))
+                .filter(transaction_queue::status.eq(TransactionStatus::Processing.as_str()))
+                .filter(transaction_queue::retry_count.lt(transaction_queue::max_retries)),
+        )
+        .set((
+            StatusChange {
+                status: TransactionStatus::Retry.as_str(),
+                processed_at: Some(None),
+                error_message: Some(&stored),
+                scheduled_at: Some(retry_at),
+            },
+            transaction_queue::retry_count.eq(transaction_queue::retry_count + 1),
+        ))
+        .returning(TransactionQueue::as_returning())
+        .get_result(conn)
+        .await
+        .optional()?;
+        if retried.is_some() {
+            return Ok(retried);
+        }
+        Self::transition(conn, id, TransactionStatus::Processing, TransactionStatus::Failed, Some(&stored)).await
+    }
+
+    /// Move the row to "cancelled" if it is still pending, returning it as
+    /// updated. Returns `None` when it does not exist or a worker claimed
+    /// it first, so of a cancel and a claim racing only one succeeds.
+    pub async fn cancel(conn: &mut AsyncPgConnection, id: Uuid) -> Result<Option<TransactionQueue>, DbError> {
+        let row = diesel::update(
+            transaction_queue::table
+                .filter(transaction_queue::id.eq(id))
+                .filter(transaction_queue::status.eq(TransactionStatus::Pending.as_str())),
+        )
+        .set((
+            transaction_queue::status.eq(TransactionStatus::Cancelled.as_str()),
+            transaction_queue::updated_at.eq(diesel::dsl::now),
+        ))
+        .returning(TransactionQueue::as_returning())
+        .get_result(conn)
+        .await
+        .optional()?;
+        Ok(row)
+    }
+
+    /// Move up to `limit` of the account's pending rows matching `filter` to
+    /// "cancelled" and return them.
+    ///
+    /// Rows a worker claims between the select and the update are no longer
+    /// pending and are left alone, so calling this until it returns nothing
+    /// cancels whatever was still waiting.
+    pub async fn cancel_pending(
+        conn: &mut AsyncPgConnection,
+        account_id: &str,
+        filter: &CancelFilter,
+        limit: i64,
+    ) -> Result<Vec<CancelledTransaction>, DbError> {
+        let mut query = transaction_queue::table
+            .filter(transaction_queue::account_id.eq(account_id))
+            .filter(transaction_queue::status.eq(TransactionStatus::Pending.as_str()))
+            .select(transaction_queue::id)
+            .into_boxed();
+        if let Some(min) = filter.min_priority {
+            query = query.filter(transaction_queue::priority.ge(min));
+        }
+        if let Some(max) = filter.max_priority {
+            query = query.filter(transaction_queue::priority.le(max));
+        }
+        if let Some(before) = filter.created_before {
+            query = query.filter(transaction_queue::created_at.lt(before));
+        }
+        let candidates: Vec<Uuid> = query.order(transaction_queue::created_at.asc()).limit(limit).load(conn).await?;
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let cancelled = diesel::update(
+            transaction_queue::table
+                .filter(transaction_queue::id.eq_any(&candidates))
+                .filter(transaction_queue::status.eq(TransactionStatus::Pending.as_str())),
+        )
+        .set(transaction_queue::status.eq(TransactionStatus::Cancelled.as_str()))
+        .returning(CancelledTransaction::as_returning())
+        .get_results(conn)
+        .await?;
+        Ok(cancelled)
+    }
+
+    /// Move a row from "processing" to "completed", storing its result
+    /// capped by `truncate::result`.
+    ///
+    /// Returns false when the row is not processing, so of two workers
+    /// finishing the same row only the first records its result.
+    pub async fn complete(
+        conn: &mut AsyncPgConnection,
+        id: Uuid,
+        result: &serde_json::Value,
+    ) -> Result<bool, DbError> {
+        let result = truncate::result(result);
+        if let Cow::Owned(summary) = &result {
+            tracing::warn!(
+                transaction_id = %id,
+                original_bytes = %summary["original_bytes"],
+                sha256 = %summary["sha256"],
+                "Storing a summary of an oversized result"
+            );
+        }
+        let updated = diesel::update(
+            transaction_queue::table
+                .filter(transaction_queue::id.eq(id))
+                .filter(transaction_queue::status.eq(TransactionStatus::Processing.as_str())),
+        )
+        .set((
+            transaction_queue::status.eq(TransactionStatus::Completed.as_str()),
+            transaction_queue::result.eq(result.as_ref()),
+            transaction_queue::processed_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)
+        .await?;
+        Ok(updated == 1)
+    }
+
+    /// Store why the latest processing attempt failed, capped by
+    /// `truncate::error_message`. The full message is logged here, since
+    /// the row may only keep part of it.
+    pub async fn record_error(conn: &mut AsyncPgConnection, id: Uuid, message: &str) -> Result<(), DbError> {
+        let stored = stored_error_message(id, message);
+        diesel::update(transaction_queue::table.filter(transaction_queue::id.eq(id)))
+            .set(transaction_queue::error_message.eq(stored.as_ref()))
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// `message` capped by `truncate::error_message`. The full message is
+/// logged when it is cut, since the row only keeps part of it.
+fn stored_error_message(id: Uuid, message: &str) -> Cow<'_, str> {
+    let stored = truncate::error_message(message);
+    if let Cow::Owned(_) = stored {
+        tracing::warn!(transaction_id = %id, error = message, "Truncating stored error message");
+    }
+    stored
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
@@ -32,22 +597,124 @@ pub struct NewTransactionQueue {
     pub priority: i32,
     pub retry_count: i32,
     pub max_retries: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
     pub scheduled_at: Option<DateTime<Utc>>,
 }
 
 impl NewTransactionQueue {
-    pub fn new(account_id: String, transaction_data: serde_json::Value) -> Self {
+    /// A pending row with its id and timestamps taken from `ids` and `clock`
+    pub fn new(
+        account_id: String,
+        transaction_data: serde_json::Value,
+        ids: &dyn IdGenerator,
+        clock: &dyn Clock,
+    ) -> Self {
+        let now = clock.now();
         Self {
-            id: Uuid::new_v4(),
+            id: ids.new_id(),
             account_id,
             transaction_data,
             status: "pending".to_string(),
             priority: 0,
             retry_count: 0,
             max_retries: 3,
+            created_at: now,
+            updated_at: now,
             scheduled_at: None,
         }
     }
+
+    /// Number of bound columns per row, kept in sync with the struct fields
+    const COLUMNS: usize = 10;
+
+    /// Largest number of rows that fit in a single INSERT statement
+    pub const MAX_ROWS_PER_CHUNK: usize = POSTGRES_MAX_BIND_PARAMS / Self::COLUMNS;
+
+    /// Insert any number of rows, chunked to stay under the bind parameter limit.
+    ///
+    /// All chunks run inside one transaction, so a failure in any chunk rolls
+    /// back the whole batch and is reported with the failing chunk index.
+    /// Inserted rows are returned in the same order as `rows`.
+    pub async fn insert_batch(
+        conn: &mut AsyncPgConnection,
+        rows: &[NewTransactionQueue],
+    ) -> Result<Vec<TransactionQueue>, DbError> {
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut inserted = conn
+            .transaction::<_, DbError, _>(|conn| {
+                async move {
+                    let mut inserted = HashMap::with_capacity(rows.len());
+                    for (chunk_index, chunk) in rows.chunks(Self::MAX_ROWS_PER_CHUNK).enumerate() {
+                        let chunk_rows = diesel::insert_into(transaction_queue::table)
+                            .values(chunk)
+                            .returning(TransactionQueue::as_returning())
+                            .get_results(conn)
+                            .await
+                            .map_err(|source| DbError::BatchChunk { chunk_index, source })?;
+
+                        inserted.extend(chunk_rows.into_iter().map(|row| (row.id, row)));
+                    }
+                    Ok(inserted)
+                }
+                .scope_boxed()
+            })
+            .await?;
+
+        // RETURNING order is not guaranteed, so restore the caller's order by id
+        Ok(rows
+            .iter()
+            .filter_map(|row| inserted.remove(&row.id))
+            .collect())
+    }
+}
+
+/// Borrowed form of `NewTransactionQueue` for the submit hot path. The
+/// payload is bound straight from the request's JSON text, so a large
+/// transaction is never parsed, cloned or re-serialized on insert.
+#[derive(Debug, Insertable)]
+#[diesel(table_name = transaction_queue)]
+pub struct NewTransactionQueueRef<'a> {
+    pub id: Uuid,
+    pub account_id: &'a str,
+    pub transaction_data: RawJsonb<'a>,
+    pub status: &'a str,
+    pub priority: i32,
+    pub retry_count: i32,
+    pub max_retries: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    /// Idempotency-Key of the submit, unique per account
+    pub idempotency_key: Option<&'a str>,
+}
+
+impl<'a> NewTransactionQueueRef<'a> {
+    /// A pending row with its id and timestamps taken from `ids` and `clock`
+    pub fn new(
+        account_id: &'a str,
+        transaction_data: &'a serde_json::value::RawValue,
+        ids: &dyn IdGenerator,
+        clock: &dyn Clock,
+    ) -> Self {
+        let now = clock.now();
+        Self {
+            id: ids.new_id(),
+            account_id,
+            transaction_data: RawJsonb(transaction_data),
+            status: TransactionStatus::Pending.as_str(),
+            priority: 0,
+            retry_count: 0,
+            max_retries: 3,
+            created_at: now,
+            updated_at: now,
+            scheduled_at: None,
+            idempotency_key: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -57,9 +724,25 @@ pub enum TransactionStatus {
     Completed,
     Failed,
     Retry,
+    /// Withdrawn by the account before a worker claimed it
+    Cancelled,
 }
 
 impl TransactionStatus {
+    pub const ALL: [Self; 6] = [
+        Self::Pending,
+        Self::Processing,
+        Self::Completed,
+        Self::Failed,
+        Self::Retry,
+        Self::Cancelled,
+    ];
+
+    /// The status stored as `value`, if any
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.as_str() == value)
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Pending => "pending",
@@ -67,6 +750,33 @@ impl TransactionStatus {
             Self::Completed => "completed",
             Self::Failed => "failed",
             Self::Retry => "retry",
+            Self::Cancelled => "cancelled",
         }
     }
+
+    /// Completed, failed and cancelled rows are finished and no longer hold
+    /// a pending slot. Only a failed row may move again, back to retry.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+
+    /// Whether a row may move from this status to `next`. A move to retry
+    /// also needs a retry left, which only the row knows.
+    pub fn can_transition_to(&self, next: TransactionStatus) -> bool {
+        matches!(
+            (self, next),
+            (Self::Pending | Self::Retry, Self::Processing)
+                | (Self::Pending, Self::Cancelled)
+                | (Self::Processing, Self::Completed | Self::Failed | Self::Retry)
+                | (Self::Failed, Self::Retry)
+        )
+    }
+}
+
+impl FromStr for TransactionStatus {
+    type Err = DbError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value).ok_or_else(|| DbError::UnknownStatus(value.to_string()))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,42 @@
+use crate::schema::webhooks;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = webhooks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Webhook {
+    pub id: Uuid,
+    pub account_id: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+    /// Signing secret, only ever returned to the caller when the webhook is created
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable)]
+#[diesel(table_name = webhooks)]
+pub struct NewWebhook {
+    pub id: Uuid,
+    pub account_id: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub secret: String,
+}
+
+impl NewWebhook {
+    pub fn new(account_id: String, url: String, event_types: Vec<String>, secret: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            account_id,
+            url,
+            event_types,
+            secret,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Executing queued transactions exactly once.
+//!
+//! A worker can crash after a transaction has been executed but before its
+//! result is stored, leaving the row in "processing" for another worker to
+//! pick up. Every execution therefore carries an idempotency key derived
+//! from the transaction id, and a processor that sees a key again hands back
+//! the result of the earlier execution instead of executing twice. Storing
+//! the result is conditional on the row still being in "processing", so it
+//! is recorded once however many workers finish the same row.
+
+use crate::models::{TransactionQueue, TransactionStatus};
+use crate::DbError;
+use diesel_async::AsyncPgConnection;
+use serde_json::{json, Value};
+use std::future::Future;
+use uuid::Uuid;
+
+/// Key a transaction is executed under; the same for every attempt
+pub fn idempotency_key(id: Uuid) -> String {
+    format!("tx:{}", id.simple())
+}
+
+pub trait TransactionProcessor: Send + Sync {
+    /// Execute `transaction` and return what it produced.
+    ///
+    /// Called again with the same `idempotency_key` after a crash, this must
+    /// return the earlier result rather than repeat the work.
+    fn execute(
+        &self,
+        idempotency_key: &str,
+        transaction: &TransactionQueue,
+    ) -> impl Future<Output = Result<Value, String>> + Send;
+}
+
+/// Pretends to execute on chain. The signature is derived from the
+/// idempotency key, so a repeated key yields the same result.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimulatedProcessor;
+
+impl TransactionProcessor for SimulatedProcessor {
+    async fn execute(&self, idempotency_key: &str, transaction: &TransactionQueue) -> Result<Value, String> {
+        Ok(json!({
+            "simulated": true,
+            "signature": format!("sim_{}", idempotency_key.trim_start_matches("tx:")),
+            "account_id": transaction.account_id,
+        }))
+    }
+}
+
+/// Completes items without doing any work
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopProcessor;
+
+impl TransactionProcessor for NoopProcessor {
+    async fn execute(&self, _idempotency_key: &str, _transaction: &TransactionQueue) -> Result<Value, String> {
+        Ok(json!({}))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessError {
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error("Transaction {0} not found")]
+    NotFound(Uuid),
+
+    #[error("Processor failed: {0}")]
+    Processor(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessOutcome {
+    /// Executed, or recovered from the processor, and stored by this call
+    Completed(Value),
+    /// Already completed before this call; the processor was not invoked
+    AlreadyCompleted(Option<Value>),
+    /// Cancelled before a worker claimed it; the processor was not invoked
+    Cancelled,
+}
+
+impl ProcessOutcome {
+    pub fn result(&self) -> Option<&Value> {
+        match self {
+            Self::Completed(result) => Some(result),
+            Self::AlreadyCompleted(result) => result.as_ref(),
+            Self::Cancelled => None,
+        }
+    }
+}
+
+/// Execute a transaction the caller has moved to "processing" and store
+/// its result.
+///
+/// The row is read first, so one that has already completed or was
+/// cancelled is reported without invoking the processor. Failures record
+/// their message on the row and leave it in "processing" for the caller to
+/// retry or fail.
+pub async fn process<P: TransactionProcessor>(
+    conn: &mut AsyncPgConnection,
+    processor: &P,
+    id: Uuid,
+) -> Result<ProcessOutcome, ProcessError> {
+    let transaction = load(conn, id).await?;
+    match TransactionStatus::parse(&transaction.status) {
+        Some(TransactionStatus::Completed) => return Ok(ProcessOutcome::AlreadyCompleted(transaction.result)),
+        Some(TransactionStatus::Cancelled) => return Ok(ProcessOutcome::Cancelled),
+        Some(TransactionStatus::Processing) => {}
+        other => {
+            return Err(DbError::InvalidTransition {
+                from: other.map_or("unknown", |status| status.as_str()),
+                to: TransactionStatus::Completed.as_str(),
+            }
+            .into())
+        }
+    }
+
+    let result = match processor.execute(&idempotency_key(id), &transaction).await {
+        Ok(result) => result,
+        Err(message) => {
+            TransactionQueue::record_error(conn, id, &message).await?;
+            return Err(ProcessError::Processor(message));
+        }
+    };
+
+    if TransactionQueue::complete(conn, id, &result).await? {
+        return Ok(ProcessOutcome::Completed(result));
+    }
+    // Another worker finished it while this one was executing
+    Ok(ProcessOutcome::AlreadyCompleted(load(conn, id).await?.result))
+}
+
+async fn load(conn: &mut AsyncPgConnection, id: Uuid) -> Result<TransactionQueue, ProcessError> {
+    TransactionQueue::find(conn, id).await?.ok_or(ProcessError::NotFound(id))
+}
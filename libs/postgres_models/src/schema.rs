@@ -14,6 +14,8 @@ diesel::table! {
         scheduled_at -> Nullable<Timestamptz>,
         processed_at -> Nullable<Timestamptz>,
         error_message -> Nullable<Text>,
+        result -> Nullable<Jsonb>,
+        idempotency_key -> Nullable<Text>,
     }
 }
 
@@ -26,5 +28,65 @@ diesel::table! {
         window_seconds -> Int4,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        strategy -> Text,
     }
-}
\ No newline at end of file
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Uuid,
+        actor -> Text,
+        method -> Text,
+        path -> Text,
+        status_code -> Int4,
+        created_at -> Timestamptz,
+        event_type -> Nullable<Text>,
+        account_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Uuid,
+        account_id -> Text,
+        url -> Text,
+        event_types -> Array<Text>,
+        secret -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    limit_change_requests (id) {
+        id -> Uuid,
+        account_id -> Text,
+        requested_max_requests -> Int4,
+        justification -> Text,
+        status -> Text,
+        decided_by -> Nullable<Text>,
+        decided_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    accounts (account_id) {
+        account_id -> Text,
+        tier -> Text,
+        default_priority -> Int4,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    payload_schemas (account_id) {
+        account_id -> Text,
+        schema -> Jsonb,
+        enabled -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
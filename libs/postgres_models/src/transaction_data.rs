@@ -0,0 +1,220 @@
+//! Typed `transaction_data` for the Solana account creations the queue
+//! carries, told apart by their `account_type`.
+//!
+//! Payloads are still stored as the JSON they arrived as; these types only
+//! decide whether a payload naming a known account type is well formed.
+//! Fields the types do not list, such as `metadata`, are allowed and kept.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Number, Value};
+
+/// Longest seed the runtime accepts when deriving a program address
+pub const MAX_SEED_BYTES: usize = 32;
+
+/// Largest account the runtime lets a program allocate
+pub const MAX_SPACE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// The `account_type` values with a typed payload
+pub const ACCOUNT_TYPES: [&str; 4] = ["user_pda", "token_account", "multisig_pda", "vault_account"];
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "account_type", rename_all = "snake_case")]
+pub enum TransactionData {
+    UserPda(AccountCreation),
+    TokenAccount(TokenAccountCreation),
+    MultisigPda(MultisigCreation),
+    VaultAccount(AccountCreation),
+}
+
+/// The fields every single-owner account creation carries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountCreation {
+    pub owner_pubkey: String,
+    pub seed: String,
+    pub program_id: String,
+    #[serde(deserialize_with = "non_negative")]
+    pub space_bytes: u64,
+    #[serde(deserialize_with = "non_negative")]
+    pub lamports: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenAccountCreation {
+    #[serde(flatten)]
+    pub account: AccountCreation,
+    /// Mint the token account holds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mint_pubkey: Option<String>,
+}
+
+/// A program address owned jointly, needing `threshold` of `owners` to sign
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigCreation {
+    pub owners: Vec<String>,
+    pub threshold: u32,
+    pub seed: String,
+    pub program_id: String,
+    #[serde(deserialize_with = "non_negative")]
+    pub space_bytes: u64,
+    #[serde(deserialize_with = "non_negative")]
+    pub lamports: u64,
+}
+
+/// Why a payload was refused, naming the field at fault, e.g. `lamports`
+/// or `owners[1]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[error("{field}: {message}")]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl TransactionData {
+    /// Type a payload by its `account_type` and check its fields.
+    ///
+    /// `Ok(None)` for a payload without an `account_type`, and for one this
+    /// crate does not know when `allow_unknown` is set; without it an
+    /// unknown type is an error.
+    pub fn parse(payload: &Value, allow_unknown: bool) -> Result<Option<Self>, FieldError> {
+        let Some(account_type) = payload.get("account_type") else {
+            return Ok(None);
+        };
+        let data = match account_type.as_str() {
+            Some("user_pda") => Self::UserPda(fields(payload)?),
+            Some("token_account") => Self::TokenAccount(fields(payload)?),
+            Some("multisig_pda") => Self::MultisigPda(fields(payload)?),
+            Some("vault_account") => Self::VaultAccount(fields(payload)?),
+            Some(_) if allow_unknown => return Ok(None),
+            _ => {
+                return Err(FieldError::new(
+                    "account_type",
+                    format!("must be one of: {}", ACCOUNT_TYPES.join(", ")),
+                ))
+            }
+        };
+        data.validate()?;
+        Ok(Some(data))
+    }
+
+    pub fn account_type(&self) -> &'static str {
+        match self {
+            Self::UserPda(_) => "user_pda",
+            Self::TokenAccount(_) => "token_account",
+            Self::MultisigPda(_) => "multisig_pda",
+            Self::VaultAccount(_) => "vault_account",
+        }
+    }
+
+    /// Bytes of account space the creation allocates
+    pub fn space_bytes(&self) -> u64 {
+        match self {
+            Self::UserPda(account) | Self::VaultAccount(account) => account.space_bytes,
+            Self::TokenAccount(token) => token.account.space_bytes,
+            Self::MultisigPda(multisig) => multisig.space_bytes,
+        }
+    }
+
+    /// Check what the types alone do not: key formats, seed and account
+    /// sizes, and the multisig threshold
+    pub fn validate(&self) -> Result<(), FieldError> {
+        match self {
+            Self::UserPda(account) | Self::VaultAccount(account) => account.validate(),
+            Self::TokenAccount(token) => {
+                token.account.validate()?;
+                match &token.mint_pubkey {
+                    Some(mint) => check_pubkey("mint_pubkey", mint),
+                    None => Ok(()),
+                }
+            }
+            Self::MultisigPda(multisig) => multisig.validate(),
+        }
+    }
+}
+
+impl AccountCreation {
+    fn validate(&self) -> Result<(), FieldError> {
+        check_pubkey("owner_pubkey", &self.owner_pubkey)?;
+        check_program_account(&self.seed, &self.program_id, self.space_bytes)
+    }
+}
+
+impl MultisigCreation {
+    fn validate(&self) -> Result<(), FieldError> {
+        if self.owners.is_empty() {
+            return Err(FieldError::new("owners", "must name at least one owner"));
+        }
+        for (i, owner) in self.owners.iter().enumerate() {
+            check_pubkey(&format!("owners[{}]", i), owner)?;
+        }
+        if self.threshold == 0 || self.threshold as usize > self.owners.len() {
+            return Err(FieldError::new(
+                "threshold",
+                format!("must be between 1 and the {} owners", self.owners.len()),
+            ));
+        }
+        check_program_account(&self.seed, &self.program_id, self.space_bytes)
+    }
+}
+
+/// Deserialize the variant's own fields, so an error can name its field;
+/// serde loses the path inside an internally tagged enum
+fn fields<T: for<'de> Deserialize<'de>>(payload: &Value) -> Result<T, FieldError> {
+    serde_path_to_error::deserialize(payload).map_err(|e| {
+        let field = e.path().to_string();
+        let message = e.into_inner().to_string();
+        // A missing field is reported against the object holding it
+        match message.strip_prefix("missing field `").and_then(|rest| rest.strip_suffix('`')) {
+            Some(missing) => FieldError::new(missing, "is required"),
+            None => FieldError::new(field, message),
+        }
+    })
+}
+
+fn check_program_account(seed: &str, program_id: &str, space_bytes: u64) -> Result<(), FieldError> {
+    if seed.is_empty() || seed.len() > MAX_SEED_BYTES {
+        return Err(FieldError::new(
+            "seed",
+            format!("must be between 1 and {} bytes", MAX_SEED_BYTES),
+        ));
+    }
+    check_pubkey("program_id", program_id)?;
+    if space_bytes > MAX_SPACE_BYTES {
+        return Err(FieldError::new(
+            "space_bytes",
+            format!("must be at most {}", MAX_SPACE_BYTES),
+        ));
+    }
+    Ok(())
+}
+
+/// A public key is 32 bytes written in base58, which takes 32 to 44
+/// characters
+fn check_pubkey(field: &str, value: &str) -> Result<(), FieldError> {
+    if !(32..=44).contains(&value.len()) || !value.chars().all(|c| BASE58_ALPHABET.contains(c)) {
+        return Err(FieldError::new(field, "must be a base58 public key of 32 to 44 characters"));
+    }
+    Ok(())
+}
+
+fn non_negative<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let number = Number::deserialize(deserializer)?;
+    number.as_u64().ok_or_else(|| {
+        let message = if number.as_f64().is_some_and(|n| n < 0.0) {
+            "must not be negative"
+        } else {
+            "must be a whole number"
+        };
+        serde::de::Error::custom(message)
+    })
+}
@@ -0,0 +1,65 @@
+//! Size caps on what processing writes back to a transaction row.
+//!
+//! Processors fail with whatever their dependencies said, and a chatty RPC
+//! error repeated on every retry would otherwise bloat the row without
+//! bound. Oversized text keeps a prefix cut on a character boundary and ends
+//! with a marker holding the full size and a SHA-256 prefix of the original,
+//! so the row can be matched with the full text in the logs. Capped values
+//! fit their limit, so capping them again leaves them unchanged.
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+
+/// Largest `error_message` stored, in bytes
+pub const MAX_ERROR_MESSAGE_BYTES: usize = 4096;
+
+/// Largest serialized `result` stored, in bytes
+pub const MAX_RESULT_BYTES: usize = 64 * 1024;
+
+/// Bytes of an oversized result kept as its preview
+pub const RESULT_PREVIEW_BYTES: usize = 1024;
+
+/// Hex digits of the SHA-256 kept in markers
+const HASH_PREFIX_LEN: usize = 16;
+
+/// `text` if it fits in `max_bytes`, otherwise its longest prefix that fits
+/// together with a `…[truncated N bytes, sha256:…]` marker. Limits shorter
+/// than the marker yield the marker alone.
+pub fn truncate_text(text: &str, max_bytes: usize) -> Cow<'_, str> {
+    if text.len() <= max_bytes {
+        return Cow::Borrowed(text);
+    }
+    let marker = format!("…[truncated {} bytes, sha256:{}]", text.len(), hash_prefix(text.as_bytes()));
+    let mut end = max_bytes.saturating_sub(marker.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    Cow::Owned(format!("{}{}", &text[..end], marker))
+}
+
+/// An error message as stored on the row
+pub fn error_message(message: &str) -> Cow<'_, str> {
+    truncate_text(message, MAX_ERROR_MESSAGE_BYTES)
+}
+
+/// A result as stored on the row. One serializing past `MAX_RESULT_BYTES`
+/// is replaced by a summary with its size, hash and a preview of its text.
+pub fn result(result: &Value) -> Cow<'_, Value> {
+    let text = result.to_string();
+    if text.len() <= MAX_RESULT_BYTES {
+        return Cow::Borrowed(result);
+    }
+    Cow::Owned(json!({
+        "truncated": true,
+        "original_bytes": text.len(),
+        "sha256": hex::encode(Sha256::digest(text.as_bytes())),
+        "preview": truncate_text(&text, RESULT_PREVIEW_BYTES),
+    }))
+}
+
+fn hash_prefix(bytes: &[u8]) -> String {
+    let mut digest = hex::encode(Sha256::digest(bytes));
+    digest.truncate(HASH_PREFIX_LEN);
+    digest
+}
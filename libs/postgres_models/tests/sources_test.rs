@@ -0,0 +1,23 @@
+use chrono::Utc;
+use postgres_models::sources::{IdGenerator, TimeOrderedIds};
+
+/// Test ids made one after another keep increasing, even within a millisecond
+#[test]
+fn test_time_ordered_ids_increase() {
+    let ids: Vec<_> = (0..10_000).map(|_| TimeOrderedIds.new_id()).collect();
+
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(ids.iter().all(|id| id.get_version_num() == 7));
+}
+
+/// Test the id carries the time it was made
+#[test]
+fn test_time_ordered_id_timestamp() {
+    let before = Utc::now().timestamp_millis() as u64;
+    let id = TimeOrderedIds.new_id();
+    let after = Utc::now().timestamp_millis() as u64;
+
+    let (seconds, nanos) = id.get_timestamp().unwrap().to_unix();
+    let millis = seconds * 1000 + nanos as u64 / 1_000_000;
+    assert!((before..=after).contains(&millis), "{} not in {}..={}", millis, before, after);
+}
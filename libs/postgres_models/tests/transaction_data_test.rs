@@ -0,0 +1,106 @@
+use postgres_models::transaction_data::{FieldError, TransactionData};
+use serde_json::{json, Value};
+
+const OWNER: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+const PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+fn user_pda() -> Value {
+    json!({
+        "account_type": "user_pda",
+        "owner_pubkey": OWNER,
+        "seed": "user_vault_test",
+        "program_id": PROGRAM,
+        "space_bytes": 165,
+        "lamports": 2039280
+    })
+}
+
+fn multisig_pda() -> Value {
+    json!({
+        "account_type": "multisig_pda",
+        "owners": [
+            OWNER,
+            "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+            "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1"
+        ],
+        "threshold": 2,
+        "seed": "enterprise_multisig_vault",
+        "program_id": "11111111111111111111111111111112",
+        "space_bytes": 355,
+        "lamports": 5000000,
+        "metadata": { "vault_type": "treasury" }
+    })
+}
+
+fn with(mut payload: Value, field: &str, value: Value) -> Value {
+    payload[field] = value;
+    payload
+}
+
+fn field_error(payload: &Value) -> FieldError {
+    TransactionData::parse(payload, true).expect_err("payload was accepted")
+}
+
+/// Test each account type parses into its variant, extra fields allowed, and serializes back under its tag
+#[test]
+fn test_known_account_types() {
+    let vault = with(user_pda(), "account_type", json!("vault_account"));
+    let token = with(user_pda(), "account_type", json!("token_account"));
+    let token = with(token, "mint_pubkey", json!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"));
+    for payload in [user_pda(), vault, token, multisig_pda()] {
+        let data = TransactionData::parse(&payload, false).unwrap().unwrap();
+        assert_eq!(data.account_type(), payload["account_type"]);
+        let round_trip: TransactionData = serde_json::from_value(serde_json::to_value(&data).unwrap()).unwrap();
+        assert_eq!(round_trip, data);
+    }
+    let TransactionData::MultisigPda(multisig) = TransactionData::parse(&multisig_pda(), false).unwrap().unwrap() else {
+        panic!("not a multisig");
+    };
+    assert_eq!((multisig.owners.len(), multisig.threshold), (3, 2));
+}
+
+/// Test malformed fields are reported by name
+#[test]
+fn test_field_errors() {
+    let cases = [
+        (with(user_pda(), "owner_pubkey", json!("not-a-key")), "owner_pubkey"),
+        // 0, O, I and l are not base58
+        (with(user_pda(), "owner_pubkey", json!("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJos0001")), "owner_pubkey"),
+        (with(user_pda(), "program_id", json!("x".repeat(45))), "program_id"),
+        (with(user_pda(), "lamports", json!(-1)), "lamports"),
+        (with(user_pda(), "lamports", json!(1.5)), "lamports"),
+        (with(user_pda(), "space_bytes", json!("165")), "space_bytes"),
+        (with(user_pda(), "space_bytes", json!(10 * 1024 * 1024 + 1)), "space_bytes"),
+        (with(user_pda(), "seed", json!("s".repeat(33))), "seed"),
+        (with(user_pda(), "seed", json!("")), "seed"),
+        (with(multisig_pda(), "threshold", json!(4)), "threshold"),
+        (with(multisig_pda(), "threshold", json!(0)), "threshold"),
+        (with(multisig_pda(), "owners", json!([])), "owners"),
+        (with(multisig_pda(), "owners", json!([OWNER, "short"])), "owners[1]"),
+        (with(with(user_pda(), "account_type", json!("token_account")), "mint_pubkey", json!("bad")), "mint_pubkey"),
+    ];
+    for (payload, field) in cases {
+        assert_eq!(field_error(&payload).field, field, "{}", payload);
+    }
+    assert_eq!(field_error(&with(user_pda(), "lamports", json!(-5))).message, "must not be negative");
+
+    let mut missing = user_pda();
+    missing.as_object_mut().unwrap().remove("seed");
+    assert_eq!(field_error(&missing), FieldError::new("seed", "is required"));
+    // A multisig needs its owners, not an owner_pubkey
+    let owners_only = with(user_pda(), "account_type", json!("multisig_pda"));
+    assert_eq!(field_error(&owners_only).field, "owners");
+}
+
+/// Test payloads without an account type are left alone and unknown types follow `allow_unknown`
+#[test]
+fn test_untyped_and_unknown_payloads() {
+    assert_eq!(TransactionData::parse(&json!({ "amount": 10 }), false), Ok(None));
+    assert_eq!(TransactionData::parse(&json!([1, 2]), false), Ok(None));
+
+    let unknown = with(user_pda(), "account_type", json!("stake_account"));
+    assert_eq!(TransactionData::parse(&unknown, true), Ok(None));
+    assert_eq!(TransactionData::parse(&unknown, false).unwrap_err().field, "account_type");
+    // A type that is not even a string is never accepted
+    assert_eq!(field_error(&with(user_pda(), "account_type", json!(1))).field, "account_type");
+}
@@ -0,0 +1,51 @@
+use postgres_models::models::TransactionStatus::{self, *};
+use postgres_models::DbError;
+
+/// Every move the lifecycle allows; any other pair is illegal
+const EDGES: [(TransactionStatus, TransactionStatus); 7] = [
+    (Pending, Processing),
+    (Pending, Cancelled),
+    (Processing, Completed),
+    (Processing, Failed),
+    (Processing, Retry),
+    (Failed, Retry),
+    (Retry, Processing),
+];
+
+/// Test every ordered pair of statuses, self moves included, against the graph
+#[test]
+fn test_every_edge_of_the_graph() {
+    for from in TransactionStatus::ALL {
+        for to in TransactionStatus::ALL {
+            let legal = EDGES.contains(&(from, to));
+            assert_eq!(from.can_transition_to(to), legal, "{} -> {}", from.as_str(), to.as_str());
+        }
+    }
+}
+
+/// Test only pending rows can be cancelled and nothing leaves completed or cancelled
+#[test]
+fn test_cancel_and_finished_rows() {
+    for from in TransactionStatus::ALL {
+        assert_eq!(from.can_transition_to(Cancelled), from == Pending, "{}", from.as_str());
+    }
+    for to in TransactionStatus::ALL {
+        assert!(!Completed.can_transition_to(to), "completed -> {}", to.as_str());
+        assert!(!Cancelled.can_transition_to(to), "cancelled -> {}", to.as_str());
+    }
+    // A failed row is finished unless it is retried
+    assert!(Failed.is_terminal());
+    assert!(Failed.can_transition_to(Retry));
+}
+
+/// Test statuses parse from their stored names and nothing else
+#[test]
+fn test_from_str() {
+    for status in TransactionStatus::ALL {
+        assert_eq!(status.as_str().parse::<TransactionStatus>().unwrap(), status);
+    }
+    for unknown in ["", "Pending", "done", "pending "] {
+        let err = unknown.parse::<TransactionStatus>().unwrap_err();
+        assert!(matches!(&err, DbError::UnknownStatus(value) if value == unknown), "{}", err);
+    }
+}
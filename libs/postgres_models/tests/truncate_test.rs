@@ -0,0 +1,77 @@
+use postgres_models::truncate::{
+    error_message, result, truncate_text, MAX_ERROR_MESSAGE_BYTES, MAX_RESULT_BYTES, RESULT_PREVIEW_BYTES,
+};
+use serde_json::json;
+use std::borrow::Cow;
+
+/// Test text at exactly the limit is kept whole and borrowed
+#[test]
+fn test_exactly_at_limit_is_unchanged() {
+    let message = "x".repeat(MAX_ERROR_MESSAGE_BYTES);
+    assert!(matches!(error_message(&message), Cow::Borrowed(stored) if stored == message));
+}
+
+/// Test text over the limit is cut to fit, marked with its size and hash
+#[test]
+fn test_over_limit_is_truncated_with_marker() {
+    let message = "x".repeat(MAX_ERROR_MESSAGE_BYTES + 1);
+    let stored = error_message(&message);
+
+    assert!(stored.len() <= MAX_ERROR_MESSAGE_BYTES, "{} bytes", stored.len());
+    assert!(stored.starts_with("xxxx"));
+    assert!(stored.contains(&format!("…[truncated {} bytes, sha256:", message.len())), "{}", stored);
+    assert!(stored.ends_with(']'));
+
+    // Messages differing only past the cut keep different hashes
+    let other = format!("{}y", &message[..MAX_ERROR_MESSAGE_BYTES]);
+    assert_ne!(error_message(&other), stored);
+}
+
+/// Test cuts landing inside multi-byte characters back off to a character boundary
+#[test]
+fn test_multibyte_boundaries() {
+    for filler in ["é", "€", "🦀"] {
+        for extra in 0..8 {
+            let message = format!("{}{}", "a".repeat(extra), filler.repeat(MAX_ERROR_MESSAGE_BYTES));
+            let stored = error_message(&message);
+            assert!(stored.len() <= MAX_ERROR_MESSAGE_BYTES);
+            assert!(stored.len() > MAX_ERROR_MESSAGE_BYTES - filler.len() - 64, "{} bytes", stored.len());
+        }
+    }
+    for max_bytes in 0..64 {
+        truncate_text("ééééééééééééééééééééééééééééééééééééééééééééééé", max_bytes);
+    }
+}
+
+/// Test truncating already truncated text changes nothing
+#[test]
+fn test_retruncation_is_idempotent() {
+    let message = "🦀 failed: ".repeat(2000);
+    let once = error_message(&message).into_owned();
+    assert_eq!(error_message(&once), once);
+
+    let capped = result(&json!({ "log": message })).into_owned();
+    assert_eq!(result(&capped).as_ref(), &capped);
+}
+
+/// Test results within the cap are stored as they are
+#[test]
+fn test_small_result_is_unchanged() {
+    let small = json!({ "status": "ok", "amount": 5 });
+    assert!(matches!(result(&small), Cow::Borrowed(stored) if *stored == small));
+}
+
+/// Test oversized results are replaced by a summary with a bounded preview
+#[test]
+fn test_large_result_is_summarized() {
+    let large = json!({ "log": "z".repeat(MAX_RESULT_BYTES) });
+    let stored = result(&large);
+
+    assert_eq!(stored["truncated"], true);
+    assert_eq!(stored["original_bytes"], large.to_string().len());
+    assert_eq!(stored["sha256"].as_str().unwrap().len(), 64);
+    let preview = stored["preview"].as_str().unwrap();
+    assert!(preview.len() <= RESULT_PREVIEW_BYTES);
+    assert!(preview.starts_with(r#"{"log":"zzz"#));
+    assert!(stored.to_string().len() < MAX_RESULT_BYTES);
+}
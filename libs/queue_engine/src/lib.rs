@@ -0,0 +1,197 @@
+//! The transaction queue without the HTTP API around it.
+//!
+//! `QueueEngine` runs the same steps as the API's submit handler and the
+//! worker against the same Postgres table and Redis keys, so a service can
+//! embed it in-process and share queues with deployed API instances:
+//!
+//! - `submit` checks the account's rate limit and pending cap, stores the
+//!   transaction and adds it to the priority queue
+//! - `status` reads a transaction back
+//! - `process_next` pops the next transaction, executes it through a
+//!   `TransactionProcessor` and stores the result, retrying failures after
+//!   a backoff while the transaction has retries left
+//! - `dequeue_batch` and `process_dequeued` split that in two, so a worker
+//!   can pop several transactions at once and execute them concurrently
+//!
+//! With `publish_events` set, submits and the transitions processing makes
+//! are also published to the queue's event stream.
+//!
+//! What only makes sense behind HTTP stays in the API crate: deadlines,
+//! feature flags, shadow comparisons, soft limit warnings and backpressure.
+
+pub mod submit;
+pub mod worker;
+
+use chrono::{DateTime, Utc};
+use postgres_models::sources::{Clock, IdGenerator, SystemClock, TimeOrderedIds};
+use postgres_models::{DbConnection, DbError, DbPool};
+use redis_cache::{
+    EventPublisher, KeySpace, QueueEnvelope, QueueEvent, QueueEventKind, QueueManager, QueueName, RateLimitResult,
+    RateLimiter, RedisError, RedisPool,
+};
+use serde_json::value::to_raw_value;
+use std::sync::Arc;
+
+pub use postgres_models::models::{TransactionQueue, TransactionStatus};
+pub use postgres_models::processing::{
+    NoopProcessor, ProcessError, ProcessOutcome, SimulatedProcessor, TransactionProcessor,
+};
+pub use submit::{SubmitRequest, Submitted};
+pub use worker::{Dequeued, Processed, RetryBackoff};
+
+/// Queue the API submits to and the worker drains
+pub static TRANSACTION_QUEUE: QueueName = QueueName::from_static("tx_queue");
+
+#[derive(Debug, thiserror::Error)]
+pub enum EngineError {
+    #[error("Invalid request: {0}")]
+    Invalid(String),
+
+    #[error("Rate limit exceeded")]
+    RateLimited(RateLimitResult),
+
+    #[error("Account already has {0} pending transactions, the maximum allowed")]
+    PendingLimit(u32),
+
+    #[error(transparent)]
+    Db(#[from] DbError),
+
+    #[error(transparent)]
+    Redis(#[from] RedisError),
+
+    #[error(transparent)]
+    Process(#[from] ProcessError),
+}
+
+impl From<diesel::result::Error> for EngineError {
+    fn from(err: diesel::result::Error) -> Self {
+        Self::Db(err.into())
+    }
+}
+
+/// Connection pools the engine works through
+#[derive(Clone)]
+pub struct EnginePools {
+    pub db: DbPool,
+    pub redis: RedisPool,
+}
+
+/// Limits and queue layout; the defaults match the API's
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub queue_name: QueueName,
+    /// Submits an account may make per window
+    pub max_requests: u32,
+    pub window_seconds: u64,
+    /// Transactions an account may have queued and not yet finished
+    pub max_pending_per_account: u32,
+    /// Payloads larger than this are compressed in the queue
+    pub compress_over_bytes: usize,
+    /// Key layout and prefix of every Redis key the engine touches
+    pub keys: KeySpace,
+    /// Publish lifecycle events to the queue's event stream
+    pub publish_events: bool,
+    /// Wait between a failed attempt and the next
+    pub retry_backoff: RetryBackoff,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            queue_name: TRANSACTION_QUEUE.clone(),
+            max_requests: 100,
+            window_seconds: 60,
+            max_pending_per_account: 1000,
+            compress_over_bytes: 16 * 1024,
+            keys: KeySpace::default(),
+            publish_events: false,
+            retry_backoff: RetryBackoff::default(),
+        }
+    }
+}
+
+pub struct QueueEngine {
+    db_pool: DbPool,
+    limiter: RateLimiter,
+    queue: QueueManager,
+    events: EventPublisher,
+    config: EngineConfig,
+    ids: Arc<dyn IdGenerator>,
+    clock: Arc<dyn Clock>,
+}
+
+impl QueueEngine {
+    pub fn new(pools: EnginePools, config: EngineConfig) -> Self {
+        let events = if config.publish_events {
+            let queue = QueueManager::new(pools.redis.clone()).with_keys(config.keys.clone());
+            EventPublisher::new(queue, config.queue_name.clone())
+        } else {
+            EventPublisher::disabled()
+        };
+        Self {
+            db_pool: pools.db,
+            limiter: RateLimiter::new(pools.redis.clone()).with_keys(config.keys.clone()),
+            queue: QueueManager::new(pools.redis).with_keys(config.keys.clone()),
+            events,
+            config,
+            ids: Arc::new(TimeOrderedIds),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Take ids and timestamps of new rows from `ids` and `clock`
+    pub fn with_sources(mut self, ids: Arc<dyn IdGenerator>, clock: Arc<dyn Clock>) -> Self {
+        self.ids = ids;
+        self.clock = clock;
+        self
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    pub fn queue_manager(&self) -> &QueueManager {
+        &self.queue
+    }
+
+    async fn connection(&self) -> Result<DbConnection, DbError> {
+        self.db_pool
+            .get_owned()
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))
+    }
+
+    /// Queue member of `transaction`, compressed past the configured size
+    fn envelope(&self, transaction: &TransactionQueue) -> Result<String, EngineError> {
+        let raw = to_raw_value(&transaction.transaction_data).map_err(|e| EngineError::Invalid(e.to_string()))?;
+        Ok(QueueEnvelope::encode_compressed(
+            &transaction.id.to_string(),
+            &transaction.account_id,
+            &raw,
+            self.config.compress_over_bytes,
+            None,
+        )?)
+    }
+
+    /// A transaction by id, if it exists
+    pub async fn status(&self, id: uuid::Uuid) -> Result<Option<TransactionQueue>, EngineError> {
+        let mut conn = self.connection().await?;
+        Ok(TransactionQueue::find(&mut conn, id).await?)
+    }
+}
+
+/// `kind` of `transaction`, happening at `at`
+fn lifecycle_event(kind: QueueEventKind, transaction: &TransactionQueue, at: DateTime<Utc>) -> QueueEvent {
+    QueueEvent::new(
+        kind,
+        transaction.id.to_string(),
+        transaction.account_id.clone(),
+        transaction.priority,
+        at.timestamp_millis().max(0) as u64,
+    )
+}
+
+/// Milliseconds from `from` to `to`, 0 if `to` is earlier
+fn millis_between(from: DateTime<Utc>, to: DateTime<Utc>) -> u64 {
+    (to - from).num_milliseconds().max(0) as u64
+}
@@ -0,0 +1,112 @@
+use crate::{lifecycle_event, EngineError, QueueEngine};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{NewTransactionQueue, TransactionQueue};
+use postgres_models::schema::transaction_queue;
+use redis_cache::{QueueEventKind, RateLimitResult, MAX_PRIORITY, MIN_PRIORITY};
+use uuid::Uuid;
+
+/// Longest account id accepted
+pub const MAX_ACCOUNT_ID_LEN: usize = 255;
+
+#[derive(Debug, Clone)]
+pub struct SubmitRequest {
+    pub account_id: String,
+    pub transaction_data: serde_json::Value,
+    /// Higher is processed first; `None` means 0
+    pub priority: Option<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Submitted {
+    pub transaction_id: Uuid,
+    /// 1-based rank in the priority queue
+    pub queue_position: i64,
+    pub rate_limit: RateLimitResult,
+}
+
+pub fn validate_account_id(account_id: &str) -> Result<(), String> {
+    if account_id.is_empty() || account_id.len() > MAX_ACCOUNT_ID_LEN {
+        return Err("Invalid account_id: must be 1-255 characters".to_string());
+    }
+    Ok(())
+}
+
+pub fn validate_priority(priority: Option<i32>) -> Result<(), String> {
+    match priority {
+        Some(priority) if !(MIN_PRIORITY..=MAX_PRIORITY).contains(&priority) => {
+            Err("priority must be between -1000 and 1000".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+impl QueueEngine {
+    /// Rate limit, store and enqueue a transaction.
+    ///
+    /// A slot under the account's pending cap is held before the row is
+    /// written and handed back if the write fails; the worker frees it once
+    /// the transaction completes.
+    pub async fn submit(&self, request: SubmitRequest) -> Result<Submitted, EngineError> {
+        validate_account_id(&request.account_id).map_err(EngineError::Invalid)?;
+        if request.transaction_data.is_null() {
+            return Err(EngineError::Invalid("transaction_data is required".to_string()));
+        }
+        validate_priority(request.priority).map_err(EngineError::Invalid)?;
+
+        let rate_limit = self
+            .limiter
+            .check_rate_limit_script(&request.account_id, self.config.max_requests, self.config.window_seconds)
+            .await?;
+        if !rate_limit.allowed {
+            return Err(EngineError::RateLimited(rate_limit));
+        }
+
+        let cap = self.config.max_pending_per_account;
+        if !self.queue.reserve_pending(&request.account_id, cap).await? {
+            return Err(EngineError::PendingLimit(cap));
+        }
+
+        let transaction = match self.insert(&request).await {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                // Nothing was stored, so the slot goes back
+                let _ = self.queue.release_pending(&request.account_id).await;
+                return Err(e);
+            }
+        };
+
+        let envelope = self.envelope(&transaction)?;
+        let queue_position = self
+            .queue
+            .enqueue_with_priority(&self.config.queue_name, &envelope, transaction.priority)
+            .await?;
+        self.events
+            .publish(lifecycle_event(QueueEventKind::Submitted, &transaction, transaction.created_at));
+
+        Ok(Submitted {
+            transaction_id: transaction.id,
+            queue_position,
+            rate_limit,
+        })
+    }
+
+    async fn insert(&self, request: &SubmitRequest) -> Result<TransactionQueue, EngineError> {
+        let mut new_transaction = NewTransactionQueue::new(
+            request.account_id.clone(),
+            request.transaction_data.clone(),
+            &*self.ids,
+            &*self.clock,
+        );
+        new_transaction.priority = request.priority.unwrap_or(0);
+        new_transaction.scheduled_at = Some(new_transaction.created_at);
+
+        let mut conn = self.connection().await?;
+        let transaction = diesel::insert_into(transaction_queue::table)
+            .values(&new_transaction)
+            .returning(TransactionQueue::as_returning())
+            .get_result(&mut conn)
+            .await?;
+        Ok(transaction)
+    }
+}
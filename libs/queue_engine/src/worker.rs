@@ -0,0 +1,353 @@
+use crate::{lifecycle_event, millis_between, EngineError, QueueEngine};
+use chrono::{DateTime, TimeDelta, Utc};
+use diesel_async::AsyncPgConnection;
+use postgres_models::{DbConnection, DbError};
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use postgres_models::processing::{self, ProcessError, ProcessOutcome, TransactionProcessor};
+use redis_cache::{PoppedEnvelope, QueueEventKind};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Retries that are not due yet, and members that are not transactions,
+/// `dequeue_batch` passes over before reporting that nothing is ready
+const MAX_DEFERRED_PER_CALL: usize = 32;
+/// Tries at putting a popped entry back before logging it as lost
+const PUT_BACK_ATTEMPTS: u32 = 3;
+/// Wait before the second try, growing by as much for each try after it
+const PUT_BACK_DELAY: Duration = Duration::from_millis(100);
+
+/// What `process_dequeued` did with the transaction it was given
+#[derive(Debug, Clone, PartialEq)]
+pub struct Processed {
+    pub transaction_id: Uuid,
+    pub outcome: ProcessOutcome,
+}
+
+/// How long a transaction whose attempt failed waits before the next one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryBackoff {
+    /// Delay before the first retry, doubled for each retry after it
+    pub base: Duration,
+    /// Longest delay between attempts
+    pub max: Duration,
+}
+
+impl RetryBackoff {
+    /// Delay before the next attempt of a transaction retried `retries` times so far
+    pub fn delay(&self, retries: i32) -> Duration {
+        let doublings = retries.clamp(0, 31) as u32;
+        self.base.saturating_mul(1 << doublings).min(self.max)
+    }
+}
+
+impl Default for RetryBackoff {
+    fn default() -> Self {
+        Self {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(300),
+        }
+    }
+}
+
+/// A transaction `dequeue_batch` popped and claimed, with the database
+/// connection it is processed on. Dropping one unprocessed leaves its row
+/// in "processing" for the stale processing check to find.
+pub struct Dequeued {
+    conn: DbConnection,
+    popped: PoppedEnvelope,
+    transaction_id: Uuid,
+    /// The row as claimed, when it could be loaded
+    claimed: Option<TransactionQueue>,
+    dequeued_at: DateTime<Utc>,
+}
+
+impl Dequeued {
+    pub fn transaction_id(&self) -> Uuid {
+        self.transaction_id
+    }
+}
+
+impl QueueEngine {
+    /// Pop the next transaction by priority, execute it and store its result.
+    ///
+    /// Returns `None` once nothing in the queue is ready. See `dequeue_batch`
+    /// and `process_dequeued` for the two halves.
+    pub async fn process_next<P: TransactionProcessor>(&self, processor: &P) -> Result<Option<Processed>, EngineError> {
+        match self.dequeue_batch(1).await?.pop() {
+            Some(dequeued) => self.process_dequeued(processor, dequeued).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Pop up to `max` transactions by priority in one batch and claim them.
+    ///
+    /// Each one gets a database connection of its own, checked out before
+    /// anything is popped: the first is waited for, and the rest only taken
+    /// while the pool has them idle, so the batch shrinks to fit the pool
+    /// rather than waiting on it. Nothing is popped when no connection is
+    /// to be had.
+    ///
+    /// Returns an empty batch once nothing in the queue is ready. Retries
+    /// popped before they are due are put back at the end of their priority
+    /// and passed over. A popped entry that cannot be claimed goes back in
+    /// the queue where it was and the failure is logged; it is only
+    /// returned when nothing could be claimed.
+    pub async fn dequeue_batch(&self, max: usize) -> Result<Vec<Dequeued>, EngineError> {
+        if max == 0 {
+            return Ok(Vec::new());
+        }
+        let mut conns = vec![self.connection().await?];
+        while conns.len() < max && self.db_pool.state().idle_connections > 0 {
+            match self.connection().await {
+                Ok(conn) => conns.push(conn),
+                Err(_) => break,
+            }
+        }
+
+        let mut dequeued = Vec::new();
+        let mut passed_over = 0;
+        let mut failed = None;
+        while dequeued.is_empty() && failed.is_none() && passed_over <= MAX_DEFERRED_PER_CALL {
+            let batch = self.queue.dequeue_envelopes(&self.config.queue_name, conns.len()).await?;
+            if batch.is_empty() {
+                break;
+            }
+            let dequeued_at = self.clock.now();
+            for popped in batch {
+                let mut conn = conns.pop().expect("a connection per popped entry");
+                match self.claim(&mut conn, &popped, dequeued_at).await {
+                    Ok(Some((transaction_id, claimed))) => dequeued.push(Dequeued {
+                        conn,
+                        popped,
+                        transaction_id,
+                        claimed,
+                        dequeued_at,
+                    }),
+                    Ok(None) => {
+                        passed_over += 1;
+                        conns.push(conn);
+                    }
+                    Err(e) => {
+                        tracing::warn!(transaction_id = popped.envelope.transaction_id, "Failed to claim: {}", e);
+                        self.put_back(&popped).await;
+                        failed.get_or_insert(e);
+                        conns.push(conn);
+                    }
+                }
+            }
+        }
+        match failed {
+            Some(e) if dequeued.is_empty() => Err(e),
+            _ => Ok(dequeued),
+        }
+    }
+
+    /// Claim the row of a popped entry, publishing that it was dequeued.
+    /// Returns its id, with the row when that could be loaded, or `None` when
+    /// it was passed over: requeued as a retry not due yet, or quarantined
+    /// as not a transaction.
+    async fn claim(
+        &self,
+        conn: &mut AsyncPgConnection,
+        popped: &PoppedEnvelope,
+        dequeued_at: DateTime<Utc>,
+    ) -> Result<Option<(Uuid, Option<TransactionQueue>)>, EngineError> {
+        let Ok(transaction_id) = popped.envelope.transaction_id.parse::<Uuid>() else {
+            let error = format!("queued transaction id {:?} is not a UUID", popped.envelope.transaction_id);
+            tracing::warn!(queue = %self.config.queue_name, error, "Quarantining queue member");
+            self.queue.quarantine(&self.config.queue_name, &popped.member, &error).await?;
+            return Ok(None);
+        };
+
+        // Only feeds statistics and events, so process() still decides what a missing row means
+        let claimed = match TransactionQueue::claim(conn, transaction_id).await? {
+            Some(transaction) => Some(transaction),
+            None => match TransactionQueue::find(conn, transaction_id).await {
+                Ok(Some(transaction)) if is_deferred(&transaction, dequeued_at) => {
+                    if let Err(e) = self.requeue(&transaction).await {
+                        tracing::warn!(%transaction_id, "Failed to requeue a deferred retry: {}", e);
+                        self.put_back(popped).await;
+                    }
+                    return Ok(None);
+                }
+                Ok(transaction) => transaction.filter(|t| t.status == TransactionStatus::Processing.as_str()),
+                Err(e) => {
+                    tracing::warn!(%transaction_id, "Failed to load claimed transaction: {}", e);
+                    None
+                }
+            },
+        };
+        if let Some(transaction) = &claimed {
+            self.events.publish(
+                lifecycle_event(QueueEventKind::Dequeued, transaction, dequeued_at)
+                    .with_wait_ms(millis_between(transaction.created_at, dequeued_at)),
+            );
+        }
+        Ok(Some((transaction_id, claimed)))
+    }
+
+    /// Execute a transaction `dequeue_batch` claimed and store its result.
+    ///
+    /// A transaction left in "processing" by a crashed worker is picked up
+    /// where it stopped; the processor's idempotency key keeps it from
+    /// executing twice. One cancelled while queued is reported as
+    /// `Cancelled` without executing.
+    ///
+    /// When the processor fails the error is returned once the transaction
+    /// is settled: it goes back in the queue in "retry", due after the
+    /// configured backoff, while it has retries left, and is failed for good
+    /// once it has none. When the database fails instead, its entry goes
+    /// back in the queue where it was popped from, to be picked up again.
+    pub async fn process_dequeued<P: TransactionProcessor>(
+        &self,
+        processor: &P,
+        dequeued: Dequeued,
+    ) -> Result<Processed, EngineError> {
+        let Dequeued {
+            mut conn,
+            popped,
+            transaction_id,
+            claimed,
+            dequeued_at,
+        } = dequeued;
+        let outcome = match processing::process(&mut conn, processor, transaction_id).await {
+            Ok(outcome) => outcome,
+            Err(ProcessError::Processor(message)) => {
+                let retries = claimed.as_ref().map_or(0, |transaction| transaction.retry_count);
+                self.settle_failure(&mut conn, transaction_id, &popped, &message, retries).await?;
+                if let Some(transaction) = &claimed {
+                    self.publish_finished(QueueEventKind::Failed, transaction, dequeued_at);
+                }
+                return Err(ProcessError::Processor(message).into());
+            }
+            Err(ProcessError::Db(e)) if is_transient(&e) => {
+                self.put_back(&popped).await;
+                return Err(ProcessError::Db(e).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let ProcessOutcome::Completed(_) = outcome {
+            self.queue.release_pending(&popped.envelope.account_id).await?;
+            self.queue.record_processed(&self.config.queue_name, 1).await?;
+            if let Some(transaction) = &claimed {
+                self.record_wait(transaction, dequeued_at).await;
+                self.publish_finished(QueueEventKind::Completed, transaction, dequeued_at);
+            }
+        }
+        Ok(Processed {
+            transaction_id,
+            outcome,
+        })
+    }
+
+    /// Retry a transaction whose attempt failed once the backoff for its
+    /// `retries` so far has passed, or fail it for good when none are left.
+    /// A failed transaction is finished: its pending slot is freed and it
+    /// counts towards throughput like a completed one.
+    ///
+    /// Until the retry is back in the queue the popped entry is the only
+    /// one left, so it is put back where it was if recording the failure or
+    /// requeueing the retry fails. Once due, a retry popped through that
+    /// entry is requeued at its backoff like any other.
+    async fn settle_failure(
+        &self,
+        conn: &mut AsyncPgConnection,
+        transaction_id: Uuid,
+        popped: &PoppedEnvelope,
+        message: &str,
+        retries: i32,
+    ) -> Result<(), EngineError> {
+        let delay = self.config.retry_backoff.delay(retries);
+        let retry_at = self.clock.now() + TimeDelta::milliseconds(delay.as_millis() as i64);
+        let transaction = match TransactionQueue::fail_attempt(conn, transaction_id, message, retry_at).await {
+            Ok(Some(transaction)) => transaction,
+            // Another worker settled it first
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                self.put_back(popped).await;
+                return Err(e.into());
+            }
+        };
+        if transaction.status == TransactionStatus::Retry.as_str() {
+            if let Err(e) = self.requeue(&transaction).await {
+                tracing::warn!(%transaction_id, "Failed to requeue a retry: {}", e);
+                self.put_back(popped).await;
+            }
+            tracing::info!(
+                %transaction_id,
+                retry_count = transaction.retry_count,
+                delay_ms = delay.as_millis() as u64,
+                error = message,
+                "Transaction failed, retrying"
+            );
+        } else {
+            self.queue.release_pending(&transaction.account_id).await?;
+            self.queue.record_processed(&self.config.queue_name, 1).await?;
+            tracing::warn!(
+                %transaction_id,
+                retry_count = transaction.retry_count,
+                error = message,
+                "Transaction failed with no retries left"
+            );
+        }
+        Ok(())
+    }
+
+    /// Put a transaction back in the queue at its priority
+    async fn requeue(&self, transaction: &TransactionQueue) -> Result<(), EngineError> {
+        let envelope = self.envelope(transaction)?;
+        self.queue.add_with_priority(&self.config.queue_name, &envelope, transaction.priority).await?;
+        Ok(())
+    }
+
+    /// Put a popped entry back in the queue where it was, trying a few
+    /// times. The entry exists nowhere else, so one that cannot be put back
+    /// is logged with its member for an operator to restore.
+    async fn put_back(&self, popped: &PoppedEnvelope) {
+        let entry = [(popped.member.clone(), popped.score)];
+        for attempt in 1..=PUT_BACK_ATTEMPTS {
+            match self.queue.restore(&self.config.queue_name, &entry).await {
+                Ok(()) => return,
+                Err(e) if attempt == PUT_BACK_ATTEMPTS => tracing::error!(
+                    queue = %self.config.queue_name,
+                    transaction_id = popped.envelope.transaction_id,
+                    member = popped.member,
+                    score = popped.score,
+                    "Failed to put a popped entry back in the queue: {}",
+                    e
+                ),
+                Err(_) => tokio::time::sleep(PUT_BACK_DELAY * attempt).await,
+            }
+        }
+    }
+
+    /// Sample how long a completed transaction sat in the queue. The
+    /// samples only feed statistics, so failures are logged.
+    async fn record_wait(&self, transaction: &TransactionQueue, dequeued_at: DateTime<Utc>) {
+        let wait_ms = millis_between(transaction.created_at, dequeued_at);
+        if let Err(e) = self.queue.record_wait(&self.config.queue_name, transaction.priority, wait_ms).await {
+            tracing::warn!(transaction_id = %transaction.id, "Failed to record wait time: {}", e);
+        }
+    }
+
+    /// Publish the end of processing `transaction`, with its wait and processing time
+    fn publish_finished(&self, kind: QueueEventKind, transaction: &TransactionQueue, dequeued_at: DateTime<Utc>) {
+        let now = self.clock.now();
+        self.events.publish(
+            lifecycle_event(kind, transaction, now)
+                .with_wait_ms(millis_between(transaction.created_at, dequeued_at))
+                .with_processing_ms(millis_between(dequeued_at, now)),
+        );
+    }
+}
+
+/// Whether a database failure may pass on its own, so the transaction is
+/// worth picking up again later
+fn is_transient(error: &DbError) -> bool {
+    matches!(error, DbError::Pool(_) | DbError::Query(_) | DbError::Connection(_))
+}
+
+/// Whether `transaction` is a retry that is not due yet at `now`
+fn is_deferred(transaction: &TransactionQueue, now: DateTime<Utc>) -> bool {
+    transaction.status == TransactionStatus::Retry.as_str() && transaction.scheduled_at.is_some_and(|at| at > now)
+}
@@ -0,0 +1,43 @@
+//! Per-account index of the priority queue.
+//!
+//! Adding an envelope to the priority queue also adds its transaction id to
+//! the account's index, a sorted set scored like the queue member, and
+//! records when it was added. Popping or removing the member takes it out
+//! again. Listing what an account has queued therefore reads only that
+//! account's index, never the whole queue, and an item's global rank is a
+//! ZCOUNT of the queue below its score.
+//!
+//! Only the add is atomic with the queue write. If an instance stops
+//! between popping a member and unindexing it, the id is left behind with a
+//! score no queue member has; listing the account drops it.
+//!
+//! Members that are not envelopes name no account and are not indexed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::envelope;
+
+/// Most items listed for one account per call
+pub const MAX_ACCOUNT_ITEMS: usize = 1000;
+
+/// Transaction id, score, members ahead of it and add time of an index
+/// entry, as the listing script returns them
+pub(crate) type ListedEntry = (String, f64, u64, Option<u64>);
+
+/// One of an account's members of the priority queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountQueueItem {
+    pub transaction_id: String,
+    /// Position in the whole queue (1-indexed), across every account
+    pub rank: u64,
+    pub priority: i32,
+    /// When the member was added, in unix milliseconds by the Redis clock
+    pub enqueued_at_ms: Option<u64>,
+    /// How long the member has waited so far, by the same clock
+    pub age_ms: Option<u64>,
+}
+
+/// Account and transaction id a member is indexed under, if it is an envelope
+pub fn index_entry(member: &str) -> Option<(String, String)> {
+    envelope::account_id_of(member).zip(envelope::transaction_id_of(member))
+}
@@ -0,0 +1,200 @@
+//! Queue members. Large payloads are stored zstd-compressed and base64
+//! encoded, marked with `"enc": "zstd"`; decoding undoes it, so readers
+//! always see the original payload text.
+//!
+//! Envelopes may also carry the W3C `traceparent` of the request that
+//! enqueued them, so processing can continue the submit trace.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use crate::RedisError;
+
+pub const COMPRESSED_BYTES_SAVED_TOTAL: &str = "queue_compressed_bytes_saved_total";
+
+/// `enc` value of envelopes with a compressed payload
+pub const ZSTD_ENCODING: &str = "zstd";
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// Refuse to inflate a member past this, whatever its header claims
+const MAX_DECOMPRESSED_BYTES: usize = 16 * 1024 * 1024;
+
+/// Member stored in the priority queue for each transaction.
+///
+/// The transaction id makes every member unique, so identical payloads
+/// submitted twice are two queue entries instead of collapsing into one.
+#[derive(Debug)]
+pub struct QueueEnvelope {
+    pub transaction_id: String,
+    pub account_id: String,
+    pub transaction_data: Box<RawValue>,
+    /// Trace context of the submit request, if it was traced
+    pub traceparent: Option<String>,
+}
+
+/// An envelope popped from the priority queue, with the member and score it
+/// was stored under, so `QueueManager::restore` can put it back unchanged
+#[derive(Debug)]
+pub struct PoppedEnvelope {
+    pub envelope: QueueEnvelope,
+    pub member: String,
+    pub score: f64,
+}
+
+#[derive(Serialize)]
+struct EnvelopeRef<'a, T: Serialize + ?Sized> {
+    transaction_id: &'a str,
+    account_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enc: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    traceparent: Option<&'a str>,
+    transaction_data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct StoredEnvelope {
+    transaction_id: String,
+    account_id: String,
+    #[serde(default)]
+    enc: Option<String>,
+    #[serde(default)]
+    traceparent: Option<String>,
+    transaction_data: Box<RawValue>,
+}
+
+impl QueueEnvelope {
+    /// Serialize an envelope, copying the payload text as-is
+    pub fn encode(transaction_id: &str, account_id: &str, transaction_data: &RawValue) -> Result<String, RedisError> {
+        Ok(serde_json::to_string(&EnvelopeRef {
+            transaction_id,
+            account_id,
+            enc: None,
+            traceparent: None,
+            transaction_data,
+        })?)
+    }
+
+    /// Serialize an envelope with the submit request's `traceparent`,
+    /// compressing payloads larger than `compress_over_bytes`. Payloads that
+    /// do not shrink are stored as-is.
+    pub fn encode_compressed(
+        transaction_id: &str,
+        account_id: &str,
+        transaction_data: &RawValue,
+        compress_over_bytes: usize,
+        traceparent: Option<&str>,
+    ) -> Result<String, RedisError> {
+        let raw = transaction_data.get();
+        let plain = || {
+            Ok(serde_json::to_string(&EnvelopeRef {
+                transaction_id,
+                account_id,
+                enc: None,
+                traceparent,
+                transaction_data,
+            })?)
+        };
+        if raw.len() <= compress_over_bytes {
+            return plain();
+        }
+
+        let compressed = zstd::bulk::compress(raw.as_bytes(), ZSTD_LEVEL)
+            .map_err(|e| RedisError::Compression(e.to_string()))?;
+        let encoded = BASE64.encode(compressed);
+        // The encoded payload is stored as a JSON string, quotes included
+        let stored_len = encoded.len() + 2;
+        if stored_len >= raw.len() {
+            return plain();
+        }
+
+        metrics::counter!(COMPRESSED_BYTES_SAVED_TOTAL).increment((raw.len() - stored_len) as u64);
+        Ok(serde_json::to_string(&EnvelopeRef {
+            transaction_id,
+            account_id,
+            enc: Some(ZSTD_ENCODING),
+            traceparent,
+            transaction_data: encoded.as_str(),
+        })?)
+    }
+
+    pub fn decode(member: &str) -> Result<Self, RedisError> {
+        let stored: StoredEnvelope = serde_json::from_str(member)?;
+        let transaction_data = match stored.enc.as_deref() {
+            None => stored.transaction_data,
+            Some(ZSTD_ENCODING) => decompress(&stored.transaction_data)?,
+            Some(other) => {
+                return Err(RedisError::Compression(format!("unknown envelope encoding {:?}", other)));
+            }
+        };
+
+        Ok(Self {
+            transaction_id: stored.transaction_id,
+            account_id: stored.account_id,
+            transaction_data,
+            traceparent: stored.traceparent,
+        })
+    }
+}
+
+/// Key that precedes the account id in every encoded envelope
+const ACCOUNT_ID_FIELD: &str = "\"account_id\":";
+
+/// Key that opens every encoded envelope
+const TRANSACTION_ID_FIELD: &str = "\"transaction_id\":";
+
+/// Account id of an encoded envelope, read from the start of the member
+/// without parsing or decompressing the payload. `member` may be truncated
+/// anywhere after the account id.
+pub fn account_id_of(member: &str) -> Option<String> {
+    string_field(member, ACCOUNT_ID_FIELD)
+}
+
+/// Transaction id of an encoded envelope, read the same way as `account_id_of`
+pub fn transaction_id_of(member: &str) -> Option<String> {
+    string_field(member, TRANSACTION_ID_FIELD)
+}
+
+/// Start of the encoded envelope of `transaction_id`, which is always the
+/// first field, up to and including the comma after it
+pub fn member_prefix(transaction_id: &str) -> String {
+    format!("{{{}{},", TRANSACTION_ID_FIELD, serde_json::to_string(transaction_id).unwrap_or_default())
+}
+
+/// Redis glob matching the encoded envelope of `transaction_id`. Glob
+/// metacharacters in the id are escaped.
+pub fn member_pattern(transaction_id: &str) -> String {
+    let mut pattern = String::new();
+    for c in member_prefix(transaction_id).chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('*');
+    pattern
+}
+
+/// The string following the first occurrence of `field` in `member`. The
+/// id fields are written before the payload, so payload text never matches.
+fn string_field(member: &str, field: &str) -> Option<String> {
+    let start = member.find(field)? + field.len();
+    serde_json::Deserializer::from_str(&member[start..])
+        .into_iter::<String>()
+        .next()?
+        .ok()
+}
+
+fn decompress(stored: &RawValue) -> Result<Box<RawValue>, RedisError> {
+    let encoded: String = serde_json::from_str(stored.get())?;
+    let compressed = BASE64
+        .decode(encoded)
+        .map_err(|e| RedisError::Compression(format!("invalid base64 payload: {}", e)))?;
+    let bytes = zstd::bulk::decompress(&compressed, MAX_DECOMPRESSED_BYTES)
+        .map_err(|e| RedisError::Compression(e.to_string()))?;
+    let text = String::from_utf8(bytes).map_err(|e| RedisError::Compression(e.to_string()))?;
+    Ok(RawValue::from_string(text)?)
+}
@@ -0,0 +1,113 @@
+use deadpool::managed::TimeoutType;
+use deadpool_redis::redis::{ErrorKind, RedisError as RedisClientError};
+use deadpool_redis::PoolError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RedisError {
+    #[error("Redis pool error: {0}")]
+    Pool(PoolError),
+
+    /// Timed out waiting for a free connection; every connection is checked out
+    #[error("Redis pool exhausted")]
+    PoolExhausted,
+
+    #[error("Redis error: {0}")]
+    Redis(RedisClientError),
+
+    /// A Lua script failed to run or was not loaded on the server
+    #[error("Redis script error: {0}")]
+    Script(RedisClientError),
+
+    #[error("Redis timeout: {0}")]
+    Timeout(String),
+
+    /// The queue refused new items because it is at capacity
+    #[error("Queue {queue} is full")]
+    QueueFull { queue: String },
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// A queue member could not be compressed or decompressed
+    #[error("Compression error: {0}")]
+    Compression(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    /// A queue name failed `QueueName` validation
+    #[error("Invalid queue name {name:?}: {reason}")]
+    InvalidQueueName { name: String, reason: &'static str },
+}
+
+impl RedisError {
+    /// Whether retrying the same operation later can reasonably succeed.
+    /// Drives the retry wrapper, the circuit breaker and 503 vs 500 responses.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::PoolExhausted | Self::Timeout(_) | Self::QueueFull { .. } => true,
+            Self::Redis(err) => is_transient_kind(err),
+            Self::Pool(_) | Self::Script(_) | Self::Serialization(_) | Self::Compression(_) | Self::Config(_) => false,
+            Self::InvalidQueueName { .. } => false,
+        }
+    }
+
+    /// Whether Redis could not be reached at all: no connection could be
+    /// had or the one in use failed. Script, serialization and reply errors
+    /// mean Redis is up and answered.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            Self::Pool(_) | Self::PoolExhausted | Self::Timeout(_) => true,
+            Self::Redis(err) => err.is_io_error() || err.is_connection_dropped() || err.is_connection_refusal(),
+            _ => false,
+        }
+    }
+}
+
+impl From<PoolError> for RedisError {
+    fn from(err: PoolError) -> Self {
+        match err {
+            PoolError::Timeout(TimeoutType::Wait) => Self::PoolExhausted,
+            PoolError::Timeout(TimeoutType::Create) => Self::Timeout("creating connection".to_string()),
+            PoolError::Timeout(TimeoutType::Recycle) => Self::Timeout("recycling connection".to_string()),
+            PoolError::Backend(err) => err.into(),
+            other => Self::Pool(other),
+        }
+    }
+}
+
+impl From<RedisClientError> for RedisError {
+    fn from(err: RedisClientError) -> Self {
+        if err.is_timeout() {
+            return Self::Timeout(err.to_string());
+        }
+        if is_script_error(&err) {
+            return Self::Script(err);
+        }
+        Self::Redis(err)
+    }
+}
+
+/// Lua runtime errors come back as plain ERR replies mentioning the script
+fn is_script_error(err: &RedisClientError) -> bool {
+    match err.kind() {
+        ErrorKind::NoScriptError => true,
+        ErrorKind::ResponseError => err
+            .detail()
+            .is_some_and(|detail| detail.contains("script") || detail.starts_with("Error running script")),
+        _ => false,
+    }
+}
+
+fn is_transient_kind(err: &RedisClientError) -> bool {
+    err.is_io_error()
+        || err.is_connection_dropped()
+        || err.is_connection_refusal()
+        || matches!(
+            err.kind(),
+            ErrorKind::BusyLoadingError
+                | ErrorKind::TryAgain
+                | ErrorKind::ClusterDown
+                | ErrorKind::MasterDown
+        )
+}
@@ -0,0 +1,198 @@
+//! Lifecycle events of queued transactions, for analytics.
+//!
+//! Each transition a transaction goes through (submitted, dequeued,
+//! completed, failed, cancelled) is appended to a Redis stream per queue, so
+//! consumers can follow the queue without reading Postgres. The stream is
+//! capped at about `EVENT_STREAM_MAXLEN` entries with approximate trimming,
+//! which lets Redis drop whole nodes instead of single entries.
+//!
+//! Publishing is fire-and-forget: `EventPublisher` sends each event from a
+//! spawned task and only logs failures, so an unreachable stream never fails
+//! or slows the operation that produced the event.
+
+use crate::{QueueManager, QueueName};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Entries a stream is trimmed to, approximately
+pub const EVENT_STREAM_MAXLEN: usize = 1_000_000;
+
+/// Stream id to read from to get every event still in the stream
+pub const EVENTS_FROM_START: &str = "0-0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueEventKind {
+    Submitted,
+    Dequeued,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl QueueEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Submitted => "submitted",
+            Self::Dequeued => "dequeued",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "submitted" => Some(Self::Submitted),
+            "dequeued" => Some(Self::Dequeued),
+            "completed" => Some(Self::Completed),
+            "failed" => Some(Self::Failed),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// One transition of one transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueueEvent {
+    pub kind: QueueEventKind,
+    pub transaction_id: String,
+    pub account_id: String,
+    pub priority: i32,
+    /// When the transition happened, in Unix epoch milliseconds
+    pub at_ms: u64,
+    /// Time from submission to dequeue, or to cancellation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_ms: Option<u64>,
+    /// Time from dequeue to completion or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processing_ms: Option<u64>,
+}
+
+impl QueueEvent {
+    pub fn new(
+        kind: QueueEventKind,
+        transaction_id: impl Into<String>,
+        account_id: impl Into<String>,
+        priority: i32,
+        at_ms: u64,
+    ) -> Self {
+        Self {
+            kind,
+            transaction_id: transaction_id.into(),
+            account_id: account_id.into(),
+            priority,
+            at_ms,
+            wait_ms: None,
+            processing_ms: None,
+        }
+    }
+
+    pub fn with_wait_ms(mut self, wait_ms: u64) -> Self {
+        self.wait_ms = Some(wait_ms);
+        self
+    }
+
+    pub fn with_processing_ms(mut self, processing_ms: u64) -> Self {
+        self.processing_ms = Some(processing_ms);
+        self
+    }
+
+    /// Stream entry fields, with short names since every entry repeats them
+    pub fn to_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("kind", self.kind.as_str().to_string()),
+            ("id", self.transaction_id.clone()),
+            ("account", self.account_id.clone()),
+            ("priority", self.priority.to_string()),
+            ("at_ms", self.at_ms.to_string()),
+        ];
+        if let Some(wait_ms) = self.wait_ms {
+            fields.push(("wait_ms", wait_ms.to_string()));
+        }
+        if let Some(processing_ms) = self.processing_ms {
+            fields.push(("processing_ms", processing_ms.to_string()));
+        }
+        fields
+    }
+
+    /// The event of a stream entry; `None` if a required field is missing
+    /// or malformed, e.g. an entry written by a newer version
+    pub fn from_fields(fields: &HashMap<String, String>) -> Option<Self> {
+        let optional = |name: &str| fields.get(name).and_then(|value| value.parse().ok());
+        Some(Self {
+            kind: QueueEventKind::parse(fields.get("kind")?)?,
+            transaction_id: fields.get("id")?.clone(),
+            account_id: fields.get("account")?.clone(),
+            priority: fields.get("priority")?.parse().ok()?,
+            at_ms: fields.get("at_ms")?.parse().ok()?,
+            wait_ms: optional("wait_ms"),
+            processing_ms: optional("processing_ms"),
+        })
+    }
+}
+
+/// Events read from a stream after a given id
+#[derive(Debug, Clone, Default)]
+pub struct EventBatch {
+    pub events: Vec<QueueEvent>,
+    /// Id to read the next batch after; the one passed in when nothing was read
+    pub last_id: String,
+}
+
+/// Publishes a queue's events without waiting for them to be written
+#[derive(Clone)]
+pub struct EventPublisher {
+    target: Option<Arc<(QueueManager, QueueName)>>,
+    max_len: usize,
+}
+
+impl EventPublisher {
+    /// Publisher to `queue_name`'s stream
+    pub fn new(queue: QueueManager, queue_name: QueueName) -> Self {
+        Self {
+            target: Some(Arc::new((queue, queue_name))),
+            max_len: EVENT_STREAM_MAXLEN,
+        }
+    }
+
+    /// Publisher that drops every event, for when events are turned off
+    pub fn disabled() -> Self {
+        Self {
+            target: None,
+            max_len: EVENT_STREAM_MAXLEN,
+        }
+    }
+
+    /// Trim the stream to about `max_len` entries instead of `EVENT_STREAM_MAXLEN`
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// Send `event` from a spawned task, logging a failure. Must be called
+    /// from within a Tokio runtime.
+    pub fn publish(&self, event: QueueEvent) {
+        let Some(target) = self.target.clone() else {
+            return;
+        };
+        let max_len = self.max_len;
+        tokio::spawn(async move {
+            let (queue, queue_name) = &*target;
+            if let Err(e) = queue.publish_event(queue_name, &event, max_len).await {
+                tracing::warn!(
+                    transaction_id = event.transaction_id,
+                    kind = event.kind.as_str(),
+                    "Failed to publish queue event: {}",
+                    e
+                );
+            }
+        });
+    }
+}
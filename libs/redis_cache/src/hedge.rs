@@ -0,0 +1,74 @@
+//! Hedged reads for idempotent commands. When the first attempt has not
+//! answered within the budget a second one goes out on another pooled
+//! connection; whichever finishes first wins and the other is dropped.
+
+use std::future::Future;
+use std::time::Duration;
+
+use crate::RedisError;
+
+pub const HEDGED_READS_TOTAL: &str = "redis_hedged_reads_total";
+pub const HEDGE_WINS_TOTAL: &str = "redis_hedge_wins_total";
+
+/// Which attempt produced the result of a hedged read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HedgeOutcome {
+    /// The first attempt answered within the budget; no hedge was sent
+    Primary,
+    /// A hedge was sent but the first attempt still answered first
+    PrimaryAfterHedge,
+    /// The hedge answered first
+    Hedge,
+}
+
+impl HedgeOutcome {
+    pub fn hedged(&self) -> bool {
+        !matches!(self, HedgeOutcome::Primary)
+    }
+}
+
+/// Run `attempt`, sending a second copy if the first is still pending after `budget`.
+/// An attempt that fails while the other is in flight falls back to the other one.
+pub async fn race<T, F, Fut>(budget: Duration, mut attempt: F) -> Result<(T, HedgeOutcome), RedisError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RedisError>>,
+{
+    let primary = attempt();
+    tokio::pin!(primary);
+
+    tokio::select! {
+        result = &mut primary => return result.map(|value| (value, HedgeOutcome::Primary)),
+        _ = tokio::time::sleep(budget) => {}
+    }
+
+    let hedge = attempt();
+    tokio::pin!(hedge);
+
+    tokio::select! {
+        result = &mut primary => match result {
+            Ok(value) => Ok((value, HedgeOutcome::PrimaryAfterHedge)),
+            Err(_) => hedge.await.map(|value| (value, HedgeOutcome::Hedge)),
+        },
+        result = &mut hedge => match result {
+            Ok(value) => Ok((value, HedgeOutcome::Hedge)),
+            Err(_) => primary.await.map(|value| (value, HedgeOutcome::PrimaryAfterHedge)),
+        },
+    }
+}
+
+/// `race` with the hedged and won-by-hedge counters recorded for `command`
+pub async fn run<T, F, Fut>(command: &'static str, budget: Duration, attempt: F) -> Result<T, RedisError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RedisError>>,
+{
+    let (value, outcome) = race(budget, attempt).await?;
+    if outcome.hedged() {
+        metrics::counter!(HEDGED_READS_TOTAL, "command" => command).increment(1);
+    }
+    if outcome == HedgeOutcome::Hedge {
+        metrics::counter!(HEDGE_WINS_TOTAL, "command" => command).increment(1);
+    }
+    Ok(value)
+}
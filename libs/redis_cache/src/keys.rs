@@ -0,0 +1,404 @@
+//! Names of the Redis keys this crate reads and writes.
+//!
+//! Redis Cluster only runs a script or multi-key command when every key it
+//! touches hashes to the same slot. With `KeyLayout::Cluster` the part of a
+//! key that groups it is wrapped in a hash tag, so only that part is hashed:
+//! an account's keys share the `{<account>}` tag and a queue's keys share the
+//! `{<queue>}` tag. `KeyLayout::Standalone` keeps the original names, so
+//! existing deployments see no change. Switching layout renames every tagged
+//! key, so live queues and windows must be drained or migrated first.
+//!
+//! A `KeySpace` adds an optional prefix in front of every key, so several
+//! deployments, or parallel test runs, can share one Redis without seeing
+//! each other's queues and windows.
+//!
+//! Limiter keys of a named scope, such as the admin or per-IP limits, live
+//! under `rate_limit_scope:<scope>:` rather than `rate_limit:`. Account ids
+//! may contain `:`, so a scope inside the `rate_limit:` family could always
+//! be reached by an account named `<scope>:<key>`.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::wait_times::PriorityBand;
+use crate::{QueueCounter, RedisError};
+
+/// How keys are named; see the module docs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyLayout {
+    #[default]
+    Standalone,
+    /// Hash tags on the account or queue part of each key
+    Cluster,
+}
+
+impl KeyLayout {
+    /// `id` as the grouping part of a key
+    pub(crate) fn tag(self, id: &str) -> String {
+        match self {
+            Self::Standalone => id.to_string(),
+            Self::Cluster => format!("{{{}}}", id),
+        }
+    }
+
+    /// Sliding window of a limiter key, e.g. an account id
+    pub fn sliding_window(self, key: &str) -> String {
+        format!("rate_limit:{}", self.tag(key))
+    }
+
+    /// Fixed window counter of a limiter key for window `index`
+    pub fn fixed_window(self, key: &str, index: u64) -> String {
+        format!("rate_limit:fixed:{}:{}", self.tag(key), index)
+    }
+
+    /// Token bucket of a limiter key
+    pub fn token_bucket(self, key: &str) -> String {
+        format!("rate_limit:bucket:{}", self.tag(key))
+    }
+
+    /// An account's pending transaction counter
+    pub fn pending(self, account_id: &str) -> String {
+        format!("account:{}:pending", self.tag(account_id))
+    }
+
+    /// Account id of a pending counter key in either layout
+    pub fn pending_account(key: &str) -> Option<&str> {
+        let id = key.strip_prefix("account:")?.strip_suffix(":pending")?;
+        Some(id.strip_prefix('{').and_then(|id| id.strip_suffix('}')).unwrap_or(id))
+    }
+
+    /// An account's cached limit of one type
+    pub fn limit_cache(self, account_id: &str, limit_type: &str) -> String {
+        format!("account:{}:limit:{}", self.tag(account_id), limit_type)
+    }
+
+    /// Claim on an Idempotency-Key of an account's submits
+    pub fn idempotency(self, account_id: &str, idempotency_key: &str) -> String {
+        format!("account:{}:idempotency:{}", self.tag(account_id), idempotency_key)
+    }
+}
+
+/// Longest key prefix accepted, in bytes
+pub const MAX_KEY_PREFIX_LEN: usize = 64;
+
+/// A key layout plus an optional prefix, `<prefix>:`, in front of every key.
+///
+/// Prefixes are ASCII letters, digits, `_`, `-` or `.`, which keeps hash tag
+/// delimiters and glob characters out of them, so a prefixed key lands in
+/// the same cluster slot as the unprefixed one and SCAN patterns built from
+/// the prefix only match this key space.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct KeySpace {
+    layout: KeyLayout,
+    prefix: Option<Arc<str>>,
+    limit_scope: Option<Arc<str>>,
+}
+
+impl KeySpace {
+    pub fn new(layout: KeyLayout) -> Self {
+        Self {
+            layout,
+            prefix: None,
+            limit_scope: None,
+        }
+    }
+
+    /// Put `prefix` in front of every key
+    pub fn with_prefix(mut self, prefix: &str) -> Result<Self, RedisError> {
+        if let Some(reason) = invalid_prefix_reason(prefix) {
+            return Err(RedisError::Config(format!("Invalid key prefix {:?}: {}", prefix, reason)));
+        }
+        self.prefix = Some(prefix.into());
+        Ok(self)
+    }
+
+    pub fn layout(&self) -> KeyLayout {
+        self.layout
+    }
+
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// This key space with its windows and buckets in `scope`, apart from
+    /// the unscoped ones and those of every other scope. Scopes are fixed
+    /// names without `:`; the keys of other families are unchanged.
+    pub fn in_limit_scope(&self, scope: &str) -> Self {
+        Self {
+            limit_scope: Some(scope.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Sliding window of `key` in `scope`
+    pub fn scoped_rate_limit(&self, scope: &str, key: &str) -> String {
+        self.in_limit_scope(scope).sliding_window(key)
+    }
+
+    fn prefixed(&self, key: String) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}:{}", prefix, key),
+            None => key,
+        }
+    }
+
+    /// `id` as the grouping part of the first segment of a key
+    fn tag(&self, id: &str) -> String {
+        self.prefixed(self.layout.tag(id))
+    }
+
+    /// A `rate_limit:` key of the layout, moved into the limit scope if any
+    fn limiter(&self, key: String) -> String {
+        match (&self.limit_scope, key.strip_prefix("rate_limit:")) {
+            (Some(scope), Some(rest)) => self.prefixed(format!("rate_limit_scope:{}:{}", scope, rest)),
+            _ => self.prefixed(key),
+        }
+    }
+
+    /// Sliding window of a limiter key, e.g. an account id
+    pub fn sliding_window(&self, key: &str) -> String {
+        self.limiter(self.layout.sliding_window(key))
+    }
+
+    /// Fixed window counter of a limiter key for window `index`
+    pub fn fixed_window(&self, key: &str, index: u64) -> String {
+        self.limiter(self.layout.fixed_window(key, index))
+    }
+
+    /// Token bucket of a limiter key
+    pub fn token_bucket(&self, key: &str) -> String {
+        self.limiter(self.layout.token_bucket(key))
+    }
+
+    /// An account's pending transaction counter
+    pub fn pending(&self, account_id: &str) -> String {
+        self.prefixed(self.layout.pending(account_id))
+    }
+
+    /// Account id of a pending counter key in this key space
+    pub fn pending_account<'a>(&self, key: &'a str) -> Option<&'a str> {
+        let key = match &self.prefix {
+            Some(prefix) => key.strip_prefix(&**prefix)?.strip_prefix(':')?,
+            None => key,
+        };
+        KeyLayout::pending_account(key)
+    }
+
+    /// An account's cached limit of one type
+    pub fn limit_cache(&self, account_id: &str, limit_type: &str) -> String {
+        self.prefixed(self.layout.limit_cache(account_id, limit_type))
+    }
+
+    /// Claim on an Idempotency-Key of an account's submits
+    pub fn idempotency(&self, account_id: &str, idempotency_key: &str) -> String {
+        self.prefixed(self.layout.idempotency(account_id, idempotency_key))
+    }
+
+    /// Sorted set of worker ids by their last heartbeat
+    pub fn worker_heartbeat(&self) -> String {
+        self.prefixed("workers:heartbeat".to_string())
+    }
+
+    /// Set of accounts whose transactions are not processed
+    pub fn paused_accounts(&self) -> String {
+        self.prefixed("accounts:paused".to_string())
+    }
+
+    /// Hash of feature flag name to rollout percentage
+    pub fn feature_flags(&self) -> String {
+        self.prefixed("feature_flags".to_string())
+    }
+
+    /// Last skip decision recorded for a transaction
+    pub fn hold(&self, transaction_id: &str) -> String {
+        self.prefixed(format!("hold:{}", transaction_id))
+    }
+
+    /// SCAN pattern matching every pending counter, in either layout
+    pub fn pending_pattern(&self) -> String {
+        self.prefixed("account:*:pending".to_string())
+    }
+
+    /// SCAN pattern matching every sliding and fixed window and token bucket,
+    /// scoped or not, in either layout
+    pub fn rate_limit_pattern(&self) -> String {
+        self.prefixed("rate_limit*".to_string())
+    }
+
+    /// SCAN pattern matching every key of this key space. Without a prefix
+    /// that is every key in the database.
+    pub fn all_pattern(&self) -> String {
+        self.prefixed("*".to_string())
+    }
+}
+
+impl From<KeyLayout> for KeySpace {
+    fn from(layout: KeyLayout) -> Self {
+        Self::new(layout)
+    }
+}
+
+/// Why `prefix` is not a valid key prefix, if it is not
+fn invalid_prefix_reason(prefix: &str) -> Option<&'static str> {
+    if prefix.is_empty() {
+        return Some("must not be empty");
+    }
+    if prefix.len() > MAX_KEY_PREFIX_LEN {
+        return Some("must be at most 64 bytes");
+    }
+    if !prefix.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.')) {
+        return Some("may only contain ASCII letters, digits, '_', '-' and '.'");
+    }
+    None
+}
+
+/// Longest queue name accepted, in bytes
+pub const MAX_QUEUE_NAME_LEN: usize = 64;
+
+/// A validated queue name, from which every key of the queue is derived.
+///
+/// Names are 1 to `MAX_QUEUE_NAME_LEN` ASCII letters, digits, `_` or `-`.
+/// That keeps `:` (the key separator) and `{`/`}` (hash tag delimiters) out,
+/// so one queue's keys can never collide with another's or land in the wrong
+/// cluster slot.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueueName(Cow<'static, str>);
+
+impl QueueName {
+    pub fn new(name: impl Into<String>) -> Result<Self, RedisError> {
+        let name = name.into();
+        match invalid_reason(&name) {
+            Some(reason) => Err(RedisError::InvalidQueueName { name, reason }),
+            None => Ok(Self(Cow::Owned(name))),
+        }
+    }
+
+    /// A name known at compile time; invalid names fail the build when used
+    /// in a `const`
+    pub const fn from_static(name: &'static str) -> Self {
+        assert!(invalid_reason(name).is_none(), "invalid queue name");
+        Self(Cow::Borrowed(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// List behind a FIFO queue
+    pub fn list_key(&self, keys: &KeySpace) -> String {
+        keys.tag(self.as_str())
+    }
+
+    /// Sorted set behind a priority queue
+    pub fn priority_key(&self, keys: &KeySpace) -> String {
+        format!("{}_priority", keys.tag(self.as_str()))
+    }
+
+    /// Counter handing out a priority queue's FIFO sequence
+    pub fn sequence_key(&self, keys: &KeySpace) -> String {
+        format!("{}:sequence", keys.tag(self.as_str()))
+    }
+
+    /// Sorted set of one account's members of a priority queue, holding
+    /// their transaction ids at the members' own scores
+    pub fn account_index_key(&self, keys: &KeySpace, account_id: &str) -> String {
+        format!("{}:account:{}", keys.tag(self.as_str()), account_id)
+    }
+
+    /// Hash of transaction id to when its member was added to the priority
+    /// queue, in unix milliseconds
+    pub fn enqueued_at_key(&self, keys: &KeySpace) -> String {
+        format!("{}:enqueued_at", keys.tag(self.as_str()))
+    }
+
+    /// Members of a queue that could not be decoded; this is the queue's
+    /// dead letter list
+    pub fn dlq_key(&self, keys: &KeySpace) -> String {
+        format!("{}:quarantine", keys.tag(self.as_str()))
+    }
+
+    /// Counter bucket of a queue for a given unix minute
+    pub fn counter_key(&self, keys: &KeySpace, counter: QueueCounter, minute: u64) -> String {
+        format!("{}:{}:{}", keys.tag(self.as_str()), counter.as_str(), minute)
+    }
+
+    /// Stream of a queue's lifecycle events
+    pub fn events_key(&self, keys: &KeySpace) -> String {
+        format!("{}:events", keys.tag(self.as_str()))
+    }
+
+    /// Recent wait times of a queue's completions in one priority band
+    pub fn wait_samples_key(&self, keys: &KeySpace, band: PriorityBand) -> String {
+        format!("{}:wait_ms:{}", keys.tag(self.as_str()), band.as_str())
+    }
+}
+
+/// Why `name` is not a valid queue name, if it is not
+const fn invalid_reason(name: &str) -> Option<&'static str> {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() {
+        return Some("must not be empty");
+    }
+    if bytes.len() > MAX_QUEUE_NAME_LEN {
+        return Some("must be at most 64 bytes");
+    }
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if !(b.is_ascii_alphanumeric() || b == b'_' || b == b'-') {
+            return Some("may only contain ASCII letters, digits, '_' and '-'");
+        }
+        i += 1;
+    }
+    None
+}
+
+impl TryFrom<&str> for QueueName {
+    type Error = RedisError;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        Self::new(name)
+    }
+}
+
+impl TryFrom<String> for QueueName {
+    type Error = RedisError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Self::new(name)
+    }
+}
+
+impl FromStr for QueueName {
+    type Err = RedisError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::new(name)
+    }
+}
+
+impl AsRef<str> for QueueName {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for QueueName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The part of `key` Redis Cluster hashes: the contents of the first `{...}`
+/// if non-empty, otherwise the whole key
+pub fn hash_tag(key: &str) -> &str {
+    key.find('{')
+        .and_then(|open| {
+            let rest = &key[open + 1..];
+            rest.find('}').map(|close| &rest[..close])
+        })
+        .filter(|tag| !tag.is_empty())
+        .unwrap_or(key)
+}
@@ -1,4 +1,36 @@
-use deadpool_redis::{redis::AsyncCommands, Config, Pool, Runtime};
+use deadpool_redis::redis::{AsyncCommands, IntoConnectionInfo};
+use deadpool_redis::{Config, Hook, Pool, Runtime};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+pub mod account_index;
+pub mod envelope;
+mod error;
+pub mod events;
+pub mod hedge;
+pub mod keys;
+pub mod memory;
+pub mod quarantine;
+pub mod sequence;
+pub mod token_bucket;
+pub mod wait_times;
+pub mod window;
+
+pub use account_index::AccountQueueItem;
+pub use envelope::{PoppedEnvelope, QueueEnvelope};
+pub use error::RedisError;
+pub use events::{EventBatch, EventPublisher, QueueEvent, QueueEventKind};
+pub use keys::{KeyLayout, KeySpace, QueueName};
+pub use memory::MemoryReport;
+pub use quarantine::QuarantinedMember;
+pub use service_config::PoolConfig;
+pub use token_bucket::{RateLimitStrategy, TokenBucket};
+pub use wait_times::{PriorityBand, WaitPercentiles};
+pub use window::{validate_window, FixedWindow, WindowAlignment, MAX_WINDOW_SECONDS};
 
 pub type RedisPool = Pool;
 pub type RedisConnection = deadpool_redis::Connection;
@@ -6,203 +38,1890 @@ pub type RedisConnection = deadpool_redis::Connection;
 pub const MAX_PRIORITY: i32 = 1000;
 pub const MIN_PRIORITY: i32 = -1000;
 
-#[derive(Debug, thiserror::Error)]
-pub enum RedisError {
-    #[error("Redis pool error: {0}")]
-    Pool(#[from] deadpool_redis::PoolError),
-    
-    #[error("Redis error: {0}")]
-    Redis(#[from] deadpool_redis::redis::RedisError),
-    
-    #[error("Serialization error: {0}")]
-    Serialization(#[from] serde_json::Error),
-    
-    #[error("Configuration error: {0}")]
-    Config(String),
+const COUNTER_TTL_SECONDS: i64 = 3600;
+/// Holds outlive a few worker passes but expire once an item stops being skipped
+const HOLD_TTL_SECONDS: i64 = 3600;
+/// Members ZSCAN is asked for per call when looking up transactions by id
+const SCAN_COUNT: usize = 500;
+/// Sliding window keys outlive their window by this much, so a key is never
+/// expired while a request it still counts is inside the window
+pub const SLIDING_WINDOW_TTL_BUFFER_MS: u64 = 1000;
+/// Rejected requests whose units could not be taken out of the window again
+pub const SLIDING_WINDOW_ROLLBACK_FAILURES_TOTAL: &str = "rate_limit_rollback_failures_total";
+/// Members per ZREM, and ZREMs per pipeline, when removing transactions
+const REMOVE_BATCH_SIZE: usize = 500;
+const REMOVE_PIPELINE_DEPTH: usize = 8;
+
+/// Connection settings applied on top of the Redis URL
+#[derive(Debug, Clone, Default)]
+pub struct RedisOptions {
+    /// Logical database to select. Must agree with the URL if it names one.
+    pub db: Option<i64>,
+    /// Upper bound on waiting for the reply to a single command
+    pub response_timeout: Option<Duration>,
+    /// Pool size and connect and wait timeouts. The pool keeps no idle
+    /// minimum and never closes idle connections, so `min_idle` and
+    /// `idle_timeout` are ignored.
+    pub pool: PoolConfig,
 }
 
 pub async fn create_pool(redis_url: &str) -> Result<RedisPool, RedisError> {
-    let cfg = Config::from_url(redis_url);
-    let pool = cfg.create_pool(Some(Runtime::Tokio1))
-        .map_err(|e| RedisError::Config(e.to_string()))?;
-    Ok(pool)
+    create_pool_with_options(redis_url, &RedisOptions::default()).await
 }
 
+pub async fn create_pool_with_options(redis_url: &str, options: &RedisOptions) -> Result<RedisPool, RedisError> {
+    let mut connection_info = redis_url
+        .into_connection_info()
+        .map_err(|e| RedisError::Config(format!("Invalid Redis URL: {}", e)))?;
+
+    if let Some(db) = options.db {
+        if db < 0 {
+            return Err(RedisError::Config(format!("Invalid Redis database index {}", db)));
+        }
+        if let Some(url_db) = url_db(redis_url)? {
+            if url_db != db {
+                return Err(RedisError::Config(format!(
+                    "Redis database index {} conflicts with database {} in the Redis URL",
+                    db, url_db
+                )));
+            }
+        }
+        connection_info.redis.db = db;
+    }
+
+    let mut pool_config = deadpool_redis::PoolConfig::default();
+    if let Some(max_size) = options.pool.max_size {
+        pool_config.max_size = max_size as usize;
+    }
+    pool_config.timeouts.create = options.pool.connect_timeout;
+    pool_config.timeouts.wait = options.pool.wait_timeout;
+    let mut cfg = Config::from_connection_info(connection_info);
+    cfg.pool = Some(pool_config);
+
+    let mut builder = cfg
+        .builder()
+        .map_err(|e| RedisError::Config(e.to_string()))?
+        .runtime(Runtime::Tokio1);
+    if let Some(response_timeout) = options.response_timeout {
+        builder = builder.post_create(Hook::sync_fn(move |conn, _| {
+            conn.set_response_timeout(response_timeout);
+            Ok(())
+        }));
+    }
+    builder.build().map_err(|e| RedisError::Config(e.to_string()))
+}
+
+/// Database named explicitly in a Redis URL, either as the path
+/// (`redis://host/2`) or, for Unix sockets, the `db` query parameter
+fn url_db(redis_url: &str) -> Result<Option<i64>, RedisError> {
+    let Some(url) = deadpool_redis::redis::parse_redis_url(redis_url) else {
+        return Ok(None);
+    };
+    let raw = match url.path().trim_matches('/') {
+        path if !path.is_empty() && !url.scheme().contains("unix") => Some(path.to_string()),
+        _ => url.query_pairs().find(|(key, _)| key == "db").map(|(_, value)| value.into_owned()),
+    };
+    raw.map(|raw| {
+        raw.parse()
+            .map_err(|_| RedisError::Config(format!("Invalid database {:?} in the Redis URL", raw)))
+    })
+    .transpose()
+}
+
+/// Current time in nanoseconds since the Unix epoch
+pub type NanosClock = Arc<dyn Fn() -> u128 + Send + Sync>;
+
 pub struct RateLimiter {
     pool: RedisPool,
+    now_nanos: NanosClock,
+    keys: KeySpace,
+    strategy: RateLimitStrategy,
 }
 
 impl RateLimiter {
     pub fn new(pool: RedisPool) -> Self {
-        Self { pool }
+        Self::new_with_strategy(pool, RateLimitStrategy::default())
+    }
+
+    /// Limiter whose `check_rate_limit`, `check_weighted_rate_limit` and
+    /// `peek` count requests with `strategy`
+    pub fn new_with_strategy(pool: RedisPool, strategy: RateLimitStrategy) -> Self {
+        Self {
+            pool,
+            now_nanos: Arc::new(unix_nanos),
+            keys: KeySpace::default(),
+            strategy,
+        }
+    }
+
+    pub fn strategy(&self) -> RateLimitStrategy {
+        self.strategy
     }
 
+    /// Name window keys in `keys`, e.g. with hash tags for Redis Cluster
+    pub fn with_keys(mut self, keys: impl Into<KeySpace>) -> Self {
+        self.keys = keys.into();
+        self
+    }
+
+    /// Read the time from `now_nanos` instead of the system clock
+    pub fn with_clock(mut self, now_nanos: impl Fn() -> u128 + Send + Sync + 'static) -> Self {
+        self.now_nanos = Arc::new(now_nanos);
+        self
+    }
+
+    /// Check of one request with the limiter's strategy. Windows outside
+    /// 1..=`MAX_WINDOW_SECONDS` fail with `RedisError::Config`, and a
+    /// `max_requests` of 0 denies every request; the same holds for every
+    /// check below.
+    ///
+    /// With a sliding window only allowed requests stay in the window, and
+    /// `reset_at` is when the oldest of them ages out, so retries while
+    /// limited do not push the reset back. With a token bucket see
+    /// `check_token_bucket`.
     pub async fn check_rate_limit(
         &self,
         key: &str,
         max_requests: u32,
         window_seconds: u64,
     ) -> Result<RateLimitResult, RedisError> {
+        self.check_weighted_rate_limit(key, 1, max_requests, window_seconds).await
+    }
+
+    /// Check of a request that uses `cost` (at least 1) of the
+    /// `max_requests` allowed per window, with the limiter's strategy
+    pub async fn check_weighted_rate_limit(
+        &self,
+        key: &str,
+        cost: u32,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> Result<RateLimitResult, RedisError> {
+        match self.strategy {
+            RateLimitStrategy::SlidingWindow => {
+                self.check_sliding_window(key, cost, max_requests, window_seconds).await
+            }
+            RateLimitStrategy::TokenBucket => {
+                self.check_token_bucket(key, cost, &TokenBucket::for_window(max_requests, window_seconds))
+                    .await
+            }
+        }
+    }
+
+    async fn check_sliding_window(
+        &self,
+        key: &str,
+        cost: u32,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> Result<RateLimitResult, RedisError> {
+        validate_window(window_seconds)?;
+        let now_nanos = (self.now_nanos)();
+        let window_nanos = window_seconds as u128 * 1_000_000_000;
+        let window_start_nanos = now_nanos.saturating_sub(window_nanos) as f64;
+        let current_nanos = now_nanos as f64;
+        let reset_at = ((now_nanos + window_nanos) / 1_000_000_000) as u64;
+        if max_requests == 0 {
+            return Ok(RateLimitResult::deny_all(reset_at));
+        }
         let mut conn = self.pool.get().await?;
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-        
-        let window_start = current_time - (window_seconds * 1000);
-        let window_start_nanos = (window_start * 1_000_000) as f64;
-        let current_time_nanos = (current_time * 1_000_000) as f64;
-        let rate_limit_key = format!("rate_limit:{}", key);
-        
-        // Remove old entries from sorted set
-        let _: i32 = deadpool_redis::redis::cmd("ZREMRANGEBYSCORE")
-            .arg(&rate_limit_key)
-            .arg(0.0)
-            .arg(window_start_nanos)
+        let rate_limit_key = self.keys.sliding_window(key);
+
+        // Add the request before counting so concurrent requests see each other.
+        // The score places it in time; the member only has to be unique, so
+        // requests in the same nanosecond, here or on another instance, all count.
+        // A request costing more than one takes one member per unit.
+        let member = sliding_window_member(now_nanos);
+        let members: Vec<(f64, String)> = (1..=cost.max(1))
+            .map(|unit| (current_nanos, weighted_member(&member, unit)))
+            .collect();
+        // The trim, add, expiry and count run as one transaction, so the count
+        // is this request's own: no concurrent request adds between the add
+        // and the count, and two requests never see the same `remaining`.
+        // Requests stamped later than this one, by a clock running ahead,
+        // are in the window too and are counted.
+        // The key expires whatever the outcome: rejected members are removed
+        // again below, but a removal that fails would otherwise leave them in
+        // a key that lives forever.
+        // i64 so limits above i32::MAX do not wrap negative and reject everything
+        let (count,): (i64,) = deadpool_redis::redis::pipe()
+            .atomic()
+            .zrembyscore(&rate_limit_key, 0.0, window_start_nanos)
+            .ignore()
+            .zadd_multiple(&rate_limit_key, &members)
+            .ignore()
+            .pexpire(&rate_limit_key, sliding_window_ttl_ms(window_seconds))
+            .ignore()
+            .zcount(&rate_limit_key, window_start_nanos, "+inf")
             .query_async(&mut *conn)
             .await?;
-        
-        // Add new request first with unique score to handle concurrent requests
-        // Use nanoseconds instead of milliseconds for better uniqueness
-        let current_nanos = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as f64;
-        let _: i32 = conn.zadd(&rate_limit_key, current_nanos, current_nanos).await?;
-        
-        // Count current requests in window (including the one we just added)
-        // Use the current nanos time that we just added to ensure consistency
-        let count: i32 = conn.zcount(&rate_limit_key, window_start_nanos, current_nanos).await?;
-        
-        if count > max_requests as i32 {
-            return Ok(RateLimitResult {
-                allowed: false,
-                remaining: 0,
-                reset_at: (current_time + (window_seconds * 1000)) / 1000,
-            });
-        }
-        let _: bool = conn.expire(&rate_limit_key, window_seconds as i64).await?;
-        
+        let allowed = count <= max_requests as i64;
+
+        // A rejected request takes nothing from the window, so a client that
+        // keeps retrying while limited is let in once its oldest request ages
+        // out. The removal is a second round trip: when it fails the rejected
+        // units count against the window until they age out, which is logged
+        // and counted, but the request itself is still answered as decided.
+        // The script check does both in one step and has no such gap.
+        let mut pipe = deadpool_redis::redis::pipe();
+        if !allowed {
+            let added: Vec<&String> = members.iter().map(|(_, member)| member).collect();
+            pipe.zrem(&rate_limit_key, added).ignore();
+        }
+        let oldest: Result<(Vec<(String, f64)>,), _> =
+            pipe.zrange_withscores(&rate_limit_key, 0, 0).query_async(&mut *conn).await;
+        let oldest_nanos = match oldest {
+            Ok((oldest,)) => oldest.first().map_or(now_nanos, |(_, score)| *score as u128),
+            Err(e) if !allowed => {
+                tracing::warn!(
+                    key = %rate_limit_key,
+                    cost,
+                    "Failed to take a rejected request out of its window: {}",
+                    e
+                );
+                metrics::counter!(SLIDING_WINDOW_ROLLBACK_FAILURES_TOTAL).increment(1);
+                now_nanos
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(RateLimitResult {
+            allowed,
+            remaining: if allowed { (max_requests as i64 - count).max(0) as u32 } else { 0 },
+            reset_at: sliding_window_reset_at(oldest_nanos, window_nanos),
+        })
+    }
+
+    /// Same sliding window as `check_rate_limit`, on the same keys, run as
+    /// one script so the trim, add, count and expiry take a single round
+    /// trip and no other client sees the window half updated. Always a
+    /// sliding window, whatever the limiter's strategy.
+    pub async fn check_rate_limit_script(
+        &self,
+        key: &str,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> Result<RateLimitResult, RedisError> {
+        self.check_weighted_rate_limit_script(key, 1, max_requests, window_seconds)
+            .await
+    }
+
+    /// `check_weighted_rate_limit` as a single script
+    pub async fn check_weighted_rate_limit_script(
+        &self,
+        key: &str,
+        cost: u32,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> Result<RateLimitResult, RedisError> {
+        validate_window(window_seconds)?;
+        let now_nanos = (self.now_nanos)();
+        let window_nanos = window_seconds as u128 * 1_000_000_000;
+        let reset_at = ((now_nanos + window_nanos) / 1_000_000_000) as u64;
+        if max_requests == 0 {
+            return Ok(RateLimitResult::deny_all(reset_at));
+        }
+        let mut conn = self.pool.get().await?;
+
+        // Rejected units are taken out again before the script returns
+        let (count, oldest): (i64, Option<f64>) = deadpool_redis::redis::Script::new(
+            r"
+            redis.call('ZREMRANGEBYSCORE', KEYS[1], 0, ARGV[1])
+            redis.call('ZADD', KEYS[1], ARGV[2], ARGV[3])
+            for unit = 2, tonumber(ARGV[6]) do
+                redis.call('ZADD', KEYS[1], ARGV[2], ARGV[3] .. ':' .. unit)
+            end
+            local count = redis.call('ZCOUNT', KEYS[1], ARGV[1], '+inf')
+            if count > tonumber(ARGV[4]) then
+                redis.call('ZREM', KEYS[1], ARGV[3])
+                for unit = 2, tonumber(ARGV[6]) do
+                    redis.call('ZREM', KEYS[1], ARGV[3] .. ':' .. unit)
+                end
+            end
+            redis.call('PEXPIRE', KEYS[1], ARGV[5])
+            local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+            return {count, oldest[2]}
+            ",
+        )
+        .key(self.keys.sliding_window(key))
+        .arg(now_nanos.saturating_sub(window_nanos) as f64)
+        .arg(now_nanos as f64)
+        .arg(sliding_window_member(now_nanos))
+        .arg(max_requests)
+        .arg(sliding_window_ttl_ms(window_seconds))
+        .arg(cost.max(1))
+        .invoke_async(&mut *conn)
+        .await?;
+        let allowed = count <= max_requests as i64;
+
+        Ok(RateLimitResult {
+            allowed,
+            remaining: if allowed { (max_requests as i64 - count).max(0) as u32 } else { 0 },
+            reset_at: sliding_window_reset_at(oldest.map_or(now_nanos, |score| score as u128), window_nanos),
+        })
+    }
+
+    /// Token bucket check of a request taking `cost` (at least 1) tokens.
+    /// The refill, take and expiry run as one script, so concurrent checks
+    /// never spend the same token twice. A bucket seen for the first time
+    /// starts full.
+    ///
+    /// Rejected requests take nothing. `remaining` is the whole tokens left,
+    /// and `reset_at` is when the next token arrives: for a rejected request,
+    /// when the bucket holds enough for it, so a client retrying then gets in.
+    pub async fn check_token_bucket(
+        &self,
+        key: &str,
+        cost: u32,
+        bucket: &TokenBucket,
+    ) -> Result<RateLimitResult, RedisError> {
+        bucket.validate()?;
+        let now_ms = ((self.now_nanos)() / 1_000_000) as u64;
+        let cost = cost.max(1);
+        if bucket.burst == 0 {
+            return Ok(RateLimitResult::deny_all(now_ms.div_ceil(1000) + bucket.refill_seconds));
+        }
+        let mut conn = self.pool.get().await?;
+
+        // Lua numbers are doubles, so tokens come back as a string rather
+        // than being truncated to an integer reply
+        let (allowed, tokens): (i64, String) = deadpool_redis::redis::Script::new(
+            r"
+            local burst = tonumber(ARGV[1])
+            local now = tonumber(ARGV[4])
+            local cost = tonumber(ARGV[5])
+            local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'last_refill')
+            local tokens = tonumber(bucket[1]) or burst
+            local last_refill = tonumber(bucket[2]) or now
+            if now > last_refill then
+                tokens = math.min(burst, tokens + (now - last_refill) * tonumber(ARGV[2]) / tonumber(ARGV[3]))
+                tokens = math.floor(tokens * 1000000 + 0.5) / 1000000
+                last_refill = now
+            end
+            local allowed = 0
+            if tokens >= cost then
+                tokens = tokens - cost
+                allowed = 1
+            end
+            redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'last_refill', string.format('%d', last_refill))
+            redis.call('PEXPIRE', KEYS[1], ARGV[6])
+            return {allowed, tostring(tokens)}
+            ",
+        )
+        .key(self.keys.token_bucket(key))
+        .arg(bucket.burst)
+        .arg(bucket.refill)
+        .arg(bucket.refill_seconds * 1000)
+        .arg(now_ms)
+        .arg(cost)
+        .arg(bucket.fill_ms() + SLIDING_WINDOW_TTL_BUFFER_MS)
+        .invoke_async(&mut *conn)
+        .await?;
+        let tokens: f64 = tokens
+            .parse()
+            .map_err(|_| RedisError::Config(format!("Unreadable token count {:?}", tokens)))?;
+        let allowed = allowed == 1;
+
+        Ok(RateLimitResult {
+            allowed,
+            remaining: tokens.floor() as u32,
+            reset_at: if allowed {
+                bucket.next_token_at(tokens, now_ms)
+            } else {
+                bucket.available_at(tokens, cost as f64, now_ms)
+            },
+        })
+    }
+
+    /// Check a limit in a named scope (e.g. "admin"). Its windows are keyed
+    /// by `KeySpace::in_limit_scope`, so no customer account id, whatever
+    /// it contains, shares one with it.
+    pub async fn check_scoped_rate_limit(
+        &self,
+        scope: &str,
+        key: &str,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> Result<RateLimitResult, RedisError> {
+        let scoped = Self {
+            pool: self.pool.clone(),
+            now_nanos: self.now_nanos.clone(),
+            keys: self.keys.in_limit_scope(scope),
+            strategy: self.strategy,
+        };
+        scoped.check_rate_limit(key, max_requests, window_seconds).await
+    }
+
+    /// Fixed window counter. With `WindowAlignment::Jittered` each key's
+    /// boundaries are offset by a stable amount so resets are spread out.
+    pub async fn check_fixed_window(
+        &self,
+        key: &str,
+        max_requests: u32,
+        window_seconds: u64,
+        alignment: WindowAlignment,
+    ) -> Result<RateLimitResult, RedisError> {
+        self.check_weighted_fixed_window(key, 1, max_requests, window_seconds, alignment)
+            .await
+    }
+
+    /// Fixed window check of a request that uses `cost` (at least 1) of the
+    /// `max_requests` allowed per window
+    pub async fn check_weighted_fixed_window(
+        &self,
+        key: &str,
+        cost: u32,
+        max_requests: u32,
+        window_seconds: u64,
+        alignment: WindowAlignment,
+    ) -> Result<RateLimitResult, RedisError> {
+        validate_window(window_seconds)?;
+        let window = FixedWindow::for_key(key, unix_seconds(), window_seconds, alignment);
+        if max_requests == 0 {
+            return Ok(RateLimitResult::deny_all(window.reset_at));
+        }
+        let mut conn = self.pool.get().await?;
+        let counter_key = self.keys.fixed_window(key, window.index);
+
+        let (count,): (u64,) = deadpool_redis::redis::pipe()
+            .atomic()
+            .incr(&counter_key, cost.max(1))
+            .expire(&counter_key, window_seconds as i64 + 1)
+            .ignore()
+            .query_async(&mut *conn)
+            .await?;
+
         Ok(RateLimitResult {
-            allowed: true,
-            remaining: (max_requests as i32 - count).max(0) as u32,
-            reset_at: (current_time + (window_seconds * 1000)) / 1000,
+            allowed: count <= max_requests as u64,
+            remaining: (max_requests as u64).saturating_sub(count) as u32,
+            reset_at: window.reset_at,
         })
     }
+
+    /// Give every limiter key without an expiry one of `ttl_seconds`,
+    /// which should be at least the longest window in use.
+    ///
+    /// Checks expire their keys on every call, so only keys written before
+    /// that, or by hand, need this. The TTL is read and set atomically per
+    /// key, so a key a check expires meanwhile keeps its own TTL.
+    pub async fn repair_missing_expiry(&self, ttl_seconds: u64) -> Result<ExpiryRepair, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let mut keys: Vec<String> = Vec::new();
+        let mut iter = conn.scan_match::<_, String>(self.keys.rate_limit_pattern()).await?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        drop(iter);
+
+        let script = deadpool_redis::redis::Script::new(
+            r"
+            if redis.call('TTL', KEYS[1]) == -1 then
+                return redis.call('EXPIRE', KEYS[1], ARGV[1])
+            end
+            return 0
+            ",
+        );
+        let mut repair = ExpiryRepair {
+            scanned: keys.len() as u64,
+            repaired: 0,
+        };
+        for key in &keys {
+            let repaired: i64 = script.key(key).arg(ttl_seconds).invoke_async(&mut *conn).await?;
+            repair.repaired += repaired as u64;
+        }
+        Ok(repair)
+    }
+
+    /// Requests recorded in the trailing sliding window, without recording one
+    pub async fn sliding_window_usage(&self, key: &str, window_seconds: u64) -> Result<u64, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let window_start_nanos = (self.now_nanos)().saturating_sub(window_seconds as u128 * 1_000_000_000) as f64;
+        let count: u64 = conn.zcount(self.keys.sliding_window(key), window_start_nanos, "+inf").await?;
+        Ok(count)
+    }
+
+    /// Requests counted in the current fixed window, without recording one
+    pub async fn fixed_window_usage(
+        &self,
+        key: &str,
+        window_seconds: u64,
+        alignment: WindowAlignment,
+    ) -> Result<u64, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let window = FixedWindow::for_key(key, unix_seconds(), window_seconds, alignment);
+        let count: Option<u64> = conn.get(self.keys.fixed_window(key, window.index)).await?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// What `check_rate_limit` would answer for the next request, read
+    /// without adding an entry or taking a token, so looking uses none of
+    /// the quota. Sliding window entries that aged out are counted out
+    /// rather than trimmed.
+    pub async fn peek(&self, key: &str, max_requests: u32, window_seconds: u64) -> Result<RateLimitResult, RedisError> {
+        if self.strategy == RateLimitStrategy::TokenBucket {
+            return self.peek_token_bucket(key, &TokenBucket::for_window(max_requests, window_seconds)).await;
+        }
+        validate_window(window_seconds)?;
+        let now_nanos = (self.now_nanos)();
+        let window_nanos = window_seconds as u128 * 1_000_000_000;
+        let window_start_nanos = now_nanos.saturating_sub(window_nanos) as f64;
+        let rate_limit_key = self.keys.sliding_window(key);
+        let mut conn = self.pool.get().await?;
+        let (count, oldest): (i64, Vec<(String, f64)>) = deadpool_redis::redis::pipe()
+            .zcount(&rate_limit_key, window_start_nanos, "+inf")
+            .zrangebyscore_limit_withscores(&rate_limit_key, window_start_nanos, "+inf", 0, 1)
+            .query_async(&mut *conn)
+            .await?;
+        let oldest_nanos = oldest.first().map_or(now_nanos, |(_, score)| *score as u128);
+
+        Ok(RateLimitResult {
+            allowed: count < max_requests as i64,
+            remaining: (max_requests as i64 - count).max(0) as u32,
+            reset_at: sliding_window_reset_at(oldest_nanos, window_nanos),
+        })
+    }
+
+    /// `peek` for a token bucket: the bucket is read and refilled in memory,
+    /// and left as it was
+    pub async fn peek_token_bucket(&self, key: &str, bucket: &TokenBucket) -> Result<RateLimitResult, RedisError> {
+        bucket.validate()?;
+        let now_ms = ((self.now_nanos)() / 1_000_000) as u64;
+        if bucket.burst == 0 {
+            return Ok(RateLimitResult::deny_all(now_ms.div_ceil(1000) + bucket.refill_seconds));
+        }
+        let mut conn = self.pool.get().await?;
+        let (tokens, last_refill): (Option<f64>, Option<u64>) =
+            conn.hget(self.keys.token_bucket(key), &["tokens", "last_refill"]).await?;
+        let tokens = match (tokens, last_refill) {
+            (Some(tokens), Some(last_refill)) => bucket.refilled(tokens, last_refill, now_ms),
+            _ => bucket.burst as f64,
+        };
+
+        Ok(RateLimitResult {
+            allowed: tokens >= 1.0,
+            remaining: tokens.floor() as u32,
+            reset_at: bucket.next_token_at(tokens, now_ms),
+        })
+    }
+
+    /// `peek` for the fixed window counter
+    pub async fn peek_fixed_window(
+        &self,
+        key: &str,
+        max_requests: u32,
+        window_seconds: u64,
+        alignment: WindowAlignment,
+    ) -> Result<RateLimitResult, RedisError> {
+        validate_window(window_seconds)?;
+        let window = FixedWindow::for_key(key, unix_seconds(), window_seconds, alignment);
+        let mut conn = self.pool.get().await?;
+        let count: Option<u64> = conn.get(self.keys.fixed_window(key, window.index)).await?;
+        let count = count.unwrap_or(0);
+
+        Ok(RateLimitResult {
+            allowed: count < max_requests as u64,
+            remaining: (max_requests as u64).saturating_sub(count) as u32,
+            reset_at: window.reset_at,
+        })
+    }
+}
+
+/// Outcome of `RateLimiter::repair_missing_expiry`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ExpiryRepair {
+    /// Rate limit keys found
+    pub scanned: u64,
+    /// Keys that had no expiry and were given one
+    pub repaired: u64,
 }
 
 #[derive(Debug, Clone)]
 pub struct RateLimitResult {
     pub allowed: bool,
+    /// Requests left in the window, 0 once the count reaches the limit
     pub remaining: u32,
+    /// When the window resets, in Unix epoch seconds. Never milliseconds:
+    /// clients compare it against their own clock in seconds.
     pub reset_at: u64,
 }
 
+impl RateLimitResult {
+    /// Answer to a check against a limit of zero, which blocks everything.
+    /// Nothing is recorded, so the window has no key to expire.
+    pub fn deny_all(reset_at: u64) -> Self {
+        Self {
+            allowed: false,
+            remaining: 0,
+            reset_at,
+        }
+    }
+
+    /// Whole seconds from `now` (Unix seconds) until a rejected request can
+    /// be retried: at least 1, since a rejection never means retry at once.
+    /// `reset_at` is already rounded up, so this is too.
+    pub fn retry_after_seconds(&self, now: u64) -> u64 {
+        self.reset_at.saturating_sub(now).max(1)
+    }
+}
+
 pub struct QueueManager {
     pool: RedisPool,
+    hedge_budget: Option<Duration>,
+    keys: KeySpace,
 }
 
 impl QueueManager {
     pub fn new(pool: RedisPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            hedge_budget: None,
+            keys: KeySpace::default(),
+        }
+    }
+
+    /// Name queue and account keys in `keys`, e.g. with hash tags for Redis Cluster
+    pub fn with_keys(mut self, keys: impl Into<KeySpace>) -> Self {
+        self.keys = keys.into();
+        self
+    }
+
+    /// Hedge idempotent reads (ZRANK, ZCOUNT, LLEN) that take longer than `budget`
+    pub fn with_hedging(mut self, budget: Duration) -> Self {
+        self.hedge_budget = Some(budget);
+        self
+    }
+
+    /// Run a read-only command on its own pooled connection, hedged when enabled
+    async fn read<T, F, Fut>(&self, command: &'static str, read: F) -> Result<T, RedisError>
+    where
+        F: Fn(RedisConnection) -> Fut,
+        Fut: Future<Output = Result<T, RedisError>>,
+    {
+        let attempt = || async {
+            let conn = self.pool.get().await?;
+            read(conn).await
+        };
+        match self.hedge_budget {
+            Some(budget) => hedge::run(command, budget, attempt).await,
+            None => attempt().await,
+        }
     }
 
-    pub async fn enqueue(&self, queue_name: &str, data: &str) -> Result<i64, RedisError> {
+    /// Append to the list queue, returning the item's 1-indexed position.
+    /// RPUSH answers the list's length after the push and consumed items
+    /// have been popped off its head, so that counts only the items still
+    /// ahead of this one.
+    pub async fn enqueue(&self, queue_name: &QueueName, data: &str) -> Result<i64, RedisError> {
         let mut conn = self.pool.get().await?;
-        let position: i64 = conn.rpush(queue_name, data).await?;
+        let position: i64 = conn.rpush(queue_name.list_key(&self.keys), data).await?;
+        increment_counter(&mut conn, &self.keys, queue_name, QueueCounter::Enqueued, 1).await?;
         Ok(position)
     }
 
     /// Enqueue with priority - higher priority number = processed first
-    pub async fn enqueue_with_priority(&self, queue_name: &str, data: &str, priority: i32) -> Result<i64, RedisError> {
-        let mut conn = self.pool.get().await?;
-        let priority_queue_name = format!("{}_priority", queue_name);
-        
-        // Use timestamp in nanoseconds for tie-breaking (FIFO within same priority)
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as f64;
-        
-        // Score calculation: higher priority = lower score (processed first)
-        // Use timestamp for tie-breaking within same priority level
-        let score = (1000 - priority) as f64 + (timestamp / 1e15); // Timestamp scaled to avoid affecting priority
-        
-        // Add to priority queue (sorted set)
-        let _: i32 = conn.zadd(&priority_queue_name, data, score).await?;
+    pub async fn enqueue_with_priority(&self, queue_name: &QueueName, data: &str, priority: i32) -> Result<i64, RedisError> {
+        self.add_with_priority(queue_name, data, priority).await?;
+        self.priority_position(queue_name, data).await
+    }
 
-        // Get current position in priority order
-        let position = self.get_priority_position(&priority_queue_name, data).await?;
-        Ok(position)
+    /// Add to the priority queue without looking up the resulting position.
+    ///
+    /// The FIFO tie-breaker is a per-queue sequence INCRed in the same
+    /// script as the ZADD, so order within a priority is the order adds
+    /// reached Redis whatever the clocks of the enqueuing instances say.
+    /// See `sequence` for how it is encoded into the score.
+    ///
+    /// Envelopes are added to their account's index in the same script;
+    /// see `account_index`.
+    pub async fn add_with_priority(&self, queue_name: &QueueName, data: &str, priority: i32) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let counter = queue_name.counter_key(&self.keys, QueueCounter::Enqueued, unix_seconds() / 60);
+        let script = deadpool_redis::redis::Script::new(
+            r"
+            local limit = tonumber(ARGV[3])
+            local sequence = redis.call('INCR', KEYS[2])
+            if sequence >= limit then
+                if redis.call('ZCARD', KEYS[1]) == 0 then
+                    sequence = 1
+                    redis.call('SET', KEYS[2], sequence)
+                else
+                    sequence = limit - 1
+                end
+            end
+            local score = (tonumber(ARGV[4]) - tonumber(ARGV[2])) + sequence / limit
+            redis.call('ZADD', KEYS[1], score, ARGV[1])
+            redis.call('INCR', KEYS[3])
+            redis.call('EXPIRE', KEYS[3], ARGV[5])
+            if ARGV[6] then
+                local now = redis.call('TIME')
+                redis.call('ZADD', KEYS[4], score, ARGV[6])
+                redis.call('HSET', KEYS[5], ARGV[6], now[1] .. string.format('%03d', math.floor(now[2] / 1000)))
+            end
+            ",
+        );
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(queue_name.priority_key(&self.keys))
+            .key(queue_name.sequence_key(&self.keys))
+            .key(counter)
+            .arg(data)
+            .arg(priority.clamp(MIN_PRIORITY, MAX_PRIORITY))
+            .arg(sequence::SEQUENCE_LIMIT)
+            .arg(sequence::SEQUENCE_SCORE_BASE)
+            .arg(COUNTER_TTL_SECONDS);
+        if let Some((account_id, transaction_id)) = account_index::index_entry(data) {
+            invocation
+                .key(queue_name.account_index_key(&self.keys, &account_id))
+                .key(queue_name.enqueued_at_key(&self.keys))
+                .arg(transaction_id);
+        }
+        let _: () = invocation.invoke_async(&mut *conn).await?;
+        Ok(())
     }
 
-    /// Get position in priority queue (1-indexed)
-    async fn get_priority_position(&self, priority_queue_name: &str, data: &str) -> Result<i64, RedisError> {
+    /// Up to `limit` of an account's members of the priority queue, in the
+    /// order they will be popped, capped at `MAX_ACCOUNT_ITEMS`. Reads the
+    /// account's index, so the cost depends on the items listed rather than
+    /// the length of the queue. Index entries whose member has already left
+    /// the queue are dropped, so fewer than `limit` items may come back
+    /// while the account has more queued.
+    pub async fn list_account_items(
+        &self,
+        queue_name: &QueueName,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<Vec<AccountQueueItem>, RedisError> {
+        let limit = limit.min(account_index::MAX_ACCOUNT_ITEMS);
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
         let mut conn = self.pool.get().await?;
-        
+
+        // A listed id is live if a member at its score is its envelope
+        let (now_ms, entries): (u64, Vec<account_index::ListedEntry>) = deadpool_redis::redis::Script::new(
+            r#"
+            local now = redis.call('TIME')
+            local items = {}
+            local index = redis.call('ZRANGE', KEYS[2], 0, tonumber(ARGV[1]) - 1, 'WITHSCORES')
+            for i = 1, #index, 2 do
+                local id, score = index[i], index[i + 1]
+                local prefix = '{"transaction_id":' .. cjson.encode(id) .. ','
+                local live = false
+                for _, member in ipairs(redis.call('ZRANGEBYSCORE', KEYS[1], score, score)) do
+                    if string.sub(member, 1, #prefix) == prefix then
+                        live = true
+                        break
+                    end
+                end
+                if live then
+                    local ahead = redis.call('ZCOUNT', KEYS[1], '-inf', '(' .. score)
+                    items[#items + 1] = {id, score, ahead, redis.call('HGET', KEYS[3], id)}
+                else
+                    redis.call('ZREM', KEYS[2], id)
+                    redis.call('HDEL', KEYS[3], id)
+                end
+            end
+            return {now[1] .. string.format('%03d', math.floor(now[2] / 1000)), items}
+            "#,
+        )
+        .key(queue_name.priority_key(&self.keys))
+        .key(queue_name.account_index_key(&self.keys, account_id))
+        .key(queue_name.enqueued_at_key(&self.keys))
+        .arg(limit)
+        .invoke_async(&mut *conn)
+        .await?;
+
+        Ok(entries
+            .into_iter()
+            .map(|(transaction_id, score, ahead, enqueued_at_ms)| AccountQueueItem {
+                transaction_id,
+                rank: ahead + 1,
+                priority: sequence::priority_of(score),
+                enqueued_at_ms,
+                age_ms: enqueued_at_ms.map(|enqueued_at| now_ms.saturating_sub(enqueued_at)),
+            })
+            .collect())
+    }
+
+    /// Take popped or removed members out of their accounts' indexes
+    async fn unindex(
+        &self,
+        conn: &mut RedisConnection,
+        queue_name: &QueueName,
+        members: &[String],
+    ) -> Result<(), RedisError> {
+        let enqueued_at_key = queue_name.enqueued_at_key(&self.keys);
+        let mut pipe = deadpool_redis::redis::pipe();
+        for member in members {
+            let Some((account_id, transaction_id)) = account_index::index_entry(member) else {
+                continue;
+            };
+            pipe.zrem(queue_name.account_index_key(&self.keys, &account_id), &transaction_id)
+                .ignore()
+                .hdel(&enqueued_at_key, &transaction_id)
+                .ignore();
+        }
+        let _: () = pipe.query_async(&mut **conn).await?;
+        Ok(())
+    }
+
+    /// Position of `data` in the priority queue (1-indexed)
+    pub async fn priority_position(&self, queue_name: &QueueName, data: &str) -> Result<i64, RedisError> {
+        let priority_queue_name = queue_name.priority_key(&self.keys);
+        let priority_queue_name = priority_queue_name.as_str();
         // Get rank (0-indexed) and convert to 1-indexed position
-        let rank: Option<i64> = conn.zrank(priority_queue_name, data).await?;
+        let rank: Option<i64> = self
+            .read("zrank", move |mut conn| async move {
+                Ok(conn.zrank(priority_queue_name, data).await?)
+            })
+            .await?;
         match rank {
             Some(r) => Ok(r + 1),
             None => Ok(1), // Fallback if not found
         }
     }
 
+    /// Position of `data` among its account's members of the priority queue
+    /// (1-indexed), read from the account's index; `None` for members that
+    /// are not envelopes or are not indexed. Entries an interrupted pop left
+    /// behind count until the account is next listed.
+    pub async fn account_position(&self, queue_name: &QueueName, data: &str) -> Result<Option<i64>, RedisError> {
+        let Some((account_id, transaction_id)) = account_index::index_entry(data) else {
+            return Ok(None);
+        };
+        let index_key = queue_name.account_index_key(&self.keys, &account_id);
+        let (index_key, transaction_id) = (index_key.as_str(), transaction_id.as_str());
+        let rank: Option<i64> = self
+            .read("zrank", move |mut conn| async move {
+                Ok(conn.zrank(index_key, transaction_id).await?)
+            })
+            .await?;
+        Ok(rank.map(|rank| rank + 1))
+    }
+
+    /// Position of an account's transaction in the priority queue
+    /// (1-indexed), or `None` once it has left the queue. The member's score
+    /// is read from the account's index, so this takes one script call
+    /// whatever the length of the queue.
+    pub async fn transaction_position(
+        &self,
+        queue_name: &QueueName,
+        account_id: &str,
+        transaction_id: &str,
+    ) -> Result<Option<i64>, RedisError> {
+        let mut conn = self.pool.get().await?;
+        // The score comes back as text, so it is matched exactly. Members
+        // rarely share a score, so the member is then picked out by its id
+        // among the few at it. An index entry whose member an interrupted
+        // pop already took finds none and counts as gone.
+        let position: Option<i64> = deadpool_redis::redis::Script::new(
+            r"
+            local score = redis.call('ZSCORE', KEYS[1], ARGV[1])
+            if not score then
+                return false
+            end
+            for _, member in ipairs(redis.call('ZRANGEBYSCORE', KEYS[2], score, score)) do
+                if string.sub(member, 1, #ARGV[2]) == ARGV[2] then
+                    return redis.call('ZRANK', KEYS[2], member) + 1
+                end
+            end
+            return false
+            ",
+        )
+        .key(queue_name.account_index_key(&self.keys, account_id))
+        .key(queue_name.priority_key(&self.keys))
+        .arg(transaction_id)
+        .arg(envelope::member_prefix(transaction_id))
+        .invoke_async(&mut *conn)
+        .await?;
+        Ok(position)
+    }
+
     /// Get total count of items in priority queue
-    pub async fn priority_queue_length(&self, queue_name: &str) -> Result<i64, RedisError> {
+    pub async fn priority_queue_length(&self, queue_name: &QueueName) -> Result<i64, RedisError> {
         let mut conn = self.pool.get().await?;
-        let priority_queue_name = format!("{}_priority", queue_name);
+        let priority_queue_name = queue_name.priority_key(&self.keys);
         let length: i64 = conn.zcard(&priority_queue_name).await?;
         Ok(length)
     }
 
+    /// Estimate how much of the priority queue's memory each account holds
+    /// from a random sample of up to `sample` members, capped at
+    /// `MAX_MEMORY_SAMPLE`. Only the start of each sampled member leaves
+    /// Redis, so large payloads are not transferred.
+    pub async fn memory_report(&self, queue_name: &QueueName, sample: usize) -> Result<MemoryReport, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let priority_queue_name = queue_name.priority_key(&self.keys);
+        let sample = sample.clamp(1, memory::MAX_MEMORY_SAMPLE);
+
+        // Prefixes may end mid-character, so they come back as bytes
+        let members: Vec<(u64, Vec<u8>)> = deadpool_redis::redis::Script::new(
+            r"
+            local sampled = {}
+            for i, member in ipairs(redis.call('ZRANDMEMBER', KEYS[1], ARGV[1])) do
+                sampled[i] = {string.len(member), string.sub(member, 1, tonumber(ARGV[2]))}
+            end
+            return sampled
+            ",
+        )
+        .key(&priority_queue_name)
+        .arg(sample)
+        .arg(memory::MEMBER_PREFIX_BYTES)
+        .invoke_async(&mut *conn)
+        .await?;
+
+        let (total_members, total_bytes): (u64, Option<u64>) = deadpool_redis::redis::pipe()
+            .zcard(&priority_queue_name)
+            .cmd("MEMORY")
+            .arg("USAGE")
+            .arg(&priority_queue_name)
+            .arg("SAMPLES")
+            .arg(sample)
+            .query_async(&mut *conn)
+            .await?;
+
+        let sample: Vec<memory::SampledMember> = members
+            .into_iter()
+            .map(|(bytes, prefix)| memory::SampledMember {
+                bytes,
+                account_id: envelope::account_id_of(&String::from_utf8_lossy(&prefix)),
+            })
+            .collect();
+        Ok(MemoryReport::from_sample(
+            &priority_queue_name,
+            total_members,
+            total_bytes.unwrap_or(0),
+            &sample,
+        ))
+    }
+
     /// Dequeue next item by priority (highest priority first)
-    pub async fn dequeue_by_priority(&self, queue_name: &str) -> Result<Option<String>, RedisError> {
+    pub async fn dequeue_by_priority(&self, queue_name: &QueueName) -> Result<Option<String>, RedisError> {
+        let popped = self.dequeue_batch(queue_name, 1).await?;
+        Ok(popped.into_iter().next().map(|(member, _)| member))
+    }
+
+    /// Pop up to `count` items by priority in one ZPOPMIN, with their
+    /// scores, in the order they are due. Fewer come back when the queue
+    /// holds fewer, and none when it is empty.
+    ///
+    /// Once popped the items exist nowhere else, so they are returned even
+    /// when counting them or dropping them from the account index fails
+    /// afterwards; that only leaves a low dequeue count or a stale index
+    /// entry, and is logged.
+    pub async fn dequeue_batch(&self, queue_name: &QueueName, count: usize) -> Result<Vec<(String, f64)>, RedisError> {
+        if count == 0 {
+            return Ok(Vec::new());
+        }
         let mut conn = self.pool.get().await?;
-        let priority_queue_name = format!("{}_priority", queue_name);
-        
-        // Pop item with lowest score (highest priority)
-        let result: Vec<String> = conn.zpopmin(&priority_queue_name, 1).await?;
-        
-        if result.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(result[0].clone()))
+        // Replies alternate member and score, read here as pairs
+        let popped: Vec<(String, f64)> = conn.zpopmin(queue_name.priority_key(&self.keys), count as isize).await?;
+        if popped.is_empty() {
+            return Ok(popped);
+        }
+        self.count_dequeued(&mut conn, queue_name, popped.len()).await;
+        let members: Vec<String> = popped.iter().map(|(member, _)| member.clone()).collect();
+        if let Err(e) = self.unindex(&mut conn, queue_name, &members).await {
+            tracing::warn!(queue = %queue_name, count = members.len(), "Failed to unindex dequeued items: {}", e);
+        }
+        Ok(popped)
+    }
+
+    /// Count `count` popped items as dequeued. They cannot be put back, so a
+    /// failure is logged rather than returned.
+    async fn count_dequeued(&self, conn: &mut RedisConnection, queue_name: &QueueName, count: usize) {
+        if let Err(e) = increment_counter(conn, &self.keys, queue_name, QueueCounter::Dequeued, count as u64).await {
+            tracing::warn!(queue = %queue_name, count, "Failed to count dequeued items: {}", e);
+        }
+    }
+
+    /// Dequeue the next envelope by priority, decompressing its payload.
+    /// Members that cannot be decoded are quarantined and skipped, so one bad
+    /// member never blocks the ones behind it.
+    pub async fn dequeue_envelope(&self, queue_name: &QueueName) -> Result<Option<QueueEnvelope>, RedisError> {
+        loop {
+            let popped = self.dequeue_batch(queue_name, 1).await?;
+            if popped.is_empty() {
+                return Ok(None);
+            }
+            if let Some(popped) = self.decode_popped(queue_name, popped).await?.pop() {
+                return Ok(Some(popped.envelope));
+            }
+        }
+    }
+
+    /// Pop up to `count` envelopes by priority in one ZPOPMIN, decompressing
+    /// their payloads, each with the member and score it was stored under.
+    /// Members that cannot be decoded are quarantined and left out, so fewer
+    /// than `count` may come back while the queue has more.
+    pub async fn dequeue_envelopes(
+        &self,
+        queue_name: &QueueName,
+        count: usize,
+    ) -> Result<Vec<PoppedEnvelope>, RedisError> {
+        let popped = self.dequeue_batch(queue_name, count).await?;
+        self.decode_popped(queue_name, popped).await
+    }
+
+    /// Decode popped members, quarantining the ones that cannot be.
+    ///
+    /// When a quarantine fails the whole batch is put back and the error
+    /// returned, so nothing popped is dropped. Members that cannot be put
+    /// back either are logged, as the log is then the only copy left.
+    async fn decode_popped(
+        &self,
+        queue_name: &QueueName,
+        popped: Vec<(String, f64)>,
+    ) -> Result<Vec<PoppedEnvelope>, RedisError> {
+        let mut envelopes = Vec::with_capacity(popped.len());
+        let mut failed = None;
+        let mut unhandled = Vec::new();
+        for (member, score) in popped {
+            let error = match QueueEnvelope::decode(&member) {
+                Ok(envelope) => {
+                    envelopes.push(PoppedEnvelope { envelope, member, score });
+                    continue;
+                }
+                Err(e) => e.to_string(),
+            };
+            tracing::warn!(queue = %queue_name, %error, "Quarantining undecodable queue member");
+            if let Err(e) = self.quarantine(queue_name, &member, &error).await {
+                tracing::warn!(queue = %queue_name, "Failed to quarantine queue member: {}", e);
+                failed.get_or_insert(e);
+                unhandled.push((member, score));
+            }
+        }
+        let Some(error) = failed else {
+            return Ok(envelopes);
+        };
+        unhandled.extend(envelopes.into_iter().map(|popped| (popped.member, popped.score)));
+        if let Err(e) = self.restore(queue_name, &unhandled).await {
+            for (member, _) in &unhandled {
+                tracing::error!(queue = %queue_name, member, "Failed to put back queue member: {}", e);
+            }
+        }
+        Err(error)
+    }
+
+    /// Put popped members back in the priority queue at the scores they
+    /// were popped with, so they keep their place, and back in their
+    /// accounts' indexes. When they were enqueued is not kept once they are
+    /// popped, so `list_account_items` reports no age for restored members.
+    pub async fn restore(&self, queue_name: &QueueName, members: &[(String, f64)]) -> Result<(), RedisError> {
+        if members.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.pool.get().await?;
+        let mut pipe = deadpool_redis::redis::pipe();
+        pipe.atomic();
+        for (member, score) in members {
+            pipe.zadd(queue_name.priority_key(&self.keys), member, *score).ignore();
+            if let Some((account_id, transaction_id)) = account_index::index_entry(member) {
+                pipe.zadd(queue_name.account_index_key(&self.keys, &account_id), transaction_id, *score)
+                    .ignore();
+            }
+        }
+        let _: () = pipe.query_async(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Move a member that cannot be processed to the queue's quarantine list
+    pub async fn quarantine(
+        &self,
+        queue_name: &QueueName,
+        member: &str,
+        error: &str,
+    ) -> Result<QuarantinedMember, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let entry = QuarantinedMember::new(queue_name.as_str(), member.to_string(), error.to_string(), unix_seconds());
+        let key = queue_name.dlq_key(&self.keys);
+        let _: () = deadpool_redis::redis::pipe()
+            .atomic()
+            .lpush(&key, serde_json::to_string(&entry)?)
+            .ignore()
+            .ltrim(&key, 0, quarantine::MAX_QUARANTINED as isize - 1)
+            .ignore()
+            .query_async(&mut *conn)
+            .await?;
+        metrics::counter!(quarantine::QUARANTINED_TOTAL, "queue" => queue_name.to_string()).increment(1);
+        Ok(entry)
+    }
+
+    /// Up to `limit` quarantined members of a queue, newest first
+    pub async fn quarantined(&self, queue_name: &QueueName, limit: usize) -> Result<Vec<QuarantinedMember>, RedisError> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.pool.get().await?;
+        let entries: Vec<String> = conn
+            .lrange(queue_name.dlq_key(&self.keys), 0, limit as isize - 1)
+            .await?;
+        Ok(entries.iter().filter_map(|entry| serde_json::from_str(entry).ok()).collect())
+    }
+
+    /// Delete a quarantined member by id. Returns false if there is none.
+    pub async fn delete_quarantined(&self, queue_name: &QueueName, id: &str) -> Result<bool, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let key = queue_name.dlq_key(&self.keys);
+        let entries: Vec<String> = conn.lrange(&key, 0, -1).await?;
+        let Some(entry) = entries.into_iter().find(|entry| {
+            serde_json::from_str::<QuarantinedMember>(entry).is_ok_and(|quarantined| quarantined.id == id)
+        }) else {
+            return Ok(false);
+        };
+        let removed: i64 = conn.lrem(&key, 1, entry).await?;
+        Ok(removed > 0)
+    }
+
+    /// Remove the priority queue members of the given transactions, returning
+    /// how many were removed. Members are found with ZSCAN, reading only
+    /// their transaction id, and removed in pipelined ZREM batches; ones a
+    /// worker pops meanwhile are simply not counted.
+    pub async fn remove_transactions(
+        &self,
+        queue_name: &QueueName,
+        transaction_ids: &HashSet<String>,
+    ) -> Result<u64, RedisError> {
+        if transaction_ids.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = self.pool.get().await?;
+        let priority_queue_name = queue_name.priority_key(&self.keys);
+
+        let mut matched = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            // Replies alternate member and score
+            let (next, page): (u64, Vec<String>) = deadpool_redis::redis::cmd("ZSCAN")
+                .arg(&priority_queue_name)
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(SCAN_COUNT)
+                .query_async(&mut *conn)
+                .await?;
+            matched.extend(page.into_iter().step_by(2).filter(|member| {
+                envelope::transaction_id_of(member).is_some_and(|id| transaction_ids.contains(&id))
+            }));
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        let mut removed = 0;
+        for batches in matched.chunks(REMOVE_BATCH_SIZE * REMOVE_PIPELINE_DEPTH) {
+            let mut pipe = deadpool_redis::redis::pipe();
+            for batch in batches.chunks(REMOVE_BATCH_SIZE) {
+                pipe.zrem(&priority_queue_name, batch);
+            }
+            let counts: Vec<u64> = pipe.query_async(&mut *conn).await?;
+            removed += counts.iter().sum::<u64>();
+            self.unindex(&mut conn, queue_name, batches).await?;
         }
+        Ok(removed)
+    }
+
+    /// The priority queue member of a transaction, found through its
+    /// account's index instead of scanning the queue. `None` once the member
+    /// has left the queue.
+    pub async fn member_of(
+        &self,
+        queue_name: &QueueName,
+        account_id: &str,
+        transaction_id: &str,
+    ) -> Result<Option<String>, RedisError> {
+        let mut conn = self.pool.get().await?;
+        // Kept as text so the score is matched exactly
+        let score: Option<String> =
+            conn.zscore(queue_name.account_index_key(&self.keys, account_id), transaction_id).await?;
+        let Some(score) = score else {
+            return Ok(None);
+        };
+        let members: Vec<String> = conn.zrangebyscore(queue_name.priority_key(&self.keys), &score, &score).await?;
+        Ok(members
+            .into_iter()
+            .find(|member| envelope::transaction_id_of(member).is_some_and(|id| id == transaction_id)))
+    }
+
+    /// Remove `member` from the priority queue and its account's index.
+    /// Returns false when it was not queued, e.g. because a worker popped it.
+    pub async fn remove(&self, queue_name: &QueueName, member: &str) -> Result<bool, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let removed: u64 = conn.zrem(queue_name.priority_key(&self.keys), member).await?;
+        if removed == 0 {
+            return Ok(false);
+        }
+        self.unindex(&mut conn, queue_name, &[member.to_string()]).await?;
+        Ok(true)
+    }
+
+    /// Record how long a completed transaction of `priority` waited in the
+    /// queue, keeping only the latest samples of its band
+    pub async fn record_wait(&self, queue_name: &QueueName, priority: i32, wait_ms: u64) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let key = queue_name.wait_samples_key(&self.keys, PriorityBand::of(priority));
+        let _: () = deadpool_redis::redis::pipe()
+            .atomic()
+            .lpush(&key, wait_ms)
+            .ignore()
+            .ltrim(&key, 0, wait_times::WAIT_SAMPLE_CAPACITY as isize - 1)
+            .ignore()
+            .expire(&key, wait_times::WAIT_SAMPLE_TTL_SECONDS)
+            .ignore()
+            .query_async(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Append `event` to the queue's event stream, trimming it to about
+    /// `max_len` entries. Returns the entry's stream id.
+    pub async fn publish_event(
+        &self,
+        queue_name: &QueueName,
+        event: &QueueEvent,
+        max_len: usize,
+    ) -> Result<String, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let id: String = deadpool_redis::redis::cmd("XADD")
+            .arg(queue_name.events_key(&self.keys))
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(max_len)
+            .arg("*")
+            .arg(event.to_fields())
+            .query_async(&mut *conn)
+            .await?;
+        Ok(id)
+    }
+
+    /// Up to `count` events of the queue's stream that came after the entry
+    /// `after`; pass `events::EVENTS_FROM_START` to start at the oldest.
+    /// Entries that do not parse are skipped but still move `last_id` on.
+    pub async fn read_events(
+        &self,
+        queue_name: &QueueName,
+        after: &str,
+        count: usize,
+    ) -> Result<EventBatch, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let entries: Vec<(String, HashMap<String, String>)> = deadpool_redis::redis::cmd("XRANGE")
+            .arg(queue_name.events_key(&self.keys))
+            .arg(format!("({}", after))
+            .arg("+")
+            .arg("COUNT")
+            .arg(count)
+            .query_async(&mut *conn)
+            .await?;
+
+        let last_id = entries.last().map_or_else(|| after.to_string(), |(id, _)| id.clone());
+        let events = entries
+            .iter()
+            .filter_map(|(id, fields)| {
+                let event = QueueEvent::from_fields(fields);
+                if event.is_none() {
+                    tracing::debug!(id, "Skipping malformed queue event");
+                }
+                event
+            })
+            .collect();
+        Ok(EventBatch { events, last_id })
+    }
+
+    /// Entries in the queue's event stream
+    pub async fn event_stream_len(&self, queue_name: &QueueName) -> Result<u64, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let len: u64 = deadpool_redis::redis::cmd("XLEN")
+            .arg(queue_name.events_key(&self.keys))
+            .query_async(&mut *conn)
+            .await?;
+        Ok(len)
+    }
+
+    /// Wait time percentiles of every band with recent completions
+    pub async fn wait_percentiles(&self, queue_name: &QueueName) -> Result<Vec<WaitPercentiles>, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let mut pipe = deadpool_redis::redis::pipe();
+        for band in PriorityBand::ALL {
+            pipe.lrange(queue_name.wait_samples_key(&self.keys, band), 0, -1);
+        }
+        let samples: Vec<Vec<u64>> = pipe.query_async(&mut *conn).await?;
+        Ok(PriorityBand::ALL
+            .into_iter()
+            .zip(samples)
+            .filter_map(|(band, mut samples)| WaitPercentiles::from_samples(band, &mut samples))
+            .collect())
     }
 
     /// Get queue contents in priority order for testing
-    pub async fn get_priority_queue_order(&self, queue_name: &str) -> Result<Vec<String>, RedisError> {
+    pub async fn get_priority_queue_order(&self, queue_name: &QueueName) -> Result<Vec<String>, RedisError> {
         let mut conn = self.pool.get().await?;
-        let priority_queue_name = format!("{}_priority", queue_name);
+        let priority_queue_name = queue_name.priority_key(&self.keys);
         
         // Get all items in score order (ascending = highest priority first)
         let items: Vec<String> = conn.zrange(&priority_queue_name, 0, -1).await?;
         Ok(items)
     }
 
-    pub async fn dequeue(&self, queue_name: &str) -> Result<Option<String>, RedisError> {
+    pub async fn dequeue(&self, queue_name: &QueueName) -> Result<Option<String>, RedisError> {
         let mut conn = self.pool.get().await?;
-        let result: Option<String> = conn.lpop(queue_name, None).await?;
+        let result: Option<String> = conn.lpop(queue_name.list_key(&self.keys), None).await?;
+        if result.is_some() {
+            self.count_dequeued(&mut conn, queue_name, 1).await;
+        }
         Ok(result)
     }
 
-    pub async fn queue_length(&self, queue_name: &str) -> Result<i64, RedisError> {
+    /// Pop up to `count` items of the list queue in one LPOP, oldest first.
+    /// Fewer come back when the list holds fewer. As with `dequeue_batch`,
+    /// popped items are returned even if counting them fails.
+    pub async fn dequeue_list_batch(&self, queue_name: &QueueName, count: usize) -> Result<Vec<String>, RedisError> {
+        let Some(count) = NonZeroUsize::new(count) else {
+            return Ok(Vec::new());
+        };
         let mut conn = self.pool.get().await?;
-        let length: i64 = conn.llen(queue_name).await?;
-        Ok(length)
+        // An empty list answers nil, read as no items
+        let popped: Vec<String> = conn.lpop(queue_name.list_key(&self.keys), Some(count)).await?;
+        if !popped.is_empty() {
+            self.count_dequeued(&mut conn, queue_name, popped.len()).await;
+        }
+        Ok(popped)
     }
 
-    pub async fn get_queue_position(&self, queue_name: &str, data: &str) -> Result<Option<i64>, RedisError> {
+    pub async fn queue_length(&self, queue_name: &QueueName) -> Result<i64, RedisError> {
+        let key = queue_name.list_key(&self.keys);
+        let key = key.as_str();
+        self.read("llen", move |mut conn| async move { Ok(conn.llen(key).await?) })
+            .await
+    }
+
+    /// Position of `data` in the list queue (1-indexed), or `None` once it
+    /// has been popped. LPOS finds it in Redis, so the list is not read back.
+    pub async fn get_queue_position(&self, queue_name: &QueueName, data: &str) -> Result<Option<i64>, RedisError> {
         let mut conn = self.pool.get().await?;
-        let items: Vec<String> = conn.lrange(queue_name, 0, -1).await?;
-        
-        for (index, item) in items.iter().enumerate() {
-            if item == data {
-                return Ok(Some(index as i64 + 1));
+        let index: Option<i64> = deadpool_redis::redis::cmd("LPOS")
+            .arg(queue_name.list_key(&self.keys))
+            .arg(data)
+            .query_async(&mut *conn)
+            .await?;
+        Ok(index.map(|index| index + 1))
+    }
+
+    /// Record `count` items as processed in the current minute bucket
+    pub async fn record_processed(&self, queue_name: &QueueName, count: u64) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        increment_counter(&mut conn, &self.keys, queue_name, QueueCounter::Processed, count).await
+    }
+
+    /// Items processed per second over the last `window_minutes` complete minutes.
+    /// Returns `None` when nothing was processed in that window.
+    pub async fn processing_rate(&self, queue_name: &QueueName, window_minutes: u64) -> Result<Option<f64>, RedisError> {
+        self.counter_rate(queue_name, QueueCounter::Processed, window_minutes).await
+    }
+
+    /// Per-second rate of a queue counter over the last `window_minutes` complete
+    /// minutes. The current minute is excluded because it is still filling up.
+    /// Returns `None` when the counter did not move in that window.
+    pub async fn counter_rate(
+        &self,
+        queue_name: &QueueName,
+        counter: QueueCounter,
+        window_minutes: u64,
+    ) -> Result<Option<f64>, RedisError> {
+        if window_minutes == 0 {
+            return Ok(None);
+        }
+
+        let mut conn = self.pool.get().await?;
+        let current_minute = unix_seconds() / 60;
+        let keys: Vec<String> = (1..=window_minutes)
+            .map(|offset| queue_name.counter_key(&self.keys, counter, current_minute - offset))
+            .collect();
+
+        let counts: Vec<Option<u64>> = deadpool_redis::redis::cmd("MGET")
+            .arg(&keys)
+            .query_async(&mut *conn)
+            .await?;
+        let total: u64 = counts.into_iter().flatten().sum();
+
+        if total == 0 {
+            return Ok(None);
+        }
+        Ok(Some(total as f64 / (window_minutes * 60) as f64))
+    }
+
+    /// Stop workers from picking up this account's items until resumed
+    pub async fn pause_account(&self, account_id: &str) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let _: i32 = conn.sadd(self.keys.paused_accounts(), account_id).await?;
+        Ok(())
+    }
+
+    /// Returns false if the account was not paused
+    pub async fn resume_account(&self, account_id: &str) -> Result<bool, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let removed: i32 = conn.srem(self.keys.paused_accounts(), account_id).await?;
+        Ok(removed > 0)
+    }
+
+    pub async fn is_account_paused(&self, account_id: &str) -> Result<bool, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let paused: bool = conn.sismember(self.keys.paused_accounts(), account_id).await?;
+        Ok(paused)
+    }
+
+    /// Count one more pending transaction for an account unless it is already
+    /// at `cap`. The check and increment are one script so concurrent submits
+    /// cannot overshoot the cap. Returns false when the account is full.
+    pub async fn reserve_pending(&self, account_id: &str, cap: u32) -> Result<bool, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let reserved: i32 = deadpool_redis::redis::Script::new(
+            r"
+            local count = tonumber(redis.call('GET', KEYS[1]) or '0')
+            if count >= tonumber(ARGV[1]) then
+                return 0
+            end
+            redis.call('INCR', KEYS[1])
+            return 1
+            ",
+        )
+        .key(self.keys.pending(account_id))
+        .arg(cap)
+        .invoke_async(&mut *conn)
+        .await?;
+        Ok(reserved == 1)
+    }
+
+    /// Count one pending transaction less for an account, e.g. once it reaches
+    /// a terminal status. Never goes below zero; returns the new count.
+    pub async fn release_pending(&self, account_id: &str) -> Result<i64, RedisError> {
+        self.release_pending_by(account_id, 1).await
+    }
+
+    /// `release_pending` for `released` transactions at once
+    pub async fn release_pending_by(&self, account_id: &str, released: u64) -> Result<i64, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let count: i64 = deadpool_redis::redis::Script::new(
+            r"
+            local count = redis.call('DECRBY', KEYS[1], ARGV[1])
+            if count < 0 then
+                redis.call('SET', KEYS[1], 0)
+                return 0
+            end
+            return count
+            ",
+        )
+        .key(self.keys.pending(account_id))
+        .arg(released)
+        .invoke_async(&mut *conn)
+        .await?;
+        Ok(count)
+    }
+
+    /// Pending transactions currently counted for an account
+    pub async fn pending_count(&self, account_id: &str) -> Result<i64, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let count: Option<i64> = conn.get(self.keys.pending(account_id)).await?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Overwrite an account's pending counter, used when reconciling against
+    /// Postgres. A zero count removes the key.
+    pub async fn set_pending_count(&self, account_id: &str, count: i64) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let key = self.keys.pending(account_id);
+        if count <= 0 {
+            let _: i32 = conn.del(key).await?;
+        } else {
+            let _: () = conn.set(key, count).await?;
+        }
+        Ok(())
+    }
+
+    /// Every account with a pending counter in Redis
+    pub async fn pending_accounts(&self) -> Result<Vec<String>, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let mut keys: Vec<String> = Vec::new();
+        let mut iter = conn.scan_match::<_, String>(self.keys.pending_pattern()).await?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        Ok(keys.iter().filter_map(|key| self.keys.pending_account(key)).map(str::to_string).collect())
+    }
+
+    /// Cached copies of an account's `limit_types` limits, read together,
+    /// keyed by type. A type missing from the map was last seen without a
+    /// row. `None` is a cache miss, which any one type missing makes.
+    pub async fn cached_limits(
+        &self,
+        account_id: &str,
+        limit_types: &[&str],
+    ) -> Result<Option<HashMap<String, CachedLimit>>, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let keys: Vec<String> =
+            limit_types.iter().map(|limit_type| self.keys.limit_cache(account_id, limit_type)).collect();
+        let cached: Vec<Option<String>> = deadpool_redis::redis::cmd("MGET").arg(&keys).query_async(&mut *conn).await?;
+
+        let mut limits = HashMap::new();
+        for (limit_type, value) in limit_types.iter().zip(cached) {
+            // An unreadable entry is treated as a miss and overwritten on the next load
+            match value.as_deref().and_then(CachedLimit::decode) {
+                Some(Some(limit)) => {
+                    limits.insert(limit_type.to_string(), limit);
+                }
+                Some(None) => {}
+                None => return Ok(None),
             }
         }
-        
-        Ok(None)
+        Ok(Some(limits))
+    }
+
+    /// Cache an account's `limit_types` limits from `limits`, where a type
+    /// without an entry is cached as having none, for `ttl_seconds`
+    pub async fn cache_limits(
+        &self,
+        account_id: &str,
+        limit_types: &[&str],
+        limits: &HashMap<String, CachedLimit>,
+        ttl_seconds: u64,
+    ) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let mut pipe = deadpool_redis::redis::pipe();
+        pipe.atomic();
+        for limit_type in limit_types {
+            let value = CachedLimit::encode(limits.get(*limit_type).copied());
+            pipe.set_ex(self.keys.limit_cache(account_id, limit_type), value, ttl_seconds).ignore();
+        }
+        let _: () = pipe.query_async(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Drop the cached `limit_type` limit so the next lookup reads Postgres
+    pub async fn invalidate_limit(&self, account_id: &str, limit_type: &str) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let _: i32 = conn.del(self.keys.limit_cache(account_id, limit_type)).await?;
+        Ok(())
+    }
+
+    /// Claim an account's `idempotency_key` for `ttl_seconds`, storing
+    /// `record` under it, unless it is already claimed. Returns the record
+    /// already stored, or `None` when this call made the claim.
+    pub async fn claim_idempotency_key(
+        &self,
+        account_id: &str,
+        idempotency_key: &str,
+        record: &str,
+        ttl_seconds: u64,
+    ) -> Result<Option<String>, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let existing: Option<String> = deadpool_redis::redis::Script::new(
+            r"
+            local existing = redis.call('GET', KEYS[1])
+            if existing then
+                return existing
+            end
+            redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[2])
+            return false
+            ",
+        )
+        .key(self.keys.idempotency(account_id, idempotency_key))
+        .arg(record)
+        .arg(ttl_seconds)
+        .invoke_async(&mut *conn)
+        .await?;
+        Ok(existing)
+    }
+
+    /// Replace the record of a claimed `idempotency_key`, keeping it for
+    /// another `ttl_seconds`
+    pub async fn update_idempotency_key(
+        &self,
+        account_id: &str,
+        idempotency_key: &str,
+        record: &str,
+        ttl_seconds: u64,
+    ) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let _: () = deadpool_redis::redis::cmd("SET")
+            .arg(self.keys.idempotency(account_id, idempotency_key))
+            .arg(record)
+            .arg("EX")
+            .arg(ttl_seconds)
+            .query_async(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Give up the claim on `idempotency_key` so the next submit with it runs
+    pub async fn release_idempotency_key(&self, account_id: &str, idempotency_key: &str) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let _: i32 = conn.del(self.keys.idempotency(account_id, idempotency_key)).await?;
+        Ok(())
+    }
+
+    /// Rollout percentage of every feature flag that has one
+    pub async fn feature_flags(&self) -> Result<HashMap<String, u32>, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let flags: HashMap<String, u32> = conn.hgetall(self.keys.feature_flags()).await?;
+        Ok(flags)
+    }
+
+    pub async fn set_feature_flag(&self, flag: &str, percentage: u32) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let _: i32 = conn.hset(self.keys.feature_flags(), flag, percentage).await?;
+        Ok(())
+    }
+
+    /// Returns false if the flag had no percentage set
+    pub async fn clear_feature_flag(&self, flag: &str) -> Result<bool, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let removed: i32 = conn.hdel(self.keys.feature_flags(), flag).await?;
+        Ok(removed > 0)
     }
-}
\ No newline at end of file
+
+    /// Record that a worker skipped a transaction. `since` is kept from the
+    /// existing hold when the reason is unchanged, so it reflects when the
+    /// item first started being held for that reason.
+    pub async fn record_hold(&self, transaction_id: &str, reason: HoldReason) -> Result<ProcessingHold, RedisError> {
+        let existing = self.get_hold(transaction_id).await?;
+        let hold = match existing {
+            Some(hold) if hold.reason == reason => hold,
+            _ => ProcessingHold {
+                reason,
+                since: unix_seconds(),
+            },
+        };
+
+        let mut conn = self.pool.get().await?;
+        let key = self.keys.hold(transaction_id);
+        let _: () = deadpool_redis::redis::pipe()
+            .hset_multiple(&key, &[("reason", reason.as_str().to_string()), ("since", hold.since.to_string())])
+            .ignore()
+            .expire(&key, HOLD_TTL_SECONDS)
+            .ignore()
+            .query_async(&mut *conn)
+            .await?;
+        Ok(hold)
+    }
+
+    /// Last recorded skip decision for a transaction, if any
+    pub async fn get_hold(&self, transaction_id: &str) -> Result<Option<ProcessingHold>, RedisError> {
+        let mut conn = self.pool.get().await?;
+        let (reason, since): (Option<String>, Option<u64>) = deadpool_redis::redis::cmd("HMGET")
+            .arg(self.keys.hold(transaction_id))
+            .arg("reason")
+            .arg("since")
+            .query_async(&mut *conn)
+            .await?;
+
+        // An unknown reason means a newer worker wrote it; treat as no hold
+        let reason = reason.and_then(|r| HoldReason::parse(&r));
+        Ok(reason.zip(since).map(|(reason, since)| ProcessingHold { reason, since }))
+    }
+
+    pub async fn clear_hold(&self, transaction_id: &str) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let _: i32 = conn.del(self.keys.hold(transaction_id)).await?;
+        Ok(())
+    }
+
+    /// Record that a worker is alive
+    pub async fn record_heartbeat(&self, worker_id: &str) -> Result<(), RedisError> {
+        let mut conn = self.pool.get().await?;
+        let _: i32 = conn.zadd(self.keys.worker_heartbeat(), worker_id, unix_seconds()).await?;
+        Ok(())
+    }
+
+    /// Number of workers that sent a heartbeat within the last `max_age_seconds`
+    pub async fn live_worker_count(&self, max_age_seconds: u64) -> Result<i64, RedisError> {
+        let cutoff = unix_seconds().saturating_sub(max_age_seconds);
+        let key = self.keys.worker_heartbeat();
+        let key = key.as_str();
+        self.read("zcount", move |mut conn| async move { Ok(conn.zcount(key, cutoff, "+inf").await?) })
+        .await
+    }
+
+    /// Delete every key under this manager's prefix: queues, windows,
+    /// counters and flags alike. Returns the number of keys deleted.
+    /// Refused without a prefix, where the key space is the whole database.
+    pub async fn clear_key_space(&self) -> Result<u64, RedisError> {
+        if self.keys.prefix().is_none() {
+            return Err(RedisError::Config("Refusing to clear a key space without a prefix".to_string()));
+        }
+        let mut conn = self.pool.get().await?;
+        let mut keys: Vec<String> = Vec::new();
+        let mut iter = conn.scan_match::<_, String>(self.keys.all_pattern()).await?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        drop(iter);
+
+        let mut deleted = 0;
+        for batch in keys.chunks(REMOVE_BATCH_SIZE) {
+            let removed: u64 = conn.del(batch).await?;
+            deleted += removed;
+        }
+        Ok(deleted)
+    }
+}
+
+/// An account's rate_limits row as cached in Redis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedLimit {
+    pub max_requests: i32,
+    pub window_seconds: i32,
+    pub strategy: RateLimitStrategy,
+}
+
+impl CachedLimit {
+    /// Cached value meaning the account has no such limit
+    const NONE: &'static str = "none";
+
+    fn encode(limit: Option<Self>) -> String {
+        match limit {
+            Some(limit) => format!("{}:{}:{}", limit.max_requests, limit.window_seconds, limit.strategy),
+            None => Self::NONE.to_string(),
+        }
+    }
+
+    fn decode(value: &str) -> Option<Option<Self>> {
+        if value == Self::NONE {
+            return Some(None);
+        }
+        let mut parts = value.splitn(3, ':');
+        let max_requests = parts.next()?.parse().ok()?;
+        let window_seconds = parts.next()?.parse().ok()?;
+        // Entries cached before limits had a strategy are sliding windows
+        let strategy = match parts.next() {
+            Some(strategy) => RateLimitStrategy::parse(strategy)?,
+            None => RateLimitStrategy::SlidingWindow,
+        };
+        Some(Some(Self {
+            max_requests,
+            window_seconds,
+            strategy,
+        }))
+    }
+}
+
+/// Per-minute counters maintained for each queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueCounter {
+    Enqueued,
+    Dequeued,
+    Processed,
+}
+
+impl QueueCounter {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Enqueued => "enqueued",
+            Self::Dequeued => "dequeued",
+            Self::Processed => "processed",
+        }
+    }
+}
+
+/// Why a worker passed over a queued item instead of processing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldReason {
+    AccountPaused,
+    ConcurrencyCap,
+    ScheduledInFuture,
+    CircuitOpen,
+}
+
+impl HoldReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AccountPaused => "account_paused",
+            Self::ConcurrencyCap => "concurrency_cap",
+            Self::ScheduledInFuture => "scheduled_in_future",
+            Self::CircuitOpen => "circuit_open",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "account_paused" => Some(Self::AccountPaused),
+            "concurrency_cap" => Some(Self::ConcurrencyCap),
+            "scheduled_in_future" => Some(Self::ScheduledInFuture),
+            "circuit_open" => Some(Self::CircuitOpen),
+            _ => None,
+        }
+    }
+}
+
+/// Last skip decision recorded for a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessingHold {
+    pub reason: HoldReason,
+    /// Unix seconds when the item was first held for `reason`
+    pub since: u64,
+}
+
+/// Bump the current minute bucket of a counter, pipelined into one round trip
+async fn increment_counter(
+    conn: &mut RedisConnection,
+    keys: &KeySpace,
+    queue_name: &QueueName,
+    counter: QueueCounter,
+    count: u64,
+) -> Result<(), RedisError> {
+    let key = queue_name.counter_key(keys, counter, unix_seconds() / 60);
+    let _: () = deadpool_redis::redis::pipe()
+        .incr(&key, count)
+        .ignore()
+        .expire(&key, COUNTER_TTL_SECONDS)
+        .ignore()
+        .query_async(conn)
+        .await?;
+    Ok(())
+}
+
+/// Sliding window member for a request at `nanos`: the time, then a token for
+/// this process and a sequence number, so no two requests share a member
+fn sliding_window_member(nanos: u128) -> String {
+    static INSTANCE: OnceLock<String> = OnceLock::new();
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+    let instance = INSTANCE.get_or_init(|| uuid::Uuid::new_v4().simple().to_string()[..8].to_string());
+    format!("{}-{}-{}", nanos, instance, SEQUENCE.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Member of the `unit`th unit of a weighted request; the first unit keeps
+/// the plain member, as the script does
+fn weighted_member(member: &str, unit: u32) -> String {
+    if unit == 1 {
+        member.to_string()
+    } else {
+        format!("{}:{}", member, unit)
+    }
+}
+
+/// TTL of a sliding window key, in milliseconds: the window plus
+/// `SLIDING_WINDOW_TTL_BUFFER_MS`
+fn sliding_window_ttl_ms(window_seconds: u64) -> i64 {
+    (window_seconds * 1000 + SLIDING_WINDOW_TTL_BUFFER_MS) as i64
+}
+
+/// Unix second by which the oldest request in a sliding window, recorded at
+/// `oldest_nanos`, has aged out. Rounded up, so the window has room again
+/// at `reset_at` and not a fraction of a second later.
+fn sliding_window_reset_at(oldest_nanos: u128, window_nanos: u128) -> u64 {
+    (oldest_nanos + window_nanos).div_ceil(1_000_000_000) as u64
+}
+
+fn unix_nanos() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+}
+
+fn unix_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,107 @@
+//! Estimates of how much of a queue's memory each account holds.
+//!
+//! Redis only measures whole keys, so the key's `MEMORY USAGE` is split
+//! between accounts using a random sample of members. Each member costs its
+//! length plus a fixed per-member overhead (skiplist node and dict entry),
+//! and the overhead is whatever the key uses beyond the members' bytes.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Most members a single report samples, whatever the caller asks for
+pub const MAX_MEMORY_SAMPLE: usize = 1000;
+
+/// Bytes of each sampled member fetched to find its account id; enough for
+/// the transaction id and the longest escaped account id
+pub const MEMBER_PREFIX_BYTES: usize = 2048;
+
+/// One sampled queue member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampledMember {
+    pub bytes: u64,
+    /// `None` when the member is not a readable envelope
+    pub account_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountMemory {
+    pub account_id: String,
+    pub sampled_members: u64,
+    pub estimated_members: u64,
+    pub estimated_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryReport {
+    pub key: String,
+    pub total_members: u64,
+    /// `MEMORY USAGE` of the whole key
+    pub total_bytes: u64,
+    pub sampled_members: u64,
+    /// Accounts seen in the sample, largest estimated footprint first
+    pub accounts: Vec<AccountMemory>,
+    /// Estimated bytes of members that could not be attributed to an account
+    pub unattributed_bytes: u64,
+}
+
+impl MemoryReport {
+    /// Extrapolate per-account usage of `key` from `sample`
+    pub fn from_sample(key: &str, total_members: u64, total_bytes: u64, sample: &[SampledMember]) -> Self {
+        let mut report = Self {
+            key: key.to_string(),
+            total_members,
+            total_bytes,
+            sampled_members: sample.len() as u64,
+            accounts: Vec::new(),
+            unattributed_bytes: 0,
+        };
+        if sample.is_empty() || total_members == 0 {
+            return report;
+        }
+
+        let sampled = sample.len() as f64;
+        let scale = total_members as f64 / sampled;
+        let sample_bytes: u64 = sample.iter().map(|member| member.bytes).sum();
+        let overhead = (total_bytes as f64 - sample_bytes as f64 * scale).max(0.0) / total_members as f64;
+        // Share of the key's memory that one sampled byte or member stands for
+        let weight = |bytes: u64, members: u64| bytes as f64 + members as f64 * overhead;
+        let sample_weight = weight(sample_bytes, sample.len() as u64);
+        let estimate = |bytes: u64, members: u64| {
+            if sample_weight == 0.0 {
+                return 0;
+            }
+            (total_bytes as f64 * weight(bytes, members) / sample_weight).round() as u64
+        };
+
+        let mut by_account: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+        let (mut unattributed_members, mut unattributed_bytes) = (0, 0);
+        for member in sample {
+            match &member.account_id {
+                Some(account_id) => {
+                    let entry = by_account.entry(account_id).or_default();
+                    entry.0 += 1;
+                    entry.1 += member.bytes;
+                }
+                None => {
+                    unattributed_members += 1;
+                    unattributed_bytes += member.bytes;
+                }
+            }
+        }
+
+        report.accounts = by_account
+            .into_iter()
+            .map(|(account_id, (members, bytes))| AccountMemory {
+                account_id: account_id.to_string(),
+                sampled_members: members,
+                estimated_members: (members as f64 * scale).round() as u64,
+                estimated_bytes: estimate(bytes, members),
+            })
+            .collect();
+        report
+            .accounts
+            .sort_by(|a, b| b.estimated_bytes.cmp(&a.estimated_bytes).then_with(|| a.account_id.cmp(&b.account_id)));
+        report.unattributed_bytes = estimate(unattributed_bytes, unattributed_members);
+        report
+    }
+}
@@ -0,0 +1,37 @@
+//! Queue members that could not be decoded.
+//!
+//! A member that is not a readable envelope (bad JSON, unknown encoding)
+//! would fail the same way on every attempt, so dequeuing moves it to the
+//! queue's quarantine list with the decode error and carries on with the
+//! next member. Admins list and delete quarantined members; nothing
+//! retries them automatically.
+
+use serde::{Deserialize, Serialize};
+
+pub const QUARANTINED_TOTAL: &str = "queue_quarantined_total";
+
+/// Quarantined members kept per queue; the oldest are dropped past this
+pub const MAX_QUARANTINED: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantinedMember {
+    pub id: String,
+    pub queue: String,
+    /// The member exactly as it was stored in the queue
+    pub member: String,
+    pub error: String,
+    /// Unix seconds
+    pub quarantined_at: u64,
+}
+
+impl QuarantinedMember {
+    pub fn new(queue: &str, member: String, error: String, quarantined_at: u64) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            queue: queue.to_string(),
+            member,
+            error,
+            quarantined_at,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Scores of the priority queue.
+//!
+//! A member's score is `SEQUENCE_SCORE_BASE - priority + sequence / 2^40`,
+//! so lower scores pop first: priority decides, and within a priority the
+//! sequence keeps arrivals in order. The sequence is INCRed by the same
+//! script that adds the member, so Redis assigns it in the order adds arrive
+//! and no API instance's clock takes part.
+//!
+//! Precision: the integer part stays below 2^13, which leaves 40 bits of an
+//! f64's mantissa for the fraction. Every sequence below `SEQUENCE_LIMIT`
+//! (about 1.1 trillion adds per queue) is therefore exact and can never
+//! carry into the next priority.
+//!
+//! Rollover: when the counter reaches the limit it restarts at 1, but only
+//! once the queue is empty, so no waiting member is overtaken. Until then
+//! new members take the largest sequence and tie with each other, which
+//! Redis breaks by member bytes. Envelopes start with their transaction id,
+//! a UUIDv7 whose text sorts by creation time, so tied envelopes still pop
+//! in the order their ids were made, to the millisecond; other members lose
+//! arrival order within one priority. The priority order is never lost.
+//!
+//! Members scored by the earlier timestamp scheme all sit below 4000, and
+//! the base keeps every sequenced score above that, so items queued before
+//! the upgrade are served before anything queued after it.
+
+use crate::{MAX_PRIORITY, MIN_PRIORITY};
+
+/// Bits of the score fraction taken by the sequence
+pub const SEQUENCE_BITS: u32 = 40;
+
+/// First sequence that no longer fits the fraction
+pub const SEQUENCE_LIMIT: u64 = 1 << SEQUENCE_BITS;
+
+/// Integer part of a priority 0 member's score
+pub const SEQUENCE_SCORE_BASE: i32 = 5000;
+
+/// Score of a member with `priority` added as `sequence`
+pub fn score(priority: i32, sequence: u64) -> f64 {
+    let priority = priority.clamp(MIN_PRIORITY, MAX_PRIORITY);
+    let sequence = sequence.min(SEQUENCE_LIMIT - 1);
+    (SEQUENCE_SCORE_BASE - priority) as f64 + sequence as f64 / SEQUENCE_LIMIT as f64
+}
+
+/// Priority of a member from its score; the inverse of `score`
+pub fn priority_of(score: f64) -> i32 {
+    SEQUENCE_SCORE_BASE - score.floor() as i32
+}
@@ -0,0 +1,126 @@
+//! Token bucket limits. A bucket holds up to `burst` tokens and refills
+//! continuously at `refill` tokens per `refill_seconds`; a request takes
+//! `cost` tokens or is rejected. Unlike a sliding window, a client that
+//! waited may spend a whole burst at once, then settles at the refill rate.
+//!
+//! The bucket is a hash of `tokens` and `last_refill` (Unix milliseconds),
+//! refilled lazily by whichever check reads it next.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{validate_window, RedisError};
+
+/// How a `RateLimiter` counts requests against a limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RateLimitStrategy {
+    /// At most `max_requests` in any trailing window
+    #[default]
+    SlidingWindow,
+    /// A bucket of `max_requests` tokens refilled over each window
+    TokenBucket,
+}
+
+impl RateLimitStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SlidingWindow => "sliding_window",
+            Self::TokenBucket => "token_bucket",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sliding_window" => Some(Self::SlidingWindow),
+            "token_bucket" => Some(Self::TokenBucket),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RateLimitStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RateLimitStrategy {
+    type Err = RedisError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value).ok_or_else(|| RedisError::Config(format!("Unknown rate limit strategy {:?}", value)))
+    }
+}
+
+/// Size and refill rate of a token bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBucket {
+    /// Tokens a full bucket holds, so the largest burst allowed at once
+    pub burst: u32,
+    /// Tokens added every `refill_seconds`, a fraction at a time
+    pub refill: u32,
+    pub refill_seconds: u64,
+}
+
+impl TokenBucket {
+    /// Bucket standing in for a limit of `max_requests` per window: it holds
+    /// `max_requests` and refills all of them once per window, so a steady
+    /// client gets the same rate from either strategy
+    pub fn for_window(max_requests: u32, window_seconds: u64) -> Self {
+        Self {
+            burst: max_requests,
+            refill: max_requests,
+            refill_seconds: window_seconds,
+        }
+    }
+
+    /// Tokens added per second
+    pub fn refill_per_second(&self) -> f64 {
+        self.refill as f64 / self.refill_seconds.max(1) as f64
+    }
+
+    /// Reject buckets no check can enforce: the refill period follows the
+    /// window bounds, and a bucket that never refills would block for good
+    pub fn validate(&self) -> Result<(), RedisError> {
+        validate_window(self.refill_seconds)?;
+        if self.refill == 0 && self.burst > 0 {
+            return Err(RedisError::Config("A token bucket must refill at least 1 token".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Milliseconds a bucket takes to refill from empty to full, which is
+    /// also as long as its hash is worth keeping
+    pub fn fill_ms(&self) -> u64 {
+        (self.burst as u64 * self.refill_seconds * 1000).div_ceil(self.refill.max(1) as u64)
+    }
+
+    /// Tokens in a bucket that held `tokens` at `last_refill_ms`, once
+    /// refilled up to `now_ms`. Rounded to millionths of a token, so float
+    /// error never leaves a bucket a hair short of a whole token. The
+    /// check script computes the same.
+    pub fn refilled(&self, tokens: f64, last_refill_ms: u64, now_ms: u64) -> f64 {
+        let elapsed_ms = now_ms.saturating_sub(last_refill_ms) as f64;
+        let added = elapsed_ms * self.refill as f64 / (self.refill_seconds * 1000) as f64;
+        round_tokens((tokens + added).min(self.burst as f64))
+    }
+
+    /// Unix second by which a bucket holding `tokens` at `now_ms` holds
+    /// `wanted`, rounded up. `wanted` is capped at `burst`, so it is always
+    /// reached.
+    pub fn available_at(&self, tokens: f64, wanted: f64, now_ms: u64) -> u64 {
+        let missing = (wanted.min(self.burst as f64) - tokens).max(0.0);
+        let wait_ms = (missing * (self.refill_seconds * 1000) as f64 / self.refill.max(1) as f64).ceil() as u64;
+        (now_ms + wait_ms).div_ceil(1000)
+    }
+
+    /// Unix second the next whole token arrives in a bucket holding `tokens`,
+    /// or `now_ms` rounded up when it is already full
+    pub fn next_token_at(&self, tokens: f64, now_ms: u64) -> u64 {
+        self.available_at(tokens, tokens.floor() + 1.0, now_ms)
+    }
+}
+
+fn round_tokens(tokens: f64) -> f64 {
+    (tokens * 1_000_000.0).round() / 1_000_000.0
+}
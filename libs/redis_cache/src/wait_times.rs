@@ -0,0 +1,79 @@
+//! How long transactions waited between enqueue and dequeue, by priority.
+//!
+//! Workers push each completed transaction's wait onto a capped list per
+//! priority band, so Redis only ever holds the latest `WAIT_SAMPLE_CAPACITY`
+//! samples of each band. Percentiles are computed from those samples when
+//! read, in linear time per band.
+
+use serde::Serialize;
+
+/// Samples kept per band; older ones are trimmed as new ones arrive
+pub const WAIT_SAMPLE_CAPACITY: usize = 1000;
+
+/// Samples of a band that has seen no completions for this long expire
+pub const WAIT_SAMPLE_TTL_SECONDS: i64 = 86_400;
+
+/// Range of priorities whose wait times are reported together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityBand {
+    /// Below 0
+    Low,
+    /// Exactly 0, the priority of submits that do not set one
+    Normal,
+    /// Above 0
+    High,
+}
+
+impl PriorityBand {
+    pub const ALL: [Self; 3] = [Self::Low, Self::Normal, Self::High];
+
+    pub fn of(priority: i32) -> Self {
+        match priority {
+            i32::MIN..=-1 => Self::Low,
+            0 => Self::Normal,
+            _ => Self::High,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+        }
+    }
+}
+
+/// Wait time percentiles of one band's recent completions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WaitPercentiles {
+    pub band: PriorityBand,
+    pub samples: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+}
+
+impl WaitPercentiles {
+    /// Percentiles of `samples`, reordering them in place; `None` when empty
+    pub fn from_samples(band: PriorityBand, samples: &mut [u64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        Some(Self {
+            band,
+            samples: samples.len(),
+            p50_ms: nearest_rank(samples, 50),
+            p95_ms: nearest_rank(samples, 95),
+            p99_ms: nearest_rank(samples, 99),
+        })
+    }
+}
+
+/// The `percentile`th value of non-empty `samples` by nearest rank. Selection
+/// partitions around the rank without sorting the rest.
+fn nearest_rank(samples: &mut [u64], percentile: usize) -> u64 {
+    let rank = (samples.len() * percentile).div_ceil(100).max(1);
+    *samples.select_nth_unstable(rank - 1).1
+}
@@ -0,0 +1,79 @@
+//! Fixed window boundaries with a stable per-key offset, so that keys
+//! sharing a window length do not all reset on the same wall-clock second.
+
+use crate::RedisError;
+
+/// Longest window a limit may use, 30 days. Sliding windows keep a member
+/// per request for the whole window, so longer ones mostly hold memory.
+pub const MAX_WINDOW_SECONDS: u64 = 86_400 * 30;
+
+/// Reject window lengths no limiter can enforce. A window of 0 starts at
+/// the current instant, so nothing is ever counted against it.
+pub fn validate_window(window_seconds: u64) -> Result<(), RedisError> {
+    if window_seconds == 0 || window_seconds > MAX_WINDOW_SECONDS {
+        return Err(RedisError::Config(format!(
+            "window_seconds must be between 1 and {}, got {}",
+            MAX_WINDOW_SECONDS, window_seconds
+        )));
+    }
+    Ok(())
+}
+
+/// How a key's fixed windows line up with the wall clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowAlignment {
+    /// Boundaries shifted by a per-key offset derived from the key
+    #[default]
+    Jittered,
+    /// Boundaries on multiples of the window length (e.g. :00 every minute)
+    Aligned,
+}
+
+/// Stable offset in `[0, window_seconds)` for a key.
+///
+/// FNV-1a rather than the std hasher, whose output may change between
+/// releases and would move every boundary on upgrade.
+pub fn window_offset(key: &str, window_seconds: u64) -> u64 {
+    if window_seconds == 0 {
+        return 0;
+    }
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    hash % window_seconds
+}
+
+/// The fixed window containing a point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedWindow {
+    /// Sequence number of the window, used in the counter key
+    pub index: u64,
+    /// Unix seconds the window opened
+    pub start: u64,
+    /// Unix seconds the window closes and the count resets
+    pub reset_at: u64,
+}
+
+impl FixedWindow {
+    pub fn containing(now_seconds: u64, window_seconds: u64, offset: u64) -> Self {
+        let window_seconds = window_seconds.max(1);
+        let offset = offset % window_seconds;
+        // Shift by a whole window so times before the first offset boundary stay positive
+        let index = (now_seconds + window_seconds - offset) / window_seconds;
+        let reset_at = index * window_seconds + offset;
+        Self {
+            index,
+            start: reset_at.saturating_sub(window_seconds),
+            reset_at,
+        }
+    }
+
+    /// Window for `key` at `now_seconds` under the given alignment
+    pub fn for_key(key: &str, now_seconds: u64, window_seconds: u64, alignment: WindowAlignment) -> Self {
+        let offset = match alignment {
+            WindowAlignment::Jittered => window_offset(key, window_seconds),
+            WindowAlignment::Aligned => 0,
+        };
+        Self::containing(now_seconds, window_seconds, offset)
+    }
+}
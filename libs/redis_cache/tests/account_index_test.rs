@@ -0,0 +1,174 @@
+use deadpool_redis::redis::AsyncCommands;
+use redis_cache::sequence::{priority_of, score};
+use redis_cache::{KeySpace, QueueEnvelope, QueueManager, QueueName, MAX_PRIORITY, MIN_PRIORITY};
+use serde_json::value::RawValue;
+use std::collections::HashSet;
+
+const REDIS_URL: &str = "redis://localhost:6379";
+
+fn envelope(transaction_id: &str, account_id: &str) -> String {
+    let data = RawValue::from_string(r#"{"amount":1}"#.to_string()).unwrap();
+    QueueEnvelope::encode(transaction_id, account_id, &data).unwrap()
+}
+
+fn ids(items: &[redis_cache::AccountQueueItem]) -> Vec<&str> {
+    items.iter().map(|item| item.transaction_id.as_str()).collect()
+}
+
+/// Test removed and popped members leave their account's index
+#[tokio::test]
+async fn test_index_follows_removals() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("account_index_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    let adds = [("a1", "acct_a", 0), ("b1", "acct_b", 3), ("a2", "acct_a", 0), ("a3", "acct_a", 1)];
+    for (id, account_id, priority) in adds {
+        queue_manager.add_with_priority(&queue_name, &envelope(id, account_id), priority).await.unwrap();
+    }
+    // Members that are not envelopes are queued but not indexed
+    queue_manager.add_with_priority(&queue_name, "plain", 0).await.unwrap();
+
+    let items = queue_manager.list_account_items(&queue_name, "acct_a", 10).await.unwrap();
+    assert_eq!(ids(&items), ["a3", "a1", "a2"]);
+    assert_eq!(items.iter().map(|item| item.rank).collect::<Vec<_>>(), [2, 3, 4]);
+    assert_eq!(items[0].priority, 1);
+
+    let removed = HashSet::from(["a1".to_string()]);
+    assert_eq!(queue_manager.remove_transactions(&queue_name, &removed).await.unwrap(), 1);
+    let popped = queue_manager.dequeue_envelope(&queue_name).await.unwrap().unwrap();
+    assert_eq!(popped.transaction_id, "b1");
+
+    let items = queue_manager.list_account_items(&queue_name, "acct_a", 10).await.unwrap();
+    assert_eq!(ids(&items), ["a3", "a2"]);
+    assert_eq!(items.iter().map(|item| item.rank).collect::<Vec<_>>(), [1, 2]);
+    assert!(queue_manager.list_account_items(&queue_name, "acct_b", 10).await.unwrap().is_empty());
+}
+
+/// Test an index entry whose member left the queue behind its back is dropped when listed
+#[tokio::test]
+async fn test_stale_entries_are_dropped() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool.clone());
+    let queue_name = QueueName::new(format!("account_index_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+    let keys = KeySpace::default();
+
+    queue_manager.add_with_priority(&queue_name, &envelope("a1", "acct_a"), 0).await.unwrap();
+    queue_manager.add_with_priority(&queue_name, &envelope("a2", "acct_a"), 0).await.unwrap();
+    // Popped as an instance that stops before unindexing would leave it
+    let mut conn = pool.get().await.unwrap();
+    let _: Vec<String> = conn.zpopmin(queue_name.priority_key(&keys), 1).await.unwrap();
+
+    let items = queue_manager.list_account_items(&queue_name, "acct_a", 10).await.unwrap();
+    assert_eq!(ids(&items), ["a2"]);
+    assert_eq!(items[0].rank, 1);
+    let indexed: u64 = conn.zcard(queue_name.account_index_key(&keys, "acct_a")).await.unwrap();
+    assert_eq!(indexed, 1);
+    let recorded: bool = conn.hexists(queue_name.enqueued_at_key(&keys), "a1").await.unwrap();
+    assert!(!recorded);
+}
+
+/// Test an envelope's account position counts only its own account's members ahead of it
+#[tokio::test]
+async fn test_account_position() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("account_index_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    for (id, account_id) in [("a1", "acct_a"), ("a2", "acct_a"), ("a3", "acct_a")] {
+        queue_manager.add_with_priority(&queue_name, &envelope(id, account_id), 0).await.unwrap();
+    }
+    let b1 = envelope("b1", "acct_b");
+    queue_manager.add_with_priority(&queue_name, &b1, 0).await.unwrap();
+    assert_eq!(queue_manager.priority_position(&queue_name, &b1).await.unwrap(), 4);
+    assert_eq!(queue_manager.account_position(&queue_name, &b1).await.unwrap(), Some(1));
+
+    let a4 = envelope("a4", "acct_a");
+    queue_manager.add_with_priority(&queue_name, &a4, 2).await.unwrap();
+    assert_eq!(queue_manager.account_position(&queue_name, &a4).await.unwrap(), Some(1));
+    let a2 = envelope("a2", "acct_a");
+    assert_eq!(queue_manager.account_position(&queue_name, &a2).await.unwrap(), Some(3));
+    assert_eq!(queue_manager.account_position(&queue_name, "plain").await.unwrap(), None);
+}
+
+/// Test a transaction's queue position is found through its account's index, and is gone once popped
+#[tokio::test]
+async fn test_transaction_position() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool.clone());
+    let queue_name = QueueName::new(format!("account_index_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    for (id, account_id, priority) in [("a1", "acct_a", 0), ("b1", "acct_b", 0), ("a2", "acct_a", 5)] {
+        queue_manager.add_with_priority(&queue_name, &envelope(id, account_id), priority).await.unwrap();
+    }
+    assert_eq!(queue_manager.transaction_position(&queue_name, "acct_a", "a2").await.unwrap(), Some(1));
+    assert_eq!(queue_manager.transaction_position(&queue_name, "acct_a", "a1").await.unwrap(), Some(2));
+    assert_eq!(queue_manager.transaction_position(&queue_name, "acct_b", "b1").await.unwrap(), Some(3));
+    assert_eq!(queue_manager.transaction_position(&queue_name, "acct_b", "a1").await.unwrap(), None);
+
+    // Popped as an instance that stops before unindexing would leave it
+    let mut conn = pool.get().await.unwrap();
+    let _: Vec<String> = conn.zpopmin(queue_name.priority_key(&KeySpace::default()), 1).await.unwrap();
+    assert_eq!(queue_manager.transaction_position(&queue_name, "acct_a", "a2").await.unwrap(), None);
+    assert_eq!(queue_manager.transaction_position(&queue_name, "acct_a", "a1").await.unwrap(), Some(1));
+}
+
+/// Test a score's priority is read back whatever its sequence
+#[test]
+fn test_priority_of_score() {
+    for priority in [MIN_PRIORITY, -1, 0, 1, MAX_PRIORITY] {
+        for sequence in [0, 1, 1 << 39, (1 << 40) - 1] {
+            assert_eq!(priority_of(score(priority, sequence)), priority, "{} {}", priority, sequence);
+        }
+    }
+}
+
+/// Test a member found through its account's index is removed once, and not after it was popped
+#[tokio::test]
+async fn test_remove_member_of_transaction() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("account_index_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    for (id, priority) in [("a1", 0), ("a2", 0), ("a3", 2)] {
+        queue_manager.add_with_priority(&queue_name, &envelope(id, "acct_a"), priority).await.unwrap();
+    }
+
+    let member = queue_manager.member_of(&queue_name, "acct_a", "a2").await.unwrap().unwrap();
+    assert_eq!(member, envelope("a2", "acct_a"));
+    assert!(queue_manager.remove(&queue_name, &member).await.unwrap());
+    assert!(!queue_manager.remove(&queue_name, &member).await.unwrap());
+    assert_eq!(queue_manager.member_of(&queue_name, "acct_a", "a2").await.unwrap(), None);
+    assert_eq!(queue_manager.member_of(&queue_name, "acct_b", "a1").await.unwrap(), None);
+    let items = queue_manager.list_account_items(&queue_name, "acct_a", 10).await.unwrap();
+    assert_eq!(ids(&items), ["a3", "a1"]);
+
+    let member = queue_manager.member_of(&queue_name, "acct_a", "a3").await.unwrap().unwrap();
+    let popped = queue_manager.dequeue_envelope(&queue_name).await.unwrap().unwrap();
+    assert_eq!(popped.transaction_id, "a3");
+    assert!(!queue_manager.remove(&queue_name, &member).await.unwrap());
+    assert_eq!(queue_manager.priority_queue_length(&queue_name).await.unwrap(), 1);
+}
+
+/// Test popped envelopes restored go back to their place in the queue and their account's index
+#[tokio::test]
+async fn test_restore_keeps_place() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("account_index_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    for (id, priority) in [("a1", 2), ("a2", 1), ("a3", 0)] {
+        queue_manager.add_with_priority(&queue_name, &envelope(id, "acct_a"), priority).await.unwrap();
+    }
+    let popped = queue_manager.dequeue_envelopes(&queue_name, 2).await.unwrap();
+    let popped_ids: Vec<&str> = popped.iter().map(|popped| popped.envelope.transaction_id.as_str()).collect();
+    assert_eq!(popped_ids, ["a1", "a2"]);
+    assert_eq!(ids(&queue_manager.list_account_items(&queue_name, "acct_a", 10).await.unwrap()), ["a3"]);
+
+    let entries: Vec<(String, f64)> = popped.into_iter().map(|popped| (popped.member, popped.score)).collect();
+    queue_manager.restore(&queue_name, &entries).await.unwrap();
+    let items = queue_manager.list_account_items(&queue_name, "acct_a", 10).await.unwrap();
+    assert_eq!(ids(&items), ["a1", "a2", "a3"]);
+    assert_eq!(items[0].priority, 2);
+    assert_eq!(queue_manager.dequeue_envelope(&queue_name).await.unwrap().unwrap().transaction_id, "a1");
+}
@@ -0,0 +1,143 @@
+use redis_cache::envelope::{account_id_of, member_pattern, member_prefix, transaction_id_of, ZSTD_ENCODING};
+use redis_cache::{QueueEnvelope, QueueManager, QueueName, RedisError};
+use serde_json::value::RawValue;
+use serde_json::{json, Value};
+
+const REDIS_URL: &str = "redis://localhost:6379";
+const THRESHOLD: usize = 16 * 1024;
+
+fn payload(value: Value) -> Box<RawValue> {
+    RawValue::from_string(value.to_string()).unwrap()
+}
+
+/// A repetitive payload well over the threshold, like a large batch of similar items
+fn large_payload() -> Box<RawValue> {
+    let items: Vec<Value> = (0..2000)
+        .map(|i| json!({ "type": "transfer", "amount": i, "currency": "USD", "memo": "monthly settlement" }))
+        .collect();
+    payload(json!({ "items": items }))
+}
+
+fn stored_enc(member: &str) -> Option<String> {
+    serde_json::from_str::<Value>(member).unwrap()["enc"].as_str().map(str::to_string)
+}
+
+/// Test payloads at or under the threshold are stored verbatim
+#[test]
+fn test_small_payload_stays_plain() {
+    let data = payload(json!({ "type": "transfer", "amount": 100 }));
+
+    let member = QueueEnvelope::encode_compressed("tx-1", "acct", &data, THRESHOLD, None).unwrap();
+    assert_eq!(member, QueueEnvelope::encode("tx-1", "acct", &data).unwrap());
+    assert!(stored_enc(&member).is_none());
+
+    let decoded = QueueEnvelope::decode(&member).unwrap();
+    assert_eq!(decoded.transaction_data.get(), data.get());
+}
+
+/// Test a large payload is stored compressed, smaller, and decodes to the same text
+#[test]
+fn test_large_payload_round_trips_compressed() {
+    let data = large_payload();
+    assert!(data.get().len() > THRESHOLD);
+
+    let member = QueueEnvelope::encode_compressed("tx-2", "acct", &data, THRESHOLD, None).unwrap();
+    let plain = QueueEnvelope::encode("tx-2", "acct", &data).unwrap();
+    assert_eq!(stored_enc(&member).as_deref(), Some(ZSTD_ENCODING));
+    assert!(
+        member.len() * 4 < plain.len(),
+        "compressed member is {} bytes, plain is {}",
+        member.len(),
+        plain.len()
+    );
+
+    let decoded = QueueEnvelope::decode(&member).unwrap();
+    assert_eq!(decoded.transaction_id, "tx-2");
+    assert_eq!(decoded.account_id, "acct");
+    assert_eq!(decoded.transaction_data.get(), data.get());
+}
+
+/// Test members written before compression existed still decode
+#[test]
+fn test_decodes_members_without_enc() {
+    let member = r#"{"transaction_id":"tx-3","account_id":"acct","transaction_data":{"amount":5}}"#;
+    let decoded = QueueEnvelope::decode(member).unwrap();
+    assert_eq!(decoded.transaction_data.get(), r#"{"amount":5}"#);
+}
+
+/// Test unknown encodings and corrupt payloads are errors, not garbage
+#[test]
+fn test_rejects_unknown_or_corrupt_encoding() {
+    let unknown = r#"{"transaction_id":"tx","account_id":"acct","enc":"brotli","transaction_data":"AAAA"}"#;
+    assert!(matches!(QueueEnvelope::decode(unknown), Err(RedisError::Compression(_))));
+
+    let corrupt = r#"{"transaction_id":"tx","account_id":"acct","enc":"zstd","transaction_data":"bm90IHpzdGQ="}"#;
+    assert!(matches!(QueueEnvelope::decode(corrupt), Err(RedisError::Compression(_))));
+}
+
+/// Test the traceparent round-trips for plain and compressed payloads and is omitted when absent
+#[test]
+fn test_traceparent_round_trips() {
+    let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    for data in [payload(json!({ "amount": 1 })), large_payload()] {
+        let member = QueueEnvelope::encode_compressed("tx-5", "acct", &data, THRESHOLD, Some(traceparent)).unwrap();
+        let decoded = QueueEnvelope::decode(&member).unwrap();
+        assert_eq!(decoded.traceparent.as_deref(), Some(traceparent));
+        assert_eq!(decoded.transaction_data.get(), data.get());
+    }
+
+    let member = QueueEnvelope::encode_compressed("tx-6", "acct", &payload(json!({})), THRESHOLD, None).unwrap();
+    assert!(!member.contains("traceparent"));
+    assert!(QueueEnvelope::decode(&member).unwrap().traceparent.is_none());
+}
+
+/// Test dequeue hands back the original payload of a compressed member
+#[tokio::test]
+async fn test_dequeue_decompresses() {
+    let queue_manager = QueueManager::new(redis_cache::create_pool(REDIS_URL).await.unwrap());
+    let queue_name = QueueName::new(format!("envelope_test_{}", std::process::id())).unwrap();
+    let data = large_payload();
+
+    let member = QueueEnvelope::encode_compressed("tx-4", "acct", &data, THRESHOLD, None).unwrap();
+    queue_manager.enqueue_with_priority(&queue_name, &member, 0).await.unwrap();
+
+    let envelope = queue_manager.dequeue_envelope(&queue_name).await.unwrap().expect("queue is empty");
+    assert_eq!(envelope.transaction_id, "tx-4");
+    assert_eq!(envelope.transaction_data.get(), data.get());
+    assert!(queue_manager.dequeue_envelope(&queue_name).await.unwrap().is_none());
+}
+
+/// Test the account id is read from the start of a member, truncated or escaped
+#[test]
+fn test_account_id_from_member_prefix() {
+    let member = QueueEnvelope::encode_compressed("tx-1", "acct \"q\" é", &large_payload(), THRESHOLD, None).unwrap();
+    assert_eq!(account_id_of(&member).as_deref(), Some("acct \"q\" é"));
+    assert_eq!(account_id_of(&member[..64]).as_deref(), Some("acct \"q\" é"));
+
+    assert_eq!(account_id_of("not an envelope"), None);
+    // Cut inside the account id
+    assert_eq!(account_id_of(&member[..40]), None);
+}
+
+/// Test the transaction id is read from the start of a member, even when the payload mentions one
+#[test]
+fn test_transaction_id_from_member_prefix() {
+    let data = serde_json::value::to_raw_value(&json!({ "transaction_id": "spoofed" })).unwrap();
+    let member = QueueEnvelope::encode_compressed("tx-9", "acct", &data, THRESHOLD, None).unwrap();
+    assert_eq!(transaction_id_of(&member).as_deref(), Some("tx-9"));
+    assert_eq!(transaction_id_of("not an envelope"), None);
+}
+
+/// Test the member prefix and pattern match the member's start, with glob characters escaped in the pattern
+#[test]
+fn test_member_pattern() {
+    let data = serde_json::value::to_raw_value(&json!({ "amount": 1 })).unwrap();
+    let member = QueueEnvelope::encode("tx-9", "acct", &data).unwrap();
+    let pattern = member_pattern("tx-9");
+    assert!(member.starts_with(pattern.strip_suffix('*').unwrap()), "{} vs {}", pattern, member);
+    assert!(!member_pattern("tx-").starts_with(pattern.strip_suffix('*').unwrap()));
+    assert!(member.starts_with(&member_prefix("tx-9")));
+    assert!(!member.starts_with(&member_prefix("tx-")));
+    assert_eq!(member_pattern("a*b?[c]"), r#"{"transaction_id":"a\*b\?\[c\]",*"#);
+}
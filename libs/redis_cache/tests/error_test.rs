@@ -0,0 +1,114 @@
+use deadpool::managed::TimeoutType;
+use deadpool_redis::redis::{ErrorKind, RedisError as RedisClientError};
+use deadpool_redis::PoolError;
+use redis_cache::RedisError;
+use std::io;
+
+fn client_error(kind: ErrorKind, description: &'static str) -> RedisClientError {
+    (kind, description).into()
+}
+
+fn client_error_with_detail(kind: ErrorKind, detail: &str) -> RedisClientError {
+    (kind, "An error was signalled by the server", detail.to_string()).into()
+}
+
+/// Test waiting for a free pool slot maps to PoolExhausted
+#[test]
+fn test_pool_wait_timeout_is_exhausted() {
+    let err: RedisError = PoolError::Timeout(TimeoutType::Wait).into();
+    assert!(matches!(err, RedisError::PoolExhausted));
+    assert!(err.is_transient());
+}
+
+/// Test connection create and recycle timeouts map to Timeout
+#[test]
+fn test_pool_connect_timeouts() {
+    for timeout in [TimeoutType::Create, TimeoutType::Recycle] {
+        let err: RedisError = PoolError::Timeout(timeout).into();
+        assert!(matches!(err, RedisError::Timeout(_)));
+        assert!(err.is_transient());
+    }
+}
+
+/// Test a closed pool is permanent
+#[test]
+fn test_pool_closed_is_permanent() {
+    let err: RedisError = PoolError::Closed.into();
+    assert!(matches!(err, RedisError::Pool(_)));
+    assert!(!err.is_transient());
+    assert!(err.is_unavailable());
+}
+
+/// Test backend errors from the pool are classified like direct client errors
+#[test]
+fn test_pool_backend_error_is_unwrapped() {
+    let io_err = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+    let err: RedisError = PoolError::Backend(io_err.into()).into();
+    assert!(matches!(err, RedisError::Redis(_)));
+    assert!(err.is_transient());
+    assert!(err.is_unavailable());
+}
+
+/// Test client IO timeouts map to Timeout
+#[test]
+fn test_client_timeout() {
+    let io_err = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+    let err: RedisError = RedisClientError::from(io_err).into();
+    assert!(matches!(err, RedisError::Timeout(_)));
+    assert!(err.is_transient());
+}
+
+/// Test missing and failing scripts map to Script and are not retried
+#[test]
+fn test_script_errors() {
+    let err: RedisError = client_error(ErrorKind::NoScriptError, "NOSCRIPT").into();
+    assert!(matches!(err, RedisError::Script(_)));
+    assert!(!err.is_transient());
+
+    let err: RedisError =
+        client_error_with_detail(ErrorKind::ResponseError, "Error running script (call to f_abc): @user_script:1: boom").into();
+    assert!(matches!(err, RedisError::Script(_)));
+    assert!(!err.is_transient());
+}
+
+/// Test server-side transient conditions are retried and plain errors are not
+#[test]
+fn test_client_error_transience() {
+    for kind in [ErrorKind::BusyLoadingError, ErrorKind::TryAgain, ErrorKind::ClusterDown, ErrorKind::MasterDown] {
+        let err: RedisError = client_error(kind, "transient").into();
+        assert!(matches!(err, RedisError::Redis(_)));
+        assert!(err.is_transient(), "{:?} should be transient", kind);
+    }
+
+    for kind in [ErrorKind::TypeError, ErrorKind::AuthenticationFailed, ErrorKind::ResponseError] {
+        let err: RedisError = client_error(kind, "permanent").into();
+        assert!(matches!(err, RedisError::Redis(_)));
+        assert!(!err.is_transient(), "{:?} should not be transient", kind);
+    }
+}
+
+/// Test variants raised by this crate classify as expected
+#[test]
+fn test_crate_variants() {
+    assert!(RedisError::QueueFull { queue: "tx_queue".to_string() }.is_transient());
+    assert!(!RedisError::Config("bad url".to_string()).is_transient());
+
+    let serde_err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    assert!(!RedisError::from(serde_err).is_transient());
+}
+
+/// Test only failures to reach Redis count as unavailable
+#[test]
+fn test_unavailable() {
+    assert!(RedisError::PoolExhausted.is_unavailable());
+    assert!(RedisError::Timeout("creating connection".to_string()).is_unavailable());
+    let io_err = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+    assert!(RedisError::from(RedisClientError::from(io_err)).is_unavailable());
+
+    // Redis answered these
+    assert!(!RedisError::from(client_error(ErrorKind::NoScriptError, "NOSCRIPT")).is_unavailable());
+    assert!(!RedisError::from(client_error(ErrorKind::TryAgain, "transient")).is_unavailable());
+    assert!(!RedisError::QueueFull { queue: "tx_queue".to_string() }.is_unavailable());
+    let serde_err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    assert!(!RedisError::from(serde_err).is_unavailable());
+}
@@ -0,0 +1,95 @@
+use redis_cache::events::EVENTS_FROM_START;
+use redis_cache::{QueueEvent, QueueEventKind, QueueManager, QueueName};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REDIS_URL: &str = "redis://localhost:6379";
+
+/// A queue name no other run shares
+fn unique_queue(test: &str) -> QueueName {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    QueueName::new(format!("events_test_{}_{}_{}", test, std::process::id(), nanos)).unwrap()
+}
+
+fn event(kind: QueueEventKind, n: u64) -> QueueEvent {
+    QueueEvent::new(kind, format!("tx_{}", n), "acct_events", 5, 1_700_000_000_000 + n)
+}
+
+fn fields(event: &QueueEvent) -> HashMap<String, String> {
+    event.to_fields().into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+}
+
+/// Test events survive the trip through stream fields, timings included
+#[test]
+fn test_fields_round_trip() {
+    let submitted = event(QueueEventKind::Submitted, 1);
+    assert_eq!(QueueEvent::from_fields(&fields(&submitted)), Some(submitted.clone()));
+    assert!(!fields(&submitted).contains_key("wait_ms"));
+
+    let completed = event(QueueEventKind::Completed, 2).with_wait_ms(40).with_processing_ms(7);
+    let stored = fields(&completed);
+    assert_eq!(stored["kind"], "completed");
+    assert_eq!(stored["id"], "tx_2");
+    assert_eq!(stored["account"], "acct_events");
+    assert_eq!(stored["priority"], "5");
+    assert_eq!((stored["wait_ms"].as_str(), stored["processing_ms"].as_str()), ("40", "7"));
+    assert_eq!(QueueEvent::from_fields(&stored), Some(completed));
+}
+
+/// Test entries missing a required field or with an unknown kind are not events
+#[test]
+fn test_malformed_fields_are_rejected() {
+    let valid = fields(&event(QueueEventKind::Failed, 3));
+    for missing in ["kind", "id", "account", "priority", "at_ms"] {
+        let mut stored = valid.clone();
+        stored.remove(missing);
+        assert_eq!(QueueEvent::from_fields(&stored), None, "without {}", missing);
+    }
+    let mut unknown = valid.clone();
+    unknown.insert("kind".to_string(), "exploded".to_string());
+    assert_eq!(QueueEvent::from_fields(&unknown), None);
+}
+
+/// Test batches are read in publish order and the cursor moves past them
+#[tokio::test]
+async fn test_read_events_in_batches() {
+    let queue = QueueManager::new(redis_cache::create_pool(REDIS_URL).await.unwrap());
+    let queue_name = unique_queue("batches");
+    let kinds = [
+        QueueEventKind::Submitted,
+        QueueEventKind::Dequeued,
+        QueueEventKind::Completed,
+        QueueEventKind::Failed,
+        QueueEventKind::Cancelled,
+    ];
+    for (n, kind) in kinds.iter().enumerate() {
+        queue.publish_event(&queue_name, &event(*kind, n as u64), 1000).await.unwrap();
+    }
+
+    let first = queue.read_events(&queue_name, EVENTS_FROM_START, 3).await.unwrap();
+    assert_eq!(first.events.iter().map(|e| e.kind).collect::<Vec<_>>(), kinds[..3]);
+    let second = queue.read_events(&queue_name, &first.last_id, 3).await.unwrap();
+    assert_eq!(second.events.iter().map(|e| e.kind).collect::<Vec<_>>(), kinds[3..]);
+    assert_eq!(second.events[1].transaction_id, "tx_4");
+
+    // Nothing new keeps the cursor where it was
+    let empty = queue.read_events(&queue_name, &second.last_id, 3).await.unwrap();
+    assert!(empty.events.is_empty());
+    assert_eq!(empty.last_id, second.last_id);
+}
+
+/// Test the stream is trimmed to about its cap as events are published
+#[tokio::test]
+async fn test_stream_is_capped() {
+    let queue = QueueManager::new(redis_cache::create_pool(REDIS_URL).await.unwrap());
+    let queue_name = unique_queue("capped");
+    for n in 0..1000 {
+        queue.publish_event(&queue_name, &event(QueueEventKind::Submitted, n), 100).await.unwrap();
+    }
+
+    // Approximate trimming drops whole nodes, so a little over the cap stays
+    let len = queue.event_stream_len(&queue_name).await.unwrap();
+    assert!((100..300).contains(&len), "{} entries", len);
+    let oldest = queue.read_events(&queue_name, EVENTS_FROM_START, 1).await.unwrap();
+    assert_ne!(oldest.events[0].transaction_id, "tx_0");
+}
@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use redis_cache::hedge::{race, HedgeOutcome};
+use redis_cache::RedisError;
+
+const BUDGET: Duration = Duration::from_millis(10);
+
+/// Stands in for a pooled connection; the first `slow_calls` calls stall
+struct FakeConnection {
+    calls: AtomicUsize,
+    slow_calls: usize,
+    delay: Duration,
+    fail_fast_calls: bool,
+}
+
+impl FakeConnection {
+    fn new(slow_calls: usize, delay: Duration) -> Self {
+        Self {
+            calls: AtomicUsize::new(0),
+            slow_calls,
+            delay,
+            fail_fast_calls: false,
+        }
+    }
+
+    /// Answers with the index of the call that produced the reply
+    async fn zcount(&self) -> Result<usize, RedisError> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        if call < self.slow_calls {
+            tokio::time::sleep(self.delay).await;
+        } else if self.fail_fast_calls {
+            return Err(RedisError::PoolExhausted);
+        }
+        Ok(call)
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+#[tokio::test]
+async fn test_fast_read_is_not_hedged() {
+    let conn = FakeConnection::new(0, Duration::ZERO);
+
+    let (call, outcome) = race(BUDGET, || conn.zcount()).await.unwrap();
+
+    assert_eq!(call, 0);
+    assert_eq!(outcome, HedgeOutcome::Primary);
+    assert_eq!(conn.calls(), 1);
+}
+
+#[tokio::test]
+async fn test_hedge_wins_when_first_call_stalls() {
+    let conn = FakeConnection::new(1, Duration::from_secs(5));
+
+    let started = Instant::now();
+    let (call, outcome) = race(BUDGET, || conn.zcount()).await.unwrap();
+    let elapsed = started.elapsed();
+
+    assert_eq!(call, 1, "Reply should come from the hedged attempt");
+    assert_eq!(outcome, HedgeOutcome::Hedge);
+    assert_eq!(conn.calls(), 2);
+    assert!(
+        elapsed < BUDGET + Duration::from_millis(200),
+        "Hedged read took {:?}, the stalled attempt was not abandoned",
+        elapsed
+    );
+}
+
+#[tokio::test]
+async fn test_primary_can_still_win_after_hedge() {
+    // Both attempts are slow; the first one started earlier and finishes first
+    let conn = FakeConnection::new(2, Duration::from_millis(50));
+
+    let (call, outcome) = race(BUDGET, || conn.zcount()).await.unwrap();
+
+    assert_eq!(call, 0);
+    assert_eq!(outcome, HedgeOutcome::PrimaryAfterHedge);
+    assert!(outcome.hedged());
+}
+
+#[tokio::test]
+async fn test_failed_hedge_falls_back_to_primary() {
+    let mut conn = FakeConnection::new(1, Duration::from_millis(50));
+    conn.fail_fast_calls = true;
+
+    let (call, outcome) = race(BUDGET, || conn.zcount()).await.unwrap();
+
+    assert_eq!(call, 0);
+    assert_eq!(outcome, HedgeOutcome::PrimaryAfterHedge);
+}
+
+#[tokio::test]
+async fn test_error_without_hedge_is_returned() {
+    let mut conn = FakeConnection::new(0, Duration::ZERO);
+    conn.fail_fast_calls = true;
+
+    let err = race(BUDGET, || conn.zcount()).await.unwrap_err();
+
+    assert!(matches!(err, RedisError::PoolExhausted));
+    assert_eq!(conn.calls(), 1);
+}
@@ -0,0 +1,238 @@
+use redis_cache::keys::{hash_tag, MAX_QUEUE_NAME_LEN};
+use redis_cache::{
+    KeyLayout, KeySpace, PriorityBand, QueueCounter, QueueManager, QueueName, RateLimiter, RedisError,
+};
+
+const REDIS_URL: &str = "redis://localhost:6379";
+
+/// Test every key of an account shares the account's hash tag in cluster layout
+#[test]
+fn test_account_keys_share_a_tag() {
+    let keys = KeyLayout::Cluster;
+    let account_keys = [
+        keys.sliding_window("acct_42"),
+        keys.fixed_window("acct_42", 28_333_333),
+        keys.pending("acct_42"),
+        keys.limit_cache("acct_42", "submit"),
+        keys.idempotency("acct_42", "retry-1"),
+    ];
+    for key in &account_keys {
+        assert_eq!(hash_tag(key), "acct_42", "{}", key);
+    }
+    assert_ne!(hash_tag(&keys.pending("acct_43")), "acct_42");
+}
+
+/// Test a queue's list, sorted set and counters share the queue's hash tag,
+/// including every counter bucket a rate read fetches together
+#[test]
+fn test_queue_keys_share_a_tag() {
+    let keys = &KeySpace::new(KeyLayout::Cluster);
+    let queue = QueueName::from_static("transactions");
+    let mut queue_keys = vec![
+        queue.list_key(keys),
+        queue.priority_key(keys),
+        queue.sequence_key(keys),
+        queue.dlq_key(keys),
+        queue.wait_samples_key(keys, PriorityBand::High),
+        queue.events_key(keys),
+        queue.account_index_key(keys, "{acct_42}"),
+        queue.enqueued_at_key(keys),
+    ];
+    queue_keys.extend((0..5).map(|minute| queue.counter_key(keys, QueueCounter::Processed, minute)));
+    queue_keys.push(queue.counter_key(keys, QueueCounter::Enqueued, 7));
+    for key in &queue_keys {
+        assert_eq!(hash_tag(key), "transactions", "{}", key);
+    }
+}
+
+/// Test the standalone layout keeps the untagged key names
+#[test]
+fn test_standalone_names_are_unchanged() {
+    let keys = &KeySpace::default();
+    let queue = QueueName::from_static("transactions");
+    assert_eq!(keys.sliding_window("admin:key_1"), "rate_limit:admin:key_1");
+    assert_eq!(keys.fixed_window("acct_42", 9), "rate_limit:fixed:acct_42:9");
+    assert_eq!(queue.list_key(keys), "transactions");
+    assert_eq!(queue.priority_key(keys), "transactions_priority");
+    assert_eq!(queue.sequence_key(keys), "transactions:sequence");
+    assert_eq!(queue.dlq_key(keys), "transactions:quarantine");
+    assert_eq!(queue.wait_samples_key(keys, PriorityBand::Low), "transactions:wait_ms:low");
+    assert_eq!(queue.events_key(keys), "transactions:events");
+    assert_eq!(queue.account_index_key(keys, "acct_42"), "transactions:account:acct_42");
+    assert_eq!(queue.enqueued_at_key(keys), "transactions:enqueued_at");
+    assert_eq!(queue.counter_key(keys, QueueCounter::Enqueued, 9), "transactions:enqueued:9");
+    assert_eq!(keys.pending("acct_42"), "account:acct_42:pending");
+    assert_eq!(keys.limit_cache("acct_42", "submit"), "account:acct_42:limit:submit");
+    assert_eq!(keys.idempotency("acct_42", "retry-1"), "account:acct_42:idempotency:retry-1");
+}
+
+/// Test the cluster layout tags every key derived from a queue name
+#[test]
+fn test_cluster_queue_key_names() {
+    let keys = &KeySpace::new(KeyLayout::Cluster);
+    let queue = QueueName::from_static("tx_queue");
+    assert_eq!(queue.list_key(keys), "{tx_queue}");
+    assert_eq!(queue.priority_key(keys), "{tx_queue}_priority");
+    assert_eq!(queue.sequence_key(keys), "{tx_queue}:sequence");
+    assert_eq!(queue.dlq_key(keys), "{tx_queue}:quarantine");
+    assert_eq!(queue.wait_samples_key(keys, PriorityBand::High), "{tx_queue}:wait_ms:high");
+    assert_eq!(queue.events_key(keys), "{tx_queue}:events");
+    assert_eq!(queue.account_index_key(keys, "acct_42"), "{tx_queue}:account:acct_42");
+    assert_eq!(queue.counter_key(keys, QueueCounter::Processed, 3), "{tx_queue}:processed:3");
+}
+
+/// Test queue names that could break out of their keys are rejected
+#[test]
+fn test_invalid_queue_names_are_rejected() {
+    let too_long = "q".repeat(MAX_QUEUE_NAME_LEN + 1);
+    for name in ["", "tx:queue", "{tx_queue}", "tx queue", "tx\nqueue", "tx_quëue", too_long.as_str()] {
+        let err = QueueName::new(name).unwrap_err();
+        assert!(matches!(err, RedisError::InvalidQueueName { .. }), "{:?}: {}", name, err);
+        assert!(!err.is_transient());
+        assert!(QueueName::try_from(name).is_err());
+        assert!(name.parse::<QueueName>().is_err());
+    }
+}
+
+/// Test valid names round-trip unchanged
+#[test]
+fn test_valid_queue_names_are_accepted() {
+    let longest = "q".repeat(MAX_QUEUE_NAME_LEN);
+    for name in ["tx_queue", "Engine-Test_42", "a", longest.as_str()] {
+        let queue = QueueName::try_from(name).unwrap();
+        assert_eq!(queue.as_str(), name);
+        assert_eq!(queue.to_string(), name);
+    }
+    assert_eq!(QueueName::new("tx_queue").unwrap(), QueueName::from_static("tx_queue"));
+}
+
+/// Test pending counter keys resolve to their account in either layout
+#[test]
+fn test_pending_account_in_both_layouts() {
+    for keys in [KeyLayout::Standalone, KeyLayout::Cluster] {
+        assert_eq!(KeyLayout::pending_account(&keys.pending("acct_42")), Some("acct_42"));
+    }
+    assert_eq!(KeyLayout::pending_account("account:acct_42:limit:submit"), None);
+}
+
+/// Test a prefix goes in front of every key and keeps each key's hash tag
+#[test]
+fn test_prefixed_key_names() {
+    let keys = KeySpace::new(KeyLayout::Cluster).with_prefix("test-7f3a").unwrap();
+    let queue = QueueName::from_static("tx_queue");
+    assert_eq!(keys.prefix(), Some("test-7f3a"));
+    assert_eq!(keys.sliding_window("acct_42"), "test-7f3a:rate_limit:{acct_42}");
+    assert_eq!(keys.pending("acct_42"), "test-7f3a:account:{acct_42}:pending");
+    assert_eq!(queue.priority_key(&keys), "test-7f3a:{tx_queue}_priority");
+    assert_eq!(queue.counter_key(&keys, QueueCounter::Processed, 3), "test-7f3a:{tx_queue}:processed:3");
+    assert_eq!(keys.feature_flags(), "test-7f3a:feature_flags");
+    assert_eq!(keys.hold("tx-1"), "test-7f3a:hold:tx-1");
+    assert_eq!(keys.all_pattern(), "test-7f3a:*");
+    assert_eq!(hash_tag(&keys.limit_cache("acct_42", "submit")), "acct_42");
+
+    assert_eq!(keys.pending_account(&keys.pending("acct_42")), Some("acct_42"));
+    assert_eq!(keys.pending_account(&KeySpace::default().pending("acct_42")), None);
+    assert_eq!(KeySpace::default().pending_account("test-7f3a:account:acct_42:pending"), None);
+}
+
+/// Test scoped windows and buckets are apart from every account's, whatever the account id holds
+#[test]
+fn test_scoped_limiter_keys() {
+    let keys = KeySpace::new(KeyLayout::Cluster).with_prefix("test-7f3a").unwrap();
+    let admin = keys.in_limit_scope("admin");
+    assert_eq!(keys.scoped_rate_limit("admin", "key_1"), "test-7f3a:rate_limit_scope:admin:{key_1}");
+    assert_eq!(admin.sliding_window("key_1"), keys.scoped_rate_limit("admin", "key_1"));
+    assert_eq!(admin.fixed_window("key_1", 9), "test-7f3a:rate_limit_scope:admin:fixed:{key_1}:9");
+    assert_eq!(admin.token_bucket("key_1"), "test-7f3a:rate_limit_scope:admin:bucket:{key_1}");
+    assert_eq!(admin.pending("key_1"), keys.pending("key_1"));
+    assert_eq!(keys.rate_limit_pattern(), "test-7f3a:rate_limit*");
+
+    let standalone = KeySpace::default();
+    let scoped = standalone.scoped_rate_limit("admin", "key_1");
+    assert_eq!(scoped, "rate_limit_scope:admin:key_1");
+    for account_id in ["admin:key_1", "scope:admin:key_1", "_scope:admin:key_1", ":admin:key_1"] {
+        assert_ne!(standalone.sliding_window(account_id), scoped, "{}", account_id);
+    }
+    assert_ne!(standalone.scoped_rate_limit("ip", "key_1"), scoped);
+}
+
+/// Test prefixes that could break out of their key space are rejected
+#[test]
+fn test_invalid_key_prefixes_are_rejected() {
+    let too_long = "p".repeat(65);
+    for prefix in ["", "a:b", "{tag}", "tenant*", "ten?ant", "[ab]", "a b", too_long.as_str()] {
+        let err = KeySpace::default().with_prefix(prefix).unwrap_err();
+        assert!(matches!(err, RedisError::Config(_)), "{:?}: {}", prefix, err);
+    }
+}
+
+/// Test hash tags follow Redis Cluster's rules
+#[test]
+fn test_hash_tag_rules() {
+    assert_eq!(hash_tag("rate_limit:{acct}:minute"), "acct");
+    assert_eq!(hash_tag("{a}{b}"), "a");
+    // Empty or unclosed tags hash the whole key
+    assert_eq!(hash_tag("queue:{}:x"), "queue:{}:x");
+    assert_eq!(hash_tag("queue:{open"), "queue:{open");
+    assert_eq!(hash_tag("plain"), "plain");
+}
+
+/// Test queue and limiter operations behave the same with tagged keys
+#[tokio::test]
+async fn test_cluster_layout_round_trip() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool.clone()).with_keys(KeyLayout::Cluster);
+    let queue_name = QueueName::new(format!("keys_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    queue_manager.add_with_priority(&queue_name, "low", 0).await.unwrap();
+    queue_manager.add_with_priority(&queue_name, "high", 5).await.unwrap();
+    assert_eq!(queue_manager.priority_queue_length(&queue_name).await.unwrap(), 2);
+    assert_eq!(
+        queue_manager.dequeue_by_priority(&queue_name).await.unwrap().as_deref(),
+        Some("high")
+    );
+
+    // The untagged queue of the same name is a different key
+    let standalone = QueueManager::new(pool.clone());
+    assert_eq!(standalone.priority_queue_length(&queue_name).await.unwrap(), 0);
+
+    let limiter = RateLimiter::new(pool.clone()).with_keys(KeyLayout::Cluster);
+    let account_id = format!("acct_{}", uuid::Uuid::new_v4().simple());
+    let first = limiter.check_rate_limit_script(&account_id, 2, 60).await.unwrap();
+    assert_eq!(first.remaining, 1);
+    assert_eq!(limiter.sliding_window_usage(&account_id, 60).await.unwrap(), 1);
+}
+
+/// Test queues and windows under one prefix are invisible to another
+#[tokio::test]
+async fn test_prefixes_isolate_key_spaces() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let run = uuid::Uuid::new_v4().simple().to_string();
+    let ours = KeySpace::default().with_prefix(&format!("keys-test-{}-a", run)).unwrap();
+    let theirs = KeySpace::default().with_prefix(&format!("keys-test-{}-b", run)).unwrap();
+    let queue_name = QueueName::from_static("transaction_queue");
+
+    let queue_manager = QueueManager::new(pool.clone()).with_keys(ours.clone());
+    queue_manager.add_with_priority(&queue_name, "ours", 0).await.unwrap();
+    assert!(queue_manager.reserve_pending("acct_shared", 10).await.unwrap());
+    let other = QueueManager::new(pool.clone()).with_keys(theirs.clone());
+    assert_eq!(other.priority_queue_length(&queue_name).await.unwrap(), 0);
+    assert_eq!(other.pending_accounts().await.unwrap(), Vec::<String>::new());
+    assert_eq!(queue_manager.pending_accounts().await.unwrap(), vec!["acct_shared".to_string()]);
+
+    let limiter = RateLimiter::new(pool.clone()).with_keys(ours.clone());
+    assert!(limiter.check_rate_limit("acct_shared", 1, 60).await.unwrap().allowed);
+    assert!(!limiter.check_rate_limit("acct_shared", 1, 60).await.unwrap().allowed);
+    let other_keys = theirs.clone();
+    let other = RateLimiter::new(pool.clone()).with_keys(theirs);
+    assert!(other.check_rate_limit("acct_shared", 1, 60).await.unwrap().allowed);
+
+    assert!(queue_manager.clear_key_space().await.unwrap() >= 3);
+    assert_eq!(queue_manager.priority_queue_length(&queue_name).await.unwrap(), 0);
+    assert_eq!(queue_manager.pending_accounts().await.unwrap(), Vec::<String>::new());
+    assert!(QueueManager::new(pool.clone()).with_keys(other_keys).clear_key_space().await.unwrap() >= 1);
+
+    // Without a prefix the key space is the whole database, which is never cleared
+    let err = QueueManager::new(pool).clear_key_space().await.unwrap_err();
+    assert!(matches!(err, RedisError::Config(_)), "{}", err);
+}
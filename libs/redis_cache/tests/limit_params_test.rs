@@ -0,0 +1,78 @@
+use redis_cache::{validate_window, RateLimiter, RedisError, WindowAlignment, MAX_WINDOW_SECONDS};
+
+/// Nothing listens on port 1: a check that gets as far as Redis fails there
+const DEAD_REDIS_URL: &str = "redis://127.0.0.1:1";
+
+const NOW_NANOS: u128 = 1_700_000_000 * 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    /// Refused with `RedisError::Config` before touching Redis
+    Invalid,
+    /// Denied without touching Redis
+    DeniedAll,
+    /// Passed validation and went on to Redis
+    Checked,
+}
+
+const CASES: &[(u32, u64, Outcome)] = &[
+    (10, 0, Outcome::Invalid),
+    (0, 0, Outcome::Invalid),
+    (10, MAX_WINDOW_SECONDS + 1, Outcome::Invalid),
+    (10, u64::MAX, Outcome::Invalid),
+    (0, 1, Outcome::DeniedAll),
+    (0, 60, Outcome::DeniedAll),
+    (0, MAX_WINDOW_SECONDS, Outcome::DeniedAll),
+    (1, 1, Outcome::Checked),
+    (10, MAX_WINDOW_SECONDS, Outcome::Checked),
+    (u32::MAX, 60, Outcome::Checked),
+];
+
+fn outcome(result: Result<redis_cache::RateLimitResult, RedisError>, reset_after: u64) -> Outcome {
+    match result {
+        Err(RedisError::Config(_)) => Outcome::Invalid,
+        Ok(result) => {
+            assert!(!result.allowed);
+            assert_eq!(result.remaining, 0);
+            assert!(result.reset_at >= reset_after, "reset_at {} before {}", result.reset_at, reset_after);
+            Outcome::DeniedAll
+        }
+        Err(_) => Outcome::Checked,
+    }
+}
+
+/// Test window bounds at and around the edges
+#[test]
+fn test_validate_window() {
+    let cases = [(0, false), (1, true), (60, true), (MAX_WINDOW_SECONDS, true), (MAX_WINDOW_SECONDS + 1, false)];
+    for (window, valid) in cases {
+        assert_eq!(validate_window(window).is_ok(), valid, "window {}", window);
+    }
+}
+
+/// Test every check refuses bad windows and denies a zero limit outright
+#[tokio::test]
+async fn test_check_parameter_boundaries() {
+    let pool = redis_cache::create_pool(DEAD_REDIS_URL).await.unwrap();
+    let limiter = RateLimiter::new(pool).with_clock(|| NOW_NANOS);
+    let now_seconds = (NOW_NANOS / 1_000_000_000) as u64;
+    let wall_clock = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    for &(max_requests, window, expected) in CASES {
+        let reset_after = now_seconds.saturating_add(window);
+        let sliding = limiter.check_rate_limit("params", max_requests, window).await;
+        assert_eq!(outcome(sliding, reset_after), expected, "sliding {} per {}s", max_requests, window);
+
+        let script = limiter.check_rate_limit_script("params", max_requests, window).await;
+        assert_eq!(outcome(script, reset_after), expected, "script {} per {}s", max_requests, window);
+
+        // Fixed windows read the wall clock, and reset within one window of it
+        let fixed = limiter
+            .check_fixed_window("params", max_requests, window, WindowAlignment::Aligned)
+            .await;
+        assert_eq!(outcome(fixed, wall_clock), expected, "fixed {} per {}s", max_requests, window);
+    }
+}
@@ -0,0 +1,74 @@
+use redis_cache::memory::{SampledMember, MAX_MEMORY_SAMPLE};
+use redis_cache::{MemoryReport, QueueEnvelope, QueueManager, QueueName};
+use serde_json::json;
+use serde_json::value::RawValue;
+
+const REDIS_URL: &str = "redis://localhost:6379";
+
+fn member(account_id: Option<&str>, bytes: u64) -> SampledMember {
+    SampledMember {
+        bytes,
+        account_id: account_id.map(str::to_string),
+    }
+}
+
+/// Test the sample is scaled up to the whole key, overhead included
+#[test]
+fn test_estimates_split_key_memory() {
+    // 100 members, 10 sampled: 2 of 1000 bytes for "big", 8 of 100 for "small".
+    // The sample puts members at 2,800 bytes, so the remaining 2,000 of the key's
+    // 30,000 bytes are 20 bytes of overhead per member
+    let mut sample = vec![member(Some("big"), 1000), member(Some("big"), 1000)];
+    sample.extend((0..8).map(|_| member(Some("small"), 100)));
+
+    let report = MemoryReport::from_sample("q_priority", 100, 30_000, &sample);
+
+    assert_eq!(report.sampled_members, 10);
+    assert_eq!(report.accounts.len(), 2);
+    assert_eq!(report.accounts[0].account_id, "big");
+    assert_eq!(report.accounts[0].estimated_members, 20);
+    assert_eq!(report.accounts[0].estimated_bytes, 20_400);
+    assert_eq!(report.accounts[1].account_id, "small");
+    assert_eq!(report.accounts[1].estimated_members, 80);
+    assert_eq!(report.accounts[1].estimated_bytes, 9_600);
+    assert_eq!(report.unattributed_bytes, 0);
+}
+
+/// Test members without a readable envelope are reported apart, and empty keys report nothing
+#[test]
+fn test_unattributed_and_empty() {
+    let sample = [member(Some("acct"), 100), member(None, 100)];
+    let report = MemoryReport::from_sample("q_priority", 2, 200, &sample);
+    assert_eq!(report.accounts[0].estimated_bytes, 100);
+    assert_eq!(report.unattributed_bytes, 100);
+
+    let report = MemoryReport::from_sample("q_priority", 0, 0, &[]);
+    assert!(report.accounts.is_empty());
+    assert_eq!(report.total_bytes, 0);
+}
+
+/// Test a live queue ranks the account with large payloads first
+#[tokio::test]
+async fn test_report_ranks_accounts_by_payload_size() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("memory_report_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    let large = RawValue::from_string(json!({ "blob": "x".repeat(20_000) }).to_string()).unwrap();
+    let small = RawValue::from_string(json!({ "amount": 1 }).to_string()).unwrap();
+    for i in 0..50 {
+        let (account_id, data) = if i % 5 == 0 { ("heavy", &large) } else { ("light", &small) };
+        let member = QueueEnvelope::encode(&format!("tx-{}", i), account_id, data).unwrap();
+        queue_manager.add_with_priority(&queue_name, &member, 0).await.unwrap();
+    }
+
+    let report = queue_manager.memory_report(&queue_name, MAX_MEMORY_SAMPLE).await.unwrap();
+
+    assert_eq!(report.total_members, 50);
+    assert_eq!(report.sampled_members, 50);
+    assert!(report.total_bytes > 10 * 20_000);
+    let accounts: Vec<&str> = report.accounts.iter().map(|a| a.account_id.as_str()).collect();
+    assert_eq!(accounts, ["heavy", "light"]);
+    assert_eq!(report.accounts[0].estimated_members, 10);
+    assert!(report.accounts[0].estimated_bytes > 20 * report.accounts[1].estimated_bytes);
+}
@@ -0,0 +1,96 @@
+use deadpool_redis::redis::AsyncCommands;
+use redis_cache::{create_pool_with_options, PoolConfig, RedisError, RedisOptions};
+use std::time::Duration;
+
+const REDIS_URL: &str = "redis://localhost:6379";
+
+fn with_db(db: i64) -> RedisOptions {
+    RedisOptions {
+        db: Some(db),
+        ..Default::default()
+    }
+}
+
+/// Test a database index that disagrees with the URL is rejected
+#[tokio::test]
+async fn test_conflicting_db_is_rejected() {
+    let err = create_pool_with_options("redis://localhost:6379/3", &with_db(4))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RedisError::Config(ref message) if message.contains("conflicts")), "{}", err);
+
+    let err = create_pool_with_options("redis+unix:///tmp/redis.sock?db=1", &with_db(2))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RedisError::Config(_)));
+}
+
+/// Test matching or one-sided database settings are accepted
+#[tokio::test]
+async fn test_consistent_db_is_accepted() {
+    // Pools connect lazily, so these do not need a running Redis
+    create_pool_with_options("redis://localhost:6379/3", &with_db(3)).await.unwrap();
+    create_pool_with_options("redis://localhost:6379/3", &RedisOptions::default()).await.unwrap();
+    create_pool_with_options("redis://localhost:6379", &with_db(5)).await.unwrap();
+    create_pool_with_options("redis://localhost:6379/", &with_db(5)).await.unwrap();
+}
+
+/// Test invalid indexes and URLs are configuration errors
+#[tokio::test]
+async fn test_invalid_settings_are_rejected() {
+    assert!(matches!(
+        create_pool_with_options(REDIS_URL, &with_db(-1)).await,
+        Err(RedisError::Config(_))
+    ));
+    assert!(matches!(
+        create_pool_with_options("not a url", &RedisOptions::default()).await,
+        Err(RedisError::Config(_))
+    ));
+}
+
+/// Test pools on different database indexes do not see each other's keys
+///
+/// Requires Redis on localhost:6379
+#[tokio::test]
+async fn test_pools_on_different_dbs_are_isolated() {
+    let options = |db| RedisOptions {
+        db: Some(db),
+        response_timeout: Some(Duration::from_secs(2)),
+        pool: PoolConfig {
+            connect_timeout: Some(Duration::from_secs(2)),
+            ..PoolConfig::default()
+        },
+    };
+    let staging = create_pool_with_options(REDIS_URL, &options(14)).await.unwrap();
+    let production = create_pool_with_options(REDIS_URL, &options(15)).await.unwrap();
+
+    let key = format!("db_isolation_test:{}", std::process::id());
+    let mut staging_conn = staging.get().await.expect("Redis must be running for this test");
+    let mut production_conn = production.get().await.unwrap();
+
+    let _: () = staging_conn.set_ex(&key, "staging", 60).await.unwrap();
+    let seen: Option<String> = production_conn.get(&key).await.unwrap();
+    assert_eq!(seen, None, "Key written to db 14 is visible from db 15");
+
+    let _: () = production_conn.set_ex(&key, "production", 60).await.unwrap();
+    let staging_value: String = staging_conn.get(&key).await.unwrap();
+    assert_eq!(staging_value, "staging");
+
+    let _: () = staging_conn.del(&key).await.unwrap();
+    let _: () = production_conn.del(&key).await.unwrap();
+}
+
+/// Test the pool is sized from its pool config
+#[tokio::test]
+async fn test_pool_size_is_configured() {
+    let options = RedisOptions {
+        pool: PoolConfig {
+            max_size: Some(3),
+            wait_timeout: Some(Duration::from_millis(50)),
+            ..PoolConfig::default()
+        },
+        ..Default::default()
+    };
+    let pool = create_pool_with_options(REDIS_URL, &options).await.unwrap();
+    assert_eq!(pool.status().max_size, 3);
+}
@@ -0,0 +1,59 @@
+use deadpool_redis::redis::AsyncCommands;
+use redis_cache::{KeySpace, QueueEnvelope, QueueManager, QueueName};
+use serde_json::value::RawValue;
+
+const REDIS_URL: &str = "redis://localhost:6379";
+
+fn envelope(transaction_id: &str) -> String {
+    let data = RawValue::from_string(r#"{"amount":1}"#.to_string()).unwrap();
+    QueueEnvelope::encode(transaction_id, "acct", &data).unwrap()
+}
+
+/// Test a garbage member at the head of the queue is quarantined and the
+/// valid members behind it are still dequeued
+#[tokio::test]
+async fn test_bad_member_is_quarantined() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool.clone());
+    let queue_name = QueueName::new(format!("quarantine_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    // Written directly, ahead of everything else in the queue
+    let mut conn = pool.get().await.unwrap();
+    let _: i32 = conn
+        .zadd(queue_name.priority_key(&KeySpace::default()), "not an envelope {", 0.0)
+        .await
+        .unwrap();
+    queue_manager.add_with_priority(&queue_name, &envelope("tx-1"), 0).await.unwrap();
+    queue_manager.add_with_priority(&queue_name, &envelope("tx-2"), 0).await.unwrap();
+
+    let first = queue_manager.dequeue_envelope(&queue_name).await.unwrap().expect("queue is empty");
+    assert_eq!(first.transaction_id, "tx-1");
+    let second = queue_manager.dequeue_envelope(&queue_name).await.unwrap().expect("queue is empty");
+    assert_eq!(second.transaction_id, "tx-2");
+    assert!(queue_manager.dequeue_envelope(&queue_name).await.unwrap().is_none());
+
+    let quarantined = queue_manager.quarantined(&queue_name, 10).await.unwrap();
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].member, "not an envelope {");
+    assert_eq!(quarantined[0].queue, queue_name.as_str());
+    assert!(quarantined[0].error.starts_with("Serialization error"), "{}", quarantined[0].error);
+}
+
+/// Test quarantined members are listed newest first and deleted by id
+#[tokio::test]
+async fn test_delete_quarantined() {
+    let queue_manager = QueueManager::new(redis_cache::create_pool(REDIS_URL).await.unwrap());
+    let queue_name = QueueName::new(format!("quarantine_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    let older = queue_manager.quarantine(&queue_name, "a", "bad").await.unwrap();
+    let newer = queue_manager.quarantine(&queue_name, "b", "bad").await.unwrap();
+    let listed = queue_manager.quarantined(&queue_name, 10).await.unwrap();
+    assert_eq!(listed, [newer.clone(), older.clone()]);
+    let first_page = queue_manager.quarantined(&queue_name, 1).await.unwrap();
+    assert_eq!(first_page.len(), 1);
+    assert_eq!(first_page[0].id, newer.id);
+
+    assert!(queue_manager.delete_quarantined(&queue_name, &older.id).await.unwrap());
+    assert!(!queue_manager.delete_quarantined(&queue_name, &older.id).await.unwrap());
+    assert_eq!(queue_manager.quarantined(&queue_name, 10).await.unwrap(), [newer]);
+}
@@ -0,0 +1,166 @@
+use deadpool_redis::redis::AsyncCommands;
+use rand::Rng;
+use redis_cache::sequence::{score, SEQUENCE_LIMIT, SEQUENCE_SCORE_BASE};
+use redis_cache::{KeySpace, QueueManager, QueueName, MAX_PRIORITY, MIN_PRIORITY};
+
+const REDIS_URL: &str = "redis://localhost:6379";
+
+/// Test members pop by priority first, then in the order they reached Redis
+#[tokio::test]
+async fn test_priority_then_arrival_order() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("queue_scoring_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    // No pauses: the sequence separates adds however close together they are
+    let adds = [("low", -5), ("a", 0), ("b", 0), ("c", 0), ("high", 5), ("d", 0)];
+    for (member, priority) in adds {
+        queue_manager.add_with_priority(&queue_name, member, priority).await.unwrap();
+    }
+
+    let order = queue_manager.get_priority_queue_order(&queue_name).await.unwrap();
+    assert_eq!(order, ["high", "a", "b", "c", "d", "low"]);
+}
+
+/// Test a thousand members drained fifty at a time come out by priority, then in arrival order
+#[tokio::test]
+async fn test_batches_drain_in_order() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("queue_scoring_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    // A narrow range, so most priorities are shared by many members
+    let mut rng = rand::thread_rng();
+    let mut added: Vec<(i32, usize)> = (0..1000).map(|i| (rng.gen_range(-10..=10), i)).collect();
+    for (priority, i) in &added {
+        queue_manager.add_with_priority(&queue_name, &format!("{}:{}", i, priority), *priority).await.unwrap();
+    }
+
+    let mut drained = Vec::new();
+    loop {
+        let batch = queue_manager.dequeue_batch(&queue_name, 50).await.unwrap();
+        if batch.is_empty() {
+            break;
+        }
+        assert_eq!(batch.len(), 50);
+        drained.extend(batch);
+    }
+    assert!(drained.windows(2).all(|pair| pair[0].1 <= pair[1].1), "scores out of order");
+    added.sort_by_key(|(priority, i)| (-priority, *i));
+    let expected: Vec<String> = added.iter().map(|(priority, i)| format!("{}:{}", i, priority)).collect();
+    let members: Vec<String> = drained.into_iter().map(|(member, _)| member).collect();
+    assert_eq!(members, expected);
+}
+
+/// Test batches return what is left when fewer items are queued than asked for
+#[tokio::test]
+async fn test_short_batches() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("queue_scoring_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    for (member, priority) in [("a", 0), ("b", 1), ("c", 0)] {
+        queue_manager.add_with_priority(&queue_name, member, priority).await.unwrap();
+    }
+    let batch = queue_manager.dequeue_batch(&queue_name, 50).await.unwrap();
+    assert_eq!(batch.iter().map(|(member, _)| member.as_str()).collect::<Vec<_>>(), ["b", "a", "c"]);
+    assert!(queue_manager.dequeue_batch(&queue_name, 50).await.unwrap().is_empty());
+    assert!(queue_manager.dequeue_batch(&queue_name, 0).await.unwrap().is_empty());
+
+    for i in 0..7 {
+        queue_manager.enqueue(&queue_name, &i.to_string()).await.unwrap();
+    }
+    assert_eq!(queue_manager.dequeue_list_batch(&queue_name, 5).await.unwrap(), ["0", "1", "2", "3", "4"]);
+    assert_eq!(queue_manager.dequeue_list_batch(&queue_name, 5).await.unwrap(), ["5", "6"]);
+    assert!(queue_manager.dequeue_list_batch(&queue_name, 5).await.unwrap().is_empty());
+    assert!(queue_manager.dequeue_list_batch(&queue_name, 0).await.unwrap().is_empty());
+}
+
+/// Test positions count only the items still ahead, not everything queued since the queue was last empty
+#[tokio::test]
+async fn test_positions_after_consumption() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool);
+    let queue_name = QueueName::new(format!("queue_scoring_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+
+    for i in 0..100 {
+        queue_manager.enqueue_with_priority(&queue_name, &format!("p{}", i), 0).await.unwrap();
+        queue_manager.enqueue(&queue_name, &format!("l{}", i)).await.unwrap();
+    }
+    assert_eq!(queue_manager.dequeue_batch(&queue_name, 90).await.unwrap().len(), 90);
+    assert_eq!(queue_manager.dequeue_list_batch(&queue_name, 90).await.unwrap().len(), 90);
+
+    assert_eq!(queue_manager.enqueue_with_priority(&queue_name, "p100", 0).await.unwrap(), 11);
+    assert_eq!(queue_manager.enqueue(&queue_name, "l100").await.unwrap(), 11);
+    assert_eq!(queue_manager.get_queue_position(&queue_name, "l100").await.unwrap(), Some(11));
+    assert_eq!(queue_manager.get_queue_position(&queue_name, "l0").await.unwrap(), None);
+
+    // Adds racing at one priority each get a place of their own
+    let add = |member: &'static str| queue_manager.enqueue_with_priority(&queue_name, member, 0);
+    let (a, b, c, d) = tokio::join!(add("p101"), add("p102"), add("p103"), add("p104"));
+    let mut positions = [a.unwrap(), b.unwrap(), c.unwrap(), d.unwrap()];
+    positions.sort_unstable();
+    assert_eq!(positions, [12, 13, 14, 15]);
+}
+
+/// Test the sequence only restarts once the queue is empty
+#[tokio::test]
+async fn test_sequence_rolls_over_on_empty_queue() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let queue_manager = QueueManager::new(pool.clone());
+    let queue_name = QueueName::new(format!("queue_scoring_test_{}", uuid::Uuid::new_v4().simple())).unwrap();
+    let priority_queue = queue_name.priority_key(&KeySpace::default());
+    let mut conn = pool.get().await.unwrap();
+
+    let _: () = conn
+        .set(queue_name.sequence_key(&KeySpace::default()), SEQUENCE_LIMIT - 2)
+        .await
+        .unwrap();
+    queue_manager.add_with_priority(&queue_name, "first", 0).await.unwrap();
+    // Past the limit with a member waiting: pinned to the last sequence
+    queue_manager.add_with_priority(&queue_name, "second", 0).await.unwrap();
+    let scores: Vec<(String, f64)> = conn.zrange_withscores(&priority_queue, 0, -1).await.unwrap();
+    assert_eq!(
+        scores,
+        [
+            ("first".to_string(), score(0, SEQUENCE_LIMIT - 1)),
+            ("second".to_string(), score(0, SEQUENCE_LIMIT - 1)),
+        ]
+    );
+
+    queue_manager.dequeue_by_priority(&queue_name).await.unwrap();
+    queue_manager.dequeue_by_priority(&queue_name).await.unwrap();
+    queue_manager.add_with_priority(&queue_name, "third", 0).await.unwrap();
+    let third: Option<f64> = conn.zscore(&priority_queue, "third").await.unwrap();
+    assert_eq!(third, Some(score(0, 1)));
+}
+
+/// Test every sequence is exact and stays inside its priority
+#[test]
+fn test_score_precision() {
+    for priority in [MIN_PRIORITY, -1, 0, 1, MAX_PRIORITY] {
+        let base = (SEQUENCE_SCORE_BASE - priority) as f64;
+        assert!(score(priority, 1) > base);
+        assert!(score(priority, SEQUENCE_LIMIT - 2) < score(priority, SEQUENCE_LIMIT - 1));
+        assert!(score(priority, SEQUENCE_LIMIT - 1) < base + 1.0);
+        // The fraction survives the round trip exactly
+        let fraction = score(priority, 123_456_789_012) - base;
+        assert_eq!(fraction * SEQUENCE_LIMIT as f64, 123_456_789_012.0);
+    }
+    // A higher priority always pops first, whatever the sequences
+    assert!(score(1, SEQUENCE_LIMIT - 1) < score(0, 1));
+    // Out of range priorities and sequences are clamped rather than overlap
+    assert_eq!(score(MAX_PRIORITY + 10, 1), score(MAX_PRIORITY, 1));
+    assert_eq!(score(0, SEQUENCE_LIMIT * 2), score(0, SEQUENCE_LIMIT - 1));
+}
+
+/// Test members queued under the old timestamp scores are served first
+#[test]
+fn test_sequenced_members_follow_timestamp_scores() {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as f64;
+    let oldest_timestamp_score = (1000 - MIN_PRIORITY) as f64 + nanos / 1e15;
+    assert!(oldest_timestamp_score < score(MAX_PRIORITY, 0));
+}
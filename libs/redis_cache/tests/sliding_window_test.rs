@@ -0,0 +1,374 @@
+use deadpool_redis::redis::AsyncCommands;
+use redis_cache::{KeyLayout, RateLimiter, WindowAlignment, SLIDING_WINDOW_TTL_BUFFER_MS};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REDIS_URL: &str = "redis://localhost:6379";
+const WINDOW_SECONDS: u64 = 60;
+
+fn now_nanos() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos()
+}
+
+/// Unix second by which a request at `nanos` has left the window
+fn reset_after(nanos: u128) -> u64 {
+    (nanos + WINDOW_SECONDS as u128 * 1_000_000_000).div_ceil(1_000_000_000) as u64
+}
+
+/// A key no other run shares
+fn unique_key(test: &str) -> String {
+    format!("sliding_window_test:{}:{}:{}", test, std::process::id(), now_nanos())
+}
+
+/// Test concurrent requests stamped with the same nanosecond are each counted
+#[tokio::test]
+async fn test_same_nanosecond_requests_all_count() {
+    const TASKS: usize = 8;
+    const CALLS_PER_TASK: usize = 25;
+
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let key = unique_key("hammer");
+    // Every request lands in the same nanosecond
+    let frozen = now_nanos();
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let limiter = RateLimiter::new(pool.clone()).with_clock(move || frozen);
+            let key = key.clone();
+            tokio::spawn(async move {
+                for _ in 0..CALLS_PER_TASK {
+                    limiter.check_rate_limit(&key, 10_000, WINDOW_SECONDS).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let limiter = RateLimiter::new(pool).with_clock(move || frozen);
+    let usage = limiter.sliding_window_usage(&key, WINDOW_SECONDS).await.unwrap();
+    assert_eq!(usage, (TASKS * CALLS_PER_TASK) as u64);
+}
+
+/// Test the limit is enforced exactly when the clock does not move
+#[tokio::test]
+async fn test_limit_enforced_with_constant_clock() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let key = unique_key("constant");
+    let frozen = now_nanos();
+    let limiter = RateLimiter::new(pool).with_clock(move || frozen);
+
+    for expected_remaining in (0..5).rev() {
+        let result = limiter.check_rate_limit(&key, 5, WINDOW_SECONDS).await.unwrap();
+        assert!(result.allowed);
+        assert_eq!(result.remaining, expected_remaining);
+    }
+
+    let result = limiter.check_rate_limit(&key, 5, WINDOW_SECONDS).await.unwrap();
+    assert!(!result.allowed);
+    assert_eq!(result.reset_at, reset_after(frozen));
+}
+
+/// Test peeking reports what the next check would see without using any of the limit, on both window kinds
+#[tokio::test]
+async fn test_peek_uses_no_quota() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let key = unique_key("peek");
+    let frozen = now_nanos();
+    let limiter = RateLimiter::new(pool).with_clock(move || frozen);
+
+    let untouched = limiter.peek(&key, 5, WINDOW_SECONDS).await.unwrap();
+    assert!(untouched.allowed);
+    assert_eq!(untouched.remaining, 5);
+    for _ in 0..3 {
+        limiter.check_rate_limit(&key, 5, WINDOW_SECONDS).await.unwrap();
+    }
+    for _ in 0..10 {
+        let peeked = limiter.peek(&key, 5, WINDOW_SECONDS).await.unwrap();
+        assert!(peeked.allowed);
+        assert_eq!(peeked.remaining, 2);
+        assert_eq!(peeked.reset_at, reset_after(frozen));
+    }
+    assert_eq!(limiter.sliding_window_usage(&key, WINDOW_SECONDS).await.unwrap(), 3);
+
+    assert_eq!(limiter.check_rate_limit(&key, 5, WINDOW_SECONDS).await.unwrap().remaining, 1);
+    limiter.check_rate_limit(&key, 5, WINDOW_SECONDS).await.unwrap();
+    let exhausted = limiter.peek(&key, 5, WINDOW_SECONDS).await.unwrap();
+    assert!(!exhausted.allowed);
+    assert_eq!(exhausted.remaining, 0);
+
+    let fixed_key = unique_key("peek_fixed");
+    for _ in 0..2 {
+        limiter.check_fixed_window(&fixed_key, 5, WINDOW_SECONDS, WindowAlignment::Aligned).await.unwrap();
+    }
+    for _ in 0..3 {
+        let peeked = limiter.peek_fixed_window(&fixed_key, 5, WINDOW_SECONDS, WindowAlignment::Aligned).await.unwrap();
+        assert_eq!(peeked.remaining, 3);
+    }
+}
+
+/// Test retries while limited are not recorded, and the client is let back in exactly when
+/// its oldest allowed request ages out, on both implementations
+#[tokio::test]
+async fn test_rejected_requests_do_not_extend_the_window() {
+    const SECOND: u64 = 1_000_000_000;
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+
+    for script in [false, true] {
+        let key = unique_key(if script { "retry_script" } else { "retry_commands" });
+        let start = now_nanos() as u64;
+        let clock = Arc::new(AtomicU64::new(start));
+        let limiter = {
+            let clock = clock.clone();
+            RateLimiter::new(pool.clone()).with_clock(move || clock.load(Ordering::SeqCst) as u128)
+        };
+        let check = |at: u64| {
+            clock.store(at, Ordering::SeqCst);
+            let (limiter, key) = (&limiter, &key);
+            async move {
+                if script {
+                    limiter.check_rate_limit_script(key, 5, WINDOW_SECONDS).await.unwrap()
+                } else {
+                    limiter.check_rate_limit(key, 5, WINDOW_SECONDS).await.unwrap()
+                }
+            }
+        };
+
+        // Five allowed a second apart, then fifty retries while limited
+        for i in 0..5 {
+            assert!(check(start + i * SECOND).await.allowed);
+        }
+        for i in 0..50 {
+            let result = check(start + 10 * SECOND + i * 100_000_000).await;
+            assert!(!result.allowed);
+            assert_eq!(result.reset_at, reset_after(start as u128), "{:?}", result);
+        }
+        assert_eq!(limiter.sliding_window_usage(&key, WINDOW_SECONDS).await.unwrap(), 5);
+
+        let oldest_ages_out = start + WINDOW_SECONDS * SECOND;
+        // Scores are f64 nanoseconds, which resolve to well under a millisecond
+        assert!(!check(oldest_ages_out - 1_000_000).await.allowed);
+        let result = check(oldest_ages_out).await;
+        assert!(result.allowed, "script {}", script);
+        assert_eq!(result.remaining, 0);
+        // The next slot frees when the second request ages out
+        assert_eq!(result.reset_at, reset_after((start + SECOND) as u128));
+        assert!(!check(oldest_ages_out + 1_000_000).await.allowed);
+    }
+}
+
+/// Test limits above i32::MAX allow requests and report what is left
+#[tokio::test]
+async fn test_limit_above_i32_max() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let limiter = RateLimiter::new(pool);
+    let max_requests = u32::MAX;
+
+    let result = limiter.check_rate_limit(&unique_key("huge"), max_requests, WINDOW_SECONDS).await.unwrap();
+    assert!(result.allowed);
+    assert_eq!(result.remaining, max_requests - 1);
+
+    let result = limiter
+        .check_rate_limit_script(&unique_key("huge_script"), max_requests, WINDOW_SECONDS)
+        .await
+        .unwrap();
+    assert!(result.allowed);
+    assert_eq!(result.remaining, max_requests - 1);
+}
+
+/// Test the scripted check enforces the same limit on the same window as the command one
+#[tokio::test]
+async fn test_script_shares_window_with_commands() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let key = unique_key("script");
+    let frozen = now_nanos();
+    let limiter = RateLimiter::new(pool).with_clock(move || frozen);
+
+    for expected_remaining in (0..6).rev() {
+        // Alternate implementations, as a partial rollout would for one account
+        let result = if expected_remaining % 2 == 0 {
+            limiter.check_rate_limit_script(&key, 6, WINDOW_SECONDS).await.unwrap()
+        } else {
+            limiter.check_rate_limit(&key, 6, WINDOW_SECONDS).await.unwrap()
+        };
+        assert!(result.allowed);
+        assert_eq!(result.remaining, expected_remaining);
+    }
+
+    let result = limiter.check_rate_limit_script(&key, 6, WINDOW_SECONDS).await.unwrap();
+    assert!(!result.allowed);
+    assert_eq!(result.remaining, 0);
+    assert_eq!(result.reset_at, reset_after(frozen));
+}
+
+/// Test a weighted request uses its cost of the window on both implementations
+#[tokio::test]
+async fn test_weighted_requests_use_their_cost() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let key = unique_key("weighted");
+    let frozen = now_nanos();
+    let limiter = RateLimiter::new(pool).with_clock(move || frozen);
+
+    let result = limiter.check_weighted_rate_limit(&key, 3, 10, WINDOW_SECONDS).await.unwrap();
+    assert!(result.allowed);
+    assert_eq!(result.remaining, 7);
+
+    let result = limiter
+        .check_weighted_rate_limit_script(&key, 3, 10, WINDOW_SECONDS)
+        .await
+        .unwrap();
+    assert!(result.allowed);
+    assert_eq!(result.remaining, 4);
+    assert_eq!(limiter.sliding_window_usage(&key, WINDOW_SECONDS).await.unwrap(), 6);
+
+    // Cost 5 no longer fits in the 4 left
+    let result = limiter.check_weighted_rate_limit(&key, 5, 10, WINDOW_SECONDS).await.unwrap();
+    assert!(!result.allowed);
+}
+
+/// Test requests a second apart see remaining fall by one each while the reset stays on the
+/// first request, on both implementations
+#[tokio::test]
+async fn test_sequential_remaining_and_reset() {
+    const SECOND: u64 = 1_000_000_000;
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+
+    for script in [false, true] {
+        let key = unique_key(if script { "sequential_script" } else { "sequential_commands" });
+        let start = now_nanos() as u64;
+        let clock = Arc::new(AtomicU64::new(start));
+        let limiter = {
+            let clock = clock.clone();
+            RateLimiter::new(pool.clone()).with_clock(move || clock.load(Ordering::SeqCst) as u128)
+        };
+        for (i, expected_remaining) in (90..100).rev().enumerate() {
+            // now + window would move forward a second every request
+            clock.store(start + i as u64 * SECOND, Ordering::SeqCst);
+            let result = if script {
+                limiter.check_rate_limit_script(&key, 100, WINDOW_SECONDS).await.unwrap()
+            } else {
+                limiter.check_rate_limit(&key, 100, WINDOW_SECONDS).await.unwrap()
+            };
+            assert!(result.allowed);
+            assert_eq!(result.remaining, expected_remaining, "script: {}", script);
+            assert_eq!(result.reset_at, reset_after(start as u128), "script: {}", script);
+        }
+    }
+}
+
+/// Test concurrent requests are each told their own remaining count, on both implementations
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_requests_see_distinct_remaining() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+
+    for script in [false, true] {
+        let key = unique_key(if script { "distinct_script" } else { "distinct_commands" });
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let limiter = RateLimiter::new(pool.clone());
+                let key = key.clone();
+                tokio::spawn(async move {
+                    if script {
+                        limiter.check_rate_limit_script(&key, 100, WINDOW_SECONDS).await.unwrap()
+                    } else {
+                        limiter.check_rate_limit(&key, 100, WINDOW_SECONDS).await.unwrap()
+                    }
+                })
+            })
+            .collect();
+        let mut remaining = Vec::new();
+        for task in tasks {
+            remaining.push(task.await.unwrap().remaining);
+        }
+        remaining.sort_unstable();
+        assert_eq!(remaining, (80..100).collect::<Vec<u32>>(), "script: {}", script);
+    }
+}
+
+async fn ttl(pool: &redis_cache::RedisPool, key: &str) -> i64 {
+    let mut conn = pool.get().await.unwrap();
+    conn.ttl(KeyLayout::Standalone.sliding_window(key)).await.unwrap()
+}
+
+/// Assert the key expires after the window plus its buffer, and not sooner
+async fn assert_window_ttl(pool: &redis_cache::RedisPool, key: &str, after: &str) {
+    let mut conn = pool.get().await.unwrap();
+    let remaining: i64 = conn.pttl(KeyLayout::Standalone.sliding_window(key)).await.unwrap();
+    let window_ms = WINDOW_SECONDS as i64 * 1000;
+    assert!(
+        remaining > window_ms && remaining <= window_ms + SLIDING_WINDOW_TTL_BUFFER_MS as i64,
+        "pttl {} after {}",
+        remaining,
+        after
+    );
+}
+
+/// Test the key has a TTL within range after allowed, denied and failed checks on both implementations
+#[tokio::test]
+async fn test_window_key_always_has_ttl() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let limiter = RateLimiter::new(pool.clone());
+
+    for script in [false, true] {
+        let check = |key: String, window_seconds: u64| {
+            let limiter = &limiter;
+            async move {
+                if script {
+                    limiter.check_rate_limit_script(&key, 1, window_seconds).await
+                } else {
+                    limiter.check_rate_limit(&key, 1, window_seconds).await
+                }
+            }
+        };
+        let key = unique_key(if script { "ttl_script" } else { "ttl_commands" });
+
+        assert!(check(key.clone(), WINDOW_SECONDS).await.unwrap().allowed);
+        assert_window_ttl(&pool, &key, "an allowed request").await;
+
+        // A denied request pushes the expiry out again from now
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!check(key.clone(), WINDOW_SECONDS).await.unwrap().allowed);
+        assert_window_ttl(&pool, &key, "a denied request").await;
+
+        // A check that fails leaves the key, and its expiry, as they were
+        assert!(check(key.clone(), 0).await.is_err());
+        assert_window_ttl(&pool, &key, "a failed request").await;
+    }
+
+    // A key that only ever rejected is still given an expiry
+    let key = unique_key("rejected_only");
+    let _: i64 = pool
+        .get()
+        .await
+        .unwrap()
+        .zadd(KeyLayout::Standalone.sliding_window(&key), "earlier", now_nanos() as f64)
+        .await
+        .unwrap();
+    assert!(!limiter.check_rate_limit_script(&key, 1, WINDOW_SECONDS).await.unwrap().allowed);
+    assert_window_ttl(&pool, &key, "rejections only").await;
+}
+
+/// Test the repair sweep expires keys without a TTL and leaves others alone
+#[tokio::test]
+async fn test_repair_missing_expiry() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let limiter = RateLimiter::new(pool.clone());
+    let stale = unique_key("stale");
+    let live = unique_key("live");
+    {
+        let mut conn = pool.get().await.unwrap();
+        let _: () = conn.zadd(KeyLayout::Standalone.sliding_window(&stale), "member", 1).await.unwrap();
+    }
+    limiter.check_rate_limit(&live, 10, WINDOW_SECONDS).await.unwrap();
+    assert_eq!(ttl(&pool, &stale).await, -1);
+
+    let repair = limiter.repair_missing_expiry(3600).await.unwrap();
+
+    assert!(repair.repaired >= 1);
+    assert!(repair.scanned >= 2);
+    let remaining = ttl(&pool, &stale).await;
+    assert!(remaining > WINDOW_SECONDS as i64 && remaining <= 3600, "ttl {}", remaining);
+    assert!(ttl(&pool, &live).await <= WINDOW_SECONDS as i64 + 1);
+}
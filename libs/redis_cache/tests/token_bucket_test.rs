@@ -0,0 +1,185 @@
+use deadpool_redis::redis::AsyncCommands;
+use redis_cache::{KeyLayout, RateLimitStrategy, RateLimiter, RedisPool, TokenBucket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REDIS_URL: &str = "redis://localhost:6379";
+const STRATEGIES: [RateLimitStrategy; 2] = [RateLimitStrategy::SlidingWindow, RateLimitStrategy::TokenBucket];
+
+/// Current Unix second, in milliseconds, so reset times come out whole
+fn whole_second_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() * 1000
+}
+
+/// A key no other run shares
+fn unique_key(test: &str, strategy: RateLimitStrategy) -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    format!("token_bucket_test:{}:{}:{}:{}", test, strategy, std::process::id(), nanos)
+}
+
+/// Limiter reading the time, in milliseconds, from `clock`
+fn limiter(pool: &RedisPool, strategy: RateLimitStrategy, clock: &Arc<AtomicU64>) -> RateLimiter {
+    let clock = clock.clone();
+    RateLimiter::new_with_strategy(pool.clone(), strategy)
+        .with_clock(move || clock.load(Ordering::SeqCst) as u128 * 1_000_000)
+}
+
+/// Requests allowed out of `attempts` made at once
+async fn allowed_of(limiter: &RateLimiter, key: &str, attempts: u32, max_requests: u32, window_seconds: u64) -> u32 {
+    let mut allowed = 0;
+    for _ in 0..attempts {
+        if limiter.check_rate_limit(key, max_requests, window_seconds).await.unwrap().allowed {
+            allowed += 1;
+        }
+    }
+    allowed
+}
+
+/// Test both strategies let a client bursting once per window through exactly the limit each window
+#[tokio::test]
+async fn test_both_allow_exactly_the_limit_per_window() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    for strategy in STRATEGIES {
+        let start = whole_second_ms();
+        let clock = Arc::new(AtomicU64::new(start));
+        let limiter = limiter(&pool, strategy, &clock);
+        let key = unique_key("boundary", strategy);
+
+        for expected_remaining in (0..5).rev() {
+            let result = limiter.check_rate_limit(&key, 5, 60).await.unwrap();
+            assert!(result.allowed, "{}", strategy);
+            assert_eq!(result.remaining, expected_remaining, "{}", strategy);
+        }
+        assert!(!limiter.check_rate_limit(&key, 5, 60).await.unwrap().allowed, "{}", strategy);
+
+        // A whole window later the limit is all back, and no more than that
+        clock.store(start + 60_000, Ordering::SeqCst);
+        assert_eq!(allowed_of(&limiter, &key, 8, 5, 60).await, 5, "{}", strategy);
+        clock.store(start + 120_000, Ordering::SeqCst);
+        assert_eq!(allowed_of(&limiter, &key, 8, 5, 60).await, 5, "{}", strategy);
+    }
+}
+
+/// Test after a burst the bucket lets each token through as it arrives, while the window waits for the burst to age out
+#[tokio::test]
+async fn test_burst_then_steady_state() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let start = whole_second_ms();
+    let mut allowed = Vec::new();
+    for strategy in STRATEGIES {
+        let clock = Arc::new(AtomicU64::new(start));
+        let limiter = limiter(&pool, strategy, &clock);
+        let key = unique_key("steady", strategy);
+
+        // 10 per 10 seconds: both take the whole burst
+        assert_eq!(allowed_of(&limiter, &key, 12, 10, 10).await, 10, "{}", strategy);
+        let steady: Vec<u32> = {
+            let mut steady = Vec::new();
+            for second in 1..=10 {
+                clock.store(start + second * 1000, Ordering::SeqCst);
+                steady.push(allowed_of(&limiter, &key, 2, 10, 10).await);
+            }
+            steady
+        };
+        allowed.push(steady);
+    }
+
+    // The window stays shut until the burst ages out, then opens all at once
+    assert_eq!(allowed[0], [0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+    // The bucket refills one token a second and never holds more back
+    assert_eq!(allowed[1], [1; 10]);
+}
+
+/// Test a rejected request is told when enough tokens arrive, and retrying meanwhile does not push that back
+#[tokio::test]
+async fn test_reset_at_is_next_token() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let start = whole_second_ms();
+    let clock = Arc::new(AtomicU64::new(start));
+    let limiter = limiter(&pool, RateLimitStrategy::TokenBucket, &clock);
+    let key = unique_key("reset", RateLimitStrategy::TokenBucket);
+    // 3 burst, one token every 2 seconds
+    let bucket = TokenBucket {
+        burst: 3,
+        refill: 1,
+        refill_seconds: 2,
+    };
+
+    let first = limiter.check_token_bucket(&key, 1, &bucket).await.unwrap();
+    assert_eq!((first.allowed, first.remaining), (true, 2));
+    // The token just taken is back two seconds later
+    assert_eq!(first.reset_at, start / 1000 + 2);
+    let last = limiter.check_token_bucket(&key, 2, &bucket).await.unwrap();
+    assert_eq!((last.allowed, last.remaining), (true, 0));
+    assert_eq!(last.reset_at, start / 1000 + 2);
+
+    clock.store(start + 500, Ordering::SeqCst);
+    for _ in 0..3 {
+        let rejected = limiter.check_token_bucket(&key, 1, &bucket).await.unwrap();
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.reset_at, start / 1000 + 2);
+    }
+    // A request costing two waits for both
+    assert_eq!(limiter.check_token_bucket(&key, 2, &bucket).await.unwrap().reset_at, start / 1000 + 4);
+
+    clock.store(start + 2000, Ordering::SeqCst);
+    assert!(limiter.check_token_bucket(&key, 1, &bucket).await.unwrap().allowed);
+}
+
+/// Test peeking a bucket reports what the next check would see without taking a token
+#[tokio::test]
+async fn test_peek_takes_no_token() {
+    let pool = redis_cache::create_pool(REDIS_URL).await.unwrap();
+    let start = whole_second_ms();
+    let clock = Arc::new(AtomicU64::new(start));
+    let limiter = limiter(&pool, RateLimitStrategy::TokenBucket, &clock);
+    let key = unique_key("peek", RateLimitStrategy::TokenBucket);
+
+    assert_eq!(limiter.peek(&key, 4, 4).await.unwrap().remaining, 4);
+    assert_eq!(allowed_of(&limiter, &key, 4, 4, 4).await, 4);
+    for _ in 0..5 {
+        let peeked = limiter.peek(&key, 4, 4).await.unwrap();
+        assert!(!peeked.allowed);
+        assert_eq!((peeked.remaining, peeked.reset_at), (0, start / 1000 + 1));
+    }
+    clock.store(start + 2500, Ordering::SeqCst);
+    let peeked = limiter.peek(&key, 4, 4).await.unwrap();
+    assert_eq!((peeked.allowed, peeked.remaining), (true, 2));
+    assert_eq!(allowed_of(&limiter, &key, 4, 4, 4).await, 2);
+
+    let mut conn = pool.get().await.unwrap();
+    let ttl_ms: i64 = conn.pttl(KeyLayout::Standalone.token_bucket(&key)).await.unwrap();
+    assert!(ttl_ms > 4000 && ttl_ms <= 5000, "ttl {}", ttl_ms);
+}
+
+/// Test refills add whole tokens exactly, stop at the burst and never run backwards
+#[test]
+fn test_refill_arithmetic() {
+    let bucket = TokenBucket::for_window(10, 60);
+    assert_eq!(bucket.refill_per_second(), 10.0 / 60.0);
+    assert_eq!(bucket.fill_ms(), 60_000);
+    // 6 seconds of a 10 per minute refill is one token, not a hair under
+    assert_eq!(bucket.refilled(0.0, 0, 6_000), 1.0);
+    assert_eq!(bucket.refilled(2.0 / 3.0, 0, 2_000), 1.0);
+    assert_eq!(bucket.refilled(9.5, 0, 60_000), 10.0);
+    assert_eq!(bucket.refilled(3.0, 5_000, 1_000), 3.0);
+
+    assert_eq!(bucket.available_at(0.0, 1.0, 1_000), 7);
+    assert_eq!(bucket.next_token_at(10.0, 1_500), 2);
+    assert_eq!(bucket.available_at(0.0, 50.0, 0), 60);
+
+    assert!(TokenBucket::for_window(5, 0).validate().is_err());
+    assert!(TokenBucket { burst: 5, refill: 0, refill_seconds: 60 }.validate().is_err());
+    assert!(TokenBucket::for_window(0, 60).validate().is_ok());
+}
+
+/// Test strategies round trip through their stored names
+#[test]
+fn test_strategy_names() {
+    for strategy in STRATEGIES {
+        assert_eq!(strategy.as_str().parse::<RateLimitStrategy>().unwrap(), strategy);
+    }
+    assert_eq!(RateLimitStrategy::default(), RateLimitStrategy::SlidingWindow);
+    assert!(RateLimitStrategy::parse("leaky_bucket").is_none());
+}
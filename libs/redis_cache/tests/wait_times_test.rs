@@ -0,0 +1,101 @@
+use redis_cache::wait_times::WAIT_SAMPLE_CAPACITY;
+use redis_cache::{PriorityBand, QueueManager, QueueName, WaitPercentiles};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REDIS_URL: &str = "redis://localhost:6379";
+
+/// A queue name no other run shares
+fn unique_queue(test: &str) -> QueueName {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    QueueName::new(format!("wait_times_test_{}_{}_{}", test, std::process::id(), nanos)).unwrap()
+}
+
+/// Deterministic shuffle so percentiles never depend on insertion order
+fn shuffled(mut samples: Vec<u64>) -> Vec<u64> {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    for i in (1..samples.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        samples.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+    samples
+}
+
+fn within(actual: u64, expected: f64, tolerance: f64) -> bool {
+    (actual as f64 - expected).abs() <= expected * tolerance
+}
+
+/// Test a uniform distribution yields its exact nearest-rank percentiles
+#[test]
+fn test_uniform_percentiles() {
+    let mut samples = shuffled((1..=1000).collect());
+    let percentiles = WaitPercentiles::from_samples(PriorityBand::Normal, &mut samples).unwrap();
+
+    assert_eq!(percentiles.samples, 1000);
+    assert_eq!((percentiles.p50_ms, percentiles.p95_ms, percentiles.p99_ms), (500, 950, 990));
+}
+
+/// Test an exponential distribution's percentiles land near their analytic values
+#[test]
+fn test_exponential_percentiles() {
+    // Inverse CDF at evenly spaced quantiles of an exponential with a 200ms mean
+    let mean = 200.0;
+    let samples: Vec<u64> = (0..1000)
+        .map(|i| (-mean * (1.0 - (i as f64 + 0.5) / 1000.0).ln()).round() as u64)
+        .collect();
+    let mut samples = shuffled(samples);
+    let percentiles = WaitPercentiles::from_samples(PriorityBand::High, &mut samples).unwrap();
+
+    assert!(within(percentiles.p50_ms, mean * 2f64.ln(), 0.02), "{:?}", percentiles);
+    assert!(within(percentiles.p95_ms, mean * 20f64.ln(), 0.02), "{:?}", percentiles);
+    assert!(within(percentiles.p99_ms, mean * 100f64.ln(), 0.05), "{:?}", percentiles);
+}
+
+/// Test a single sample is every percentile, and no samples report nothing
+#[test]
+fn test_degenerate_samples() {
+    let percentiles = WaitPercentiles::from_samples(PriorityBand::Low, &mut [42]).unwrap();
+    assert_eq!((percentiles.p50_ms, percentiles.p95_ms, percentiles.p99_ms), (42, 42, 42));
+    assert!(WaitPercentiles::from_samples(PriorityBand::Low, &mut []).is_none());
+}
+
+/// Test priorities map onto the bands around the default of 0
+#[test]
+fn test_priority_bands() {
+    assert_eq!(PriorityBand::of(-1000), PriorityBand::Low);
+    assert_eq!(PriorityBand::of(-1), PriorityBand::Low);
+    assert_eq!(PriorityBand::of(0), PriorityBand::Normal);
+    assert_eq!(PriorityBand::of(1), PriorityBand::High);
+    assert_eq!(PriorityBand::of(1000), PriorityBand::High);
+}
+
+/// Test recorded waits are kept per band and only the latest samples count
+#[tokio::test]
+async fn test_recorded_waits_are_bounded_per_band() {
+    let queue_manager = QueueManager::new(redis_cache::create_pool(REDIS_URL).await.unwrap());
+    let queue = unique_queue("bounded");
+
+    // Older, slower samples that the latest ones push out of the buffer
+    for _ in 0..500 {
+        queue_manager.record_wait(&queue, 0, 60_000).await.unwrap();
+    }
+    for wait_ms in shuffled((1..=WAIT_SAMPLE_CAPACITY as u64).collect()) {
+        queue_manager.record_wait(&queue, 0, wait_ms).await.unwrap();
+    }
+    for _ in 0..10 {
+        queue_manager.record_wait(&queue, 50, 5).await.unwrap();
+    }
+
+    let report = queue_manager.wait_percentiles(&queue).await.unwrap();
+    assert_eq!(report.len(), 2, "{:?}", report);
+
+    let normal = &report[0];
+    assert_eq!(normal.band, PriorityBand::Normal);
+    assert_eq!(normal.samples, WAIT_SAMPLE_CAPACITY);
+    assert_eq!((normal.p50_ms, normal.p95_ms, normal.p99_ms), (500, 950, 990));
+
+    let high = &report[1];
+    assert_eq!(high.band, PriorityBand::High);
+    assert_eq!((high.samples, high.p99_ms), (10, 5));
+}
@@ -0,0 +1,63 @@
+use redis_cache::window::{window_offset, FixedWindow, WindowAlignment};
+
+const WINDOW: u64 = 60;
+const NOW: u64 = 1_700_000_000;
+
+/// Test different accounts get different boundaries within the same window length
+#[test]
+fn test_accounts_get_different_boundaries() {
+    let a = FixedWindow::for_key("acct_alpha", NOW, WINDOW, WindowAlignment::Jittered);
+    let b = FixedWindow::for_key("acct_beta", NOW, WINDOW, WindowAlignment::Jittered);
+    assert_ne!(a.reset_at, b.reset_at);
+
+    // Across many accounts the resets spread over most of the window
+    let offsets: std::collections::HashSet<u64> = (0..1000)
+        .map(|i| window_offset(&format!("account_{}", i), WINDOW))
+        .collect();
+    assert!(offsets.len() > 50, "only {} distinct offsets", offsets.len());
+}
+
+/// Test an account's boundary is the same on every request inside a window
+#[test]
+fn test_boundary_is_stable_per_account() {
+    let first = FixedWindow::for_key("acct_alpha", NOW, WINDOW, WindowAlignment::Jittered);
+    for now in first.start..first.reset_at {
+        assert_eq!(FixedWindow::for_key("acct_alpha", now, WINDOW, WindowAlignment::Jittered), first);
+    }
+
+    // The next window starts exactly where this one resets
+    let next = FixedWindow::for_key("acct_alpha", first.reset_at, WINDOW, WindowAlignment::Jittered);
+    assert_eq!(next.start, first.reset_at);
+    assert_eq!(next.index, first.index + 1);
+    assert_eq!(window_offset("acct_alpha", WINDOW), first.reset_at % WINDOW);
+}
+
+/// Test the offset is pinned so boundaries do not move between releases
+#[test]
+fn test_offset_is_deterministic() {
+    assert_eq!(window_offset("acct_alpha", WINDOW), window_offset("acct_alpha", WINDOW));
+    assert_eq!(window_offset("", WINDOW), 0xcbf2_9ce4_8422_2325 % WINDOW);
+    assert!(window_offset("acct_alpha", WINDOW) < WINDOW);
+    assert_eq!(window_offset("acct_alpha", 0), 0);
+}
+
+/// Test aligned accounts reset on wall-clock multiples of the window
+#[test]
+fn test_aligned_windows_ignore_jitter() {
+    let window = FixedWindow::for_key("acct_alpha", NOW + 7, WINDOW, WindowAlignment::Aligned);
+    assert_eq!(window.reset_at % WINDOW, 0);
+    assert_eq!(window.reset_at - window.start, WINDOW);
+    assert!(window.start <= NOW + 7 && NOW + 7 < window.reset_at);
+}
+
+/// Test every point in time falls inside the window reported for it
+#[test]
+fn test_window_contains_now() {
+    for offset in [0, 1, 17, 59] {
+        for now in NOW..NOW + 2 * WINDOW {
+            let window = FixedWindow::containing(now, WINDOW, offset);
+            assert!(window.start <= now && now < window.reset_at, "now={} offset={}", now, offset);
+            assert_eq!(window.reset_at % WINDOW, offset);
+        }
+    }
+}
@@ -0,0 +1,585 @@
+use crate::{
+    serialize_redacted_optional_url, split_list, validate_redis_key_prefix, CommonConfig, ConfigError, ConfigResult,
+    Env,
+};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Database URL used in memory mode when DATABASE_URL is unset; it is never connected to
+const MEMORY_DATABASE_URL: &str = "postgres://localhost/unused";
+
+/// Longest rate limit window, 30 days; the same bound the limiter enforces
+pub const MAX_RATE_WINDOW_SECONDS: u64 = 86_400 * 30;
+/// SUBMIT_TIER_LIMITS unless configured otherwise: the tiers account ids
+/// are generated with, lower tiers waiting longer in the estimates
+pub const DEFAULT_TIER_LIMITS: &str = "basic_:10:1.5,premium_:100:1,enterprise_:1000:0.5";
+
+/// An admin API key and the identity it authenticates as
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminApiKey {
+    pub id: String,
+    #[serde(skip)]
+    pub key: String,
+}
+
+/// Submit limit of the accounts whose ids start with `prefix`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TierLimit {
+    pub prefix: String,
+    pub max_requests: u32,
+    /// Scales the estimated processing time of the tier's submits
+    pub estimate_multiplier: f64,
+}
+
+/// Algorithm used for per-account submit limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    /// Log of request timestamps; the window trails each request
+    #[default]
+    SlidingWindow,
+    /// Counter per window, with boundaries offset per account
+    FixedWindow,
+}
+
+impl FromStr for RateLimitAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sliding_window" => Ok(Self::SlidingWindow),
+            "fixed_window" => Ok(Self::FixedWindow),
+            other => Err(format!(
+                "unknown algorithm {:?}, expected sliding_window or fixed_window",
+                other
+            )),
+        }
+    }
+}
+
+/// Where the API keeps transactions, queue entries and rate limit windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Postgres and Redis
+    #[default]
+    Services,
+    /// Process memory, for running the API alone during development. Nothing
+    /// survives a restart and no worker drains the queue.
+    Memory,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "services" => Ok(Self::Services),
+            "memory" => Ok(Self::Memory),
+            other => Err(format!("unknown backend {:?}, expected services or memory", other)),
+        }
+    }
+}
+
+/// What submit and the rate limit layers do when Redis cannot be reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedisFailureMode {
+    /// Refuse submits and rate limited requests with a 503
+    #[default]
+    FailClosed,
+    /// Accept submits without rate limiting or pending caps, positioned by
+    /// the account's pending rows in Postgres. They are stored but have no
+    /// queue entry, so no worker picks them up until they are queued again.
+    /// Other rate limited requests are let through unlimited.
+    FailOpen,
+}
+
+impl FromStr for RedisFailureMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fail_closed" => Ok(Self::FailClosed),
+            "fail_open" => Ok(Self::FailOpen),
+            other => Err(format!("unknown mode {:?}, expected fail_closed or fail_open", other)),
+        }
+    }
+}
+
+/// How much of an account's submit budget one submit uses, by priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityCostCurve {
+    /// Every submit costs 1
+    #[default]
+    Flat,
+    /// A submit costs 1 plus its priority divided by `step`, rounded to the
+    /// nearest whole number; priorities of 0 and below cost 1
+    Linear { step: u32 },
+}
+
+impl PriorityCostCurve {
+    pub fn cost(&self, priority: i32) -> u32 {
+        match *self {
+            Self::Flat => 1,
+            Self::Linear { step } => 1 + (priority.max(0) as u32 + step / 2) / step,
+        }
+    }
+}
+
+impl FromStr for PriorityCostCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        if s == "flat" {
+            return Ok(Self::Flat);
+        }
+        match s.strip_prefix("linear:").map(str::parse) {
+            Some(Ok(step)) if step > 0 => Ok(Self::Linear { step }),
+            _ => Err(format!("unknown curve {:?}, expected flat or linear:<step> with step > 0", s)),
+        }
+    }
+}
+
+/// Startup warm-up of connection pools before the listeners open
+#[derive(Debug, Clone, Serialize)]
+pub struct WarmupConfig {
+    pub enabled: bool,
+    /// Redis connections to open before serving
+    pub redis_connections: usize,
+    /// Upper bound on the whole warm-up; serving starts when it runs out
+    pub budget_ms: u64,
+    /// Accounts whose limit lookups are primed, from WARMUP_HOT_ACCOUNTS as "id,id"
+    pub hot_accounts: Vec<String>,
+}
+
+/// Status reads from a Postgres replica, falling back to the primary for
+/// transactions submitted moments ago that the replica may not have yet
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadYourWritesConfig {
+    /// Replica the status endpoint reads from; unset, it reads the primary
+    #[serde(serialize_with = "serialize_redacted_optional_url")]
+    pub replica_url: Option<String>,
+    /// How long after submit a status read the replica misses is retried on the primary
+    pub window_ms: u64,
+    /// Key signing the consistency tokens submit hands out. Unset, each
+    /// instance signs with a random key, so only its own tokens are honored.
+    #[serde(skip)]
+    pub token_secret: Option<String>,
+}
+
+/// Detection of transactions stuck in "processing"
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleProcessingConfig {
+    /// Rows in "processing" without an update for this long are reported
+    pub threshold_seconds: u64,
+    pub check_interval_seconds: u64,
+    /// Rows stuck for this long are moved back to "retry"; unset disables auto-heal
+    pub heal_after_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiConfig {
+    pub common: CommonConfig,
+    /// Storage behind the API, from BACKEND; `Backend::Memory` is refused in production
+    pub backend: Backend,
+    pub port: u16,
+    /// TCP addresses to listen on, from LISTEN_ADDRESSES as "addr:port,addr:port".
+    /// Defaults to 0.0.0.0 on `port`.
+    pub listen_addresses: Vec<SocketAddr>,
+    /// Optional Unix domain socket to listen on in addition to TCP
+    pub listen_unix_socket: Option<PathBuf>,
+    /// Internal budget of the submit handler. Once spent after the insert,
+    /// submit answers 202 without a queue position instead of running long.
+    pub submit_deadline_ms: u64,
+    /// Upper bound for `estimated_processing_time_seconds` in submit responses
+    pub max_estimated_processing_seconds: i64,
+    /// Workers that have not sent a heartbeat within this window are considered dead
+    pub worker_heartbeat_timeout_seconds: u64,
+    /// Consumer lag (seconds to drain the queue) above which sustained samples warn
+    pub lag_warn_threshold_seconds: f64,
+    /// Consecutive samples above the threshold required before warning
+    pub lag_warn_consecutive_samples: u32,
+    pub lag_sample_interval_seconds: u64,
+    /// Consumer lag at which successful submits start suggesting a backoff
+    pub backoff_elevated_drain_seconds: f64,
+    /// Consumer lag at which the backpressure level becomes high
+    pub backoff_high_drain_seconds: f64,
+    /// Cap on the suggested backoff
+    pub backoff_max_delay_ms: u64,
+    /// Keys accepted on the admin API, from ADMIN_API_KEYS as "id:key,id:key"
+    pub admin_api_keys: Vec<AdminApiKey>,
+    pub rate_limit_algorithm: RateLimitAlgorithm,
+    /// Submit limits of account tiers, from SUBMIT_TIER_LIMITS as
+    /// "prefix:max_requests[:estimate_multiplier],...", `DEFAULT_TIER_LIMITS`
+    /// when unset and no tiers at all when "none". An account without a "submit"
+    /// rate_limits row gets the limit of the longest prefix its id starts
+    /// with, and every account the estimate multiplier of that tier.
+    pub submit_tier_limits: Vec<TierLimit>,
+    /// Submit cost by priority, from PRIORITY_COST_CURVE as "flat" or
+    /// "linear:<step>"; high priority submits use up the limit faster
+    pub priority_cost_curve: PriorityCostCurve,
+    /// Space a typed `transaction_data` may ask for per extra unit of submit
+    /// cost: a payload costs 1 + space_bytes / this, times its priority cost.
+    /// Unset, payloads do not change the cost.
+    pub space_bytes_per_cost_unit: Option<u32>,
+    /// Percentage of the limit at which responses start carrying a warning;
+    /// accounts can override it with a "soft_pct" rate_limits row
+    pub rate_limit_soft_pct: u32,
+    /// Accounts whose fixed windows stay on wall-clock boundaries instead of
+    /// being offset, from ALIGNED_WINDOW_ACCOUNTS as "id,id"
+    pub aligned_window_accounts: Vec<String>,
+    /// How long an account's submit, soft and pending limits are cached in Redis before Postgres
+    /// is read again; changes made through the API invalidate it immediately
+    pub limit_cache_ttl_seconds: u64,
+    /// Accounts each instance keeps in memory, both for account limits in
+    /// front of the Redis cache and for recent rejections
+    pub local_limit_cache_capacity: usize,
+    /// How long in-memory account limits are used. Changes made through
+    /// another instance reach this one within this time.
+    pub local_limit_cache_ttl_ms: u64,
+    /// How long each instance reuses an account's compiled payload schema.
+    /// Changes made through another instance reach this one within this time.
+    pub payload_schema_cache_ttl_ms: u64,
+    /// Accept `transaction_data` whose `account_type` has no typed payload,
+    /// stored unchecked; when off such submits are refused
+    pub allow_unknown_account_types: bool,
+    /// How often each instance rereads the feature flag percentages from Redis
+    pub feature_flag_refresh_ms: u64,
+    /// Longest `/health/ready` waits on Postgres or Redis before counting
+    /// that dependency as down
+    pub readiness_check_timeout_ms: u64,
+    /// How long a readiness probe reuses the last dependency checks, so a
+    /// burst of probes runs them once
+    pub readiness_cache_ttl_ms: u64,
+    /// Longest a shadow rate limit check may run before it counts as timed out
+    pub shadow_rate_limit_timeout_ms: u64,
+    /// Difference in `remaining` between the live and shadow limiters that
+    /// is not counted as a mismatch
+    pub shadow_rate_limit_tolerance: u32,
+    /// Transactions an account may have waiting or in flight before submits are
+    /// rejected; accounts can override it with a "max_pending" rate_limits row
+    pub max_pending_per_account: u32,
+    /// How often the Redis pending counters are corrected from Postgres
+    pub pending_reconcile_interval_seconds: u64,
+    /// How long a submit's Idempotency-Key is remembered in Redis. Reuse after
+    /// that is still caught by Postgres, but only once it has been rate limited.
+    pub idempotency_key_ttl_seconds: u64,
+    /// Requests per window allowed on the read endpoints for each client
+    pub read_rate_limit: u32,
+    pub read_rate_window_seconds: u64,
+    /// Requests per window allowed for each admin identity
+    pub admin_rate_limit: u32,
+    pub admin_rate_window_seconds: u64,
+    /// Requests per window allowed from each client IP across every API
+    /// route, whatever account they name. Unset turns the limit off.
+    pub ip_rate_limit: Option<u32>,
+    pub ip_rate_window_seconds: u64,
+    /// Take the client IP from the X-Forwarded-For entry appended by the proxy
+    /// in front of the API instead of the peer address. Only safe when every
+    /// request comes through that proxy.
+    pub trust_forwarded_for: bool,
+    /// Webhook endpoints an account may register
+    pub max_webhooks_per_account: i64,
+    /// Timeout for a single webhook delivery
+    pub webhook_timeout_ms: u64,
+    /// Queue payloads larger than this are stored zstd-compressed in Redis
+    pub queue_compress_over_bytes: usize,
+    /// Longest gap allowed between request body chunks before answering 408
+    pub body_read_timeout_ms: u64,
+    /// Reject request bodies without a Content-Type instead of sniffing for JSON
+    pub strict_content_type: bool,
+    /// Longest submit-stream line; a longer one fails alone with a 413 result
+    pub submit_stream_max_line_bytes: usize,
+    /// Non-blank lines one submit-stream request may carry
+    pub submit_stream_max_lines: usize,
+    /// Submit-stream lines of one request submitted at the same time
+    pub submit_stream_concurrency: usize,
+    /// Send a second attempt for idempotent Redis reads that exceed the hedge budget
+    pub redis_hedging: bool,
+    pub redis_hedge_budget_ms: u64,
+    /// Hash-tag Redis keys by account and queue, as Redis Cluster requires
+    pub redis_cluster_keys: bool,
+    /// Prepended, with a `:`, to every Redis key so deployments can share a database
+    pub redis_key_prefix: Option<String>,
+    /// Whether submit and the rate limit layers fail open or closed while
+    /// Redis cannot be reached
+    pub redis_failure_mode: RedisFailureMode,
+    pub stale_processing: StaleProcessingConfig,
+    pub warmup: WarmupConfig,
+    pub read_your_writes: ReadYourWritesConfig,
+}
+
+impl ApiConfig {
+    pub fn from_env() -> ConfigResult<Self> {
+        Self::from_lookup(&Env::new(&crate::process_env))
+    }
+
+    pub fn from_lookup(env: &Env) -> ConfigResult<Self> {
+        let port = env.parse_or("PORT", 3000)?;
+        let backend = env.parse_or("BACKEND", Backend::default())?;
+        // Nothing connects to Postgres in memory mode, so its URL may be left unset
+        let memory_database_url = |var: &str| match var {
+            "DATABASE_URL" => Some(env.string_or(var, MEMORY_DATABASE_URL)),
+            _ => env.get(var),
+        };
+        let common = match backend {
+            Backend::Services => CommonConfig::from_lookup(env)?,
+            Backend::Memory => CommonConfig::from_lookup(&Env::new(&memory_database_url))?,
+        };
+
+        let config = Self {
+            common,
+            backend,
+            port,
+            listen_addresses: parse_listen_addresses(&env.string_or("LISTEN_ADDRESSES", ""), port)?,
+            listen_unix_socket: env.get("LISTEN_UNIX_SOCKET").map(PathBuf::from),
+            submit_deadline_ms: env.parse_or("SUBMIT_DEADLINE_MS", 80)?,
+            max_estimated_processing_seconds: env.parse_or("MAX_ESTIMATED_PROCESSING_SECONDS", 3600)?,
+            worker_heartbeat_timeout_seconds: env.parse_or("WORKER_HEARTBEAT_TIMEOUT_SECONDS", 15)?,
+            lag_warn_threshold_seconds: env.parse_or("LAG_WARN_THRESHOLD_SECONDS", 300.0)?,
+            lag_warn_consecutive_samples: env.parse_or("LAG_WARN_CONSECUTIVE_SAMPLES", 3)?,
+            lag_sample_interval_seconds: env.parse_or("LAG_SAMPLE_INTERVAL_SECONDS", 15)?,
+            backoff_elevated_drain_seconds: env.parse_or("BACKOFF_ELEVATED_DRAIN_SECONDS", 300.0)?,
+            backoff_high_drain_seconds: env.parse_or("BACKOFF_HIGH_DRAIN_SECONDS", 1800.0)?,
+            backoff_max_delay_ms: env.parse_or("BACKOFF_MAX_DELAY_MS", 60_000)?,
+            admin_api_keys: parse_admin_api_keys(&env.string_or("ADMIN_API_KEYS", ""))?,
+            rate_limit_algorithm: env.parse_or("RATE_LIMIT_ALGORITHM", RateLimitAlgorithm::default())?,
+            submit_tier_limits: parse_tier_limits(&env.string_or("SUBMIT_TIER_LIMITS", DEFAULT_TIER_LIMITS))?,
+            priority_cost_curve: env.parse_or("PRIORITY_COST_CURVE", PriorityCostCurve::default())?,
+            space_bytes_per_cost_unit: env.parse_optional("SPACE_BYTES_PER_COST_UNIT")?,
+            rate_limit_soft_pct: env.parse_or("RATE_LIMIT_SOFT_PCT", 80)?,
+            aligned_window_accounts: split_list(&env.string_or("ALIGNED_WINDOW_ACCOUNTS", ""))
+                .map(str::to_string)
+                .collect(),
+            limit_cache_ttl_seconds: env.parse_or("LIMIT_CACHE_TTL_SECONDS", 60)?,
+            local_limit_cache_capacity: env.parse_or("LOCAL_LIMIT_CACHE_CAPACITY", 10_000)?,
+            local_limit_cache_ttl_ms: env.parse_or("LOCAL_LIMIT_CACHE_TTL_MS", 1000)?,
+            payload_schema_cache_ttl_ms: env.parse_or("PAYLOAD_SCHEMA_CACHE_TTL_MS", 30_000)?,
+            allow_unknown_account_types: env.parse_or("ALLOW_UNKNOWN_ACCOUNT_TYPES", true)?,
+            feature_flag_refresh_ms: env.parse_or("FEATURE_FLAG_REFRESH_MS", 5000)?,
+            readiness_check_timeout_ms: env.parse_or("READINESS_CHECK_TIMEOUT_MS", 250)?,
+            readiness_cache_ttl_ms: env.parse_or("READINESS_CACHE_TTL_MS", 2000)?,
+            shadow_rate_limit_timeout_ms: env.parse_or("SHADOW_RATE_LIMIT_TIMEOUT_MS", 20)?,
+            shadow_rate_limit_tolerance: env.parse_or("SHADOW_RATE_LIMIT_TOLERANCE", 1)?,
+            max_pending_per_account: env.parse_or("MAX_PENDING_PER_ACCOUNT", 1000)?,
+            pending_reconcile_interval_seconds: env.parse_or("PENDING_RECONCILE_INTERVAL_SECONDS", 60)?,
+            idempotency_key_ttl_seconds: env.parse_or("IDEMPOTENCY_KEY_TTL_SECONDS", 86_400)?,
+            read_rate_limit: env.parse_or("READ_RATE_LIMIT", 300)?,
+            read_rate_window_seconds: env.parse_or("READ_RATE_WINDOW_SECONDS", 60)?,
+            admin_rate_limit: env.parse_or("ADMIN_RATE_LIMIT", 60)?,
+            admin_rate_window_seconds: env.parse_or("ADMIN_RATE_WINDOW_SECONDS", 60)?,
+            ip_rate_limit: env.parse_optional("IP_RATE_LIMIT")?,
+            ip_rate_window_seconds: env.parse_or("IP_RATE_WINDOW_SECONDS", 60)?,
+            trust_forwarded_for: env.parse_or("TRUST_FORWARDED_FOR", false)?,
+            max_webhooks_per_account: env.parse_or("MAX_WEBHOOKS_PER_ACCOUNT", 10)?,
+            webhook_timeout_ms: env.parse_or("WEBHOOK_TIMEOUT_MS", 5000)?,
+            queue_compress_over_bytes: env.parse_or("QUEUE_COMPRESS_OVER_BYTES", 16 * 1024)?,
+            body_read_timeout_ms: env.parse_or("BODY_READ_TIMEOUT_MS", 10_000)?,
+            strict_content_type: env.parse_or("STRICT_CONTENT_TYPE", false)?,
+            submit_stream_max_line_bytes: env.parse_or("SUBMIT_STREAM_MAX_LINE_BYTES", 2 * 1024 * 1024)?,
+            submit_stream_max_lines: env.parse_or("SUBMIT_STREAM_MAX_LINES", 10_000)?,
+            submit_stream_concurrency: env.parse_or("SUBMIT_STREAM_CONCURRENCY", 16)?,
+            redis_hedging: env.parse_or("REDIS_HEDGING", false)?,
+            redis_hedge_budget_ms: env.parse_or("REDIS_HEDGE_BUDGET_MS", 10)?,
+            redis_cluster_keys: env.parse_or("REDIS_CLUSTER_KEYS", false)?,
+            redis_key_prefix: env.get("REDIS_KEY_PREFIX").map(|prefix| prefix.trim().to_string()),
+            redis_failure_mode: env.parse_or("REDIS_FAILURE_MODE", RedisFailureMode::default())?,
+            stale_processing: StaleProcessingConfig {
+                threshold_seconds: env.parse_or("STALE_PROCESSING_THRESHOLD_SECONDS", 600)?,
+                check_interval_seconds: env.parse_or("STALE_PROCESSING_CHECK_INTERVAL_SECONDS", 60)?,
+                heal_after_seconds: env.parse_optional("STALE_PROCESSING_HEAL_AFTER_SECONDS")?,
+            },
+            warmup: WarmupConfig {
+                enabled: env.parse_or("WARMUP", true)?,
+                redis_connections: env.parse_or("WARMUP_REDIS_CONNECTIONS", 16)?,
+                budget_ms: env.parse_or("WARMUP_BUDGET_MS", 5000)?,
+                hot_accounts: split_list(&env.string_or("WARMUP_HOT_ACCOUNTS", ""))
+                    .map(str::to_string)
+                    .collect(),
+            },
+            read_your_writes: ReadYourWritesConfig {
+                replica_url: env.get("DATABASE_REPLICA_URL"),
+                window_ms: env.parse_or("READ_YOUR_WRITES_WINDOW_MS", 5000)?,
+                token_secret: env.get("CONSISTENCY_TOKEN_SECRET"),
+            },
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Tier limit for `account_id`: the longest prefix it starts with
+    pub fn tier_limit(&self, account_id: &str) -> Option<&TierLimit> {
+        self.submit_tier_limits
+            .iter()
+            .filter(|tier| account_id.starts_with(&tier.prefix))
+            .max_by_key(|tier| tier.prefix.len())
+    }
+
+    fn validate(&self) -> ConfigResult<()> {
+        if self.backend == Backend::Memory && self.common.environment == "production" {
+            return Err(ConfigError::invalid("BACKEND", "memory is not durable and cannot be used in production"));
+        }
+        if self.submit_deadline_ms == 0 {
+            return Err(ConfigError::invalid("SUBMIT_DEADLINE_MS", "must be greater than 0"));
+        }
+        if self.idempotency_key_ttl_seconds == 0 {
+            return Err(ConfigError::invalid("IDEMPOTENCY_KEY_TTL_SECONDS", "must be greater than 0"));
+        }
+        if self.max_estimated_processing_seconds < 0 {
+            return Err(ConfigError::invalid("MAX_ESTIMATED_PROCESSING_SECONDS", "must not be negative"));
+        }
+        if self.lag_sample_interval_seconds == 0 {
+            return Err(ConfigError::invalid("LAG_SAMPLE_INTERVAL_SECONDS", "must be greater than 0"));
+        }
+        if self.backoff_elevated_drain_seconds <= 0.0 {
+            return Err(ConfigError::invalid("BACKOFF_ELEVATED_DRAIN_SECONDS", "must be greater than 0"));
+        }
+        if self.backoff_high_drain_seconds <= self.backoff_elevated_drain_seconds {
+            return Err(ConfigError::invalid(
+                "BACKOFF_HIGH_DRAIN_SECONDS",
+                "must be above BACKOFF_ELEVATED_DRAIN_SECONDS",
+            ));
+        }
+        if self.body_read_timeout_ms == 0 {
+            return Err(ConfigError::invalid("BODY_READ_TIMEOUT_MS", "must be greater than 0"));
+        }
+        if self.submit_stream_max_line_bytes == 0 {
+            return Err(ConfigError::invalid("SUBMIT_STREAM_MAX_LINE_BYTES", "must be greater than 0"));
+        }
+        if self.submit_stream_max_lines == 0 {
+            return Err(ConfigError::invalid("SUBMIT_STREAM_MAX_LINES", "must be greater than 0"));
+        }
+        if self.submit_stream_concurrency == 0 {
+            return Err(ConfigError::invalid("SUBMIT_STREAM_CONCURRENCY", "must be greater than 0"));
+        }
+        if self.max_webhooks_per_account < 0 {
+            return Err(ConfigError::invalid("MAX_WEBHOOKS_PER_ACCOUNT", "must not be negative"));
+        }
+        if !(1..=100).contains(&self.rate_limit_soft_pct) {
+            return Err(ConfigError::invalid("RATE_LIMIT_SOFT_PCT", "must be between 1 and 100"));
+        }
+        if self.space_bytes_per_cost_unit == Some(0) {
+            return Err(ConfigError::invalid("SPACE_BYTES_PER_COST_UNIT", "must be greater than 0"));
+        }
+        if self.limit_cache_ttl_seconds == 0 {
+            return Err(ConfigError::invalid("LIMIT_CACHE_TTL_SECONDS", "must be greater than 0"));
+        }
+        if self.local_limit_cache_capacity == 0 {
+            return Err(ConfigError::invalid("LOCAL_LIMIT_CACHE_CAPACITY", "must be greater than 0"));
+        }
+        if self.payload_schema_cache_ttl_ms == 0 {
+            return Err(ConfigError::invalid("PAYLOAD_SCHEMA_CACHE_TTL_MS", "must be greater than 0"));
+        }
+        if self.feature_flag_refresh_ms == 0 {
+            return Err(ConfigError::invalid("FEATURE_FLAG_REFRESH_MS", "must be greater than 0"));
+        }
+        if self.readiness_check_timeout_ms == 0 {
+            return Err(ConfigError::invalid("READINESS_CHECK_TIMEOUT_MS", "must be greater than 0"));
+        }
+        if self.readiness_cache_ttl_ms == 0 {
+            return Err(ConfigError::invalid("READINESS_CACHE_TTL_MS", "must be greater than 0"));
+        }
+        if self.shadow_rate_limit_timeout_ms == 0 {
+            return Err(ConfigError::invalid("SHADOW_RATE_LIMIT_TIMEOUT_MS", "must be greater than 0"));
+        }
+        if self.max_pending_per_account == 0 {
+            return Err(ConfigError::invalid("MAX_PENDING_PER_ACCOUNT", "must be greater than 0"));
+        }
+        if self.pending_reconcile_interval_seconds == 0 {
+            return Err(ConfigError::invalid("PENDING_RECONCILE_INTERVAL_SECONDS", "must be greater than 0"));
+        }
+        if self.redis_hedging && self.redis_hedge_budget_ms == 0 {
+            return Err(ConfigError::invalid("REDIS_HEDGE_BUDGET_MS", "must be greater than 0"));
+        }
+        validate_redis_key_prefix(self.redis_key_prefix.as_deref())?;
+        if self.read_your_writes.window_ms == 0 {
+            return Err(ConfigError::invalid("READ_YOUR_WRITES_WINDOW_MS", "must be greater than 0"));
+        }
+        if self.stale_processing.check_interval_seconds == 0 {
+            return Err(ConfigError::invalid("STALE_PROCESSING_CHECK_INTERVAL_SECONDS", "must be greater than 0"));
+        }
+        if let Some(heal_after) = self.stale_processing.heal_after_seconds {
+            if heal_after < self.stale_processing.threshold_seconds {
+                return Err(ConfigError::invalid(
+                    "STALE_PROCESSING_HEAL_AFTER_SECONDS",
+                    "must not be below STALE_PROCESSING_THRESHOLD_SECONDS",
+                ));
+            }
+        }
+        for (var, window) in [
+            ("READ_RATE_WINDOW_SECONDS", self.read_rate_window_seconds),
+            ("ADMIN_RATE_WINDOW_SECONDS", self.admin_rate_window_seconds),
+            ("IP_RATE_WINDOW_SECONDS", self.ip_rate_window_seconds),
+        ] {
+            if window == 0 || window > MAX_RATE_WINDOW_SECONDS {
+                return Err(ConfigError::invalid(
+                    var,
+                    format!("must be between 1 and {}", MAX_RATE_WINDOW_SECONDS),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_listen_addresses(raw: &str, default_port: u16) -> ConfigResult<Vec<SocketAddr>> {
+    let addresses = split_list(raw)
+        .map(|entry| {
+            entry
+                .parse()
+                .map_err(|_| ConfigError::invalid("LISTEN_ADDRESSES", format!("bad address {:?}", entry)))
+        })
+        .collect::<ConfigResult<Vec<_>>>()?;
+
+    if addresses.is_empty() {
+        return Ok(vec![SocketAddr::from(([0, 0, 0, 0], default_port))]);
+    }
+    Ok(addresses)
+}
+
+fn parse_admin_api_keys(raw: &str) -> ConfigResult<Vec<AdminApiKey>> {
+    split_list(raw)
+        .map(|entry| {
+            let (id, key) = entry
+                .split_once(':')
+                .filter(|(id, key)| !id.is_empty() && !key.is_empty())
+                .ok_or_else(|| ConfigError::invalid("ADMIN_API_KEYS", "expected id:key"))?;
+            Ok(AdminApiKey {
+                id: id.to_string(),
+                key: key.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_tier_limits(raw: &str) -> ConfigResult<Vec<TierLimit>> {
+    if raw.trim() == "none" {
+        return Ok(Vec::new());
+    }
+    split_list(raw)
+        .map(|entry| {
+            let mut fields = entry.splitn(3, ':');
+            let prefix = fields.next().filter(|prefix| !prefix.is_empty());
+            let max_requests = fields.next().and_then(|max| max.parse().ok());
+            let estimate_multiplier = fields.next().map_or(Some(1.0), |multiplier| {
+                multiplier.parse().ok().filter(|multiplier: &f64| multiplier.is_finite() && *multiplier > 0.0)
+            });
+            match (prefix, max_requests, estimate_multiplier) {
+                (Some(prefix), Some(max_requests), Some(estimate_multiplier)) => Ok(TierLimit {
+                    prefix: prefix.to_string(),
+                    max_requests,
+                    estimate_multiplier,
+                }),
+                _ => Err(ConfigError::invalid(
+                    "SUBMIT_TIER_LIMITS",
+                    "expected prefix:max_requests[:estimate_multiplier], the multiplier above 0",
+                )),
+            }
+        })
+        .collect()
+}
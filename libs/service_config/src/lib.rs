@@ -0,0 +1,285 @@
+//! Typed configuration shared by the API and worker binaries.
+//!
+//! Every config is built from a variable lookup function so tests can supply
+//! values without touching the process environment. `from_env` wraps the
+//! lookup around `std::env::var`.
+//!
+//! Configs serialize to what an operator may see: URLs lose their
+//! passwords and admin API keys only show their ids.
+
+mod api;
+mod worker;
+
+pub use api::{
+    AdminApiKey, ApiConfig, Backend, PriorityCostCurve, RateLimitAlgorithm, ReadYourWritesConfig,
+    RedisFailureMode, StaleProcessingConfig, TierLimit, WarmupConfig, MAX_RATE_WINDOW_SECONDS,
+};
+pub use worker::{ProcessorKind, WorkerConfig};
+
+use serde::{Serialize, Serializer};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("{0} must be set")]
+    Missing(&'static str),
+
+    #[error("Invalid {var}: {message}")]
+    Invalid { var: &'static str, message: String },
+}
+
+impl ConfigError {
+    pub fn invalid(var: &'static str, message: impl Into<String>) -> Self {
+        Self::Invalid {
+            var,
+            message: message.into(),
+        }
+    }
+}
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
+
+/// Variable source used while building a config
+pub struct Env<'a> {
+    lookup: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl<'a> Env<'a> {
+    pub fn new(lookup: &'a dyn Fn(&str) -> Option<String>) -> Self {
+        Self { lookup }
+    }
+
+    /// Value of a variable, treating empty as unset
+    pub fn get(&self, var: &str) -> Option<String> {
+        (self.lookup)(var).filter(|value| !value.trim().is_empty())
+    }
+
+    pub fn required(&self, var: &'static str) -> ConfigResult<String> {
+        self.get(var).ok_or(ConfigError::Missing(var))
+    }
+
+    pub fn string_or(&self, var: &str, default: &str) -> String {
+        self.get(var).unwrap_or_else(|| default.to_string())
+    }
+
+    /// Parse a variable, falling back to `default` when unset
+    pub fn parse_or<T>(&self, var: &'static str, default: T) -> ConfigResult<T>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        Ok(self.parse_optional(var)?.unwrap_or(default))
+    }
+
+    /// Parse a variable that has no default
+    pub fn parse_optional<T>(&self, var: &'static str) -> ConfigResult<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.get(var)
+            .map(|raw| {
+                raw.trim()
+                    .parse()
+                    .map_err(|e: T::Err| ConfigError::invalid(var, format!("{:?}: {}", raw, e)))
+            })
+            .transpose()
+    }
+}
+
+/// Look variables up in the process environment
+pub fn process_env(var: &str) -> Option<String> {
+    std::env::var(var).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown log format {:?}, expected text or json", other)),
+        }
+    }
+}
+
+/// Settings every service needs
+#[derive(Debug, Clone, Serialize)]
+pub struct CommonConfig {
+    #[serde(serialize_with = "serialize_redacted_url")]
+    pub database_url: String,
+    #[serde(serialize_with = "serialize_redacted_url")]
+    pub redis_url: String,
+    /// Logical Redis database, for environments sharing one Redis; must agree
+    /// with the URL if it names a database too
+    pub redis_db: Option<i64>,
+    pub redis_response_timeout_ms: Option<u64>,
+    pub db_pool: PoolConfig,
+    /// Redis pool. Its connect timeout is REDIS_CONNECTION_TIMEOUT_MS; the
+    /// Redis pool keeps no idle minimum and never closes idle connections.
+    pub redis_pool: PoolConfig,
+    pub environment: String,
+    pub log_format: LogFormat,
+    /// Publish queue lifecycle events to the per-queue Redis stream for analytics
+    pub queue_events: bool,
+}
+
+impl CommonConfig {
+    pub fn from_lookup(env: &Env) -> ConfigResult<Self> {
+        Ok(Self {
+            database_url: env.required("DATABASE_URL")?,
+            redis_url: env.string_or("REDIS_URL", "redis://localhost:6379"),
+            redis_db: env.parse_optional("REDIS_DB")?,
+            redis_response_timeout_ms: env.parse_optional("REDIS_RESPONSE_TIMEOUT_MS")?,
+            db_pool: PoolConfig::from_vars(env, &DB_POOL_VARS)?,
+            redis_pool: PoolConfig::from_vars(env, &REDIS_POOL_VARS)?,
+            environment: env.string_or("ENVIRONMENT", "development"),
+            log_format: env.parse_or("LOG_FORMAT", LogFormat::Text)?,
+            queue_events: env.parse_or("QUEUE_EVENTS", false)?,
+        })
+    }
+
+    pub fn is_development(&self) -> bool {
+        self.environment == "development"
+    }
+}
+
+/// Connection pool sizing and timeouts. `None` leaves the pool library's
+/// default, so an empty config changes nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PoolConfig {
+    /// Most connections open at once
+    pub max_size: Option<u32>,
+    /// Idle connections kept open ahead of demand
+    pub min_idle: Option<u32>,
+    /// Upper bound on opening a new connection
+    pub connect_timeout: Option<Duration>,
+    /// Longest a caller waits for a connection to come free before the pool
+    /// counts as exhausted
+    pub wait_timeout: Option<Duration>,
+    /// Idle connections are closed after this long
+    pub idle_timeout: Option<Duration>,
+}
+
+/// Variables one pool's settings are read from; pools without a setting
+/// have no variable for it
+struct PoolVars {
+    max_size: &'static str,
+    min_idle: Option<&'static str>,
+    connect_timeout_ms: &'static str,
+    wait_timeout_ms: &'static str,
+    idle_timeout_seconds: Option<&'static str>,
+}
+
+const DB_POOL_VARS: PoolVars = PoolVars {
+    max_size: "DB_POOL_MAX",
+    min_idle: Some("DB_POOL_MIN_IDLE"),
+    connect_timeout_ms: "DB_POOL_CONNECT_TIMEOUT_MS",
+    wait_timeout_ms: "DB_POOL_WAIT_TIMEOUT_MS",
+    idle_timeout_seconds: Some("DB_POOL_IDLE_TIMEOUT_SECONDS"),
+};
+
+const REDIS_POOL_VARS: PoolVars = PoolVars {
+    max_size: "REDIS_POOL_MAX",
+    min_idle: None,
+    connect_timeout_ms: "REDIS_CONNECTION_TIMEOUT_MS",
+    wait_timeout_ms: "REDIS_POOL_WAIT_TIMEOUT_MS",
+    idle_timeout_seconds: None,
+};
+
+impl PoolConfig {
+    fn from_vars(env: &Env, vars: &PoolVars) -> ConfigResult<Self> {
+        let config = Self {
+            max_size: env.parse_optional(vars.max_size)?,
+            min_idle: vars.min_idle.map_or(Ok(None), |var| env.parse_optional(var))?,
+            connect_timeout: env.parse_optional(vars.connect_timeout_ms)?.map(Duration::from_millis),
+            wait_timeout: env.parse_optional(vars.wait_timeout_ms)?.map(Duration::from_millis),
+            idle_timeout: vars
+                .idle_timeout_seconds
+                .map_or(Ok(None), |var| env.parse_optional(var))?
+                .map(Duration::from_secs),
+        };
+
+        if config.max_size == Some(0) {
+            return Err(ConfigError::invalid(vars.max_size, "must be greater than 0"));
+        }
+        if let (Some(min_idle), Some(var)) = (config.min_idle, vars.min_idle) {
+            if min_idle > config.max_size.unwrap_or(u32::MAX) {
+                return Err(ConfigError::invalid(var, format!("must not exceed {}", vars.max_size)));
+            }
+        }
+        for (var, timeout) in [
+            (Some(vars.connect_timeout_ms), config.connect_timeout),
+            (Some(vars.wait_timeout_ms), config.wait_timeout),
+            (vars.idle_timeout_seconds, config.idle_timeout),
+        ] {
+            if let (Some(var), Some(Duration::ZERO)) = (var, timeout) {
+                return Err(ConfigError::invalid(var, "must be greater than 0"));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// `url` with any password replaced by "***", e.g.
+/// "postgres://app:***@db:5432/queue"
+pub fn redact_url(url: &str) -> String {
+    let Some(scheme_end) = url.find("://").map(|i| i + 3) else {
+        return url.to_string();
+    };
+    let authority_end = url[scheme_end..].find(['/', '?', '#']).map_or(url.len(), |i| scheme_end + i);
+    let authority = &url[scheme_end..authority_end];
+    let Some(at) = authority.rfind('@') else {
+        return url.to_string();
+    };
+    let user = match authority[..at].split_once(':') {
+        Some((user, _password)) => format!("{}:***", user),
+        // A lone userinfo is the password for Redis URLs like redis://secret@host
+        None if !authority[..at].is_empty() => "***".to_string(),
+        None => String::new(),
+    };
+    format!("{}{}@{}{}", &url[..scheme_end], user, &authority[at + 1..], &url[authority_end..])
+}
+
+fn serialize_redacted_url<S: Serializer>(url: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&redact_url(url))
+}
+
+pub(crate) fn serialize_redacted_optional_url<S: Serializer>(
+    url: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match url {
+        Some(url) => serializer.serialize_str(&redact_url(url)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Check REDIS_KEY_PREFIX, which every service reading the same keys must agree on
+pub(crate) fn validate_redis_key_prefix(prefix: Option<&str>) -> ConfigResult<()> {
+    if let Some(prefix) = prefix {
+        let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+        if prefix.len() > 64 || !prefix.chars().all(allowed) {
+            return Err(ConfigError::invalid(
+                "REDIS_KEY_PREFIX",
+                "must be at most 64 ASCII letters, digits, '_', '-' or '.'",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Split a comma separated variable into trimmed, non-empty entries
+pub(crate) fn split_list(raw: &str) -> impl Iterator<Item = &str> {
+    raw.split(',').map(str::trim).filter(|entry| !entry.is_empty())
+}
@@ -0,0 +1,106 @@
+use crate::{split_list, validate_redis_key_prefix, CommonConfig, ConfigError, ConfigResult, Env};
+use std::str::FromStr;
+
+/// Which transaction processor the worker runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProcessorKind {
+    /// Simulate execution without touching the chain
+    #[default]
+    Simulated,
+    /// Mark items completed without doing any work
+    Noop,
+}
+
+impl FromStr for ProcessorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "simulated" => Ok(Self::Simulated),
+            "noop" => Ok(Self::Noop),
+            other => Err(format!("unknown processor {:?}, expected simulated or noop", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    pub common: CommonConfig,
+    /// Maximum number of items processed at the same time
+    pub concurrency: usize,
+    /// Queues to consume, from WORKER_QUEUES as "queue,queue"
+    pub queues: Vec<String>,
+    /// Delay between polls when the queues are empty
+    pub poll_interval_ms: u64,
+    /// How long in-flight items may run after a shutdown signal
+    pub drain_timeout_seconds: u64,
+    pub processor: ProcessorKind,
+    pub heartbeat_interval_seconds: u64,
+    /// Delay before the first retry of a failed item; doubled for each
+    /// retry after it
+    pub retry_base_delay_ms: u64,
+    /// Longest delay between retries
+    pub retry_max_delay_ms: u64,
+    /// Payloads of retried items larger than this are compressed in the
+    /// queue, as the API does on submit
+    pub queue_compress_over_bytes: usize,
+    /// Key layout and prefix of the API's Redis keys, which the worker must share
+    pub redis_cluster_keys: bool,
+    pub redis_key_prefix: Option<String>,
+}
+
+impl WorkerConfig {
+    pub const DEFAULT_QUEUE: &'static str = "tx_queue";
+
+    pub fn from_env() -> ConfigResult<Self> {
+        Self::from_lookup(&Env::new(&crate::process_env))
+    }
+
+    pub fn from_lookup(env: &Env) -> ConfigResult<Self> {
+        let queues = split_list(&env.string_or("WORKER_QUEUES", Self::DEFAULT_QUEUE))
+            .map(str::to_string)
+            .collect();
+
+        let config = Self {
+            common: CommonConfig::from_lookup(env)?,
+            concurrency: env.parse_or("WORKER_CONCURRENCY", 4)?,
+            queues,
+            poll_interval_ms: env.parse_or("WORKER_POLL_INTERVAL_MS", 500)?,
+            drain_timeout_seconds: env.parse_or("WORKER_DRAIN_TIMEOUT_SECONDS", 30)?,
+            processor: env.parse_or("WORKER_PROCESSOR", ProcessorKind::Simulated)?,
+            heartbeat_interval_seconds: env.parse_or("WORKER_HEARTBEAT_INTERVAL_SECONDS", 5)?,
+            retry_base_delay_ms: env.parse_or("WORKER_RETRY_BASE_DELAY_MS", 1000)?,
+            retry_max_delay_ms: env.parse_or("WORKER_RETRY_MAX_DELAY_MS", 300_000)?,
+            queue_compress_over_bytes: env.parse_or("QUEUE_COMPRESS_OVER_BYTES", 16 * 1024)?,
+            redis_cluster_keys: env.parse_or("REDIS_CLUSTER_KEYS", false)?,
+            redis_key_prefix: env.get("REDIS_KEY_PREFIX").map(|prefix| prefix.trim().to_string()),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> ConfigResult<()> {
+        if self.concurrency == 0 {
+            return Err(ConfigError::invalid("WORKER_CONCURRENCY", "must be greater than 0"));
+        }
+        if self.queues.is_empty() {
+            return Err(ConfigError::invalid("WORKER_QUEUES", "at least one queue is required"));
+        }
+        if self.poll_interval_ms == 0 {
+            return Err(ConfigError::invalid("WORKER_POLL_INTERVAL_MS", "must be greater than 0"));
+        }
+        if self.heartbeat_interval_seconds == 0 {
+            return Err(ConfigError::invalid("WORKER_HEARTBEAT_INTERVAL_SECONDS", "must be greater than 0"));
+        }
+        if self.retry_base_delay_ms == 0 {
+            return Err(ConfigError::invalid("WORKER_RETRY_BASE_DELAY_MS", "must be greater than 0"));
+        }
+        if self.retry_max_delay_ms < self.retry_base_delay_ms {
+            return Err(ConfigError::invalid(
+                "WORKER_RETRY_MAX_DELAY_MS",
+                "must be at least WORKER_RETRY_BASE_DELAY_MS",
+            ));
+        }
+        validate_redis_key_prefix(self.redis_key_prefix.as_deref())
+    }
+}
@@ -0,0 +1,52 @@
+//! Records which commit was built and when, for GET /v1/admin/info.
+//!
+//! GIT_SHA overrides the commit, for builds outside a checkout such as the
+//! Docker image, and SOURCE_DATE_EPOCH the time, for reproducible builds.
+//! The script reruns when HEAD moves, so the time is that of the first
+//! build of a commit.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let git_sha = std::env::var("GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.trim().is_empty())
+        .or_else(|| git(&["rev-parse", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=BUILD_GIT_SHA={}", git_sha.trim());
+
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs()));
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", timestamp);
+
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]).map(PathBuf::from) {
+        watch_head(&git_dir);
+    }
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Rerun on a checkout or a new commit: HEAD, the branch it points to and
+/// packed-refs, which holds branches git has packed
+fn watch_head(git_dir: &Path) {
+    let head = git_dir.join("HEAD");
+    let mut watched = vec![head.clone(), git_dir.join("packed-refs")];
+    if let Some(branch) = std::fs::read_to_string(&head).ok().and_then(|head| {
+        head.strip_prefix("ref: ").map(|branch| git_dir.join(branch.trim()))
+    }) {
+        watched.push(branch);
+    }
+    for path in watched.iter().filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
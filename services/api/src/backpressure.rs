@@ -0,0 +1,51 @@
+//! Backoff hints for clients while the queue is backed up.
+//!
+//! A submit that is accepted into a queue hours deep is worse for the
+//! client than one that waits a little first, so successful submits say how
+//! far behind the queue is and how long to wait before the next one. The
+//! assessment uses the queue stats cached by the lag sampler, so it costs
+//! no Redis calls on the submit path.
+//!
+//! The suggested delay starts from the account's own spacing under its
+//! submit limit (window / max requests), so higher tiers are slowed less,
+//! and grows with the drain estimate past the elevated threshold.
+
+use crate::{config::Config, queue_stats::QueueStats, rate_limit::SubmitLimit};
+use axum::http::{HeaderMap, HeaderValue};
+
+pub use api_client::types::{Backpressure, BackpressureLevel};
+
+/// Milliseconds the client should wait before its next submit; 0 when healthy
+pub const BACKOFF_HINT_HEADER: &str = "X-Backoff-Hint-Ms";
+
+/// Backpressure for an account from the latest queue stats. Without a
+/// sample yet there is nothing to go on, so clients are not slowed.
+pub fn assess(stats: Option<&QueueStats>, limit: SubmitLimit, config: &Config) -> Backpressure {
+    let Some(stats) = stats else {
+        return Backpressure::NONE;
+    };
+    let drain_seconds = stats.estimated_drain_seconds;
+    let elevated = config.backoff_elevated_drain_seconds;
+    if drain_seconds < elevated {
+        return Backpressure::NONE;
+    }
+
+    let level = if drain_seconds >= config.backoff_high_drain_seconds {
+        BackpressureLevel::High
+    } else {
+        BackpressureLevel::Elevated
+    };
+    let spacing_ms = limit.window_seconds as f64 * 1000.0 / limit.max_requests.max(1) as f64;
+    let suggested_delay_ms = (spacing_ms * drain_seconds / elevated)
+        .round()
+        .min(config.backoff_max_delay_ms as f64) as u64;
+    Backpressure {
+        level,
+        suggested_delay_ms,
+    }
+}
+
+/// Set the backoff hint header from `backpressure`
+pub fn insert_header(backpressure: &Backpressure, headers: &mut HeaderMap) {
+    headers.insert(BACKOFF_HINT_HEADER, HeaderValue::from(backpressure.suggested_delay_ms));
+}
@@ -0,0 +1,75 @@
+//! Post-deploy smoke check.
+//!
+//! Usage: smoke <base-url> [--admin-key <key>] [--account <id>] [--status-timeout-ms <ms>]
+//!
+//! The admin key may also come from SMOKE_ADMIN_KEY. Prints the report as
+//! JSON and exits 1 if any step failed, 2 on bad arguments.
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use transaction_queue_api::smoke::{self, SmokeClient, SmokeConfig};
+
+const USAGE: &str = "usage: smoke <base-url> [--admin-key <key>] [--account <id>] [--status-timeout-ms <ms>]";
+
+struct Args {
+    base_url: String,
+    admin_key: Option<String>,
+    config: SmokeConfig,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut base_url = None;
+    let mut admin_key = std::env::var("SMOKE_ADMIN_KEY").ok().filter(|key| !key.is_empty());
+    let mut config = SmokeConfig::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--admin-key" => admin_key = Some(value()?),
+            "--account" => config.account_id = value()?,
+            "--status-timeout-ms" => {
+                let ms = value()?
+                    .parse()
+                    .map_err(|_| "--status-timeout-ms must be a number of milliseconds".to_string())?;
+                config.budgets.status = Duration::from_millis(ms);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if base_url.is_none() => base_url = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    Ok(Args {
+        base_url: base_url.ok_or("missing base URL")?,
+        admin_key,
+        config,
+    })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let client = SmokeClient::new(&args.base_url, args.admin_key);
+    let report = smoke::run(&client, &args.config).await;
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize report: {}", e),
+    }
+
+    if report.passed {
+        ExitCode::SUCCESS
+    } else {
+        for failure in report.failures() {
+            eprintln!("{:?} failed: {}", failure.step, failure.detail.as_deref().unwrap_or("no detail"));
+        }
+        ExitCode::FAILURE
+    }
+}
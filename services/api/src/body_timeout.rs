@@ -0,0 +1,93 @@
+//! Guard against clients that trickle a request body. The body is wrapped
+//! so that a gap between chunks longer than the budget fails the read, and
+//! the request is answered with 408 whatever the handler made of the error.
+
+use crate::errors::AppError;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body::{Frame, SizeHint};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+#[derive(Debug, thiserror::Error)]
+#[error("request body read timed out")]
+pub struct BodyReadTimedOut;
+
+/// Middleware enforcing `budget` between body chunks; use with
+/// `axum::middleware::from_fn_with_state(budget, body_read_timeout)`
+pub async fn body_read_timeout(State(budget): State<Duration>, request: Request, next: Next) -> Response {
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let (parts, body) = request.into_parts();
+    let body = Body::new(TimeoutBody::new(body, budget, timed_out.clone()));
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+    if !timed_out.load(Ordering::Acquire) {
+        return response;
+    }
+
+    tracing::warn!(budget_ms = budget.as_millis() as u64, "Request body read timed out");
+    let mut response = AppError::new(StatusCode::REQUEST_TIMEOUT, "Request body read timed out").into_response();
+    // The rest of the body may still be in flight, so the connection cannot be reused
+    response
+        .headers_mut()
+        .insert(header::CONNECTION, HeaderValue::from_static("close"));
+    response
+}
+
+struct TimeoutBody {
+    inner: Body,
+    budget: Duration,
+    deadline: Pin<Box<Sleep>>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl TimeoutBody {
+    fn new(inner: Body, budget: Duration, timed_out: Arc<AtomicBool>) -> Self {
+        Self {
+            inner,
+            budget,
+            deadline: Box::pin(tokio::time::sleep(budget)),
+            timed_out,
+        }
+    }
+}
+
+impl http_body::Body for TimeoutBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(frame) => {
+                this.deadline.as_mut().reset(Instant::now() + this.budget);
+                Poll::Ready(frame)
+            }
+            Poll::Pending => {
+                if this.deadline.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.timed_out.store(true, Ordering::Release);
+                Poll::Ready(Some(Err(axum::Error::new(BodyReadTimedOut))))
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,168 @@
+//! Size- and age-bounded in-process caches.
+//!
+//! Anything cached per account or per key inside the API process goes
+//! through `BoundedCache`, so a long-running instance holds at most
+//! `capacity` entries per cache however many distinct keys it sees.
+//! Entries also expire after `ttl`. A lookup that misses, whether the key
+//! was never cached, expired or was evicted, goes back to the source of
+//! truth through `get_or_load`.
+//!
+//! Every cache reports its size, evictions and hits and misses under its
+//! name, so hit rates can be watched per cache.
+
+use crate::metrics::{LOCAL_CACHE_ENTRIES, LOCAL_CACHE_EVICTIONS_TOTAL, LOCAL_CACHE_LOOKUPS_TOTAL};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+struct Inner<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by last use, least recently used first
+    recency: BTreeMap<u64, K>,
+    next_tick: u64,
+}
+
+pub struct BoundedCache<K, V> {
+    name: &'static str,
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Inner<K, V>>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> BoundedCache<K, V> {
+    /// A cache of at most `capacity` entries (at least 1), each kept for `ttl`
+    pub fn new(name: &'static str, capacity: usize, ttl: Duration) -> Self {
+        Self {
+            name,
+            capacity: capacity.max(1),
+            ttl,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                next_tick: 0,
+            }),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The cached value of `key`, if present and not expired
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut guard = self.lock();
+        let Inner {
+            entries,
+            recency,
+            next_tick,
+        } = &mut *guard;
+
+        let value = match entries.get_mut(key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                recency.remove(&entry.last_used);
+                entry.last_used = *next_tick;
+                recency.insert(*next_tick, key.clone());
+                *next_tick += 1;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                if let Some(expired) = entries.remove(key) {
+                    recency.remove(&expired.last_used);
+                }
+                self.record_eviction("expired", entries.len());
+                None
+            }
+            None => None,
+        };
+        let result = if value.is_some() { "hit" } else { "miss" };
+        metrics::counter!(LOCAL_CACHE_LOOKUPS_TOTAL, "cache" => self.name, "result" => result).increment(1);
+        value
+    }
+
+    /// Cache `value` under `key`, evicting the least recently used entry
+    /// when the cache is full
+    pub fn insert(&self, key: K, value: V) {
+        self.insert_with_ttl(key, value, self.ttl);
+    }
+
+    /// `insert` with an entry of its own lifetime, e.g. one bound by the
+    /// value's own expiry
+    pub fn insert_with_ttl(&self, key: K, value: V, ttl: Duration) {
+        let mut guard = self.lock();
+        let Inner {
+            entries,
+            recency,
+            next_tick,
+        } = &mut *guard;
+
+        if let Some(previous) = entries.remove(&key) {
+            recency.remove(&previous.last_used);
+        } else if entries.len() >= self.capacity {
+            if let Some((_, oldest)) = recency.pop_first() {
+                entries.remove(&oldest);
+                self.record_eviction("capacity", entries.len());
+            }
+        }
+
+        recency.insert(*next_tick, key.clone());
+        entries.insert(
+            key,
+            Entry {
+                value,
+                expires_at: Instant::now() + ttl,
+                last_used: *next_tick,
+            },
+        );
+        *next_tick += 1;
+        metrics::gauge!(LOCAL_CACHE_ENTRIES, "cache" => self.name).set(entries.len() as f64);
+    }
+
+    /// Drop `key`, e.g. after its source changed
+    pub fn remove(&self, key: &K) {
+        let mut guard = self.lock();
+        if let Some(entry) = guard.entries.remove(key) {
+            guard.recency.remove(&entry.last_used);
+        }
+        metrics::gauge!(LOCAL_CACHE_ENTRIES, "cache" => self.name).set(guard.entries.len() as f64);
+    }
+
+    /// The cached value of `key`, or the one `load` reads from the source of
+    /// truth, which is then cached. Errors from `load` are returned uncached.
+    pub async fn get_or_load<E, F, Fut>(&self, key: K, load: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+        let value = load().await?;
+        self.insert(key, value.clone());
+        Ok(value)
+    }
+
+    fn record_eviction(&self, reason: &'static str, entries: usize) {
+        metrics::counter!(LOCAL_CACHE_EVICTIONS_TOTAL, "cache" => self.name, "reason" => reason).increment(1);
+        metrics::gauge!(LOCAL_CACHE_ENTRIES, "cache" => self.name).set(entries as f64);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner<K, V>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
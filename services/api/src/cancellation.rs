@@ -0,0 +1,69 @@
+//! Cooperative cancellation of requests whose client has gone away.
+//!
+//! Hyper drops a request's future when its connection closes, which would
+//! stop a handler at whatever await it was on, possibly between two writes
+//! that belong together. Handlers that must not stop halfway run their work
+//! through `detach`: the work runs on its own task and is never dropped, and
+//! the `CancellationToken` it is given is cancelled once the request is
+//! dropped. The work checks the token between phases, aborting while nothing
+//! has been written and finishing regardless once something has.
+
+use crate::errors::AppError;
+use axum::http::StatusCode;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::Instrument;
+
+/// Status of a request abandoned by its client, as nginx logs it. The client
+/// never sees it.
+pub const CLIENT_CLOSED_REQUEST: u16 = 499;
+
+/// Set once the client of a request has disconnected
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Cancels its token when dropped
+struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Run `work` to completion on its own task, with a token cancelled if the
+/// caller is dropped first. A panic in `work` resumes in the caller, so
+/// `catch_panic` still answers it.
+pub async fn detach<F, Fut, T>(work: F) -> T
+where
+    F: FnOnce(CancellationToken) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let token = CancellationToken::default();
+    let _cancel_on_drop = CancelOnDrop(token.clone());
+    let task = tokio::spawn(work(token).instrument(tracing::Span::current()));
+    match task.await {
+        Ok(output) => output,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        // Only a shutting down runtime cancels the task, and this future goes with it
+        Err(e) => panic!("Detached request task failed: {}", e),
+    }
+}
+
+/// Error returned by work that stopped because its client disconnected
+pub fn client_closed_request() -> AppError {
+    let status = StatusCode::from_u16(CLIENT_CLOSED_REQUEST).expect("499 is a valid status code");
+    AppError::new(status, "Client closed request")
+}
@@ -0,0 +1,82 @@
+//! Read-your-writes for the status endpoint.
+//!
+//! With DATABASE_REPLICA_URL set, GET /v1/transactions/:id reads the
+//! replica, which can lag behind the primary submit wrote to. Submit answers
+//! with an `X-Consistency-Token` saying when the transaction was written,
+//! signed together with its id. A status read the replica misses is retried
+//! on the primary if it carries a valid token for that transaction written
+//! less than READ_YOUR_WRITES_WINDOW_MS ago. Misses without one stay 404s,
+//! so looking up unknown ids never loads the primary.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const CONSISTENCY_TOKEN_HEADER: &str = "x-consistency-token";
+
+/// Status reads the replica missed and were retried on the primary, by
+/// whether the primary had the transaction
+pub const PRIMARY_FALLBACK_READS_TOTAL: &str = "status_primary_fallback_reads_total";
+
+const RANDOM_KEY_BYTES: usize = 32;
+
+/// Issues and checks consistency tokens
+pub struct ConsistencyTokens {
+    key: Vec<u8>,
+    window: Duration,
+}
+
+impl ConsistencyTokens {
+    /// Tokens signed with `secret`, or with a random key of this process when
+    /// there is none, honored for `window` after the write
+    pub fn new(secret: Option<&str>, window: Duration) -> Self {
+        let key = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                let mut key = vec![0u8; RANDOM_KEY_BYTES];
+                rand::thread_rng().fill_bytes(&mut key);
+                key
+            }
+        };
+        Self { key, window }
+    }
+
+    /// Token for transaction `id` written at `written_at`:
+    /// `<unix milliseconds>.<hex hmac>`
+    pub fn issue(&self, id: Uuid, written_at: DateTime<Utc>) -> String {
+        let written_at_ms = written_at.timestamp_millis();
+        format!("{}.{}", written_at_ms, hex::encode(self.sign(id, written_at_ms)))
+    }
+
+    /// Whether `token` was issued for `id` by a holder of this key and is
+    /// still inside the window at `now`
+    pub fn is_fresh(&self, token: &str, id: Uuid, now: DateTime<Utc>) -> bool {
+        let Some((written_at_ms, signature)) = token.split_once('.') else {
+            return false;
+        };
+        let (Ok(written_at_ms), Ok(signature)) = (written_at_ms.parse::<i64>(), hex::decode(signature)) else {
+            return false;
+        };
+        let age_ms = now.timestamp_millis() - written_at_ms;
+        if !(0..self.window.as_millis() as i64).contains(&age_ms) {
+            return false;
+        }
+        self.mac(id, written_at_ms).verify_slice(&signature).is_ok()
+    }
+
+    fn sign(&self, id: Uuid, written_at_ms: i64) -> Vec<u8> {
+        self.mac(id, written_at_ms).finalize().into_bytes().to_vec()
+    }
+
+    /// HMAC-SHA256 over the transaction id and its write time
+    fn mac(&self, id: Uuid, written_at_ms: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(id.as_bytes());
+        mac.update(b".");
+        mac.update(written_at_ms.to_string().as_bytes());
+        mac
+    }
+}
@@ -0,0 +1,152 @@
+//! Context logged with server errors so support has something to go on.
+//!
+//! Every 5xx is logged with a snapshot of pool, queue and account state.
+//! The snapshot is best effort: each Redis read gets what is left of a short
+//! budget and is reported as missing if it fails or runs out of time.
+
+use crate::{
+    errors::ErrorDetail,
+    rate_limit::{account_usage, SUBMIT_WINDOW_SECONDS},
+    AppState, TRANSACTION_QUEUE,
+};
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Id of the request being served, set by `log_server_errors` for the
+/// layers and handlers inside it
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Upper bound on gathering a snapshot
+pub const SNAPSHOT_BUDGET: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticSnapshot {
+    pub request_id: Option<String>,
+    pub db_pool: DbPoolStatus,
+    pub redis_pool: RedisPoolStatus,
+    /// Items in the priority queue; `None` if Redis did not answer in time
+    pub queue_depth: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<AccountSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DbPoolStatus {
+    pub connections: u32,
+    pub idle: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RedisPoolStatus {
+    pub max_size: usize,
+    pub size: usize,
+    pub available: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountSnapshot {
+    pub account_id: String,
+    /// Submit requests used in the current rate limit window
+    pub rate_limit_used: Option<u64>,
+    pub rate_limit_window_seconds: u64,
+    /// Transactions counted against the account's pending cap
+    pub pending: Option<i64>,
+}
+
+impl AppState {
+    /// Gather a snapshot within `SNAPSHOT_BUDGET`. Pool statuses are always
+    /// present; Redis reads that fail or do not finish in time are `None`.
+    pub async fn diagnostic_snapshot(&self, request_id: Option<&str>, account_id: Option<&str>) -> DiagnosticSnapshot {
+        let deadline = Instant::now() + SNAPSHOT_BUDGET;
+        let queue_manager = self.queue_manager();
+
+        let db_state = self.db_pool.state();
+        let redis_status = self.redis_pool.status();
+
+        let queue_depth = within(deadline, queue_manager.priority_queue_length(&TRANSACTION_QUEUE));
+        let account = async {
+            let account_id = account_id?;
+            let (rate_limit_used, pending) = tokio::join!(
+                within(deadline, account_usage(self, account_id, SUBMIT_WINDOW_SECONDS)),
+                within(deadline, queue_manager.pending_count(account_id)),
+            );
+            Some(AccountSnapshot {
+                account_id: account_id.to_string(),
+                rate_limit_used,
+                rate_limit_window_seconds: SUBMIT_WINDOW_SECONDS,
+                pending,
+            })
+        };
+        let (queue_depth, account) = tokio::join!(queue_depth, account);
+
+        DiagnosticSnapshot {
+            request_id: request_id.map(str::to_string),
+            db_pool: DbPoolStatus {
+                connections: db_state.connections,
+                idle: db_state.idle_connections,
+            },
+            redis_pool: RedisPoolStatus {
+                max_size: redis_status.max_size,
+                size: redis_status.size,
+                available: redis_status.available,
+            },
+            queue_depth,
+            account,
+        }
+    }
+}
+
+/// Result of `read` if it succeeds before `deadline`
+async fn within<T, E>(deadline: Instant, read: impl Future<Output = Result<T, E>>) -> Option<T> {
+    tokio::time::timeout_at(deadline, read).await.ok()?.ok()
+}
+
+/// Middleware tagging every response with a request id (the caller's
+/// X-Request-Id if it sent one) and logging server errors with a snapshot;
+/// use with `axum::middleware::from_fn_with_state(state, log_server_errors)`
+pub async fn log_server_errors(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    if !response.status().is_server_error() {
+        return response;
+    }
+
+    let detail = response.extensions().get::<ErrorDetail>();
+    let message = detail.map_or("", |detail| detail.message.as_str());
+    let account_id = detail.and_then(|detail| detail.account_id.as_deref());
+    let snapshot = state.diagnostic_snapshot(Some(&request_id), account_id).await;
+    tracing::error!(
+        status = response.status().as_u16(),
+        %method,
+        path,
+        request_id,
+        error = message,
+        snapshot = %serde_json::to_string(&snapshot).unwrap_or_default(),
+        "Request failed"
+    );
+    response
+}
@@ -3,15 +3,34 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use api_client::types::{ErrorBody, ErrorResponse, RetryInfo};
+use redis_cache::RateLimitResult;
 use std::fmt;
-use axum::http::HeaderMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use axum::http::{header, HeaderMap, HeaderValue};
 
 #[derive(Debug)]
 pub struct AppError {
     pub status: StatusCode,
     pub message: String,
+    /// Machine readable reason, for errors clients are expected to branch on
+    pub code: Option<&'static str>,
+    /// Account the failed request was for, logged with server errors
+    pub account_id: Option<String>,
     pub headers: Option<HeaderMap>,
+    /// Structured detail sent to the client alongside the message
+    pub details: Option<serde_json::Value>,
+    /// When a rate limited request can be retried, sent in the body and as
+    /// Retry-After
+    pub retry: Option<RetryInfo>,
+}
+
+/// Attached to error responses so server errors can be logged with the
+/// detail the client does not see
+#[derive(Debug, Clone)]
+pub struct ErrorDetail {
+    pub message: String,
+    pub account_id: Option<String>,
 }
 
 impl AppError {
@@ -19,7 +38,11 @@ impl AppError {
         Self {
             status,
             message: message.into(),
+            code: None,
+            account_id: None,
             headers: None,
+            details: None,
+            retry: None,
         }
     }
 
@@ -27,18 +50,74 @@ impl AppError {
         Self::new(StatusCode::BAD_REQUEST, message)
     }
 
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
     pub fn internal_server_error(message: impl Into<String>) -> Self {
         Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
     }
 
+    pub fn service_unavailable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, message)
+    }
+
     pub fn too_many_requests(message: impl Into<String>) -> Self {
         Self::new(StatusCode::TOO_MANY_REQUESTS, message)
     }
 
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn for_account(mut self, account_id: &str) -> Self {
+        self.account_id = Some(account_id.to_string());
+        self
+    }
+
     pub fn with_headers(mut self, headers: HeaderMap) -> Self {
         self.headers = Some(headers);
         self
     }
+
+    pub fn with_details(mut self, details: impl serde::Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    /// Rejected by `result`, a check against `limit`: say when to retry
+    pub fn with_retry(mut self, limit: u32, result: &RateLimitResult) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.retry = Some(RetryInfo {
+            retry_after_seconds: result.retry_after_seconds(now),
+            limit,
+            remaining: result.remaining.min(limit),
+            reset_at: result.reset_at,
+        });
+        self
+    }
+
+    /// The error as the client sees it
+    pub fn body(&self) -> ErrorBody {
+        // Server error details stay in the logs; clients get the status text
+        let message = if self.status.is_server_error() {
+            self.status.canonical_reason().unwrap_or("Server error")
+        } else {
+            self.message.as_str()
+        };
+        ErrorBody {
+            message: message.to_string(),
+            status: self.status.as_u16(),
+            code: self.code.map(str::to_string),
+            details: self.details.clone(),
+            retry: self.retry,
+        }
+    }
 }
 
 impl fmt::Display for AppError {
@@ -51,14 +130,13 @@ impl std::error::Error for AppError {}
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let body = Json(json!({
-            "error": {
-                "message": self.message,
-                "status": self.status.as_u16(),
-            }
-        }));
+        let body = Json(ErrorResponse { error: self.body() });
 
         let mut resp = (self.status, body).into_response();
+        resp.extensions_mut().insert(ErrorDetail {
+            message: self.message,
+            account_id: self.account_id,
+        });
 
         // Add custom HEADERS
         if let Some(headers) = self.headers {
@@ -67,6 +145,9 @@ impl IntoResponse for AppError {
                 headers_mut.insert(key, value.clone());
             }
         }
+        if let Some(retry) = self.retry {
+            resp.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry.retry_after_seconds));
+        }
 
         resp
     }
@@ -80,6 +161,9 @@ impl From<postgres_models::DbError> for AppError {
 
 impl From<redis_cache::RedisError> for AppError {
     fn from(err: redis_cache::RedisError) -> Self {
+        if err.is_transient() {
+            return AppError::service_unavailable(format!("Redis temporarily unavailable: {}", err));
+        }
         AppError::internal_server_error(format!("Redis error: {}", err))
     }
 }
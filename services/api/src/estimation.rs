@@ -0,0 +1,70 @@
+use crate::{config::Config, submit_deadline::SubmitQueue};
+use redis_cache::QueueName;
+
+/// Fallback cost of a single queued item when no throughput has been measured yet
+pub const DEFAULT_SECONDS_PER_ITEM: f64 = 30.0;
+
+/// Window over which measured throughput is averaged
+pub const THROUGHPUT_WINDOW_MINUTES: u64 = 5;
+
+/// Estimate how long until a queued item has been processed.
+///
+/// `items_ahead` is the number of items that will be dequeued before this one
+/// (0-indexed rank in the queue). When nothing is ahead and at least one worker
+/// is alive the item will be picked up immediately, so the estimate is 0.
+/// Otherwise the item waits for everything ahead of it plus its own slot,
+/// priced at the measured throughput (items/second) or the 30s heuristic,
+/// scaled by the `tier_multiplier` of the submitting account.
+pub fn estimate_processing_seconds(
+    items_ahead: i64,
+    throughput_per_second: Option<f64>,
+    workers_alive: bool,
+    tier_multiplier: f64,
+    max_seconds: i64,
+) -> i64 {
+    let items_ahead = items_ahead.max(0);
+    if items_ahead == 0 && workers_alive {
+        return 0;
+    }
+
+    let seconds_per_item = match throughput_per_second {
+        Some(rate) if rate > 0.0 => 1.0 / rate,
+        _ => DEFAULT_SECONDS_PER_ITEM,
+    };
+
+    let estimate = ((items_ahead + 1) as f64 * seconds_per_item * tier_multiplier).ceil();
+    (estimate as i64).clamp(0, max_seconds.max(0))
+}
+
+pub use api_client::types::EstimateUnavailable;
+
+/// Estimate for an item at a 1-indexed queue `position`, shared by submit
+/// and status so the two agree. With no live workers the queue is not
+/// draining and any number would be a guess, so there is none.
+pub fn estimate_at_position(
+    position: i64,
+    throughput_per_second: Option<f64>,
+    workers_alive: bool,
+    tier_multiplier: f64,
+    max_seconds: i64,
+) -> Result<i64, EstimateUnavailable> {
+    if !workers_alive {
+        return Err(EstimateUnavailable::NoLiveWorkers);
+    }
+    Ok(estimate_processing_seconds(position - 1, throughput_per_second, true, tier_multiplier, max_seconds))
+}
+
+/// Estimate multiplier of the tier `account_id` falls in, 1 outside any
+pub fn tier_multiplier(config: &Config, account_id: &str) -> f64 {
+    config.tier_limit(account_id).map_or(1.0, |tier| tier.estimate_multiplier)
+}
+
+/// Measured throughput and worker liveness, the live inputs of an estimate.
+/// Lookup failures count as no measurement and no live workers.
+pub async fn live_inputs(queue: &dyn SubmitQueue, queue_name: &QueueName, config: &Config) -> (Option<f64>, bool) {
+    let (throughput, workers) = tokio::join!(
+        queue.processing_rate(queue_name),
+        queue.live_worker_count(config.worker_heartbeat_timeout_seconds),
+    );
+    (throughput.unwrap_or(None), workers.map(|count| count > 0).unwrap_or(false))
+}
@@ -0,0 +1,64 @@
+use crate::{config::AdminApiKey, errors::AppError, AppState};
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::{request::Parts, Extensions, StatusCode},
+};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+
+pub const ADMIN_KEY_HEADER: &str = "x-admin-key";
+
+/// Authenticated caller of the admin API.
+///
+/// The identity is the id of the presented API key, or the client IP when
+/// no keys are configured in development.
+#[derive(Debug, Clone)]
+pub struct AdminIdentity(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminIdentity
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let app_state = AppState::from_ref(state);
+        let keys = &app_state.config.admin_api_keys;
+
+        if keys.is_empty() {
+            if app_state.config.common.is_development() {
+                let ip = client_ip(&parts.extensions).unwrap_or_else(|| "unknown".to_string());
+                return Ok(AdminIdentity(format!("ip:{}", ip)));
+            }
+            return Err(AppError::new(StatusCode::FORBIDDEN, "Admin API is disabled"));
+        }
+
+        let presented = parts
+            .headers
+            .get(ADMIN_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| AppError::unauthorized("Missing admin API key"))?;
+
+        matching_key(keys, presented)
+            .map(|admin_key| AdminIdentity(admin_key.id.clone()))
+            .ok_or_else(|| AppError::unauthorized("Invalid admin API key"))
+    }
+}
+
+/// The configured key `presented` matches. Keys are compared as SHA-256
+/// digests, so how long a comparison takes says nothing about how much of a
+/// key was guessed right.
+fn matching_key<'a>(keys: &'a [AdminApiKey], presented: &str) -> Option<&'a AdminApiKey> {
+    let presented = Sha256::digest(presented.as_bytes());
+    keys.iter().find(|admin_key| Sha256::digest(admin_key.key.as_bytes()) == presented)
+}
+
+/// Peer address of the connection, available when the server is started with connect info
+pub fn client_ip(extensions: &Extensions) -> Option<String> {
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+}
@@ -1,10 +1,84 @@
-use crate::lib::AppState;
+use crate::{errors::AppError, metrics, AppState};
 use axum::{
     async_trait,
     extract::{FromRef, FromRequestParts},
-    http::{request::Parts, StatusCode},
+    http::{header, request::Parts, HeaderMap, HeaderValue},
 };
-use postgres_models::DbConnection;
+use bb8::RunError;
+use postgres_models::{DbConnection, DbPool};
+use rand::Rng;
+use serde_json::json;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Tries at borrowing a connection per request: the first plus two retries
+pub const ACQUIRE_ATTEMPTS: u32 = 3;
+
+/// Time the retries may take together, counted from the first failure
+pub const ACQUIRE_RETRY_BUDGET: Duration = Duration::from_millis(50);
+
+/// Longest pause before a retry; the actual pause is uniform up to this
+const ACQUIRE_RETRY_BACKOFF: Duration = Duration::from_millis(15);
+
+/// Seconds clients are told to wait when no connection could be borrowed
+const RETRY_AFTER_SECONDS: u64 = 1;
+
+/// Error code of the 503 sent when every pooled connection stayed in use
+pub const DB_POOL_EXHAUSTED: &str = "db_pool_exhausted";
+
+/// Borrow a connection, retrying briefly when the pool is momentarily
+/// exhausted. A burst that holds every connection usually releases one
+/// within milliseconds, so a short jittered retry turns most of those 503s
+/// into successes; past the budget the client is told to back off.
+pub async fn acquire_connection(pool: &DbPool) -> Result<DbConnection, AppError> {
+    let mut attempts = 1;
+    let mut result = pool.get_owned().await;
+    let retry_deadline = Instant::now() + ACQUIRE_RETRY_BUDGET;
+
+    while result.is_err() && attempts < ACQUIRE_ATTEMPTS {
+        let backoff = rand::thread_rng().gen_range(Duration::ZERO..=ACQUIRE_RETRY_BACKOFF);
+        tokio::time::sleep_until((Instant::now() + backoff).min(retry_deadline)).await;
+        if Instant::now() >= retry_deadline {
+            break;
+        }
+        attempts += 1;
+        result = match tokio::time::timeout_at(retry_deadline, pool.get_owned()).await {
+            Ok(acquired) => acquired,
+            Err(_) => Err(RunError::TimedOut),
+        };
+    }
+
+    ::metrics::histogram!(metrics::DB_ACQUIRE_ATTEMPTS).record(attempts as f64);
+    result.map_err(|e| {
+        let state = pool.state();
+        match e {
+            // Timing out with every open connection checked out means the pool
+            // is too small for the load, not that the database is down
+            RunError::TimedOut if state.connections > 0 && state.idle_connections == 0 => {
+                database_unavailable(format!(
+                    "Database connection pool exhausted: all {} connections stayed in use for as long as the \
+                     request could wait",
+                    state.connections
+                ))
+                .with_code(DB_POOL_EXHAUSTED)
+                // Server error messages stay in the logs, so the reason goes in the details
+                .with_details(json!({
+                    "reason": "every pooled database connection stayed in use",
+                    "connections": state.connections,
+                    "attempts": attempts,
+                }))
+            }
+            e => database_unavailable(format!("Database unavailable after {} attempts: {}", attempts, e)),
+        }
+    })
+}
+
+/// 503 telling the client when to try again, for a database that could not be reached
+pub fn database_unavailable(message: impl Into<String>) -> AppError {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECONDS));
+    AppError::service_unavailable(message).with_headers(headers)
+}
 
 pub struct DatabaseConnection(pub DbConnection);
 
@@ -14,15 +88,11 @@ where
     AppState: FromRef<S>,
     S: Send + Sync,
 {
-    type Rejection = StatusCode;
+    type Rejection = AppError;
 
     async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let app_state = AppState::from_ref(state);
-        let conn = app_state
-            .db_pool
-            .get_owned()
-            .await
-            .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+        let conn = acquire_connection(&app_state.db_pool).await?;
         
         Ok(DatabaseConnection(conn))
     }
@@ -36,15 +106,11 @@ where
     AppState: FromRef<S>,
     S: Send + Sync,
 {
-    type Rejection = StatusCode;
+    type Rejection = AppError;
 
     async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let app_state = AppState::from_ref(state);
-        let conn = app_state
-            .db_pool
-            .get_owned()
-            .await
-            .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+        let conn = acquire_connection(&app_state.db_pool).await?;
         
         Ok(ReadOnlyDatabaseConnection(conn))
     }
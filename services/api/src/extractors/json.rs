@@ -0,0 +1,100 @@
+use crate::{errors::AppError, AppState};
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{rejection::BytesRejection, FromRef, FromRequest, Request},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+};
+use serde::de::DeserializeOwned;
+
+/// Request body types the API accepts, advertised in `Accept-Post`
+pub const SUPPORTED_CONTENT_TYPES: &[&str] = &["application/json"];
+
+/// Largest request body read; longer ones are answered 413
+pub const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// JSON body extractor that checks the content type before parsing.
+///
+/// Unsupported content types are rejected with 415 and an `Accept-Post`
+/// header. A missing content type is accepted when the body looks like
+/// JSON, unless `Config::strict_content_type` is set. All rejections use
+/// the standard error envelope.
+#[derive(Debug)]
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned,
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let strict = AppState::from_ref(state).config.strict_content_type;
+        let content_type = content_type(req.headers());
+
+        if let Some(content_type) = &content_type {
+            if !is_json_content_type(content_type) {
+                return Err(unsupported_media_type());
+            }
+        } else if strict {
+            return Err(unsupported_media_type());
+        }
+
+        let body = Bytes::from_request(req, state).await.map_err(body_rejection)?;
+
+        if content_type.is_none() && !looks_like_json(&body) {
+            return Err(unsupported_media_type());
+        }
+
+        serde_json::from_slice(&body).map(ValidatedJson).map_err(invalid_json)
+    }
+}
+
+/// 422 for JSON of the wrong shape, 400 for text that is not JSON at all
+pub fn invalid_json(e: serde_json::Error) -> AppError {
+    match e.classify() {
+        serde_json::error::Category::Data => {
+            AppError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid request body: {}", e))
+        }
+        _ => AppError::bad_request(format!("Malformed JSON: {}", e)),
+    }
+}
+
+/// Media type of the request without parameters, lowercased
+pub fn content_type(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    let essence = value.split(';').next().unwrap_or_default().trim();
+    (!essence.is_empty()).then(|| essence.to_ascii_lowercase())
+}
+
+fn is_json_content_type(content_type: &str) -> bool {
+    SUPPORTED_CONTENT_TYPES.contains(&content_type)
+        || (content_type.starts_with("application/") && content_type.ends_with("+json"))
+}
+
+fn looks_like_json(body: &[u8]) -> bool {
+    matches!(
+        body.iter().find(|b| !b.is_ascii_whitespace()),
+        Some(b'{') | Some(b'[')
+    )
+}
+
+fn unsupported_media_type() -> AppError {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::HeaderName::from_static("accept-post"),
+        HeaderValue::from_str(&SUPPORTED_CONTENT_TYPES.join(", ")).expect("static header value"),
+    );
+    AppError::new(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        format!("Unsupported content type, expected one of: {}", SUPPORTED_CONTENT_TYPES.join(", ")),
+    )
+    .with_headers(headers)
+}
+
+fn body_rejection(rejection: BytesRejection) -> AppError {
+    AppError::new(rejection.status(), rejection.body_text())
+}
@@ -1,3 +1,9 @@
+pub mod admin;
 pub mod database;
+pub mod json;
+pub mod path;
 
-pub use database::{DatabaseConnection, ReadOnlyDatabaseConnection};
\ No newline at end of file
+pub use admin::AdminIdentity;
+pub use database::{acquire_connection, DatabaseConnection, ReadOnlyDatabaseConnection};
+pub use json::ValidatedJson;
+pub use path::ValidatedPath;
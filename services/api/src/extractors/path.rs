@@ -0,0 +1,30 @@
+use crate::errors::AppError;
+use axum::{
+    async_trait,
+    extract::{rejection::PathRejection, FromRequestParts, Path},
+    http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+
+/// Path parameter extractor whose rejections use the standard error
+/// envelope, so a malformed id is a JSON 400 like any other bad request
+#[derive(Debug)]
+pub struct ValidatedPath<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidatedPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<T>::from_request_parts(parts, state).await.map_err(path_rejection)?;
+        Ok(ValidatedPath(value))
+    }
+}
+
+fn path_rejection(rejection: PathRejection) -> AppError {
+    AppError::new(rejection.status(), rejection.body_text())
+}
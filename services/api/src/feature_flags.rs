@@ -0,0 +1,138 @@
+//! Percentage rollouts for new code paths.
+//!
+//! Each flag's percentage lives in the Redis hash `feature_flags`, so every
+//! instance sees a change without a restart. Instances keep a local copy
+//! and reread the hash at most once per refresh interval; a change made
+//! through this instance is seen immediately. An account is in a flag's
+//! rollout when its bucket, a stable hash of flag and account id in
+//! `0..100`, is below the percentage, so raising the percentage only ever
+//! adds accounts.
+
+use redis_cache::{QueueManager, RedisError};
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+/// Sliding window checks run as a single Lua script
+pub const LUA_RATE_LIMITER: &str = "lua_rate_limiter";
+
+/// Submit limit checks are repeated on the candidate limiter and compared
+pub const SHADOW_RATE_LIMITER: &str = "shadow_rate_limiter";
+
+/// Flags the code consults; the admin API refuses to set any other
+pub const KNOWN_FLAGS: [&str; 2] = [LUA_RATE_LIMITER, SHADOW_RATE_LIMITER];
+
+#[derive(Debug, Default)]
+struct Snapshot {
+    percentages: HashMap<String, u32>,
+    loaded_at: Option<Instant>,
+}
+
+pub struct FeatureFlags {
+    queue: QueueManager,
+    refresh_interval: Duration,
+    snapshot: RwLock<Snapshot>,
+    /// Held while reloading so one caller refreshes and the rest use the old copy
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+impl FeatureFlags {
+    /// Flags kept in the key space of `queue`
+    pub fn new(queue: QueueManager, refresh_interval: Duration) -> Self {
+        Self {
+            queue,
+            refresh_interval,
+            snapshot: RwLock::default(),
+            refreshing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Whether `account_id` is in the rollout of `flag`. Flags that were never
+    /// set, or could not be read, are off.
+    pub async fn enabled(&self, flag: &str, account_id: &str) -> bool {
+        let percentage = self.percentage(flag).await;
+        percentage > 0 && rollout_bucket(flag, account_id) < percentage
+    }
+
+    /// Current rollout percentage of `flag`, 0 when unset
+    pub async fn percentage(&self, flag: &str) -> u32 {
+        self.refresh_if_stale().await;
+        let snapshot = self.snapshot.read().unwrap_or_else(PoisonError::into_inner);
+        snapshot.percentages.get(flag).copied().unwrap_or(0).min(100)
+    }
+
+    /// Set a flag's percentage for every instance
+    pub async fn set(&self, flag: &str, percentage: u32) -> Result<(), RedisError> {
+        self.queue.set_feature_flag(flag, percentage).await?;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Turn a flag off for every instance. Returns false if it was not set.
+    pub async fn clear(&self, flag: &str) -> Result<bool, RedisError> {
+        let removed = self.queue.clear_feature_flag(flag).await?;
+        self.invalidate();
+        Ok(removed)
+    }
+
+    /// Percentages as stored in Redis, bypassing the local copy
+    pub async fn load(&self) -> Result<HashMap<String, u32>, RedisError> {
+        self.queue.feature_flags().await
+    }
+
+    /// Drop the local copy so the next check rereads Redis
+    pub fn invalidate(&self) {
+        self.snapshot.write().unwrap_or_else(PoisonError::into_inner).loaded_at = None;
+    }
+
+    async fn refresh_if_stale(&self) {
+        if !self.is_stale() {
+            return;
+        }
+        let Ok(_refreshing) = self.refreshing.try_lock() else {
+            return;
+        };
+        // Another caller may have finished a refresh while this one waited
+        if !self.is_stale() {
+            return;
+        }
+
+        let loaded = self.load().await;
+        let mut snapshot = self.snapshot.write().unwrap_or_else(PoisonError::into_inner);
+        match loaded {
+            // Only flags the code consults are kept, so stray hash fields cost no memory
+            Ok(percentages) => {
+                snapshot.percentages = percentages
+                    .into_iter()
+                    .filter(|(flag, _)| KNOWN_FLAGS.contains(&flag.as_str()))
+                    .collect()
+            }
+            // Keep the last known percentages and try again next interval
+            Err(e) => tracing::warn!("Failed to refresh feature flags: {}", e),
+        }
+        snapshot.loaded_at = Some(Instant::now());
+    }
+
+    fn is_stale(&self) -> bool {
+        let snapshot = self.snapshot.read().unwrap_or_else(PoisonError::into_inner);
+        snapshot
+            .loaded_at
+            .is_none_or(|loaded_at| loaded_at.elapsed() >= self.refresh_interval)
+    }
+}
+
+/// Stable bucket in `0..100` of an account for a flag.
+///
+/// Hashed per flag so the same accounts are not first in every rollout.
+/// FNV-1a rather than the std hasher, whose output may change between
+/// releases and would reshuffle rollouts on upgrade.
+pub fn rollout_bucket(flag: &str, account_id: &str) -> u32 {
+    let hash = flag
+        .bytes()
+        .chain(std::iter::once(b':'))
+        .chain(account_id.bytes())
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    (hash % 100) as u32
+}
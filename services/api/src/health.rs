@@ -0,0 +1,243 @@
+use crate::AppState;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use postgres_models::DbPool;
+use redis_cache::RedisPool;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Liveness routes, mounted at the root of every listener. They answer ok
+/// whenever the process can serve a request; `/health` is kept for probes
+/// configured before `/health/live` existed.
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new().route("/health", get(live)).route("/health/live", get(live))
+}
+
+/// Readiness route, which load balancers poll to decide whether to send
+/// this instance traffic
+pub fn ready_router() -> Router<AppState> {
+    Router::new().route("/health/ready", get(ready))
+}
+
+async fn live() -> Json<serde_json::Value> {
+    Json(json!({
+        "status": "ok",
+        "service": "transaction-queue-api"
+    }))
+}
+
+/// Whether this instance wants new traffic. Ops drain an instance by
+/// clearing it, through the admin API or SIGUSR1; requests still arriving
+/// are served as usual, only `/health/ready` changes.
+#[derive(Debug)]
+pub struct Readiness {
+    ready: AtomicBool,
+    started_at: Instant,
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self {
+            ready: AtomicBool::new(true),
+            started_at: Instant::now(),
+        }
+    }
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Set the flag, returning its previous value
+    pub fn set_ready(&self, ready: bool) -> bool {
+        self.ready.swap(ready, Ordering::AcqRel)
+    }
+
+    /// Flip the flag, returning its new value
+    pub fn toggle(&self) -> bool {
+        !self.ready.fetch_xor(true, Ordering::AcqRel)
+    }
+
+    /// The flag alone, without checking dependencies
+    pub fn report(&self) -> ReadinessReport {
+        let ready = self.is_ready();
+        ReadinessReport {
+            status: if ready { "ready" } else { "draining" },
+            ready,
+            uptime_seconds: self.started_at.elapsed().as_secs(),
+            dependencies: None,
+        }
+    }
+
+    /// The flag together with `dependencies`; ready only when the flag is
+    /// set and every dependency is up
+    pub fn report_with(&self, dependencies: Dependencies) -> ReadinessReport {
+        let mut report = self.report();
+        if report.ready && !dependencies.values().all(DependencyStatus::is_up) {
+            report.status = "unavailable";
+            report.ready = false;
+        }
+        report.dependencies = Some(dependencies);
+        report
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessReport {
+    pub status: &'static str,
+    pub ready: bool,
+    pub uptime_seconds: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<Dependencies>,
+}
+
+/// Outcome of each dependency check, by dependency name
+pub type Dependencies = BTreeMap<&'static str, DependencyStatus>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencyStatus {
+    /// "up" or "down"
+    pub status: &'static str,
+    pub latency_ms: u64,
+    /// Why it is down, kept generic as the body is public; the underlying
+    /// error is only logged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyStatus {
+    pub fn is_up(&self) -> bool {
+        self.status == "up"
+    }
+}
+
+/// The Postgres and Redis checks behind `/health/ready`.
+///
+/// A result is reused for the cache TTL. The lock is held while the checks
+/// run, so probes arriving meanwhile wait for that run and share its result
+/// rather than each checking out a connection of their own. In memory mode
+/// there is nothing to check.
+pub struct DependencyChecks {
+    pools: Option<(DbPool, RedisPool)>,
+    timeout: Duration,
+    ttl: Duration,
+    last: Mutex<Option<(Instant, Dependencies)>>,
+    runs: AtomicU64,
+}
+
+impl DependencyChecks {
+    pub fn new(pools: Option<(DbPool, RedisPool)>, timeout: Duration, ttl: Duration) -> Self {
+        Self {
+            pools,
+            timeout,
+            ttl,
+            last: Mutex::new(None),
+            runs: AtomicU64::new(0),
+        }
+    }
+
+    /// The latest checks, run again if the cached ones are older than the TTL
+    pub async fn check(&self) -> Dependencies {
+        let Some((db_pool, redis_pool)) = &self.pools else {
+            return Dependencies::new();
+        };
+        let mut last = self.last.lock().await;
+        if let Some((checked_at, dependencies)) = last.as_ref() {
+            if checked_at.elapsed() < self.ttl {
+                return dependencies.clone();
+            }
+        }
+
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        let (postgres, redis) = tokio::join!(
+            timed("postgres", self.timeout, check_postgres(db_pool)),
+            timed("redis", self.timeout, check_redis(redis_pool)),
+        );
+        let dependencies = Dependencies::from([("postgres", postgres), ("redis", redis)]);
+        *last = Some((Instant::now(), dependencies.clone()));
+        dependencies
+    }
+
+    /// How many times the checks have run, cached results aside
+    pub fn runs(&self) -> u64 {
+        self.runs.load(Ordering::Relaxed)
+    }
+}
+
+/// Run the check of dependency `name` within `timeout`, timing it. A
+/// failure is logged with its error and reported with a generic reason.
+async fn timed(
+    name: &'static str,
+    timeout: Duration,
+    check: impl Future<Output = Result<(), String>>,
+) -> DependencyStatus {
+    let started = Instant::now();
+    let error = match tokio::time::timeout(timeout, check).await {
+        Ok(Ok(())) => None,
+        Ok(Err(error)) => {
+            tracing::warn!(dependency = name, %error, "Readiness check failed");
+            Some(format!("{} unavailable", name))
+        }
+        Err(_) => {
+            tracing::warn!(dependency = name, timeout_ms = timeout.as_millis() as u64, "Readiness check timed out");
+            Some(format!("{} timed out", name))
+        }
+    };
+    DependencyStatus {
+        status: if error.is_none() { "up" } else { "down" },
+        latency_ms: started.elapsed().as_millis() as u64,
+        error,
+    }
+}
+
+/// Check out a connection and run `SELECT 1` on it
+async fn check_postgres(db_pool: &DbPool) -> Result<(), String> {
+    let mut conn = db_pool.get().await.map_err(|e| e.to_string())?;
+    let query = diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>("1"));
+    // Called through the trait, whose `load` would shadow the atomics' here
+    diesel_async::RunQueryDsl::execute(query, &mut conn).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn check_redis(redis_pool: &RedisPool) -> Result<(), String> {
+    let mut conn = redis_pool.get().await.map_err(|e| e.to_string())?;
+    let _: String = deadpool_redis::redis::cmd("PING")
+        .query_async(&mut *conn)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Ready when the flag is set and Postgres and Redis both answer in time;
+/// 503 otherwise, with each dependency's status and latency in the body
+async fn ready(State(state): State<AppState>) -> (StatusCode, Json<ReadinessReport>) {
+    let report = state.readiness.report_with(state.dependencies.check().await);
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// Flip readiness on every SIGUSR1, so an instance can be drained and
+/// restored from its host
+pub fn spawn_drain_signal_handler(readiness: Arc<Readiness>) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    let mut signals = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())?;
+    Ok(tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            let ready = readiness.toggle();
+            tracing::warn!(ready, "Readiness toggled by SIGUSR1");
+        }
+    }))
+}
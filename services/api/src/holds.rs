@@ -0,0 +1,44 @@
+use chrono::Utc;
+use postgres_models::models::TransactionQueue;
+use redis_cache::{HoldReason, ProcessingHold, QueueManager, RedisError};
+
+/// Decide whether a pending transaction has to be skipped on this pass.
+///
+/// Only checks what can be decided from the item and shared Redis state;
+/// concurrency caps and circuit breakers are decided by the worker itself
+/// and recorded through `QueueManager::record_hold` directly.
+pub async fn evaluate(
+    queue_manager: &QueueManager,
+    transaction: &TransactionQueue,
+) -> Result<Option<HoldReason>, RedisError> {
+    if queue_manager.is_account_paused(&transaction.account_id).await? {
+        return Ok(Some(HoldReason::AccountPaused));
+    }
+
+    if transaction.scheduled_at.is_some_and(|at| at > Utc::now()) {
+        return Ok(Some(HoldReason::ScheduledInFuture));
+    }
+
+    Ok(None)
+}
+
+/// Evaluate a transaction and record the outcome, clearing any previous
+/// hold once the item is eligible again
+pub async fn apply(
+    queue_manager: &QueueManager,
+    transaction: &TransactionQueue,
+) -> Result<Option<ProcessingHold>, RedisError> {
+    let transaction_id = transaction.id.to_string();
+
+    match evaluate(queue_manager, transaction).await? {
+        Some(reason) => {
+            let hold = queue_manager.record_hold(&transaction_id, reason).await?;
+            tracing::debug!(%transaction_id, reason = reason.as_str(), "Holding transaction");
+            Ok(Some(hold))
+        }
+        None => {
+            queue_manager.clear_hold(&transaction_id).await?;
+            Ok(None)
+        }
+    }
+}
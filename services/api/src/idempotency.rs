@@ -0,0 +1,293 @@
+//! Idempotency-Key on submit.
+//!
+//! A submit carrying a key, as the Idempotency-Key header or the
+//! `idempotency_key` field, first claims the account's key in Redis for
+//! IDEMPOTENCY_KEY_TTL_SECONDS along with a fingerprint of its priority and
+//! payload. Later submits with the same account and key get the first one's
+//! response back with a 200 and `Idempotent-Replayed: true`, without being
+//! rate limited or stored again; a different priority or payload under the
+//! key is a 409. A submit arriving while the first is still running waits up
+//! to `IN_FLIGHT_WAIT` for its response, then gets a 409 to retry later.
+//!
+//! A claim whose submit fails is released, so the retry runs afresh. The key
+//! is also stored on the row, unique per account: a retry the claim no
+//! longer covers, e.g. after it expired, fails the insert and is answered
+//! from the stored row instead, without a queue position.
+
+use crate::{
+    errors::{AppError, AppResult},
+    estimation::{estimate_processing_seconds, tier_multiplier},
+    payload::TransactionPayload,
+    redis_failure::{fails_open, refusal},
+    submit_deadline::SubmitPhase,
+    AppState,
+};
+use api_client::types::{Backpressure, SubmitTransactionResponse};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use futures::future::BoxFuture;
+use postgres_models::models::TransactionQueue;
+use redis_cache::{QueueManager, RedisError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Set on responses replayed for a repeated key
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Error code of the 409 sent when a key is reused with another request
+pub const IDEMPOTENCY_KEY_REUSED: &str = "idempotency_key_reused";
+
+/// Error code of the 409 sent when the key's first submit is still running
+pub const IDEMPOTENCY_KEY_IN_PROGRESS: &str = "idempotency_key_in_progress";
+
+/// Longest key accepted, in bytes
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// How long a repeated submit waits for the first one's response
+const IN_FLIGHT_WAIT: Duration = Duration::from_secs(2);
+
+const IN_FLIGHT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type SubmitTransactionRequest = api_client::types::SubmitTransactionRequest<TransactionPayload>;
+
+/// Where idempotency claims are kept
+pub trait IdempotencyKeys: Send + Sync {
+    /// Store `record` under the account's key for `ttl_seconds` unless the
+    /// key is taken. Returns the record already stored, or `None` when this
+    /// call made the claim.
+    fn claim<'a>(
+        &'a self,
+        account_id: &'a str,
+        key: &'a str,
+        record: &'a str,
+        ttl_seconds: u64,
+    ) -> BoxFuture<'a, Result<Option<String>, RedisError>>;
+
+    /// Replace the record of a claimed key, keeping it for another `ttl_seconds`
+    fn update<'a>(
+        &'a self,
+        account_id: &'a str,
+        key: &'a str,
+        record: &'a str,
+        ttl_seconds: u64,
+    ) -> BoxFuture<'a, Result<(), RedisError>>;
+
+    /// Drop the claim so the next submit with the key runs
+    fn release<'a>(&'a self, account_id: &'a str, key: &'a str) -> BoxFuture<'a, Result<(), RedisError>>;
+}
+
+impl IdempotencyKeys for QueueManager {
+    fn claim<'a>(
+        &'a self,
+        account_id: &'a str,
+        key: &'a str,
+        record: &'a str,
+        ttl_seconds: u64,
+    ) -> BoxFuture<'a, Result<Option<String>, RedisError>> {
+        Box::pin(self.claim_idempotency_key(account_id, key, record, ttl_seconds))
+    }
+
+    fn update<'a>(
+        &'a self,
+        account_id: &'a str,
+        key: &'a str,
+        record: &'a str,
+        ttl_seconds: u64,
+    ) -> BoxFuture<'a, Result<(), RedisError>> {
+        Box::pin(self.update_idempotency_key(account_id, key, record, ttl_seconds))
+    }
+
+    fn release<'a>(&'a self, account_id: &'a str, key: &'a str) -> BoxFuture<'a, Result<(), RedisError>> {
+        Box::pin(self.release_idempotency_key(account_id, key))
+    }
+}
+
+/// What a claim holds: the fingerprint of the request that made it and,
+/// once that submit has finished, its response
+#[derive(Serialize, Deserialize)]
+struct ClaimRecord {
+    fingerprint: String,
+    #[serde(default)]
+    response: Option<SubmitTransactionResponse>,
+}
+
+impl ClaimRecord {
+    fn encode(&self) -> String {
+        serde_json::to_string(self).expect("claim records serialize")
+    }
+}
+
+/// Outcome of claiming a submit's key
+pub enum Claim<'a> {
+    /// This submit runs, and settles the claim when done
+    Owned(OwnedClaim<'a>),
+    /// An earlier submit with the key finished with this response
+    Replay(SubmitTransactionResponse),
+    /// Redis is down and submit fails open, so only Postgres guards the key
+    Unguarded,
+}
+
+/// The key's header value or field, refusing a request that sends both with
+/// different values
+pub fn request_key(request: &SubmitTransactionRequest, headers: &HeaderMap) -> Result<Option<String>, &'static str> {
+    let header = match headers.get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => Some(value.to_str().map_err(|_| "Idempotency-Key must be visible ASCII")?.to_string()),
+        None => None,
+    };
+    match (header, &request.idempotency_key) {
+        (Some(header), Some(field)) if header != *field => Err("Idempotency-Key header and idempotency_key differ"),
+        (header, field) => Ok(header.or_else(|| field.clone())),
+    }
+}
+
+/// Why `key` is not a valid idempotency key, if it is not
+pub fn validate_key(key: &str) -> Result<(), &'static str> {
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err("idempotency_key must be 1 to 255 characters");
+    }
+    if !key.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err("idempotency_key may only contain visible ASCII characters");
+    }
+    Ok(())
+}
+
+/// Hex SHA-256 of the priority and payload, with the payload's object keys
+/// sorted so the same JSON written differently matches
+fn fingerprint(priority: i32, transaction_data: &serde_json::Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(priority.to_be_bytes());
+    hasher.update(transaction_data.to_string());
+    hex::encode(hasher.finalize())
+}
+
+/// Fingerprint of a submit, for comparing it with the key's first submit
+pub fn request_fingerprint(request: &SubmitTransactionRequest) -> Result<String, serde_json::Error> {
+    let transaction_data = serde_json::from_str(request.transaction_data.as_raw().get())?;
+    Ok(fingerprint(request.priority.unwrap_or(0), &transaction_data))
+}
+
+fn reused() -> AppError {
+    AppError::new(StatusCode::CONFLICT, "Idempotency-Key was already used for a different transaction")
+        .with_code(IDEMPOTENCY_KEY_REUSED)
+}
+
+/// Claim the account's `key` for this submit, waiting for the response of a
+/// submit that already holds it
+pub async fn claim<'a>(
+    state: &'a AppState,
+    account_id: &'a str,
+    key: &'a str,
+    fingerprint: String,
+) -> AppResult<Claim<'a>> {
+    let ttl_seconds = state.config.idempotency_key_ttl_seconds;
+    let pending = ClaimRecord { fingerprint, response: None };
+    let record = pending.encode();
+    let give_up_at = Instant::now() + IN_FLIGHT_WAIT;
+    loop {
+        let existing = match state.idempotency.claim(account_id, key, &record, ttl_seconds).await {
+            Ok(existing) => existing,
+            Err(e) if fails_open(&state.config, SubmitPhase::Idempotency, &e) => return Ok(Claim::Unguarded),
+            Err(e) => return Err(refusal(e, AppError::from)),
+        };
+        let Some(existing) = existing else {
+            return Ok(Claim::Owned(OwnedClaim { state, account_id, key, record: pending }));
+        };
+        let existing: ClaimRecord = serde_json::from_str(&existing)
+            .map_err(|e| AppError::internal_server_error(format!("Unreadable idempotency record: {}", e)))?;
+        if existing.fingerprint != pending.fingerprint {
+            return Err(reused());
+        }
+        if let Some(response) = existing.response {
+            return Ok(Claim::Replay(response));
+        }
+        if Instant::now() >= give_up_at {
+            return Err(AppError::new(
+                StatusCode::CONFLICT,
+                "A submit with this Idempotency-Key is still in progress, retry later",
+            )
+            .with_code(IDEMPOTENCY_KEY_IN_PROGRESS));
+        }
+        tokio::time::sleep(IN_FLIGHT_POLL_INTERVAL).await;
+    }
+}
+
+/// A key this submit claimed
+pub struct OwnedClaim<'a> {
+    state: &'a AppState,
+    account_id: &'a str,
+    key: &'a str,
+    record: ClaimRecord,
+}
+
+impl OwnedClaim<'_> {
+    /// Keep the response for replays, or release the key when submit failed
+    /// and there is none. A claim that cannot be updated is left to expire,
+    /// logged rather than failing a submit that has already happened.
+    pub async fn settle(mut self, response: Option<&SubmitTransactionResponse>) {
+        let keys = &*self.state.idempotency;
+        let settled = match response {
+            Some(response) => {
+                self.record.response = Some(response.clone());
+                let ttl_seconds = self.state.config.idempotency_key_ttl_seconds;
+                keys.update(self.account_id, self.key, &self.record.encode(), ttl_seconds).await
+            }
+            None => keys.release(self.account_id, self.key).await,
+        };
+        if let Err(e) = settled {
+            tracing::warn!(account_id = self.account_id, "Failed to settle idempotency key: {}", e);
+        }
+    }
+}
+
+/// Headers of a response replayed for a repeated key
+pub fn replay_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    headers
+}
+
+/// Response for the row stored earlier under the account's `key`, when an
+/// insert was refused because of it. A row stored for a different request
+/// is a 409.
+pub async fn stored_response(
+    state: &AppState,
+    request: &SubmitTransactionRequest,
+    key: &str,
+) -> AppResult<Option<SubmitTransactionResponse>> {
+    let row = state
+        .submit_store
+        .find_by_idempotency_key(&request.account_id, key)
+        .await
+        .map_err(|e| AppError::internal_server_error(e.to_string()))?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let fingerprint_of_request = request_fingerprint(request)
+        .map_err(|e| AppError::bad_request(format!("Invalid transaction_data: {}", e)))?;
+    if fingerprint(row.priority, &row.transaction_data) != fingerprint_of_request {
+        return Err(reused());
+    }
+    Ok(Some(response_from_row(state, &row)))
+}
+
+fn response_from_row(state: &AppState, row: &TransactionQueue) -> SubmitTransactionResponse {
+    let stats = state.queue_stats.get();
+    SubmitTransactionResponse {
+        transaction_id: row.id,
+        queue_position: None,
+        account_position: None,
+        estimated_processing_time_seconds: estimate_processing_seconds(
+            stats.as_ref().map_or(0, |stats| stats.queue_depth),
+            stats.as_ref().map(|stats| stats.dequeue_rate_per_second),
+            false,
+            tier_multiplier(&state.config, &row.account_id),
+            state.config.max_estimated_processing_seconds,
+        ),
+        status: row.status.clone(),
+        warnings: Vec::new(),
+        backpressure: Backpressure::NONE,
+    }
+}
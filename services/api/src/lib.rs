@@ -1,22 +1,388 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{extract::DefaultBodyLimit, middleware, Router};
+use metrics_exporter_prometheus::PrometheusHandle;
+use postgres_models::sources::{Clock, IdGenerator, SystemClock, TimeOrderedIds};
 use postgres_models::DbPool;
-use redis_cache::RedisPool;
+use redis_cache::{
+    EventPublisher, KeyLayout, KeySpace, QueueManager, RateLimitStrategy, RateLimiter, RedisOptions, RedisPool,
+};
+
+pub mod backpressure;
+pub mod body_timeout;
+pub mod bounded_cache;
+pub mod cancellation;
+pub mod config;
+pub mod consistency;
+pub mod diagnostics;
+pub mod errors;
+pub mod estimation;
+pub mod extractors;
+pub mod feature_flags;
+pub mod health;
+pub mod holds;
+pub mod idempotency;
+pub mod memory_backend;
+pub mod metrics;
+pub mod panics;
+pub mod payload;
+pub mod payload_schema;
+pub mod pending;
+pub mod queue_stats;
+pub mod rate_limit;
+pub mod redis_failure;
+pub mod runtime_info;
+pub mod server;
+pub mod smoke;
+pub mod stale_processing;
+pub mod submit_deadline;
+pub mod submit_store;
+pub mod trace_context;
+pub mod v1;
+pub mod warmup;
+pub mod webhooks;
+
+use tower::ServiceBuilder;
+use tower_http::cors::CorsLayer;
+use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
+use tracing::Level;
+
+use crate::body_timeout::body_read_timeout;
+use crate::bounded_cache::BoundedCache;
+use crate::config::{Backend, Config};
+use crate::consistency::ConsistencyTokens;
+use crate::diagnostics::log_server_errors;
+use crate::extractors::json::MAX_BODY_BYTES;
+use crate::panics::catch_panic;
+use crate::feature_flags::FeatureFlags;
+use crate::health::{DependencyChecks, Readiness};
+use crate::idempotency::IdempotencyKeys;
+use crate::memory_backend::{MemoryIdempotency, MemoryLimiter, MemoryQueue, MemoryStore};
+use crate::payload_schema::PayloadSchemas;
+use crate::rate_limit::rejections::RejectionMarkers;
+use crate::rate_limit::shadow::{ScriptShadowLimiter, ShadowCompare, ShadowLimiter};
+use crate::rate_limit::AccountLimits;
+use crate::queue_stats::LatestQueueStats;
+use crate::runtime_info::RuntimeInfo;
+use crate::submit_deadline::SubmitQueue;
+use crate::submit_store::{PgSubmitStore, SubmitStore};
+
+/// Redis connection settings from config
+pub fn redis_options(config: &Config) -> RedisOptions {
+    RedisOptions {
+        db: config.common.redis_db,
+        response_timeout: config.common.redis_response_timeout_ms.map(Duration::from_millis),
+        pool: config.common.redis_pool.clone(),
+    }
+}
+
+/// The API's routes and middleware over `state`, with `/metrics` served from
+/// `metrics_handle` when given
+pub fn app(state: AppState, metrics_handle: Option<PrometheusHandle>) -> Router {
+    let mut router = Router::new().merge(health::router()).merge(health::ready_router());
+    if let Some(handle) = metrics_handle {
+        let scraped = state.clone();
+        router = router.route(
+            "/metrics",
+            axum::routing::get(move || {
+                let (state, handle) = (scraped.clone(), handle.clone());
+                async move { metrics::render(&state, &handle).await }
+            }),
+        );
+    }
+
+    let body_read_budget = Duration::from_millis(state.config.body_read_timeout_ms);
+    router
+        .nest("/v1", v1::router(state.clone()))
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .layer(
+            ServiceBuilder::new()
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+                        .on_response(DefaultOnResponse::new().level(Level::INFO)),
+                )
+                .layer(CorsLayer::permissive())
+                .layer(middleware::from_fn_with_state(state.clone(), log_server_errors))
+                .layer(middleware::from_fn_with_state(body_read_budget, body_read_timeout))
+                .layer(middleware::from_fn(catch_panic)),
+        )
+        .layer(middleware::from_fn(metrics::track_requests))
+        .with_state(state)
+}
+
+/// Redis queue every submitted transaction goes through
+pub use queue_engine::TRANSACTION_QUEUE;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: DbPool,
     pub redis_pool: RedisPool,
+    pub config: Arc<Config>,
+    /// Source of transaction ids
+    pub ids: Arc<dyn IdGenerator>,
+    /// Source of row timestamps
+    pub clock: Arc<dyn Clock>,
+    /// Latest queue stats sample, kept by the lag sampler
+    pub queue_stats: Arc<LatestQueueStats>,
+    /// Queue operations submit runs under its deadline
+    pub submit_queue: Arc<dyn SubmitQueue>,
+    /// Rollout state of code paths behind feature flags
+    pub flags: Arc<FeatureFlags>,
+    /// Candidate limiter compared against the live one for accounts in the
+    /// shadow_rate_limiter rollout
+    pub shadow: Arc<ShadowCompare>,
+    /// Account limits read recently, in front of the Redis limit cache
+    pub limit_cache: Arc<BoundedCache<String, AccountLimits>>,
+    /// Accounts rejected by their submit limit moments ago
+    pub rejections: Arc<RejectionMarkers>,
+    /// Compiled payload schemas of accounts that submitted recently
+    pub payload_schemas: Arc<PayloadSchemas>,
+    /// Limit lookups and inserts of the submit path
+    pub submit_store: Arc<dyn SubmitStore>,
+    /// Claims on the Idempotency-Keys of submits
+    pub idempotency: Arc<dyn IdempotencyKeys>,
+    /// Signs the tokens that let status reads right after submit see the write
+    pub consistency: Arc<ConsistencyTokens>,
+    /// Whether this instance reports itself ready for traffic
+    pub readiness: Arc<Readiness>,
+    /// Postgres and Redis checks of `/health/ready`, cached briefly
+    pub dependencies: Arc<DependencyChecks>,
+    /// Rate limit windows kept in process in memory mode, in place of Redis
+    pub local_limiter: Option<Arc<MemoryLimiter>>,
+    /// Lifecycle events of the transaction queue; drops them unless
+    /// QUEUE_EVENTS is set
+    pub events: EventPublisher,
+    /// Build, config and route facts reported by GET /v1/admin/info
+    pub info: Arc<RuntimeInfo>,
 }
 
 impl AppState {
-    pub async fn new(database_url: &str, redis_url: &str) -> anyhow::Result<Self> {
-        let db_pool = postgres_models::create_pool(database_url).await
-            .map_err(|e| anyhow::anyhow!("Failed to create database pool: {}", e))?;
-        let redis_pool = redis_cache::create_pool(redis_url).await
-            .map_err(|e| anyhow::anyhow!("Failed to create Redis pool: {}", e))?;
+    /// State with every dependency built from config
+    pub async fn new(config: Config) -> anyhow::Result<Self> {
+        Self::builder(config).build().await
+    }
+
+    /// Builder for state with some dependencies supplied by the caller
+    pub fn builder(config: Config) -> AppStateBuilder {
+        AppStateBuilder {
+            config,
+            db_pool: None,
+            redis_pool: None,
+            ids: None,
+            clock: None,
+            submit_queue: None,
+            shadow_limiter: None,
+            submit_store: None,
+        }
+    }
+
+    /// Replace the id and time sources, e.g. with seeded ones in tests
+    pub fn with_sources(mut self, ids: Arc<dyn IdGenerator>, clock: Arc<dyn Clock>) -> Self {
+        self.ids = ids;
+        self.clock = clock;
+        self
+    }
+
+    /// Replace the queue submit writes to, e.g. with a slow fake in tests
+    pub fn with_submit_queue(mut self, queue: Arc<dyn SubmitQueue>) -> Self {
+        self.submit_queue = queue;
+        self
+    }
+
+    /// Replace the candidate limiter, e.g. with a divergent fake in tests
+    pub fn with_shadow_limiter(mut self, limiter: Arc<dyn ShadowLimiter>) -> Self {
+        self.shadow = Arc::new(ShadowCompare::new(limiter));
+        self
+    }
+
+    /// Queue manager with Redis read hedging applied from config
+    pub fn queue_manager(&self) -> QueueManager {
+        hedged_queue_manager(&self.redis_pool, &self.config)
+    }
+
+    /// Rate limiter using the configured key layout and prefix
+    pub fn rate_limiter(&self) -> RateLimiter {
+        self.rate_limiter_with_strategy(RateLimitStrategy::default())
+    }
+
+    /// Limiter counting requests with `strategy`, on the same keys
+    pub fn rate_limiter_with_strategy(&self, strategy: RateLimitStrategy) -> RateLimiter {
+        RateLimiter::new_with_strategy(self.redis_pool.clone(), strategy).with_keys(key_space(&self.config))
+    }
+}
+
+/// Builds an `AppState`, creating from config whatever was not supplied.
+///
+/// Given a submit store, the database pool is created without connecting,
+/// so state with a stubbed store can be built while Postgres is down. In
+/// memory mode the store, queue and limiter default to the in-memory ones
+/// and neither Postgres nor Redis is connected to.
+pub struct AppStateBuilder {
+    config: Config,
+    db_pool: Option<DbPool>,
+    redis_pool: Option<RedisPool>,
+    ids: Option<Arc<dyn IdGenerator>>,
+    clock: Option<Arc<dyn Clock>>,
+    submit_queue: Option<Arc<dyn SubmitQueue>>,
+    shadow_limiter: Option<Arc<dyn ShadowLimiter>>,
+    submit_store: Option<Arc<dyn SubmitStore>>,
+}
 
-        Ok(Self {
+impl AppStateBuilder {
+    pub fn db_pool(mut self, pool: DbPool) -> Self {
+        self.db_pool = Some(pool);
+        self
+    }
+
+    pub fn redis_pool(mut self, pool: RedisPool) -> Self {
+        self.redis_pool = Some(pool);
+        self
+    }
+
+    pub fn ids(mut self, ids: Arc<dyn IdGenerator>) -> Self {
+        self.ids = Some(ids);
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub fn submit_queue(mut self, queue: Arc<dyn SubmitQueue>) -> Self {
+        self.submit_queue = Some(queue);
+        self
+    }
+
+    pub fn shadow_limiter(mut self, limiter: Arc<dyn ShadowLimiter>) -> Self {
+        self.shadow_limiter = Some(limiter);
+        self
+    }
+
+    pub fn submit_store(mut self, store: Arc<dyn SubmitStore>) -> Self {
+        self.submit_store = Some(store);
+        self
+    }
+
+    pub async fn build(self) -> anyhow::Result<AppState> {
+        let config = self.config;
+        let memory = config.backend == Backend::Memory;
+        let db_pool = match self.db_pool {
+            Some(pool) => pool,
+            None if memory || self.submit_store.is_some() => {
+                postgres_models::create_lazy_pool_with_config(&config.common.database_url, &config.common.db_pool)
+            }
+            None => postgres_models::create_pool_with_config(&config.common.database_url, &config.common.db_pool).await
+                .map_err(|e| anyhow::anyhow!("Failed to create database pool: {}", e))?,
+        };
+        let redis_pool = match self.redis_pool {
+            Some(pool) => pool,
+            None => redis_cache::create_pool_with_options(&config.common.redis_url, &redis_options(&config)).await
+                .map_err(|e| anyhow::anyhow!("Failed to create Redis pool: {}", e))?,
+        };
+
+        let shadow_limiter = self.shadow_limiter.unwrap_or_else(|| {
+            Arc::new(ScriptShadowLimiter::new(
+                RateLimiter::new(redis_pool.clone()).with_keys(key_space(&config)),
+            ))
+        });
+        Ok(AppState {
+            submit_queue: self.submit_queue.unwrap_or_else(|| match config.backend {
+                Backend::Services => Arc::new(hedged_queue_manager(&redis_pool, &config)),
+                Backend::Memory => Arc::new(MemoryQueue::default()),
+            }),
+            flags: Arc::new(FeatureFlags::new(
+                QueueManager::new(redis_pool.clone()).with_keys(key_space(&config)),
+                Duration::from_millis(config.feature_flag_refresh_ms),
+            )),
+            shadow: Arc::new(ShadowCompare::new(shadow_limiter)),
+            limit_cache: Arc::new(submit_limit_cache(&config)),
+            rejections: Arc::new(RejectionMarkers::new(config.local_limit_cache_capacity)),
+            payload_schemas: Arc::new(PayloadSchemas::new(
+                config.local_limit_cache_capacity,
+                Duration::from_millis(config.payload_schema_cache_ttl_ms),
+            )),
+            submit_store: self.submit_store.unwrap_or_else(|| match config.backend {
+                Backend::Services => Arc::new(pg_submit_store(&db_pool, &config)),
+                Backend::Memory => Arc::new(MemoryStore::default()),
+            }),
+            idempotency: match config.backend {
+                Backend::Services => Arc::new(QueueManager::new(redis_pool.clone()).with_keys(key_space(&config))),
+                Backend::Memory => Arc::new(MemoryIdempotency::default()),
+            },
+            consistency: Arc::new(ConsistencyTokens::new(
+                config.read_your_writes.token_secret.as_deref(),
+                Duration::from_millis(config.read_your_writes.window_ms),
+            )),
+            local_limiter: memory.then(|| Arc::new(MemoryLimiter::default())),
+            events: event_publisher(&redis_pool, &config),
+            dependencies: Arc::new(DependencyChecks::new(
+                (!memory).then(|| (db_pool.clone(), redis_pool.clone())),
+                Duration::from_millis(config.readiness_check_timeout_ms),
+                Duration::from_millis(config.readiness_cache_ttl_ms),
+            )),
+            info: Arc::new(RuntimeInfo::new(&config, chrono::Utc::now())),
             db_pool,
             redis_pool,
+            config: Arc::new(config),
+            ids: self.ids.unwrap_or_else(|| Arc::new(TimeOrderedIds)),
+            clock: self.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+            queue_stats: Arc::default(),
+            readiness: Arc::default(),
         })
     }
-}
\ No newline at end of file
+}
+
+/// The Postgres store over `db_pool`, reading status from the replica when
+/// DATABASE_REPLICA_URL is set. The replica pool connects on first use, so a
+/// replica that is down fails status reads rather than startup.
+fn pg_submit_store(db_pool: &DbPool, config: &Config) -> PgSubmitStore {
+    let store = PgSubmitStore::new(db_pool.clone());
+    match &config.read_your_writes.replica_url {
+        Some(url) => store.with_replica(postgres_models::create_lazy_pool_with_config(url, &config.common.db_pool)),
+        None => store,
+    }
+}
+
+/// Publisher to the transaction queue's event stream when QUEUE_EVENTS is
+/// set. The memory backend has no stream to publish to.
+fn event_publisher(redis_pool: &RedisPool, config: &Config) -> EventPublisher {
+    if !config.common.queue_events || config.backend == Backend::Memory {
+        return EventPublisher::disabled();
+    }
+    let queue = QueueManager::new(redis_pool.clone()).with_keys(key_space(config));
+    EventPublisher::new(queue, TRANSACTION_QUEUE.clone())
+}
+
+/// Redis key naming from config
+pub fn key_space(config: &Config) -> KeySpace {
+    let layout = if config.redis_cluster_keys {
+        KeyLayout::Cluster
+    } else {
+        KeyLayout::Standalone
+    };
+    let keys = KeySpace::new(layout);
+    match &config.redis_key_prefix {
+        Some(prefix) => keys.with_prefix(prefix).expect("REDIS_KEY_PREFIX is validated with the config"),
+        None => keys,
+    }
+}
+
+/// The in-memory account limit cache sized from config
+pub fn submit_limit_cache(config: &Config) -> BoundedCache<String, AccountLimits> {
+    BoundedCache::new(
+        "submit_limit",
+        config.local_limit_cache_capacity,
+        Duration::from_millis(config.local_limit_cache_ttl_ms),
+    )
+}
+
+fn hedged_queue_manager(redis_pool: &RedisPool, config: &Config) -> QueueManager {
+    let queue_manager = QueueManager::new(redis_pool.clone()).with_keys(key_space(config));
+    if config.redis_hedging {
+        return queue_manager.with_hedging(Duration::from_millis(config.redis_hedge_budget_ms));
+    }
+    queue_manager
+}
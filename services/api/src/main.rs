@@ -1,71 +1,100 @@
 use anyhow::Result;
-use axum::{Router, Json};
 use dotenvy::dotenv;
-use serde_json::json;
-use std::net::SocketAddr;
-use tower::ServiceBuilder;
-use tower_http::cors::CorsLayer;
-use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
-use tracing::{info, Level};
-
-pub mod config;
-mod errors;
-mod extractors;
-mod lib;
-mod v1;
-
-use crate::config::Config;
-use crate::lib::AppState;
+use service_config::Env;
+use tracing::{info, warn};
+
+use transaction_queue_api::config::{Backend, Config, LogFormat};
+use transaction_queue_api::server::Listeners;
+use transaction_queue_api::{health, metrics, panics, pending, queue_stats, stale_processing, warmup, AppState};
+
+/// Run on the in-memory backend, as with BACKEND=memory
+const DEV_INMEMORY_FLAG: &str = "--dev-inmemory";
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load environment variables
     dotenv().ok();
 
+    // Load configuration; --dev-inmemory selects the memory backend whatever BACKEND says
+    let dev_inmemory = std::env::args().skip(1).any(|arg| arg == DEV_INMEMORY_FLAG);
+    let lookup = |var: &str| match var {
+        "BACKEND" if dev_inmemory => Some("memory".to_string()),
+        _ => service_config::process_env(var),
+    };
+    let config = Config::from_lookup(&Env::new(&lookup))?;
+
     // Initialize tracing
-    tracing_subscriber::fmt()
+    let subscriber = tracing_subscriber::fmt()
         .with_target(false)
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "transaction_queue_api=debug,tower_http=debug".into()),
-        )
-        .init();
+        );
+    match config.common.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+
+    // Keep backtraces of handler panics for the log
+    panics::install_hook();
 
-    // Load configuration
-    let config = Config::from_env()?;
+    // Install the Prometheus recorder before anything records metrics
+    let metrics_handle = metrics::install_recorder()?;
 
     // Create application state
-    let state = AppState::new(&config.database_url, &config.redis_url).await?;
-
-    // Health check endpoint
-    async fn health() -> Json<serde_json::Value> {
-        Json(json!({
-            "status": "ok",
-            "service": "transaction-queue-api"
-        }))
+    let listen_addresses = config.listen_addresses.clone();
+    let listen_unix_socket = config.listen_unix_socket.clone();
+    let state = AppState::new(config).await?;
+
+    match state.config.backend {
+        Backend::Services => {
+            // Open connections before the listeners accept traffic
+            if state.config.warmup.enabled {
+                warmup::run(&state.db_pool, &state.redis_pool, &state.config.warmup).await;
+            }
+
+            // Background queue lag sampling
+            queue_stats::spawn_lag_sampler(state.clone());
+            stale_processing::spawn_stale_processing_check(state.clone());
+            pending::spawn_pending_reconciler(state.clone());
+        }
+        // Nothing to warm up, sample or reconcile without Postgres and Redis
+        Backend::Memory => warn!(
+            "Running on the in-memory backend: nothing is durable, data is lost on restart \
+             and no worker processes the queue. For local development only."
+        ),
     }
+    health::spawn_drain_signal_handler(state.readiness.clone())?;
 
     // Build the application
-    let app = Router::new()
-        .route("/health", axum::routing::get(health))
-        .nest("/v1", v1::router())
-        .layer(
-            ServiceBuilder::new()
-                .layer(
-                    TraceLayer::new_for_http()
-                        .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
-                        .on_response(DefaultOnResponse::new().level(Level::INFO)),
-                )
-                .layer(CorsLayer::permissive()),
-        )
-        .with_state(state);
-
-    // Start the server
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
-    info!("Starting server on {}", addr);
-
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    let app = transaction_queue_api::app(state, Some(metrics_handle));
+
+    // Start the server on every configured listener
+    let listeners = Listeners::bind(&listen_addresses, listen_unix_socket.as_deref()).await?;
+    listeners.serve(app, shutdown_signal()).await?;
+    info!("Server stopped");
 
     Ok(())
+}
+
+/// Resolves on Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutdown signal received");
 }
\ No newline at end of file
@@ -0,0 +1,356 @@
+//! In-memory backends for running the API without Postgres or Redis.
+//!
+//! Selected with BACKEND=memory or `--dev-inmemory`. Transactions, queue
+//! entries, pending counts, idempotency claims and rate limit windows live
+//! in this process only: nothing survives a restart, nothing is shared
+//! between instances and no worker drains the queue, so submitted
+//! transactions stay pending. Config refuses this mode when
+//! ENVIRONMENT=production.
+
+use crate::idempotency::IdempotencyKeys;
+use crate::submit_deadline::{QueueEntry, SubmitQueue};
+use crate::submit_store::SubmitStore;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use futures::future::BoxFuture;
+use postgres_models::models::{NewTransactionQueueRef, TransactionQueue, TransactionStatus};
+use postgres_models::DbError;
+use redis_cache::{envelope, CachedLimit, KeySpace, ProcessingHold, QueueName, RateLimitResult, RedisError};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Transactions by id, standing in for the transaction_queue table. There
+/// are no rate_limits rows, so every account gets its tier's or the default
+/// limits.
+#[derive(Default)]
+pub struct MemoryStore {
+    transactions: Mutex<HashMap<Uuid, TransactionQueue>>,
+    /// Ids of transactions inserted with an idempotency key, by account and key
+    idempotency_keys: Mutex<HashMap<(String, String), Uuid>>,
+}
+
+impl MemoryStore {
+    fn transactions(&self) -> MutexGuard<'_, HashMap<Uuid, TransactionQueue>> {
+        self.transactions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn idempotency_keys(&self) -> MutexGuard<'_, HashMap<(String, String), Uuid>> {
+        self.idempotency_keys.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl SubmitStore for MemoryStore {
+    fn account_limits<'a>(
+        &'a self,
+        _account_id: &'a str,
+        _limit_types: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<HashMap<String, CachedLimit>, DbError>> {
+        Box::pin(async move { Ok(HashMap::new()) })
+    }
+
+    fn insert<'a>(&'a self, transaction: &'a NewTransactionQueueRef<'a>) -> BoxFuture<'a, Result<Uuid, DbError>> {
+        Box::pin(async move {
+            let transaction_data = serde_json::from_str(transaction.transaction_data.0.get())
+                .map_err(|e| DbError::Query(DieselError::SerializationError(Box::new(e))))?;
+            let mut transactions = self.transactions();
+            let mut idempotency_keys = self.idempotency_keys();
+            // Fail a reused id or idempotency key the way the unique indexes would
+            let idempotency_key =
+                transaction.idempotency_key.map(|key| (transaction.account_id.to_string(), key.to_string()));
+            if transactions.contains_key(&transaction.id)
+                || idempotency_key.as_ref().is_some_and(|key| idempotency_keys.contains_key(key))
+            {
+                return Err(DbError::Query(DieselError::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    Box::new(format!("transaction {} already exists", transaction.id)),
+                )));
+            }
+            if let Some(key) = idempotency_key {
+                idempotency_keys.insert(key, transaction.id);
+            }
+            transactions.insert(
+                transaction.id,
+                TransactionQueue {
+                    id: transaction.id,
+                    account_id: transaction.account_id.to_string(),
+                    transaction_data,
+                    status: transaction.status.to_string(),
+                    priority: transaction.priority,
+                    retry_count: transaction.retry_count,
+                    max_retries: transaction.max_retries,
+                    created_at: transaction.created_at,
+                    updated_at: transaction.updated_at,
+                    scheduled_at: transaction.scheduled_at,
+                    processed_at: None,
+                    error_message: None,
+                    result: None,
+                },
+            );
+            Ok(transaction.id)
+        })
+    }
+
+    fn queued_count<'a>(&'a self, account_id: &'a str) -> BoxFuture<'a, Result<Option<i64>, DbError>> {
+        Box::pin(async move {
+            let transactions = self.transactions();
+            let pending = TransactionStatus::Pending.as_str();
+            let count = transactions.values().filter(|tx| tx.account_id == account_id && tx.status == pending).count();
+            Ok(Some(count as i64))
+        })
+    }
+
+    fn find(&self, id: Uuid) -> BoxFuture<'_, Result<Option<TransactionQueue>, DbError>> {
+        Box::pin(async move { Ok(self.transactions().get(&id).cloned()) })
+    }
+
+    fn find_by_idempotency_key<'a>(
+        &'a self,
+        account_id: &'a str,
+        idempotency_key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<TransactionQueue>, DbError>> {
+        Box::pin(async move {
+            let key = (account_id.to_string(), idempotency_key.to_string());
+            let Some(id) = self.idempotency_keys().get(&key).copied() else {
+                return Ok(None);
+            };
+            Ok(self.transactions().get(&id).cloned())
+        })
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Members of each queue in processing order: highest priority first,
+    /// then first come first served, as in the Redis priority queue
+    queues: HashMap<String, BTreeMap<(Reverse<i32>, u64), String>>,
+    next_sequence: u64,
+    /// Transactions each account has waiting or in flight
+    pending: HashMap<String, u32>,
+}
+
+impl QueueState {
+    /// 1-indexed position of the first member matching `is_member`
+    fn position(&self, queue_name: &QueueName, is_member: impl Fn(&str) -> bool) -> Option<i64> {
+        let queue = self.queues.get(queue_name.as_str())?;
+        let rank = queue.values().position(|member| is_member(member))?;
+        Some(rank as i64 + 1)
+    }
+}
+
+/// Priority queues and pending counts in memory. Nothing reports
+/// throughput or worker heartbeats, so status answers with positions but
+/// reports estimates as unavailable.
+#[derive(Default)]
+pub struct MemoryQueue {
+    state: Mutex<QueueState>,
+}
+
+impl MemoryQueue {
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl SubmitQueue for MemoryQueue {
+    fn add<'a>(&'a self, queue_name: &'a QueueName, entry: QueueEntry<'a>) -> BoxFuture<'a, Result<(), RedisError>> {
+        Box::pin(async move {
+            let mut state = self.state();
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            state
+                .queues
+                .entry(queue_name.to_string())
+                .or_default()
+                .insert((Reverse(entry.priority), sequence), entry.member.to_string());
+            Ok(())
+        })
+    }
+
+    fn position<'a>(&'a self, queue_name: &'a QueueName, member: &'a str) -> BoxFuture<'a, Result<i64, RedisError>> {
+        // Same fallback as the Redis queue for a member that is not there
+        Box::pin(async move { Ok(self.state().position(queue_name, |queued| queued == member).unwrap_or(1)) })
+    }
+
+    fn account_position<'a>(
+        &'a self,
+        queue_name: &'a QueueName,
+        member: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>> {
+        Box::pin(async move {
+            let Some(account_id) = envelope::account_id_of(member) else {
+                return Ok(None);
+            };
+            let state = self.state();
+            let Some(queue) = state.queues.get(queue_name.as_str()) else {
+                return Ok(None);
+            };
+            let rank = queue
+                .values()
+                .filter(|queued| envelope::account_id_of(queued).as_deref() == Some(account_id.as_str()))
+                .position(|queued| queued == member);
+            Ok(rank.map(|rank| rank as i64 + 1))
+        })
+    }
+
+    fn processing_rate<'a>(&'a self, _queue_name: &'a QueueName) -> BoxFuture<'a, Result<Option<f64>, RedisError>> {
+        Box::pin(async move { Ok(None) })
+    }
+
+    fn live_worker_count(&self, _max_age_seconds: u64) -> BoxFuture<'_, Result<i64, RedisError>> {
+        Box::pin(async move { Ok(0) })
+    }
+
+    fn transaction_position<'a>(
+        &'a self,
+        queue_name: &'a QueueName,
+        _account_id: &'a str,
+        transaction_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>> {
+        Box::pin(async move {
+            Ok(self.state().position(queue_name, |member| {
+                envelope::transaction_id_of(member).is_some_and(|id| id == transaction_id)
+            }))
+        })
+    }
+
+    fn reserve_pending<'a>(&'a self, account_id: &'a str, cap: u32) -> BoxFuture<'a, Result<bool, RedisError>> {
+        Box::pin(async move {
+            let mut state = self.state();
+            let count = state.pending.entry(account_id.to_string()).or_default();
+            if *count >= cap {
+                return Ok(false);
+            }
+            *count += 1;
+            Ok(true)
+        })
+    }
+
+    fn release_pending<'a>(&'a self, account_id: &'a str) -> BoxFuture<'a, Result<i64, RedisError>> {
+        Box::pin(async move {
+            let mut state = self.state();
+            let count = state.pending.entry(account_id.to_string()).or_default();
+            *count = count.saturating_sub(1);
+            Ok(*count as i64)
+        })
+    }
+
+    fn hold<'a>(&'a self, _transaction_id: &'a str) -> BoxFuture<'a, Result<Option<ProcessingHold>, RedisError>> {
+        Box::pin(async move { Ok(None) })
+    }
+}
+
+/// Idempotency claims in memory, expiring like the Redis keys
+#[derive(Default)]
+pub struct MemoryIdempotency {
+    claims: Mutex<HashMap<(String, String), (String, Instant)>>,
+}
+
+impl MemoryIdempotency {
+    fn claims(&self) -> MutexGuard<'_, HashMap<(String, String), (String, Instant)>> {
+        self.claims.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl IdempotencyKeys for MemoryIdempotency {
+    fn claim<'a>(
+        &'a self,
+        account_id: &'a str,
+        key: &'a str,
+        record: &'a str,
+        ttl_seconds: u64,
+    ) -> BoxFuture<'a, Result<Option<String>, RedisError>> {
+        Box::pin(async move {
+            let now = Instant::now();
+            let mut claims = self.claims();
+            let claim = (account_id.to_string(), key.to_string());
+            match claims.get(&claim) {
+                Some((existing, expires_at)) if *expires_at > now => Ok(Some(existing.clone())),
+                _ => {
+                    claims.insert(claim, (record.to_string(), now + Duration::from_secs(ttl_seconds)));
+                    Ok(None)
+                }
+            }
+        })
+    }
+
+    fn update<'a>(
+        &'a self,
+        account_id: &'a str,
+        key: &'a str,
+        record: &'a str,
+        ttl_seconds: u64,
+    ) -> BoxFuture<'a, Result<(), RedisError>> {
+        Box::pin(async move {
+            let expires_at = Instant::now() + Duration::from_secs(ttl_seconds);
+            self.claims()
+                .insert((account_id.to_string(), key.to_string()), (record.to_string(), expires_at));
+            Ok(())
+        })
+    }
+
+    fn release<'a>(&'a self, account_id: &'a str, key: &'a str) -> BoxFuture<'a, Result<(), RedisError>> {
+        Box::pin(async move {
+            self.claims().remove(&(account_id.to_string(), key.to_string()));
+            Ok(())
+        })
+    }
+}
+
+/// Sliding window limiter over request timestamps kept in memory, with the
+/// semantics of the Redis one: a request uses `cost` slots, rejected
+/// requests are not recorded, and the window resets when its oldest request
+/// ages out. Windows are named as in Redis, so scoped ones never meet an
+/// account's, and are kept for the life of the process.
+#[derive(Default)]
+pub struct MemoryLimiter {
+    windows: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl MemoryLimiter {
+    /// Check of an account's own window
+    pub fn check(&self, account_id: &str, cost: u32, max_requests: u32, window_seconds: u64) -> RateLimitResult {
+        self.check_window(KeySpace::default().sliding_window(account_id), cost, max_requests, window_seconds)
+    }
+
+    /// Check of `key`'s window in `scope`, as `check_scoped_rate_limit` does in Redis
+    pub fn check_scoped(
+        &self,
+        scope: &str,
+        key: &str,
+        cost: u32,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> RateLimitResult {
+        let window = KeySpace::default().scoped_rate_limit(scope, key);
+        self.check_window(window, cost, max_requests, window_seconds)
+    }
+
+    fn check_window(&self, window_key: String, cost: u32, max_requests: u32, window_seconds: u64) -> RateLimitResult {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        if max_requests == 0 {
+            return RateLimitResult::deny_all(since_epoch.as_secs() + window_seconds);
+        }
+        let now = Instant::now();
+        let window = Duration::from_secs(window_seconds);
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+        let requests = windows.entry(window_key).or_default();
+        while requests.front().is_some_and(|&at| now.duration_since(at) >= window) {
+            requests.pop_front();
+        }
+
+        let count = requests.len() as u64 + cost as u64;
+        let allowed = count <= max_requests as u64;
+        if allowed {
+            requests.extend(std::iter::repeat_n(now, cost as usize));
+        }
+        let oldest_age = requests.front().map_or(Duration::ZERO, |&at| now.duration_since(at));
+        let reset_at = (since_epoch + window).saturating_sub(oldest_age);
+        RateLimitResult {
+            allowed,
+            remaining: if allowed { (max_requests as u64).saturating_sub(count) as u32 } else { 0 },
+            reset_at: reset_at.as_secs() + u64::from(reset_at.subsec_nanos() > 0),
+        }
+    }
+}
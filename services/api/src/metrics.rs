@@ -0,0 +1,96 @@
+use crate::{config::Backend, AppState, TRANSACTION_QUEUE};
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::{Duration, Instant};
+
+pub const QUEUE_CONSUMER_LAG_SECONDS: &str = "queue_consumer_lag_seconds";
+pub const STALE_PROCESSING_TRANSACTIONS: &str = "stale_processing_transactions";
+pub const STALE_PROCESSING_HEALED_TOTAL: &str = "stale_processing_healed_total";
+pub const PENDING_COUNTERS_CORRECTED_TOTAL: &str = "pending_counters_corrected_total";
+pub const SUBMIT_DEADLINE_EXCEEDED_TOTAL: &str = "submit_deadline_exceeded_total";
+pub const SUBMIT_ABANDONED_TOTAL: &str = "submit_abandoned_total";
+pub const SUBMIT_REDIS_FAIL_OPEN_TOTAL: &str = "submit_redis_fail_open_total";
+pub const RATE_LIMIT_REDIS_FAIL_OPEN_TOTAL: &str = "rate_limit_redis_fail_open_total";
+pub const RATE_LIMIT_SHADOW_MISMATCH_TOTAL: &str = "rate_limit_shadow_mismatch_total";
+pub const RATE_LIMIT_SHADOW_FAILURES_TOTAL: &str = "rate_limit_shadow_failures_total";
+pub const LOCAL_CACHE_ENTRIES: &str = "local_cache_entries";
+pub const LOCAL_CACHE_EVICTIONS_TOTAL: &str = "local_cache_evictions_total";
+pub const LOCAL_CACHE_LOOKUPS_TOTAL: &str = "local_cache_lookups_total";
+pub const HTTP_PANICS_TOTAL: &str = "http_panics_total";
+pub const DB_ACQUIRE_ATTEMPTS: &str = "db_acquire_attempts";
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+pub const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+pub const SUBMISSIONS_TOTAL: &str = "submissions_total";
+pub const RATE_LIMIT_REJECTIONS_TOTAL: &str = "rate_limit_rejections_total";
+pub const QUEUE_DEPTH: &str = "queue_depth";
+pub const DB_POOL_CONNECTIONS: &str = "db_pool_connections";
+
+/// Tier label of rate limit rejections for accounts in no configured tier
+pub const NO_TIER: &str = "none";
+
+/// Handler latency buckets, in seconds, around the 100ms p99 target
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// How long a scrape waits for the queue depth before serving the last one
+const SCRAPE_SAMPLE_BUDGET: Duration = Duration::from_millis(100);
+
+/// Route label of requests that matched no route, so unknown paths cannot
+/// grow the label set
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Install the process-wide Prometheus recorder. Call once at startup.
+pub fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(DB_ACQUIRE_ATTEMPTS.to_string()), &[1.0, 2.0, 3.0])
+        .map_err(|e| anyhow::anyhow!("Invalid metric buckets: {}", e))?
+        .set_buckets_for_metric(Matcher::Full(HTTP_REQUEST_DURATION_SECONDS.to_string()), &LATENCY_BUCKETS)
+        .map_err(|e| anyhow::anyhow!("Invalid metric buckets: {}", e))?
+        .install_recorder()
+        .map_err(|e| anyhow::anyhow!("Failed to install metrics recorder: {}", e))
+}
+
+/// Middleware counting every response by method, route and status, and
+/// timing the handler. Routes are labelled by their pattern, e.g.
+/// `/v1/transactions/:id`, never by the raw path.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE.to_string(), |path| path.as_str().to_string());
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::histogram!(HTTP_REQUEST_DURATION_SECONDS, "method" => method.clone(), "route" => route.clone())
+        .record(started.elapsed().as_secs_f64());
+    metrics::counter!(HTTP_REQUESTS_TOTAL, "method" => method, "route" => route, "status" => status).increment(1);
+    response
+}
+
+/// Render the registry for a scrape, sampling the gauges that are only
+/// worth reading when someone looks: queue depth and database pool use.
+/// A queue depth Redis does not return in time keeps its last value.
+pub async fn render(state: &AppState, handle: &PrometheusHandle) -> String {
+    let pool = state.db_pool.state();
+    metrics::gauge!(DB_POOL_CONNECTIONS, "state" => "idle").set(pool.idle_connections as f64);
+    metrics::gauge!(DB_POOL_CONNECTIONS, "state" => "in_use")
+        .set(pool.connections.saturating_sub(pool.idle_connections) as f64);
+
+    if state.config.backend == Backend::Services {
+        let queue_manager = state.queue_manager();
+        let depth = tokio::time::timeout(SCRAPE_SAMPLE_BUDGET, queue_manager.priority_queue_length(&TRANSACTION_QUEUE));
+        match depth.await {
+            Ok(Ok(depth)) => {
+                metrics::gauge!(QUEUE_DEPTH, "queue" => TRANSACTION_QUEUE.to_string()).set(depth as f64)
+            }
+            Ok(Err(e)) => tracing::debug!("Failed to sample queue depth: {}", e),
+            Err(_) => tracing::debug!("Timed out sampling queue depth"),
+        }
+    }
+    handle.render()
+}
@@ -0,0 +1,74 @@
+//! Turn handler panics into 500 responses.
+//!
+//! A panicking handler would otherwise drop the connection without a
+//! response, which clients see as a protocol error. `catch_panic` answers
+//! with the usual error body and code `internal_panic` instead, logs the
+//! panic message and backtrace with the request id, and counts the panic.
+//!
+//! The backtrace no longer exists once the panic has unwound, so the hook
+//! set by `install_hook` captures it as the panic starts.
+
+use crate::{diagnostics::RequestId, errors::AppError, metrics::HTTP_PANICS_TOTAL};
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::FutureExt;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
+
+/// Error code of a 500 caused by a panic
+pub const INTERNAL_PANIC: &str = "internal_panic";
+
+thread_local! {
+    /// Backtrace of the latest panic on this thread, until it is logged
+    static LAST_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// Capture a backtrace on every panic before running the previous hook.
+/// Call once at startup.
+pub fn install_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        LAST_BACKTRACE.with(|slot| *slot.borrow_mut() = Some(Backtrace::force_capture()));
+        previous(info);
+    }));
+}
+
+/// Middleware answering a panic in the inner service with a 500; use with
+/// `axum::middleware::from_fn(catch_panic)` inside the tracing layer
+pub async fn catch_panic(request: Request, next: Next) -> Response {
+    let request_id = request.extensions().get::<RequestId>().map(|id| id.0.clone());
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    // The handler's state is dropped with the panic, so none of it is observed again
+    let payload = match AssertUnwindSafe(next.run(request)).catch_unwind().await {
+        Ok(response) => return response,
+        Err(payload) => payload,
+    };
+
+    let message = panic_message(&*payload);
+    let backtrace = LAST_BACKTRACE
+        .with(|slot| slot.borrow_mut().take())
+        .map_or_else(|| "unavailable".to_string(), |backtrace| backtrace.to_string());
+    metrics::counter!(HTTP_PANICS_TOTAL).increment(1);
+    tracing::error!(%method, path, request_id, panic = message, backtrace, "Handler panicked");
+
+    AppError::internal_server_error(format!("Handler panicked: {}", message))
+        .with_code(INTERNAL_PANIC)
+        .into_response()
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "non-string panic payload"
+    }
+}
@@ -0,0 +1,112 @@
+use postgres_models::transaction_data::{FieldError, TransactionData};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+/// Largest accepted `transaction_data`, measured on its JSON text
+pub const MAX_TRANSACTION_DATA_BYTES: usize = 1024 * 1024;
+
+/// Error code of the 400 sent for valid JSON that Postgres cannot store
+pub const UNSUPPORTED_CHARACTERS: &str = "unsupported_characters";
+
+/// Error code of the 400 sent for a typed `transaction_data` with a malformed field
+pub const INVALID_TRANSACTION_DATA: &str = "invalid_transaction_data";
+
+/// `transaction_data` kept as the JSON text it arrived as.
+///
+/// The text is copied out of the request body once and never parsed into a
+/// `serde_json::Value`. That one buffer is used for the size check, as the
+/// Redis queue member and as the jsonb bind parameter on insert.
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+pub struct TransactionPayload(Box<RawValue>);
+
+impl TransactionPayload {
+    /// Returns the validation message suitable for a 400 response
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let text = self.as_str();
+        if text == "null" {
+            return Err("transaction_data cannot be null");
+        }
+        if text.is_empty() {
+            return Err("transaction_data cannot be empty");
+        }
+        if text.len() > MAX_TRANSACTION_DATA_BYTES {
+            return Err("transaction_data too large: must be < 1MB");
+        }
+        Ok(())
+    }
+
+    /// Rejects text jsonb refuses: a `\u0000` escape or a surrogate escape
+    /// without its other half. Both are valid JSON, so only Postgres would
+    /// notice, and the insert would fail after rate limiting had counted it.
+    pub fn check_storable(&self) -> Result<(), &'static str> {
+        if has_unstorable_escape(self.as_str()) {
+            return Err("transaction_data contains \\u0000 or an unpaired surrogate, which cannot be stored");
+        }
+        Ok(())
+    }
+
+    /// The typed form of a payload naming an `account_type`, its fields
+    /// checked; `None` for payloads without one, which are only scanned for
+    /// the key, not parsed, and for unknown types when those are allowed.
+    pub fn typed(&self, allow_unknown: bool) -> Result<Option<TransactionData>, FieldError> {
+        #[derive(Deserialize)]
+        struct Tagged<'a> {
+            #[serde(borrow)]
+            account_type: Option<&'a RawValue>,
+        }
+        if !matches!(serde_json::from_str(self.as_str()), Ok(Tagged { account_type: Some(_) })) {
+            return Ok(None);
+        }
+        let payload: Value =
+            serde_json::from_str(self.as_str()).map_err(|e| FieldError::new("transaction_data", e.to_string()))?;
+        TransactionData::parse(&payload, allow_unknown)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.get()
+    }
+
+    pub fn as_raw(&self) -> &RawValue {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_str().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.as_str().is_empty()
+    }
+}
+
+/// Whether JSON text holds a `\u0000` escape or an unpaired surrogate escape.
+/// Backslashes only appear inside strings, so no string tracking is needed.
+pub fn has_unstorable_escape(json: &str) -> bool {
+    let bytes = json.as_bytes();
+    let mut at = 0;
+    while let Some(offset) = bytes.get(at..).and_then(|rest| rest.iter().position(|&b| b == b'\\')) {
+        at += offset;
+        match unicode_escape(bytes, at) {
+            Some(0) | Some(0xDC00..=0xDFFF) => return true,
+            Some(0xD800..=0xDBFF) => match unicode_escape(bytes, at + 6) {
+                Some(0xDC00..=0xDFFF) => at += 12,
+                _ => return true,
+            },
+            Some(_) => at += 6,
+            // Any other escape is two characters, e.g. \\ or \"
+            None => at += 2,
+        }
+    }
+    false
+}
+
+/// Code unit of the `\uXXXX` escape starting at `at`, if there is one
+fn unicode_escape(bytes: &[u8], at: usize) -> Option<u16> {
+    if bytes.get(at..at + 2)? != b"\\u" {
+        return None;
+    }
+    let hex = std::str::from_utf8(bytes.get(at + 2..at + 6)?).ok()?;
+    u16::from_str_radix(hex, 16).ok()
+}
@@ -0,0 +1,134 @@
+//! Per-account JSON Schemas for transaction_data.
+//!
+//! An account can have a JSON Schema registered through the admin API and,
+//! separately, enabled. While it is enabled, submit rejects payloads that do
+//! not match it with a 400 listing where they differ; accounts without an
+//! enabled schema are never checked. Compiled schemas, and the absence of
+//! one, are cached per account for PAYLOAD_SCHEMA_CACHE_TTL_MS. Changing a
+//! schema drops its entry on the instance that made the change; others pick
+//! it up once theirs expires.
+//!
+//! Schemas are compiled without fetching remote or file `$ref`s, so
+//! registering one never makes the API reach out to another host.
+
+use crate::bounded_cache::BoundedCache;
+use crate::errors::AppError;
+use crate::payload::TransactionPayload;
+use crate::submit_store::SubmitStore;
+use crate::AppState;
+use jsonschema::Validator;
+use postgres_models::DbError;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Error code of the 400 sent when a payload does not match the account's schema
+pub const PAYLOAD_SCHEMA_MISMATCH: &str = "payload_schema_mismatch";
+
+/// Most violations listed in one rejection
+pub const MAX_REPORTED_VIOLATIONS: usize = 20;
+
+/// Where a payload differs from the schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaViolation {
+    /// JSON Pointer to the offending value, "" for the payload itself
+    pub path: String,
+    /// JSON Pointer to the schema keyword it failed
+    pub schema_path: String,
+    pub message: String,
+}
+
+/// Compiled schemas of recently seen accounts
+pub struct PayloadSchemas {
+    cache: BoundedCache<String, Option<Arc<Validator>>>,
+    compiled: AtomicU64,
+}
+
+impl PayloadSchemas {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            cache: BoundedCache::new("payload_schema", capacity, ttl),
+            compiled: AtomicU64::new(0),
+        }
+    }
+
+    /// Schemas compiled by this instance since it started
+    pub fn compiled(&self) -> u64 {
+        self.compiled.load(Ordering::Relaxed)
+    }
+
+    /// Drop an account's cached schema after it changed
+    pub fn invalidate(&self, account_id: &str) {
+        self.cache.remove(&account_id.to_string());
+    }
+
+    /// The account's enabled schema, compiled, reading `store` on a miss.
+    /// A stored schema that no longer compiles is logged and not enforced.
+    pub async fn validator(
+        &self,
+        store: &dyn SubmitStore,
+        account_id: &str,
+    ) -> Result<Option<Arc<Validator>>, DbError> {
+        self.cache
+            .get_or_load(account_id.to_string(), || async {
+                let Some(schema) = store.payload_schema(account_id).await? else {
+                    return Ok(None);
+                };
+                self.compiled.fetch_add(1, Ordering::Relaxed);
+                match compile(&schema) {
+                    Ok(validator) => Ok(Some(Arc::new(validator))),
+                    Err(e) => {
+                        tracing::error!(account_id, "Stored payload schema does not compile: {}", e);
+                        Ok(None)
+                    }
+                }
+            })
+            .await
+    }
+}
+
+/// Compile a schema, refusing ones that are invalid or need a remote `$ref`
+pub fn compile(schema: &Value) -> Result<Validator, String> {
+    jsonschema::validator_for(schema).map_err(|e| e.to_string())
+}
+
+/// Where `payload` differs from the schema, at most `MAX_REPORTED_VIOLATIONS`
+pub fn violations(validator: &Validator, payload: &Value) -> Vec<SchemaViolation> {
+    validator
+        .iter_errors(payload)
+        .take(MAX_REPORTED_VIOLATIONS)
+        .map(|error| SchemaViolation {
+            path: error.instance_path.to_string(),
+            schema_path: error.schema_path.to_string(),
+            message: error.to_string(),
+        })
+        .collect()
+}
+
+/// Reject a payload that does not match the account's enabled schema. A
+/// failed schema lookup lets the payload through, like other account
+/// settings submit reads.
+pub async fn check(state: &AppState, account_id: &str, payload: &TransactionPayload) -> Result<(), AppError> {
+    let validator = match state.payload_schemas.validator(&*state.submit_store, account_id).await {
+        Ok(Some(validator)) => validator,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            tracing::warn!(account_id, "Failed to load payload schema, not checking it: {}", e);
+            return Ok(());
+        }
+    };
+
+    let payload: Value = serde_json::from_str(payload.as_str())
+        .map_err(|e| AppError::bad_request(format!("transaction_data is not valid JSON: {}", e)))?;
+    let violations = violations(&validator, &payload);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(
+        AppError::bad_request("transaction_data does not match the account's payload schema")
+            .with_code(PAYLOAD_SCHEMA_MISMATCH)
+            .with_details(serde_json::json!({ "violations": violations })),
+    )
+}
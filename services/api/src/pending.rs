@@ -0,0 +1,113 @@
+use crate::{
+    metrics,
+    rate_limit::{LimitSource, RateLimitPolicy},
+    AppState,
+};
+use diesel_async::AsyncPgConnection;
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use postgres_models::DbError;
+use redis_cache::{CachedLimit, QueueManager};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// `limit_type` of the rate_limits row holding an account's pending cap, with
+/// the cap stored in `max_requests`
+pub const MAX_PENDING_TYPE: &str = "max_pending";
+
+/// Error code of the 429 sent when an account is at its pending cap
+pub const PENDING_LIMIT_EXCEEDED: &str = "pending_limit_exceeded";
+
+/// Pending cap from an account's limit rows, falling back to the configured
+/// default
+pub fn pending_cap(limits: &HashMap<String, CachedLimit>, default_cap: u32) -> RateLimitPolicy {
+    let (max_requests, source) = match limits.get(MAX_PENDING_TYPE) {
+        Some(row) => (row.max_requests.max(1) as u32, LimitSource::AccountConfig),
+        None => (default_cap, LimitSource::GlobalDefault),
+    };
+    RateLimitPolicy {
+        limit_type: Cow::Borrowed(MAX_PENDING_TYPE),
+        source,
+        max_requests,
+        window_seconds: None,
+    }
+}
+
+/// Move a processing transaction to `to` and, once it is terminal, free its
+/// slot under the account's pending cap. Called by whatever finishes the
+/// item; returns false if another writer moved the row first.
+///
+/// A failed decrement only leaves the counter high until the next
+/// reconciliation, so it is logged rather than returned.
+pub async fn finish(
+    conn: &mut AsyncPgConnection,
+    queue_manager: &QueueManager,
+    transaction_id: Uuid,
+    account_id: &str,
+    to: TransactionStatus,
+) -> Result<bool, DbError> {
+    let moved = TransactionQueue::transition_status(conn, transaction_id, TransactionStatus::Processing, to).await?;
+
+    if moved && to.is_terminal() {
+        if let Err(e) = queue_manager.release_pending(account_id).await {
+            tracing::warn!(%transaction_id, account_id, "Failed to release pending slot: {}", e);
+        }
+    }
+    Ok(moved)
+}
+
+/// Set every Redis pending counter to the number of non-terminal rows in
+/// Postgres, including counters of accounts that no longer have any.
+/// Returns how many counters were wrong.
+///
+/// Submits that land between the count and the write can leave a counter
+/// off by the in-flight requests until the next pass.
+pub async fn reconcile(conn: &mut AsyncPgConnection, queue_manager: &QueueManager) -> anyhow::Result<usize> {
+    let actual = TransactionQueue::count_pending_by_account(conn).await?;
+
+    let mut accounts: BTreeSet<String> = queue_manager.pending_accounts().await?.into_iter().collect();
+    accounts.extend(actual.keys().cloned());
+
+    let mut corrected = 0;
+    for account_id in accounts {
+        let expected = actual.get(&account_id).copied().unwrap_or(0);
+        let counted = queue_manager.pending_count(&account_id).await?;
+        if counted != expected {
+            queue_manager.set_pending_count(&account_id, expected).await?;
+            tracing::debug!(account_id, counted, expected, "Corrected pending counter");
+            corrected += 1;
+        }
+    }
+    Ok(corrected)
+}
+
+/// Periodically correct the pending counters from Postgres
+pub fn spawn_pending_reconciler(state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let queue_manager = state.queue_manager();
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(state.config.pending_reconcile_interval_seconds));
+
+        loop {
+            interval.tick().await;
+
+            let mut conn = match state.db_pool.get().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::debug!("Failed to get a connection for pending reconciliation: {}", e);
+                    continue;
+                }
+            };
+            match reconcile(&mut conn, &queue_manager).await {
+                Ok(corrected) => {
+                    ::metrics::counter!(metrics::PENDING_COUNTERS_CORRECTED_TOTAL).increment(corrected as u64);
+                    if corrected > 0 {
+                        tracing::warn!(corrected, "Pending counters drifted from Postgres");
+                    }
+                }
+                Err(e) => tracing::debug!("Pending reconciliation failed: {}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,139 @@
+use crate::{estimation::THROUGHPUT_WINDOW_MINUTES, metrics, AppState, TRANSACTION_QUEUE};
+use redis_cache::{QueueCounter, QueueManager, QueueName, RedisError};
+use serde::Serialize;
+use std::sync::{PoisonError, RwLock};
+use std::time::Duration;
+
+/// Floor for the dequeue rate so an idle consumer yields a large but finite lag
+pub const MIN_DEQUEUE_RATE: f64 = 1e-3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueStats {
+    pub queue_depth: i64,
+    pub enqueue_rate_per_second: f64,
+    pub dequeue_rate_per_second: f64,
+    pub estimated_drain_seconds: f64,
+}
+
+impl QueueStats {
+    pub async fn collect(queue_manager: &QueueManager, queue_name: &QueueName) -> Result<Self, RedisError> {
+        let queue_depth = queue_manager.priority_queue_length(queue_name).await?;
+        let enqueue_rate = queue_manager
+            .counter_rate(queue_name, QueueCounter::Enqueued, THROUGHPUT_WINDOW_MINUTES)
+            .await?;
+        let dequeue_rate = queue_manager
+            .counter_rate(queue_name, QueueCounter::Dequeued, THROUGHPUT_WINDOW_MINUTES)
+            .await?;
+
+        Ok(Self {
+            queue_depth,
+            enqueue_rate_per_second: enqueue_rate.unwrap_or(0.0),
+            dequeue_rate_per_second: dequeue_rate.unwrap_or(0.0),
+            estimated_drain_seconds: consumer_lag_seconds(queue_depth, dequeue_rate),
+        })
+    }
+}
+
+/// Latest sample taken by the lag sampler, for callers that cannot wait on Redis
+#[derive(Debug, Default)]
+pub struct LatestQueueStats(RwLock<Option<QueueStats>>);
+
+impl LatestQueueStats {
+    pub fn get(&self) -> Option<QueueStats> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub fn set(&self, stats: QueueStats) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some(stats);
+    }
+}
+
+/// Seconds needed to drain the queue at the current dequeue rate
+pub fn consumer_lag_seconds(queue_depth: i64, dequeue_rate: Option<f64>) -> f64 {
+    if queue_depth <= 0 {
+        return 0.0;
+    }
+    queue_depth as f64 / dequeue_rate.unwrap_or(0.0).max(MIN_DEQUEUE_RATE)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagLevel {
+    Normal,
+    /// Above the threshold, but not for long enough to warn yet
+    Elevated,
+    Warning,
+}
+
+/// Hysteresis over lag samples so a single slow minute does not raise a warning
+#[derive(Debug)]
+pub struct LagMonitor {
+    threshold_seconds: f64,
+    required_samples: u32,
+    consecutive_samples: u32,
+}
+
+impl LagMonitor {
+    pub fn new(threshold_seconds: f64, required_samples: u32) -> Self {
+        Self {
+            threshold_seconds,
+            required_samples,
+            consecutive_samples: 0,
+        }
+    }
+
+    /// Record a lag sample. The level becomes `Warning` once more than
+    /// `required_samples` consecutive samples exceed the threshold, and
+    /// drops back to `Normal` on the first sample at or below it.
+    pub fn observe(&mut self, lag_seconds: f64) -> LagLevel {
+        if lag_seconds <= self.threshold_seconds {
+            self.consecutive_samples = 0;
+            return LagLevel::Normal;
+        }
+
+        self.consecutive_samples = self.consecutive_samples.saturating_add(1);
+        if self.consecutive_samples > self.required_samples {
+            LagLevel::Warning
+        } else {
+            LagLevel::Elevated
+        }
+    }
+}
+
+/// Periodically sample queue lag into the Prometheus gauge and warn on sustained lag
+pub fn spawn_lag_sampler(state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let queue_manager = state.queue_manager();
+        let mut monitor = LagMonitor::new(
+            state.config.lag_warn_threshold_seconds,
+            state.config.lag_warn_consecutive_samples,
+        );
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(state.config.lag_sample_interval_seconds));
+
+        loop {
+            interval.tick().await;
+
+            let stats = match QueueStats::collect(&queue_manager, &TRANSACTION_QUEUE).await {
+                Ok(stats) => stats,
+                Err(e) => {
+                    tracing::debug!("Failed to sample queue lag: {}", e);
+                    continue;
+                }
+            };
+
+            ::metrics::gauge!(metrics::QUEUE_CONSUMER_LAG_SECONDS, "queue" => TRANSACTION_QUEUE.to_string())
+                .set(stats.estimated_drain_seconds);
+            state.queue_stats.set(stats.clone());
+
+            if monitor.observe(stats.estimated_drain_seconds) == LagLevel::Warning {
+                tracing::warn!(
+                    queue = %TRANSACTION_QUEUE,
+                    lag_seconds = stats.estimated_drain_seconds,
+                    queue_depth = stats.queue_depth,
+                    dequeue_rate = stats.dequeue_rate_per_second,
+                    "Queue consumer lag above threshold"
+                );
+            }
+        }
+    })
+}
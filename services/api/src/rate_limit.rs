@@ -0,0 +1,417 @@
+use crate::{
+    config::{Backend, Config, RateLimitAlgorithm},
+    feature_flags::{LUA_RATE_LIMITER, SHADOW_RATE_LIMITER},
+    pending::{pending_cap, MAX_PENDING_TYPE},
+    AppState,
+};
+use axum::http::{HeaderMap, HeaderValue};
+use postgres_models::transaction_data::TransactionData;
+use postgres_models::DbError;
+use redis_cache::{
+    CachedLimit, RateLimitResult, RateLimitStrategy, RedisError, WindowAlignment, MAX_PRIORITY, MAX_WINDOW_SECONDS,
+    MIN_PRIORITY,
+};
+use shadow::ShadowCheck;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::time::Duration;
+
+pub use api_client::types::{LimitSource, RateLimitPolicy, RateLimitWarning};
+
+pub mod layer;
+pub mod rejections;
+pub mod shadow;
+
+pub const RATE_LIMIT_WARNING_HEADER: &str = "X-RateLimit-Warning";
+
+/// Sent with 429s: the limit that tripped, as "<max>;w=<window>;type=<limit_type>;source=<source>"
+pub const RATE_LIMIT_POLICY_HEADER: &str = "X-RateLimit-Policy";
+
+/// Window of the per-account submit limit
+pub const SUBMIT_WINDOW_SECONDS: u64 = 60;
+
+// The config checks its windows against its own copy of the limiter's bound
+const _: () = assert!(service_config::MAX_RATE_WINDOW_SECONDS == MAX_WINDOW_SECONDS);
+
+/// Submits allowed per window for accounts without a "submit" rate_limits row
+pub const DEFAULT_SUBMIT_LIMIT: u32 = 100;
+
+/// `limit_type` of the rate_limits row holding an account's submit limit
+pub const SUBMIT_LIMIT_TYPE: &str = "submit";
+
+/// `limit_type` of the rate_limits row holding an account's soft limit, with
+/// the percentage stored in `max_requests`
+pub const SOFT_LIMIT_PCT_TYPE: &str = "soft_pct";
+
+/// Build the X-RateLimit-* headers sent with every rate limited response.
+/// X-RateLimit-Reset is `reset_at` in Unix epoch seconds.
+pub fn rate_limit_headers(limit: u32, result: &RateLimitResult) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    insert_header(&mut headers, "X-RateLimit-Limit", limit);
+    // A limiter never reports more left than the limit, whatever its count
+    insert_header(&mut headers, "X-RateLimit-Remaining", result.remaining.min(limit));
+    insert_header(&mut headers, "X-RateLimit-Reset", result.reset_at);
+    headers
+}
+
+/// Insert `value` as the `name` header. Rate limit headers are advisory, so a
+/// value that is not a valid header is logged and left out rather than
+/// failing the response.
+pub fn insert_header(headers: &mut HeaderMap, name: &'static str, value: impl Display) {
+    let value = value.to_string();
+    match HeaderValue::from_str(&value) {
+        Ok(header) => {
+            headers.insert(name, header);
+        }
+        Err(_) => tracing::warn!(header = name, value = %value, "Omitting invalid header value"),
+    }
+}
+
+/// Warning for an allowed request once usage reaches `threshold_pct` of `limit`
+pub fn soft_limit_warning(limit: u32, result: &RateLimitResult, threshold_pct: u32) -> Option<RateLimitWarning> {
+    let used = limit.saturating_sub(result.remaining);
+    // Round the threshold up so 80% of 101 warns at 81, never before the percentage is reached
+    let threshold = (limit as u64 * threshold_pct as u64).div_ceil(100);
+    if !result.allowed || limit == 0 || (used as u64) < threshold {
+        return None;
+    }
+
+    Some(RateLimitWarning {
+        code: "soft_limit_reached".to_string(),
+        message: format!(
+            "{} of {} requests used in the current window; requests will be rejected at the limit",
+            used, limit
+        ),
+        used,
+        limit,
+        threshold_pct,
+        reset_at: result.reset_at,
+    })
+}
+
+pub fn insert_warning_header(headers: &mut HeaderMap, warning: &RateLimitWarning) {
+    let value = format!("soft limit reached: {}/{} requests used", warning.used, warning.limit);
+    insert_header(headers, RATE_LIMIT_WARNING_HEADER, value);
+}
+
+/// Submit requests an account may make per window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitLimit {
+    pub max_requests: u32,
+    pub window_seconds: u64,
+    pub source: LimitSource,
+    /// From the account's row; tier and global defaults are sliding windows
+    pub strategy: RateLimitStrategy,
+}
+
+impl SubmitLimit {
+    /// The limit of the account's "submit" row if it has one, otherwise of
+    /// its tier, otherwise the global default
+    pub fn resolve(row: Option<CachedLimit>, account_id: &str, config: &Config) -> Self {
+        if let Some(row) = row {
+            return Self {
+                max_requests: row.max_requests.max(0) as u32,
+                // Rows written before the table checked its bounds may be out of range
+                window_seconds: (row.window_seconds.max(1) as u64).min(MAX_WINDOW_SECONDS),
+                source: LimitSource::AccountConfig,
+                strategy: row.strategy,
+            };
+        }
+        match config.tier_limit(account_id) {
+            Some(tier) => Self {
+                max_requests: tier.max_requests,
+                window_seconds: SUBMIT_WINDOW_SECONDS,
+                source: LimitSource::TierDefault,
+                strategy: RateLimitStrategy::SlidingWindow,
+            },
+            None => Self::default(),
+        }
+    }
+
+    /// This limit as reported on a 429
+    pub fn policy(&self) -> RateLimitPolicy {
+        RateLimitPolicy {
+            limit_type: Cow::Borrowed(SUBMIT_LIMIT_TYPE),
+            source: self.source,
+            max_requests: self.max_requests,
+            window_seconds: Some(self.window_seconds),
+        }
+    }
+}
+
+impl Default for SubmitLimit {
+    fn default() -> Self {
+        Self {
+            max_requests: DEFAULT_SUBMIT_LIMIT,
+            window_seconds: SUBMIT_WINDOW_SECONDS,
+            source: LimitSource::GlobalDefault,
+            strategy: RateLimitStrategy::SlidingWindow,
+        }
+    }
+}
+
+/// Limit rows read and cached together for each account
+const CACHED_LIMIT_TYPES: [&str; 3] = [SUBMIT_LIMIT_TYPE, SOFT_LIMIT_PCT_TYPE, MAX_PENDING_TYPE];
+
+/// The limits submit holds an account to, resolved from its rate_limits rows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountLimits {
+    pub submit: SubmitLimit,
+    /// Share of the submit limit, in percent, from which submits are warned
+    pub soft_limit_pct: u32,
+    /// Most transactions the account may have pending at once
+    pub max_pending: RateLimitPolicy,
+}
+
+impl AccountLimits {
+    pub fn resolve(rows: &HashMap<String, CachedLimit>, account_id: &str, config: &Config) -> Self {
+        Self {
+            submit: SubmitLimit::resolve(rows.get(SUBMIT_LIMIT_TYPE).copied(), account_id, config),
+            soft_limit_pct: soft_limit_pct(rows, config.rate_limit_soft_pct),
+            max_pending: pending_cap(rows, config.max_pending_per_account),
+        }
+    }
+}
+
+/// An account's submit limit; see `account_limits`
+pub async fn submit_limit(state: &AppState, account_id: &str) -> SubmitLimit {
+    account_limits(state, account_id).await.submit
+}
+
+/// An account's limits, read through the in-memory and Redis limit caches.
+///
+/// Submit checks the limit before taking a connection of its own, so a cache
+/// miss borrows one only for the lookup. If neither Redis nor Postgres
+/// answers, the account gets its tier's or the default limits rather than an
+/// error. Those stand-ins are not cached, so the next call reads the rows
+/// again instead of holding the account to the wrong limit.
+pub async fn account_limits(state: &AppState, account_id: &str) -> AccountLimits {
+    let key = account_id.to_string();
+    if let Some(limits) = state.limit_cache.get(&key) {
+        return limits;
+    }
+    match read_account_limits(state, account_id).await {
+        Ok(limits) => {
+            state.limit_cache.insert(key, limits.clone());
+            limits
+        }
+        Err(e) => {
+            tracing::warn!(account_id, "Failed to load account limits, using the defaults: {}", e);
+            AccountLimits::resolve(&HashMap::new(), account_id, &state.config)
+        }
+    }
+}
+
+async fn read_account_limits(state: &AppState, account_id: &str) -> Result<AccountLimits, DbError> {
+    // Without Redis the in-memory cache is the only one
+    if state.config.backend == Backend::Memory {
+        let rows = load_account_limits(state, account_id).await?;
+        return Ok(AccountLimits::resolve(&rows, account_id, &state.config));
+    }
+
+    let queue_manager = state.queue_manager();
+    match queue_manager.cached_limits(account_id, &CACHED_LIMIT_TYPES).await {
+        Ok(Some(rows)) => return Ok(AccountLimits::resolve(&rows, account_id, &state.config)),
+        Ok(None) => {}
+        Err(e) => tracing::debug!(account_id, "Failed to read cached account limits: {}", e),
+    }
+
+    let rows = load_account_limits(state, account_id).await?;
+    if let Err(e) = queue_manager
+        .cache_limits(account_id, &CACHED_LIMIT_TYPES, &rows, state.config.limit_cache_ttl_seconds)
+        .await
+    {
+        tracing::debug!(account_id, "Failed to cache account limits: {}", e);
+    }
+    Ok(AccountLimits::resolve(&rows, account_id, &state.config))
+}
+
+async fn load_account_limits(state: &AppState, account_id: &str) -> Result<HashMap<String, CachedLimit>, DbError> {
+    state.submit_store.account_limits(account_id, &CACHED_LIMIT_TYPES).await
+}
+
+/// Drop an account's cached limits after its rate_limits row changed. A
+/// failed delete leaves the old limit in place until the cache entry expires.
+pub async fn invalidate_cached_limit(state: &AppState, account_id: &str, limit_type: &str) {
+    if CACHED_LIMIT_TYPES.contains(&limit_type) {
+        state.limit_cache.remove(&account_id.to_string());
+    }
+    if limit_type == SUBMIT_LIMIT_TYPE {
+        state.rejections.clear(account_id);
+    }
+    if let Err(e) = state.queue_manager().invalidate_limit(account_id, limit_type).await {
+        tracing::warn!(account_id, limit_type, "Failed to invalidate cached limit: {}", e);
+    }
+}
+
+/// Soft limit percentage from an account's limit rows, falling back to the
+/// configured default
+pub fn soft_limit_pct(limits: &HashMap<String, CachedLimit>, default_pct: u32) -> u32 {
+    limits
+        .get(SOFT_LIMIT_PCT_TYPE)
+        .map(|row| row.max_requests.clamp(1, 100) as u32)
+        .unwrap_or(default_pct)
+}
+
+/// Window alignment for an account; accounts listed in ALIGNED_WINDOW_ACCOUNTS opt out of jitter
+pub fn window_alignment(state: &AppState, account_id: &str) -> WindowAlignment {
+    if state.config.aligned_window_accounts.iter().any(|id| id == account_id) {
+        WindowAlignment::Aligned
+    } else {
+        WindowAlignment::Jittered
+    }
+}
+
+/// How a sliding window check talks to Redis; both share keys and semantics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlidingWindowImplementation {
+    /// One command per step
+    Commands,
+    /// A single Lua script, rolled out behind the `lua_rate_limiter` flag
+    Script,
+}
+
+pub async fn sliding_window_implementation(state: &AppState, account_id: &str) -> SlidingWindowImplementation {
+    if state.flags.enabled(LUA_RATE_LIMITER, account_id).await {
+        SlidingWindowImplementation::Script
+    } else {
+        SlidingWindowImplementation::Commands
+    }
+}
+
+/// Share of the submit limit a submit at `priority` uses, by the configured
+/// priority cost curve. Priorities outside the accepted range are clamped.
+/// With `SPACE_BYTES_PER_COST_UNIT` set, a typed payload costs that many
+/// times more again for each step of account space it asks for.
+pub fn submit_cost(state: &AppState, priority: Option<i32>, data: Option<&TransactionData>) -> u32 {
+    let priority = priority.unwrap_or(0).clamp(MIN_PRIORITY, MAX_PRIORITY);
+    let cost = state.config.priority_cost_curve.cost(priority);
+    let units = match (state.config.space_bytes_per_cost_unit, data) {
+        (Some(per_unit), Some(data)) => 1 + data.space_bytes() / u64::from(per_unit),
+        _ => 1,
+    };
+    cost.saturating_mul(u32::try_from(units).unwrap_or(u32::MAX))
+}
+
+/// Check an account's submit limit with the configured algorithm, counting
+/// the request as `cost` requests. A token bucket `strategy`, set on the
+/// account's row, takes precedence over the algorithm. An account rejected
+/// moments ago is rejected again from memory without a Redis round trip. In
+/// memory mode the local sliding window is checked whatever the configured
+/// algorithm or strategy.
+pub async fn check_account_limit(
+    state: &AppState,
+    account_id: &str,
+    cost: u32,
+    max_requests: u32,
+    window_seconds: u64,
+    strategy: RateLimitStrategy,
+) -> Result<RateLimitResult, RedisError> {
+    // Anything over the limit is rejected all the same, so a huge cost need
+    // not record one window entry per unit
+    let cost = cost.min(max_requests.saturating_add(1));
+    if let Some(limiter) = &state.local_limiter {
+        return Ok(limiter.check(account_id, cost, max_requests, window_seconds));
+    }
+    state
+        .rejections
+        .check(account_id, || {
+            check_limit_in_redis(state, account_id, cost, max_requests, window_seconds, strategy)
+        })
+        .await
+}
+
+async fn check_limit_in_redis(
+    state: &AppState,
+    account_id: &str,
+    cost: u32,
+    max_requests: u32,
+    window_seconds: u64,
+    strategy: RateLimitStrategy,
+) -> Result<RateLimitResult, RedisError> {
+    if strategy == RateLimitStrategy::TokenBucket {
+        return state
+            .rate_limiter_with_strategy(strategy)
+            .check_weighted_rate_limit(account_id, cost, max_requests, window_seconds)
+            .await;
+    }
+    let rate_limiter = state.rate_limiter();
+    match state.config.rate_limit_algorithm {
+        RateLimitAlgorithm::SlidingWindow => {
+            let result = match sliding_window_implementation(state, account_id).await {
+                SlidingWindowImplementation::Commands => {
+                    rate_limiter
+                        .check_weighted_rate_limit(account_id, cost, max_requests, window_seconds)
+                        .await?
+                }
+                SlidingWindowImplementation::Script => {
+                    rate_limiter
+                        .check_weighted_rate_limit_script(account_id, cost, max_requests, window_seconds)
+                        .await?
+                }
+            };
+            if state.flags.enabled(SHADOW_RATE_LIMITER, account_id).await {
+                let check = ShadowCheck {
+                    account_id: account_id.to_string(),
+                    cost,
+                    max_requests,
+                    window_seconds,
+                    live: result.clone(),
+                };
+                state.shadow.spawn(
+                    check,
+                    Duration::from_millis(state.config.shadow_rate_limit_timeout_ms),
+                    state.config.shadow_rate_limit_tolerance,
+                );
+            }
+            Ok(result)
+        }
+        RateLimitAlgorithm::FixedWindow => {
+            let alignment = window_alignment(state, account_id);
+            rate_limiter
+                .check_weighted_fixed_window(account_id, cost, max_requests, window_seconds, alignment)
+                .await
+        }
+    }
+}
+
+/// Requests an account has used in its current submit window, read without
+/// counting a request
+pub async fn account_usage(state: &AppState, account_id: &str, window_seconds: u64) -> Result<u64, RedisError> {
+    let rate_limiter = state.rate_limiter();
+    match state.config.rate_limit_algorithm {
+        RateLimitAlgorithm::SlidingWindow => rate_limiter.sliding_window_usage(account_id, window_seconds).await,
+        RateLimitAlgorithm::FixedWindow => {
+            let alignment = window_alignment(state, account_id);
+            rate_limiter.fixed_window_usage(account_id, window_seconds, alignment).await
+        }
+    }
+}
+
+/// What the account's next submit would be told about its submit limit,
+/// read without counting a request
+pub async fn peek_submit_limit(
+    state: &AppState,
+    account_id: &str,
+    limit: &SubmitLimit,
+) -> Result<RateLimitResult, RedisError> {
+    if limit.strategy == RateLimitStrategy::TokenBucket {
+        return state
+            .rate_limiter_with_strategy(limit.strategy)
+            .peek(account_id, limit.max_requests, limit.window_seconds)
+            .await;
+    }
+    let rate_limiter = state.rate_limiter();
+    match state.config.rate_limit_algorithm {
+        RateLimitAlgorithm::SlidingWindow => {
+            rate_limiter.peek(account_id, limit.max_requests, limit.window_seconds).await
+        }
+        RateLimitAlgorithm::FixedWindow => {
+            let alignment = window_alignment(state, account_id);
+            rate_limiter
+                .peek_fixed_window(account_id, limit.max_requests, limit.window_seconds, alignment)
+                .await
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Rate limiting as a tower layer, so routes get limits without each
+//! handler repeating the check and header plumbing.
+//!
+//! The layer derives a key from the request, checks it against a scoped
+//! sliding window and either answers 429 itself or passes the request on
+//! with an `AppliedRateLimit` extension. X-RateLimit-* headers are added to
+//! the handler's response unless it already set them. In memory mode the
+//! windows are kept by a `MemoryLimiter` instead of Redis.
+//!
+//! When Redis cannot be reached the layer follows REDIS_FAILURE_MODE like
+//! submit: failing closed it answers 503 `redis_unavailable`, failing open
+//! it passes the request on unlimited and without rate limit headers,
+//! counting it in `rate_limit_redis_fail_open_total`.
+
+use super::{rate_limit_headers, LimitSource, RateLimitPolicy};
+use crate::{
+    config::RedisFailureMode, errors::AppError, extractors::admin::client_ip, memory_backend::MemoryLimiter,
+    metrics::RATE_LIMIT_REDIS_FAIL_OPEN_TOTAL, redis_failure::refusal, AppState,
+};
+use axum::{
+    extract::{MatchedPath, OriginalUri, Request},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+use redis_cache::{KeySpace, RateLimitResult, RateLimiter, RedisPool};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Limiter scope of the read endpoints
+pub const READ_RATE_LIMIT_SCOPE: &str = "read";
+/// Limiter scope of the per-IP limit over every API route
+pub const IP_RATE_LIMIT_SCOPE: &str = "ip";
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+type KeyFn = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// Outcome of the layer's check, available to handlers as a request extension
+#[derive(Debug, Clone)]
+pub struct AppliedRateLimit {
+    pub limit: u32,
+    pub result: RateLimitResult,
+}
+
+impl AppliedRateLimit {
+    pub fn headers(&self) -> HeaderMap {
+        rate_limit_headers(self.limit, &self.result)
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    pool: RedisPool,
+    keys: KeySpace,
+    scope: &'static str,
+    max_requests: u32,
+    window_seconds: u64,
+    key: KeyFn,
+    local: Option<Arc<MemoryLimiter>>,
+    failure_mode: RedisFailureMode,
+}
+
+impl RateLimitLayer {
+    /// Limit each client IP to `max_requests` per `window_seconds` in `scope`
+    pub fn new(pool: RedisPool, scope: &'static str, max_requests: u32, window_seconds: u64) -> Self {
+        Self {
+            pool,
+            keys: KeySpace::default(),
+            scope,
+            max_requests,
+            window_seconds,
+            key: Arc::new(by_client_ip()),
+            local: None,
+            failure_mode: RedisFailureMode::default(),
+        }
+    }
+
+    /// Derive the limiter key with `key` instead of the client IP. Requests
+    /// it returns no key for are passed through unlimited.
+    pub fn key_by<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = Arc::new(key);
+        self
+    }
+
+    /// Name window keys in `keys`
+    pub fn with_keys(mut self, keys: KeySpace) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// The limit this layer applies, as reported to clients
+    pub fn policy(&self) -> RateLimitPolicy {
+        RateLimitPolicy {
+            limit_type: Cow::Borrowed(self.scope),
+            source: LimitSource::GlobalDefault,
+            max_requests: self.max_requests,
+            window_seconds: Some(self.window_seconds),
+        }
+    }
+
+    /// Keep the windows in `limiter` instead of Redis
+    pub fn with_local_limiter(mut self, limiter: Arc<MemoryLimiter>) -> Self {
+        self.local = Some(limiter);
+        self
+    }
+
+    /// Fail open or closed per `mode` while Redis cannot be reached
+    pub fn with_failure_mode(mut self, mode: RedisFailureMode) -> Self {
+        self.failure_mode = mode;
+        self
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            config: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    config: RateLimitLayer,
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        // Use the service that was polled ready and leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+
+        Box::pin(async move {
+            let Some(key) = (config.key)(&request) else {
+                return inner.call(request).await;
+            };
+
+            let applied = match check(&config, &key).await {
+                Ok(Some(applied)) => applied,
+                Ok(None) => return inner.call(request).await,
+                Err(err) => return Ok(err.into_response()),
+            };
+            request.extensions_mut().insert(applied.clone());
+
+            let mut response = inner.call(request).await?;
+            for (name, value) in applied.headers() {
+                if let Some(name) = name {
+                    response.headers_mut().entry(name).or_insert(value);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// The limit applied to `key`, or `None` when Redis cannot be reached and
+/// the layer fails open
+async fn check(config: &RateLimitLayer, key: &str) -> Result<Option<AppliedRateLimit>, AppError> {
+    let result = match &config.local {
+        Some(limiter) => limiter.check_scoped(config.scope, key, 1, config.max_requests, config.window_seconds),
+        None => match RateLimiter::new(config.pool.clone())
+            .with_keys(config.keys.clone())
+            .check_scoped_rate_limit(config.scope, key, config.max_requests, config.window_seconds)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) if config.failure_mode == RedisFailureMode::FailOpen && e.is_unavailable() => {
+                tracing::warn!(scope = config.scope, "Redis unavailable, skipping the rate limit: {}", e);
+                metrics::counter!(RATE_LIMIT_REDIS_FAIL_OPEN_TOTAL, "scope" => config.scope).increment(1);
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(refusal(e, |e| {
+                    AppError::internal_server_error(format!("Rate limit check failed: {}", e))
+                }))
+            }
+        },
+    };
+
+    let applied = AppliedRateLimit {
+        limit: config.max_requests,
+        result,
+    };
+    if !applied.result.allowed {
+        tracing::debug!(scope = config.scope, key, "Rate limit exceeded");
+        return Err(AppError::too_many_requests("Rate limit exceeded")
+            .with_headers(applied.headers())
+            .with_retry(applied.limit, &applied.result));
+    }
+    Ok(Some(applied))
+}
+
+/// Limit for the read endpoints, per client IP, from config
+pub fn read_limit(state: &AppState) -> RateLimitLayer {
+    let layer = RateLimitLayer::new(
+        state.redis_pool.clone(),
+        READ_RATE_LIMIT_SCOPE,
+        state.config.read_rate_limit,
+        state.config.read_rate_window_seconds,
+    )
+    .with_keys(crate::key_space(&state.config))
+    .with_failure_mode(state.config.redis_failure_mode);
+    match &state.local_limiter {
+        Some(limiter) => layer.with_local_limiter(limiter.clone()),
+        None => layer,
+    }
+}
+
+/// Limit per client IP over every API route, from config, or None when
+/// IP_RATE_LIMIT is unset. Account ids are whatever the client sends, so
+/// this is what holds back a client rotating through them.
+pub fn ip_limit(state: &AppState) -> Option<RateLimitLayer> {
+    let max_requests = state.config.ip_rate_limit?;
+    let layer = RateLimitLayer::new(
+        state.redis_pool.clone(),
+        IP_RATE_LIMIT_SCOPE,
+        max_requests,
+        state.config.ip_rate_window_seconds,
+    )
+    .key_by(by_forwarded_ip(state.config.trust_forwarded_for))
+    .with_keys(crate::key_space(&state.config))
+    .with_failure_mode(state.config.redis_failure_mode);
+    Some(match &state.local_limiter {
+        Some(limiter) => layer.with_local_limiter(limiter.clone()),
+        None => layer,
+    })
+}
+
+/// Key by the peer IP; connections without connect info (Unix sockets) are not limited
+pub fn by_client_ip() -> impl Fn(&Request) -> Option<String> + Send + Sync + 'static {
+    |request| client_ip(request.extensions())
+}
+
+/// Key by the client IP, taken from X-Forwarded-For when `trust_forwarded_for`
+/// is set and from the peer address otherwise or when the header is unusable
+pub fn by_forwarded_ip(trust_forwarded_for: bool) -> impl Fn(&Request) -> Option<String> + Send + Sync + 'static {
+    move |request| {
+        trust_forwarded_for
+            .then(|| forwarded_for(request.headers()))
+            .flatten()
+            .or_else(|| client_ip(request.extensions()))
+    }
+}
+
+/// Address the proxy in front of the API appended to X-Forwarded-For, the
+/// last entry of the last header. Entries before it were sent by the client
+/// and could say anything.
+pub fn forwarded_for(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get_all(X_FORWARDED_FOR).iter().next_back()?.to_str().ok()?;
+    let ip: IpAddr = value.rsplit(',').next()?.trim().parse().ok()?;
+    Some(ip.to_string())
+}
+
+/// Key by a named parameter of the matched route, e.g. "account_id" in
+/// "/:account_id/webhooks"
+pub fn by_path_param(name: &'static str) -> impl Fn(&Request) -> Option<String> + Send + Sync + 'static {
+    move |request| path_param(request, name).map(|value| format!("{}:{}", name, value))
+}
+
+/// Value of a named parameter of the matched route, read before the
+/// handler's extractors run
+pub fn path_param(request: &Request, name: &str) -> Option<String> {
+    let pattern = request.extensions().get::<MatchedPath>()?.as_str();
+    // The matched path spans every nesting level, so compare it with the
+    // URI as it was before nested routers stripped their prefixes
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path(), |OriginalUri(uri)| uri.path());
+    let placeholder = format!(":{}", name);
+    pattern
+        .split('/')
+        .zip(path.split('/'))
+        .find(|(segment, _)| *segment == placeholder)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Key by a request extension set by an earlier layer, e.g. an authenticated account
+pub fn by_extension<T, F>(key: F) -> impl Fn(&Request) -> Option<String> + Send + Sync + 'static
+where
+    T: Send + Sync + 'static,
+    F: Fn(&T) -> String + Send + Sync + 'static,
+{
+    move |request| request.extensions().get::<T>().map(&key)
+}
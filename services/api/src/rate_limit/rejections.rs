@@ -0,0 +1,77 @@
+//! Local short-circuit for accounts that are over their submit limit.
+//!
+//! An account in a 429 storm would otherwise cost a Redis round trip for
+//! every rejected submit. Once a check rejects an account, the rejection is
+//! kept in memory and returned for the account's next checks without asking
+//! Redis, headers and all. A marker lives at most `MAX_MARKER_TTL` and never
+//! past the window reset the rejection reported, so the account is checked
+//! against Redis again as soon as its window could have room.
+//!
+//! Only submit checks consult the markers; reads of an account's usage, such
+//! as the estimate endpoint, always go to Redis.
+
+use crate::bounded_cache::BoundedCache;
+use redis_cache::RateLimitResult;
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longest a rejection is answered locally before Redis is asked again
+pub const MAX_MARKER_TTL: Duration = Duration::from_secs(2);
+
+pub struct RejectionMarkers {
+    cache: BoundedCache<String, RateLimitResult>,
+}
+
+impl RejectionMarkers {
+    /// Markers for at most `capacity` accounts
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            cache: BoundedCache::new("rejection_marker", capacity, MAX_MARKER_TTL),
+        }
+    }
+
+    /// The rejection still in force for `account_id`, if any
+    pub fn get(&self, account_id: &str) -> Option<RateLimitResult> {
+        self.cache.get(&account_id.to_string())
+    }
+
+    /// Remember `result` if it rejected the account and its window has not
+    /// reset yet
+    pub fn record(&self, account_id: &str, result: &RateLimitResult) {
+        if result.allowed {
+            return;
+        }
+        let ttl = marker_ttl(result.reset_at, SystemTime::now());
+        if !ttl.is_zero() {
+            self.cache.insert_with_ttl(account_id.to_string(), result.clone(), ttl);
+        }
+    }
+
+    /// Forget the account's rejection, e.g. after its limit changed
+    pub fn clear(&self, account_id: &str) {
+        self.cache.remove(&account_id.to_string());
+    }
+
+    /// The marked rejection of `account_id`, or the result of `check`,
+    /// which is marked if it rejects
+    pub async fn check<E, F, Fut>(&self, account_id: &str, check: F) -> Result<RateLimitResult, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<RateLimitResult, E>>,
+    {
+        if let Some(rejected) = self.get(account_id) {
+            return Ok(rejected);
+        }
+        let result = check().await?;
+        self.record(account_id, &result);
+        Ok(result)
+    }
+}
+
+/// Time left until `reset_at` (Unix seconds), capped at `MAX_MARKER_TTL`
+fn marker_ttl(reset_at: u64, now: SystemTime) -> Duration {
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Duration::from_secs(reset_at)
+        .saturating_sub(now)
+        .min(MAX_MARKER_TTL)
+}
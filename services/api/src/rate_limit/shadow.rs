@@ -0,0 +1,174 @@
+//! Shadow comparison of a candidate limiter against the live one.
+//!
+//! For accounts in the `shadow_rate_limiter` rollout, every submit check is
+//! repeated on the candidate limiter once the live result is known. The
+//! candidate keeps its own window under the `shadow` scope, so it sees the
+//! same requests without counting them twice in the live window. Its answer
+//! is only compared and recorded: the live result is always what the client
+//! gets, and the shadow check runs in the background under a short timeout
+//! so it never adds latency.
+
+use crate::metrics::{RATE_LIMIT_SHADOW_FAILURES_TOTAL, RATE_LIMIT_SHADOW_MISMATCH_TOTAL};
+use futures::future::BoxFuture;
+use redis_cache::{RateLimitResult, RateLimiter, RedisError};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// Limiter scope of the candidate's windows
+pub const SHADOW_RATE_LIMIT_SCOPE: &str = "shadow";
+
+/// Shadow checks allowed in flight at once; past this they are skipped
+pub const MAX_SHADOW_CHECKS_IN_FLIGHT: usize = 1024;
+
+/// A limiter under evaluation
+pub trait ShadowLimiter: Send + Sync {
+    fn check<'a>(
+        &'a self,
+        account_id: &'a str,
+        cost: u32,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> BoxFuture<'a, Result<RateLimitResult, RedisError>>;
+}
+
+/// The single-script sliding window, on its own keys
+pub struct ScriptShadowLimiter(RateLimiter);
+
+impl ScriptShadowLimiter {
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self(limiter)
+    }
+}
+
+impl ShadowLimiter for ScriptShadowLimiter {
+    fn check<'a>(
+        &'a self,
+        account_id: &'a str,
+        cost: u32,
+        max_requests: u32,
+        window_seconds: u64,
+    ) -> BoxFuture<'a, Result<RateLimitResult, RedisError>> {
+        Box::pin(async move {
+            let key = format!("{}:{}", SHADOW_RATE_LIMIT_SCOPE, account_id);
+            self.0
+                .check_weighted_rate_limit_script(&key, cost, max_requests, window_seconds)
+                .await
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowOutcome {
+    Match,
+    /// One limiter allowed the request and the other rejected it
+    AllowedMismatch,
+    /// Both agreed on the decision but `remaining` differed by more than the tolerance
+    RemainingMismatch,
+    Timeout,
+    Error,
+}
+
+impl ShadowOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Match => "match",
+            Self::AllowedMismatch => "allowed",
+            Self::RemainingMismatch => "remaining",
+            Self::Timeout => "timeout",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Compare the candidate's answer with the live one
+pub fn compare(live: &RateLimitResult, shadow: &RateLimitResult, remaining_tolerance: u32) -> ShadowOutcome {
+    if live.allowed != shadow.allowed {
+        ShadowOutcome::AllowedMismatch
+    } else if live.remaining.abs_diff(shadow.remaining) > remaining_tolerance {
+        ShadowOutcome::RemainingMismatch
+    } else {
+        ShadowOutcome::Match
+    }
+}
+
+/// Inputs and live answer of one shadowed check
+#[derive(Debug, Clone)]
+pub struct ShadowCheck {
+    pub account_id: String,
+    /// Requests the checked submit counts as
+    pub cost: u32,
+    pub max_requests: u32,
+    pub window_seconds: u64,
+    pub live: RateLimitResult,
+}
+
+/// The candidate limiter and the bounds its checks run under
+pub struct ShadowCompare {
+    limiter: Arc<dyn ShadowLimiter>,
+    in_flight: Arc<Semaphore>,
+}
+
+impl ShadowCompare {
+    pub fn new(limiter: Arc<dyn ShadowLimiter>) -> Self {
+        Self {
+            limiter,
+            in_flight: Arc::new(Semaphore::new(MAX_SHADOW_CHECKS_IN_FLIGHT)),
+        }
+    }
+
+    /// Run the candidate in the background. Skipped when too many checks are
+    /// already in flight, so a slow candidate cannot pile up tasks.
+    pub fn spawn(&self, check: ShadowCheck, timeout: Duration, remaining_tolerance: u32) {
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            return;
+        };
+        let limiter = self.limiter.clone();
+        tokio::spawn(async move {
+            run(&*limiter, &check, timeout, remaining_tolerance).await;
+            drop(permit);
+        });
+    }
+}
+
+/// Run the candidate for `check` and record how it compared
+pub async fn run(
+    limiter: &dyn ShadowLimiter,
+    check: &ShadowCheck,
+    timeout: Duration,
+    remaining_tolerance: u32,
+) -> ShadowOutcome {
+    let shadow = tokio::time::timeout(
+        timeout,
+        limiter.check(&check.account_id, check.cost, check.max_requests, check.window_seconds),
+    )
+    .await;
+
+    let outcome = match &shadow {
+        Ok(Ok(shadow)) => compare(&check.live, shadow, remaining_tolerance),
+        Ok(Err(e)) => {
+            tracing::debug!(account_id = %check.account_id, "Shadow rate limit check failed: {}", e);
+            ShadowOutcome::Error
+        }
+        Err(_) => ShadowOutcome::Timeout,
+    };
+
+    match outcome {
+        ShadowOutcome::Match => {}
+        ShadowOutcome::AllowedMismatch | ShadowOutcome::RemainingMismatch => {
+            metrics::counter!(RATE_LIMIT_SHADOW_MISMATCH_TOTAL, "kind" => outcome.as_str()).increment(1);
+            tracing::warn!(
+                account_id = %check.account_id,
+                max_requests = check.max_requests,
+                window_seconds = check.window_seconds,
+                live = ?check.live,
+                shadow = ?shadow.ok().and_then(Result::ok),
+                "Shadow rate limiter disagreed"
+            );
+        }
+        ShadowOutcome::Timeout | ShadowOutcome::Error => {
+            metrics::counter!(RATE_LIMIT_SHADOW_FAILURES_TOTAL, "kind" => outcome.as_str()).increment(1);
+        }
+    }
+    outcome
+}
@@ -0,0 +1,74 @@
+//! What submit does when Redis cannot be reached.
+//!
+//! Only failures to reach Redis are degraded; errors Redis answered with,
+//! like a failed script, fail submit as before. Under fail closed, the
+//! default, submit answers 503 `redis_unavailable`. Under fail open it
+//! carries on: no rate limit is checked and no rate limit headers are sent,
+//! no slot under the pending cap is held, and the transaction is stored but
+//! not queued, so its position is counted from the account's pending rows
+//! in Postgres. Each skipped step counts in `submit_redis_fail_open_total`.
+//!
+//! The read and per-IP rate limit layers follow the same mode; see
+//! `rate_limit::layer`.
+
+use crate::{
+    config::{Config, RedisFailureMode},
+    errors::AppError,
+    estimation::{estimate_processing_seconds, tier_multiplier},
+    metrics::SUBMIT_REDIS_FAIL_OPEN_TOTAL,
+    queue_stats::QueueStats,
+    submit_deadline::{QueuePlacement, SubmitPhase},
+    submit_store::SubmitStore,
+};
+use redis_cache::RedisError;
+
+/// Error code of the 503 sent when Redis cannot be reached under fail closed
+pub const REDIS_UNAVAILABLE: &str = "redis_unavailable";
+
+/// Whether submit goes on without Redis after `err` in `phase`, counting it
+/// when it does
+pub fn fails_open(config: &Config, phase: SubmitPhase, err: &RedisError) -> bool {
+    let fails_open = config.redis_failure_mode == RedisFailureMode::FailOpen && err.is_unavailable();
+    if fails_open {
+        tracing::warn!(phase = phase.as_str(), "Redis unavailable, submitting without it: {}", err);
+        metrics::counter!(SUBMIT_REDIS_FAIL_OPEN_TOTAL, "phase" => phase.as_str()).increment(1);
+    }
+    fails_open
+}
+
+/// The error submit and the rate limit layers answer with after `err`: a
+/// 503 when Redis could not be reached, `otherwise` when it answered
+pub fn refusal(err: RedisError, otherwise: impl FnOnce(RedisError) -> AppError) -> AppError {
+    if err.is_unavailable() {
+        return AppError::service_unavailable("Redis is unavailable, requests needing it are refused until it recovers")
+            .with_code(REDIS_UNAVAILABLE);
+    }
+    otherwise(err)
+}
+
+/// Placement of a transaction stored but not queued: behind the account's
+/// other pending rows, estimated from the latest queue stats. Without a
+/// count the position is unknown, as when the deadline runs out.
+pub async fn placement_from_store(
+    store: &dyn SubmitStore,
+    account_id: &str,
+    config: &Config,
+    stats: Option<&QueueStats>,
+) -> QueuePlacement {
+    let position = store.queued_count(account_id).await.unwrap_or_else(|e| {
+        tracing::warn!(account_id, "Failed to count pending transactions: {}", e);
+        None
+    });
+    let items_ahead = position.map_or_else(|| stats.map_or(0, |stats| stats.queue_depth), |position| position - 1);
+    QueuePlacement {
+        position,
+        account_position: position,
+        estimated_processing_time_seconds: estimate_processing_seconds(
+            items_ahead,
+            stats.map(|stats| stats.dequeue_rate_per_second),
+            false,
+            tier_multiplier(config, account_id),
+            config.max_estimated_processing_seconds,
+        ),
+    }
+}
@@ -0,0 +1,135 @@
+//! What this instance is running, for GET /v1/admin/info.
+//!
+//! Assembled once at startup from values the build script recorded and the
+//! effective config. The config goes through its `Serialize` impl, which
+//! redacts URL passwords and leaves admin API keys out, so nothing here can
+//! carry a secret that the config crate does not already hide.
+
+use crate::config::Config;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Every route the app serves, as "METHOD /path". `/metrics` is left out as
+/// it is only served when metrics are exported; `runtime_info_test` checks
+/// each entry resolves.
+pub const ROUTES: &[&str] = &[
+    "GET /health",
+    "GET /health/live",
+    "GET /health/ready",
+    "POST /v1/transactions/submit",
+    "POST /v1/transactions/submit-stream",
+    "GET /v1/transactions/:id",
+    "DELETE /v1/transactions/:id",
+    "GET /v1/accounts/:account_id/estimate",
+    "GET /v1/accounts/:account_id/queue",
+    "POST /v1/accounts/:account_id/transactions/cancel-all",
+    "GET /v1/accounts/:account_id/webhooks",
+    "POST /v1/accounts/:account_id/webhooks",
+    "DELETE /v1/accounts/:account_id/webhooks/:webhook_id",
+    "POST /v1/accounts/:account_id/webhooks/:webhook_id/test",
+    "GET /v1/accounts/:account_id/limit-requests",
+    "POST /v1/accounts/:account_id/limit-requests",
+    "GET /v1/queue/stats",
+    "GET /v1/limits",
+    "GET /v1/admin/accounts/:account_id/limits",
+    "PUT /v1/admin/accounts/:account_id/limits/:limit_type",
+    "DELETE /v1/admin/accounts/:account_id/limits/:limit_type",
+    "PUT /v1/admin/accounts/:account_id/pause",
+    "DELETE /v1/admin/accounts/:account_id/pause",
+    "GET /v1/admin/accounts/:account_id/payload-schema",
+    "PUT /v1/admin/accounts/:account_id/payload-schema",
+    "DELETE /v1/admin/accounts/:account_id/payload-schema",
+    "GET /v1/admin/accounts/:account_id/queue",
+    "POST /v1/admin/accounts/:account_id/tier",
+    "GET /v1/admin/audit-log",
+    "GET /v1/admin/feature-flags",
+    "PUT /v1/admin/feature-flags/:flag",
+    "DELETE /v1/admin/feature-flags/:flag",
+    "GET /v1/admin/info",
+    "GET /v1/admin/limit-requests",
+    "POST /v1/admin/limit-requests/:request_id/approve",
+    "POST /v1/admin/limit-requests/:request_id/deny",
+    "POST /v1/admin/maintenance/rate-limit-expiry",
+    "GET /v1/admin/queue/memory",
+    "GET /v1/admin/queue/quarantine",
+    "DELETE /v1/admin/queue/quarantine/:id",
+    "POST /v1/admin/readiness",
+    "GET /v1/admin/stale-processing",
+    "GET /v1/admin/transactions/search",
+    "PATCH /v1/admin/transactions/:id/status",
+];
+
+/// Cargo features of this crate compiled into the binary
+const FEATURES: &[(&str, bool)] = &[("otel", cfg!(feature = "otel"))];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// "unknown" when built outside a git checkout without GIT_SHA
+    pub git_sha: &'static str,
+    pub built_at: Option<DateTime<Utc>>,
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: env!("BUILD_GIT_SHA"),
+            built_at: env!("BUILD_TIMESTAMP")
+                .parse()
+                .ok()
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| *feature)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteCounts {
+    pub total: usize,
+    /// Routes under /v1/admin
+    pub admin: usize,
+    pub by_method: BTreeMap<&'static str, usize>,
+}
+
+impl RouteCounts {
+    fn of(routes: &[&'static str]) -> Self {
+        let mut by_method = BTreeMap::new();
+        for route in routes {
+            let method = route.split_once(' ').map_or(*route, |(method, _)| method);
+            *by_method.entry(method).or_default() += 1;
+        }
+        Self {
+            total: routes.len(),
+            admin: routes.iter().filter(|route| route.contains(" /v1/admin/")).count(),
+            by_method,
+        }
+    }
+}
+
+/// Build and startup facts of this instance; never changes once built
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeInfo {
+    pub build: BuildInfo,
+    pub started_at: DateTime<Utc>,
+    /// The effective config, with secrets redacted
+    pub config: serde_json::Value,
+    pub routes: RouteCounts,
+}
+
+impl RuntimeInfo {
+    pub fn new(config: &Config, started_at: DateTime<Utc>) -> Self {
+        Self {
+            build: BuildInfo::current(),
+            started_at,
+            config: serde_json::to_value(config).unwrap_or_default(),
+            routes: RouteCounts::of(ROUTES),
+        }
+    }
+}
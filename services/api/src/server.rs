@@ -0,0 +1,140 @@
+use axum::Router;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+    service::TowerToHyperService,
+};
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    net::{TcpListener, UnixListener},
+    sync::watch,
+    task::JoinSet,
+};
+use tracing::{debug, info};
+
+/// All listeners the API serves on. The same router is served on every one.
+pub struct Listeners {
+    tcp: Vec<TcpListener>,
+    unix: Option<UnixSocketListener>,
+}
+
+struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            debug!("Failed to remove Unix socket {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+impl Listeners {
+    /// Bind every TCP address and the optional Unix socket. A stale socket
+    /// file left behind by a previous run is removed before binding.
+    pub async fn bind(addresses: &[SocketAddr], unix_socket: Option<&Path>) -> io::Result<Self> {
+        let mut tcp = Vec::with_capacity(addresses.len());
+        for addr in addresses {
+            tcp.push(TcpListener::bind(addr).await?);
+        }
+
+        let unix = match unix_socket {
+            Some(path) => {
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                Some(UnixSocketListener {
+                    listener: UnixListener::bind(path)?,
+                    path: path.to_path_buf(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self { tcp, unix })
+    }
+
+    /// Local addresses of the bound TCP listeners (resolves port 0)
+    pub fn tcp_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.tcp.iter().map(TcpListener::local_addr).collect()
+    }
+
+    /// Serve `app` on every listener until `shutdown` resolves. The Unix
+    /// socket file is removed once serving stops.
+    pub async fn serve(self, app: Router, shutdown: impl Future<Output = ()> + Send + 'static) -> io::Result<()> {
+        let (shutdown_tx, shutdown_rx) = watch::channel(());
+        let mut tasks = JoinSet::new();
+
+        for listener in self.tcp {
+            info!("Listening on {}", listener.local_addr()?);
+            let app = app.clone();
+            let mut shutdown_rx = shutdown_rx.clone();
+            tasks.spawn(async move {
+                axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                    .with_graceful_shutdown(async move {
+                        let _ = shutdown_rx.changed().await;
+                    })
+                    .await
+            });
+        }
+
+        if let Some(unix) = self.unix {
+            info!("Listening on unix:{}", unix.path.display());
+            let app = app.clone();
+            let shutdown_rx = shutdown_rx.clone();
+            tasks.spawn(serve_unix(unix, app, shutdown_rx));
+        }
+
+        tokio::spawn(async move {
+            shutdown.await;
+            let _ = shutdown_tx.send(());
+        });
+
+        let mut result = Ok(());
+        while let Some(joined) = tasks.join_next().await {
+            let outcome = joined.unwrap_or_else(|e| Err(io::Error::other(e)));
+            if let Err(e) = outcome {
+                // Stop the remaining listeners instead of serving half the addresses
+                tasks.abort_all();
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+/// Accept loop for the Unix socket. axum::serve only takes TCP listeners in
+/// axum 0.7, so connections are driven by hyper directly.
+async fn serve_unix(
+    unix: UnixSocketListener,
+    app: Router,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> io::Result<()> {
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = unix.listener.accept() => accepted?,
+            _ = shutdown_rx.changed() => break,
+        };
+
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            if let Err(e) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Unix socket connection error: {}", e);
+            }
+        });
+    }
+
+    // Dropping the listener removes the socket file
+    drop(unix);
+    Ok(())
+}
@@ -0,0 +1,394 @@
+//! Post-deploy smoke check, run by the `smoke` binary.
+//!
+//! `run` walks a deployed API through the calls a client makes and reports
+//! every step with its latency against a budget:
+//!
+//! 1. `health`: GET /health answers ok
+//! 2. `dry_run`: a submit that validation rejects, which exercises the
+//!    submit route without writing anything (the API has no dry-run mode)
+//! 3. `submit`: a real submit to the smoke account, with rate limit headers
+//! 4. `status`: the transaction is polled until it completes or fails
+//! 5. `rate_limit`: the smoke account's limits, read through the admin API
+//! 6. `cleanup`: the smoke transaction is left alone once terminal and
+//!    cancelled if it is still queued
+//!
+//! A step that fails or runs over its budget fails the run. Steps that need
+//! an earlier step's transaction, or an admin key that was not given, are
+//! skipped rather than failed.
+
+use postgres_models::models::TransactionStatus;
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::extractors::admin::ADMIN_KEY_HEADER;
+
+/// Account every smoke submit goes to, so its traffic is easy to filter out
+pub const SMOKE_ACCOUNT_ID: &str = "smoke-test";
+
+/// Requests against one API deployment
+#[derive(Clone)]
+pub struct SmokeClient {
+    client: Client,
+    base_url: String,
+    admin_key: Option<String>,
+}
+
+impl SmokeClient {
+    pub fn new(base_url: &str, admin_key: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            admin_key,
+        }
+    }
+
+    pub fn has_admin_key(&self) -> bool {
+        self.admin_key.is_some()
+    }
+
+    pub async fn health(&self) -> reqwest::Result<Response> {
+        self.client.get(format!("{}/health", self.base_url)).send().await
+    }
+
+    pub async fn submit(&self, payload: &Value) -> reqwest::Result<Response> {
+        self.client
+            .post(format!("{}/v1/transactions/submit", self.base_url))
+            .json(payload)
+            .send()
+            .await
+    }
+
+    pub async fn status(&self, id: Uuid) -> reqwest::Result<Response> {
+        self.client
+            .get(format!("{}/v1/transactions/{}", self.base_url, id))
+            .send()
+            .await
+    }
+
+    pub async fn cancel(&self, id: Uuid) -> reqwest::Result<Response> {
+        self.client
+            .delete(format!("{}/v1/transactions/{}", self.base_url, id))
+            .send()
+            .await
+    }
+
+    /// A call under /v1/admin, sent with the admin key if one was given
+    pub async fn admin(&self, method: Method, path: &str) -> reqwest::Result<Response> {
+        let mut request = self.client.request(method, format!("{}/v1/admin{}", self.base_url, path));
+        if let Some(key) = &self.admin_key {
+            request = request.header(ADMIN_KEY_HEADER, key);
+        }
+        request.send().await
+    }
+}
+
+/// Longest each step may take
+#[derive(Debug, Clone, Copy)]
+pub struct StepBudgets {
+    pub health: Duration,
+    pub dry_run: Duration,
+    pub submit: Duration,
+    /// The whole status poll, until the transaction is terminal
+    pub status: Duration,
+    pub rate_limit: Duration,
+    pub cleanup: Duration,
+}
+
+impl Default for StepBudgets {
+    fn default() -> Self {
+        Self {
+            health: Duration::from_millis(500),
+            dry_run: Duration::from_millis(500),
+            submit: Duration::from_millis(1000),
+            status: Duration::from_secs(30),
+            rate_limit: Duration::from_millis(500),
+            cleanup: Duration::from_millis(1000),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SmokeConfig {
+    pub account_id: String,
+    pub budgets: StepBudgets,
+    /// Pause between status reads
+    pub poll_interval: Duration,
+}
+
+impl Default for SmokeConfig {
+    fn default() -> Self {
+        Self {
+            account_id: SMOKE_ACCOUNT_ID.to_string(),
+            budgets: StepBudgets::default(),
+            poll_interval: Duration::from_millis(250),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Health,
+    DryRun,
+    Submit,
+    Status,
+    RateLimit,
+    Cleanup,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepOutcome {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepReport {
+    pub step: Step,
+    pub outcome: StepOutcome,
+    pub latency_ms: u64,
+    pub budget_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SmokeReport {
+    pub base_url: String,
+    pub passed: bool,
+    pub steps: Vec<StepReport>,
+}
+
+impl SmokeReport {
+    pub fn step(&self, step: Step) -> Option<&StepReport> {
+        self.steps.iter().find(|report| report.step == step)
+    }
+
+    /// Steps that failed, in the order they ran
+    pub fn failures(&self) -> impl Iterator<Item = &StepReport> {
+        self.steps.iter().filter(|report| report.outcome == StepOutcome::Failed)
+    }
+}
+
+/// Run every step against `client` and report each one
+pub async fn run(client: &SmokeClient, config: &SmokeConfig) -> SmokeReport {
+    let budgets = config.budgets;
+    let mut steps = Vec::new();
+
+    steps.push(timed(Step::Health, budgets.health, check_health(client)).await.0);
+    steps.push(timed(Step::DryRun, budgets.dry_run, dry_run_submit(client, &config.account_id)).await.0);
+
+    let (report, submitted) = timed(Step::Submit, budgets.submit, submit(client, &config.account_id)).await;
+    steps.push(report);
+
+    let final_status = match submitted {
+        Some(id) => {
+            let (report, status) = timed(
+                Step::Status,
+                budgets.status,
+                poll_until_terminal(client, id, budgets.status, config.poll_interval),
+            )
+            .await;
+            steps.push(report);
+            status
+        }
+        None => {
+            steps.push(skipped(Step::Status, budgets.status, "no transaction was submitted"));
+            None
+        }
+    };
+
+    if client.has_admin_key() {
+        steps.push(
+            timed(Step::RateLimit, budgets.rate_limit, read_rate_limits(client, &config.account_id))
+                .await
+                .0,
+        );
+    } else {
+        steps.push(skipped(Step::RateLimit, budgets.rate_limit, "no admin key given"));
+    }
+
+    steps.push(match (submitted, final_status) {
+        (Some(id), Some(status)) => untimed(
+            Step::Cleanup,
+            StepOutcome::Passed,
+            budgets.cleanup,
+            format!("{} finished as {}, nothing to cancel", id, status.as_str()),
+        ),
+        (Some(id), _) => timed(Step::Cleanup, budgets.cleanup, cancel(client, id)).await.0,
+        (None, _) => skipped(Step::Cleanup, budgets.cleanup, "no transaction was submitted"),
+    });
+
+    SmokeReport {
+        base_url: client.base_url.clone(),
+        passed: steps.iter().all(|report| report.outcome != StepOutcome::Failed),
+        steps,
+    }
+}
+
+/// GET /health answers 200 with status ok
+pub async fn check_health(client: &SmokeClient) -> Result<(), String> {
+    let response = client.health().await.map_err(|e| e.to_string())?;
+    let body = expect_status(response, StatusCode::OK).await?;
+    if body["status"] != "ok" {
+        return Err(format!("unexpected health body: {}", body));
+    }
+    Ok(())
+}
+
+/// Cancel a transaction that did not finish. One a worker took up since is
+/// answered 409, and left to finish.
+pub async fn cancel(client: &SmokeClient, id: Uuid) -> Result<(), String> {
+    let response = client.cancel(id).await.map_err(|e| e.to_string())?;
+    if response.status() == StatusCode::CONFLICT {
+        return Ok(());
+    }
+    expect_status(response, StatusCode::OK).await?;
+    Ok(())
+}
+
+/// A submit with an empty account id is answered 400 without being stored
+pub async fn dry_run_submit(client: &SmokeClient, account_id: &str) -> Result<(), String> {
+    let payload = json!({
+        "account_id": "",
+        "transaction_data": smoke_payload(account_id),
+    });
+    let response = client.submit(&payload).await.map_err(|e| e.to_string())?;
+    expect_status(response, StatusCode::BAD_REQUEST).await?;
+    Ok(())
+}
+
+/// Submit a transaction to `account_id` and return its id
+pub async fn submit(client: &SmokeClient, account_id: &str) -> Result<Uuid, String> {
+    let payload = json!({
+        "account_id": account_id,
+        "transaction_data": smoke_payload(account_id),
+    });
+    let response = client.submit(&payload).await.map_err(|e| e.to_string())?;
+    let has_limit_headers = response.headers().contains_key("X-RateLimit-Remaining");
+    let body = expect_status(response, StatusCode::OK).await?;
+    if !has_limit_headers {
+        return Err("submit response has no X-RateLimit-Remaining header".to_string());
+    }
+    body["transaction_id"]
+        .as_str()
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| format!("submit response has no transaction_id: {}", body))
+}
+
+/// Read the status of `id` until it is terminal, for at most `timeout`.
+/// Reads answered 429 are retried.
+pub async fn poll_until_terminal(
+    client: &SmokeClient,
+    id: Uuid,
+    timeout: Duration,
+    interval: Duration,
+) -> Result<TransactionStatus, String> {
+    let started = Instant::now();
+    let mut last = None;
+    loop {
+        let response = client.status(id).await.map_err(|e| e.to_string())?;
+        if response.status() != StatusCode::TOO_MANY_REQUESTS {
+            let body = expect_status(response, StatusCode::OK).await?;
+            let status = body["status"]
+                .as_str()
+                .and_then(TransactionStatus::parse)
+                .ok_or_else(|| format!("unexpected status body: {}", body))?;
+            if status.is_terminal() {
+                return Ok(status);
+            }
+            last = Some(status);
+        }
+        if started.elapsed() + interval > timeout {
+            let last = last.map_or("unknown", |status| status.as_str());
+            return Err(format!("transaction {} still {} after {}ms", id, last, millis(started.elapsed())));
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// The account's configured limits, read through the admin API
+pub async fn read_rate_limits(client: &SmokeClient, account_id: &str) -> Result<Value, String> {
+    let response = client
+        .admin(Method::GET, &format!("/accounts/{}/limits", account_id))
+        .await
+        .map_err(|e| e.to_string())?;
+    let body = expect_status(response, StatusCode::OK).await?;
+    if !body.is_array() {
+        return Err(format!("unexpected limits body: {}", body));
+    }
+    Ok(body)
+}
+
+fn smoke_payload(account_id: &str) -> Value {
+    json!({
+        "type": "smoke",
+        "account_id": account_id,
+        "amount": 0,
+    })
+}
+
+/// The JSON body of `response`, or an error naming the status it came with
+async fn expect_status(response: Response, expected: StatusCode) -> Result<Value, String> {
+    let status = response.status();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    if status != expected {
+        return Err(format!("expected {}, got {}: {}", expected, status, text));
+    }
+    serde_json::from_str(&text).map_err(|e| format!("invalid JSON body ({}): {}", e, text))
+}
+
+/// Run `step`, failing it if it errs or takes longer than `budget`
+async fn timed<T>(
+    step: Step,
+    budget: Duration,
+    future: impl Future<Output = Result<T, String>>,
+) -> (StepReport, Option<T>) {
+    let started = Instant::now();
+    let result = future.await;
+    let latency = started.elapsed();
+
+    // A slow step still hands on its value, so later steps run and clean up
+    let (outcome, detail, value) = match result {
+        Ok(value) if latency > budget => (
+            StepOutcome::Failed,
+            Some(format!("took {}ms, over the {}ms budget", millis(latency), millis(budget))),
+            Some(value),
+        ),
+        Ok(value) => (StepOutcome::Passed, None, Some(value)),
+        Err(e) => (StepOutcome::Failed, Some(e), None),
+    };
+    let report = StepReport {
+        step,
+        outcome,
+        latency_ms: millis(latency),
+        budget_ms: millis(budget),
+        detail,
+    };
+    (report, value)
+}
+
+fn skipped(step: Step, budget: Duration, reason: &str) -> StepReport {
+    untimed(step, StepOutcome::Skipped, budget, reason.to_string())
+}
+
+/// Report of a step that sent no request
+fn untimed(step: Step, outcome: StepOutcome, budget: Duration, detail: String) -> StepReport {
+    StepReport {
+        step,
+        outcome,
+        latency_ms: 0,
+        budget_ms: millis(budget),
+        detail: Some(detail),
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
@@ -0,0 +1,118 @@
+use crate::{config::StaleProcessingConfig, metrics, AppState};
+use chrono::{DateTime, Utc};
+use diesel_async::AsyncPgConnection;
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use postgres_models::DbError;
+use serde::Serialize;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A transaction that has sat in "processing" past the threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleTransaction {
+    pub id: Uuid,
+    pub account_id: String,
+    pub retry_count: i32,
+    /// Last time the row was updated, i.e. when it entered "processing"
+    pub processing_since: DateTime<Utc>,
+    pub age_seconds: i64,
+}
+
+impl StaleTransaction {
+    fn from_row(row: TransactionQueue, now: DateTime<Utc>) -> Self {
+        Self {
+            age_seconds: (now - row.updated_at).num_seconds(),
+            id: row.id,
+            account_id: row.account_id,
+            retry_count: row.retry_count,
+            processing_since: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StaleReport {
+    /// Every stale row found, including the ones healed afterwards
+    pub stale: Vec<StaleTransaction>,
+    /// Rows moved back to "retry" by this check
+    pub healed: Vec<Uuid>,
+}
+
+/// Rows in "processing" without an update for longer than `older_than`
+pub async fn find(
+    conn: &mut AsyncPgConnection,
+    older_than: Duration,
+) -> Result<Vec<StaleTransaction>, DbError> {
+    let now = Utc::now();
+    let rows = TransactionQueue::find_stale_processing(conn, older_than).await?;
+    Ok(rows.into_iter().map(|row| StaleTransaction::from_row(row, now)).collect())
+}
+
+/// Find stale rows and, when auto-heal is configured, move the ones past the
+/// hard limit back to "retry". A row that a worker finishes in the meantime,
+/// or that has no retries left, is left alone by the conditional transition
+/// and stays reported as stale.
+pub async fn check(
+    conn: &mut AsyncPgConnection,
+    config: &StaleProcessingConfig,
+) -> Result<StaleReport, DbError> {
+    let stale = find(conn, Duration::from_secs(config.threshold_seconds)).await?;
+
+    let mut healed = Vec::new();
+    if let Some(heal_after) = config.heal_after_seconds {
+        for transaction in stale.iter().filter(|t| t.age_seconds >= heal_after as i64) {
+            let moved = TransactionQueue::transition_status(
+                conn,
+                transaction.id,
+                TransactionStatus::Processing,
+                TransactionStatus::Retry,
+            )
+            .await?;
+            if moved {
+                healed.push(transaction.id);
+            }
+        }
+    }
+
+    Ok(StaleReport { stale, healed })
+}
+
+/// Publish a check result to the gauge and log the offenders
+pub fn record(report: &StaleReport) {
+    ::metrics::gauge!(metrics::STALE_PROCESSING_TRANSACTIONS).set(report.stale.len() as f64);
+    ::metrics::counter!(metrics::STALE_PROCESSING_HEALED_TOTAL).increment(report.healed.len() as u64);
+
+    if let Some(oldest) = report.stale.first() {
+        tracing::warn!(
+            count = report.stale.len(),
+            oldest_id = %oldest.id,
+            oldest_age_seconds = oldest.age_seconds,
+            healed = report.healed.len(),
+            "Transactions stuck in processing"
+        );
+    }
+}
+
+/// Periodically check for stale "processing" rows
+pub fn spawn_stale_processing_check(state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let config = state.config.stale_processing.clone();
+        let mut interval = tokio::time::interval(Duration::from_secs(config.check_interval_seconds));
+
+        loop {
+            interval.tick().await;
+
+            let mut conn = match state.db_pool.get().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::debug!("Failed to get a connection for the stale processing check: {}", e);
+                    continue;
+                }
+            };
+            match check(&mut conn, &config).await {
+                Ok(report) => record(&report),
+                Err(e) => tracing::debug!("Stale processing check failed: {}", e),
+            }
+        }
+    })
+}
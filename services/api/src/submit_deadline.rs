@@ -0,0 +1,257 @@
+//! Latency budget of the submit handler.
+//!
+//! Submit checks a `Deadline` as each phase finishes. Phases up to the insert
+//! always run, since there is nothing to answer before the row exists, and
+//! the transaction is always queued. Once the budget is spent the queue
+//! position is not looked up: submit answers 202 with no position and an
+//! estimate from the latest queue stats sample, and clients follow up on the
+//! status endpoint. The first phase to finish past the deadline is counted
+//! in `submit_deadline_exceeded_total`.
+
+use crate::{
+    config::Config,
+    estimation::{
+        estimate_at_position, estimate_processing_seconds, live_inputs, tier_multiplier, THROUGHPUT_WINDOW_MINUTES,
+    },
+    metrics::SUBMIT_DEADLINE_EXCEEDED_TOTAL,
+    queue_stats::QueueStats,
+};
+use futures::future::BoxFuture;
+use redis_cache::{ProcessingHold, QueueManager, QueueName, RedisError};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitPhase {
+    Idempotency,
+    RateLimit,
+    DbConnection,
+    AccountLimits,
+    PendingReservation,
+    Insert,
+    Enqueue,
+    QueuePosition,
+    Estimate,
+}
+
+impl SubmitPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Idempotency => "idempotency",
+            Self::RateLimit => "rate_limit",
+            Self::DbConnection => "db_connection",
+            Self::AccountLimits => "account_limits",
+            Self::PendingReservation => "pending_reservation",
+            Self::Insert => "insert",
+            Self::Enqueue => "enqueue",
+            Self::QueuePosition => "queue_position",
+            Self::Estimate => "estimate",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Deadline {
+    expires_at: Instant,
+    exceeded_in: Option<SubmitPhase>,
+}
+
+impl Deadline {
+    pub fn after(budget: Duration) -> Self {
+        Self {
+            expires_at: Instant::now() + budget,
+            exceeded_in: None,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.expires_at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Phase that was running when the budget ran out
+    pub fn exceeded_in(&self) -> Option<SubmitPhase> {
+        self.exceeded_in
+    }
+
+    /// Mark `phase` as finished, recording it if it finished past the deadline
+    /// and no earlier phase did
+    pub fn checkpoint(&mut self, phase: SubmitPhase) {
+        if self.exceeded_in.is_none() && self.is_exhausted() {
+            self.exceeded_in = Some(phase);
+            metrics::counter!(SUBMIT_DEADLINE_EXCEEDED_TOTAL, "phase" => phase.as_str()).increment(1);
+        }
+    }
+
+    /// Run `phase` with what is left of the budget; `None` if it did not finish in time
+    pub async fn within<F: Future>(&mut self, phase: SubmitPhase, run: F) -> Option<F::Output> {
+        let output = tokio::time::timeout_at(self.expires_at, run).await.ok();
+        self.checkpoint(phase);
+        output
+    }
+}
+
+/// Member submit adds to the queue
+#[derive(Debug, Clone, Copy)]
+pub struct QueueEntry<'a> {
+    pub member: &'a str,
+    pub account_id: &'a str,
+    pub priority: i32,
+}
+
+impl<'a> QueueEntry<'a> {
+    pub fn new(member: &'a str, account_id: &'a str, priority: i32) -> Self {
+        Self {
+            member,
+            account_id,
+            priority,
+        }
+    }
+}
+
+/// Queue operations of submit, around the insert, and what status reads back
+pub trait SubmitQueue: Send + Sync {
+    fn add<'a>(&'a self, queue_name: &'a QueueName, entry: QueueEntry<'a>) -> BoxFuture<'a, Result<(), RedisError>>;
+    fn position<'a>(&'a self, queue_name: &'a QueueName, member: &'a str) -> BoxFuture<'a, Result<i64, RedisError>>;
+    /// Position of `member` among its own account's members, if it names one
+    fn account_position<'a>(
+        &'a self,
+        queue_name: &'a QueueName,
+        member: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>>;
+    fn processing_rate<'a>(&'a self, queue_name: &'a QueueName) -> BoxFuture<'a, Result<Option<f64>, RedisError>>;
+    fn live_worker_count(&self, max_age_seconds: u64) -> BoxFuture<'_, Result<i64, RedisError>>;
+    fn transaction_position<'a>(
+        &'a self,
+        queue_name: &'a QueueName,
+        account_id: &'a str,
+        transaction_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>>;
+    /// Take one of the account's `cap` pending slots; false if all are taken
+    fn reserve_pending<'a>(&'a self, account_id: &'a str, cap: u32) -> BoxFuture<'a, Result<bool, RedisError>>;
+    fn release_pending<'a>(&'a self, account_id: &'a str) -> BoxFuture<'a, Result<i64, RedisError>>;
+    fn hold<'a>(&'a self, transaction_id: &'a str) -> BoxFuture<'a, Result<Option<ProcessingHold>, RedisError>>;
+}
+
+impl SubmitQueue for QueueManager {
+    fn add<'a>(&'a self, queue_name: &'a QueueName, entry: QueueEntry<'a>) -> BoxFuture<'a, Result<(), RedisError>> {
+        Box::pin(self.add_with_priority(queue_name, entry.member, entry.priority))
+    }
+
+    fn position<'a>(&'a self, queue_name: &'a QueueName, member: &'a str) -> BoxFuture<'a, Result<i64, RedisError>> {
+        Box::pin(self.priority_position(queue_name, member))
+    }
+
+    fn account_position<'a>(
+        &'a self,
+        queue_name: &'a QueueName,
+        member: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>> {
+        Box::pin(QueueManager::account_position(self, queue_name, member))
+    }
+
+    fn processing_rate<'a>(&'a self, queue_name: &'a QueueName) -> BoxFuture<'a, Result<Option<f64>, RedisError>> {
+        Box::pin(QueueManager::processing_rate(self, queue_name, THROUGHPUT_WINDOW_MINUTES))
+    }
+
+    fn live_worker_count(&self, max_age_seconds: u64) -> BoxFuture<'_, Result<i64, RedisError>> {
+        Box::pin(QueueManager::live_worker_count(self, max_age_seconds))
+    }
+
+    fn transaction_position<'a>(
+        &'a self,
+        queue_name: &'a QueueName,
+        account_id: &'a str,
+        transaction_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>> {
+        Box::pin(QueueManager::transaction_position(self, queue_name, account_id, transaction_id))
+    }
+
+    fn reserve_pending<'a>(&'a self, account_id: &'a str, cap: u32) -> BoxFuture<'a, Result<bool, RedisError>> {
+        Box::pin(QueueManager::reserve_pending(self, account_id, cap))
+    }
+
+    fn release_pending<'a>(&'a self, account_id: &'a str) -> BoxFuture<'a, Result<i64, RedisError>> {
+        Box::pin(QueueManager::release_pending(self, account_id))
+    }
+
+    fn hold<'a>(&'a self, transaction_id: &'a str) -> BoxFuture<'a, Result<Option<ProcessingHold>, RedisError>> {
+        Box::pin(self.get_hold(transaction_id))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuePlacement {
+    /// Position in the whole queue; `None` when the budget ran out before
+    /// the position was looked up
+    pub position: Option<i64>,
+    /// Position among the account's own queued items, looked up with
+    /// `position`
+    pub account_position: Option<i64>,
+    pub estimated_processing_time_seconds: i64,
+}
+
+/// Queue `entry` and work out its position and estimate within the deadline.
+///
+/// Only queueing itself can fail; lookups that run out of budget fall back
+/// to `stats`, the latest queue stats sample.
+pub async fn place(
+    queue: &dyn SubmitQueue,
+    deadline: &mut Deadline,
+    queue_name: &QueueName,
+    entry: QueueEntry<'_>,
+    config: &Config,
+    stats: Option<&QueueStats>,
+) -> Result<QueuePlacement, RedisError> {
+    queue.add(queue_name, entry).await?;
+    deadline.checkpoint(SubmitPhase::Enqueue);
+
+    let positions = if deadline.is_exhausted() {
+        None
+    } else {
+        let lookup = async {
+            tokio::try_join!(queue.position(queue_name, entry.member), queue.account_position(queue_name, entry.member))
+        };
+        deadline.within(SubmitPhase::QueuePosition, lookup).await.transpose()?
+    };
+    let stats_rate = stats.map(|stats| stats.dequeue_rate_per_second);
+    let multiplier = tier_multiplier(config, entry.account_id);
+    let Some((position, account_position)) = positions else {
+        // Assume the item waits behind everything queued at the last sample
+        let queue_depth = stats.map_or(0, |stats| stats.queue_depth);
+        return Ok(QueuePlacement {
+            position: None,
+            account_position: None,
+            estimated_processing_time_seconds: estimate_processing_seconds(
+                queue_depth,
+                stats_rate,
+                false,
+                multiplier,
+                config.max_estimated_processing_seconds,
+            ),
+        });
+    };
+
+    // Throughput and worker liveness only refine the estimate, so lookup failures
+    // and timeouts fall back to the stats sample instead of failing the submission
+    let (throughput, workers_alive) =
+        match deadline.within(SubmitPhase::Estimate, live_inputs(queue, queue_name, config)).await {
+            Some(inputs) => inputs,
+            None => (stats_rate, false),
+        };
+    // Submit always answers with a number; without live workers that is the
+    // item's full turn once they return, where status would report none
+    let max_seconds = config.max_estimated_processing_seconds;
+    let estimate = estimate_at_position(position, throughput, workers_alive, multiplier, max_seconds)
+        .unwrap_or_else(|_| estimate_processing_seconds(position - 1, throughput, false, multiplier, max_seconds));
+
+    Ok(QueuePlacement {
+        position: Some(position),
+        account_position,
+        estimated_processing_time_seconds: estimate,
+    })
+}
@@ -0,0 +1,181 @@
+//! Postgres reads and writes of the submit path.
+//!
+//! Submit only needs an account's limit rows, its payload schema and the
+//! insert of the new transaction, and status only the row read back, so
+//! those sit behind `SubmitStore` and both can run against a stub, e.g. a
+//! test with a real Redis and no database, or the in-memory backend.
+//!
+//! Status reads may go to a replica; see `consistency` for how a read the
+//! replica misses right after submit is retried on the primary.
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use futures::future::BoxFuture;
+use crate::extractors::acquire_connection;
+use postgres_models::models::{NewTransactionQueueRef, PayloadSchema, TransactionQueue};
+use postgres_models::schema::{rate_limits, transaction_queue};
+use postgres_models::{DbError, DbPool};
+use redis_cache::{CachedLimit, RateLimitStrategy};
+use serde_json::Value;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Database operations submit runs
+pub trait SubmitStore: Send + Sync {
+    /// The account's rate_limits rows of the given types, by `limit_type`
+    fn account_limits<'a>(
+        &'a self,
+        account_id: &'a str,
+        limit_types: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<HashMap<String, CachedLimit>, DbError>>;
+
+    /// The JSON Schema the account's payloads must match, if it has one
+    /// enabled. Stores that cannot hold schemas, like the in-memory one,
+    /// have none.
+    fn payload_schema<'a>(&'a self, _account_id: &'a str) -> BoxFuture<'a, Result<Option<Value>, DbError>> {
+        Box::pin(async move { Ok(None) })
+    }
+
+    /// Store the transaction and return its id. A connection that cannot be
+    /// had is a `DbError::Connection`.
+    fn insert<'a>(&'a self, transaction: &'a NewTransactionQueueRef<'a>) -> BoxFuture<'a, Result<Uuid, DbError>>;
+
+    /// How many of the account's transactions are pending, counting one just
+    /// inserted. Stores that cannot count them, like test stubs, know none.
+    fn queued_count<'a>(&'a self, _account_id: &'a str) -> BoxFuture<'a, Result<Option<i64>, DbError>> {
+        Box::pin(async move { Ok(None) })
+    }
+
+    /// The transaction with `id`, if any. A connection that cannot be had is
+    /// a `DbError::Connection`.
+    fn find(&self, id: Uuid) -> BoxFuture<'_, Result<Option<TransactionQueue>, DbError>>;
+
+    /// The account's transaction inserted with `idempotency_key`, read where
+    /// `insert` wrote it. Stores that keep no keys have none.
+    fn find_by_idempotency_key<'a>(
+        &'a self,
+        _account_id: &'a str,
+        _idempotency_key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<TransactionQueue>, DbError>> {
+        Box::pin(async move { Ok(None) })
+    }
+
+    /// Whether `find` reads a replica that can lag behind `insert`
+    fn reads_replica(&self) -> bool {
+        false
+    }
+
+    /// The transaction with `id` read where `insert` wrote it, for a read
+    /// `find` may have missed because the replica lags
+    fn find_on_primary(&self, id: Uuid) -> BoxFuture<'_, Result<Option<TransactionQueue>, DbError>> {
+        self.find(id)
+    }
+}
+
+/// The store backed by the service's Postgres pool
+pub struct PgSubmitStore {
+    pool: DbPool,
+    /// Where status reads go when set
+    replica: Option<DbPool>,
+}
+
+impl PgSubmitStore {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool, replica: None }
+    }
+
+    /// Read transactions for status from `replica`
+    pub fn with_replica(mut self, replica: DbPool) -> Self {
+        self.replica = Some(replica);
+        self
+    }
+
+    async fn find_in(pool: &DbPool, id: Uuid) -> Result<Option<TransactionQueue>, DbError> {
+        // Status reads retry a briefly exhausted pool like the extractors do
+        let mut conn = acquire_connection(pool)
+            .await
+            .map_err(|e| DbError::Connection(e.message))?;
+        TransactionQueue::find(&mut conn, id).await
+    }
+}
+
+impl SubmitStore for PgSubmitStore {
+    fn account_limits<'a>(
+        &'a self,
+        account_id: &'a str,
+        limit_types: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<HashMap<String, CachedLimit>, DbError>> {
+        Box::pin(async move {
+            let mut conn = self.pool.get().await.map_err(|e| DbError::Connection(e.to_string()))?;
+            let rows = rate_limits::table
+                .filter(rate_limits::account_id.eq(account_id))
+                .filter(rate_limits::limit_type.eq_any(limit_types))
+                .select((
+                    rate_limits::limit_type,
+                    rate_limits::max_requests,
+                    rate_limits::window_seconds,
+                    rate_limits::strategy,
+                ))
+                .load::<(String, i32, i32, String)>(&mut conn)
+                .await?;
+            Ok(rows
+                .into_iter()
+                .map(|(limit_type, max_requests, window_seconds, strategy)| {
+                    // The column is checked, so only a newer deployment's strategy is unknown here
+                    let strategy = RateLimitStrategy::parse(&strategy).unwrap_or_default();
+                    (limit_type, CachedLimit { max_requests, window_seconds, strategy })
+                })
+                .collect())
+        })
+    }
+
+    fn payload_schema<'a>(&'a self, account_id: &'a str) -> BoxFuture<'a, Result<Option<Value>, DbError>> {
+        Box::pin(async move {
+            let mut conn = self.pool.get().await.map_err(|e| DbError::Connection(e.to_string()))?;
+            PayloadSchema::find_enabled(&mut conn, account_id).await
+        })
+    }
+
+    fn insert<'a>(&'a self, transaction: &'a NewTransactionQueueRef<'a>) -> BoxFuture<'a, Result<Uuid, DbError>> {
+        Box::pin(async move {
+            let mut conn = self.pool.get().await.map_err(|e| DbError::Connection(e.to_string()))?;
+            // Only the id is returned, so the payload is not copied back out of Postgres
+            let id = diesel::insert_into(transaction_queue::table)
+                .values(transaction)
+                .returning(transaction_queue::id)
+                .get_result::<Uuid>(&mut conn)
+                .await?;
+            Ok(id)
+        })
+    }
+
+    fn queued_count<'a>(&'a self, account_id: &'a str) -> BoxFuture<'a, Result<Option<i64>, DbError>> {
+        Box::pin(async move {
+            let mut conn = self.pool.get().await.map_err(|e| DbError::Connection(e.to_string()))?;
+            TransactionQueue::count_queued_for_account(&mut conn, account_id).await.map(Some)
+        })
+    }
+
+    fn find(&self, id: Uuid) -> BoxFuture<'_, Result<Option<TransactionQueue>, DbError>> {
+        Box::pin(Self::find_in(self.replica.as_ref().unwrap_or(&self.pool), id))
+    }
+
+    fn find_by_idempotency_key<'a>(
+        &'a self,
+        account_id: &'a str,
+        idempotency_key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<TransactionQueue>, DbError>> {
+        Box::pin(async move {
+            let mut conn = self.pool.get().await.map_err(|e| DbError::Connection(e.to_string()))?;
+            TransactionQueue::find_by_idempotency_key(&mut conn, account_id, idempotency_key).await
+        })
+    }
+
+    fn reads_replica(&self) -> bool {
+        self.replica.is_some()
+    }
+
+    fn find_on_primary(&self, id: Uuid) -> BoxFuture<'_, Result<Option<TransactionQueue>, DbError>> {
+        Box::pin(Self::find_in(&self.pool, id))
+    }
+}
\ No newline at end of file
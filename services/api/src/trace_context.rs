@@ -0,0 +1,61 @@
+//! Trace context carried through the queue, so a trace started at submit
+//! continues when a worker processes the transaction.
+//!
+//! Submit stores the W3C traceparent of its span in the queue envelope and
+//! the worker starts its processing span as a child of it; the time between
+//! the two shows up as a gap in the trace. Without the `otel` feature no
+//! traceparent is written and processing spans start on their own.
+
+use redis_cache::QueueEnvelope;
+use tracing::Span;
+
+/// Traceparent of the current span, if it belongs to an OpenTelemetry trace
+pub fn current_traceparent() -> Option<String> {
+    #[cfg(feature = "otel")]
+    return otel::traceparent(&Span::current());
+
+    #[cfg(not(feature = "otel"))]
+    None
+}
+
+/// Span for processing a dequeued transaction, continuing the submit trace
+/// when the envelope carries one
+pub fn processing_span(envelope: &QueueEnvelope) -> Span {
+    let span = tracing::info_span!(
+        "process_transaction",
+        transaction_id = %envelope.transaction_id,
+        account_id = %envelope.account_id,
+    );
+
+    #[cfg(feature = "otel")]
+    if let Some(traceparent) = &envelope.traceparent {
+        otel::set_parent(&span, traceparent);
+    }
+    span
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use std::collections::HashMap;
+    use tracing::Span;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    const TRACEPARENT: &str = "traceparent";
+
+    pub fn traceparent(span: &Span) -> Option<String> {
+        let mut carrier = HashMap::new();
+        // Spans outside a valid trace inject nothing
+        TraceContextPropagator::new().inject_context(&span.context(), &mut carrier);
+        carrier.remove(TRACEPARENT)
+    }
+
+    pub fn set_parent(span: &Span, traceparent: &str) {
+        let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+        let parent = TraceContextPropagator::new().extract(&carrier);
+        if let Err(err) = span.set_parent(parent) {
+            tracing::debug!(error = %err, "Processing span not linked to submit trace");
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{DatabaseConnection, ValidatedJson},
+    AppState, TRANSACTION_QUEUE,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use postgres_models::models::{CancelFilter, CancelledTransaction, TransactionQueue};
+use redis_cache::{QueueEvent, QueueEventKind};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Rows moved to "cancelled" per UPDATE
+pub const CANCEL_BATCH_SIZE: i64 = 500;
+
+/// Most cancelled ids listed in a response; the count covers the rest
+pub const MAX_RETURNED_IDS: usize = 100;
+
+pub use api_client::types::{CancelAllRequest, CancelAllResponse};
+
+/// Cancel every pending transaction of an account matching the filter.
+///
+/// Only rows still pending are cancelled: ones a worker has claimed run to
+/// completion. Transactions submitted while this runs are left alone unless
+/// `created_before` says otherwise.
+pub async fn cancel_all(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+    ValidatedJson(request): ValidatedJson<CancelAllRequest>,
+) -> AppResult<Json<CancelAllResponse>> {
+    if let (Some(min), Some(max)) = (request.min_priority, request.max_priority) {
+        if min > max {
+            return Err(AppError::bad_request("min_priority must not be greater than max_priority"));
+        }
+    }
+    let filter = CancelFilter {
+        min_priority: request.min_priority,
+        max_priority: request.max_priority,
+        created_before: Some(request.created_before.unwrap_or_else(|| state.clock.now())),
+    };
+
+    let mut cancelled = Vec::new();
+    loop {
+        let batch = TransactionQueue::cancel_pending(&mut db_conn, &account_id, &filter, CANCEL_BATCH_SIZE).await?;
+        if batch.is_empty() {
+            break;
+        }
+        cancelled.extend(batch);
+    }
+
+    let ids: Vec<Uuid> = cancelled.iter().map(|transaction| transaction.id).collect();
+    let removed_from_queue = remove_queue_entries(&state, &account_id, &ids).await;
+    publish_cancelled(&state, &account_id, &cancelled);
+    tracing::info!(
+        account_id,
+        cancelled = cancelled.len(),
+        removed_from_queue,
+        "Cancelled pending transactions"
+    );
+
+    let has_more = cancelled.len() > MAX_RETURNED_IDS;
+    Ok(Json(CancelAllResponse {
+        cancelled: cancelled.len() as u64,
+        removed_from_queue,
+        transaction_ids: ids.into_iter().take(MAX_RETURNED_IDS).collect(),
+        has_more,
+    }))
+}
+
+/// Drop the cancelled transactions from the queue and free their pending
+/// slots. The rows are already cancelled, so failures are logged: the worker
+/// skips what is left and the reconciler corrects the counter.
+async fn remove_queue_entries(state: &AppState, account_id: &str, cancelled: &[Uuid]) -> u64 {
+    if cancelled.is_empty() {
+        return 0;
+    }
+    let queue_manager = state.queue_manager();
+    let ids: HashSet<String> = cancelled.iter().map(Uuid::to_string).collect();
+
+    if let Err(e) = queue_manager.release_pending_by(account_id, cancelled.len() as u64).await {
+        tracing::warn!(account_id, "Failed to release pending slots of cancelled transactions: {}", e);
+    }
+    match queue_manager.remove_transactions(&TRANSACTION_QUEUE, &ids).await {
+        Ok(removed) => removed,
+        Err(e) => {
+            tracing::warn!(account_id, "Failed to remove cancelled transactions from the queue: {}", e);
+            0
+        }
+    }
+}
+
+/// Publish a cancelled event per transaction, with how long it waited
+pub fn publish_cancelled(state: &AppState, account_id: &str, cancelled: &[CancelledTransaction]) {
+    if !state.events.is_enabled() {
+        return;
+    }
+    let now = state.clock.now();
+    for transaction in cancelled {
+        let wait_ms = (now - transaction.created_at).num_milliseconds().max(0) as u64;
+        state.events.publish(
+            QueueEvent::new(
+                QueueEventKind::Cancelled,
+                transaction.id.to_string(),
+                account_id,
+                transaction.priority,
+                now.timestamp_millis().max(0) as u64,
+            )
+            .with_wait_ms(wait_ms),
+        );
+    }
+}
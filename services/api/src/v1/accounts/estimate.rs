@@ -0,0 +1,58 @@
+use crate::{
+    errors::AppResult,
+    rate_limit::{peek_submit_limit, submit_cost, submit_limit, LimitSource},
+    AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use redis_cache::{MAX_PRIORITY, MIN_PRIORITY};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct EstimateQuery {
+    pub priority: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubmitEstimate {
+    pub account_id: String,
+    /// The priority the cost is charged for, after clamping
+    pub priority: i32,
+    /// Share of the submit limit one submit at this priority uses
+    pub cost: u32,
+    pub limit: u32,
+    pub window_seconds: u64,
+    /// Where the limit came from
+    pub source: LimitSource,
+    /// Limit left in the current window
+    pub remaining: u32,
+    /// Submits at this priority that still fit in the current window
+    pub submits_remaining: u32,
+}
+
+/// What a submit at a given priority would cost the account, without
+/// counting one
+pub async fn handler(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+    Query(query): Query<EstimateQuery>,
+) -> AppResult<Json<SubmitEstimate>> {
+    let priority = query.priority.unwrap_or(0).clamp(MIN_PRIORITY, MAX_PRIORITY);
+    let cost = submit_cost(&state, Some(priority), None);
+    let limit = submit_limit(&state, &account_id).await;
+    // Peeked with the account's strategy, so a token bucket reports its tokens
+    let remaining = peek_submit_limit(&state, &account_id, &limit).await?.remaining;
+
+    Ok(Json(SubmitEstimate {
+        account_id,
+        priority,
+        cost,
+        limit: limit.max_requests,
+        window_seconds: limit.window_seconds,
+        source: limit.source,
+        remaining,
+        submits_remaining: remaining / cost,
+    }))
+}
@@ -0,0 +1,76 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{DatabaseConnection, ValidatedJson},
+};
+use axum::{extract::Path, http::StatusCode, Json};
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{LimitChangeRequest, NewLimitChangeRequest};
+use postgres_models::schema::limit_change_requests;
+use serde::Deserialize;
+
+/// Longest justification accepted, in characters
+pub const MAX_JUSTIFICATION_CHARS: usize = 2000;
+
+/// Error code of the 409 sent when the account already has an open request
+pub const LIMIT_REQUEST_PENDING: &str = "limit_request_pending";
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLimitRequest {
+    pub requested_max_requests: i32,
+    pub justification: String,
+}
+
+/// Ask for a higher submit limit. An account has at most one pending request.
+pub async fn create(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+    ValidatedJson(request): ValidatedJson<CreateLimitRequest>,
+) -> AppResult<(StatusCode, Json<LimitChangeRequest>)> {
+    if request.requested_max_requests <= 0 {
+        return Err(AppError::bad_request("requested_max_requests must be positive"));
+    }
+    let justification = request.justification.trim();
+    if justification.is_empty() {
+        return Err(AppError::bad_request("justification must not be empty"));
+    }
+    if justification.chars().count() > MAX_JUSTIFICATION_CHARS {
+        return Err(AppError::bad_request(format!(
+            "justification must be at most {} characters",
+            MAX_JUSTIFICATION_CHARS
+        )));
+    }
+
+    let new_request = NewLimitChangeRequest::new(account_id, request.requested_max_requests, justification.to_string());
+    // The one-pending-per-account index decides races between concurrent requests
+    let created = diesel::insert_into(limit_change_requests::table)
+        .values(&new_request)
+        .returning(LimitChangeRequest::as_returning())
+        .get_result(&mut db_conn)
+        .await
+        .map_err(|err| match err {
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => AppError::new(
+                StatusCode::CONFLICT,
+                "Account already has a pending limit request",
+            )
+            .with_code(LIMIT_REQUEST_PENDING),
+            err => err.into(),
+        })?;
+
+    tracing::info!(
+        account_id = %created.account_id,
+        request_id = %created.id,
+        requested_max_requests = created.requested_max_requests,
+        "Limit change requested"
+    );
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+/// List an account's limit requests, newest first
+pub async fn list(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+) -> AppResult<Json<Vec<LimitChangeRequest>>> {
+    Ok(Json(LimitChangeRequest::list_for_account(&mut db_conn, &account_id).await?))
+}
@@ -0,0 +1,29 @@
+use crate::rate_limit::layer::{by_path_param, read_limit};
+use axum::{
+    handler::Handler,
+    routing::{delete, get, post},
+    Router,
+};
+
+pub mod cancel;
+mod estimate;
+mod limit_requests;
+mod queue;
+mod webhooks;
+
+pub fn router(state: &crate::AppState) -> Router<crate::AppState> {
+    let list_limit = read_limit(state).key_by(by_path_param("account_id"));
+    let estimate_limit = read_limit(state).key_by(by_path_param("account_id"));
+    let queue_limit = read_limit(state).key_by(by_path_param("account_id"));
+    Router::new()
+        .route("/:account_id/estimate", get(estimate::handler.layer(estimate_limit)))
+        .route("/:account_id/queue", get(queue::handler.layer(queue_limit)))
+        .route("/:account_id/transactions/cancel-all", post(cancel::cancel_all))
+        .route("/:account_id/webhooks", get(webhooks::list.layer(list_limit)).post(webhooks::create))
+        .route("/:account_id/webhooks/:webhook_id", delete(webhooks::delete))
+        .route("/:account_id/webhooks/:webhook_id/test", post(webhooks::test_fire))
+        .route(
+            "/:account_id/limit-requests",
+            get(limit_requests::list).post(limit_requests::create),
+        )
+}
@@ -0,0 +1,114 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::DatabaseConnection,
+    rate_limit::{peek_submit_limit, submit_limit},
+    AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use queue_engine::submit::validate_account_id;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Rows per page when the caller does not pass `limit`
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+/// Statuses of transactions that have not finished yet
+const IN_FLIGHT: [TransactionStatus; 3] = [
+    TransactionStatus::Pending,
+    TransactionStatus::Processing,
+    TransactionStatus::Retry,
+];
+
+#[derive(Debug, Deserialize)]
+pub struct AccountQueueQuery {
+    /// List only this in-flight status
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountQueueResponse {
+    pub account_id: String,
+    /// In-flight transactions per status, whatever the page and filter
+    pub counts: BTreeMap<&'static str, i64>,
+    pub rate_limit: RateLimitUsage,
+    pub limit: i64,
+    pub offset: i64,
+    /// Offset of the next page, if there is one
+    pub next_offset: Option<i64>,
+    /// Highest priority first, oldest first within a priority
+    pub transactions: Vec<TransactionQueue>,
+}
+
+/// Where the account stands against its submit limit
+#[derive(Debug, Serialize)]
+pub struct RateLimitUsage {
+    pub limit: u32,
+    pub window_seconds: u64,
+    pub remaining: u32,
+    /// Unix seconds
+    pub reset_at: u64,
+}
+
+/// What an account has in flight: its pending, processing and retrying
+/// transactions a page at a time, how many it has in each status, and how
+/// much of its submit limit is left. The limit is peeked, so looking uses
+/// none of it.
+pub async fn handler(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+    Query(query): Query<AccountQueueQuery>,
+) -> AppResult<Json<AccountQueueResponse>> {
+    validate_account_id(&account_id).map_err(AppError::bad_request)?;
+    let statuses = match query.status.as_deref() {
+        None => IN_FLIGHT.to_vec(),
+        Some(status) => vec![IN_FLIGHT
+            .into_iter()
+            .find(|in_flight| in_flight.as_str() == status)
+            .ok_or_else(|| AppError::bad_request("status must be one of pending, processing or retry"))?],
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::bad_request(format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    let offset = query.offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(AppError::bad_request("offset must not be negative"));
+    }
+
+    // One extra row tells whether another page exists
+    let mut transactions =
+        TransactionQueue::list_for_account(&mut db_conn, &account_id, &statuses, limit + 1, offset).await?;
+    let has_more = transactions.len() as i64 > limit;
+    transactions.truncate(limit as usize);
+    let stored = TransactionQueue::count_for_account_by_status(&mut db_conn, &account_id, &IN_FLIGHT).await?;
+    let counts = IN_FLIGHT
+        .iter()
+        .map(|status| (status.as_str(), stored.get(status.as_str()).copied().unwrap_or(0)))
+        .collect();
+
+    let submit = submit_limit(&state, &account_id).await;
+    let usage = peek_submit_limit(&state, &account_id, &submit).await?;
+
+    Ok(Json(AccountQueueResponse {
+        account_id,
+        counts,
+        rate_limit: RateLimitUsage {
+            limit: submit.max_requests,
+            window_seconds: submit.window_seconds,
+            remaining: usage.remaining,
+            reset_at: usage.reset_at,
+        },
+        limit,
+        offset,
+        next_offset: has_more.then_some(offset + limit),
+        transactions,
+    }))
+}
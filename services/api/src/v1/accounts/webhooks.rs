@@ -0,0 +1,162 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{DatabaseConnection, ValidatedJson},
+    webhooks::{self, DeliveryResult, TEST_EVENT_TYPE, WEBHOOK_EVENT_TYPES},
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{NewWebhook, Webhook};
+use postgres_models::schema::webhooks as webhooks_table;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub event_types: Vec<String>,
+}
+
+/// Creation response, the only place the signing secret is ever returned
+#[derive(Debug, Serialize)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+/// Register a webhook endpoint for an account
+pub async fn create(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+    ValidatedJson(request): ValidatedJson<CreateWebhookRequest>,
+) -> AppResult<(StatusCode, Json<CreateWebhookResponse>)> {
+    validate_url(&request.url).map_err(AppError::bad_request)?;
+    let event_types = validate_event_types(request.event_types).map_err(AppError::bad_request)?;
+
+    let existing: i64 = webhooks_table::table
+        .filter(webhooks_table::account_id.eq(&account_id))
+        .count()
+        .get_result(&mut db_conn)
+        .await?;
+    let max_webhooks = state.config.max_webhooks_per_account;
+    if existing >= max_webhooks {
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            format!("Account already has the maximum of {} webhooks", max_webhooks),
+        ));
+    }
+
+    let secret = webhooks::generate_secret();
+    let webhook = diesel::insert_into(webhooks_table::table)
+        .values(&NewWebhook::new(account_id, request.url, event_types, secret.clone()))
+        .returning(Webhook::as_returning())
+        .get_result(&mut db_conn)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(CreateWebhookResponse { webhook, secret })))
+}
+
+/// List an account's webhooks, without their secrets
+pub async fn list(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+) -> AppResult<Json<Vec<Webhook>>> {
+    let webhooks = webhooks_table::table
+        .filter(webhooks_table::account_id.eq(&account_id))
+        .order(webhooks_table::created_at.asc())
+        .select(Webhook::as_select())
+        .load(&mut db_conn)
+        .await?;
+
+    Ok(Json(webhooks))
+}
+
+pub async fn delete(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path((account_id, webhook_id)): Path<(String, Uuid)>,
+) -> AppResult<StatusCode> {
+    let deleted = diesel::delete(
+        webhooks_table::table
+            .filter(webhooks_table::id.eq(webhook_id))
+            .filter(webhooks_table::account_id.eq(&account_id)),
+    )
+    .execute(&mut db_conn)
+    .await?;
+
+    if deleted == 0 {
+        return Err(AppError::not_found("Webhook not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Send a signed sample event to the endpoint and report the downstream response
+pub async fn test_fire(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path((account_id, webhook_id)): Path<(String, Uuid)>,
+) -> AppResult<Json<DeliveryResult>> {
+    let webhook = webhooks_table::table
+        .filter(webhooks_table::id.eq(webhook_id))
+        .filter(webhooks_table::account_id.eq(&account_id))
+        .select(Webhook::as_select())
+        .first(&mut db_conn)
+        .await
+        .optional()?
+        .ok_or_else(|| AppError::not_found("Webhook not found"))?;
+    // Don't hold a pooled connection across the outbound request
+    drop(db_conn);
+
+    let payload = json!({
+        "id": Uuid::new_v4(),
+        "type": TEST_EVENT_TYPE,
+        "created_at": chrono::Utc::now(),
+        "data": {
+            "webhook_id": webhook.id,
+            "account_id": webhook.account_id,
+        },
+    });
+
+    let result = webhooks::deliver(
+        &webhook.url,
+        &webhook.secret,
+        TEST_EVENT_TYPE,
+        &payload,
+        Duration::from_millis(state.config.webhook_timeout_ms),
+    )
+    .await;
+
+    Ok(Json(result))
+}
+
+fn validate_url(raw: &str) -> Result<(), String> {
+    let url = url::Url::parse(raw).map_err(|_| "url must be a valid absolute URL".to_string())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err("url must be an http or https URL".to_string());
+    }
+    Ok(())
+}
+
+fn validate_event_types(mut event_types: Vec<String>) -> Result<Vec<String>, String> {
+    if event_types.is_empty() {
+        return Err("event_types must not be empty".to_string());
+    }
+    if let Some(unknown) = event_types.iter().find(|t| !WEBHOOK_EVENT_TYPES.contains(&t.as_str())) {
+        return Err(format!(
+            "Unknown event type {:?}, expected one of: {}",
+            unknown,
+            WEBHOOK_EVENT_TYPES.join(", ")
+        ));
+    }
+    event_types.sort();
+    event_types.dedup();
+    Ok(event_types)
+}
@@ -0,0 +1,47 @@
+use crate::{
+    errors::{AppError, AppResult},
+    AppState, TRANSACTION_QUEUE,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use redis_cache::{account_index::MAX_ACCOUNT_ITEMS, AccountQueueItem};
+use serde::{Deserialize, Serialize};
+
+/// Items listed when the caller does not pass `limit`
+pub const DEFAULT_ACCOUNT_QUEUE_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct AccountQueueQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountQueueResponse {
+    pub account_id: String,
+    /// In the order they will be processed
+    pub items: Vec<AccountQueueItem>,
+}
+
+/// What an account has in the transaction queue right now, with each item's
+/// rank in the whole queue
+pub async fn list(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+    Query(query): Query<AccountQueueQuery>,
+) -> AppResult<Json<AccountQueueResponse>> {
+    let limit = query.limit.unwrap_or(DEFAULT_ACCOUNT_QUEUE_LIMIT);
+    if !(1..=MAX_ACCOUNT_ITEMS).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_ACCOUNT_ITEMS
+        )));
+    }
+
+    let items = state
+        .queue_manager()
+        .list_account_items(&TRANSACTION_QUEUE, &account_id, limit)
+        .await?;
+    Ok(Json(AccountQueueResponse { account_id, items }))
+}
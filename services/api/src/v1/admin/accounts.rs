@@ -0,0 +1,68 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{DatabaseConnection, ValidatedJson},
+    rate_limit::invalidate_cached_limit,
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use postgres_models::models::{upgrade_account_tier, AccountTier, TierChange};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeTierRequest {
+    pub tier: String,
+}
+
+/// Pause processing of an account's transactions. Submissions are still accepted.
+pub async fn pause(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> AppResult<StatusCode> {
+    state.queue_manager().pause_account(&account_id).await?;
+    tracing::info!(%account_id, "Account paused");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Resume processing of a paused account
+pub async fn resume(
+    State(state): State<AppState>,
+    Path(account_id): Path<String>,
+) -> AppResult<StatusCode> {
+    if state.queue_manager().resume_account(&account_id).await? {
+        tracing::info!(%account_id, "Account resumed");
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Move an account to another tier, rewriting its tier-derived limits from
+/// the tier's template. Cached limits and flags are dropped once the change
+/// commits, so the account's next submit is checked against the new limits.
+pub async fn change_tier(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+    ValidatedJson(request): ValidatedJson<ChangeTierRequest>,
+) -> AppResult<Json<TierChange>> {
+    let Some(tier) = AccountTier::parse(&request.tier) else {
+        let known: Vec<_> = AccountTier::ALL.iter().map(AccountTier::as_str).collect();
+        return Err(AppError::bad_request(format!("tier must be one of: {}", known.join(", "))));
+    };
+
+    let change = upgrade_account_tier(&mut db_conn, &account_id, tier).await?;
+    for limit in &change.limits {
+        invalidate_cached_limit(&state, &account_id, &limit.limit_type).await;
+    }
+    state.flags.invalidate();
+
+    tracing::info!(
+        %account_id,
+        previous_tier = ?change.previous_tier,
+        tier = %change.tier,
+        "Account tier changed"
+    );
+    Ok(Json(change))
+}
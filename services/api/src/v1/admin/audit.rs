@@ -0,0 +1,74 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::DatabaseConnection,
+};
+use axum::{
+    extract::Query,
+    http::{HeaderMap, HeaderValue},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use postgres_models::models::{AuditCursor, AuditLog, AuditLogFilter};
+use serde::{Deserialize, Serialize};
+
+/// Entries per page when the caller does not pass `limit`
+pub const DEFAULT_AUDIT_PAGE_SIZE: i64 = 100;
+pub const MAX_AUDIT_PAGE_SIZE: i64 = 500;
+
+/// "true" when entries remain past this page
+pub const HAS_MORE_HEADER: &str = "X-Has-More";
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub event_type: Option<String>,
+    pub account_id: Option<String>,
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditLog>,
+    /// Pass as `cursor` with the same filters to get the next page
+    pub next_cursor: Option<String>,
+}
+
+/// Audit entries, newest first, filtered by time range, event type and account
+pub async fn list(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Query(query): Query<AuditLogQuery>,
+) -> AppResult<(HeaderMap, Json<AuditLogPage>)> {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_PAGE_SIZE);
+    if !(1..=MAX_AUDIT_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_AUDIT_PAGE_SIZE
+        )));
+    }
+    let after = match query.cursor.as_deref() {
+        Some(cursor) => Some(AuditCursor::decode(cursor).ok_or_else(|| AppError::bad_request("Invalid cursor"))?),
+        None => None,
+    };
+
+    let filter = AuditLogFilter {
+        since: query.since,
+        until: query.until,
+        event_type: query.event_type,
+        account_id: query.account_id,
+    };
+    // One extra entry tells whether another page exists
+    let mut entries = AuditLog::list(&mut db_conn, &filter, after, limit + 1).await?;
+    let has_more = entries.len() as i64 > limit;
+    entries.truncate(limit as usize);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(HAS_MORE_HEADER, HeaderValue::from_static(if has_more { "true" } else { "false" }));
+    let next_cursor = entries
+        .last()
+        .filter(|_| has_more)
+        .map(|entry| AuditCursor::after(entry).encode());
+    Ok((headers, Json(AuditLogPage { entries, next_cursor })))
+}
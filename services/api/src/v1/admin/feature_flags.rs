@@ -0,0 +1,73 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::ValidatedJson,
+    feature_flags::KNOWN_FLAGS,
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+pub struct SetFlagRequest {
+    pub percentage: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FlagState {
+    pub flag: String,
+    pub percentage: u32,
+}
+
+/// Rollout percentage of every known flag, 0 for flags never set
+pub async fn list(State(state): State<AppState>) -> AppResult<Json<BTreeMap<String, u32>>> {
+    let stored = state.flags.load().await?;
+    let flags = KNOWN_FLAGS
+        .iter()
+        .map(|flag| (flag.to_string(), stored.get(*flag).copied().unwrap_or(0)))
+        .collect();
+    Ok(Json(flags))
+}
+
+/// Set the share of accounts, 0 to 100, a flag is enabled for
+pub async fn set(
+    State(state): State<AppState>,
+    Path(flag): Path<String>,
+    ValidatedJson(request): ValidatedJson<SetFlagRequest>,
+) -> AppResult<Json<FlagState>> {
+    check_known(&flag).map_err(AppError::not_found)?;
+    if request.percentage > 100 {
+        return Err(AppError::bad_request("percentage must be between 0 and 100"));
+    }
+
+    state.flags.set(&flag, request.percentage).await?;
+    tracing::info!(%flag, percentage = request.percentage, "Feature flag updated");
+    Ok(Json(FlagState {
+        flag,
+        percentage: request.percentage,
+    }))
+}
+
+/// Turn a flag off everywhere
+pub async fn clear(State(state): State<AppState>, Path(flag): Path<String>) -> AppResult<StatusCode> {
+    check_known(&flag).map_err(AppError::not_found)?;
+    if state.flags.clear(&flag).await? {
+        tracing::info!(%flag, "Feature flag cleared");
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn check_known(flag: &str) -> Result<(), String> {
+    if !KNOWN_FLAGS.contains(&flag) {
+        return Err(format!(
+            "Unknown feature flag {:?}, expected one of: {}",
+            flag,
+            KNOWN_FLAGS.join(", ")
+        ));
+    }
+    Ok(())
+}
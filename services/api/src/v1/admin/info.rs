@@ -0,0 +1,25 @@
+use crate::{feature_flags::KNOWN_FLAGS, runtime_info::RuntimeInfo, AppState};
+use axum::{extract::State, Json};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+pub struct InfoResponse {
+    #[serde(flatten)]
+    pub info: RuntimeInfo,
+    /// Rollout percentage of every known flag, as this instance applies them
+    pub feature_flags: BTreeMap<&'static str, u32>,
+}
+
+/// What this instance is running: build, effective config and routes, plus
+/// the feature flag percentages it currently acts on
+pub async fn show(State(state): State<AppState>) -> Json<InfoResponse> {
+    let mut feature_flags = BTreeMap::new();
+    for flag in KNOWN_FLAGS {
+        feature_flags.insert(flag, state.flags.percentage(flag).await);
+    }
+    Json(InfoResponse {
+        info: RuntimeInfo::clone(&state.info),
+        feature_flags,
+    })
+}
@@ -0,0 +1,87 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{AdminIdentity, DatabaseConnection},
+    rate_limit::{invalidate_cached_limit, SUBMIT_LIMIT_TYPE, SUBMIT_WINDOW_SECONDS},
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use postgres_models::models::{LimitChangeRequest, RateLimit};
+use diesel_async::AsyncPgConnection;
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct ApprovedLimitRequest {
+    pub request: LimitChangeRequest,
+    pub limit: RateLimit,
+}
+
+/// Limit requests waiting for a decision, oldest first
+pub async fn list_pending(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+) -> AppResult<Json<Vec<LimitChangeRequest>>> {
+    Ok(Json(LimitChangeRequest::list_pending(&mut db_conn).await?))
+}
+
+/// Approve a pending request, raising the account's submit limit to the
+/// requested maximum. The request and the limit are updated in one
+/// transaction; the cached limit is dropped once it commits.
+pub async fn approve(
+    State(state): State<AppState>,
+    AdminIdentity(actor): AdminIdentity,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(request_id): Path<Uuid>,
+) -> AppResult<Json<ApprovedLimitRequest>> {
+    let Some((request, limit)) = LimitChangeRequest::approve(
+        &mut db_conn,
+        request_id,
+        &actor,
+        SUBMIT_LIMIT_TYPE,
+        SUBMIT_WINDOW_SECONDS as i32,
+    )
+    .await?
+    else {
+        return Err(not_pending(&mut db_conn, request_id).await);
+    };
+    invalidate_cached_limit(&state, &limit.account_id, &limit.limit_type).await;
+
+    tracing::info!(
+        account_id = %request.account_id,
+        %request_id,
+        max_requests = limit.max_requests,
+        decided_by = %actor,
+        "Limit request approved"
+    );
+    Ok(Json(ApprovedLimitRequest { request, limit }))
+}
+
+/// Deny a pending request, leaving the account's limit unchanged
+pub async fn deny(
+    AdminIdentity(actor): AdminIdentity,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(request_id): Path<Uuid>,
+) -> AppResult<Json<LimitChangeRequest>> {
+    let Some(request) = LimitChangeRequest::deny(&mut db_conn, request_id, &actor).await? else {
+        return Err(not_pending(&mut db_conn, request_id).await);
+    };
+
+    tracing::info!(account_id = %request.account_id, %request_id, decided_by = %actor, "Limit request denied");
+    Ok(Json(request))
+}
+
+/// Error for a decision on a request that is not pending: 404 if it does
+/// not exist, 409 if it was already decided
+async fn not_pending(conn: &mut AsyncPgConnection, request_id: Uuid) -> AppError {
+    match LimitChangeRequest::find(conn, request_id).await {
+        Ok(Some(request)) => AppError::new(
+            StatusCode::CONFLICT,
+            format!("Limit request was already {}", request.status),
+        ),
+        Ok(None) => AppError::not_found("Limit request not found"),
+        Err(err) => err.into(),
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{DatabaseConnection, ValidatedJson},
+    pending::MAX_PENDING_TYPE,
+    rate_limit::{invalidate_cached_limit, SOFT_LIMIT_PCT_TYPE},
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{NewRateLimit, RateLimit};
+use postgres_models::schema::rate_limits;
+use redis_cache::{RateLimitStrategy, MAX_WINDOW_SECONDS};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertLimitRequest {
+    pub max_requests: i32,
+    pub window_seconds: i32,
+    /// `sliding_window` (the default) or `token_bucket`
+    pub strategy: Option<String>,
+}
+
+/// List every configured limit for an account
+pub async fn list(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+) -> AppResult<Json<Vec<RateLimit>>> {
+    let limits = rate_limits::table
+        .filter(rate_limits::account_id.eq(&account_id))
+        .order(rate_limits::limit_type.asc())
+        .select(RateLimit::as_select())
+        .load(&mut db_conn)
+        .await?;
+
+    Ok(Json(limits))
+}
+
+/// Create or replace the limit of a given type for an account
+pub async fn upsert(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path((account_id, limit_type)): Path<(String, String)>,
+    ValidatedJson(request): ValidatedJson<UpsertLimitRequest>,
+) -> AppResult<Json<RateLimit>> {
+    if request.max_requests < 0 {
+        return Err(AppError::bad_request("max_requests must not be negative"));
+    }
+    if request.window_seconds <= 0 || request.window_seconds as u64 > MAX_WINDOW_SECONDS {
+        return Err(AppError::bad_request(format!(
+            "window_seconds must be between 1 and {}",
+            MAX_WINDOW_SECONDS
+        )));
+    }
+    if limit_type == SOFT_LIMIT_PCT_TYPE && !(1..=100).contains(&request.max_requests) {
+        return Err(AppError::bad_request("max_requests of a soft_pct limit is a percentage between 1 and 100"));
+    }
+    if limit_type == MAX_PENDING_TYPE && request.max_requests < 1 {
+        return Err(AppError::bad_request("max_requests of a max_pending limit must be at least 1"));
+    }
+
+    let strategy = match request.strategy.as_deref() {
+        None => RateLimitStrategy::default(),
+        Some(strategy) => RateLimitStrategy::parse(strategy)
+            .ok_or_else(|| AppError::bad_request("strategy must be sliding_window or token_bucket"))?,
+    };
+
+    let new_limit = NewRateLimit::new(account_id, limit_type, request.max_requests, request.window_seconds)
+        .with_strategy(strategy.as_str());
+
+    let limit = diesel::insert_into(rate_limits::table)
+        .values(&new_limit)
+        .on_conflict((rate_limits::account_id, rate_limits::limit_type))
+        .do_update()
+        .set((
+            rate_limits::max_requests.eq(excluded(rate_limits::max_requests)),
+            rate_limits::window_seconds.eq(excluded(rate_limits::window_seconds)),
+            rate_limits::strategy.eq(excluded(rate_limits::strategy)),
+        ))
+        .returning(RateLimit::as_returning())
+        .get_result(&mut db_conn)
+        .await?;
+    invalidate_cached_limit(&state, &limit.account_id, &limit.limit_type).await;
+
+    tracing::info!(
+        account_id = %limit.account_id,
+        limit_type = %limit.limit_type,
+        max_requests = limit.max_requests,
+        window_seconds = limit.window_seconds,
+        strategy = %limit.strategy,
+        "Rate limit updated"
+    );
+    Ok(Json(limit))
+}
+
+/// Remove a configured limit so the account falls back to the defaults
+pub async fn delete(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path((account_id, limit_type)): Path<(String, String)>,
+) -> AppResult<StatusCode> {
+    let deleted = diesel::delete(
+        rate_limits::table
+            .filter(rate_limits::account_id.eq(&account_id))
+            .filter(rate_limits::limit_type.eq(&limit_type)),
+    )
+    .execute(&mut db_conn)
+    .await?;
+
+    if deleted == 0 {
+        return Err(AppError::not_found("Rate limit not found"));
+    }
+    invalidate_cached_limit(&state, &account_id, &limit_type).await;
+    Ok(StatusCode::NO_CONTENT)
+}
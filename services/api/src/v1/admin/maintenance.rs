@@ -0,0 +1,37 @@
+use crate::{
+    errors::{AppError, AppResult},
+    AppState,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use redis_cache::ExpiryRepair;
+use serde::Deserialize;
+
+/// Expiry given to rate limit keys that have none when the caller does not
+/// pass `ttl_seconds`; longer than any window in use
+pub const DEFAULT_REPAIR_TTL_SECONDS: u64 = 86_400;
+
+#[derive(Debug, Deserialize)]
+pub struct RepairExpiryQuery {
+    pub ttl_seconds: Option<u64>,
+}
+
+/// Give every rate limit key left without an expiry one, e.g. after a load
+/// test sprayed one-off account ids at a build that skipped it on rejection
+pub async fn repair_rate_limit_expiry(
+    State(state): State<AppState>,
+    Query(query): Query<RepairExpiryQuery>,
+) -> AppResult<Json<ExpiryRepair>> {
+    let ttl_seconds = query.ttl_seconds.unwrap_or(DEFAULT_REPAIR_TTL_SECONDS);
+    if ttl_seconds == 0 {
+        return Err(AppError::bad_request("ttl_seconds must be positive"));
+    }
+
+    let repair = state.rate_limiter().repair_missing_expiry(ttl_seconds).await?;
+    if repair.repaired > 0 {
+        tracing::warn!(repaired = repair.repaired, scanned = repair.scanned, "Expired rate limit keys that had no TTL");
+    }
+    Ok(Json(repair))
+}
@@ -0,0 +1,161 @@
+use crate::{
+    errors::AppError,
+    extractors::{admin::client_ip, AdminIdentity},
+    rate_limit::{layer::path_param, rate_limit_headers},
+    AppState,
+};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post, put},
+    Router,
+};
+use diesel_async::RunQueryDsl;
+use postgres_models::models::NewAuditLog;
+use postgres_models::schema::audit_log;
+
+mod account_queue;
+mod accounts;
+mod audit;
+mod feature_flags;
+mod info;
+mod limit_requests;
+mod limits;
+mod maintenance;
+mod payload_schemas;
+mod quarantine;
+mod queue_memory;
+mod readiness;
+mod search;
+mod stale_processing;
+mod transition;
+
+/// Limiter scope for admin calls, kept apart from customer submit limits
+pub const ADMIN_RATE_LIMIT_SCOPE: &str = "admin";
+
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/accounts/:account_id/limits", get(limits::list))
+        .route("/audit-log", get(audit::list))
+        .route(
+            "/accounts/:account_id/limits/:limit_type",
+            put(limits::upsert).delete(limits::delete),
+        )
+        .route(
+            "/accounts/:account_id/pause",
+            put(accounts::pause).delete(accounts::resume),
+        )
+        .route(
+            "/accounts/:account_id/payload-schema",
+            get(payload_schemas::show).put(payload_schemas::put).delete(payload_schemas::delete),
+        )
+        .route("/accounts/:account_id/queue", get(account_queue::list))
+        .route("/accounts/:account_id/tier", post(accounts::change_tier))
+        .route("/feature-flags", get(feature_flags::list))
+        .route(
+            "/feature-flags/:flag",
+            put(feature_flags::set).delete(feature_flags::clear),
+        )
+        .route("/info", get(info::show))
+        .route("/limit-requests", get(limit_requests::list_pending))
+        .route("/limit-requests/:request_id/approve", post(limit_requests::approve))
+        .route("/limit-requests/:request_id/deny", post(limit_requests::deny))
+        .route(
+            "/maintenance/rate-limit-expiry",
+            post(maintenance::repair_rate_limit_expiry),
+        )
+        .route("/queue/memory", get(queue_memory::report))
+        .route("/queue/quarantine", get(quarantine::list))
+        .route("/queue/quarantine/:id", delete(quarantine::delete))
+        .route("/readiness", post(readiness::set))
+        .route("/stale-processing", get(stale_processing::list))
+        .route("/transactions/search", get(search::search))
+        .route("/transactions/:id/status", patch(transition::handler))
+        .layer(middleware::from_fn_with_state(state, admin_guard))
+}
+
+/// Authenticate, rate limit and audit every admin call.
+///
+/// The audit entry is written regardless of outcome, including rejected
+/// and rate limited calls, so a runaway script is visible after the fact.
+async fn admin_guard(
+    State(state): State<AppState>,
+    identity: Result<AdminIdentity, AppError>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let event_type = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|route| format!("{} {}", method, route.as_str()));
+    let account_id = path_param(&request, "account_id");
+
+    let (actor, response) = match identity {
+        Ok(AdminIdentity(actor)) => {
+            let response = match check_admin_rate_limit(&state, &actor).await {
+                Ok(()) => next.run(request).await,
+                Err(err) => err.into_response(),
+            };
+            (actor, response)
+        }
+        Err(err) => {
+            let actor = client_ip(request.extensions())
+                .map(|ip| format!("ip:{}", ip))
+                .unwrap_or_else(|| "anonymous".to_string());
+            (actor, err.into_response())
+        }
+    };
+
+    let entry = NewAuditLog::new(actor, method, path, response.status().as_u16() as i32)
+        .with_event_type(event_type)
+        .with_account_id(account_id);
+    record_audit(&state, entry).await;
+    response
+}
+
+async fn check_admin_rate_limit(state: &AppState, actor: &str) -> Result<(), AppError> {
+    let limit = state.config.admin_rate_limit;
+    let window_seconds = state.config.admin_rate_window_seconds;
+
+    let result = match &state.local_limiter {
+        Some(limiter) => limiter.check_scoped(ADMIN_RATE_LIMIT_SCOPE, actor, 1, limit, window_seconds),
+        None => state
+            .rate_limiter()
+            .check_scoped_rate_limit(ADMIN_RATE_LIMIT_SCOPE, actor, limit, window_seconds)
+            .await
+            .map_err(|e| {
+                tracing::error!("Admin rate limit check failed: {}", e);
+                AppError::internal_server_error("Failed to check rate limit")
+            })?,
+    };
+
+    if !result.allowed {
+        tracing::warn!(actor, "Admin rate limit exceeded");
+        return Err(AppError::too_many_requests("Admin rate limit exceeded")
+            .with_headers(rate_limit_headers(limit, &result))
+            .with_retry(limit, &result));
+    }
+    Ok(())
+}
+
+/// Audit failures are logged but never change the response
+async fn record_audit(state: &AppState, entry: NewAuditLog) {
+    let mut conn = match state.db_pool.get().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            tracing::error!(?entry, "Failed to acquire connection for audit entry: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = diesel::insert_into(audit_log::table)
+        .values(&entry)
+        .execute(&mut conn)
+        .await
+    {
+        tracing::error!(?entry, "Failed to write audit entry: {}", e);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{DatabaseConnection, ValidatedJson},
+    payload_schema::compile,
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use postgres_models::models::{NewPayloadSchema, PayloadSchema};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct PutPayloadSchemaRequest {
+    pub schema: serde_json::Value,
+    /// Whether submit enforces the schema; registering one alone changes nothing
+    pub enabled: bool,
+}
+
+/// The account's payload schema and whether it is enforced
+pub async fn show(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+) -> AppResult<Json<PayloadSchema>> {
+    PayloadSchema::find(&mut db_conn, &account_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::not_found("Payload schema not found"))
+}
+
+/// Register or replace the account's payload schema, and turn its
+/// enforcement on or off. Schemas that do not compile are refused.
+pub async fn put(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+    ValidatedJson(request): ValidatedJson<PutPayloadSchemaRequest>,
+) -> AppResult<Json<PayloadSchema>> {
+    if let Err(e) = compile(&request.schema) {
+        return Err(AppError::bad_request(format!("schema is not a usable JSON Schema: {}", e)));
+    }
+
+    let new_schema = NewPayloadSchema {
+        account_id,
+        schema: request.schema,
+        enabled: request.enabled,
+    };
+    let schema = PayloadSchema::upsert(&mut db_conn, &new_schema).await?;
+    state.payload_schemas.invalidate(&schema.account_id);
+
+    tracing::info!(account_id = %schema.account_id, enabled = schema.enabled, "Payload schema updated");
+    Ok(Json(schema))
+}
+
+/// Remove the account's payload schema, so its payloads are no longer checked
+pub async fn delete(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Path(account_id): Path<String>,
+) -> AppResult<StatusCode> {
+    if !PayloadSchema::delete(&mut db_conn, &account_id).await? {
+        return Err(AppError::not_found("Payload schema not found"));
+    }
+    state.payload_schemas.invalidate(&account_id);
+    tracing::info!(%account_id, "Payload schema removed");
+    Ok(StatusCode::NO_CONTENT)
+}
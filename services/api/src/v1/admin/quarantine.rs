@@ -0,0 +1,45 @@
+use crate::{
+    errors::{AppError, AppResult},
+    AppState, TRANSACTION_QUEUE,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use redis_cache::{quarantine::MAX_QUARANTINED, QuarantinedMember};
+use serde::Deserialize;
+
+/// Members listed when the caller does not pass `limit`
+pub const DEFAULT_QUARANTINE_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct QuarantineQuery {
+    pub limit: Option<usize>,
+}
+
+/// Members of the transaction queue that could not be decoded, newest first
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<QuarantineQuery>,
+) -> AppResult<Json<Vec<QuarantinedMember>>> {
+    let limit = query.limit.unwrap_or(DEFAULT_QUARANTINE_LIMIT);
+    if !(1..=MAX_QUARANTINED).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_QUARANTINED
+        )));
+    }
+
+    let members = state.queue_manager().quarantined(&TRANSACTION_QUEUE, limit).await?;
+    Ok(Json(members))
+}
+
+/// Drop a quarantined member for good
+pub async fn delete(State(state): State<AppState>, Path(id): Path<String>) -> AppResult<StatusCode> {
+    if !state.queue_manager().delete_quarantined(&TRANSACTION_QUEUE, &id).await? {
+        return Err(AppError::not_found(format!("Quarantined member {} not found", id)));
+    }
+    tracing::info!(%id, "Quarantined queue member deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,35 @@
+use crate::{
+    errors::{AppError, AppResult},
+    AppState, TRANSACTION_QUEUE,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use redis_cache::{memory::MAX_MEMORY_SAMPLE, MemoryReport};
+use serde::Deserialize;
+
+/// Members sampled when the caller does not pass `sample`
+pub const DEFAULT_MEMORY_SAMPLE: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct MemoryQuery {
+    pub sample: Option<usize>,
+}
+
+/// Estimate the transaction queue's Redis memory per account, largest first
+pub async fn report(
+    State(state): State<AppState>,
+    Query(query): Query<MemoryQuery>,
+) -> AppResult<Json<MemoryReport>> {
+    let sample = query.sample.unwrap_or(DEFAULT_MEMORY_SAMPLE);
+    if !(1..=MAX_MEMORY_SAMPLE).contains(&sample) {
+        return Err(AppError::bad_request(format!(
+            "sample must be between 1 and {}",
+            MAX_MEMORY_SAMPLE
+        )));
+    }
+
+    let report = state.queue_manager().memory_report(&TRANSACTION_QUEUE, sample).await?;
+    Ok(Json(report))
+}
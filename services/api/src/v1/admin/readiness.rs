@@ -0,0 +1,25 @@
+use crate::{
+    errors::AppResult,
+    extractors::ValidatedJson,
+    health::ReadinessReport,
+    AppState,
+};
+use axum::{extract::State, Json};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct SetReadinessRequest {
+    pub ready: bool,
+}
+
+/// Drain this instance, or put it back in rotation. Only `/health/ready`
+/// changes; requests that still arrive are served.
+pub async fn set(
+    State(state): State<AppState>,
+    ValidatedJson(request): ValidatedJson<SetReadinessRequest>,
+) -> AppResult<Json<ReadinessReport>> {
+    if state.readiness.set_ready(request.ready) != request.ready {
+        tracing::warn!(ready = request.ready, "Readiness set through the admin API");
+    }
+    Ok(Json(state.readiness.report()))
+}
@@ -0,0 +1,171 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::DatabaseConnection,
+};
+use axum::{extract::Query, Json};
+use chrono::{DateTime, Utc};
+use postgres_models::models::{TransactionQueue, TransactionSearch, TransactionStatus};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Rows per page when the caller does not pass `limit`
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+/// Deepest row a search may page to; past this the filters need narrowing
+pub const SEARCH_RESULT_CAP: i64 = 1000;
+
+/// Error code of searches without an account or payload filter
+pub const SEARCH_FILTER_REQUIRED: &str = "search_filter_required";
+
+const PAYLOAD_PARAM: &str = "payload";
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    pub count: usize,
+    pub limit: i64,
+    pub offset: i64,
+    /// Offset of the next page, if there are more matches within the cap
+    pub next_offset: Option<i64>,
+    pub transactions: Vec<TransactionQueue>,
+}
+
+#[derive(Debug)]
+struct Page {
+    limit: i64,
+    offset: i64,
+}
+
+/// Why the query parameters do not describe an acceptable search
+enum InvalidSearch {
+    FilterRequired,
+    Param(String),
+}
+
+impl From<InvalidSearch> for AppError {
+    fn from(err: InvalidSearch) -> Self {
+        match err {
+            InvalidSearch::FilterRequired => AppError::bad_request("Search needs an account_id or payload filter")
+                .with_code(SEARCH_FILTER_REQUIRED),
+            InvalidSearch::Param(message) => AppError::bad_request(message),
+        }
+    }
+}
+
+/// Search transactions for support, newest first.
+///
+/// Query parameters: `account_id`, `status`, `created_after` and
+/// `created_before` (RFC 3339), `limit`, `offset`, and payload filters.
+/// `payload.<path>=<text>` matches a string field, e.g. `payload.seed=abc` or
+/// `payload.meta.user=abc`; `payload=<json object>` matches typed values.
+/// Payload filters are combined into one jsonb containment query. Either an
+/// account or a payload filter is required so a search never scans the table.
+pub async fn search(
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Query(params): Query<Vec<(String, String)>>,
+) -> AppResult<Json<SearchResponse>> {
+    let (search, page) = parse_params(params)?;
+
+    // One extra row tells whether another page exists
+    let mut transactions = TransactionQueue::search(&mut db_conn, &search, page.limit + 1, page.offset).await?;
+    let has_more = transactions.len() as i64 > page.limit;
+    transactions.truncate(page.limit as usize);
+
+    let next_offset = page.offset + page.limit;
+    Ok(Json(SearchResponse {
+        count: transactions.len(),
+        limit: page.limit,
+        offset: page.offset,
+        next_offset: (has_more && next_offset < SEARCH_RESULT_CAP).then_some(next_offset),
+        transactions,
+    }))
+}
+
+fn parse_params(params: Vec<(String, String)>) -> Result<(TransactionSearch, Page), InvalidSearch> {
+    let mut search = TransactionSearch::default();
+    let mut payload = Map::new();
+    let mut page = Page {
+        limit: DEFAULT_PAGE_SIZE,
+        offset: 0,
+    };
+
+    for (name, value) in params {
+        match name.as_str() {
+            "account_id" => search.account_id = Some(value),
+            "status" => {
+                search.status = Some(
+                    TransactionStatus::parse(&value)
+                        .ok_or_else(|| InvalidSearch::Param(format!("Unknown status '{}'", value)))?,
+                )
+            }
+            "created_after" => search.created_after = Some(parse_time(&name, &value)?),
+            "created_before" => search.created_before = Some(parse_time(&name, &value)?),
+            "limit" => page.limit = parse_number(&name, &value)?,
+            "offset" => page.offset = parse_number(&name, &value)?,
+            PAYLOAD_PARAM => {
+                let Ok(Value::Object(fields)) = serde_json::from_str(&value) else {
+                    return Err(InvalidSearch::Param("payload must be a JSON object".to_string()));
+                };
+                for (field, value) in fields {
+                    merge(&mut payload, &[field.as_str()], value)?;
+                }
+            }
+            _ => match name.strip_prefix("payload.") {
+                Some(path) if path.split('.').all(|segment| !segment.is_empty()) => {
+                    merge(&mut payload, &path.split('.').collect::<Vec<_>>(), Value::String(value))?
+                }
+                _ => return Err(InvalidSearch::Param(format!("Unknown search parameter '{}'", name))),
+            },
+        }
+    }
+
+    if !payload.is_empty() {
+        search.payload = Some(Value::Object(payload));
+    }
+    if !search.is_selective() {
+        return Err(InvalidSearch::FilterRequired);
+    }
+    if !(1..=MAX_PAGE_SIZE).contains(&page.limit) {
+        return Err(InvalidSearch::Param(format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    if page.offset < 0 || page.offset >= SEARCH_RESULT_CAP {
+        return Err(InvalidSearch::Param(format!(
+            "offset must be between 0 and {}; narrow the search to see older matches",
+            SEARCH_RESULT_CAP - 1
+        )));
+    }
+    // The last page stops at the cap
+    page.limit = page.limit.min(SEARCH_RESULT_CAP - page.offset);
+
+    Ok((search, page))
+}
+
+/// Set `value` at `path` in `document`, creating objects along the way
+fn merge(document: &mut Map<String, Value>, path: &[&str], value: Value) -> Result<(), InvalidSearch> {
+    let (last, parents) = path.split_last().expect("payload paths are never empty");
+    let mut target = document;
+    for segment in parents {
+        let entry = target
+            .entry(segment.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        target = entry
+            .as_object_mut()
+            .ok_or_else(|| InvalidSearch::Param(format!("Conflicting payload filters on '{}'", segment)))?;
+    }
+    if target.insert(last.to_string(), value).is_some() {
+        return Err(InvalidSearch::Param(format!("Conflicting payload filters on '{}'", last)));
+    }
+    Ok(())
+}
+
+fn parse_time(name: &str, value: &str) -> Result<DateTime<Utc>, InvalidSearch> {
+    value
+        .parse()
+        .map_err(|_| InvalidSearch::Param(format!("{} must be an RFC 3339 timestamp", name)))
+}
+
+fn parse_number(name: &str, value: &str) -> Result<i64, InvalidSearch> {
+    value
+        .parse()
+        .map_err(|_| InvalidSearch::Param(format!("{} must be an integer", name)))
+}
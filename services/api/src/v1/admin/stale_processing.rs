@@ -0,0 +1,44 @@
+use crate::{
+    errors::AppResult,
+    extractors::DatabaseConnection,
+    stale_processing::{self, StaleTransaction},
+    AppState,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+pub struct StaleProcessingQuery {
+    /// Overrides the configured threshold
+    pub older_than_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StaleProcessingResponse {
+    pub threshold_seconds: u64,
+    pub count: usize,
+    pub transactions: Vec<StaleTransaction>,
+}
+
+/// List transactions stuck in "processing", oldest first
+pub async fn list(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Query(query): Query<StaleProcessingQuery>,
+) -> AppResult<Json<StaleProcessingResponse>> {
+    let threshold_seconds = query
+        .older_than_seconds
+        .unwrap_or(state.config.stale_processing.threshold_seconds);
+
+    let transactions = stale_processing::find(&mut db_conn, Duration::from_secs(threshold_seconds)).await?;
+
+    Ok(Json(StaleProcessingResponse {
+        threshold_seconds,
+        count: transactions.len(),
+        transactions,
+    }))
+}
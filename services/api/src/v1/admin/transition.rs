@@ -0,0 +1,144 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{DatabaseConnection, ValidatedJson, ValidatedPath},
+    v1::transactions::{cancel, status},
+    AppState, TRANSACTION_QUEUE,
+};
+use api_client::types::TransactionStatusResponse;
+use axum::{extract::State, http::StatusCode, Json};
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use redis_cache::QueueEnvelope;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+/// Error code of the 409 sent when the transaction's status cannot move to the one asked for
+pub const INVALID_STATUS_TRANSITION: &str = "invalid_status_transition";
+/// Error code of the 409 sent when a retry is asked for a transaction that has used all its retries
+pub const RETRIES_EXHAUSTED: &str = "retries_exhausted";
+/// Error code of the 409 sent when another writer moved the transaction first
+pub const STATUS_CONFLICT: &str = "status_conflict";
+
+#[derive(Debug, Deserialize)]
+pub struct StatusTransitionRequest {
+    pub status: String,
+    /// Stored on the row, e.g. why processing failed
+    pub error_message: Option<String>,
+}
+
+/// Move a transaction along its lifecycle: pending to processing to
+/// completed or failed, processing or failed to retry while the row has
+/// retries left, and pending to cancelled. For workers and operators, so
+/// it sits behind the admin key like the rest of the admin API.
+///
+/// The row moves in one UPDATE guarded by the status it was read in, so of
+/// two writers racing only one succeeds; the other is answered 409 with the
+/// status the row moved to. A retry counts against the row's retries and
+/// is queued again at its original priority. Cancelling goes through the
+/// same path as `DELETE /v1/transactions/:id`.
+pub async fn handler(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    ValidatedPath(id): ValidatedPath<Uuid>,
+    ValidatedJson(request): ValidatedJson<StatusTransitionRequest>,
+) -> AppResult<Json<TransactionStatusResponse>> {
+    let to: TransactionStatus = request.status.parse().map_err(|_| {
+        let known: Vec<_> = TransactionStatus::ALL.iter().map(TransactionStatus::as_str).collect();
+        AppError::bad_request(format!("status must be one of: {}", known.join(", ")))
+    })?;
+    if to == TransactionStatus::Cancelled {
+        return cancel::cancel(&state, &mut db_conn, id).await.map(Json);
+    }
+
+    let transaction = TransactionQueue::find(&mut db_conn, id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Transaction not found"))?;
+    let from = TransactionStatus::parse(&transaction.status)
+        .ok_or_else(|| AppError::internal_server_error(format!("Stored status {:?} is unknown", transaction.status)))?;
+    if !from.can_transition_to(to) {
+        return Err(conflict(
+            format!("Transaction is {} and cannot move to {}", from.as_str(), to.as_str()),
+            INVALID_STATUS_TRANSITION,
+            from.as_str(),
+        ));
+    }
+    if to == TransactionStatus::Retry && transaction.retry_count >= transaction.max_retries {
+        return Err(conflict(
+            format!("Transaction has used all {} of its retries", transaction.max_retries),
+            RETRIES_EXHAUSTED,
+            from.as_str(),
+        ));
+    }
+
+    let Some(transaction) =
+        TransactionQueue::transition(&mut db_conn, id, from, to, request.error_message.as_deref()).await?
+    else {
+        // Someone else moved it between the read and the update
+        let current = TransactionQueue::find(&mut db_conn, id)
+            .await?
+            .ok_or_else(|| AppError::not_found("Transaction not found"))?;
+        return Err(conflict(
+            format!("Transaction is now {} and was not moved to {}", current.status, to.as_str()),
+            STATUS_CONFLICT,
+            &current.status,
+        ));
+    };
+
+    update_pending_slot(&state, &transaction.account_id, from, to).await;
+    if to == TransactionStatus::Retry {
+        requeue(&state, &transaction).await?;
+    }
+    tracing::info!(
+        transaction_id = %id,
+        account_id = transaction.account_id,
+        from = from.as_str(),
+        to = to.as_str(),
+        "Transaction status changed"
+    );
+
+    Ok(Json(status::response(transaction, None, None, state.clock.now())))
+}
+
+fn conflict(message: String, code: &'static str, status: &str) -> AppError {
+    AppError::new(StatusCode::CONFLICT, message)
+        .with_code(code)
+        .with_details(json!({ "status": status }))
+}
+
+/// Free the account's pending slot once the transaction finishes, and take
+/// it back when a failed one is retried. Retries were admitted once already,
+/// so they are not held to the cap. Failures only leave the counter off until
+/// the next reconciliation, so they are logged.
+async fn update_pending_slot(state: &AppState, account_id: &str, from: TransactionStatus, to: TransactionStatus) {
+    let queue_manager = state.queue_manager();
+    let updated = match (from.is_terminal(), to.is_terminal()) {
+        (false, true) => queue_manager.release_pending(account_id).await.map(|_| ()),
+        (true, false) => queue_manager.reserve_pending(account_id, u32::MAX).await.map(|_| ()),
+        _ => Ok(()),
+    };
+    if let Err(e) = updated {
+        tracing::warn!(account_id, "Failed to update the pending slot after a status change: {}", e);
+    }
+}
+
+/// Queue a retried transaction again at its original priority. The row is
+/// already in retry, so a failure here is answered 503 for the caller to
+/// look into rather than retried.
+async fn requeue(state: &AppState, transaction: &TransactionQueue) -> AppResult<()> {
+    let payload = serde_json::value::to_raw_value(&transaction.transaction_data)
+        .map_err(|e| AppError::internal_server_error(format!("Failed to encode payload: {}", e)))?;
+    let envelope = QueueEnvelope::encode_compressed(
+        &transaction.id.to_string(),
+        &transaction.account_id,
+        &payload,
+        state.config.queue_compress_over_bytes,
+        None,
+    )?;
+    state
+        .queue_manager()
+        .add_with_priority(&TRANSACTION_QUEUE, &envelope, transaction.priority)
+        .await
+        .map_err(|e| {
+            AppError::service_unavailable(format!("Transaction moved to retry but could not be queued: {}", e))
+        })
+}
@@ -0,0 +1,58 @@
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::json::{MAX_BODY_BYTES, SUPPORTED_CONTENT_TYPES},
+    payload::MAX_TRANSACTION_DATA_BYTES,
+    rate_limit::{account_limits, layer::{ip_limit, read_limit}},
+    AppState,
+};
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
+use queue_engine::submit::validate_account_id;
+use redis_cache::{MAX_PRIORITY, MIN_PRIORITY};
+use serde::Deserialize;
+
+/// Lets clients reuse the limits briefly; a changed limit reaches them
+/// within this long
+const LIMITS_CACHE_CONTROL: &str = "private, max-age=30";
+
+#[derive(Debug, Deserialize)]
+pub struct LimitsQuery {
+    pub account_id: String,
+}
+
+pub use api_client::types::{LimitsResponse, PriorityRange};
+
+/// The limits the API enforces on an account, for clients to throttle
+/// themselves by. There are no per-caller credentials, so the account is
+/// named the way submit names it. Every value comes from the lookup the
+/// enforcing code uses, so it matches what the next request is held to.
+pub async fn handler(
+    State(state): State<AppState>,
+    Query(query): Query<LimitsQuery>,
+) -> AppResult<impl IntoResponse> {
+    validate_account_id(&query.account_id).map_err(AppError::bad_request)?;
+    let account = account_limits(&state, &query.account_id).await;
+
+    let mut rate_limits = vec![account.submit.policy(), read_limit(&state).policy()];
+    rate_limits.extend(ip_limit(&state).map(|layer| layer.policy()));
+
+    let response = LimitsResponse {
+        rate_limits,
+        max_pending: account.max_pending,
+        soft_limit_pct: account.soft_limit_pct,
+        priority: PriorityRange {
+            min: MIN_PRIORITY,
+            max: MAX_PRIORITY,
+            default: 0,
+        },
+        max_body_bytes: MAX_BODY_BYTES,
+        max_transaction_data_bytes: MAX_TRANSACTION_DATA_BYTES,
+        supported_content_types: SUPPORTED_CONTENT_TYPES.iter().map(|content_type| content_type.to_string()).collect(),
+        account_id: query.account_id,
+    };
+    Ok(([(header::CACHE_CONTROL, HeaderValue::from_static(LIMITS_CACHE_CONTROL))], Json(response)))
+}
@@ -1,8 +1,24 @@
-use axum::{routing::post, Router};
+use crate::rate_limit::layer::{ip_limit, read_limit};
+use axum::{handler::Handler, routing::get, Router};
 
+mod accounts;
+mod admin;
+mod limits;
+mod queue;
 mod transactions;
 
-pub fn router() -> Router<crate::lib::AppState> {
-    Router::new()
-        .nest("/transactions", transactions::router())
+pub fn router(state: crate::AppState) -> Router<crate::AppState> {
+    let ip_limit = ip_limit(&state);
+    let router = Router::new()
+        .nest("/transactions", transactions::router(&state))
+        .nest("/accounts", accounts::router(&state))
+        .nest("/queue", queue::router(&state))
+        .route("/limits", get(limits::handler.layer(read_limit(&state))))
+        .nest("/admin", admin::router(state));
+    // Around every route, so a client over it is turned away before any
+    // per-account limit is looked at
+    match ip_limit {
+        Some(layer) => router.layer(layer),
+        None => router,
+    }
 }
\ No newline at end of file
@@ -0,0 +1,9 @@
+use crate::rate_limit::layer::read_limit;
+use axum::{handler::Handler, routing::get, Router};
+
+mod stats;
+
+pub fn router(state: &crate::AppState) -> Router<crate::AppState> {
+    Router::new()
+        .route("/stats", get(stats::handler.layer(read_limit(state))))
+}
@@ -0,0 +1,22 @@
+use crate::{errors::AppResult, queue_stats::QueueStats, AppState, TRANSACTION_QUEUE};
+use axum::{extract::State, Json};
+use redis_cache::WaitPercentiles;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct QueueStatsResponse {
+    #[serde(flatten)]
+    pub stats: QueueStats,
+    /// Enqueue to dequeue wait of recent completions, per priority band.
+    /// Bands without recent completions are left out.
+    pub wait_times: Vec<WaitPercentiles>,
+}
+
+/// Current depth, throughput and estimated drain time of the transaction
+/// queue, and how long recent transactions of each priority waited
+pub async fn handler(State(state): State<AppState>) -> AppResult<Json<QueueStatsResponse>> {
+    let queue_manager = state.queue_manager();
+    let stats = QueueStats::collect(&queue_manager, &TRANSACTION_QUEUE).await?;
+    let wait_times = queue_manager.wait_percentiles(&TRANSACTION_QUEUE).await?;
+    Ok(Json(QueueStatsResponse { stats, wait_times }))
+}
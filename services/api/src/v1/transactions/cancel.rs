@@ -0,0 +1,92 @@
+use super::status;
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::{DatabaseConnection, ValidatedPath},
+    v1::accounts::cancel::publish_cancelled,
+    AppState, TRANSACTION_QUEUE,
+};
+use axum::{extract::State, http::StatusCode, Json};
+use diesel_async::AsyncPgConnection;
+use postgres_models::models::{CancelledTransaction, TransactionQueue};
+use serde_json::json;
+use uuid::Uuid;
+
+pub use api_client::types::TransactionStatusResponse;
+
+/// Error code of the 409 sent when the transaction is no longer pending
+pub const TRANSACTION_NOT_PENDING: &str = "transaction_not_pending";
+
+/// Cancel a transaction that is still pending and return it as cancelled.
+///
+/// The row only moves to "cancelled" while it is pending, in one
+/// conditional UPDATE. A worker claiming it at the same time either claims
+/// it first, and the cancel is answered 409 with the status it moved to, or
+/// finds it cancelled and skips it. The queue entry is removed only once the
+/// row is cancelled.
+pub async fn handler(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    ValidatedPath(id): ValidatedPath<Uuid>,
+) -> AppResult<Json<TransactionStatusResponse>> {
+    cancel(&state, &mut db_conn, id).await.map(Json)
+}
+
+/// Cancel the transaction if it is still pending, as `handler` describes
+pub async fn cancel(
+    state: &AppState,
+    db_conn: &mut AsyncPgConnection,
+    id: Uuid,
+) -> AppResult<TransactionStatusResponse> {
+    let Some(transaction) = TransactionQueue::cancel(db_conn, id).await? else {
+        let transaction = TransactionQueue::find(db_conn, id)
+            .await?
+            .ok_or_else(|| AppError::not_found("Transaction not found"))?;
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            format!("Transaction is {} and can no longer be cancelled", transaction.status),
+        )
+        .with_code(TRANSACTION_NOT_PENDING)
+        .with_details(json!({ "status": transaction.status })));
+    };
+
+    let removed_from_queue = remove_queue_entry(state, &transaction).await;
+    publish_cancelled(
+        state,
+        &transaction.account_id,
+        &[CancelledTransaction {
+            id,
+            priority: transaction.priority,
+            created_at: transaction.created_at,
+        }],
+    );
+    tracing::info!(
+        transaction_id = %id,
+        account_id = transaction.account_id,
+        removed_from_queue,
+        "Cancelled transaction"
+    );
+
+    Ok(status::response(transaction, None, None, state.clock.now()))
+}
+
+/// Drop the cancelled transaction from the queue and free its pending slot,
+/// returning whether its entry was still queued. The row is already
+/// cancelled, so failures are logged: the worker skips an entry left behind
+/// and the reconciler corrects the counter.
+async fn remove_queue_entry(state: &AppState, transaction: &TransactionQueue) -> bool {
+    let queue_manager = state.queue_manager();
+    let account_id = transaction.account_id.as_str();
+    if let Err(e) = queue_manager.release_pending(account_id).await {
+        tracing::warn!(account_id, "Failed to release the pending slot of a cancelled transaction: {}", e);
+    }
+
+    let removed = match queue_manager.member_of(&TRANSACTION_QUEUE, account_id, &transaction.id.to_string()).await {
+        Ok(Some(member)) => queue_manager.remove(&TRANSACTION_QUEUE, &member).await,
+        Ok(None) => Ok(false),
+        Err(e) => Err(e),
+    };
+    removed.unwrap_or_else(|e| {
+        tracing::warn!(account_id, "Failed to remove a cancelled transaction from the queue: {}", e);
+        false
+    })
+}
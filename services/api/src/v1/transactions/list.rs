@@ -0,0 +1,81 @@
+use super::status;
+use crate::{
+    errors::{AppError, AppResult},
+    extractors::DatabaseConnection,
+    AppState,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use postgres_models::models::{TransactionCursor, TransactionListFilter, TransactionQueue, TransactionStatus};
+use queue_engine::submit::validate_account_id;
+
+pub use api_client::types::{ListTransactionsQuery, ListTransactionsResponse};
+
+/// Rows per page when the caller does not pass `limit`
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+/// An account's transactions, newest first, filtered by status, lowest
+/// priority and creation time, for dashboards and reconciliation.
+///
+/// Pages follow an opaque cursor rather than an offset, so walking an
+/// account of millions of rows neither slows down nor skips or repeats
+/// rows as new ones arrive. Each page also carries the account's totals
+/// per status.
+pub async fn handler(
+    State(state): State<AppState>,
+    DatabaseConnection(mut db_conn): DatabaseConnection,
+    Query(query): Query<ListTransactionsQuery>,
+) -> AppResult<Json<ListTransactionsResponse>> {
+    validate_account_id(&query.account_id).map_err(AppError::bad_request)?;
+    let status = match query.status.as_deref() {
+        Some(status) => Some(
+            TransactionStatus::parse(status)
+                .ok_or_else(|| AppError::bad_request(format!("Unknown status '{}'", status)))?,
+        ),
+        None => None,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::bad_request(format!("limit must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    let after = match query.cursor.as_deref() {
+        Some(cursor) => Some(TransactionCursor::decode(cursor).ok_or_else(|| AppError::bad_request("Invalid cursor"))?),
+        None => None,
+    };
+
+    let filter = TransactionListFilter {
+        account_id: query.account_id,
+        status,
+        min_priority: query.min_priority,
+        created_after: query.created_after,
+    };
+    // One extra row tells whether another page exists
+    let mut transactions = TransactionQueue::list(&mut db_conn, &filter, after, limit + 1).await?;
+    let has_more = transactions.len() as i64 > limit;
+    transactions.truncate(limit as usize);
+    let next_cursor = transactions
+        .last()
+        .filter(|_| has_more)
+        .map(|transaction| TransactionCursor::after(transaction).encode());
+
+    let counts =
+        TransactionQueue::count_for_account_by_status(&mut db_conn, &filter.account_id, &TransactionStatus::ALL)
+            .await?;
+    let status_counts = TransactionStatus::ALL
+        .iter()
+        .map(|status| (status.as_str().to_string(), counts.get(status.as_str()).copied().unwrap_or(0)))
+        .collect();
+
+    let now = state.clock.now();
+    Ok(Json(ListTransactionsResponse {
+        items: transactions
+            .into_iter()
+            .map(|transaction| status::response(transaction, None, None, now))
+            .collect(),
+        next_cursor,
+        status_counts,
+    }))
+}
@@ -1,8 +1,20 @@
-use axum::{routing::post, Router};
+use crate::rate_limit::layer::read_limit;
+use axum::{
+    handler::Handler,
+    routing::{get, post},
+    Router,
+};
 
+pub(super) mod cancel;
+mod list;
+pub(super) mod status;
 mod submit;
+mod submit_stream;
 
-pub fn router() -> Router<crate::lib::AppState> {
+pub fn router(state: &crate::AppState) -> Router<crate::AppState> {
     Router::new()
+        .route("/", get(list::handler.layer(read_limit(state))))
         .route("/submit", post(submit::handler))
-}
\ No newline at end of file
+        .route("/submit-stream", post(submit_stream::handler))
+        .route("/:id", get(status::handler.layer(read_limit(state))).delete(cancel::handler))
+}
@@ -0,0 +1,131 @@
+use crate::{
+    consistency::{CONSISTENCY_TOKEN_HEADER, PRIMARY_FALLBACK_READS_TOTAL},
+    errors::{AppError, AppResult},
+    estimation::{estimate_at_position, live_inputs, tier_multiplier, EstimateUnavailable},
+    extractors::{database::database_unavailable, ValidatedPath},
+    AppState, TRANSACTION_QUEUE,
+};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use postgres_models::models::TransactionQueue;
+use postgres_models::DbError;
+use uuid::Uuid;
+
+pub use api_client::types::{ProcessingHoldResponse, TransactionStatusResponse, WaitEstimate};
+
+/// Current state of a submitted transaction
+pub async fn handler(
+    State(state): State<AppState>,
+    ValidatedPath(id): ValidatedPath<Uuid>,
+    headers: HeaderMap,
+) -> AppResult<Json<TransactionStatusResponse>> {
+    let transaction = find(&state, id, &headers)
+        .await
+        .map_err(|e| match e {
+            DbError::Connection(message) => database_unavailable(message),
+            e => e.into(),
+        })?
+        .ok_or_else(|| AppError::not_found("Transaction not found"))?;
+
+    let processing_hold = if transaction.status == "pending" {
+        state
+            .submit_queue
+            .hold(&id.to_string())
+            .await?
+            .map(|hold| ProcessingHoldResponse {
+                reason: hold.reason.as_str().to_string(),
+                since: DateTime::from_timestamp(hold.since as i64, 0).unwrap_or_default(),
+            })
+    } else {
+        None
+    };
+    let wait = if transaction.status == "pending" {
+        Some(wait_estimate(&state, &id.to_string(), &transaction.account_id).await?)
+    } else {
+        None
+    };
+    Ok(Json(response(transaction, processing_hold, wait, state.clock.now())))
+}
+
+/// Response for `transaction` as of `now`; the result is only shown once it
+/// has completed
+pub fn response(
+    transaction: TransactionQueue,
+    processing_hold: Option<ProcessingHoldResponse>,
+    wait: Option<WaitEstimate>,
+    now: DateTime<Utc>,
+) -> TransactionStatusResponse {
+    let finished_at = transaction.processed_at.unwrap_or(now);
+    let elapsed_wait_seconds = (finished_at - transaction.created_at).num_seconds().max(0);
+    let result = if transaction.status == "completed" {
+        transaction.result
+    } else {
+        None
+    };
+
+    TransactionStatusResponse {
+        id: transaction.id,
+        account_id: transaction.account_id,
+        status: transaction.status,
+        priority: transaction.priority,
+        retry_count: transaction.retry_count,
+        created_at: transaction.created_at,
+        processed_at: transaction.processed_at,
+        error_message: transaction.error_message,
+        result,
+        processing_hold,
+        elapsed_wait_seconds,
+        wait,
+    }
+}
+
+/// The transaction from the status store, retried on the primary when the
+/// replica misses it and the request carries a fresh consistency token for it
+async fn find(state: &AppState, id: Uuid, headers: &HeaderMap) -> Result<Option<TransactionQueue>, DbError> {
+    let found = state.submit_store.find(id).await?;
+    if found.is_some() || !state.submit_store.reads_replica() {
+        return Ok(found);
+    }
+    let fresh = headers
+        .get(CONSISTENCY_TOKEN_HEADER)
+        .and_then(|token| token.to_str().ok())
+        .is_some_and(|token| state.consistency.is_fresh(token, id, state.clock.now()));
+    if !fresh {
+        return Ok(None);
+    }
+
+    let found = state.submit_store.find_on_primary(id).await?;
+    metrics::counter!(PRIMARY_FALLBACK_READS_TOTAL, "found" => found.is_some().to_string()).increment(1);
+    Ok(found)
+}
+
+/// Position and estimate of a pending transaction of `account_id` from the
+/// live queue, using the same inputs and formula as submit
+async fn wait_estimate(state: &AppState, transaction_id: &str, account_id: &str) -> AppResult<WaitEstimate> {
+    let queue = &*state.submit_queue;
+    let (position, (throughput, workers_alive)) = tokio::join!(
+        queue.transaction_position(&TRANSACTION_QUEUE, account_id, transaction_id),
+        live_inputs(queue, &TRANSACTION_QUEUE, &state.config),
+    );
+    let queue_position = position?;
+    let estimate = match queue_position {
+        Some(position) => estimate_at_position(
+            position,
+            throughput,
+            workers_alive,
+            tier_multiplier(&state.config, account_id),
+            state.config.max_estimated_processing_seconds,
+        ),
+        None => Err(EstimateUnavailable::NotQueued),
+    };
+
+    Ok(WaitEstimate {
+        queue_position,
+        estimated_processing_time_seconds: estimate.ok(),
+        estimate_unavailable_reason: estimate.err(),
+    })
+}
@@ -1,7 +1,22 @@
 use crate::{
+    backpressure,
+    cancellation::{client_closed_request, detach, CancellationToken},
+    consistency::CONSISTENCY_TOKEN_HEADER,
     errors::{AppError, AppResult},
-    extractors::DatabaseConnection,
-    lib::AppState,
+    extractors::ValidatedJson,
+    idempotency::{self, request_fingerprint, Claim},
+    metrics::{NO_TIER, RATE_LIMIT_REJECTIONS_TOTAL, SUBMISSIONS_TOTAL, SUBMIT_ABANDONED_TOTAL},
+    payload::{TransactionPayload, INVALID_TRANSACTION_DATA, UNSUPPORTED_CHARACTERS},
+    payload_schema,
+    pending::PENDING_LIMIT_EXCEEDED,
+    rate_limit::{
+        account_limits, check_account_limit, insert_header, insert_warning_header,
+        rate_limit_headers, soft_limit_warning, submit_cost, RateLimitWarning, RATE_LIMIT_POLICY_HEADER,
+    },
+    redis_failure::{fails_open, placement_from_store, refusal},
+    submit_deadline::{place, Deadline, QueueEntry, SubmitPhase},
+    trace_context::current_traceparent,
+    AppState, TRANSACTION_QUEUE,
 };
 use axum::http::HeaderMap;
 use axum::{
@@ -10,27 +25,19 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use diesel_async::RunQueryDsl;
-use postgres_models::models::{NewTransactionQueue, TransactionQueue};
-use postgres_models::schema::transaction_queue;
-use redis_cache::{QueueManager, RateLimiter, MAX_PRIORITY, MIN_PRIORITY};
-use serde::{Deserialize, Serialize};
-use uuid::Uuid;
-
-#[derive(Debug, Deserialize)]
-pub struct SubmitTransactionRequest {
-    pub account_id: String,
-    pub transaction_data: serde_json::Value,
-    pub priority: Option<i32>,
-}
+use postgres_models::models::NewTransactionQueueRef;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use postgres_models::transaction_data::FieldError;
+use postgres_models::DbError;
+use queue_engine::submit::{validate_account_id, validate_priority};
+use redis_cache::{QueueEnvelope, QueueEvent, QueueEventKind};
+use serde::Serialize;
+use std::time::Duration;
 
-#[derive(Debug, Serialize)]
-pub struct SubmitTransactionResponse {
-    pub transaction_id: Uuid,
-    pub queue_position: i64,
-    pub estimated_processing_time_seconds: i64,
-    pub status: String,
-}
+pub use api_client::types::SubmitTransactionResponse;
+
+/// The shared submit body, with `transaction_data` kept as its JSON text
+pub type SubmitTransactionRequest = api_client::types::SubmitTransactionRequest<TransactionPayload>;
 
 pub struct JsonWithHeaders<T> {
     pub status: StatusCode,
@@ -79,12 +86,18 @@ impl<T> JsonWithHeaders<T> {
 /// - Validate transaction_data: not null, reasonable size (< 1MB)
 /// - Validate priority: if provided, should be reasonable range (-1000 to 1000)
 /// - Return 400 Bad Request for invalid input with descriptive errors
+/// - An Idempotency-Key header or idempotency_key field makes retries replay
+///   the first submit's response; see `idempotency`
 /// 
 /// Step 2: RATE LIMITING (Performance Critical)
 /// - Get rate limiter from state: &state.redis_pool
 /// - Use libs/redis_cache/src/rate_limiter.rs::RateLimiter::check_rate_limit()
 /// - Check account-specific limits from account_rate_limits table
-/// - Return 429 Too Many Requests if exceeded
+/// - A submit uses as much of the limit as Config::priority_cost_curve
+///   charges for its priority
+/// - Return 429 Too Many Requests if exceeded, with the limit's type and
+///   source (account_config, tier_default or global_default) in the details
+///   and X-RateLimit-Policy
 /// - MUST include rate limit headers in ALL responses:
 ///   - X-RateLimit-Limit: requests per minute allowed
 ///   - X-RateLimit-Remaining: requests remaining in current window
@@ -92,24 +105,33 @@ impl<T> JsonWithHeaders<T> {
 /// 
 /// Step 3: DATABASE PERSISTENCE (Reliability Critical)
 /// - Create NewTransactionQueue using libs/postgres_models/src/models.rs
-/// - Generate UUID for transaction_id using Uuid::new_v4()
-/// - Set created_at to current UTC timestamp
+/// - Generate UUID for transaction_id from state.ids
+/// - Set created_at from state.clock
 /// - Set status to "pending"
-/// - Insert into transaction_queue table using diesel
+/// - Insert into transaction_queue table through state.submit_store
 /// - Handle database errors gracefully (return 500 Internal Server Error)
+/// - While Redis cannot be reached, Config::redis_failure_mode either refuses
+///   with 503 or submits without rate limiting and queueing; see `redis_failure`
 /// 
 /// Step 4: QUEUE MANAGEMENT (Business Logic Critical)
 /// - Use libs/redis_cache/src/queue_manager.rs::QueueManager
-/// - Add transaction to Redis queue with priority
+/// - Add transaction to Redis queue with priority, wrapped in a QueueEnvelope
+///   so every row has exactly one queue entry even for identical payloads
 /// - Get current queue position considering priority ordering
 /// - Higher priority numbers should be processed first
 /// - Use Redis sorted sets for efficient priority queue
 /// 
 /// Step 5: RESPONSE CALCULATION
+/// - Runs under the Config::submit_deadline_ms budget; if it is spent after the
+///   insert, answer 202 with queue_position null and an estimate from the
+///   latest queue stats instead of running long
 /// - Calculate estimated_processing_time_seconds:
-///   - Base time: 30 seconds per transaction
-///   - Multiply by queue position ahead of current transaction
-///   - Cap at reasonable maximum (e.g., 3600 seconds)
+///   - Items ahead of this transaction in the unified priority queue
+///   - Priced at measured throughput, falling back to 30 seconds per item
+///   - 0 when nothing is ahead and workers are alive
+///   - Capped at Config::max_estimated_processing_seconds
+/// - Include backpressure from the cached queue stats: a level and a
+///   suggested delay before the next submit, also sent as X-Backoff-Hint-Ms
 /// - Return proper JSON response with all fields
 /// 
 /// Step 6: ERROR HANDLING
@@ -134,118 +156,310 @@ impl<T> JsonWithHeaders<T> {
 /// - Log security-relevant events
 pub async fn handler(
     State(state): State<AppState>,
-    DatabaseConnection(mut db_conn): DatabaseConnection,
-    Json(request): Json<SubmitTransactionRequest>,
+    headers: HeaderMap,
+    ValidatedJson(mut request): ValidatedJson<SubmitTransactionRequest>,
+) -> AppResult<JsonWithHeaders<SubmitTransactionResponse>> {
+    request.idempotency_key = idempotency::request_key(&request, &headers).map_err(AppError::bad_request)?;
+    // Detached so a client disconnect cannot stop submit between the insert
+    // and the enqueue; submit gives up on its own while nothing is written
+    let result = detach(move |cancel| async move {
+        submit(&state, &request, &cancel)
+            .await
+            .map_err(|err| err.for_account(&request.account_id))
+    })
+    .await;
+    let status = match &result {
+        Ok(response) => response.status,
+        Err(err) => err.status,
+    };
+    metrics::counter!(SUBMISSIONS_TOTAL, "status" => status.as_u16().to_string()).increment(1);
+    result
+}
+
+pub(super) async fn submit(
+    state: &AppState,
+    request: &SubmitTransactionRequest,
+    cancel: &CancellationToken,
 ) -> AppResult<JsonWithHeaders<SubmitTransactionResponse>> {
     // Step 1: INPUT VALIDATION
-    if request.account_id.is_empty() || request.account_id.len() > 255 {
-        return Err(AppError::bad_request("Invalid account_id: must be 1-255 characters"));
+    validate_account_id(&request.account_id).map_err(AppError::bad_request)?;
+    if request.account_id.contains('\0') {
+        return Err(unsupported_characters("account_id contains a null character, which cannot be stored"));
+    }
+    request
+        .transaction_data
+        .validate()
+        .map_err(AppError::bad_request)?;
+    request
+        .transaction_data
+        .check_storable()
+        .map_err(unsupported_characters)?;
+    let typed = request
+        .transaction_data
+        .typed(state.config.allow_unknown_account_types)
+        .map_err(invalid_transaction_data)?;
+    validate_priority(request.priority).map_err(AppError::bad_request)?;
+    if let Some(key) = &request.idempotency_key {
+        idempotency::validate_key(key).map_err(AppError::bad_request)?;
     }
-    if request.transaction_data.is_null() {
-        return Err(AppError::bad_request("transaction_data cannot be null"));
+    let cost = submit_cost(state, request.priority, typed.as_ref());
+
+    // A retry of a submit that already ran gets that submit's response
+    let Some(key) = request.idempotency_key.as_deref() else {
+        return submit_new(state, request, cost, cancel).await;
     };
+    let fingerprint = request_fingerprint(request)
+        .map_err(|e| AppError::bad_request(format!("Invalid transaction_data: {}", e)))?;
+    let claim = match idempotency::claim(state, &request.account_id, key, fingerprint).await? {
+        Claim::Owned(claim) => claim,
+        Claim::Replay(response) => return Ok(replayed(response)),
+        Claim::Unguarded => return submit_new(state, request, cost, cancel).await,
+    };
+    let result = submit_new(state, request, cost, cancel).await;
+    claim.settle(result.as_ref().ok().map(|response| &response.json)).await;
+    result
+}
+
+/// Submit a validated request as a new transaction, counting it as `cost`
+/// requests against the account's limit
+async fn submit_new(
+    state: &AppState,
+    request: &SubmitTransactionRequest,
+    cost: u32,
+    cancel: &CancellationToken,
+) -> AppResult<JsonWithHeaders<SubmitTransactionResponse>> {
+    let mut deadline = Deadline::after(Duration::from_millis(state.config.submit_deadline_ms));
+
+    // Step 2: RATE LIMITING
+    let account = account_limits(state, &request.account_id).await;
+    let limit = account.submit;
+    let limit_per_minute = limit.max_requests;
+    let window_in_seconds = limit.window_seconds;
 
-    // Validate transaction_data
-    let transaction_size = serde_json::to_vec(&request.transaction_data)
-        .map_err(|_| AppError::bad_request("transaction_data must be valid JSON"))?
-        .len();
-    if transaction_size == 0 {
-        return Err(AppError::bad_request("transaction_data cannot be empty"));
+    if abandoned(cancel, SubmitPhase::RateLimit) {
+        return Err(client_closed_request());
     }
-    if transaction_size > 1024 * 1024 {
-        return Err(AppError::bad_request("transaction_data too large: must be < 1MB"));
+    // None when the check was skipped because Redis is down
+    let rate_limit_result = match check_account_limit(
+        state,
+        &request.account_id,
+        cost,
+        limit_per_minute,
+        window_in_seconds,
+        limit.strategy,
+    )
+    .await
+    {
+        Ok(result) => Some(result),
+        Err(e) if fails_open(&state.config, SubmitPhase::RateLimit, &e) => None,
+        Err(e) => return Err(refusal(e, |_| AppError::internal_server_error("Failed to check rate limit"))),
+    };
+
+    deadline.checkpoint(SubmitPhase::RateLimit);
+    let mut header_map = match &rate_limit_result {
+        Some(result) => rate_limit_headers(limit_per_minute, result),
+        None => HeaderMap::new(),
+    };
+
+    if let Some(rate_limit_result) = rate_limit_result.as_ref().filter(|result| !result.allowed) {
+        let tier = state.config.tier_limit(&request.account_id).map_or(NO_TIER, |tier| tier.prefix.as_str());
+        metrics::counter!(RATE_LIMIT_REJECTIONS_TOTAL, "tier" => tier.to_string()).increment(1);
+        let policy = limit.policy();
+        let mut headers = header_map.clone();
+        insert_header(&mut headers, RATE_LIMIT_POLICY_HEADER, policy.header_value());
+        let err = AppError::too_many_requests("Rate limit exceeded")
+            .with_details(policy)
+            .with_headers(headers)
+            .with_retry(limit_per_minute, rate_limit_result);
+        return Err(err);
     }
 
-    // Validate priority
-    if let Some(priority) = request.priority {
-        if priority < MIN_PRIORITY || priority > MAX_PRIORITY {
-            return Err(AppError::bad_request("priority must be between -1000 and 1000"));
-        }
+    // The account's payload schema is read only once the body has arrived
+    // and passed rate limiting, so slow or rejected clients never hold a
+    // connection
+    payload_schema::check(state, &request.account_id, &request.transaction_data).await?;
+    deadline.checkpoint(SubmitPhase::DbConnection);
+
+    // Warn once usage crosses the account's soft limit, ahead of the 429s
+    let threshold_pct = account.soft_limit_pct;
+    let warnings: Vec<RateLimitWarning> = rate_limit_result
+        .as_ref()
+        .and_then(|result| soft_limit_warning(limit_per_minute, result, threshold_pct))
+        .into_iter()
+        .collect();
+    for warning in &warnings {
+        insert_warning_header(&mut header_map, warning);
     }
 
-    // Step 2: RATE LIMITING
-    let rate_limiter = RateLimiter::new(state.redis_pool.clone());
-    let limit_per_minute = 100;
-    let window_in_seconds = 60;
-
-    let rate_limit_result = rate_limiter
-        .check_rate_limit(&request.account_id, limit_per_minute, window_in_seconds)
-        .await
-        .map_err(|e| {
-            AppError::internal_server_error("Failed to check rate limit")
-        })?;
-
-    let mut header_map = HeaderMap::new();
-    header_map.insert("X-RateLimit-Limit", limit_per_minute.into());
-    header_map.insert("X-RateLimit-Remaining", rate_limit_result.remaining.into());
-    header_map.insert("X-RateLimit-Reset", rate_limit_result.reset_at.into());
-
-    if !rate_limit_result.allowed {
-        let err = AppError::too_many_requests("Rate limit exceeded")
-            .with_headers(header_map.clone());
+    // Hold a slot under the account's pending cap before the row exists, so
+    // concurrent submits cannot overshoot it. The worker frees the slot when
+    // the transaction reaches a terminal status.
+    let pending_cap = account.max_pending;
+    deadline.checkpoint(SubmitPhase::AccountLimits);
+    let queue = &*state.submit_queue;
+    if abandoned(cancel, SubmitPhase::PendingReservation) {
+        return Err(client_closed_request());
+    }
+    let reserved = match queue.reserve_pending(&request.account_id, pending_cap.max_requests).await {
+        Ok(reserved) => reserved,
+        Err(e) if fails_open(&state.config, SubmitPhase::PendingReservation, &e) => true,
+        Err(e) => return Err(refusal(e, AppError::from)),
+    };
+    if !reserved {
+        let mut headers = header_map.clone();
+        insert_header(&mut headers, RATE_LIMIT_POLICY_HEADER, pending_cap.header_value());
+        let err = AppError::too_many_requests(format!(
+            "Account already has {} pending transactions, the maximum allowed",
+            pending_cap.max_requests
+        ))
+        .with_code(PENDING_LIMIT_EXCEEDED)
+        .with_details(pending_cap)
+        .with_headers(headers);
         return Err(err);
     }
+    deadline.checkpoint(SubmitPhase::PendingReservation);
 
     // Step 3: DATABASE PERSISTENCE
-    let mut new_transaction = NewTransactionQueue::new(
-        request.account_id.clone(),
-        request.transaction_data.clone(),
+    // The payload is bound from the request's JSON text
+    let mut new_transaction = NewTransactionQueueRef::new(
+        &request.account_id,
+        request.transaction_data.as_raw(),
+        &*state.ids,
+        &*state.clock,
     );
     new_transaction.priority = request.priority.unwrap_or(0);
-    new_transaction.scheduled_at = Some(chrono::Utc::now());
-    new_transaction.status = "pending".to_string();
+    new_transaction.scheduled_at = Some(new_transaction.created_at);
+    new_transaction.idempotency_key = request.idempotency_key.as_deref();
 
-    let transaction_result = diesel::insert_into(transaction_queue::table)
-        .values(&new_transaction)
-        .get_result::<TransactionQueue>(&mut db_conn)
-        .await;
-
-    let transaction = match transaction_result {
-        Ok(tx) => tx,
+    if abandoned(cancel, SubmitPhase::Insert) {
+        let _ = queue.release_pending(&request.account_id).await;
+        return Err(client_closed_request());
+    }
+    // From here on submit finishes even if the client is gone, so the row
+    // is never left without its queue entry
+    let transaction_id = match state.submit_store.insert(&new_transaction).await {
+        Ok(id) => id,
         Err(e) => {
-            let err = Err(AppError::internal_server_error(e.to_string()));
-            return err;
+            // Nothing was stored, so the slot goes back; the reconciler covers a failed release
+            let _ = queue.release_pending(&request.account_id).await;
+            // The key's row was stored by a submit its claim no longer covers
+            if let (Some(key), true) = (request.idempotency_key.as_deref(), is_unique_violation(&e)) {
+                if let Some(response) = idempotency::stored_response(state, request, key).await? {
+                    return Ok(replayed(response));
+                }
+            }
+            return Err(match e {
+                DbError::Connection(_) => AppError::service_unavailable(format!("Database unavailable: {}", e)),
+                _ => AppError::internal_server_error(e.to_string()),
+            });
         }
     };
+    deadline.checkpoint(SubmitPhase::Insert);
 
     // Step 4: QUEUE MANAGEMENT
-    let queue_manager = QueueManager::new(state.redis_pool);
-    let queue_name = "tx_queue";
-    let tx_data = request.transaction_data.to_string();
-
-    let queue_position = if request.priority.is_some() {
-        queue_manager
-            .enqueue_with_priority(
-                &queue_name,
-                &tx_data,
-                request.priority.unwrap()
-            )
-            .await
-            .map_err(|err| {
-                AppError::internal_server_error(format!("Queue management failed: {:#?}", err))
-            })?
-    } else {
-        queue_manager
-            .enqueue(&queue_name, &tx_data)
-            .await
-            .map_err(|err| {
+    // Every submission goes through the priority queue (no priority means 0) so
+    // positions are ranks in a single ordering regardless of how they were submitted
+    // The envelope carries the transaction id so identical payloads stay distinct
+    // members; large payloads are compressed so they do not bloat the sorted set.
+    // The traceparent lets the worker continue this request's trace
+    let envelope = QueueEnvelope::encode_compressed(
+        &transaction_id.to_string(),
+        &request.account_id,
+        request.transaction_data.as_raw(),
+        state.config.queue_compress_over_bytes,
+        current_traceparent().as_deref(),
+    )?;
+
+    let stats = state.queue_stats.get();
+    let placement = match place(
+        queue,
+        &mut deadline,
+        &TRANSACTION_QUEUE,
+        QueueEntry::new(&envelope, &request.account_id, new_transaction.priority),
+        &state.config,
+        stats.as_ref(),
+    )
+    .await
+    {
+        Ok(placement) => placement,
+        Err(e) if fails_open(&state.config, SubmitPhase::Enqueue, &e) => {
+            placement_from_store(&*state.submit_store, &request.account_id, &state.config, stats.as_ref()).await
+        }
+        Err(e) => {
+            return Err(refusal(e, |err| {
                 AppError::internal_server_error(format!("Queue management failed: {:#?}", err))
-            })?
+            }))
+        }
     };
+    state.events.publish(QueueEvent::new(
+        QueueEventKind::Submitted,
+        transaction_id.to_string(),
+        request.account_id.as_str(),
+        new_transaction.priority,
+        new_transaction.created_at.timestamp_millis().max(0) as u64,
+    ));
 
     // Step 5: RESPONSE CALCULATION
-    let estimated_processing_time_seconds = std::cmp::min(queue_position * 30, 3600);
+    // Without a position the answer is partial: accepted, details to follow
+    let status = match placement.position {
+        Some(_) => StatusCode::OK,
+        None => StatusCode::ACCEPTED,
+    };
+
+    let backpressure = backpressure::assess(stats.as_ref(), limit, &state.config);
+    backpressure::insert_header(&backpressure, &mut header_map);
+    // Lets a status read right after this one see the row on a lagging replica
+    insert_header(
+        &mut header_map,
+        CONSISTENCY_TOKEN_HEADER,
+        state.consistency.issue(transaction_id, new_transaction.created_at),
+    );
 
     // Placeholder response
     let response_body = SubmitTransactionResponse {
-        transaction_id: transaction.id,
-        queue_position,
-        estimated_processing_time_seconds,
-        status: new_transaction.status,
+        transaction_id,
+        queue_position: placement.position,
+        account_position: placement.account_position,
+        estimated_processing_time_seconds: placement.estimated_processing_time_seconds,
+        status: new_transaction.status.to_string(),
+        warnings,
+        backpressure,
     };
 
     // Step 6: Add rate limit headers to response
-    let response = JsonWithHeaders::new(StatusCode::OK, response_body)
+    let response = JsonWithHeaders::new(status, response_body)
         .with_headers(header_map);
     Ok(response)
-}
\ No newline at end of file
+}
+
+/// Whether submit should give up before `phase` because the client has
+/// disconnected, counting the abort. Only asked while nothing the client
+/// would have to know about has been written.
+fn abandoned(cancel: &CancellationToken, phase: SubmitPhase) -> bool {
+    let abandoned = cancel.is_cancelled();
+    if abandoned {
+        metrics::counter!(SUBMIT_ABANDONED_TOTAL, "phase" => phase.as_str()).increment(1);
+    }
+    abandoned
+}
+
+/// 200 with the response of the submit that first used the request's key
+fn replayed(response: SubmitTransactionResponse) -> JsonWithHeaders<SubmitTransactionResponse> {
+    JsonWithHeaders::new(StatusCode::OK, response).with_headers(idempotency::replay_headers())
+}
+
+fn is_unique_violation(err: &DbError) -> bool {
+    matches!(err, DbError::Query(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)))
+}
+
+/// 400 for valid JSON holding text Postgres refuses to store
+fn unsupported_characters(message: &str) -> AppError {
+    AppError::bad_request(message).with_code(UNSUPPORTED_CHARACTERS)
+}
+
+/// 400 naming the malformed field of a typed payload
+fn invalid_transaction_data(err: FieldError) -> AppError {
+    AppError::bad_request(format!("Invalid transaction_data.{}", err))
+        .with_code(INVALID_TRANSACTION_DATA)
+        .with_details(err)
+}
@@ -0,0 +1,231 @@
+//! Batch submit over NDJSON, streamed both ways.
+//!
+//! Every line of the request body is one submit body. Lines are read as they
+//! arrive and submitted `Config::submit_stream_concurrency` at a time, each
+//! through the same path as POST /v1/transactions/submit, rate limits and
+//! pending cap included. Results go back one NDJSON line each, in request
+//! order, as soon as every earlier line has its own.
+//!
+//! The response pulls the whole pipeline: the next line is read only when a
+//! result has been taken by the client, so a slow reader slows the upload
+//! down instead of growing buffers, and at most the lines in flight are held
+//! in memory. A failing line gets its own error result and leaves the rest
+//! of the stream alone; only an unreadable body, or one with more than
+//! `Config::submit_stream_max_lines` lines, ends the stream early, with a
+//! last result saying why.
+
+use super::submit::{submit, SubmitTransactionRequest};
+use crate::{
+    cancellation::detach,
+    errors::{AppError, AppResult},
+    extractors::json::{content_type, invalid_json},
+    AppState,
+};
+use api_client::types::SubmitStreamResult;
+use api_client::NDJSON_CONTENT_TYPE;
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::{Stream, StreamExt};
+use std::convert::Infallible;
+
+pub async fn handler(State(state): State<AppState>, headers: HeaderMap, body: Body) -> AppResult<Response> {
+    match content_type(&headers) {
+        Some(content_type) if content_type == NDJSON_CONTENT_TYPE => {}
+        None if !state.config.strict_content_type => {}
+        _ => {
+            return Err(AppError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                format!("Unsupported content type, expected {}", NDJSON_CONTENT_TYPE),
+            ))
+        }
+    }
+
+    let reader = LineReader::new(
+        body.into_data_stream(),
+        state.config.submit_stream_max_line_bytes,
+        state.config.submit_stream_max_lines,
+    );
+    let concurrency = state.config.submit_stream_concurrency;
+    let results = reader
+        .into_stream()
+        .map(move |(line, read)| submit_line(state.clone(), line, read))
+        .buffered(concurrency)
+        .map(|result| {
+            let mut encoded = serde_json::to_vec(&result).expect("results serialize");
+            encoded.push(b'\n');
+            Ok::<_, Infallible>(Bytes::from(encoded))
+        });
+
+    let mut response = Body::from_stream(results).into_response();
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(NDJSON_CONTENT_TYPE));
+    Ok(response)
+}
+
+/// Submit one line, answering with what the single submit endpoint would have
+async fn submit_line(state: AppState, line: u64, read: Result<Bytes, AppError>) -> SubmitStreamResult {
+    let request = match read {
+        Ok(text) => serde_json::from_slice::<SubmitTransactionRequest>(&text).map_err(invalid_json),
+        Err(err) => Err(err),
+    };
+    let outcome = match request {
+        // Detached like a single submit, so a client hanging up mid-stream
+        // cannot leave a row without its queue entry
+        Ok(request) => {
+            detach(move |cancel| async move {
+                submit(&state, &request, &cancel)
+                    .await
+                    .map_err(|err| err.for_account(&request.account_id))
+            })
+            .await
+        }
+        Err(err) => Err(err),
+    };
+
+    match outcome {
+        Ok(submitted) => SubmitStreamResult {
+            line,
+            status: submitted.status.as_u16(),
+            result: Some(submitted.json),
+            error: None,
+        },
+        Err(err) => {
+            if err.status.is_server_error() {
+                tracing::error!(
+                    line,
+                    status = err.status.as_u16(),
+                    account_id = err.account_id.as_deref(),
+                    "Submit-stream line failed: {}",
+                    err.message
+                );
+            }
+            SubmitStreamResult {
+                line,
+                status: err.status.as_u16(),
+                result: None,
+                error: Some(err.body()),
+            }
+        }
+    }
+}
+
+/// Splits a body into numbered, non-blank lines without holding more than
+/// one line at a time
+struct LineReader<S> {
+    body: S,
+    buffer: Vec<u8>,
+    /// Bytes of `buffer` already searched for a newline
+    scanned: usize,
+    max_line_bytes: usize,
+    max_lines: usize,
+    /// Physical lines taken from the buffer, blank ones included
+    line: u64,
+    /// Non-blank lines handed out
+    lines: usize,
+    /// The line being buffered is already too long; the rest of it is dropped
+    oversized: bool,
+    /// The body has ended; what is left in the buffer is the last line
+    eof: bool,
+    /// Nothing more will be read
+    done: bool,
+}
+
+impl<S> LineReader<S>
+where
+    S: Stream<Item = Result<Bytes, axum::Error>> + Unpin,
+{
+    fn new(body: S, max_line_bytes: usize, max_lines: usize) -> Self {
+        Self {
+            body,
+            buffer: Vec::new(),
+            scanned: 0,
+            max_line_bytes,
+            max_lines,
+            line: 0,
+            lines: 0,
+            oversized: false,
+            eof: false,
+            done: false,
+        }
+    }
+
+    fn into_stream(self) -> impl Stream<Item = (u64, Result<Bytes, AppError>)> {
+        futures::stream::unfold(self, |mut reader| async move {
+            let next = reader.next_line().await?;
+            Some((next, reader))
+        })
+    }
+
+    /// The next non-blank line and its number, or the error taking its place
+    async fn next_line(&mut self) -> Option<(u64, Result<Bytes, AppError>)> {
+        while !self.done {
+            if let Some(end) = self.buffer[self.scanned..].iter().position(|&b| b == b'\n') {
+                let rest = self.buffer.split_off(self.scanned + end + 1);
+                let mut text = std::mem::replace(&mut self.buffer, rest);
+                text.pop();
+                self.scanned = 0;
+                self.line += 1;
+                if let Some(next) = self.take(text) {
+                    return Some(next);
+                }
+                continue;
+            }
+            if self.eof {
+                break;
+            }
+            self.scanned = self.buffer.len();
+            // Keep reading to the end of an overlong line, but not into memory
+            if self.buffer.len() > self.max_line_bytes {
+                self.oversized = true;
+                self.buffer.clear();
+                self.scanned = 0;
+            }
+            match self.body.next().await {
+                Some(Ok(chunk)) => self.buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    self.done = true;
+                    let err = AppError::bad_request(format!("Failed to read request body: {}", e));
+                    return Some((self.line + 1, Err(err)));
+                }
+                None => {
+                    self.eof = true;
+                    // A last line without a trailing newline still counts
+                    if !self.buffer.is_empty() || self.oversized {
+                        self.buffer.push(b'\n');
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Result for one physical line, without its newline; `None` for a blank one
+    fn take(&mut self, text: Vec<u8>) -> Option<(u64, Result<Bytes, AppError>)> {
+        let oversized = std::mem::take(&mut self.oversized) || text.len() > self.max_line_bytes;
+        if !oversized && text.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+        self.lines += 1;
+        if self.lines > self.max_lines {
+            self.done = true;
+            let err = AppError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Too many lines: at most {} are read per request", self.max_lines),
+            );
+            return Some((self.line, Err(err)));
+        }
+        if oversized {
+            let err = AppError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Line too long: at most {} bytes", self.max_line_bytes),
+            );
+            return Some((self.line, Err(err)));
+        }
+        Some((self.line, Ok(Bytes::from(text))))
+    }
+}
@@ -0,0 +1,98 @@
+use crate::config::WarmupConfig;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use futures::future::{join_all, try_join_all};
+use postgres_models::schema::rate_limits;
+use postgres_models::{DbPool, MIN_IDLE_CONNECTIONS};
+use redis_cache::RedisPool;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// What the warm-up managed to do before finishing or running out of budget
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WarmupReport {
+    pub db_connections: usize,
+    pub redis_connections: usize,
+    pub hot_accounts_primed: usize,
+    pub timed_out: bool,
+}
+
+/// Open pool connections and prime hot lookups so the first requests after
+/// boot don't pay for connection setup.
+///
+/// Failures are logged and never stop startup; the pools connect lazily anyway.
+pub async fn run(db_pool: &DbPool, redis_pool: &RedisPool, config: &WarmupConfig) -> WarmupReport {
+    let started = Instant::now();
+    let mut report = WarmupReport::default();
+
+    let budget = Duration::from_millis(config.budget_ms);
+    let steps = async {
+        report.db_connections = warm_db(db_pool).await;
+        report.redis_connections = warm_redis(redis_pool, config.redis_connections).await;
+        report.hot_accounts_primed = prime_hot_accounts(db_pool, &config.hot_accounts).await;
+    };
+    report.timed_out = tokio::time::timeout(budget, steps).await.is_err();
+
+    if report.timed_out {
+        warn!(?report, "Warm-up ran out of its {}ms budget", config.budget_ms);
+    }
+    info!(?report, "Warm-up finished in {:?}", started.elapsed());
+    report
+}
+
+/// Check out the pool's idle connections at once so each is established and verified
+async fn warm_db(db_pool: &DbPool) -> usize {
+    let checkouts = (0..MIN_IDLE_CONNECTIONS).map(|_| async {
+        let mut conn = db_pool.get().await.ok()?;
+        diesel::select(diesel::dsl::sql::<diesel::sql_types::Integer>("1"))
+            .execute(&mut conn)
+            .await
+            .ok()
+    });
+    let warmed = join_all(checkouts).await.into_iter().flatten().count();
+    if warmed < MIN_IDLE_CONNECTIONS as usize {
+        warn!("Warm-up established {} of {} database connections", warmed, MIN_IDLE_CONNECTIONS);
+    }
+    warmed
+}
+
+/// Hold `count` Redis connections at the same time, then return them all to the pool
+async fn warm_redis(redis_pool: &RedisPool, count: usize) -> usize {
+    let count = count.min(redis_pool.status().max_size);
+    let checkouts = (0..count).map(|_| async {
+        let mut conn = redis_pool.get().await?;
+        let _: String = deadpool_redis::redis::cmd("PING").query_async(&mut *conn).await?;
+        Ok::<_, redis_cache::RedisError>(conn)
+    });
+
+    match try_join_all(checkouts).await {
+        Ok(connections) => connections.len(),
+        Err(e) => {
+            warn!("Redis warm-up failed: {}", e);
+            redis_pool.status().available
+        }
+    }
+}
+
+/// Run the per-account limit lookup for hot accounts so the rows are cached by Postgres
+async fn prime_hot_accounts(db_pool: &DbPool, accounts: &[String]) -> usize {
+    if accounts.is_empty() {
+        return 0;
+    }
+
+    let Ok(mut conn) = db_pool.get().await else {
+        return 0;
+    };
+    match rate_limits::table
+        .filter(rate_limits::account_id.eq_any(accounts))
+        .select(rate_limits::account_id)
+        .load::<String>(&mut conn)
+        .await
+    {
+        Ok(_) => accounts.len(),
+        Err(e) => {
+            warn!("Failed to prime hot account limits: {}", e);
+            0
+        }
+    }
+}
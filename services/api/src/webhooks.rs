@@ -0,0 +1,96 @@
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::{Duration, Instant};
+
+/// Events an endpoint can subscribe to
+pub const WEBHOOK_EVENT_TYPES: &[&str] = &["transaction.completed", "transaction.failed"];
+
+/// Event type of the sample delivery sent by the test-fire route
+pub const TEST_EVENT_TYPE: &str = "webhook.test";
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const EVENT_HEADER: &str = "x-webhook-event";
+
+const SECRET_PREFIX: &str = "whsec_";
+const SECRET_BYTES: usize = 32;
+
+/// Random signing secret returned once when a webhook is created
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", SECRET_PREFIX, hex::encode(bytes))
+}
+
+/// Signature header value for a delivery: `t=<unix seconds>,v1=<hex hmac>`.
+///
+/// The HMAC-SHA256 covers `"{timestamp}.{body}"` so a captured delivery
+/// cannot be replayed with a different timestamp.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Outcome of a single delivery attempt
+#[derive(Debug, Serialize)]
+pub struct DeliveryResult {
+    pub delivered: bool,
+    /// Status returned by the endpoint, absent when no response was received
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// POST a signed event to an endpoint and report what happened
+pub async fn deliver(
+    url: &str,
+    secret: &str,
+    event_type: &str,
+    payload: &serde_json::Value,
+    timeout: Duration,
+) -> DeliveryResult {
+    let started = Instant::now();
+    let body = payload.to_string();
+    let signature = sign(secret, chrono::Utc::now().timestamp(), body.as_bytes());
+
+    let client = match reqwest::Client::builder().timeout(timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            return DeliveryResult {
+                delivered: false,
+                status_code: None,
+                error: Some(e.to_string()),
+                duration_ms: 0,
+            }
+        }
+    };
+
+    let result = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .header(EVENT_HEADER, event_type)
+        .body(body)
+        .send()
+        .await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(response) => DeliveryResult {
+            delivered: response.status().is_success(),
+            status_code: Some(response.status().as_u16()),
+            error: None,
+            duration_ms,
+        },
+        Err(e) => DeliveryResult {
+            delivered: false,
+            status_code: None,
+            error: Some(e.to_string()),
+            duration_ms,
+        },
+    }
+}
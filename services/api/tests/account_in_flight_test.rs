@@ -0,0 +1,68 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::isolated::{IsolatedApp, TestResponse};
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+async fn account_queue(app: &IsolatedApp, account_id: &str, query: &str) -> TestResponse {
+    let request = Request::get(format!("/v1/accounts/{}/queue{}", account_id, query))
+        .body(Body::empty())
+        .unwrap();
+    app.request(request).await
+}
+
+fn listed_ids(body: &Value) -> Vec<String> {
+    body["transactions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|transaction| transaction["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+/// Test the listing pages through in-flight rows by priority, counts them per status and reads the limit
+#[tokio::test]
+async fn test_account_queue_lists_in_flight() {
+    let app = IsolatedApp::new().await;
+    let mut submitted = Vec::new();
+    for (n, priority) in [0, 5, 0, -2, 5].into_iter().enumerate() {
+        let (id, _, _) = app.submit_transaction_expect_success("acct_flight", json!({ "n": n }), Some(priority)).await;
+        submitted.push(id);
+    }
+    app.submit_transaction_expect_success("acct_other", json!({ "n": 0 }), None).await;
+    let mut conn = app.state.db_pool.get().await.expect("Failed to get connection");
+    let claimed: Uuid = submitted[2].parse().unwrap();
+    TransactionQueue::transition_status(&mut conn, claimed, TransactionStatus::Pending, TransactionStatus::Processing)
+        .await
+        .unwrap();
+    drop(conn);
+
+    let response = account_queue(&app, "acct_flight", "").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let body = &response.body;
+    assert_eq!(body["counts"], json!({ "pending": 4, "processing": 1, "retry": 0 }));
+    let by_priority: Vec<String> = [1, 4, 0, 2, 3].iter().map(|&i| submitted[i].clone()).collect();
+    assert_eq!(listed_ids(body), by_priority);
+    assert_eq!(body["next_offset"], Value::Null);
+    let rate_limit = body["rate_limit"].clone();
+    assert_eq!(rate_limit["remaining"], rate_limit["limit"].as_u64().unwrap() - 5);
+
+    // Looking again uses none of the submit limit
+    let response = account_queue(&app, "acct_flight", "?limit=2&offset=2").await;
+    assert_eq!(response.body["rate_limit"], rate_limit);
+    assert_eq!(listed_ids(&response.body), by_priority[2..4]);
+    assert_eq!(response.body["next_offset"], 4);
+
+    let response = account_queue(&app, "acct_flight", "?status=processing").await;
+    assert_eq!(listed_ids(&response.body), [submitted[2].clone()]);
+    assert_eq!(response.body["counts"]["pending"], 4);
+
+    for query in ["?status=completed", "?limit=0", "?offset=-1"] {
+        assert_eq!(account_queue(&app, "acct_flight", query).await.status, StatusCode::BAD_REQUEST, "{}", query);
+    }
+
+    app.teardown().await;
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::isolated::{IsolatedApp, TestResponse};
+use serde_json::json;
+
+const ADMIN_KEY: &str = "queue-admin-key";
+
+async fn account_queue(app: &IsolatedApp, account_id: &str, query: &str) -> TestResponse {
+    let request = Request::get(format!("/v1/admin/accounts/{}/queue{}", account_id, query))
+        .header("x-admin-key", ADMIN_KEY)
+        .body(Body::empty())
+        .unwrap();
+    app.request(request).await
+}
+
+/// Test each account's listing holds only its items, in processing order, with their global ranks
+#[tokio::test]
+async fn test_interleaved_accounts_listed_separately() {
+    let app = IsolatedApp::with_vars(&[("ADMIN_API_KEYS", "support:queue-admin-key")]).await;
+
+    // Interleaved, with priorities that reorder them across accounts
+    let submits = [
+        ("acct_a", 0),
+        ("acct_b", 0),
+        ("acct_a", 5),
+        ("acct_b", 5),
+        ("acct_a", -3),
+        ("acct_b", 0),
+        ("acct_a", 0),
+    ];
+    let mut submitted = Vec::new();
+    for (i, (account_id, priority)) in submits.into_iter().enumerate() {
+        let (id, _, _) = app.submit_transaction_expect_success(account_id, json!({ "n": i }), Some(priority)).await;
+        submitted.push((id, account_id, priority));
+    }
+    let global = app.queue_order().await;
+    assert_eq!(global.len(), submits.len());
+
+    for account_id in ["acct_a", "acct_b"] {
+        let response = account_queue(&app, account_id, "").await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(response.body["account_id"], account_id);
+        let items = response.body["items"].as_array().unwrap();
+
+        let expected: Vec<&String> = global
+            .iter()
+            .filter(|id| submitted.iter().any(|(other, account, _)| other == *id && *account == account_id))
+            .collect();
+        let listed: Vec<&str> = items.iter().map(|item| item["transaction_id"].as_str().unwrap()).collect();
+        assert_eq!(listed, expected, "{}", account_id);
+
+        for item in items {
+            let id = item["transaction_id"].as_str().unwrap();
+            let rank = global.iter().position(|other| other == id).unwrap() + 1;
+            assert_eq!(item["rank"], rank, "{}", id);
+            let (_, _, priority) = submitted.iter().find(|(other, _, _)| other == id).unwrap();
+            assert_eq!(item["priority"], *priority, "{}", id);
+            assert!(item["age_ms"].as_u64().unwrap() < 60_000, "{}", item);
+        }
+    }
+
+    // Limited listings are the head of the full one
+    let response = account_queue(&app, "acct_a", "?limit=2").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["items"].as_array().unwrap().len(), 2);
+    let response = account_queue(&app, "acct_a", "?limit=0").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", response.body);
+
+    let response = account_queue(&app, "acct_idle", "").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["items"], json!([]));
+    app.teardown().await;
+}
+
+/// Test items leave their account's listing once processed, and ranks move up
+#[tokio::test]
+async fn test_dequeued_items_leave_listing() {
+    let app = IsolatedApp::with_vars(&[("ADMIN_API_KEYS", "support:queue-admin-key")]).await;
+    let (first, _, _) = app.submit_transaction_expect_success("acct_a", json!({ "n": 1 }), None).await;
+    let (second, _, _) = app.submit_transaction_expect_success("acct_b", json!({ "n": 2 }), None).await;
+    let (third, _, _) = app.submit_transaction_expect_success("acct_a", json!({ "n": 3 }), None).await;
+
+    let popped = app
+        .state
+        .queue_manager()
+        .dequeue_envelope(&transaction_queue_api::TRANSACTION_QUEUE)
+        .await
+        .unwrap()
+        .expect("queue is empty");
+    assert_eq!(popped.transaction_id, first);
+
+    let response = account_queue(&app, "acct_a", "").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let items = response.body["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1, "{}", response.body);
+    assert_eq!(items[0]["transaction_id"], third);
+    assert_eq!(items[0]["rank"], 2);
+
+    let response = account_queue(&app, "acct_b", "").await;
+    assert_eq!(response.body["items"][0]["transaction_id"], second);
+    assert_eq!(response.body["items"][0]["rank"], 1);
+    app.teardown().await;
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::isolated::{IsolatedApp, TestResponse};
+use common::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{upgrade_account_tier, Account, AccountTier, NewRateLimit};
+use postgres_models::schema::rate_limits;
+use serde_json::{json, Value};
+use transaction_queue_api::rate_limit::{DEFAULT_SUBMIT_LIMIT, SOFT_LIMIT_PCT_TYPE, SUBMIT_LIMIT_TYPE};
+
+const ADMIN_KEY: &str = "tier-admin-key";
+
+async fn tier_app() -> IsolatedApp {
+    IsolatedApp::with_vars(&[("ADMIN_API_KEYS", "ops:tier-admin-key")]).await
+}
+
+async fn change_tier(app: &IsolatedApp, account_id: &str, tier: &str) -> TestResponse {
+    let request = Request::post(format!("/v1/admin/accounts/{}/tier", account_id))
+        .header("x-admin-key", ADMIN_KEY)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "tier": tier }).to_string()))
+        .unwrap();
+    app.request(request).await
+}
+
+fn template_max(tier: AccountTier, limit_type: &str) -> i64 {
+    let limit = tier.template().limits.iter().find(|limit| limit.limit_type == limit_type).unwrap();
+    limit.max_requests as i64
+}
+
+fn limit_header(response: &TestResponse) -> i64 {
+    let value = response.headers.get("x-ratelimit-limit").expect("Missing x-ratelimit-limit header");
+    value.to_str().unwrap().parse().unwrap()
+}
+
+/// The summary's entry for `limit_type`
+fn limit_change<'a>(change: &'a Value, limit_type: &str) -> &'a Value {
+    change["limits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|limit| limit["limit_type"] == limit_type)
+        .unwrap_or_else(|| panic!("No {} limit in {}", limit_type, change))
+}
+
+/// Test a tier change rewrites the template's rows, keeps others and reports what changed
+#[tokio::test]
+async fn test_upgrade_rewrites_template_limits() {
+    let app = tier_app().await;
+    let mut conn = app.state.db_pool.get().await.unwrap();
+    diesel::insert_into(rate_limits::table)
+        .values(&NewRateLimit::new("acct_rows".to_string(), SOFT_LIMIT_PCT_TYPE.to_string(), 90, 60))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let created = upgrade_account_tier(&mut conn, "acct_rows", AccountTier::Basic).await.unwrap();
+    assert_eq!(created.previous_tier, None);
+    assert_eq!(created.tier, "basic");
+    assert!(created.limits.iter().all(|limit| limit.previous_max_requests.is_none()));
+
+    let upgraded = upgrade_account_tier(&mut conn, "acct_rows", AccountTier::Enterprise).await.unwrap();
+    let template = AccountTier::Enterprise.template();
+    assert_eq!(upgraded.previous_tier.as_deref(), Some("basic"));
+    assert_eq!(upgraded.previous_default_priority, Some(AccountTier::Basic.template().default_priority));
+    assert_eq!(upgraded.default_priority, template.default_priority);
+    assert_eq!(upgraded.limits.len(), template.limits.len());
+    for (change, limit) in upgraded.limits.iter().zip(template.limits) {
+        assert_eq!(change.limit_type, limit.limit_type);
+        assert_eq!(change.max_requests, limit.max_requests);
+        assert_eq!(change.window_seconds, limit.window_seconds);
+        assert_eq!(
+            change.previous_max_requests.map(i64::from),
+            Some(template_max(AccountTier::Basic, limit.limit_type))
+        );
+    }
+
+    let account = Account::find(&mut conn, "acct_rows").await.unwrap().unwrap();
+    assert_eq!(account.tier, "enterprise");
+
+    let response = app
+        .request(
+            Request::get("/v1/admin/accounts/acct_rows/limits")
+                .header("x-admin-key", ADMIN_KEY)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let soft = response.body.as_array().unwrap().iter().find(|row| row["limit_type"] == SOFT_LIMIT_PCT_TYPE);
+    assert_eq!(soft.expect("soft limit row should be kept")["max_requests"], 90);
+
+    drop(conn);
+    app.teardown().await;
+}
+
+/// Test an upgrade while the account is submitting applies to its very next submit
+#[tokio::test]
+async fn test_upgrade_mid_traffic_applies_to_next_submit() {
+    let app = tier_app().await;
+    let response = change_tier(&app, "acct_upgrade", "basic").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let basic_limit = template_max(AccountTier::Basic, SUBMIT_LIMIT_TYPE);
+    for _ in 0..basic_limit {
+        let response = app.submit_transaction("acct_upgrade", TestData::sample_transaction_data(), None).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(limit_header(&response), basic_limit);
+    }
+
+    // Keep the account submitting, and being rejected, while the tier changes
+    let traffic = async {
+        for _ in 0..20 {
+            let response = app.submit_transaction("acct_upgrade", TestData::sample_transaction_data(), None).await;
+            assert!(response.status == StatusCode::OK || response.status == StatusCode::TOO_MANY_REQUESTS);
+        }
+    };
+    let upgrade = async {
+        let response = change_tier(&app, "acct_upgrade", "enterprise").await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let submit = limit_change(&response.body, SUBMIT_LIMIT_TYPE);
+        assert_eq!(submit["previous_max_requests"], basic_limit);
+        assert_eq!(submit["max_requests"], template_max(AccountTier::Enterprise, SUBMIT_LIMIT_TYPE));
+
+        let next = app.submit_transaction("acct_upgrade", TestData::sample_transaction_data(), None).await;
+        assert_eq!(next.status, StatusCode::OK, "{}", next.body);
+        assert_eq!(limit_header(&next), template_max(AccountTier::Enterprise, SUBMIT_LIMIT_TYPE));
+    };
+    futures::join!(traffic, upgrade);
+    app.teardown().await;
+}
+
+/// Test a downgrade past the new limit rejects the very next submit
+#[tokio::test]
+async fn test_downgrade_applies_to_next_submit() {
+    let app = tier_app().await;
+    assert_eq!(change_tier(&app, "acct_downgrade", "enterprise").await.status, StatusCode::OK);
+
+    // Prime every cache with the enterprise limit, then use more than basic allows
+    let basic_limit = template_max(AccountTier::Basic, SUBMIT_LIMIT_TYPE);
+    for _ in 0..=basic_limit {
+        let response = app.submit_transaction("acct_downgrade", TestData::sample_transaction_data(), None).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    assert_eq!(change_tier(&app, "acct_downgrade", "basic").await.status, StatusCode::OK);
+    let next = app.submit_transaction("acct_downgrade", TestData::sample_transaction_data(), None).await;
+    assert_eq!(next.status, StatusCode::TOO_MANY_REQUESTS, "{}", next.body);
+    assert_eq!(limit_header(&next), basic_limit);
+    app.teardown().await;
+}
+
+/// Test unknown tiers are refused without touching the account, and every change is audited
+#[tokio::test]
+async fn test_unknown_tier_and_audit() {
+    let app = tier_app().await;
+
+    let response = change_tier(&app, "acct_audit", "platinum").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", response.body);
+    let next = app.submit_transaction("acct_audit", TestData::sample_transaction_data(), None).await;
+    assert_eq!(limit_header(&next), DEFAULT_SUBMIT_LIMIT as i64);
+
+    assert_eq!(change_tier(&app, "acct_audit", "premium").await.status, StatusCode::OK);
+
+    let response = app
+        .request(
+            Request::get("/v1/admin/audit-log?account_id=acct_audit")
+                .header("x-admin-key", ADMIN_KEY)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let entries = response.body["entries"].as_array().unwrap();
+    let statuses: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry["event_type"] == "POST /v1/admin/accounts/:account_id/tier")
+        .map(|entry| entry["status_code"].as_i64().unwrap())
+        .collect();
+    assert_eq!(statuses, vec![200, 400]);
+    app.teardown().await;
+}
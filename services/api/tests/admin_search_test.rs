@@ -0,0 +1,161 @@
+mod common;
+
+use common::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{NewTransactionQueue, TransactionQueue, TransactionSearch, TransactionStatus};
+use postgres_models::schema::transaction_queue;
+use postgres_models::sources::{RandomIds, SystemClock};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// Insert one row per seed for `account_id`, with the seed in the payload
+async fn seed_rows(conn: &mut postgres_models::DbConnection, account_id: &str, seeds: &[&str]) {
+    let rows: Vec<NewTransactionQueue> = seeds
+        .iter()
+        .map(|seed| {
+            let mut data = TestData::sample_transaction_data();
+            data["seed"] = json!(seed);
+            data["meta"] = json!({ "user": format!("user_{}", seed) });
+            NewTransactionQueue::new(account_id.to_string(), data, &RandomIds, &SystemClock)
+        })
+        .collect();
+    diesel::insert_into(transaction_queue::table)
+        .values(&rows)
+        .execute(conn)
+        .await
+        .expect("Failed to seed rows");
+}
+
+/// Test containment matches exactly the row with the seed, alone and combined with other filters
+#[tokio::test]
+async fn test_search_matches_payload_containment() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+    let seed = format!("vault_{}", account_id);
+    seed_rows(&mut conn, &account_id, &[&seed, "other_a", "other_b"]).await;
+
+    let search = TransactionSearch {
+        payload: Some(json!({ "seed": seed })),
+        ..Default::default()
+    };
+    let rows = TransactionQueue::search(&mut conn, &search, 10, 0).await.unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].account_id, account_id);
+    assert_eq!(rows[0].transaction_data["seed"], seed.as_str());
+
+    // Nested fields and the other filters narrow the same way
+    let search = TransactionSearch {
+        account_id: Some(account_id.clone()),
+        status: Some(TransactionStatus::Pending),
+        payload: Some(json!({ "meta": { "user": "user_other_a" } })),
+        ..Default::default()
+    };
+    let rows = TransactionQueue::search(&mut conn, &search, 10, 0).await.unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].transaction_data["seed"], "other_a");
+
+    let search = TransactionSearch {
+        account_id: Some(account_id.clone()),
+        status: Some(TransactionStatus::Completed),
+        ..Default::default()
+    };
+    assert!(TransactionQueue::search(&mut conn, &search, 10, 0).await.unwrap().is_empty());
+}
+
+/// Test results page newest first without overlap
+#[tokio::test]
+async fn test_search_pages() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+    seed_rows(&mut conn, &account_id, &["a", "b", "c"]).await;
+
+    let search = TransactionSearch {
+        account_id: Some(account_id),
+        ..Default::default()
+    };
+    let first = TransactionQueue::search(&mut conn, &search, 2, 0).await.unwrap();
+    let second = TransactionQueue::search(&mut conn, &search, 2, 2).await.unwrap();
+    assert_eq!(first.len(), 2);
+    assert_eq!(second.len(), 1);
+    assert!(first[0].created_at >= first[1].created_at);
+    assert!(first.iter().all(|row| row.id != second[0].id));
+}
+
+/// Test only account and payload filters count as selective
+#[test]
+fn test_selective_filters() {
+    assert!(!TransactionSearch::default().is_selective());
+    assert!(!TransactionSearch {
+        status: Some(TransactionStatus::Failed),
+        created_after: Some(chrono::Utc::now()),
+        ..Default::default()
+    }
+    .is_selective());
+    assert!(TransactionSearch {
+        account_id: Some("acct".to_string()),
+        ..Default::default()
+    }
+    .is_selective());
+    assert!(TransactionSearch {
+        payload: Some(json!({ "seed": "x" })),
+        ..Default::default()
+    }
+    .is_selective());
+}
+
+/// Test the endpoint translates payload.<path> params into a containment search
+#[tokio::test]
+async fn test_admin_search_by_payload_field() {
+    TestEnvironment::validate_test_environment().await;
+
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+    let seed = format!("user_vault_test_{}", account_id);
+    seed_rows(&mut conn, &account_id, &[&seed, "unrelated"]).await;
+
+    let client = TestClient::new();
+    let response = client
+        .admin_request(
+            Method::GET,
+            &format!("/transactions/search?payload.seed={}&limit=5", seed),
+            ADMIN_API_KEY,
+            None,
+        )
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.expect("Failed to parse JSON response");
+    assert_eq!(body["count"], 1);
+    assert_eq!(body["limit"], 5);
+    assert!(body["next_offset"].is_null());
+    assert_eq!(body["transactions"][0]["account_id"], account_id.as_str());
+    assert_eq!(body["transactions"][0]["transaction_data"]["seed"], seed.as_str());
+}
+
+/// Test searches without a selective filter, and out of range pages, are rejected
+#[tokio::test]
+async fn test_admin_search_rejects_unbounded_queries() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    for query in ["", "?status=pending", "?created_after=2024-01-01T00:00:00Z"] {
+        let response = client
+            .admin_request(Method::GET, &format!("/transactions/search{}", query), ADMIN_API_KEY, None)
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "query {:?}", query);
+        let body: Value = response.json().await.expect("Failed to parse JSON response");
+        assert_eq!(body["error"]["code"], "search_filter_required");
+    }
+
+    for query in ["account_id=a&limit=0", "account_id=a&limit=1000", "account_id=a&offset=5000", "acount_id=a"] {
+        let response = client
+            .admin_request(Method::GET, &format!("/transactions/search?{}", query), ADMIN_API_KEY, None)
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "query {:?}", query);
+    }
+}
@@ -0,0 +1,210 @@
+mod common;
+
+use common::*;
+use deadpool_redis::redis::AsyncCommands;
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+/// Test admin endpoints reject missing and unknown keys
+#[tokio::test]
+async fn test_admin_requires_valid_key() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let path = format!("/accounts/{}/limits", TestData::unique_account_id());
+
+    let response = client
+        .admin_request(Method::GET, &path, "not-a-real-key", None)
+        .await
+        .expect("Failed to send request");
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await.expect("Failed to parse JSON response");
+    assert!(body["error"]["message"].is_string(), "Missing error envelope");
+}
+
+/// Test creating, listing and deleting an account limit
+#[tokio::test]
+async fn test_admin_limits_crud() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+    let path = format!("/accounts/{}/limits/submit", account_id);
+
+    let response = client
+        .admin_request(
+            Method::PUT,
+            &path,
+            ADMIN_API_KEY,
+            Some(json!({ "max_requests": 10, "window_seconds": 60 })),
+        )
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .admin_request(Method::GET, &format!("/accounts/{}/limits", account_id), ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+    let limits: Value = response.json().await.expect("Failed to parse JSON response");
+    assert_eq!(limits.as_array().map(Vec::len), Some(1));
+    assert_eq!(limits[0]["max_requests"], 10);
+
+    let response = client
+        .admin_request(Method::DELETE, &path, ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = client
+        .admin_request(Method::DELETE, &path, ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test invalid limit values are rejected
+#[tokio::test]
+async fn test_admin_limits_validation() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let path = format!("/accounts/{}/limits/submit", TestData::unique_account_id());
+
+    for body in [
+        json!({ "max_requests": -1, "window_seconds": 60 }),
+        json!({ "max_requests": 10, "window_seconds": 0 }),
+        json!({ "max_requests": 10, "window_seconds": 86_400 * 30 + 1 }),
+    ] {
+        let response = client
+            .admin_request(Method::PUT, &path, ADMIN_API_KEY, Some(body))
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+/// Test the admin scope is limited independently of customer submit limits
+#[tokio::test]
+async fn test_admin_rate_limit_scope_is_separate() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let path = format!("/accounts/{}/limits", TestData::unique_account_id());
+
+    // Exhaust the admin scope for the dedicated rate limit test identity (default 60/min)
+    let mut successful = 0;
+    let mut rate_limited = false;
+    for _ in 0..70 {
+        let response = client
+            .admin_request(Method::GET, &path, ADMIN_RATE_LIMIT_TEST_KEY, None)
+            .await
+            .expect("Failed to send request");
+
+        match response.status() {
+            StatusCode::OK => successful += 1,
+            StatusCode::TOO_MANY_REQUESTS => {
+                assert!(
+                    response.headers().contains_key("x-ratelimit-limit"),
+                    "Missing X-RateLimit-Limit header"
+                );
+                rate_limited = true;
+                break;
+            }
+            status => panic!("Unexpected status code: {}", status),
+        }
+    }
+
+    assert!(rate_limited, "Admin scope should eventually be rate limited");
+    assert!(successful <= 60, "Admin scope allowed {} calls", successful);
+
+    // Another admin identity is unaffected
+    let response = client
+        .admin_request(Method::GET, &path, ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Customer submits under the same name use their own limit
+    let response = client
+        .submit_transaction("ratelimit_test", TestData::sample_transaction_data(), None)
+        .await
+        .expect("Failed to send request");
+    assert_ne!(
+        response.status(),
+        StatusCode::TOO_MANY_REQUESTS,
+        "Customer submit limits should not share the admin scope"
+    );
+}
+
+/// Test quarantined queue members are listed and deleted by id
+#[tokio::test]
+async fn test_admin_quarantine_list_and_delete() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let queue_manager = redis_cache::QueueManager::new(TestEnvironment::redis_pool().await);
+    let entry = queue_manager
+        .quarantine(&transaction_queue_api::TRANSACTION_QUEUE, "garbage", "bad envelope")
+        .await
+        .expect("Failed to quarantine member");
+
+    let response = client
+        .admin_request(Method::GET, "/queue/quarantine?limit=1000", ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+    let members: Value = response.json().await.expect("Failed to parse JSON response");
+    let listed = members
+        .as_array()
+        .expect("Expected a list")
+        .iter()
+        .find(|member| member["id"] == entry.id.as_str())
+        .expect("Quarantined member not listed");
+    assert_eq!(listed["member"], "garbage");
+    assert_eq!(listed["error"], "bad envelope");
+
+    let path = format!("/queue/quarantine/{}", entry.id);
+    let response = client
+        .admin_request(Method::DELETE, &path, ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = client
+        .admin_request(Method::DELETE, &path, ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test the maintenance sweep gives a rate limit key without a TTL one
+#[tokio::test]
+async fn test_admin_repairs_rate_limit_expiry() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let pool = TestEnvironment::redis_pool().await;
+    let key = format!("rate_limit:admin_test:{}", TestData::unique_account_id());
+    let mut conn = pool.get().await.expect("Failed to get Redis connection");
+    let _: () = conn.zadd(&key, "member", 1).await.expect("Failed to write key");
+
+    let response = client
+        .admin_request(Method::POST, "/maintenance/rate-limit-expiry?ttl_seconds=600", ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+    let repair: Value = response.json().await.expect("Failed to parse JSON response");
+    assert!(repair["repaired"].as_u64().unwrap() >= 1);
+
+    let ttl: i64 = conn.ttl(&key).await.expect("Failed to read TTL");
+    assert!(ttl > 0 && ttl <= 600, "ttl {}", ttl);
+
+    let response = client
+        .admin_request(Method::POST, "/maintenance/rate-limit-expiry?ttl_seconds=0", ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use chrono::{TimeDelta, Utc};
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{AuditCursor, AuditLog, AuditLogFilter};
+use postgres_models::schema::audit_log;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const ROWS: usize = 3000;
+const PAGE: i64 = 400;
+
+/// Insert `ROWS` entries for one account, many sharing a timestamp so pages
+/// have to break ties on id. Every third entry is a pause.
+async fn insert_entries(conn: &mut postgres_models::DbConnection, account_id: &str) -> HashSet<Uuid> {
+    let base = Utc::now() - TimeDelta::hours(1);
+    let rows: Vec<_> = (0..ROWS)
+        .map(|i| {
+            let event_type = if i % 3 == 0 { "PUT /pause" } else { "GET /limits" };
+            (
+                audit_log::id.eq(Uuid::new_v4()),
+                audit_log::actor.eq("audit_test"),
+                audit_log::method.eq("GET"),
+                audit_log::path.eq("/test"),
+                audit_log::status_code.eq(200),
+                audit_log::created_at.eq(base + TimeDelta::seconds((i / 7) as i64)),
+                audit_log::event_type.eq(Some(event_type)),
+                audit_log::account_id.eq(Some(account_id)),
+            )
+        })
+        .collect();
+    let mut ids = HashSet::new();
+    for chunk in rows.chunks(1000) {
+        let inserted: Vec<Uuid> = diesel::insert_into(audit_log::table)
+            .values(chunk)
+            .returning(audit_log::id)
+            .get_results(conn)
+            .await
+            .expect("Failed to insert audit entries");
+        ids.extend(inserted);
+    }
+    ids
+}
+
+/// Page through `filter` to the end, checking each page on the way
+async fn collect_pages(conn: &mut postgres_models::DbConnection, filter: &AuditLogFilter) -> Vec<AuditLog> {
+    let mut entries: Vec<AuditLog> = Vec::new();
+    let mut after = None;
+    loop {
+        let started = Instant::now();
+        let page = AuditLog::list(conn, filter, after, PAGE).await.unwrap();
+        assert!(
+            started.elapsed() < Duration::from_secs(1),
+            "page took {:?}",
+            started.elapsed()
+        );
+        assert!(page.len() as i64 <= PAGE);
+        let Some(last) = page.last() else { break };
+        after = Some(AuditCursor::after(last));
+        entries.extend(page);
+    }
+    entries
+}
+
+/// Test keyset pages over thousands of entries are disjoint, complete and newest first
+#[tokio::test]
+async fn test_pages_are_disjoint_and_complete() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+    let inserted = insert_entries(&mut conn, &account_id).await;
+
+    let filter = AuditLogFilter {
+        account_id: Some(account_id.clone()),
+        ..Default::default()
+    };
+    let entries = collect_pages(&mut conn, &filter).await;
+
+    let seen: HashSet<Uuid> = entries.iter().map(|entry| entry.id).collect();
+    assert_eq!(entries.len(), ROWS, "an entry was returned twice");
+    assert_eq!(seen, inserted);
+    assert!(entries
+        .windows(2)
+        .all(|pair| (pair[0].created_at, pair[0].id) > (pair[1].created_at, pair[1].id)));
+
+    // Filters narrow the same walk
+    let pauses = collect_pages(
+        &mut conn,
+        &AuditLogFilter {
+            event_type: Some("PUT /pause".to_string()),
+            ..filter.clone()
+        },
+    )
+    .await;
+    assert_eq!(pauses.len(), ROWS.div_ceil(3));
+
+    let since = entries[ROWS / 2].created_at;
+    let recent = collect_pages(
+        &mut conn,
+        &AuditLogFilter {
+            since: Some(since),
+            ..filter
+        },
+    )
+    .await;
+    assert_eq!(recent.len(), entries.iter().filter(|entry| entry.created_at >= since).count());
+}
+
+/// Test cursors survive their text form
+#[test]
+fn test_cursor_round_trip() {
+    let cursor = AuditCursor {
+        created_at: "2024-05-01T12:00:00.123456Z".parse().unwrap(),
+        id: Uuid::new_v4(),
+    };
+    assert_eq!(AuditCursor::decode(&cursor.encode()), Some(cursor));
+    assert_eq!(AuditCursor::decode("not a cursor"), None);
+}
+
+/// Test the endpoint reports further pages in X-Has-More and next_cursor
+#[tokio::test]
+async fn test_audit_log_endpoint_pages() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+    let path = format!("/accounts/{}/limits", account_id);
+    for _ in 0..3 {
+        client
+            .admin_request(Method::GET, &path, ADMIN_API_KEY, None)
+            .await
+            .expect("Failed to send request");
+    }
+
+    let mut cursor: Option<String> = None;
+    let mut seen = Vec::new();
+    loop {
+        let mut query = format!("/audit-log?account_id={}&limit=2", account_id);
+        if let Some(cursor) = &cursor {
+            query.push_str(&format!("&cursor={}", cursor));
+        }
+        let response = client
+            .admin_request(Method::GET, &query, ADMIN_API_KEY, None)
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let has_more = response.headers()["X-Has-More"].to_str().unwrap() == "true";
+        let page: Value = response.json().await.expect("Failed to parse JSON response");
+        for entry in page["entries"].as_array().unwrap() {
+            assert_eq!(entry["event_type"], "GET /v1/admin/accounts/:account_id/limits");
+            seen.push(entry["id"].as_str().unwrap().to_string());
+        }
+        assert_eq!(has_more, page["next_cursor"].is_string());
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    assert_eq!(seen.len(), 3);
+
+    let response = client
+        .admin_request(Method::GET, "/audit-log?limit=100000", ADMIN_API_KEY, None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
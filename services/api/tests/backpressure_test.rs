@@ -0,0 +1,125 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use common::*;
+use redis_cache::RateLimitStrategy;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use transaction_queue_api::backpressure::{self, Backpressure, BackpressureLevel, BACKOFF_HINT_HEADER};
+use transaction_queue_api::config::Config;
+use transaction_queue_api::queue_stats::{consumer_lag_seconds, QueueStats};
+use transaction_queue_api::rate_limit::{LimitSource, SubmitLimit};
+use transaction_queue_api::v1;
+
+fn config() -> Config {
+    let lookup = |var: &str| match var {
+        "DATABASE_URL" => Some(DEFAULT_DATABASE_URL.to_string()),
+        "REDIS_URL" => Some(DEFAULT_REDIS_URL.to_string()),
+        _ => None,
+    };
+    Config::from_lookup(&service_config::Env::new(&lookup)).unwrap()
+}
+
+/// `queue_depth` items queued, draining at 10 per second
+fn stats(queue_depth: i64) -> QueueStats {
+    QueueStats {
+        queue_depth,
+        enqueue_rate_per_second: 10.0,
+        dequeue_rate_per_second: 10.0,
+        estimated_drain_seconds: consumer_lag_seconds(queue_depth, Some(10.0)),
+    }
+}
+
+/// 100 requests a minute, so 600ms apart
+const LIMIT: SubmitLimit = SubmitLimit {
+    max_requests: 100,
+    window_seconds: 60,
+    source: LimitSource::AccountConfig,
+    strategy: RateLimitStrategy::SlidingWindow,
+};
+
+/// Test the hint escalates as the queue deepens and clears once it drains
+#[test]
+fn test_hint_follows_queue_depth() {
+    let config = config();
+
+    assert_eq!(backpressure::assess(None, LIMIT, &config), Backpressure::NONE);
+    assert_eq!(backpressure::assess(Some(&stats(100)), LIMIT, &config), Backpressure::NONE);
+
+    // 300 seconds to drain: elevated, at the account's own spacing
+    let elevated = backpressure::assess(Some(&stats(3_000)), LIMIT, &config);
+    assert_eq!(elevated.level, BackpressureLevel::Elevated);
+    assert_eq!(elevated.suggested_delay_ms, 600);
+
+    // 1800 seconds: high, six times the spacing
+    let high = backpressure::assess(Some(&stats(18_000)), LIMIT, &config);
+    assert_eq!(high.level, BackpressureLevel::High);
+    assert_eq!(high.suggested_delay_ms, 3_600);
+
+    // Never past the cap, however deep
+    let deepest = backpressure::assess(Some(&stats(100_000_000)), LIMIT, &config);
+    assert_eq!(deepest.suggested_delay_ms, config.backoff_max_delay_ms);
+
+    assert_eq!(backpressure::assess(Some(&stats(0)), LIMIT, &config), Backpressure::NONE);
+}
+
+/// Test accounts with a larger limit are asked to wait less
+#[test]
+fn test_hint_scales_with_tier() {
+    let config = config();
+    let basic = SubmitLimit {
+        max_requests: 10,
+        window_seconds: 60,
+        source: LimitSource::TierDefault,
+        strategy: RateLimitStrategy::SlidingWindow,
+    };
+    let enterprise = SubmitLimit {
+        max_requests: 1000,
+        window_seconds: 60,
+        source: LimitSource::TierDefault,
+        strategy: RateLimitStrategy::SlidingWindow,
+    };
+
+    let deep = stats(6_000);
+    let basic = backpressure::assess(Some(&deep), basic, &config);
+    let enterprise = backpressure::assess(Some(&deep), enterprise, &config);
+    assert_eq!(basic.level, enterprise.level);
+    assert!(basic.suggested_delay_ms > enterprise.suggested_delay_ms);
+}
+
+async fn submit(app: &axum::Router) -> (String, Value) {
+    let request = Request::post("/transactions/submit")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            json!({
+                "account_id": TestData::unique_account_id(),
+                "transaction_data": TestData::sample_transaction_data(),
+            })
+            .to_string(),
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let hint = response.headers()[BACKOFF_HINT_HEADER].to_str().unwrap().to_string();
+    let body = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    (hint, body)
+}
+
+/// Test successful submits carry the hint from the cached queue stats
+#[tokio::test]
+async fn test_submit_reports_backpressure() {
+    let state = TestEnvironment::app_state().await;
+    let app = v1::router(state.clone()).with_state(state.clone());
+
+    state.queue_stats.set(stats(18_000));
+    let (hint, body) = submit(&app).await;
+    assert_eq!(body["backpressure"]["level"], "high");
+    assert_eq!(hint, body["backpressure"]["suggested_delay_ms"].to_string());
+    assert_ne!(hint, "0");
+
+    state.queue_stats.set(stats(0));
+    let (hint, body) = submit(&app).await;
+    assert_eq!(body["backpressure"], json!({ "level": "none", "suggested_delay_ms": 0 }));
+    assert_eq!(hint, "0");
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::NewTransactionQueue;
+use postgres_models::schema::transaction_queue;
+use postgres_models::sources::{RandomIds, SystemClock};
+use postgres_models::DbError;
+use serde_json::json;
+
+fn batch_rows(account_id: &str, count: usize) -> Vec<NewTransactionQueue> {
+    (0..count)
+        .map(|i| {
+            let mut data = TestData::sample_transaction_data();
+            data["sequence"] = json!(i);
+            NewTransactionQueue::new(account_id.to_string(), data, &RandomIds, &SystemClock)
+        })
+        .collect()
+}
+
+async fn count_rows(conn: &mut postgres_models::DbConnection, account_id: &str) -> i64 {
+    transaction_queue::table
+        .filter(transaction_queue::account_id.eq(account_id))
+        .count()
+        .get_result(conn)
+        .await
+        .expect("Failed to count rows")
+}
+
+/// Test an empty batch is a no-op
+#[tokio::test]
+async fn test_insert_batch_empty() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+
+    let inserted = NewTransactionQueue::insert_batch(&mut conn, &[])
+        .await
+        .expect("Empty batch should succeed");
+    assert!(inserted.is_empty());
+}
+
+/// Test a batch larger than one chunk is fully inserted in input order
+#[tokio::test]
+async fn test_insert_batch_10k_rows() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+    let rows = batch_rows(&account_id, 10_000);
+    assert!(rows.len() > NewTransactionQueue::MAX_ROWS_PER_CHUNK, "Batch should span chunks");
+
+    let inserted = NewTransactionQueue::insert_batch(&mut conn, &rows)
+        .await
+        .expect("Batch insert failed");
+
+    assert_eq!(inserted.len(), rows.len());
+    for (new_row, row) in rows.iter().zip(&inserted) {
+        assert_eq!(new_row.id, row.id, "Inserted rows should follow input order");
+        assert_eq!(new_row.transaction_data, row.transaction_data);
+    }
+    assert_eq!(count_rows(&mut conn, &account_id).await, 10_000);
+}
+
+/// Test a constraint violation in a later chunk rolls back the whole batch
+#[tokio::test]
+async fn test_insert_batch_rolls_back_on_later_chunk_failure() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+    let mut rows = batch_rows(&account_id, 10_000);
+
+    // Duplicate a primary key from the first chunk inside the second chunk
+    let duplicate_index = NewTransactionQueue::MAX_ROWS_PER_CHUNK + 10;
+    rows[duplicate_index].id = rows[0].id;
+
+    let err = NewTransactionQueue::insert_batch(&mut conn, &rows)
+        .await
+        .expect_err("Duplicate id should fail the batch");
+
+    match err {
+        DbError::BatchChunk { chunk_index, .. } => assert_eq!(chunk_index, 1),
+        other => panic!("Unexpected error: {}", other),
+    }
+    assert_eq!(count_rows(&mut conn, &account_id).await, 0, "Batch should be rolled back");
+}
@@ -0,0 +1,138 @@
+use axum::{body::Bytes, middleware, routing::post, Router};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use transaction_queue_api::body_timeout::body_read_timeout;
+
+const BUDGET: Duration = Duration::from_millis(300);
+
+/// Serve a router that echoes the body length behind the body read guard
+async fn start_server() -> SocketAddr {
+    let app = Router::new()
+        .route("/echo", post(|body: Bytes| async move { body.len().to_string() }))
+        .layer(middleware::from_fn_with_state(BUDGET, body_read_timeout));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+/// Write the headers, then the body in chunks with `gap` between them, and
+/// return the raw response. The body is written from a separate task so the
+/// response is read as soon as the server answers, even mid-upload.
+async fn send_in_chunks(addr: SocketAddr, chunks: &[&[u8]], gap: Duration) -> String {
+    let length: usize = chunks.iter().map(|chunk| chunk.len()).sum();
+    let (mut reader, mut writer) = TcpStream::connect(addr).await.unwrap().into_split();
+    let head = format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+        length
+    );
+    writer.write_all(head.as_bytes()).await.unwrap();
+
+    let chunks: Vec<Vec<u8>> = chunks.iter().map(|chunk| chunk.to_vec()).collect();
+    let upload = tokio::spawn(async move {
+        for (index, chunk) in chunks.iter().enumerate() {
+            if index > 0 {
+                tokio::time::sleep(gap).await;
+            }
+            // The server may answer and close before the body is complete
+            if writer.write_all(chunk).await.is_err() {
+                break;
+            }
+        }
+        writer
+    });
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 4096];
+    while let Ok(Ok(n)) = tokio::time::timeout(Duration::from_secs(5), reader.read(&mut buf)).await {
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+        if response_complete(&response) {
+            break;
+        }
+    }
+    upload.abort();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+/// Whether the headers and a Content-Length sized body have arrived
+fn response_complete(response: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(response);
+    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+        return false;
+    };
+    head.lines()
+        .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length: ").map(str::to_string))
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .is_some_and(|len| body.len() >= len)
+}
+
+fn status_and_body(response: &str) -> (u16, &str) {
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .unwrap_or_else(|| panic!("No status line in {:?}", response));
+    let body = response.split_once("\r\n\r\n").map(|(_, body)| body).unwrap_or_default();
+    (status, body)
+}
+
+/// Test a body trickled slower than the budget is answered with 408
+#[tokio::test]
+async fn test_trickled_body_gets_408() {
+    let addr = start_server().await;
+    let chunks: Vec<&[u8]> = br#"{"a":1}"#.chunks(1).collect();
+
+    let started = Instant::now();
+    let response = send_in_chunks(addr, &chunks, BUDGET * 3).await;
+    let (status, body) = status_and_body(&response);
+
+    assert_eq!(status, 408, "{}", response);
+    let envelope: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(envelope["error"]["status"], 408);
+    assert!(response.to_ascii_lowercase().contains("connection: close"));
+    // The first gap trips the guard; the remaining bytes are never waited for
+    assert!(started.elapsed() < BUDGET * 3, "took {:?}", started.elapsed());
+}
+
+/// Test a client that sends headers and then stalls gets 408
+#[tokio::test]
+async fn test_stalled_body_gets_408() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut buf = vec![0u8; 4096];
+    let n = tokio::time::timeout(BUDGET * 5, stream.read(&mut buf))
+        .await
+        .expect("Server should answer without the body")
+        .unwrap();
+    let (status, _) = status_and_body(std::str::from_utf8(&buf[..n]).unwrap());
+    assert_eq!(status, 408);
+}
+
+/// Test a slow upload is fine as long as every gap is within the budget
+#[tokio::test]
+async fn test_steady_slow_body_is_accepted() {
+    let addr = start_server().await;
+    let body = br#"{"transaction":"slow but steady"}"#;
+    let chunks: Vec<&[u8]> = body.chunks(4).collect();
+
+    // Total upload time is well past the budget, each gap is not
+    let started = Instant::now();
+    let response = send_in_chunks(addr, &chunks, BUDGET / 3).await;
+    assert!(started.elapsed() > BUDGET);
+
+    let (status, echoed) = status_and_body(&response);
+    assert_eq!(status, 200, "{}", response);
+    assert_eq!(echoed, body.len().to_string());
+}
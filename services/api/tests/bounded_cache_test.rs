@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use transaction_queue_api::bounded_cache::BoundedCache;
+
+/// Test inserting past capacity evicts the least recently used entries
+#[test]
+fn test_evicts_least_recently_used() {
+    let cache = BoundedCache::new("test_lru", 3, Duration::from_secs(60));
+    for i in 0..3 {
+        cache.insert(i, i * 10);
+    }
+    // Touching 0 makes 1 the oldest
+    assert_eq!(cache.get(&0), Some(0));
+    cache.insert(3, 30);
+
+    assert_eq!(cache.len(), 3);
+    assert_eq!(cache.get(&1), None);
+    assert_eq!(cache.get(&0), Some(0));
+    assert_eq!(cache.get(&3), Some(30));
+
+    for i in 100..1_000 {
+        cache.insert(i, i);
+    }
+    assert_eq!(cache.len(), cache.capacity());
+    assert_eq!(cache.get(&999), Some(999));
+}
+
+/// Test a key that was evicted is read again from the source of truth
+#[tokio::test]
+async fn test_miss_after_eviction_reloads() {
+    let cache = BoundedCache::new("test_reload", 2, Duration::from_secs(60));
+    let loads = AtomicUsize::new(0);
+    let load = |key: &'static str| {
+        let loads = &loads;
+        move || async move {
+            loads.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, ()>(key.len())
+        }
+    };
+
+    assert_eq!(cache.get_or_load("a", load("a")).await, Ok(1));
+    assert_eq!(cache.get_or_load("a", load("a")).await, Ok(1));
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    cache.get_or_load("bb", load("bb")).await.unwrap();
+    cache.get_or_load("ccc", load("ccc")).await.unwrap();
+    assert_eq!(cache.get(&"a"), None);
+    assert_eq!(cache.get_or_load("a", load("a")).await, Ok(1));
+    assert_eq!(loads.load(Ordering::SeqCst), 4);
+
+    // Failed loads are not cached
+    assert_eq!(cache.get_or_load("dddd", || async { Err(()) }).await, Err(()));
+    assert_eq!(cache.get(&"dddd"), None);
+}
+
+/// Test entries expire after the TTL
+#[tokio::test]
+async fn test_entries_expire() {
+    let cache = BoundedCache::new("test_ttl", 10, Duration::from_millis(20));
+    cache.insert("key", 1);
+    assert_eq!(cache.get(&"key"), Some(1));
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    assert_eq!(cache.get(&"key"), None);
+    assert!(cache.is_empty());
+}
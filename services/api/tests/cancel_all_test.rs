@@ -0,0 +1,202 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{NewTransactionQueue, TransactionQueue, TransactionStatus};
+use postgres_models::processing::{self, ProcessOutcome, SimulatedProcessor};
+use postgres_models::schema::transaction_queue;
+use postgres_models::sources::{RandomIds, SystemClock};
+use redis_cache::{EventPublisher, QueueEventKind};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tower::ServiceExt;
+use transaction_queue_api::{v1, AppState, TRANSACTION_QUEUE};
+use uuid::Uuid;
+
+/// Insert `count` pending rows for `account_id` with priorities 0, 1, 2, ...
+async fn seed_pending(state: &AppState, account_id: &str, count: i32) -> Vec<Uuid> {
+    let rows: Vec<NewTransactionQueue> = (0..count)
+        .map(|priority| {
+            let mut row = NewTransactionQueue::new(
+                account_id.to_string(),
+                TestData::sample_transaction_data(),
+                &RandomIds,
+                &SystemClock,
+            );
+            row.priority = priority;
+            row
+        })
+        .collect();
+    let mut conn = state.db_pool.get().await.expect("Failed to get connection");
+    NewTransactionQueue::insert_batch(&mut conn, &rows)
+        .await
+        .expect("Failed to seed rows")
+        .into_iter()
+        .map(|row| row.id)
+        .collect()
+}
+
+async fn cancel_all(state: &AppState, account_id: &str, filter: Value) -> (StatusCode, Value) {
+    let app = v1::router(state.clone()).with_state(state.clone());
+    let request = Request::post(format!("/accounts/{}/transactions/cancel-all", account_id))
+        .header("content-type", "application/json")
+        .body(Body::from(filter.to_string()))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    (status, body)
+}
+
+async fn statuses(state: &AppState, ids: &[Uuid]) -> Vec<String> {
+    let mut conn = state.db_pool.get().await.expect("Failed to get connection");
+    let mut rows: Vec<(Uuid, String)> = transaction_queue::table
+        .filter(transaction_queue::id.eq_any(ids))
+        .select((transaction_queue::id, transaction_queue::status))
+        .load(&mut conn)
+        .await
+        .expect("Failed to load statuses");
+    rows.sort_by_key(|(id, _)| ids.iter().position(|wanted| wanted == id));
+    rows.into_iter().map(|(_, status)| status).collect()
+}
+
+/// Test rows a worker already claimed are left to finish while the rest are cancelled
+#[tokio::test]
+async fn test_cancel_all_skips_claimed_rows() {
+    let state = TestEnvironment::app_state().await;
+    let account_id = TestData::unique_account_id();
+    let ids = seed_pending(&state, &account_id, 50).await;
+
+    let mut conn = state.db_pool.get().await.expect("Failed to get connection");
+    for id in &ids[..5] {
+        let claimed =
+            TransactionQueue::transition_status(&mut conn, *id, TransactionStatus::Pending, TransactionStatus::Processing)
+                .await
+                .unwrap();
+        assert!(claimed);
+    }
+    drop(conn);
+
+    let (status, body) = cancel_all(&state, &account_id, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cancelled"], 45);
+    assert_eq!(body["transaction_ids"].as_array().unwrap().len(), 45);
+    assert_eq!(body["has_more"], false);
+
+    let statuses = statuses(&state, &ids).await;
+    assert!(statuses[..5].iter().all(|status| status == "processing"));
+    assert!(statuses[5..].iter().all(|status| status == "cancelled"));
+
+    // A worker popping a cancelled row does not execute it
+    let mut conn = state.db_pool.get().await.expect("Failed to get connection");
+    let outcome = processing::process(&mut conn, &SimulatedProcessor, ids[5]).await.unwrap();
+    assert_eq!(outcome, ProcessOutcome::Cancelled);
+
+    let (_, body) = cancel_all(&state, &account_id, json!({})).await;
+    assert_eq!(body["cancelled"], 0);
+}
+
+/// Test the priority range and creation cutoff limit what is cancelled
+#[tokio::test]
+async fn test_cancel_all_filters() {
+    let state = TestEnvironment::app_state().await;
+    let account_id = TestData::unique_account_id();
+    let ids = seed_pending(&state, &account_id, 10).await;
+
+    let (status, body) = cancel_all(&state, &account_id, json!({ "min_priority": 3, "max_priority": 5 })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cancelled"], 3);
+    let cancelled: Vec<bool> = statuses(&state, &ids).await.iter().map(|status| status == "cancelled").collect();
+    assert_eq!(cancelled, (0..10).map(|priority| (3..=5).contains(&priority)).collect::<Vec<_>>());
+
+    let (_, body) = cancel_all(&state, &account_id, json!({ "created_before": "2000-01-01T00:00:00Z" })).await;
+    assert_eq!(body["cancelled"], 0);
+
+    let (status, _) = cancel_all(&state, &account_id, json!({ "min_priority": 5, "max_priority": 3 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Test the listed ids are capped while the count covers every row
+#[tokio::test]
+async fn test_cancel_all_caps_listed_ids() {
+    let state = TestEnvironment::app_state().await;
+    let account_id = TestData::unique_account_id();
+    seed_pending(&state, &account_id, 120).await;
+
+    let (_, body) = cancel_all(&state, &account_id, json!({})).await;
+    assert_eq!(body["cancelled"], 120);
+    assert_eq!(body["transaction_ids"].as_array().unwrap().len(), 100);
+    assert_eq!(body["has_more"], true);
+}
+
+/// Test cancelling submitted transactions empties the account's queue entries and pending count
+#[tokio::test]
+async fn test_cancel_all_removes_queue_entries() {
+    let state = TestEnvironment::app_state().await;
+    let account_id = TestData::unique_account_id();
+    let app = v1::router(state.clone()).with_state(state.clone());
+
+    let mut ids = Vec::new();
+    for _ in 0..50 {
+        let body = json!({ "account_id": account_id, "transaction_data": { "amount": 1 } });
+        let request = Request::post("/transactions/submit")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        ids.push(body["transaction_id"].as_str().unwrap().parse::<Uuid>().unwrap());
+    }
+
+    let (status, body) = cancel_all(&state, &account_id, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cancelled"], 50);
+    assert_eq!(body["removed_from_queue"], 50);
+
+    TestEnvironment::assert_queue_consistent(&account_id).await;
+    assert_eq!(state.queue_manager().pending_count(&account_id).await.unwrap(), 0);
+    let mut conn = state.db_pool.get().await.expect("Failed to get connection");
+    for id in ids {
+        let outcome = processing::process(&mut conn, &SimulatedProcessor, id).await.unwrap();
+        assert_eq!(outcome, ProcessOutcome::Cancelled);
+    }
+}
+
+/// Test submit and cancel-all publish one event per transaction when events are on
+#[tokio::test]
+async fn test_cancel_all_publishes_events() {
+    let mut state = TestEnvironment::app_state().await;
+    state.events = EventPublisher::new(state.queue_manager(), TRANSACTION_QUEUE.clone());
+    let account_id = TestData::unique_account_id();
+    let app = v1::router(state.clone()).with_state(state.clone());
+    // Stream ids start with their millisecond, so this skips what came before
+    let started_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    let after = format!("{}-0", started_ms - 1);
+
+    for priority in [0, 4, 9] {
+        let body = json!({ "account_id": account_id, "transaction_data": { "amount": 1 }, "priority": priority });
+        let request = Request::post("/transactions/submit")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+    }
+    let (_, body) = cancel_all(&state, &account_id, json!({})).await;
+    assert_eq!(body["cancelled"], 3);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let batch = state.queue_manager().read_events(&TRANSACTION_QUEUE, &after, 10_000).await.unwrap();
+    let events: Vec<_> = batch.events.into_iter().filter(|event| event.account_id == account_id).collect();
+    for kind in [QueueEventKind::Submitted, QueueEventKind::Cancelled] {
+        let mut priorities: Vec<i32> =
+            events.iter().filter(|event| event.kind == kind).map(|event| event.priority).collect();
+        priorities.sort();
+        assert_eq!(priorities, [0, 4, 9], "{:?} in {:?}", kind, events);
+    }
+    assert_eq!(events.len(), 6);
+    assert!(events.iter().filter(|event| event.kind == QueueEventKind::Cancelled).all(|event| event.wait_ms.is_some()));
+}
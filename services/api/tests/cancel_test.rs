@@ -0,0 +1,109 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::isolated::{IsolatedApp, TestResponse};
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use postgres_models::processing::{self, ProcessOutcome, SimulatedProcessor};
+use serde_json::json;
+use transaction_queue_api::TRANSACTION_QUEUE;
+use uuid::Uuid;
+
+async fn cancel(app: &IsolatedApp, id: &str) -> TestResponse {
+    let request = Request::delete(format!("/v1/transactions/{}", id)).body(Body::empty()).unwrap();
+    app.request(request).await
+}
+
+/// Test a cancelled transaction leaves the queue and a worker popping it afterwards skips it
+#[tokio::test]
+async fn test_cancel_then_dequeue() {
+    let app = IsolatedApp::new().await;
+    let (first, _, _) = app.submit_transaction_expect_success("acct_cancel", json!({ "n": 1 }), None).await;
+    let (second, _, _) = app.submit_transaction_expect_success("acct_cancel", json!({ "n": 2 }), None).await;
+
+    let response = cancel(&app, &first).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["id"], first);
+    assert_eq!(response.body["status"], "cancelled");
+    assert!(response.body["wait"].is_null());
+    assert_eq!(app.queue_order().await, std::slice::from_ref(&second));
+    assert_eq!(app.state.queue_manager().pending_count("acct_cancel").await.unwrap(), 1);
+
+    // The next worker gets the other transaction
+    let popped = app.state.queue_manager().dequeue_envelope(&TRANSACTION_QUEUE).await.unwrap().unwrap();
+    assert_eq!(popped.transaction_id, second);
+    // and one that popped the cancelled entry before it was removed does not run it
+    let mut conn = app.state.db_pool.get().await.expect("Failed to get connection");
+    let outcome = processing::process(&mut conn, &SimulatedProcessor, first.parse().unwrap()).await.unwrap();
+    assert_eq!(outcome, ProcessOutcome::Cancelled);
+    drop(conn);
+
+    let response = cancel(&app, &first).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.body["error"]["details"]["status"], "cancelled", "{}", response.body);
+    assert_eq!(cancel(&app, &Uuid::new_v4().to_string()).await.status, StatusCode::NOT_FOUND);
+
+    app.teardown().await;
+}
+
+/// Test a transaction a worker already claimed is not cancelled, and the 409 says what it is
+#[tokio::test]
+async fn test_dequeue_then_cancel() {
+    let app = IsolatedApp::new().await;
+    let (id, _, _) = app.submit_transaction_expect_success("acct_cancel", json!({ "n": 1 }), None).await;
+
+    let popped = app.state.queue_manager().dequeue_envelope(&TRANSACTION_QUEUE).await.unwrap().unwrap();
+    assert_eq!(popped.transaction_id, id);
+    let mut conn = app.state.db_pool.get().await.expect("Failed to get connection");
+    let id: Uuid = id.parse().unwrap();
+    let claimed =
+        TransactionQueue::transition_status(&mut conn, id, TransactionStatus::Pending, TransactionStatus::Processing)
+            .await
+            .unwrap();
+    assert!(claimed);
+
+    let response = cancel(&app, &id.to_string()).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.body["error"]["code"], "transaction_not_pending", "{}", response.body);
+    assert_eq!(response.body["error"]["details"]["status"], "processing");
+    let row = TransactionQueue::find(&mut conn, id).await.unwrap().unwrap();
+    assert_eq!(row.status, "processing");
+    assert_eq!(app.state.queue_manager().pending_count("acct_cancel").await.unwrap(), 1);
+    drop(conn);
+
+    app.teardown().await;
+}
+
+/// Test of a cancel and a worker claim racing on each row, exactly one wins
+#[tokio::test]
+async fn test_cancel_races_claim() {
+    let app = IsolatedApp::new().await;
+    let mut ids = Vec::new();
+    for n in 0..10 {
+        let (id, _, _) = app.submit_transaction_expect_success("acct_cancel", json!({ "n": n }), None).await;
+        ids.push(id.parse::<Uuid>().unwrap());
+    }
+
+    for id in ids {
+        let mut conn = app.state.db_pool.get().await.expect("Failed to get connection");
+        let path_id = id.to_string();
+        let claim = TransactionQueue::transition_status(
+            &mut conn,
+            id,
+            TransactionStatus::Pending,
+            TransactionStatus::Processing,
+        );
+        let (response, claimed) = tokio::join!(cancel(&app, &path_id), claim);
+        let claimed = claimed.unwrap();
+        let row = TransactionQueue::find(&mut conn, id).await.unwrap().unwrap();
+        if claimed {
+            assert_eq!(response.status, StatusCode::CONFLICT, "{}", response.body);
+            assert_eq!(row.status, "processing");
+        } else {
+            assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+            assert_eq!(row.status, "cancelled");
+        }
+    }
+
+    app.teardown().await;
+}
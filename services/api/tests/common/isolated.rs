@@ -0,0 +1,212 @@
+//! In-process API with its own Redis key prefix and Postgres schema.
+//!
+//! Each `IsolatedApp` gets a fresh prefix for every Redis key and a fresh
+//! schema, with the migrations applied, as the `search_path` of its database
+//! connections. Tests can then share one Redis and one database at any
+//! `--test-threads` without seeing each other's queue, windows or rows, and
+//! can use fixed account ids. Call `teardown` at the end of a test to drop
+//! both; a test that panics first leaves them behind under a `test` name.
+
+use super::{DEFAULT_DATABASE_URL, DEFAULT_REDIS_URL};
+use axum::body::{to_bytes, Body};
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::Router;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use diesel_async::{AsyncConnection, AsyncPgConnection, SimpleAsyncConnection};
+use serde_json::{json, Value};
+use std::path::Path;
+use tower::ServiceExt;
+use transaction_queue_api::config::Config;
+use transaction_queue_api::AppState;
+
+/// Connections each app may hold; keeps a parallel run under Postgres' limit
+const DB_POOL_SIZE: u32 = 4;
+
+/// A response read to the end
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+pub struct IsolatedApp {
+    pub state: AppState,
+    router: Router,
+    database_url: String,
+    schema: String,
+}
+
+impl IsolatedApp {
+    pub async fn new() -> Self {
+        Self::with_vars(&[]).await
+    }
+
+    /// App with `vars` set on top of the isolated service URLs and prefix
+    pub async fn with_vars(vars: &[(&str, &str)]) -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let schema = format!("test_{}", id);
+        let prefix = format!("test-{}", id);
+        let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string());
+        let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| DEFAULT_REDIS_URL.to_string());
+        create_schema(&database_url, &schema).await;
+
+        let schema_url = with_search_path(&database_url, &schema);
+        let lookup = |var: &str| match var {
+            "BACKEND" => Some("services".to_string()),
+            "DATABASE_URL" => Some(schema_url.clone()),
+            "REDIS_URL" => Some(redis_url.clone()),
+            "REDIS_KEY_PREFIX" => Some(prefix.clone()),
+            _ => vars.iter().find(|(name, _)| *name == var).map(|(_, value)| value.to_string()),
+        };
+        let config = Config::from_lookup(&service_config::Env::new(&lookup)).expect("Invalid test config");
+        let db_pool = bb8::Pool::builder()
+            .max_size(DB_POOL_SIZE)
+            .build(AsyncDieselConnectionManager::<AsyncPgConnection>::new(&schema_url))
+            .await
+            .expect("Failed to connect to test database. Please start it with: just up");
+        let state = AppState::builder(config)
+            .db_pool(db_pool)
+            .build()
+            .await
+            .expect("Failed to build app state. Please start services with: just up");
+
+        Self {
+            router: transaction_queue_api::app(state.clone(), None),
+            state,
+            database_url,
+            schema,
+        }
+    }
+
+    pub async fn request(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        TestResponse {
+            status,
+            headers,
+            body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        }
+    }
+
+    pub async fn submit_transaction(
+        &self,
+        account_id: &str,
+        transaction_data: Value,
+        priority: Option<i32>,
+    ) -> TestResponse {
+        let mut payload = json!({ "account_id": account_id, "transaction_data": transaction_data });
+        if let Some(priority) = priority {
+            payload["priority"] = json!(priority);
+        }
+        let request = Request::post("/v1/transactions/submit")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(payload.to_string()))
+            .unwrap();
+        self.request(request).await
+    }
+
+    /// Submit and assert it was accepted; returns the transaction id, queue
+    /// position and estimated processing time
+    pub async fn submit_transaction_expect_success(
+        &self,
+        account_id: &str,
+        transaction_data: Value,
+        priority: Option<i32>,
+    ) -> (String, i64, i64) {
+        let response = self.submit_transaction(account_id, transaction_data, priority).await;
+        assert_eq!(response.status, StatusCode::OK, "Expected 200 OK: {}", response.body);
+        submitted(&response.body)
+    }
+
+    /// Submit and assert it was rate limited with the limit headers; returns the body
+    pub async fn submit_transaction_expect_rate_limit(&self, account_id: &str, transaction_data: Value) -> Value {
+        let response = self.submit_transaction(account_id, transaction_data, None).await;
+        assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS, "Expected 429: {}", response.body);
+        for name in ["x-ratelimit-limit", "x-ratelimit-remaining", "x-ratelimit-reset", "retry-after"] {
+            assert!(response.headers.contains_key(name), "Missing {} header", name);
+        }
+        let error = &response.body["error"];
+        let retry_after: u64 = response.headers["retry-after"].to_str().unwrap().parse().unwrap();
+        assert!(retry_after >= 1, "Retry-After {}", retry_after);
+        assert_eq!(error["retry_after_seconds"], retry_after, "{}", response.body);
+        let mirrored = [
+            ("limit", "x-ratelimit-limit"),
+            ("remaining", "x-ratelimit-remaining"),
+            ("reset_at", "x-ratelimit-reset"),
+        ];
+        for (field, header) in mirrored {
+            assert_eq!(error[field].to_string(), response.headers[header].to_str().unwrap(), "{}", field);
+        }
+        response.body
+    }
+
+    /// Ids of the transactions in this app's queue, in processing order
+    pub async fn queue_order(&self) -> Vec<String> {
+        self.state
+            .queue_manager()
+            .get_priority_queue_order(&transaction_queue_api::TRANSACTION_QUEUE)
+            .await
+            .expect("Failed to read queue")
+            .iter()
+            .map(|member| redis_cache::QueueEnvelope::decode(member).expect("Undecodable queue member"))
+            .map(|envelope| envelope.transaction_id)
+            .collect()
+    }
+
+    /// Drop the app's schema and every Redis key under its prefix
+    pub async fn teardown(self) {
+        self.state
+            .queue_manager()
+            .clear_key_space()
+            .await
+            .expect("Failed to clear the test key space");
+        let mut conn = AsyncPgConnection::establish(&self.database_url)
+            .await
+            .expect("Failed to connect to test database");
+        conn.batch_execute(&format!("DROP SCHEMA {} CASCADE", self.schema))
+            .await
+            .expect("Failed to drop the test schema");
+    }
+}
+
+/// Transaction id, queue position and estimated processing time of a submit response
+pub fn submitted(body: &Value) -> (String, i64, i64) {
+    (
+        body["transaction_id"].as_str().expect("Missing transaction_id").to_string(),
+        body["queue_position"].as_i64().expect("Missing queue_position"),
+        body["estimated_processing_time_seconds"]
+            .as_i64()
+            .expect("Missing estimated_processing_time_seconds"),
+    )
+}
+
+/// Create `schema` and apply every migration in it, in order
+async fn create_schema(database_url: &str, schema: &str) {
+    let mut conn = AsyncPgConnection::establish(database_url)
+        .await
+        .expect("Failed to connect to test database. Please start it with: just up");
+    conn.batch_execute(&format!("CREATE SCHEMA {0}; SET search_path TO {0}", schema))
+        .await
+        .expect("Failed to create the test schema");
+
+    let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../db/migrations");
+    let mut dirs: Vec<_> = std::fs::read_dir(&migrations)
+        .expect("Failed to read db/migrations")
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    dirs.sort();
+    for dir in dirs {
+        let sql = std::fs::read_to_string(dir.join("up.sql")).unwrap();
+        conn.batch_execute(&sql)
+            .await
+            .unwrap_or_else(|e| panic!("Migration {} failed in {}: {}", dir.display(), schema, e));
+    }
+}
+
+/// `database_url` with `schema` as the search path of every connection
+fn with_search_path(database_url: &str, schema: &str) -> String {
+    let separator = if database_url.contains('?') { '&' } else { '?' };
+    format!("{}{}options=-c%20search_path%3D{}", database_url, separator, schema)
+}
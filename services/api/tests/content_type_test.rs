@@ -0,0 +1,91 @@
+mod common;
+
+use common::*;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+const SUBMIT_PATH: &str = "/v1/transactions/submit";
+
+fn submit_body() -> String {
+    json!({
+        "account_id": TestData::unique_account_id(),
+        "transaction_data": TestData::sample_transaction_data(),
+    })
+    .to_string()
+}
+
+async fn assert_unsupported(response: reqwest::Response) {
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(
+        response.headers().get("accept-post").and_then(|v| v.to_str().ok()),
+        Some("application/json")
+    );
+    let body: Value = response.json().await.expect("Error body should be JSON");
+    assert_eq!(body["error"]["status"], 415);
+}
+
+/// Test JSON bodies are accepted, including with a charset parameter
+#[tokio::test]
+async fn test_json_content_type_accepted() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    for content_type in ["application/json", "application/json; charset=utf-8"] {
+        let response = client
+            .post_raw(SUBMIT_PATH, Some(content_type), submit_body())
+            .await
+            .expect("Request failed");
+        assert_eq!(response.status(), StatusCode::OK, "{} should be accepted", content_type);
+    }
+}
+
+/// Test msgpack is rejected with 415 until it is supported
+#[tokio::test]
+async fn test_msgpack_rejected() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    let response = client
+        .post_raw(SUBMIT_PATH, Some("application/msgpack"), vec![0x81u8, 0xa1, 0x61, 0x01])
+        .await
+        .expect("Request failed");
+    assert_unsupported(response).await;
+}
+
+/// Test urlencoded and plain-text bodies are rejected with 415
+#[tokio::test]
+async fn test_form_and_text_rejected() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    let response = client
+        .post_raw(SUBMIT_PATH, Some("application/x-www-form-urlencoded"), "account_id=abc&priority=1")
+        .await
+        .expect("Request failed");
+    assert_unsupported(response).await;
+
+    let response = client
+        .post_raw(SUBMIT_PATH, Some("text/plain"), submit_body())
+        .await
+        .expect("Request failed");
+    assert_unsupported(response).await;
+}
+
+/// Test a missing content type is accepted for JSON-looking bodies only
+#[tokio::test]
+async fn test_missing_content_type() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    let response = client
+        .post_raw(SUBMIT_PATH, None, submit_body())
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .post_raw(SUBMIT_PATH, None, "account_id=abc")
+        .await
+        .expect("Request failed");
+    assert_unsupported(response).await;
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use common::*;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use diesel_async::AsyncPgConnection;
+use postgres_models::DbPool;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::time::Instant;
+use tower::ServiceExt;
+use transaction_queue_api::config::Config;
+use transaction_queue_api::extractors::acquire_connection;
+use transaction_queue_api::extractors::database::{ACQUIRE_ATTEMPTS, ACQUIRE_RETRY_BUDGET, DB_POOL_EXHAUSTED};
+use transaction_queue_api::AppState;
+
+/// How long the pool itself waits before the first attempt fails
+const POOL_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// A pool with a single connection that gives up quickly
+async fn single_connection_pool() -> DbPool {
+    let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string());
+    bb8::Pool::builder()
+        .max_size(1)
+        .connection_timeout(POOL_TIMEOUT)
+        .build(AsyncDieselConnectionManager::<AsyncPgConnection>::new(database_url))
+        .await
+        .expect("Failed to create pool")
+}
+
+/// Test a connection released shortly after the first attempt fails is picked up by a retry
+#[tokio::test]
+async fn test_retry_rescues_briefly_exhausted_pool() {
+    let pool = single_connection_pool().await;
+
+    for _ in 0..3 {
+        let held = pool.get_owned().await.unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(POOL_TIMEOUT * 2).await;
+            drop(held);
+        });
+
+        assert!(acquire_connection(&pool).await.is_ok());
+        release.await.unwrap();
+    }
+}
+
+/// Test a pool held past the budget answers 503 with Retry-After once the retries run out
+#[tokio::test]
+async fn test_retries_respect_budget() {
+    let pool = single_connection_pool().await;
+    let _held = pool.get_owned().await.unwrap();
+
+    let started = Instant::now();
+    let err = acquire_connection(&pool).await.err().expect("pool should be exhausted");
+    let elapsed = started.elapsed();
+
+    // Every attempt waited out the pool's timeout, unless the budget ran out first
+    let shortest = (POOL_TIMEOUT * ACQUIRE_ATTEMPTS).min(POOL_TIMEOUT + ACQUIRE_RETRY_BUDGET);
+    assert!(elapsed >= shortest, "gave up after {:?}", elapsed);
+    assert!(elapsed < POOL_TIMEOUT + ACQUIRE_RETRY_BUDGET + Duration::from_millis(150), "took {:?}", elapsed);
+    assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(err.headers.unwrap()[header::RETRY_AFTER], "1");
+}
+
+/// Test with the configured single connection held, a request gets the pool exhaustion 503 within the wait timeout
+#[tokio::test]
+async fn test_exhausted_pool_from_config() {
+    const WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+    let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string());
+    let lookup = |var: &str| match var {
+        "BACKEND" => Some("memory".to_string()),
+        "DATABASE_URL" => Some(database_url.clone()),
+        "DB_POOL_MAX" => Some("1".to_string()),
+        "DB_POOL_MIN_IDLE" => Some("0".to_string()),
+        "DB_POOL_WAIT_TIMEOUT_MS" => Some(WAIT_TIMEOUT.as_millis().to_string()),
+        _ => None,
+    };
+    let config = Config::from_lookup(&service_config::Env::new(&lookup)).unwrap();
+    let state = AppState::new(config).await.unwrap();
+    let _held = state.db_pool.get_owned().await.expect("Failed to get connection");
+    let app = transaction_queue_api::app(state, None);
+
+    let request = Request::patch(format!("/v1/transactions/{}/status", uuid::Uuid::new_v4()))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"status":"processing"}"#))
+        .unwrap();
+    let started = Instant::now();
+    let response = app.oneshot(request).await.unwrap();
+    let elapsed = started.elapsed();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    // The first attempt waits out the pool, the retries only what is left of their budget
+    assert!(elapsed >= WAIT_TIMEOUT, "gave up after {:?}", elapsed);
+    assert!(elapsed < WAIT_TIMEOUT + ACQUIRE_RETRY_BUDGET + Duration::from_millis(150), "took {:?}", elapsed);
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(body["error"]["code"], DB_POOL_EXHAUSTED, "{}", body);
+    assert_eq!(body["error"]["details"]["connections"], 1);
+    assert!(body["error"]["details"]["reason"].as_str().unwrap().contains("in use"), "{}", body);
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use chrono::{TimeZone, Utc};
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{NewTransactionQueue, TransactionQueue};
+use postgres_models::schema::transaction_queue;
+use postgres_models::sources::{FixedClock, IdGenerator, SequentialIds};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+use transaction_queue_api::v1;
+
+fn fixed_clock() -> FixedClock {
+    FixedClock(Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap())
+}
+
+/// Test two generators with the same seed hand out the same ids, and different seeds do not collide
+#[test]
+fn test_sequential_ids_repeat_per_seed() {
+    let first = SequentialIds::new(7);
+    let second = SequentialIds::new(7);
+    let ids: Vec<_> = (0..3).map(|_| first.new_id()).collect();
+    assert_eq!(ids, (0..3).map(|_| second.new_id()).collect::<Vec<_>>());
+    assert_ne!(ids[0], ids[1]);
+    assert_ne!(ids[0], SequentialIds::new(8).new_id());
+}
+
+/// Test a new row takes its id and timestamps from the given sources
+#[test]
+fn test_new_row_uses_sources() {
+    let clock = fixed_clock();
+    let row = NewTransactionQueue::new(
+        "acct".to_string(),
+        TestData::sample_transaction_data(),
+        &SequentialIds::new(42),
+        &clock,
+    );
+
+    assert_eq!(row.id, SequentialIds::new(42).new_id());
+    assert_eq!(row.created_at, clock.0);
+    assert_eq!(row.updated_at, clock.0);
+}
+
+/// Test the submit handler's response and stored row are fixed by the seed and clock
+#[tokio::test]
+async fn test_submit_response_is_reproducible() {
+    // A fresh seed per run keeps the ids clear of rows left by earlier runs
+    let seed = rand::random::<u64>();
+    let clock = fixed_clock();
+    let state = TestEnvironment::app_state()
+        .await
+        .with_sources(Arc::new(SequentialIds::new(seed)), Arc::new(clock));
+    let app = v1::router(state.clone()).with_state(state);
+
+    let account_id = TestData::unique_account_id();
+    let request = Request::post("/transactions/submit")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            json!({
+                "account_id": account_id,
+                "transaction_data": TestData::sample_transaction_data(),
+            })
+            .to_string(),
+        ))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+
+    // Queue position and the estimate depend on what else is in the shared
+    // queue, so they are taken from the response; everything else is fixed
+    let expected_id = SequentialIds::new(seed).new_id();
+    assert_eq!(
+        body,
+        json!({
+            "transaction_id": expected_id,
+            "queue_position": body["queue_position"],
+            "estimated_processing_time_seconds": body["estimated_processing_time_seconds"],
+            "status": "pending",
+            "backpressure": { "level": "none", "suggested_delay_ms": 0 },
+        })
+    );
+    assert!(body["queue_position"].as_i64().unwrap() >= 1);
+
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get().await.expect("Failed to get connection");
+    let stored = transaction_queue::table
+        .find(expected_id)
+        .select(TransactionQueue::as_select())
+        .first(&mut conn)
+        .await
+        .expect("Row missing");
+    assert_eq!(stored.account_id, account_id);
+    assert_eq!(stored.created_at, clock.0);
+    assert_eq!(stored.scheduled_at, Some(clock.0));
+}
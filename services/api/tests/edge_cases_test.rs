@@ -53,7 +53,7 @@ async fn test_malformed_json_edge_cases() {
 
     let extremely_nested = format!(
         "{{{}}}",
-        "\"a\":{".repeat(1000) + &"\"b\":1" + &"}".repeat(1000)
+        "\"a\":{".repeat(1000) + "\"b\":1" + &"}".repeat(1000)
     );
     let very_long_string = format!(
         "{{\"account_id\":\"{}\",\"data\":\"test\"}}",
@@ -288,6 +288,9 @@ async fn test_duplicate_concurrent_requests() {
         successes > 0,
         "Should have at least some successful submissions"
     );
+
+    // Identical payloads must not collapse into a single queue entry
+    TestEnvironment::assert_queue_consistent(&account_id).await;
 }
 
 /// Test request timeout handling
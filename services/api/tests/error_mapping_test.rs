@@ -0,0 +1,16 @@
+use axum::http::StatusCode;
+use redis_cache::RedisError;
+use transaction_queue_api::errors::AppError;
+
+/// Test transient Redis failures surface as 503 and permanent ones as 500
+#[test]
+fn test_redis_error_status() {
+    let transient: AppError = RedisError::PoolExhausted.into();
+    assert_eq!(transient.status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let transient: AppError = RedisError::Timeout("read".to_string()).into();
+    assert_eq!(transient.status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let permanent: AppError = RedisError::Config("bad url".to_string()).into();
+    assert_eq!(permanent.status, StatusCode::INTERNAL_SERVER_ERROR);
+}
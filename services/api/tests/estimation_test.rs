@@ -0,0 +1,81 @@
+use transaction_queue_api::estimation::{
+    estimate_at_position, estimate_processing_seconds, EstimateUnavailable, DEFAULT_SECONDS_PER_ITEM,
+};
+
+const MAX_SECONDS: i64 = 3600;
+
+/// Test an empty queue with live workers is processed immediately
+#[test]
+fn test_empty_queue_with_live_workers() {
+    assert_eq!(estimate_processing_seconds(0, None, true, 1.0, MAX_SECONDS), 0);
+    assert_eq!(estimate_processing_seconds(0, Some(50.0), true, 1.0, MAX_SECONDS), 0);
+}
+
+/// Test an empty queue without live workers still costs one slot
+#[test]
+fn test_empty_queue_without_workers() {
+    assert_eq!(
+        estimate_processing_seconds(0, None, false, 1.0, MAX_SECONDS),
+        DEFAULT_SECONDS_PER_ITEM as i64
+    );
+}
+
+/// Test a deep queue uses measured throughput and respects the cap
+#[test]
+fn test_deep_queue() {
+    // 99 ahead + this one at 50 items/second
+    assert_eq!(estimate_processing_seconds(99, Some(50.0), true, 1.0, MAX_SECONDS), 2);
+
+    // Without throughput data the heuristic applies
+    assert_eq!(estimate_processing_seconds(9, None, true, 1.0, MAX_SECONDS), 300);
+
+    // Very deep queues are capped
+    assert_eq!(estimate_processing_seconds(1_000_000, None, true, 1.0, MAX_SECONDS), MAX_SECONDS);
+    assert_eq!(estimate_processing_seconds(1_000_000, Some(0.5), true, 1.0, 120), 120);
+}
+
+/// Test the tier multiplier scales the whole wait, within the cap
+#[test]
+fn test_tier_multiplier_scales_estimate() {
+    assert_eq!(estimate_processing_seconds(9, Some(2.0), true, 1.5, MAX_SECONDS), 8);
+    assert_eq!(estimate_processing_seconds(9, Some(2.0), true, 0.5, MAX_SECONDS), 3);
+    assert_eq!(estimate_processing_seconds(0, Some(2.0), true, 1.5, MAX_SECONDS), 0);
+    assert_eq!(estimate_processing_seconds(1_000_000, None, true, 2.0, MAX_SECONDS), MAX_SECONDS);
+}
+
+/// Test non-positive throughput falls back to the heuristic
+#[test]
+fn test_invalid_throughput_falls_back() {
+    assert_eq!(
+        estimate_processing_seconds(1, Some(0.0), true, 1.0, MAX_SECONDS),
+        estimate_processing_seconds(1, None, true, 1.0, MAX_SECONDS)
+    );
+}
+
+/// Test the estimate never decreases as more items are ahead
+#[test]
+fn test_estimate_is_monotonic() {
+    let mut previous = 0;
+    for items_ahead in 0..500 {
+        let estimate = estimate_processing_seconds(items_ahead, Some(3.3), true, 1.0, MAX_SECONDS);
+        assert!(estimate >= previous, "estimate decreased at {} items ahead", items_ahead);
+        previous = estimate;
+    }
+}
+
+/// Test an estimate at a position prices the items ahead of it like the raw formula
+#[test]
+fn test_estimate_at_position() {
+    assert_eq!(estimate_at_position(1, Some(2.0), true, 1.0, MAX_SECONDS), Ok(0));
+    assert_eq!(
+        estimate_at_position(10, Some(2.0), true, 1.0, MAX_SECONDS),
+        Ok(estimate_processing_seconds(9, Some(2.0), true, 1.0, MAX_SECONDS))
+    );
+}
+
+/// Test there is no estimate while no workers are alive
+#[test]
+fn test_no_estimate_without_workers() {
+    assert_eq!(estimate_at_position(1, Some(2.0), false, 1.0, MAX_SECONDS), Err(EstimateUnavailable::NoLiveWorkers));
+    assert_eq!(estimate_at_position(500, None, false, 1.0, MAX_SECONDS), Err(EstimateUnavailable::NoLiveWorkers));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::*;
+use std::time::Duration;
+use transaction_queue_api::feature_flags::{rollout_bucket, FeatureFlags, LUA_RATE_LIMITER};
+use transaction_queue_api::rate_limit::{sliding_window_implementation, SlidingWindowImplementation};
+
+/// Test an account always lands in the same bucket, and buckets differ per flag
+#[test]
+fn test_bucketing_is_deterministic() {
+    for i in 0..100 {
+        let account_id = format!("acct_{}", i);
+        let bucket = rollout_bucket(LUA_RATE_LIMITER, &account_id);
+        assert!(bucket < 100);
+        assert_eq!(bucket, rollout_bucket(LUA_RATE_LIMITER, &account_id));
+    }
+
+    let differing = (0..100)
+        .map(|i| format!("acct_{}", i))
+        .filter(|account_id| rollout_bucket("flag_a", account_id) != rollout_bucket("flag_b", account_id))
+        .count();
+    assert!(differing > 80, "only {} of 100 accounts bucketed differently", differing);
+}
+
+/// Test a percentage enables about that share of accounts
+#[test]
+fn test_buckets_spread_evenly() {
+    let accounts = 10_000;
+    let enabled = (0..accounts)
+        .filter(|i| rollout_bucket(LUA_RATE_LIMITER, &format!("client_{}", i)) < 30)
+        .count();
+    assert!((2_700..=3_300).contains(&enabled), "{} of {} enabled at 30%", enabled, accounts);
+}
+
+/// Test setting a flag switches the limiter at once on this instance, and
+/// within the refresh interval on another
+#[tokio::test]
+async fn test_flag_switches_code_path_without_restart() {
+    let state = TestEnvironment::app_state().await;
+    let other_instance = FeatureFlags::new(state.queue_manager(), Duration::from_millis(100));
+    let account_id = TestData::unique_account_id();
+    state.flags.clear(LUA_RATE_LIMITER).await.unwrap();
+
+    assert_eq!(
+        sliding_window_implementation(&state, &account_id).await,
+        SlidingWindowImplementation::Commands
+    );
+    assert!(!other_instance.enabled(LUA_RATE_LIMITER, &account_id).await);
+
+    state.flags.set(LUA_RATE_LIMITER, 100).await.unwrap();
+    assert_eq!(
+        sliding_window_implementation(&state, &account_id).await,
+        SlidingWindowImplementation::Script
+    );
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(other_instance.enabled(LUA_RATE_LIMITER, &account_id).await);
+
+    state.flags.set(LUA_RATE_LIMITER, 0).await.unwrap();
+    assert_eq!(
+        sliding_window_implementation(&state, &account_id).await,
+        SlidingWindowImplementation::Commands
+    );
+    state.flags.clear(LUA_RATE_LIMITER).await.unwrap();
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::isolated::{IsolatedApp, TestResponse};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::schema::transaction_queue;
+use serde_json::{json, Value};
+use transaction_queue_api::idempotency::{IDEMPOTENCY_KEY_REUSED, IDEMPOTENT_REPLAYED_HEADER};
+
+async fn submit_with_key(app: &IsolatedApp, account_id: &str, key: &str, transaction_data: Value) -> TestResponse {
+    let request = Request::post("/v1/transactions/submit")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("idempotency-key", key)
+        .body(Body::from(
+            json!({ "account_id": account_id, "transaction_data": transaction_data }).to_string(),
+        ))
+        .unwrap();
+    app.request(request).await
+}
+
+async fn stored_rows(app: &IsolatedApp, account_id: &str) -> i64 {
+    let mut conn = app.state.db_pool.get().await.expect("Failed to get connection");
+    transaction_queue::table
+        .filter(transaction_queue::account_id.eq(account_id))
+        .count()
+        .get_result(&mut conn)
+        .await
+        .expect("Failed to count rows")
+}
+
+/// Test twenty parallel submits with one Idempotency-Key store and queue a single transaction
+#[tokio::test]
+async fn test_parallel_submits_with_one_key_store_one_row() {
+    let app = IsolatedApp::new().await;
+    let data = json!({ "amount": 100, "currency": "USD" });
+
+    let submits = (0..20).map(|_| submit_with_key(&app, "acct_idem", "order-1", data.clone()));
+    let responses = futures::future::join_all(submits).await;
+    let transaction_id = &responses[0].body["transaction_id"];
+    for response in &responses {
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(&response.body["transaction_id"], transaction_id);
+    }
+    let replayed = responses
+        .iter()
+        .filter(|response| response.headers.contains_key(IDEMPOTENT_REPLAYED_HEADER))
+        .count();
+    assert_eq!(replayed, 19);
+    assert_eq!(stored_rows(&app, "acct_idem").await, 1);
+    assert_eq!(app.queue_order().await, [transaction_id.as_str().unwrap()]);
+
+    let response = submit_with_key(&app, "acct_idem", "order-1", json!({ "amount": 200 })).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.body["error"]["code"], IDEMPOTENCY_KEY_REUSED);
+
+    app.teardown().await;
+}
+
+/// Test a retry whose Redis claim is gone is answered from the stored row
+#[tokio::test]
+async fn test_retry_after_claim_expired_replays_stored_row() {
+    let app = IsolatedApp::new().await;
+    let data = json!({ "amount": 100 });
+
+    let first = submit_with_key(&app, "acct_idem", "order-2", data.clone()).await;
+    assert_eq!(first.status, StatusCode::OK, "{}", first.body);
+    app.state
+        .queue_manager()
+        .release_idempotency_key("acct_idem", "order-2")
+        .await
+        .expect("Failed to release key");
+
+    let retry = submit_with_key(&app, "acct_idem", "order-2", data).await;
+    assert_eq!(retry.status, StatusCode::OK, "{}", retry.body);
+    assert_eq!(retry.headers[IDEMPOTENT_REPLAYED_HEADER], "true");
+    assert_eq!(retry.body["transaction_id"], first.body["transaction_id"]);
+    assert_eq!(stored_rows(&app, "acct_idem").await, 1);
+
+    app.teardown().await;
+}
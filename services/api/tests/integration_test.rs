@@ -239,7 +239,7 @@ async fn test_malformed_request() {
     
     // Send malformed JSON
     let response = client
-        .post(&format!("{}/v1/transactions/submit", API_BASE_URL))
+        .post(format!("{}/v1/transactions/submit", API_BASE_URL))
         .header("content-type", "application/json")
         .body("{ invalid json }")
         .send()
@@ -258,7 +258,7 @@ async fn test_missing_required_fields() {
     
     // Missing account_id
     let response = client
-        .post(&format!("{}/v1/transactions/submit", API_BASE_URL))
+        .post(format!("{}/v1/transactions/submit", API_BASE_URL))
         .json(&json!({
             "transaction_data": {"type": "test"}
         }))
@@ -270,7 +270,7 @@ async fn test_missing_required_fields() {
     
     // Missing transaction_data
     let response = client
-        .post(&format!("{}/v1/transactions/submit", API_BASE_URL))
+        .post(format!("{}/v1/transactions/submit", API_BASE_URL))
         .json(&json!({
             "account_id": "test_account"
         }))
@@ -0,0 +1,165 @@
+mod common;
+
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{LimitChangeRequest, NewLimitChangeRequest, NewRateLimit};
+use postgres_models::schema::{limit_change_requests, rate_limits};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use transaction_queue_api::rate_limit::{DEFAULT_SUBMIT_LIMIT, SUBMIT_LIMIT_TYPE};
+use uuid::Uuid;
+
+async fn insert_request(conn: &mut postgres_models::DbConnection, account_id: &str, max: i32) -> LimitChangeRequest {
+    diesel::insert_into(limit_change_requests::table)
+        .values(&NewLimitChangeRequest::new(account_id.to_string(), max, "launch traffic".to_string()))
+        .returning(LimitChangeRequest::as_returning())
+        .get_result(conn)
+        .await
+        .expect("Failed to insert limit request")
+}
+
+/// Test approval writes the limit with the request, keeping an existing window, and only once
+#[tokio::test]
+async fn test_approve_writes_limit_once() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+    diesel::insert_into(rate_limits::table)
+        .values(&NewRateLimit::new(account_id.clone(), SUBMIT_LIMIT_TYPE.to_string(), 100, 30))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let request = insert_request(&mut conn, &account_id, 500).await;
+
+    let (approved, limit) = LimitChangeRequest::approve(&mut conn, request.id, "ops", SUBMIT_LIMIT_TYPE, 60)
+        .await
+        .unwrap()
+        .expect("pending request should be approved");
+    assert_eq!(approved.status, "approved");
+    assert_eq!(approved.decided_by.as_deref(), Some("ops"));
+    assert!(approved.decided_at.is_some());
+    assert_eq!(limit.max_requests, 500);
+    assert_eq!(limit.window_seconds, 30);
+
+    // A decided request cannot be decided again
+    assert!(LimitChangeRequest::approve(&mut conn, request.id, "ops", SUBMIT_LIMIT_TYPE, 60)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(LimitChangeRequest::deny(&mut conn, request.id, "ops").await.unwrap().is_none());
+}
+
+/// Test denial leaves the account without a limit row
+#[tokio::test]
+async fn test_deny_leaves_limits_alone() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+    let request = insert_request(&mut conn, &account_id, 500).await;
+
+    let denied = LimitChangeRequest::deny(&mut conn, request.id, "ops").await.unwrap().unwrap();
+    assert_eq!(denied.status, "denied");
+
+    let limits: i64 = rate_limits::table
+        .filter(rate_limits::account_id.eq(&account_id))
+        .count()
+        .get_result(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(limits, 0);
+
+    // With nothing pending the account may ask again
+    insert_request(&mut conn, &account_id, 300).await;
+    let requests = LimitChangeRequest::list_for_account(&mut conn, &account_id).await.unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].status, "pending");
+}
+
+async fn submit_limit_header(client: &TestClient, account_id: &str) -> u32 {
+    let response = client
+        .submit_transaction(account_id, TestData::sample_transaction_data(), None)
+        .await
+        .expect("Failed to send request");
+    assert!(response.status().is_success(), "submit failed: {}", response.status());
+    response.headers()["X-RateLimit-Limit"].to_str().unwrap().parse().unwrap()
+}
+
+/// Test an approved request raises the limit on the very next submit
+#[tokio::test]
+async fn test_request_approve_flow() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+    // Caches the default limit, which approval must invalidate
+    assert_eq!(submit_limit_header(&client, &account_id).await, DEFAULT_SUBMIT_LIMIT);
+
+    let path = format!("/v1/accounts/{}/limit-requests", account_id);
+    let body = json!({ "requested_max_requests": 500, "justification": "Black Friday traffic" });
+    let response = client.json_request(Method::POST, &path, Some(body.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["status"], "pending");
+    assert_eq!(created["requested_max_requests"], 500);
+
+    // Only one open request at a time
+    let response = client.json_request(Method::POST, &path, Some(body)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["error"]["code"], "limit_request_pending");
+
+    let request_id = created["id"].as_str().unwrap();
+    let response = client
+        .admin_request(Method::POST, &format!("/limit-requests/{}/approve", request_id), ADMIN_API_KEY, None)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let approved: Value = response.json().await.unwrap();
+    assert_eq!(approved["request"]["status"], "approved");
+    assert_eq!(approved["limit"]["max_requests"], 500);
+
+    assert_eq!(submit_limit_header(&client, &account_id).await, 500);
+
+    let response = client.json_request(Method::GET, &path, None).await.unwrap();
+    let listed: Value = response.json().await.unwrap();
+    assert_eq!(listed[0]["id"], request_id);
+    assert_eq!(listed[0]["status"], "approved");
+    assert!(listed[0]["decided_by"].is_string());
+}
+
+/// Test invalid requests and decisions on missing or decided requests are rejected
+#[tokio::test]
+async fn test_invalid_requests_and_decisions() {
+    TestEnvironment::validate_test_environment().await;
+
+    let client = TestClient::new();
+    let path = format!("/v1/accounts/{}/limit-requests", TestData::unique_account_id());
+    for body in [
+        json!({ "requested_max_requests": 0, "justification": "more" }),
+        json!({ "requested_max_requests": 500, "justification": "   " }),
+    ] {
+        let response = client.json_request(Method::POST, &path, Some(body.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "body {}", body);
+    }
+
+    let response = client
+        .admin_request(Method::POST, &format!("/limit-requests/{}/deny", Uuid::new_v4()), ADMIN_API_KEY, None)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let body = json!({ "requested_max_requests": 500, "justification": "more" });
+    let created: Value = client
+        .json_request(Method::POST, &path, Some(body))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let deny_path = format!("/limit-requests/{}/deny", created["id"].as_str().unwrap());
+    let response = client.admin_request(Method::POST, &deny_path, ADMIN_API_KEY, None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.admin_request(Method::POST, &deny_path, ADMIN_API_KEY, None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
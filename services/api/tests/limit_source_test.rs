@@ -0,0 +1,128 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use common::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::NewRateLimit;
+use postgres_models::schema::rate_limits;
+use redis_cache::{CachedLimit, RateLimitResult, RateLimitStrategy};
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower::ServiceExt;
+use transaction_queue_api::config::Config;
+use transaction_queue_api::rate_limit::{
+    invalidate_cached_limit, LimitSource, SubmitLimit, DEFAULT_SUBMIT_LIMIT, RATE_LIMIT_POLICY_HEADER,
+    SUBMIT_LIMIT_TYPE,
+};
+use transaction_queue_api::{v1, AppState};
+
+const TIER_PREFIX: &str = "srctier_";
+
+fn config() -> Config {
+    let lookup = |var: &str| match var {
+        "DATABASE_URL" => Some(std::env::var(var).unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string())),
+        "REDIS_URL" => Some(std::env::var(var).unwrap_or_else(|_| DEFAULT_REDIS_URL.to_string())),
+        "SUBMIT_TIER_LIMITS" => Some(format!("{}:5", TIER_PREFIX)),
+        _ => None,
+    };
+    Config::from_lookup(&service_config::Env::new(&lookup)).expect("Invalid test config")
+}
+
+/// Mark the account as rejected so its next submit is answered 429 without
+/// consulting the limiter
+fn mark_rejected(state: &AppState, account_id: &str) {
+    let reset_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 60;
+    state.rejections.record(
+        account_id,
+        &RateLimitResult {
+            allowed: false,
+            remaining: 0,
+            reset_at,
+        },
+    );
+}
+
+/// Submit for `account_id` and return the 429's policy header and details
+async fn rejected_submit(state: &AppState, account_id: &str) -> (String, Value) {
+    let app = v1::router(state.clone()).with_state(state.clone());
+    let body = json!({ "account_id": account_id, "transaction_data": { "amount": 1 } });
+    let request = Request::post("/transactions/submit")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let header = response.headers()[RATE_LIMIT_POLICY_HEADER].to_str().unwrap().to_string();
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    (header, body["error"]["details"].clone())
+}
+
+/// Test an account row beats its tier, and a tier beats the global default
+#[test]
+fn test_resolve_precedence() {
+    let config = config();
+    let row = CachedLimit {
+        max_requests: 7,
+        window_seconds: 30,
+        strategy: RateLimitStrategy::TokenBucket,
+    };
+
+    let account = SubmitLimit::resolve(Some(row), "srctier_acme", &config);
+    assert_eq!((account.max_requests, account.window_seconds), (7, 30));
+    assert_eq!(account.source, LimitSource::AccountConfig);
+    assert_eq!(account.strategy, RateLimitStrategy::TokenBucket);
+
+    let tier = SubmitLimit::resolve(None, "srctier_acme", &config);
+    assert_eq!((tier.max_requests, tier.window_seconds), (5, 60));
+    assert_eq!(tier.source, LimitSource::TierDefault);
+    assert_eq!(tier.strategy, RateLimitStrategy::SlidingWindow);
+
+    let global = SubmitLimit::resolve(None, "other_acme", &config);
+    assert_eq!(global.max_requests, DEFAULT_SUBMIT_LIMIT);
+    assert_eq!(global.source, LimitSource::GlobalDefault);
+}
+
+/// Test the 429 reports the tier default until the account gets its own limit
+#[tokio::test]
+async fn test_reported_source_switches_to_account_config() {
+    let state = AppState::new(config()).await.expect("Failed to build app state");
+    let account_id = format!("{}{}", TIER_PREFIX, TestData::unique_account_id());
+
+    mark_rejected(&state, &account_id);
+    let (header, details) = rejected_submit(&state, &account_id).await;
+    assert_eq!(header, "5;w=60;type=submit;source=tier_default");
+    assert_eq!(
+        details,
+        json!({ "limit_type": "submit", "source": "tier_default", "max_requests": 5, "window_seconds": 60 })
+    );
+
+    let mut conn = state.db_pool.get().await.expect("Failed to get connection");
+    diesel::insert_into(rate_limits::table)
+        .values(&NewRateLimit::new(account_id.clone(), SUBMIT_LIMIT_TYPE.to_string(), 7, 30))
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert account limit");
+    drop(conn);
+    invalidate_cached_limit(&state, &account_id, SUBMIT_LIMIT_TYPE).await;
+
+    mark_rejected(&state, &account_id);
+    let (header, details) = rejected_submit(&state, &account_id).await;
+    assert_eq!(header, "7;w=30;type=submit;source=account_config");
+    assert_eq!(details["source"], "account_config");
+    assert_eq!(details["max_requests"], 7);
+}
+
+/// Test accounts outside every tier report the global default
+#[tokio::test]
+async fn test_untiered_account_reports_global_default() {
+    let state = AppState::new(config()).await.expect("Failed to build app state");
+    let account_id = TestData::unique_account_id();
+
+    mark_rejected(&state, &account_id);
+    let (header, details) = rejected_submit(&state, &account_id).await;
+
+    assert_eq!(header, format!("{};w=60;type=submit;source=global_default", DEFAULT_SUBMIT_LIMIT));
+    assert_eq!(details["source"], "global_default");
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use common::isolated::{IsolatedApp, TestResponse};
+use postgres_models::models::{NewTransactionQueue, TransactionQueue};
+use postgres_models::sources::{RandomIds, SystemClock};
+use serde_json::json;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+const ACCOUNT: &str = "acct_list";
+const STATUSES: [&str; 4] = ["pending", "processing", "completed", "failed"];
+
+async fn list(app: &IsolatedApp, query: &str) -> TestResponse {
+    let request = Request::get(format!("/v1/transactions?{}", query)).body(Body::empty()).unwrap();
+    app.request(request).await
+}
+
+/// Insert `count` rows for `account_id`, a second apart in groups of five
+/// sharing a creation time, cycling through statuses and priorities
+async fn insert_rows(
+    app: &IsolatedApp,
+    account_id: &str,
+    count: usize,
+    newest: DateTime<Utc>,
+) -> Vec<TransactionQueue> {
+    let rows: Vec<_> = (0..count)
+        .map(|i| {
+            let mut row = NewTransactionQueue::new(account_id.to_string(), json!({ "n": i }), &RandomIds, &SystemClock);
+            row.created_at = newest - TimeDelta::seconds((i / 5) as i64);
+            row.status = STATUSES[i % STATUSES.len()].to_string();
+            row.priority = (i % 10) as i32;
+            row
+        })
+        .collect();
+    let mut conn = app.state.db_pool.get().await.expect("Failed to get connection");
+    NewTransactionQueue::insert_batch(&mut conn, &rows).await.unwrap()
+}
+
+fn ids(response: &TestResponse) -> Vec<Uuid> {
+    let items = response.body["items"].as_array().unwrap();
+    items.iter().map(|item| item["id"].as_str().unwrap().parse().unwrap()).collect()
+}
+
+/// Test walking 250 rows a page at a time sees each exactly once, newest first, while new rows arrive
+#[tokio::test]
+async fn test_pages_have_no_duplicates_or_gaps() {
+    let app = IsolatedApp::new().await;
+    let newest = Utc::now() - TimeDelta::hours(1);
+    let inserted = insert_rows(&app, ACCOUNT, 250, newest).await;
+    insert_rows(&app, "acct_list_other", 20, newest).await;
+    let mut expected = inserted.clone();
+    expected.sort_by_key(|row| std::cmp::Reverse((row.created_at, row.id)));
+
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    let mut pages = 0;
+    loop {
+        let query = match &cursor {
+            Some(cursor) => format!("account_id={}&limit=40&cursor={}", ACCOUNT, cursor),
+            None => format!("account_id={}&limit=40", ACCOUNT),
+        };
+        let response = list(&app, &query).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(response.body["status_counts"]["pending"], 63 + pages.min(1), "{}", response.body);
+        seen.extend(ids(&response));
+        pages += 1;
+        if pages == 1 {
+            // Rows submitted mid-walk are newer than the cursor, so they neither shift nor repeat later pages
+            insert_rows(&app, ACCOUNT, 1, Utc::now()).await;
+        }
+        match response.body["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    assert_eq!(pages, 7);
+    assert_eq!(seen.len(), 250);
+    assert_eq!(seen.iter().collect::<HashSet<_>>().len(), 250);
+    assert_eq!(seen, expected.iter().map(|row| row.id).collect::<Vec<_>>());
+
+    app.teardown().await;
+}
+
+/// Test the filters narrow the listing while the status counts still cover the whole account
+#[tokio::test]
+async fn test_filters() {
+    let app = IsolatedApp::new().await;
+    let newest = Utc::now() - TimeDelta::hours(1);
+    let inserted = insert_rows(&app, ACCOUNT, 100, newest).await;
+    // As stored, to the microsecond; the 46th row is nine seconds older than the newest
+    let created_after = inserted[45].created_at;
+
+    let query = format!(
+        "account_id={}&status=failed&min_priority=5&created_after={}&limit=200",
+        ACCOUNT,
+        created_after.to_rfc3339_opts(SecondsFormat::Micros, true)
+    );
+    let response = list(&app, &query).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let expected: HashSet<Uuid> = inserted
+        .iter()
+        .filter(|row| row.status == "failed" && row.priority >= 5 && row.created_at >= created_after)
+        .map(|row| row.id)
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(ids(&response).into_iter().collect::<HashSet<_>>(), expected);
+    assert!(response.body["next_cursor"].is_null());
+    let counts = &response.body["status_counts"];
+    for status in STATUSES {
+        assert_eq!(counts[status], 25, "{}", status);
+    }
+    assert_eq!((counts["retry"].as_i64(), counts["cancelled"].as_i64()), (Some(0), Some(0)));
+
+    app.teardown().await;
+}
+
+/// Test malformed cursors, unknown statuses and out of range limits are refused
+#[tokio::test]
+async fn test_invalid_queries() {
+    let app = IsolatedApp::new().await;
+    let queries = [
+        format!("account_id={}&cursor=not-a-cursor", ACCOUNT),
+        // Valid base64 of something that is not a position
+        format!("account_id={}&cursor=aGVsbG8", ACCOUNT),
+        format!("account_id={}&status=done", ACCOUNT),
+        format!("account_id={}&limit=0", ACCOUNT),
+        format!("account_id={}&limit=201", ACCOUNT),
+        "status=pending".to_string(),
+    ];
+    for query in queries {
+        let response = list(&app, &query).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", query);
+    }
+
+    app.teardown().await;
+}
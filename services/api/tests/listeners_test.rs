@@ -0,0 +1,92 @@
+use axum::Router;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::net::SocketAddr;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+    sync::oneshot,
+};
+use transaction_queue_api::{health, server::Listeners};
+
+/// Send a plain HTTP/1.1 GET over a Unix socket and return the raw response
+async fn unix_get(path: &std::path::Path, uri: &str) -> String {
+    let mut stream = UnixStream::connect(path).await.expect("Failed to connect to Unix socket");
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", uri);
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+/// Test /health is served on an ephemeral TCP port and a Unix socket at once,
+/// and the socket file is removed on shutdown
+#[tokio::test]
+async fn test_health_on_tcp_and_unix_listeners() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket_path = dir.path().join("api.sock");
+    let tcp_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+    let listeners = Listeners::bind(&[tcp_addr], Some(&socket_path)).await.unwrap();
+    let tcp_addr = listeners.tcp_addrs().unwrap()[0];
+    assert_ne!(tcp_addr.port(), 0);
+    assert!(socket_path.exists());
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let app: Router = health::router();
+    let server = tokio::spawn(listeners.serve(app, async {
+        let _ = shutdown_rx.await;
+    }));
+
+    // TCP
+    let response = reqwest::get(format!("http://{}/health", tcp_addr)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+
+    // Unix socket
+    let response = unix_get(&socket_path, "/health").await;
+    assert!(response.starts_with("HTTP/1.1 200"), "unexpected response: {}", response);
+    assert!(response.contains(r#""status":"ok""#));
+
+    shutdown_tx.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    assert!(!socket_path.exists(), "socket file should be removed on shutdown");
+}
+
+/// Test a stale socket file from a previous run does not prevent binding
+#[tokio::test]
+async fn test_stale_unix_socket_is_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket_path = dir.path().join("api.sock");
+    std::fs::write(&socket_path, b"").unwrap();
+
+    let listeners = Listeners::bind(&[], Some(&socket_path)).await.unwrap();
+    assert!(socket_path.exists());
+
+    drop(listeners);
+    assert!(!socket_path.exists());
+}
+
+/// Test several TCP listeners can be bound together
+#[tokio::test]
+async fn test_multiple_tcp_listeners() {
+    let addrs: Vec<SocketAddr> = vec!["127.0.0.1:0".parse().unwrap(), "127.0.0.1:0".parse().unwrap()];
+    let listeners = Listeners::bind(&addrs, None).await.unwrap();
+    let bound = listeners.tcp_addrs().unwrap();
+    assert_eq!(bound.len(), 2);
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = tokio::spawn(listeners.serve(health::router(), async {
+        let _ = shutdown_rx.await;
+    }));
+
+    for addr in bound {
+        let response = reqwest::get(format!("http://{}/health", addr)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    shutdown_tx.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use common::isolated::IsolatedApp;
+use serde_json::json;
+use tower::ServiceExt;
+use transaction_queue_api::metrics;
+
+/// Sum of the samples whose name and labels start with `series`
+fn sample_sum(exposition: &str, series: &str) -> f64 {
+    exposition
+        .lines()
+        .filter(|line| line.starts_with(series))
+        .filter_map(|line| line.rsplit_once(' ')?.1.parse::<f64>().ok())
+        .sum()
+}
+
+/// Test a scrape after a few submits reports them, the 429, handler latency, queue depth and the pool
+#[tokio::test]
+async fn test_metrics_after_submits() {
+    // The recorder is process-wide, so this is the only test in this binary
+    let handle = metrics::install_recorder().expect("Failed to install the recorder");
+    let app = IsolatedApp::with_vars(&[("SUBMIT_TIER_LIMITS", "tiny_:1")]).await;
+    let router = transaction_queue_api::app(app.state.clone(), Some(handle));
+
+    for n in 0..3 {
+        app.submit_transaction_expect_success("acct_metrics", json!({ "n": n }), None).await;
+    }
+    app.submit_transaction_expect_success("tiny_metrics", json!({ "n": 0 }), None).await;
+    app.submit_transaction_expect_rate_limit("tiny_metrics", json!({ "n": 1 })).await;
+
+    let response = router.oneshot(Request::get("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let exposition = String::from_utf8(body.to_vec()).unwrap();
+
+    assert_eq!(sample_sum(&exposition, "submissions_total{"), 5.0, "{}", exposition);
+    assert_eq!(sample_sum(&exposition, "submissions_total{status=\"429\"}"), 1.0);
+    assert_eq!(sample_sum(&exposition, "rate_limit_rejections_total{tier=\"tiny_\"}"), 1.0);
+    let submit_route = "route=\"/v1/transactions/submit\"";
+    let counted: f64 = exposition
+        .lines()
+        .filter(|line| line.starts_with("http_requests_total{") && line.contains(submit_route))
+        .filter_map(|line| line.rsplit_once(' ')?.1.parse::<f64>().ok())
+        .sum();
+    assert_eq!(counted, 5.0);
+    assert!(sample_sum(&exposition, "http_request_duration_seconds_count{") >= 5.0);
+    assert!(exposition.contains("http_request_duration_seconds_bucket{"), "latency is a histogram");
+    assert_eq!(sample_sum(&exposition, "queue_depth{"), 4.0);
+    assert!(sample_sum(&exposition, "db_pool_connections{") > 0.0);
+
+    app.teardown().await;
+}
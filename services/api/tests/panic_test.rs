@@ -0,0 +1,72 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use axum::routing::get;
+use axum::{middleware, Router};
+use common::*;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use serde_json::Value;
+use tower::ServiceExt;
+use transaction_queue_api::diagnostics::{log_server_errors, REQUEST_ID_HEADER};
+use transaction_queue_api::panics::{self, catch_panic, INTERNAL_PANIC};
+use transaction_queue_api::{health, v1};
+
+async fn panicking() -> &'static str {
+    panic!("boom: {}", 42)
+}
+
+/// The API's routes plus a route that always panics, layered as in main
+async fn app() -> Router {
+    let state = TestEnvironment::app_state().await;
+    Router::new()
+        .route("/test/panic", get(panicking))
+        .merge(health::router())
+        .nest("/v1", v1::router(state.clone()))
+        .layer(middleware::from_fn(catch_panic))
+        .layer(middleware::from_fn_with_state(state.clone(), log_server_errors))
+        .with_state(state)
+}
+
+/// Test a panicking handler is answered with the JSON 500 and counted
+#[tokio::test]
+async fn test_panic_becomes_500() {
+    panics::install_hook();
+    let app = app().await;
+    let recorder = PrometheusBuilder::new().build_recorder();
+    let handle = recorder.handle();
+
+    let response = {
+        let _guard = metrics::set_default_local_recorder(&recorder);
+        let request = Request::get("/test/panic")
+            .header(REQUEST_ID_HEADER, "panic-request")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap()
+    };
+
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "panic-request");
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(body["error"]["code"], INTERNAL_PANIC);
+    assert_eq!(body["error"]["status"], 500);
+    assert_eq!(body["error"]["message"], "Internal Server Error");
+
+    let rendered = handle.render();
+    assert!(rendered.contains("http_panics_total 1"), "{}", rendered);
+}
+
+/// Test requests that do not panic pass through untouched
+#[tokio::test]
+async fn test_other_routes_unaffected() {
+    let app = app().await;
+
+    let response = app
+        .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(body["status"], "ok");
+}
@@ -0,0 +1,102 @@
+//! Allocation and timing comparison of the submit payload handling for a
+//! 1MB transaction. Kept in its own test binary because it installs a
+//! counting global allocator.
+
+use serde::Deserialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use transaction_queue_api::payload::TransactionPayload;
+
+struct CountingAllocator;
+
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: u32 = 20;
+
+#[derive(Deserialize)]
+struct ValueRequest {
+    transaction_data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct RawRequest {
+    transaction_data: TransactionPayload,
+}
+
+/// Previous submit path: parse into a Value, clone it for the insert, then
+/// serialize for the size check, the queue member and the jsonb bind
+fn value_path(body: &[u8]) -> usize {
+    let request: ValueRequest = serde_json::from_slice(body).unwrap();
+    let for_insert = request.transaction_data.clone();
+    let size = serde_json::to_vec(&request.transaction_data).unwrap().len();
+    let queue_member = request.transaction_data.to_string();
+    let bind = serde_json::to_vec(&for_insert).unwrap();
+    size + queue_member.len() + bind.len()
+}
+
+/// Current submit path: one copy of the JSON text reused everywhere
+fn raw_path(body: &[u8]) -> usize {
+    let request: RawRequest = serde_json::from_slice(body).unwrap();
+    request.transaction_data.validate().unwrap_or(());
+    let size = request.transaction_data.len();
+    let queue_member = request.transaction_data.as_str();
+    let bind = request.transaction_data.as_raw().get().as_bytes();
+    size + queue_member.len() + bind.len()
+}
+
+fn measure(body: &[u8], path: fn(&[u8]) -> usize) -> (usize, Duration) {
+    // Warm up once so lazy statics are not counted
+    std::hint::black_box(path(body));
+
+    let before = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        std::hint::black_box(path(std::hint::black_box(body)));
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    let allocated = (ALLOCATED_BYTES.load(Ordering::Relaxed) - before) / ITERATIONS as usize;
+    (allocated, elapsed)
+}
+
+/// Test the raw payload path allocates and takes far less than the Value path for 1MB
+#[test]
+fn test_raw_payload_reduces_allocations_for_1mb() {
+    let records: Vec<_> = (0..10_000)
+        .map(|i| serde_json::json!({"index": i, "account": format!("acct_{:06}", i), "memo": "x".repeat(60)}))
+        .collect();
+    let body = serde_json::to_vec(&serde_json::json!({ "transaction_data": records })).unwrap();
+    assert!(body.len() > 1024 * 1024 - 200_000, "payload should be about 1MB, got {}", body.len());
+
+    let (value_bytes, value_time) = measure(&body, value_path);
+    let (raw_bytes, raw_time) = measure(&body, raw_path);
+
+    println!("📊 1MB payload, per request:");
+    println!("   Value path: {:>10} bytes allocated, {:?}", value_bytes, value_time);
+    println!("   Raw path:   {:>10} bytes allocated, {:?}", raw_bytes, raw_time);
+
+    // The raw path allocates one copy of the text; the Value path allocates
+    // the parsed tree, its clone and three serialized buffers
+    assert!(raw_bytes <= body.len() * 2, "raw path allocated {} bytes", raw_bytes);
+    assert!(raw_bytes * 4 < value_bytes, "expected at least 4x fewer bytes: raw {} vs value {}", raw_bytes, value_bytes);
+    assert!(raw_time < value_time, "raw path should be faster: {:?} vs {:?}", raw_time, value_time);
+}
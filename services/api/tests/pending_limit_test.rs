@@ -0,0 +1,179 @@
+mod common;
+
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use postgres_models::schema::transaction_queue;
+use redis_cache::QueueManager;
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use transaction_queue_api::pending::{self, MAX_PENDING_TYPE, PENDING_LIMIT_EXCEEDED};
+use uuid::Uuid;
+
+async fn set_pending_cap(client: &TestClient, account_id: &str, cap: i32) -> reqwest::Response {
+    client
+        .admin_request(
+            Method::PUT,
+            &format!("/accounts/{}/limits/{}", account_id, MAX_PENDING_TYPE),
+            ADMIN_API_KEY,
+            Some(json!({ "max_requests": cap, "window_seconds": 60 })),
+        )
+        .await
+        .expect("Request failed")
+}
+
+/// Test an account at its cap is rejected until the worker finishes one of its items
+#[tokio::test]
+async fn test_pending_cap_rejects_until_completion() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+    assert_eq!(set_pending_cap(&client, &account_id, 3).await.status(), StatusCode::OK);
+
+    let mut transaction_ids = Vec::new();
+    for _ in 0..3 {
+        let (transaction_id, _, _) = client
+            .submit_transaction_expect_success(&account_id, TestData::sample_transaction_data(), None)
+            .await;
+        transaction_ids.push(transaction_id.parse::<Uuid>().unwrap());
+    }
+
+    let response = client
+        .submit_transaction(&account_id, TestData::sample_transaction_data(), None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get("X-RateLimit-Limit").is_some());
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], PENDING_LIMIT_EXCEEDED);
+
+    // Finish the first item the way the worker does
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let queue_manager = QueueManager::new(TestEnvironment::redis_pool().await);
+    let picked = TransactionQueue::transition_status(
+        &mut conn,
+        transaction_ids[0],
+        TransactionStatus::Pending,
+        TransactionStatus::Processing,
+    )
+    .await
+    .unwrap();
+    assert!(picked);
+    let finished = pending::finish(
+        &mut conn,
+        &queue_manager,
+        transaction_ids[0],
+        &account_id,
+        TransactionStatus::Completed,
+    )
+    .await
+    .unwrap();
+    assert!(finished);
+    assert_eq!(queue_manager.pending_count(&account_id).await.unwrap(), 2);
+
+    client
+        .submit_transaction_expect_success(&account_id, TestData::sample_transaction_data(), None)
+        .await;
+}
+
+/// Test a retry keeps the slot and a second finish does not release it twice
+#[tokio::test]
+async fn test_only_terminal_transitions_release() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+
+    let (transaction_id, _, _) = client
+        .submit_transaction_expect_success(&account_id, TestData::sample_transaction_data(), None)
+        .await;
+    let transaction_id: Uuid = transaction_id.parse().unwrap();
+
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let queue_manager = QueueManager::new(TestEnvironment::redis_pool().await);
+    assert_eq!(queue_manager.pending_count(&account_id).await.unwrap(), 1);
+
+    assert!(TransactionQueue::transition_status(
+        &mut conn,
+        transaction_id,
+        TransactionStatus::Pending,
+        TransactionStatus::Processing
+    )
+    .await
+    .unwrap());
+    assert!(pending::finish(&mut conn, &queue_manager, transaction_id, &account_id, TransactionStatus::Retry)
+        .await
+        .unwrap());
+    assert_eq!(queue_manager.pending_count(&account_id).await.unwrap(), 1);
+
+    TransactionQueue::transition_status(
+        &mut conn,
+        transaction_id,
+        TransactionStatus::Retry,
+        TransactionStatus::Processing,
+    )
+    .await
+    .unwrap();
+    assert!(pending::finish(&mut conn, &queue_manager, transaction_id, &account_id, TransactionStatus::Failed)
+        .await
+        .unwrap());
+    assert!(!pending::finish(&mut conn, &queue_manager, transaction_id, &account_id, TransactionStatus::Failed)
+        .await
+        .unwrap());
+    assert_eq!(queue_manager.pending_count(&account_id).await.unwrap(), 0);
+}
+
+/// Test reconciliation restores counters from Postgres in both directions
+#[tokio::test]
+async fn test_reconcile_corrects_drift() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let queue_manager = QueueManager::new(TestEnvironment::redis_pool().await);
+
+    // Two open rows that the counter missed
+    let undercounted = TestData::unique_account_id();
+    for _ in 0..2 {
+        diesel::insert_into(transaction_queue::table)
+            .values((
+                transaction_queue::id.eq(Uuid::new_v4()),
+                transaction_queue::account_id.eq(&undercounted),
+                transaction_queue::transaction_data.eq(TestData::sample_transaction_data()),
+            ))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert row");
+    }
+    // A counter left behind by releases that never happened
+    let overcounted = TestData::unique_account_id();
+    queue_manager.set_pending_count(&overcounted, 5).await.unwrap();
+
+    let corrected = pending::reconcile(&mut conn, &queue_manager).await.unwrap();
+    assert!(corrected >= 2, "corrected {}", corrected);
+    assert_eq!(queue_manager.pending_count(&undercounted).await.unwrap(), 2);
+    assert_eq!(queue_manager.pending_count(&overcounted).await.unwrap(), 0);
+    assert!(!queue_manager.pending_accounts().await.unwrap().contains(&overcounted));
+}
+
+/// Test releasing an empty counter stays at zero
+#[tokio::test]
+async fn test_release_floors_at_zero() {
+    let queue_manager = QueueManager::new(TestEnvironment::redis_pool().await);
+    let account_id = TestData::unique_account_id();
+
+    assert_eq!(queue_manager.release_pending(&account_id).await.unwrap(), 0);
+    assert!(queue_manager.reserve_pending(&account_id, 1).await.unwrap());
+    assert!(!queue_manager.reserve_pending(&account_id, 1).await.unwrap());
+    assert_eq!(queue_manager.pending_count(&account_id).await.unwrap(), 1);
+}
+
+/// Test a max_pending override must allow at least one transaction
+#[tokio::test]
+async fn test_invalid_pending_cap_rejected() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    let response = set_pending_cap(&client, &TestData::unique_account_id(), 0).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use common::*;
+use std::time::Duration;
+
+fn metrics(durations_ms: &[u64], total_seconds: u64) -> PerformanceMetrics {
+    let mut durations: Vec<Duration> = durations_ms.iter().map(|&ms| Duration::from_millis(ms)).collect();
+    PerformanceMetrics::calculate(&mut durations, Duration::from_secs(total_seconds))
+}
+
+fn baseline() -> PerformanceMetrics {
+    metrics(&(1..=100).collect::<Vec<_>>(), 1)
+}
+
+fn diff<'a>(comparison: &'a PerformanceComparison, metric: &str) -> &'a MetricDiff {
+    comparison.diffs.iter().find(|d| d.metric == metric).unwrap()
+}
+
+/// Test the raw percentile table and status histogram are filled in
+#[test]
+fn test_percentiles_and_status_codes() {
+    let metrics = baseline().with_status_codes(&[201, 201, 429, 0]);
+
+    let p50 = metrics.percentiles.iter().find(|row| row.percentile == 50.0).unwrap();
+    assert_eq!(p50.duration_ms, 50);
+    let p99 = metrics.percentiles.iter().find(|row| row.percentile == 99.0).unwrap();
+    assert_eq!(p99.duration_ms, metrics.p99_duration_ms);
+    assert_eq!(metrics.percentiles.len(), REPORTED_PERCENTILES.len());
+
+    assert_eq!(metrics.status_codes[&201], 2);
+    assert_eq!(metrics.status_codes[&429], 1);
+    assert_eq!(metrics.status_codes[&0], 1);
+}
+
+/// Test metrics round-trip through a JSON artifact
+#[test]
+fn test_json_round_trip() {
+    let metrics = baseline().with_status_codes(&[201, 429]);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested/run.json");
+
+    metrics.write_json(&path).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(json["p99_duration_ms"], 99);
+    assert_eq!(json["status_codes"]["429"], 1);
+    assert_eq!(json["percentiles"][0]["percentile"], 50.0);
+
+    let read = PerformanceMetrics::read_json(&path).unwrap();
+    assert_eq!(read.total_requests, metrics.total_requests);
+    assert_eq!(read.p95_duration_ms, metrics.p95_duration_ms);
+    assert_eq!(read.percentiles, metrics.percentiles);
+    assert_eq!(read.status_codes, metrics.status_codes);
+}
+
+/// Test changes within tolerance are not regressions
+#[test]
+fn test_compare_within_tolerance() {
+    let current = metrics(&(1..=100).map(|ms| ms + ms / 20).collect::<Vec<_>>(), 1);
+
+    let comparison = compare(&baseline(), &current, 0.10);
+
+    assert!(!comparison.has_regressions(), "{:?}", comparison);
+    assert!(diff(&comparison, "p99_duration_ms").relative_change > 0.0);
+}
+
+/// Test slower latencies beyond tolerance are reported as regressions
+#[test]
+fn test_compare_flags_latency_regression() {
+    let current = metrics(&(1..=100).map(|ms| ms * 2).collect::<Vec<_>>(), 1);
+
+    let comparison = compare(&baseline(), &current, 0.10);
+
+    assert!(comparison.has_regressions());
+    let p99 = diff(&comparison, "p99_duration_ms");
+    assert!(p99.regressed);
+    assert!((p99.relative_change - 1.0).abs() < 1e-9);
+
+    // The same change is fine with a loose enough tolerance
+    assert!(!diff(&compare(&baseline(), &current, 1.5), "p99_duration_ms").regressed);
+}
+
+/// Test throughput counts as a regression only when it drops
+#[test]
+fn test_compare_throughput_direction() {
+    let slower = metrics(&(1..=100).collect::<Vec<_>>(), 2);
+    let comparison = compare(&baseline(), &slower, 0.10);
+    let rps = diff(&comparison, "requests_per_second");
+    assert!(rps.regressed);
+    assert!((rps.relative_change + 0.5).abs() < 1e-9);
+
+    let comparison = compare(&slower, &baseline(), 0.10);
+    assert!(!diff(&comparison, "requests_per_second").regressed);
+}
+
+/// Test the comparison is serializable for CI output
+#[test]
+fn test_comparison_serializes() {
+    let comparison = compare(&baseline(), &baseline(), 0.05);
+    let json = serde_json::to_value(&comparison).unwrap();
+    assert_eq!(json["tolerance"], 0.05);
+    assert_eq!(json["diffs"].as_array().unwrap().len(), 5);
+    assert!(!comparison.has_regressions());
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::TransactionQueue;
+use postgres_models::schema::transaction_queue;
+use redis_cache::{HoldReason, QueueManager};
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use transaction_queue_api::holds;
+use uuid::Uuid;
+
+async fn load_transaction(transaction_id: &str) -> TransactionQueue {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get().await.expect("Failed to get connection");
+    transaction_queue::table
+        .find(Uuid::parse_str(transaction_id).unwrap())
+        .select(TransactionQueue::as_select())
+        .first(&mut conn)
+        .await
+        .expect("Transaction row missing")
+}
+
+async fn get_status(client: &TestClient, transaction_id: &str) -> Value {
+    let response = client
+        .get(&format!("/v1/transactions/{}", transaction_id))
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().await.expect("Invalid JSON")
+}
+
+/// Test hold reasons round trip through their wire names
+#[test]
+fn test_hold_reason_names() {
+    for reason in [
+        HoldReason::AccountPaused,
+        HoldReason::ConcurrencyCap,
+        HoldReason::ScheduledInFuture,
+        HoldReason::CircuitOpen,
+    ] {
+        assert_eq!(HoldReason::parse(reason.as_str()), Some(reason));
+        assert_eq!(serde_json::to_value(reason).unwrap(), reason.as_str());
+    }
+    assert_eq!(HoldReason::parse("unknown"), None);
+}
+
+/// Test a paused account's pending transaction reports the hold on its status,
+/// and the hold clears once the account is resumed
+#[tokio::test]
+async fn test_paused_account_hold_on_status() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let queue_manager = QueueManager::new(TestEnvironment::redis_pool().await);
+    let account_id = TestData::unique_account_id();
+    let pause_path = format!("/accounts/{}/pause", account_id);
+
+    let response = client
+        .admin_request(Method::PUT, &pause_path, ADMIN_API_KEY, None)
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let (transaction_id, _, _) = client
+        .submit_transaction_expect_success(&account_id, TestData::sample_transaction_data(), None)
+        .await;
+    let transaction = load_transaction(&transaction_id).await;
+
+    // No skip decision recorded yet
+    let status = get_status(&client, &transaction_id).await;
+    assert!(status.get("processing_hold").is_none());
+
+    let hold = holds::apply(&queue_manager, &transaction).await.unwrap().expect("Expected a hold");
+    assert_eq!(hold.reason, HoldReason::AccountPaused);
+
+    let status = get_status(&client, &transaction_id).await;
+    assert_eq!(status["status"], "pending");
+    assert_eq!(status["processing_hold"]["reason"], "account_paused");
+    assert!(status["processing_hold"]["since"].is_string());
+
+    // A repeated skip for the same reason keeps the original timestamp
+    let again = holds::apply(&queue_manager, &transaction).await.unwrap().unwrap();
+    assert_eq!(again.since, hold.since);
+
+    let response = client
+        .admin_request(Method::DELETE, &pause_path, ADMIN_API_KEY, None)
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    assert!(holds::apply(&queue_manager, &transaction).await.unwrap().is_none());
+    let status = get_status(&client, &transaction_id).await;
+    assert!(status.get("processing_hold").is_none());
+}
+
+/// Test an unknown transaction id returns 404
+#[tokio::test]
+async fn test_status_unknown_transaction() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    let response = client
+        .get(&format!("/v1/transactions/{}", Uuid::new_v4()))
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
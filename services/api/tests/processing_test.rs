@@ -0,0 +1,214 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use postgres_models::processing::{self, idempotency_key, ProcessError, ProcessOutcome, TransactionProcessor};
+use postgres_models::schema::transaction_queue;
+use postgres_models::truncate::MAX_ERROR_MESSAGE_BYTES;
+use postgres_models::DbConnection;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tower::ServiceExt;
+use transaction_queue_api::v1;
+use uuid::Uuid;
+
+/// Records every call and executes each idempotency key once, replaying
+/// the stored result for repeats the way a real processor must
+#[derive(Default)]
+struct RecordingProcessor {
+    calls: Mutex<Vec<String>>,
+    executed: Mutex<HashMap<String, Value>>,
+}
+
+impl RecordingProcessor {
+    fn calls(&self) -> usize {
+        self.calls.lock().unwrap().len()
+    }
+
+    fn executions(&self) -> usize {
+        self.executed.lock().unwrap().len()
+    }
+}
+
+impl TransactionProcessor for RecordingProcessor {
+    async fn execute(&self, idempotency_key: &str, _transaction: &TransactionQueue) -> Result<Value, String> {
+        self.calls.lock().unwrap().push(idempotency_key.to_string());
+        let mut executed = self.executed.lock().unwrap();
+        let execution = executed.len() + 1;
+        Ok(executed
+            .entry(idempotency_key.to_string())
+            .or_insert_with(|| json!({ "execution": execution }))
+            .clone())
+    }
+}
+
+/// Fails every call with a message far over the stored limit
+struct FailingProcessor;
+
+impl TransactionProcessor for FailingProcessor {
+    async fn execute(&self, _idempotency_key: &str, _transaction: &TransactionQueue) -> Result<Value, String> {
+        Err(format!("rpc error: {}", "é".repeat(MAX_ERROR_MESSAGE_BYTES)))
+    }
+}
+
+async fn insert_processing_row(conn: &mut DbConnection) -> Uuid {
+    let id = Uuid::new_v4();
+    diesel::insert_into(transaction_queue::table)
+        .values((
+            transaction_queue::id.eq(id),
+            transaction_queue::account_id.eq(TestData::unique_account_id()),
+            transaction_queue::transaction_data.eq(TestData::sample_transaction_data()),
+            transaction_queue::status.eq(TransactionStatus::Processing.as_str()),
+        ))
+        .execute(conn)
+        .await
+        .expect("Failed to insert processing row");
+    id
+}
+
+async fn load_row(conn: &mut DbConnection, id: Uuid) -> TransactionQueue {
+    transaction_queue::table
+        .find(id)
+        .select(TransactionQueue::as_select())
+        .first(conn)
+        .await
+        .expect("Failed to load row")
+}
+
+/// Test a processed row stores its result once and is not executed again
+#[tokio::test]
+async fn test_result_is_persisted_once() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let processor = RecordingProcessor::default();
+    let id = insert_processing_row(&mut conn).await;
+
+    let outcome = processing::process(&mut conn, &processor, id).await.unwrap();
+    assert_eq!(outcome, ProcessOutcome::Completed(json!({ "execution": 1 })));
+
+    let row = load_row(&mut conn, id).await;
+    assert_eq!(row.status, "completed");
+    assert_eq!(row.result, Some(json!({ "execution": 1 })));
+    assert!(row.processed_at.is_some());
+
+    let again = processing::process(&mut conn, &processor, id).await.unwrap();
+    assert_eq!(again, ProcessOutcome::AlreadyCompleted(Some(json!({ "execution": 1 }))));
+    assert_eq!(processor.calls(), 1);
+}
+
+/// Test re-processing after a crash between executing and persisting reuses
+/// the earlier execution
+#[tokio::test]
+async fn test_crash_before_persist_does_not_execute_twice() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let processor = RecordingProcessor::default();
+    let id = insert_processing_row(&mut conn).await;
+
+    // The first worker executes, then dies before storing anything
+    let row = load_row(&mut conn, id).await;
+    let first = TransactionProcessor::execute(&processor, &idempotency_key(id), &row)
+        .await
+        .unwrap();
+    assert_eq!(load_row(&mut conn, id).await.result, None);
+
+    let outcome = processing::process(&mut conn, &processor, id).await.unwrap();
+    assert_eq!(outcome.result(), Some(&first));
+    assert_eq!(processor.calls(), 2);
+    assert_eq!(processor.executions(), 1);
+    assert_eq!(load_row(&mut conn, id).await.result, Some(first));
+}
+
+/// Test two workers finishing the same row store a single result
+#[tokio::test]
+async fn test_concurrent_workers_persist_one_result() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut setup = pool.get_owned().await.expect("Failed to get connection");
+    let processor = RecordingProcessor::default();
+    let id = insert_processing_row(&mut setup).await;
+
+    let mut first = pool.get_owned().await.expect("Failed to get connection");
+    let mut second = pool.get_owned().await.expect("Failed to get connection");
+    let (a, b) = tokio::join!(
+        processing::process(&mut first, &processor, id),
+        processing::process(&mut second, &processor, id),
+    );
+    let outcomes = [a.unwrap(), b.unwrap()];
+
+    let completed = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome, ProcessOutcome::Completed(_)))
+        .count();
+    assert_eq!(completed, 1);
+    assert_eq!(processor.executions(), 1);
+    let stored = load_row(&mut setup, id).await.result;
+    assert!(outcomes.iter().all(|outcome| outcome.result() == stored.as_ref()));
+}
+
+/// Test a row that is not processing is refused without invoking the processor
+#[tokio::test]
+async fn test_pending_row_is_refused() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let processor = RecordingProcessor::default();
+    let id = insert_processing_row(&mut conn).await;
+    diesel::update(transaction_queue::table.find(id))
+        .set(transaction_queue::status.eq(TransactionStatus::Pending.as_str()))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    assert!(processing::process(&mut conn, &processor, id).await.is_err());
+    assert!(processing::process(&mut conn, &processor, Uuid::new_v4()).await.is_err());
+    assert_eq!(processor.calls(), 0);
+}
+
+async fn get_status(app: &axum::Router, id: Uuid) -> Value {
+    let request = Request::get(format!("/transactions/{}", id)).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+}
+
+/// Test the status endpoint shows the result only once the transaction completes
+#[tokio::test]
+async fn test_status_exposes_result_when_completed() {
+    let state = TestEnvironment::app_state().await;
+    let app = v1::router(state.clone()).with_state(state.clone());
+    let mut conn = state.db_pool.get_owned().await.expect("Failed to get connection");
+    let id = insert_processing_row(&mut conn).await;
+
+    let body = get_status(&app, id).await;
+    assert_eq!(body["status"], "processing");
+    assert!(body.get("result").is_none());
+
+    processing::process(&mut conn, &processing::SimulatedProcessor, id)
+        .await
+        .unwrap();
+    let body = get_status(&app, id).await;
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["result"]["signature"], format!("sim_{}", id.simple()));
+}
+
+/// Test a failed attempt stores its error capped, leaving the row processing
+#[tokio::test]
+async fn test_failure_stores_truncated_error() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let id = insert_processing_row(&mut conn).await;
+
+    let err = processing::process(&mut conn, &FailingProcessor, id).await.unwrap_err();
+    assert!(matches!(err, ProcessError::Processor(ref message) if message.len() > MAX_ERROR_MESSAGE_BYTES));
+
+    let row = load_row(&mut conn, id).await;
+    assert_eq!(row.status, "processing");
+    let stored = row.error_message.expect("error message not stored");
+    assert!(stored.len() <= MAX_ERROR_MESSAGE_BYTES, "{} bytes", stored.len());
+    assert!(stored.starts_with("rpc error: é"));
+    assert!(stored.contains("…[truncated"));
+}
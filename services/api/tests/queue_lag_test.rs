@@ -0,0 +1,87 @@
+mod common;
+
+use common::*;
+use reqwest::StatusCode;
+use serde_json::Value;
+use transaction_queue_api::queue_stats::{consumer_lag_seconds, LagLevel, LagMonitor, MIN_DEQUEUE_RATE};
+
+/// Test lag is depth divided by the dequeue rate
+#[test]
+fn test_lag_from_depth_and_rate() {
+    assert_eq!(consumer_lag_seconds(100, Some(2.0)), 50.0);
+    assert_eq!(consumer_lag_seconds(1, Some(0.5)), 2.0);
+}
+
+/// Test an empty queue has no lag regardless of the rate
+#[test]
+fn test_empty_queue_has_no_lag() {
+    assert_eq!(consumer_lag_seconds(0, None), 0.0);
+    assert_eq!(consumer_lag_seconds(0, Some(0.0)), 0.0);
+}
+
+/// Test a stalled consumer produces a large but finite lag
+#[test]
+fn test_stalled_consumer_lag_is_finite() {
+    let lag = consumer_lag_seconds(10, None);
+    assert!(lag.is_finite());
+    assert_eq!(lag, 10.0 / MIN_DEQUEUE_RATE);
+    assert_eq!(consumer_lag_seconds(10, Some(0.0)), lag);
+}
+
+/// Test the monitor only warns after more than N consecutive samples over threshold
+#[test]
+fn test_monitor_requires_consecutive_samples() {
+    let mut monitor = LagMonitor::new(300.0, 3);
+
+    assert_eq!(monitor.observe(301.0), LagLevel::Elevated);
+    assert_eq!(monitor.observe(400.0), LagLevel::Elevated);
+    assert_eq!(monitor.observe(500.0), LagLevel::Elevated);
+    assert_eq!(monitor.observe(500.0), LagLevel::Warning);
+    assert_eq!(monitor.observe(500.0), LagLevel::Warning);
+}
+
+/// Test a single sample under threshold resets the streak
+#[test]
+fn test_monitor_resets_on_recovery() {
+    let mut monitor = LagMonitor::new(300.0, 2);
+
+    monitor.observe(1000.0);
+    monitor.observe(1000.0);
+    assert_eq!(monitor.observe(1000.0), LagLevel::Warning);
+
+    assert_eq!(monitor.observe(300.0), LagLevel::Normal);
+    assert_eq!(monitor.observe(1000.0), LagLevel::Elevated);
+    assert_eq!(monitor.observe(10.0), LagLevel::Normal);
+    assert_eq!(monitor.observe(1000.0), LagLevel::Elevated);
+}
+
+/// Test the stats endpoint reports depth, rates and drain estimate
+#[tokio::test]
+async fn test_queue_stats_endpoint() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    client
+        .submit_transaction_expect_success(&TestData::unique_account_id(), TestData::sample_transaction_data(), None)
+        .await;
+
+    let response = client.get("/v1/queue/stats").await.expect("Request failed");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body: Value = response.json().await.expect("Invalid JSON");
+    assert!(body["queue_depth"].as_i64().unwrap() >= 1);
+    assert!(body["enqueue_rate_per_second"].as_f64().unwrap() >= 0.0);
+    assert!(body["dequeue_rate_per_second"].as_f64().unwrap() >= 0.0);
+    assert!(body["estimated_drain_seconds"].as_f64().unwrap() > 0.0);
+    assert!(body["wait_times"].is_array());
+}
+
+/// Test the Prometheus endpoint is served
+#[tokio::test]
+async fn test_metrics_endpoint_available() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    let response = client.get("/metrics").await.expect("Request failed");
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use chrono::{TimeDelta, Utc};
+use common::*;
+use postgres_models::sources::{FixedClock, RandomIds};
+use redis_cache::QueueEnvelope;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+use transaction_queue_api::{v1, TRANSACTION_QUEUE};
+
+/// An API instance whose clock is `skew` away from the others
+async fn instance(skew: TimeDelta) -> axum::Router {
+    let state = TestEnvironment::app_state()
+        .await
+        .with_sources(Arc::new(RandomIds), Arc::new(FixedClock(Utc::now() + skew)));
+    v1::router(state.clone()).with_state(state)
+}
+
+async fn submit(app: &axum::Router, account_id: &str) -> String {
+    let request = Request::post("/transactions/submit")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            json!({
+                "account_id": account_id,
+                "transaction_data": TestData::sample_transaction_data(),
+            })
+            .to_string(),
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    body["transaction_id"].as_str().unwrap().to_string()
+}
+
+/// Test submits through instances with skewed clocks queue in the order they arrive
+#[tokio::test]
+async fn test_skewed_instances_keep_arrival_order() {
+    let ahead = instance(TimeDelta::milliseconds(50)).await;
+    let behind = instance(TimeDelta::milliseconds(-50)).await;
+
+    let mut submitted = Vec::new();
+    for i in 0..6 {
+        let app = if i % 2 == 0 { &ahead } else { &behind };
+        submitted.push(submit(app, &TestData::unique_account_id()).await);
+    }
+
+    let state = TestEnvironment::app_state().await;
+    let queued: Vec<String> = state
+        .queue_manager()
+        .get_priority_queue_order(&TRANSACTION_QUEUE)
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|member| QueueEnvelope::decode(member).ok())
+        .map(|envelope| envelope.transaction_id)
+        .filter(|id| submitted.contains(id))
+        .collect();
+    assert_eq!(queued, submitted);
+}
@@ -0,0 +1,60 @@
+use axum::http::HeaderMap;
+use redis_cache::RateLimitResult;
+use transaction_queue_api::rate_limit::{insert_header, rate_limit_headers};
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers[name].to_str().unwrap()
+}
+
+/// Test the largest values every header can carry are written as plain integers
+#[test]
+fn test_extreme_values() {
+    let result = RateLimitResult {
+        allowed: true,
+        remaining: u32::MAX,
+        reset_at: u64::MAX,
+    };
+    let headers = rate_limit_headers(u32::MAX, &result);
+
+    assert_eq!(header(&headers, "x-ratelimit-limit"), u32::MAX.to_string());
+    assert_eq!(header(&headers, "x-ratelimit-remaining"), u32::MAX.to_string());
+    assert_eq!(header(&headers, "x-ratelimit-reset"), u64::MAX.to_string());
+}
+
+/// Test a reset time is sent as epoch seconds, exactly as the limiter reported it
+#[test]
+fn test_reset_is_epoch_seconds() {
+    let result = RateLimitResult {
+        allowed: false,
+        remaining: 0,
+        reset_at: 1_700_000_060,
+    };
+    let headers = rate_limit_headers(5, &result);
+
+    assert_eq!(header(&headers, "x-ratelimit-reset"), "1700000060");
+    assert_eq!(header(&headers, "x-ratelimit-remaining"), "0");
+}
+
+/// Test remaining never exceeds the limit, even when a limiter reports more
+#[test]
+fn test_remaining_capped_at_limit() {
+    let result = RateLimitResult {
+        allowed: true,
+        remaining: 50,
+        reset_at: 1_700_000_060,
+    };
+    let headers = rate_limit_headers(10, &result);
+
+    assert_eq!(header(&headers, "x-ratelimit-remaining"), "10");
+}
+
+/// Test a value that is not a valid header is left out instead of panicking
+#[test]
+fn test_invalid_value_is_omitted() {
+    let mut headers = HeaderMap::new();
+    insert_header(&mut headers, "X-RateLimit-Policy", "5\r\nInjected: yes");
+    insert_header(&mut headers, "X-RateLimit-Limit", 5);
+
+    assert!(!headers.contains_key("x-ratelimit-policy"));
+    assert_eq!(header(&headers, "x-ratelimit-limit"), "5");
+}
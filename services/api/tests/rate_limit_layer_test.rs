@@ -0,0 +1,183 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::Request;
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Router};
+use common::*;
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+use transaction_queue_api::config::RedisFailureMode;
+use transaction_queue_api::rate_limit::layer::{by_path_param, AppliedRateLimit, RateLimitLayer};
+use transaction_queue_api::redis_failure::REDIS_UNAVAILABLE;
+
+const TEST_SCOPE: &str = "layer_test";
+const KEY_HEADER: &str = "x-test-key";
+
+/// Key by a header so each test picks its own bucket
+fn by_test_header(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .get(KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+fn request(path: &str, key: &str) -> Request {
+    Request::get(path).header(KEY_HEADER, key).body(Body::empty()).unwrap()
+}
+
+async fn read_body(response: Response) -> Value {
+    serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+}
+
+/// A router whose only handler counts how often it runs
+fn counting_router(layer: RateLimitLayer) -> (Router, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let router = Router::new()
+        .route(
+            "/ping",
+            get(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "pong"
+            }),
+        )
+        .layer(layer);
+    (router, calls)
+}
+
+/// Test requests past the limit are answered by the layer without reaching the handler
+#[tokio::test]
+async fn test_layer_limits_and_short_circuits() {
+    let layer = RateLimitLayer::new(TestEnvironment::redis_pool().await, TEST_SCOPE, 3, 60).key_by(by_test_header);
+    let (router, calls) = counting_router(layer);
+    let key = TestData::unique_account_id();
+
+    for expected_remaining in [2, 1, 0] {
+        let response = router.clone().oneshot(request("/ping", &key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-RateLimit-Limit"], "3");
+        assert_eq!(response.headers()["X-RateLimit-Remaining"], expected_remaining.to_string().as_str());
+        assert!(response.headers().contains_key("X-RateLimit-Reset"));
+    }
+
+    let response = router.clone().oneshot(request("/ping", &key)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "0");
+    let body = read_body(response).await;
+    assert_eq!(body["error"]["status"], 429);
+    assert_eq!(body["error"]["message"], "Rate limit exceeded");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // Other keys have their own window
+    let response = router.oneshot(request("/ping", &TestData::unique_account_id())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// Test handlers see the check result and keep the headers they set themselves
+#[tokio::test]
+async fn test_result_reaches_handler() {
+    let layer = RateLimitLayer::new(TestEnvironment::redis_pool().await, TEST_SCOPE, 5, 60).key_by(by_test_header);
+    let router = Router::new()
+        .route(
+            "/inspect",
+            get(|Extension(applied): Extension<AppliedRateLimit>| async move {
+                let mut response = axum::Json(serde_json::json!({
+                    "limit": applied.limit,
+                    "remaining": applied.result.remaining,
+                }))
+                .into_response();
+                response
+                    .headers_mut()
+                    .insert("X-RateLimit-Limit", HeaderValue::from_static("custom"));
+                response
+            }),
+        )
+        .layer(layer);
+
+    let response = router.oneshot(request("/inspect", &TestData::unique_account_id())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["X-RateLimit-Limit"], "custom");
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "4");
+    let body = read_body(response).await;
+    assert_eq!(body["limit"], 5);
+    assert_eq!(body["remaining"], 4);
+}
+
+/// Test requests without a key skip the limiter entirely, even with Redis down
+#[tokio::test]
+async fn test_requests_without_key_pass_through() {
+    let pool = redis_cache::create_pool("redis://127.0.0.1:1").await.unwrap();
+    let (router, calls) = counting_router(RateLimitLayer::new(pool, TEST_SCOPE, 1, 60).key_by(|_: &Request| None));
+
+    for _ in 0..3 {
+        let response = router.clone().oneshot(request("/ping", "ignored")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("X-RateLimit-Limit"));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+/// Test unreachable Redis is a 503 when failing closed, the default, and the handler never runs
+#[tokio::test]
+async fn test_unreachable_redis_fails_closed() {
+    let pool = redis_cache::create_pool("redis://127.0.0.1:1").await.unwrap();
+    let (router, calls) = counting_router(RateLimitLayer::new(pool, TEST_SCOPE, 1, 60).key_by(by_test_header));
+
+    let response = router.oneshot(request("/ping", "key")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(read_body(response).await["error"]["code"], REDIS_UNAVAILABLE);
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+/// Test unreachable Redis lets requests through unlimited and without headers when failing open
+#[tokio::test]
+async fn test_unreachable_redis_fails_open() {
+    let pool = redis_cache::create_pool("redis://127.0.0.1:1").await.unwrap();
+    let layer = RateLimitLayer::new(pool, TEST_SCOPE, 1, 60)
+        .key_by(by_test_header)
+        .with_failure_mode(RedisFailureMode::FailOpen);
+    let (router, calls) = counting_router(layer);
+
+    for _ in 0..3 {
+        let response = router.clone().oneshot(request("/ping", "key")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("x-ratelimit-limit"));
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+/// Test path parameter keys resolve through nested routers
+#[tokio::test]
+async fn test_path_param_key() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let recorder = seen.clone();
+    let key = by_path_param("account_id");
+    // Record the key and skip the check, so no Redis is needed
+    let layer = RateLimitLayer::new(
+        redis_cache::create_pool("redis://127.0.0.1:1").await.unwrap(),
+        TEST_SCOPE,
+        1,
+        60,
+    )
+    .key_by(move |request: &Request| {
+        recorder.lock().unwrap().push(key(request));
+        None
+    });
+    let router = Router::new().nest(
+        "/accounts",
+        Router::new().route("/:account_id/items/:item_id", get(|| async { "ok" }).layer(layer)),
+    );
+
+    let response = router
+        .oneshot(Request::get("/accounts/acct_42/items/7").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(*seen.lock().unwrap(), vec![Some("account_id:acct_42".to_string())]);
+}
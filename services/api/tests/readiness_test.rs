@@ -0,0 +1,155 @@
+mod common;
+
+use common::*;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio::sync::oneshot;
+use transaction_queue_api::config::Config;
+use transaction_queue_api::server::Listeners;
+use transaction_queue_api::AppState;
+
+const DRAIN_ADMIN_KEY: &str = "drain-test-key";
+
+/// Test config with `vars` on top
+fn config_with(vars: &[(&str, &str)]) -> Config {
+    let lookup = |var: &str| match vars.iter().find(|(name, _)| *name == var) {
+        Some((_, value)) => Some(value.to_string()),
+        None => match var {
+            "DATABASE_URL" => Some(std::env::var(var).unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string())),
+            "REDIS_URL" => Some(std::env::var(var).unwrap_or_else(|_| DEFAULT_REDIS_URL.to_string())),
+            "ADMIN_API_KEYS" => Some(format!("drain:{}", DRAIN_ADMIN_KEY)),
+            _ => None,
+        },
+    };
+    Config::from_lookup(&service_config::Env::new(&lookup)).expect("Invalid test config")
+}
+
+fn config() -> Config {
+    config_with(&[])
+}
+
+/// Serve the app on an ephemeral port and return its base URL
+async fn serve(state: AppState) -> (String, oneshot::Sender<()>) {
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let listeners = Listeners::bind(&[addr], None).await.unwrap();
+    let addr = listeners.tcp_addrs().unwrap()[0];
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    tokio::spawn(
+        listeners.serve(transaction_queue_api::app(state, None), async {
+            let _ = shutdown_rx.await;
+        }),
+    );
+    (format!("http://{}", addr), shutdown_tx)
+}
+
+/// Status and body of `GET path`
+async fn get(base_url: &str, path: &str) -> (u16, Value) {
+    let response = reqwest::get(format!("{}{}", base_url, path)).await.unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
+
+/// Test clearing the flag fails readiness only, and setting it restores it
+#[tokio::test]
+async fn test_drained_instance_stays_live() {
+    let state = AppState::new(config())
+        .await
+        .expect("Failed to build app state");
+    let (base_url, _shutdown) = serve(state.clone()).await;
+
+    let (status, body) = get(&base_url, "/health/ready").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["ready"], true);
+    assert!(body["uptime_seconds"].is_u64());
+    assert_eq!(body["dependencies"]["postgres"]["status"], "up");
+    assert_eq!(body["dependencies"]["redis"]["status"], "up");
+
+    state.readiness.set_ready(false);
+    let (status, body) = get(&base_url, "/health/ready").await;
+    assert_eq!(status, 503);
+    assert_eq!(body["status"], "draining");
+    assert_eq!(body["ready"], false);
+    assert_eq!(get(&base_url, "/health").await.0, 200);
+    assert_eq!(get(&base_url, "/health/live").await.0, 200);
+
+    assert!(state.readiness.toggle());
+    assert_eq!(get(&base_url, "/health/ready").await.0, 200);
+}
+
+/// Test draining through the admin API leaves submits working
+#[tokio::test]
+async fn test_admin_drain_keeps_serving_submits() {
+    let state = AppState::new(config())
+        .await
+        .expect("Failed to build app state");
+    let (base_url, _shutdown) = serve(state).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/v1/admin/readiness", base_url))
+        .header("X-Admin-Key", DRAIN_ADMIN_KEY)
+        .json(&json!({ "ready": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], false);
+
+    assert_eq!(get(&base_url, "/health/ready").await.0, 503);
+    assert_eq!(get(&base_url, "/health").await.0, 200);
+
+    let response = client
+        .post(format!("{}/v1/transactions/submit", base_url))
+        .json(&json!({ "account_id": TestData::unique_account_id(), "transaction_data": { "amount": 1 } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+/// Test an unreachable Redis fails readiness with a 503 naming it, while liveness still answers
+#[tokio::test]
+async fn test_unreachable_redis_fails_readiness() {
+    let state = AppState::new(config_with(&[("REDIS_URL", "redis://127.0.0.1:1")]))
+        .await
+        .expect("Failed to build app state");
+    let (base_url, _shutdown) = serve(state).await;
+
+    let (status, body) = get(&base_url, "/health/ready").await;
+    assert_eq!(status, 503);
+    assert_eq!(body["status"], "unavailable");
+    assert_eq!(body["ready"], false);
+    let dependencies = &body["dependencies"];
+    assert_eq!(dependencies["postgres"]["status"], "up", "{}", body);
+    assert!(dependencies["postgres"]["error"].is_null());
+    assert_eq!(dependencies["redis"]["status"], "down");
+    // The reason is generic; the connection error behind it is only logged
+    assert_eq!(dependencies["redis"]["error"], "redis unavailable");
+    assert!(dependencies["redis"]["latency_ms"].is_u64());
+
+    let live = json!({ "status": "ok", "service": "transaction-queue-api" });
+    assert_eq!(get(&base_url, "/health/live").await, (200, live));
+}
+
+/// Test a burst of probes shares one run of the checks, and the checks run again once the cache expires
+#[tokio::test]
+async fn test_probes_share_cached_checks() {
+    let vars = [("REDIS_URL", "redis://127.0.0.1:1"), ("READINESS_CACHE_TTL_MS", "500")];
+    let state = AppState::new(config_with(&vars))
+        .await
+        .expect("Failed to build app state");
+    let (base_url, _shutdown) = serve(state.clone()).await;
+
+    let probes = (0..100).map(|_| get(&base_url, "/health/ready"));
+    let responses = futures::future::join_all(probes).await;
+    let first = &responses[0].1["dependencies"];
+    assert!(responses.iter().all(|(status, body)| *status == 503 && body["dependencies"] == *first));
+    assert_eq!(state.dependencies.runs(), 1);
+
+    tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+    assert_eq!(get(&base_url, "/health/ready").await.0, 503);
+    assert_eq!(state.dependencies.runs(), 2);
+}
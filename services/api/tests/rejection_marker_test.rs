@@ -0,0 +1,141 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::*;
+use redis_cache::{RateLimitResult, RedisError};
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower::ServiceExt;
+use transaction_queue_api::rate_limit::rejections::{RejectionMarkers, MAX_MARKER_TTL};
+use transaction_queue_api::v1;
+
+fn unix_seconds() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn rejected(reset_at: u64) -> RateLimitResult {
+    RateLimitResult {
+        allowed: false,
+        remaining: 0,
+        reset_at,
+    }
+}
+
+/// Stand-in for Redis that counts the checks reaching it
+struct CountingBackend {
+    result: RateLimitResult,
+    calls: AtomicUsize,
+}
+
+impl CountingBackend {
+    fn new(result: RateLimitResult) -> Self {
+        Self {
+            result,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    async fn check(&self) -> Result<RateLimitResult, RedisError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(self.result.clone())
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+/// Test only the first rejection per marker interval reaches the backend
+#[tokio::test]
+async fn test_storm_reaches_backend_once_per_interval() {
+    let markers = RejectionMarkers::new(100);
+    let backend = CountingBackend::new(rejected(unix_seconds() + 60));
+
+    for _ in 0..50 {
+        let result = markers.check("acct_storm", || backend.check()).await.unwrap();
+        assert!(!result.allowed);
+        assert_eq!(result.reset_at, backend.result.reset_at);
+    }
+    assert_eq!(backend.calls(), 1);
+
+    tokio::time::sleep(MAX_MARKER_TTL + std::time::Duration::from_millis(100)).await;
+    markers.check("acct_storm", || backend.check()).await.unwrap();
+    assert_eq!(backend.calls(), 2);
+}
+
+/// Test a rejection whose window has already reset is not marked
+#[tokio::test]
+async fn test_marker_never_outlives_window() {
+    let markers = RejectionMarkers::new(100);
+    let backend = CountingBackend::new(rejected(unix_seconds()));
+
+    for _ in 0..5 {
+        markers.check("acct_resetting", || backend.check()).await.unwrap();
+    }
+    assert_eq!(backend.calls(), 5);
+}
+
+/// Test allowed checks always reach the backend and cleared markers are forgotten
+#[tokio::test]
+async fn test_only_rejections_are_marked() {
+    let markers = RejectionMarkers::new(100);
+    let allowed = CountingBackend::new(RateLimitResult {
+        allowed: true,
+        remaining: 5,
+        reset_at: unix_seconds() + 60,
+    });
+    for _ in 0..3 {
+        markers.check("acct_allowed", || allowed.check()).await.unwrap();
+    }
+    assert_eq!(allowed.calls(), 3);
+
+    markers.record("acct_cleared", &rejected(unix_seconds() + 60));
+    assert!(markers.get("acct_cleared").is_some());
+    markers.clear("acct_cleared");
+    assert!(markers.get("acct_cleared").is_none());
+}
+
+/// Test a marked account is answered 429 with headers from the marker
+#[tokio::test]
+async fn test_marked_submit_is_rejected_locally() {
+    let state = TestEnvironment::app_state().await;
+    let app = v1::router(state.clone()).with_state(state.clone());
+    let account_id = TestData::unique_account_id();
+    let reset_at = unix_seconds() + 60;
+    state.rejections.record(&account_id, &rejected(reset_at));
+
+    let body = json!({
+        "account_id": account_id,
+        "transaction_data": TestData::sample_transaction_data(),
+    });
+    let request = Request::post("/transactions/submit")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "0");
+    assert_eq!(response.headers()["X-RateLimit-Reset"], reset_at.to_string().as_str());
+}
+
+/// Test the estimate endpoint reads the account's usage past its marker
+#[tokio::test]
+async fn test_estimate_bypasses_marker() {
+    let state = TestEnvironment::app_state().await;
+    let app = v1::router(state.clone()).with_state(state.clone());
+    let account_id = TestData::unique_account_id();
+    state.rejections.record(&account_id, &rejected(unix_seconds() + 60));
+
+    let request = Request::get(format!("/accounts/{}/estimate", account_id))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value =
+        serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(body["remaining"], 100);
+}
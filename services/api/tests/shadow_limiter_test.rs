@@ -0,0 +1,129 @@
+mod common;
+
+use common::*;
+use futures::future::BoxFuture;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use redis_cache::{RateLimitResult, RateLimitStrategy, RedisError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use transaction_queue_api::feature_flags::SHADOW_RATE_LIMITER;
+use transaction_queue_api::rate_limit::check_account_limit;
+use transaction_queue_api::rate_limit::shadow::{self, compare, ShadowCheck, ShadowLimiter, ShadowOutcome};
+
+fn result(allowed: bool, remaining: u32) -> RateLimitResult {
+    RateLimitResult {
+        allowed,
+        remaining,
+        reset_at: 0,
+    }
+}
+
+/// Candidate that always rejects, after an optional delay
+struct RejectingLimiter {
+    delay: Duration,
+    calls: AtomicUsize,
+}
+
+impl RejectingLimiter {
+    fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            calls: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ShadowLimiter for RejectingLimiter {
+    fn check<'a>(
+        &'a self,
+        _account_id: &'a str,
+        _cost: u32,
+        _max_requests: u32,
+        _window_seconds: u64,
+    ) -> BoxFuture<'a, Result<RateLimitResult, RedisError>> {
+        Box::pin(async move {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            Ok(result(false, 0))
+        })
+    }
+}
+
+fn check(live: RateLimitResult) -> ShadowCheck {
+    ShadowCheck {
+        account_id: "acct_shadow".to_string(),
+        cost: 1,
+        max_requests: 10,
+        window_seconds: 60,
+        live,
+    }
+}
+
+/// Test decisions are compared first and remaining counts within the tolerance
+#[test]
+fn test_compare() {
+    assert_eq!(compare(&result(true, 5), &result(true, 5), 0), ShadowOutcome::Match);
+    assert_eq!(compare(&result(true, 5), &result(true, 4), 1), ShadowOutcome::Match);
+    assert_eq!(compare(&result(true, 5), &result(true, 3), 1), ShadowOutcome::RemainingMismatch);
+    assert_eq!(compare(&result(true, 0), &result(false, 0), 1), ShadowOutcome::AllowedMismatch);
+}
+
+/// Test a divergent candidate is counted as a mismatch
+#[tokio::test]
+async fn test_mismatch_is_recorded() {
+    let limiter = RejectingLimiter::new(Duration::ZERO);
+    let recorder = PrometheusBuilder::new().build_recorder();
+    let handle = recorder.handle();
+
+    let outcome = {
+        let _guard = metrics::set_default_local_recorder(&recorder);
+        shadow::run(&limiter, &check(result(true, 9)), Duration::from_secs(1), 1).await
+    };
+
+    assert_eq!(outcome, ShadowOutcome::AllowedMismatch);
+    let rendered = handle.render();
+    assert!(
+        rendered.contains(r#"rate_limit_shadow_mismatch_total{kind="allowed"} 1"#),
+        "{}",
+        rendered
+    );
+}
+
+/// Test a slow candidate times out instead of holding the check
+#[tokio::test]
+async fn test_slow_candidate_times_out() {
+    let limiter = RejectingLimiter::new(Duration::from_secs(5));
+    let recorder = PrometheusBuilder::new().build_recorder();
+    let handle = recorder.handle();
+
+    let outcome = {
+        let _guard = metrics::set_default_local_recorder(&recorder);
+        shadow::run(&limiter, &check(result(true, 9)), Duration::from_millis(20), 1).await
+    };
+
+    assert_eq!(outcome, ShadowOutcome::Timeout);
+    let rendered = handle.render();
+    assert!(
+        rendered.contains(r#"rate_limit_shadow_failures_total{kind="timeout"} 1"#),
+        "{}",
+        rendered
+    );
+}
+
+/// Test the live result is returned while the divergent candidate runs
+#[tokio::test]
+async fn test_live_result_wins() {
+    let limiter = Arc::new(RejectingLimiter::new(Duration::ZERO));
+    let state = TestEnvironment::app_state().await.with_shadow_limiter(limiter.clone());
+    let account_id = TestData::unique_account_id();
+    state.flags.set(SHADOW_RATE_LIMITER, 100).await.unwrap();
+
+    let live = check_account_limit(&state, &account_id, 1, 10, 60, RateLimitStrategy::SlidingWindow).await.unwrap();
+    assert!(live.allowed);
+    assert_eq!(live.remaining, 9);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(limiter.calls.load(Ordering::SeqCst), 1);
+    state.flags.clear(SHADOW_RATE_LIMITER).await.unwrap();
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::TransactionStatus;
+use postgres_models::processing::{self, SimulatedProcessor};
+use postgres_models::schema::transaction_queue;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use transaction_queue_api::server::Listeners;
+use transaction_queue_api::smoke::{self, SmokeClient, SmokeConfig, Step, StepBudgets, StepOutcome};
+use transaction_queue_api::AppState;
+use uuid::Uuid;
+
+const SMOKE_ADMIN_KEY: &str = "smoke-admin-key";
+
+/// App state whose admin API accepts `SMOKE_ADMIN_KEY`
+async fn smoke_state() -> AppState {
+    let lookup = |var: &str| match var {
+        "DATABASE_URL" => Some(std::env::var(var).unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string())),
+        "REDIS_URL" => Some(std::env::var(var).unwrap_or_else(|_| DEFAULT_REDIS_URL.to_string())),
+        "ADMIN_API_KEYS" => Some(format!("smoke:{}", SMOKE_ADMIN_KEY)),
+        _ => None,
+    };
+    let config = transaction_queue_api::config::Config::from_lookup(&service_config::Env::new(&lookup))
+        .expect("Invalid test config");
+    AppState::new(config).await.expect("Failed to build app state")
+}
+
+/// Serve the API in-process on an ephemeral port and return its base URL
+async fn serve(state: AppState) -> (String, oneshot::Sender<()>) {
+    let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let listeners = Listeners::bind(&[addr], None).await.unwrap();
+    let addr = listeners.tcp_addrs().unwrap()[0];
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    tokio::spawn(listeners.serve(transaction_queue_api::app(state, None), async {
+        let _ = shutdown_rx.await;
+    }));
+    (format!("http://{}", addr), shutdown_tx)
+}
+
+/// Stand-in worker: claims and completes the account's pending rows
+async fn complete_pending(state: AppState, account_id: String) {
+    let mut conn = state.db_pool.get_owned().await.expect("Failed to get connection");
+    loop {
+        let ids: Vec<Uuid> = diesel::update(
+            transaction_queue::table
+                .filter(transaction_queue::account_id.eq(&account_id))
+                .filter(transaction_queue::status.eq(TransactionStatus::Pending.as_str())),
+        )
+        .set(transaction_queue::status.eq(TransactionStatus::Processing.as_str()))
+        .returning(transaction_queue::id)
+        .get_results(&mut conn)
+        .await
+        .expect("Failed to claim rows");
+        for id in ids {
+            processing::process(&mut conn, &SimulatedProcessor, id)
+                .await
+                .expect("Failed to process row");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+fn fast_config(account_id: &str) -> SmokeConfig {
+    SmokeConfig {
+        account_id: account_id.to_string(),
+        budgets: StepBudgets {
+            status: Duration::from_secs(5),
+            ..StepBudgets::default()
+        },
+        poll_interval: Duration::from_millis(50),
+    }
+}
+
+/// Test the health and dry-run steps pass against the in-process app
+#[tokio::test]
+async fn test_health_and_dry_run_pass() {
+    let (base_url, _shutdown) = serve(TestEnvironment::app_state().await).await;
+    let client = SmokeClient::new(&base_url, None);
+
+    smoke::check_health(&client).await.unwrap();
+    smoke::dry_run_submit(&client, &TestData::unique_account_id())
+        .await
+        .unwrap();
+}
+
+/// Test a full run passes once a worker completes the smoke transaction
+#[tokio::test]
+async fn test_full_run_passes() {
+    let state = smoke_state().await;
+    let (base_url, _shutdown) = serve(state.clone()).await;
+    let account_id = TestData::unique_account_id();
+    let worker = tokio::spawn(complete_pending(state, account_id.clone()));
+
+    let client = SmokeClient::new(&base_url, Some(SMOKE_ADMIN_KEY.to_string()));
+    let report = smoke::run(&client, &fast_config(&account_id)).await;
+    worker.abort();
+
+    assert!(report.passed, "smoke run failed: {:?}", report);
+    assert!(report.steps.iter().all(|step| step.outcome == StepOutcome::Passed));
+}
+
+/// Test a transaction that never finishes fails the status step and is
+/// cancelled in cleanup
+#[tokio::test]
+async fn test_unfinished_transaction_fails_status() {
+    let (base_url, _shutdown) = serve(TestEnvironment::app_state().await).await;
+    let client = SmokeClient::new(&base_url, None);
+    let mut config = fast_config(&TestData::unique_account_id());
+    config.budgets.status = Duration::from_millis(200);
+
+    let report = smoke::run(&client, &config).await;
+
+    assert!(!report.passed);
+    assert_eq!(report.step(Step::Submit).unwrap().outcome, StepOutcome::Passed);
+    assert_eq!(report.step(Step::Status).unwrap().outcome, StepOutcome::Failed);
+    assert_eq!(report.step(Step::RateLimit).unwrap().outcome, StepOutcome::Skipped);
+    assert_eq!(report.step(Step::Cleanup).unwrap().outcome, StepOutcome::Passed);
+}
+
+/// Test a step that succeeds over its latency budget fails the run
+#[tokio::test]
+async fn test_step_over_budget_fails() {
+    let (base_url, _shutdown) = serve(TestEnvironment::app_state().await).await;
+    let client = SmokeClient::new(&base_url, None);
+    let mut config = fast_config(&TestData::unique_account_id());
+    config.budgets.health = Duration::ZERO;
+
+    let report = smoke::run(&client, &config).await;
+
+    assert!(!report.passed);
+    let health = report.step(Step::Health).unwrap();
+    assert_eq!(health.outcome, StepOutcome::Failed);
+    assert!(health.detail.as_deref().unwrap().contains("budget"));
+}
+
+/// Test an unreachable API fails every request step and skips the rest
+#[tokio::test]
+async fn test_unreachable_api_report() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let client = SmokeClient::new(&base_url, None);
+
+    let report = smoke::run(&client, &SmokeConfig::default()).await;
+
+    assert!(!report.passed);
+    let failed: Vec<Step> = report.failures().map(|step| step.step).collect();
+    assert_eq!(failed, vec![Step::Health, Step::DryRun, Step::Submit]);
+    assert_eq!(report.step(Step::Status).unwrap().outcome, StepOutcome::Skipped);
+    assert_eq!(report.step(Step::Cleanup).unwrap().outcome, StepOutcome::Skipped);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["steps"][0]["step"], "health");
+    assert_eq!(json["steps"][0]["outcome"], "failed");
+}
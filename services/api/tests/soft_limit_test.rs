@@ -0,0 +1,146 @@
+mod common;
+
+use common::*;
+use redis_cache::RateLimitResult;
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time::sleep;
+use transaction_queue_api::rate_limit::{soft_limit_warning, RATE_LIMIT_WARNING_HEADER};
+
+/// Hard limit applied to submissions by the dev server
+const SUBMIT_LIMIT: u32 = 100;
+
+fn result_after(used: u32, limit: u32) -> RateLimitResult {
+    RateLimitResult {
+        allowed: used <= limit,
+        remaining: limit.saturating_sub(used),
+        reset_at: 1_700_000_060,
+    }
+}
+
+/// Test the warning appears exactly at the threshold and stays until the hard limit
+#[test]
+fn test_warning_from_threshold_to_limit() {
+    for used in 1..=SUBMIT_LIMIT {
+        let warning = soft_limit_warning(SUBMIT_LIMIT, &result_after(used, SUBMIT_LIMIT), 80);
+        assert_eq!(warning.is_some(), used >= 80, "used={}", used);
+    }
+
+    let warning = soft_limit_warning(SUBMIT_LIMIT, &result_after(85, SUBMIT_LIMIT), 80).unwrap();
+    assert_eq!(warning.used, 85);
+    assert_eq!(warning.limit, SUBMIT_LIMIT);
+    assert_eq!(warning.threshold_pct, 80);
+    assert_eq!(warning.reset_at, 1_700_000_060);
+}
+
+/// Test rejected requests and a fresh window carry no warning
+#[test]
+fn test_no_warning_when_rejected_or_after_reset() {
+    assert!(soft_limit_warning(SUBMIT_LIMIT, &result_after(101, SUBMIT_LIMIT), 80).is_none());
+    assert!(soft_limit_warning(SUBMIT_LIMIT, &result_after(1, SUBMIT_LIMIT), 80).is_none());
+    assert!(soft_limit_warning(0, &result_after(0, 0), 80).is_none());
+}
+
+/// Test the threshold rounds up so the warning never fires below the percentage
+#[test]
+fn test_threshold_rounds_up() {
+    assert!(soft_limit_warning(101, &result_after(80, 101), 80).is_none());
+    assert!(soft_limit_warning(101, &result_after(81, 101), 80).is_some());
+    assert!(soft_limit_warning(10, &result_after(5, 10), 50).is_some());
+    assert!(soft_limit_warning(10, &result_after(9, 10), 100).is_none());
+    assert!(soft_limit_warning(10, &result_after(10, 10), 100).is_some());
+}
+
+/// Submit until the hard limit, checking where warnings start
+async fn walk_to_limit(client: &TestClient, account_id: &str, expected_threshold: u32) {
+    for used in 1..=SUBMIT_LIMIT {
+        let response = client
+            .submit_transaction(account_id, TestData::sample_transaction_data(), None)
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK, "request {} should be allowed", used);
+
+        let header = response.headers().get(RATE_LIMIT_WARNING_HEADER).cloned();
+        let body: Value = response.json().await.unwrap();
+        let expect_warning = used >= expected_threshold;
+        assert_eq!(header.is_some(), expect_warning, "warning header at request {}", used);
+        assert_eq!(body.get("warnings").is_some(), expect_warning, "warnings body at request {}", used);
+        if expect_warning {
+            assert_eq!(body["warnings"][0]["code"], "soft_limit_reached");
+            assert_eq!(body["warnings"][0]["used"], used);
+        }
+    }
+
+    let response = client
+        .submit_transaction(account_id, TestData::sample_transaction_data(), None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get(RATE_LIMIT_WARNING_HEADER).is_none());
+}
+
+/// Test usage walked from 0 to the limit warns from the default 80% threshold
+#[tokio::test]
+async fn test_soft_limit_warning_before_429() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+
+    walk_to_limit(&client, &TestData::unique_account_id(), 80).await;
+}
+
+/// Test a per-account soft_pct row overrides the default threshold
+#[tokio::test]
+async fn test_per_account_soft_limit() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+
+    let response = client
+        .admin_request(
+            Method::PUT,
+            &format!("/accounts/{}/limits/soft_pct", account_id),
+            ADMIN_API_KEY,
+            Some(json!({ "max_requests": 50, "window_seconds": 60 })),
+        )
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    walk_to_limit(&client, &account_id, 50).await;
+
+    let response = client
+        .admin_request(
+            Method::PUT,
+            &format!("/accounts/{}/limits/soft_pct", account_id),
+            ADMIN_API_KEY,
+            Some(json!({ "max_requests": 150, "window_seconds": 60 })),
+        )
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Test the warning is gone once the window resets
+/// Note: This test is marked as ignored because it waits out the window
+#[tokio::test]
+#[ignore]
+async fn test_soft_limit_warning_clears_after_reset() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+
+    walk_to_limit(&client, &account_id, 80).await;
+
+    println!("Waiting for rate limit to reset...");
+    sleep(Duration::from_secs(65)).await;
+
+    let response = client
+        .submit_transaction(&account_id, TestData::sample_transaction_data(), None)
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(RATE_LIMIT_WARNING_HEADER).is_none());
+    let body: Value = response.json().await.unwrap();
+    assert!(body.get("warnings").is_none());
+}
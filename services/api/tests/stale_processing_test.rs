@@ -0,0 +1,185 @@
+mod common;
+
+use chrono::{TimeDelta, Utc};
+use common::*;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use postgres_models::schema::transaction_queue;
+use postgres_models::{DbConnection, DbError};
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use transaction_queue_api::config::StaleProcessingConfig;
+use transaction_queue_api::stale_processing::{self, StaleReport, StaleTransaction};
+use uuid::Uuid;
+
+/// Insert a row that entered "processing" `age` ago and has not been touched since
+async fn insert_processing_row(conn: &mut DbConnection, account_id: &str, age: TimeDelta) -> Uuid {
+    let id = Uuid::new_v4();
+    // The updated_at trigger only fires on UPDATE, so the backdated value sticks
+    diesel::insert_into(transaction_queue::table)
+        .values((
+            transaction_queue::id.eq(id),
+            transaction_queue::account_id.eq(account_id),
+            transaction_queue::transaction_data.eq(TestData::sample_transaction_data()),
+            transaction_queue::status.eq(TransactionStatus::Processing.as_str()),
+            transaction_queue::updated_at.eq(Utc::now() - age),
+        ))
+        .execute(conn)
+        .await
+        .expect("Failed to insert processing row");
+    id
+}
+
+async fn load_row(conn: &mut DbConnection, id: Uuid) -> TransactionQueue {
+    transaction_queue::table
+        .find(id)
+        .select(TransactionQueue::as_select())
+        .first(conn)
+        .await
+        .expect("Failed to load row")
+}
+
+fn config(heal_after_seconds: Option<u64>) -> StaleProcessingConfig {
+    StaleProcessingConfig {
+        threshold_seconds: 600,
+        check_interval_seconds: 60,
+        heal_after_seconds,
+    }
+}
+
+/// Test a row past the threshold is detected with its age, and a fresh one is not
+#[tokio::test]
+async fn test_stale_row_is_detected() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+
+    // Kept below the auto-heal limit used elsewhere in this file
+    let stale_id = insert_processing_row(&mut conn, &account_id, TimeDelta::minutes(20)).await;
+    let fresh_id = insert_processing_row(&mut conn, &account_id, TimeDelta::seconds(5)).await;
+
+    let report = stale_processing::check(&mut conn, &config(None)).await.unwrap();
+
+    let stale = report
+        .stale
+        .iter()
+        .find(|t| t.id == stale_id)
+        .expect("Stale row should be reported");
+    assert_eq!(stale.account_id, account_id);
+    assert!((1199..=1260).contains(&stale.age_seconds), "age was {}", stale.age_seconds);
+    assert!(report.stale.iter().all(|t| t.id != fresh_id));
+    assert!(report.healed.is_empty());
+
+    // Without auto-heal the row is left alone
+    assert_eq!(load_row(&mut conn, stale_id).await.status, "processing");
+}
+
+/// Test rows past the hard limit move back to retry, and others only get reported
+#[tokio::test]
+async fn test_auto_heal_moves_rows_to_retry() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let account_id = TestData::unique_account_id();
+
+    let stuck_id = insert_processing_row(&mut conn, &account_id, TimeDelta::hours(3)).await;
+    let stale_id = insert_processing_row(&mut conn, &account_id, TimeDelta::minutes(15)).await;
+
+    let report = stale_processing::check(&mut conn, &config(Some(7200))).await.unwrap();
+
+    assert!(report.healed.contains(&stuck_id));
+    assert!(!report.healed.contains(&stale_id));
+    assert!(report.stale.iter().any(|t| t.id == stale_id));
+
+    let stuck = load_row(&mut conn, stuck_id).await;
+    assert_eq!(stuck.status, "retry");
+    assert_eq!(stuck.retry_count, 1);
+    assert_eq!(load_row(&mut conn, stale_id).await.status, "processing");
+
+    // A healed row is no longer stale
+    let report = stale_processing::check(&mut conn, &config(Some(7200))).await.unwrap();
+    assert!(report.stale.iter().all(|t| t.id != stuck_id));
+}
+
+/// Test the transition helper only moves rows still in the expected status
+#[tokio::test]
+async fn test_transition_status_is_conditional() {
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let id = insert_processing_row(&mut conn, &TestData::unique_account_id(), TimeDelta::zero()).await;
+
+    let moved = TransactionQueue::transition_status(&mut conn, id, TransactionStatus::Processing, TransactionStatus::Completed)
+        .await
+        .unwrap();
+    assert!(moved);
+
+    // Someone else already finished it
+    let moved = TransactionQueue::transition_status(&mut conn, id, TransactionStatus::Processing, TransactionStatus::Retry)
+        .await
+        .unwrap();
+    assert!(!moved);
+    assert_eq!(load_row(&mut conn, id).await.status, "completed");
+
+    let err = TransactionQueue::transition_status(&mut conn, id, TransactionStatus::Completed, TransactionStatus::Pending)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, DbError::InvalidTransition { .. }));
+}
+
+/// Test the gauge reports how many stale rows the last check found
+#[test]
+fn test_gauge_reports_stale_count() {
+    let stale = |age_seconds| StaleTransaction {
+        id: Uuid::new_v4(),
+        account_id: "acct".to_string(),
+        retry_count: 0,
+        processing_since: Utc::now() - TimeDelta::seconds(age_seconds),
+        age_seconds,
+    };
+    let report = StaleReport {
+        stale: vec![stale(7200), stale(900), stale(700)],
+        healed: vec![Uuid::new_v4()],
+    };
+
+    let recorder = PrometheusBuilder::new().build_recorder();
+    let handle = recorder.handle();
+    metrics::with_local_recorder(&recorder, || stale_processing::record(&report));
+
+    let rendered = handle.render();
+    assert!(rendered.contains("stale_processing_transactions 3"), "{}", rendered);
+    assert!(rendered.contains("stale_processing_healed_total 1"), "{}", rendered);
+}
+
+/// Test the admin endpoint lists stale rows with their ages
+#[tokio::test]
+async fn test_admin_lists_stale_processing() {
+    TestEnvironment::validate_test_environment().await;
+    let pool = TestEnvironment::db_pool().await;
+    let mut conn = pool.get_owned().await.expect("Failed to get connection");
+    let stale_id = insert_processing_row(&mut conn, &TestData::unique_account_id(), TimeDelta::minutes(30)).await;
+
+    let client = TestClient::new();
+    let response = client
+        .admin_request(Method::GET, "/stale-processing?older_than_seconds=1500", ADMIN_API_KEY, None)
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["threshold_seconds"], 1500);
+    assert_eq!(body["count"].as_u64().unwrap() as usize, body["transactions"].as_array().unwrap().len());
+    let listed = body["transactions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["id"] == stale_id.to_string())
+        .expect("Stale row should be listed");
+    assert!(listed["age_seconds"].as_i64().unwrap() >= 1800);
+
+    let response = client
+        .admin_request(Method::GET, "/stale-processing", "wrong-key", None)
+        .await
+        .expect("Request failed");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use common::*;
+use futures::future::BoxFuture;
+use redis_cache::{ProcessingHold, QueueName, RedisError};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use tower::ServiceExt;
+use transaction_queue_api::estimation::estimate_at_position;
+use transaction_queue_api::submit_deadline::{QueueEntry, SubmitQueue};
+use transaction_queue_api::v1;
+
+const ITEMS_PER_SECOND: f64 = 0.5;
+
+/// Queue fake whose position and worker count the test moves
+struct DrainingQueue {
+    position: AtomicI64,
+    live_workers: AtomicI64,
+}
+
+impl DrainingQueue {
+    fn new(position: i64, live_workers: i64) -> Arc<Self> {
+        Arc::new(Self {
+            position: AtomicI64::new(position),
+            live_workers: AtomicI64::new(live_workers),
+        })
+    }
+}
+
+impl SubmitQueue for DrainingQueue {
+    fn add<'a>(&'a self, _queue_name: &'a QueueName, _entry: QueueEntry<'a>) -> BoxFuture<'a, Result<(), RedisError>> {
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn position<'a>(&'a self, _queue_name: &'a QueueName, _member: &'a str) -> BoxFuture<'a, Result<i64, RedisError>> {
+        Box::pin(async move { Ok(self.position.load(Ordering::SeqCst)) })
+    }
+
+    fn account_position<'a>(
+        &'a self,
+        _queue_name: &'a QueueName,
+        _member: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>> {
+        Box::pin(async move { Ok(None) })
+    }
+
+    fn processing_rate<'a>(&'a self, _queue_name: &'a QueueName) -> BoxFuture<'a, Result<Option<f64>, RedisError>> {
+        Box::pin(async move { Ok(Some(ITEMS_PER_SECOND)) })
+    }
+
+    fn live_worker_count(&self, _max_age_seconds: u64) -> BoxFuture<'_, Result<i64, RedisError>> {
+        Box::pin(async move { Ok(self.live_workers.load(Ordering::SeqCst)) })
+    }
+
+    fn transaction_position<'a>(
+        &'a self,
+        _queue_name: &'a QueueName,
+        _account_id: &'a str,
+        _transaction_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>> {
+        Box::pin(async move { Ok(Some(self.position.load(Ordering::SeqCst)).filter(|&position| position > 0)) })
+    }
+    fn reserve_pending<'a>(&'a self, _account_id: &'a str, _cap: u32) -> BoxFuture<'a, Result<bool, RedisError>> {
+        Box::pin(async move { Ok(true) })
+    }
+
+    fn release_pending<'a>(&'a self, _account_id: &'a str) -> BoxFuture<'a, Result<i64, RedisError>> {
+        Box::pin(async move { Ok(0) })
+    }
+
+    fn hold<'a>(&'a self, _transaction_id: &'a str) -> BoxFuture<'a, Result<Option<ProcessingHold>, RedisError>> {
+        Box::pin(async move { Ok(None) })
+    }
+}
+
+async fn app(queue: Arc<DrainingQueue>) -> Router {
+    let state = TestEnvironment::app_state().await.with_submit_queue(queue);
+    v1::router(state.clone()).with_state(state)
+}
+
+async fn submit(app: &Router) -> Value {
+    let request = Request::post("/transactions/submit")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            json!({
+                "account_id": TestData::unique_account_id(),
+                "transaction_data": TestData::sample_transaction_data(),
+            })
+            .to_string(),
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+}
+
+async fn status(app: &Router, transaction_id: &str) -> Value {
+    let request = Request::get(format!("/transactions/{}", transaction_id)).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+}
+
+fn expected(position: i64) -> i64 {
+    estimate_at_position(position, Some(ITEMS_PER_SECOND), true, 1.0, 3600).unwrap()
+}
+
+/// Test status recomputes the submit-time estimate as the queue drains
+#[tokio::test]
+async fn test_status_estimate_follows_the_queue() {
+    let queue = DrainingQueue::new(20, 1);
+    let app = app(queue.clone()).await;
+
+    let submitted = submit(&app).await;
+    assert_eq!(submitted["queue_position"], 20);
+    assert_eq!(submitted["estimated_processing_time_seconds"], expected(20));
+    let transaction_id = submitted["transaction_id"].as_str().unwrap();
+
+    // Unchanged queue, same answer as submit
+    let body = status(&app, transaction_id).await;
+    assert_eq!(body["queue_position"], 20);
+    assert_eq!(body["estimated_processing_time_seconds"], submitted["estimated_processing_time_seconds"]);
+    assert!(body["elapsed_wait_seconds"].as_i64().unwrap() >= 0);
+    assert!(body.get("estimate_unavailable_reason").is_none());
+
+    let mut previous = expected(20);
+    for position in [12, 5, 1] {
+        queue.position.store(position, Ordering::SeqCst);
+        let body = status(&app, transaction_id).await;
+        let estimate = body["estimated_processing_time_seconds"].as_i64().unwrap();
+        assert_eq!(body["queue_position"], position);
+        assert_eq!(estimate, expected(position));
+        assert!(estimate < previous, "{} at position {} after {}", estimate, position, previous);
+        previous = estimate;
+    }
+
+    // Popped by a worker but not yet marked processing
+    queue.position.store(0, Ordering::SeqCst);
+    let body = status(&app, transaction_id).await;
+    assert!(body["queue_position"].is_null());
+    assert!(body["estimated_processing_time_seconds"].is_null());
+    assert_eq!(body["estimate_unavailable_reason"], "not_queued");
+}
+
+/// Test status reports no estimate, with the reason, while no workers are alive
+#[tokio::test]
+async fn test_status_estimate_without_workers() {
+    let queue = DrainingQueue::new(3, 0);
+    let app = app(queue.clone()).await;
+
+    // Submit still answers with a number
+    let submitted = submit(&app).await;
+    assert!(submitted["estimated_processing_time_seconds"].as_i64().unwrap() > 0);
+
+    let body = status(&app, submitted["transaction_id"].as_str().unwrap()).await;
+    assert_eq!(body["status"], "pending");
+    assert_eq!(body["queue_position"], 3);
+    assert!(body["estimated_processing_time_seconds"].is_null());
+    assert_eq!(body["estimate_unavailable_reason"], "no_live_workers");
+
+    queue.live_workers.store(2, Ordering::SeqCst);
+    let body = status(&app, submitted["transaction_id"].as_str().unwrap()).await;
+    assert_eq!(body["estimated_processing_time_seconds"], expected(3));
+    assert!(body.get("estimate_unavailable_reason").is_none());
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::isolated::{IsolatedApp, TestResponse};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use postgres_models::models::{TransactionQueue, TransactionStatus};
+use postgres_models::schema::transaction_queue;
+use serde_json::{json, Value};
+use transaction_queue_api::TRANSACTION_QUEUE;
+use uuid::Uuid;
+
+const ADMIN_KEY: &str = "transition-test-key";
+
+/// Isolated app with an admin key for the status endpoint
+async fn admin_app() -> IsolatedApp {
+    IsolatedApp::with_vars(&[("ADMIN_API_KEYS", &format!("worker:{}", ADMIN_KEY))]).await
+}
+
+async fn patch_status(app: &IsolatedApp, id: &str, body: Value) -> TestResponse {
+    let request = Request::patch(format!("/v1/admin/transactions/{}/status", id))
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("X-Admin-Key", ADMIN_KEY)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    app.request(request).await
+}
+
+async fn load_row(app: &IsolatedApp, id: &str) -> TransactionQueue {
+    let mut conn = app.state.db_pool.get().await.expect("Failed to get connection");
+    TransactionQueue::find(&mut conn, id.parse().unwrap()).await.unwrap().unwrap()
+}
+
+/// Test a transaction moves through processing and failed to retry, going back in the queue at its priority
+#[tokio::test]
+async fn test_fail_then_retry_requeues() {
+    let app = admin_app().await;
+    let (id, _, _) = app.submit_transaction_expect_success("acct_transition", json!({ "n": 1 }), Some(7)).await;
+    let popped = app.state.queue_manager().dequeue_envelope(&TRANSACTION_QUEUE).await.unwrap().unwrap();
+    assert_eq!(popped.transaction_id, id);
+    let (later, _, _) = app.submit_transaction_expect_success("acct_transition", json!({ "n": 2 }), None).await;
+    let pending = || async { app.state.queue_manager().pending_count("acct_transition").await.unwrap() };
+    assert_eq!(pending().await, 2);
+
+    let response = patch_status(&app, &id, json!({ "status": "processing" })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["status"], "processing");
+
+    let response = patch_status(&app, &id, json!({ "status": "failed", "error_message": "card declined" })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let row = load_row(&app, &id).await;
+    assert_eq!((row.status.as_str(), row.retry_count), ("failed", 0));
+    assert_eq!(row.error_message.as_deref(), Some("card declined"));
+    assert!(row.processed_at.is_some());
+    assert_eq!(pending().await, 1);
+
+    let response = patch_status(&app, &id, json!({ "status": "retry" })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["status"], "retry");
+    let row = load_row(&app, &id).await;
+    assert_eq!(row.retry_count, 1);
+    assert!(row.processed_at.is_none());
+    assert_eq!(pending().await, 2);
+    // Back ahead of the later, lower priority submit
+    assert_eq!(app.queue_order().await, [id.clone(), later]);
+
+    // The next worker picks the retry up
+    let popped = app.state.queue_manager().dequeue_envelope(&TRANSACTION_QUEUE).await.unwrap().unwrap();
+    assert_eq!(popped.transaction_id, id);
+    let response = patch_status(&app, &id, json!({ "status": "processing" })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    app.teardown().await;
+}
+
+/// Test moves off the graph, unknown statuses and missing rows are refused without touching the row
+#[tokio::test]
+async fn test_illegal_transitions_are_refused() {
+    let app = admin_app().await;
+    let (id, _, _) = app.submit_transaction_expect_success("acct_transition", json!({ "n": 1 }), None).await;
+
+    for status in ["completed", "failed", "retry", "pending"] {
+        let response = patch_status(&app, &id, json!({ "status": status })).await;
+        assert_eq!(response.status, StatusCode::CONFLICT, "{}", status);
+        assert_eq!(response.body["error"]["code"], "invalid_status_transition", "{}", response.body);
+        assert_eq!(response.body["error"]["details"]["status"], "pending");
+    }
+    let response = patch_status(&app, &id, json!({ "status": "done" })).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let response = patch_status(&app, &Uuid::new_v4().to_string(), json!({ "status": "processing" })).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(load_row(&app, &id).await.status, "pending");
+
+    // Cancelling is the cancel endpoint's, and only from pending
+    let response = patch_status(&app, &id, json!({ "status": "cancelled" })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert!(app.queue_order().await.is_empty());
+    let response = patch_status(&app, &id, json!({ "status": "cancelled" })).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.body["error"]["details"]["status"], "cancelled");
+
+    app.teardown().await;
+}
+
+/// Test callers without an admin key cannot move a transaction
+#[tokio::test]
+async fn test_requires_admin_key() {
+    let app = admin_app().await;
+    let (id, _, _) = app.submit_transaction_expect_success("acct_transition", json!({ "n": 1 }), None).await;
+    for key in [None, Some("wrong-key")] {
+        let mut request = Request::patch(format!("/v1/admin/transactions/{}/status", id))
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(key) = key {
+            request = request.header("X-Admin-Key", key);
+        }
+        let body = Body::from(json!({ "status": "completed" }).to_string());
+        let response = app.request(request.body(body).unwrap()).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED, "{}", response.body);
+    }
+    // The old unauthenticated route is gone
+    let request = Request::patch(format!("/v1/transactions/{}/status", id))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "status": "completed" }).to_string()))
+        .unwrap();
+    assert_eq!(app.request(request).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(load_row(&app, &id).await.status, "pending");
+
+    app.teardown().await;
+}
+
+/// Test a transaction with no retries left is not retried, whether failed or still processing
+#[tokio::test]
+async fn test_retry_needs_retries_left() {
+    let app = admin_app().await;
+    let (id, _, _) = app.submit_transaction_expect_success("acct_transition", json!({ "n": 1 }), None).await;
+    let uuid: Uuid = id.parse().unwrap();
+    let mut conn = app.state.db_pool.get().await.expect("Failed to get connection");
+    for (from, to) in [
+        (TransactionStatus::Pending, TransactionStatus::Processing),
+        (TransactionStatus::Processing, TransactionStatus::Failed),
+    ] {
+        assert!(TransactionQueue::transition_status(&mut conn, uuid, from, to).await.unwrap());
+    }
+    diesel::update(transaction_queue::table.find(uuid))
+        .set(transaction_queue::retry_count.eq(transaction_queue::max_retries))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let response = patch_status(&app, &id, json!({ "status": "retry" })).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.body["error"]["code"], "retries_exhausted", "{}", response.body);
+    // The guard holds in the UPDATE too, for a caller that skips the read
+    let moved = TransactionQueue::transition(&mut conn, uuid, TransactionStatus::Failed, TransactionStatus::Retry, None)
+        .await
+        .unwrap();
+    assert!(moved.is_none());
+    assert_eq!(load_row(&app, &id).await.status, "failed");
+
+    // Nor can a processing row be sent round again once its retries are spent
+    let (id, _, _) = app.submit_transaction_expect_success("acct_transition", json!({ "n": 2 }), None).await;
+    let uuid: Uuid = id.parse().unwrap();
+    let (pending, processing) = (TransactionStatus::Pending, TransactionStatus::Processing);
+    assert!(TransactionQueue::transition_status(&mut conn, uuid, pending, processing).await.unwrap());
+    diesel::update(transaction_queue::table.find(uuid))
+        .set(transaction_queue::retry_count.eq(transaction_queue::max_retries))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let response = patch_status(&app, &id, json!({ "status": "retry" })).await;
+    assert_eq!(response.status, StatusCode::CONFLICT);
+    assert_eq!(response.body["error"]["code"], "retries_exhausted", "{}", response.body);
+    assert!(!TransactionQueue::transition_status(&mut conn, uuid, processing, TransactionStatus::Retry).await.unwrap());
+    assert_eq!(load_row(&app, &id).await.status, "processing");
+    drop(conn);
+
+    app.teardown().await;
+}
+
+/// Test of two writers finishing the same transaction at once, one wins and the other learns the status it lost to
+#[tokio::test]
+async fn test_racing_updates_conflict() {
+    let app = admin_app().await;
+    for n in 0..10 {
+        let (id, _, _) = app.submit_transaction_expect_success("acct_transition", json!({ "n": n }), None).await;
+        let response = patch_status(&app, &id, json!({ "status": "processing" })).await;
+        assert_eq!(response.status, StatusCode::OK);
+
+        let (completed, failed) = tokio::join!(
+            patch_status(&app, &id, json!({ "status": "completed" })),
+            patch_status(&app, &id, json!({ "status": "failed" })),
+        );
+        let (winner, loser, won) = match completed.status {
+            StatusCode::OK => (completed, failed, "completed"),
+            _ => (failed, completed, "failed"),
+        };
+        assert_eq!(winner.status, StatusCode::OK, "{}", winner.body);
+        assert_eq!(loser.status, StatusCode::CONFLICT, "{}", loser.body);
+        assert_eq!(loser.body["error"]["details"]["status"], won);
+        assert_eq!(load_row(&app, &id).await.status, won);
+    }
+    assert_eq!(app.state.queue_manager().pending_count("acct_transition").await.unwrap(), 0);
+
+    app.teardown().await;
+}
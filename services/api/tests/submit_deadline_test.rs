@@ -0,0 +1,250 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use common::*;
+use futures::future::BoxFuture;
+use redis_cache::{ProcessingHold, QueueName, RedisError};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+use transaction_queue_api::config::Config;
+use transaction_queue_api::queue_stats::QueueStats;
+use transaction_queue_api::submit_deadline::{place, Deadline, QueueEntry, SubmitPhase, SubmitQueue};
+use transaction_queue_api::{v1, TRANSACTION_QUEUE};
+
+/// Queue fake whose operations take as long as configured
+#[derive(Default)]
+struct SlowQueue {
+    add_delay: Duration,
+    position_delay: Duration,
+    lookup_delay: Duration,
+    added: Mutex<Vec<String>>,
+    position_calls: AtomicUsize,
+}
+
+impl SubmitQueue for SlowQueue {
+    fn add<'a>(&'a self, _queue_name: &'a QueueName, entry: QueueEntry<'a>) -> BoxFuture<'a, Result<(), RedisError>> {
+        Box::pin(async move {
+            tokio::time::sleep(self.add_delay).await;
+            self.added.lock().unwrap().push(entry.member.to_string());
+            Ok(())
+        })
+    }
+
+    fn position<'a>(&'a self, _queue_name: &'a QueueName, _member: &'a str) -> BoxFuture<'a, Result<i64, RedisError>> {
+        Box::pin(async move {
+            self.position_calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.position_delay).await;
+            Ok(3)
+        })
+    }
+
+    fn account_position<'a>(
+        &'a self,
+        _queue_name: &'a QueueName,
+        _member: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>> {
+        Box::pin(async move { Ok(None) })
+    }
+
+    fn processing_rate<'a>(&'a self, _queue_name: &'a QueueName) -> BoxFuture<'a, Result<Option<f64>, RedisError>> {
+        Box::pin(async move {
+            tokio::time::sleep(self.lookup_delay).await;
+            Ok(Some(1.0))
+        })
+    }
+
+    fn live_worker_count(&self, _max_age_seconds: u64) -> BoxFuture<'_, Result<i64, RedisError>> {
+        Box::pin(async move {
+            tokio::time::sleep(self.lookup_delay).await;
+            Ok(1)
+        })
+    }
+
+    fn transaction_position<'a>(
+        &'a self,
+        _queue_name: &'a QueueName,
+        _account_id: &'a str,
+        _transaction_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<i64>, RedisError>> {
+        Box::pin(async move { Ok(Some(3)) })
+    }
+    fn reserve_pending<'a>(&'a self, _account_id: &'a str, _cap: u32) -> BoxFuture<'a, Result<bool, RedisError>> {
+        Box::pin(async move { Ok(true) })
+    }
+
+    fn release_pending<'a>(&'a self, _account_id: &'a str) -> BoxFuture<'a, Result<i64, RedisError>> {
+        Box::pin(async move { Ok(0) })
+    }
+
+    fn hold<'a>(&'a self, _transaction_id: &'a str) -> BoxFuture<'a, Result<Option<ProcessingHold>, RedisError>> {
+        Box::pin(async move { Ok(None) })
+    }
+}
+
+fn config() -> Config {
+    let lookup = |var: &str| match var {
+        "DATABASE_URL" => Some(DEFAULT_DATABASE_URL.to_string()),
+        "REDIS_URL" => Some(DEFAULT_REDIS_URL.to_string()),
+        _ => None,
+    };
+    Config::from_lookup(&service_config::Env::new(&lookup)).unwrap()
+}
+
+/// The member "member" of `account_id` at priority 0
+fn entry(account_id: &str) -> QueueEntry<'_> {
+    QueueEntry::new("member", account_id, 0)
+}
+
+/// 10 items queued, draining at 2 per second
+fn stats() -> QueueStats {
+    QueueStats {
+        queue_depth: 10,
+        enqueue_rate_per_second: 2.0,
+        dequeue_rate_per_second: 2.0,
+        estimated_drain_seconds: 5.0,
+    }
+}
+
+/// Test a queue that answers in time gives the full placement
+#[tokio::test]
+async fn test_fast_queue_gets_position() {
+    let queue = SlowQueue::default();
+    let mut deadline = Deadline::after(Duration::from_millis(80));
+
+    let placement = place(&queue, &mut deadline, &TRANSACTION_QUEUE, entry("acct"), &config(), None)
+        .await
+        .unwrap();
+
+    assert_eq!(placement.position, Some(3));
+    // Two items ahead at one per second, plus its own slot
+    assert_eq!(placement.estimated_processing_time_seconds, 3);
+    assert_eq!(deadline.exceeded_in(), None);
+}
+
+/// Test the built-in tiers scale the estimate: basic waits longer and enterprise less
+#[tokio::test]
+async fn test_tier_scales_estimate() {
+    let mut estimates = Vec::new();
+    for account_id in ["basic_acct", "premium_acct", "enterprise_acct"] {
+        let queue = SlowQueue::default();
+        let mut deadline = Deadline::after(Duration::from_millis(80));
+        let placement = place(&queue, &mut deadline, &TRANSACTION_QUEUE, entry(account_id), &config(), None)
+            .await
+            .unwrap();
+        estimates.push(placement.estimated_processing_time_seconds);
+    }
+
+    // Three slots at one per second, times 1.5, 1 and 0.5
+    assert_eq!(estimates, [5, 3, 2]);
+}
+
+/// Test a slow position lookup is abandoned at the deadline in favour of a stats estimate
+#[tokio::test]
+async fn test_slow_position_degrades_within_budget() {
+    let queue = SlowQueue {
+        position_delay: Duration::from_millis(500),
+        ..Default::default()
+    };
+    let mut deadline = Deadline::after(Duration::from_millis(30));
+    let started = Instant::now();
+
+    let placement = place(&queue, &mut deadline, &TRANSACTION_QUEUE, entry("acct"), &config(), Some(&stats()))
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_millis(200), "took {:?}", started.elapsed());
+    assert_eq!(placement.position, None);
+    // Behind all 10 sampled items at 2 per second: ceil(11 / 2)
+    assert_eq!(placement.estimated_processing_time_seconds, 6);
+    assert_eq!(deadline.exceeded_in(), Some(SubmitPhase::QueuePosition));
+    assert_eq!(*queue.added.lock().unwrap(), vec!["member".to_string()]);
+}
+
+/// Test a spent budget still queues the item but skips the position lookup
+#[tokio::test]
+async fn test_spent_budget_still_enqueues() {
+    let queue = SlowQueue::default();
+    let mut deadline = Deadline::after(Duration::ZERO);
+
+    let placement = place(&queue, &mut deadline, &TRANSACTION_QUEUE, entry("acct"), &config(), None)
+        .await
+        .unwrap();
+
+    assert_eq!(placement.position, None);
+    // No stats sample yet, so the per-item heuristic
+    assert_eq!(placement.estimated_processing_time_seconds, 30);
+    assert_eq!(deadline.exceeded_in(), Some(SubmitPhase::Enqueue));
+    assert_eq!(queue.added.lock().unwrap().len(), 1);
+    assert_eq!(queue.position_calls.load(Ordering::SeqCst), 0);
+}
+
+/// Test slow estimate lookups keep the position and price it from the stats sample
+#[tokio::test]
+async fn test_slow_estimate_lookups_fall_back_to_stats() {
+    let queue = SlowQueue {
+        lookup_delay: Duration::from_millis(500),
+        ..Default::default()
+    };
+    let mut deadline = Deadline::after(Duration::from_millis(30));
+
+    let placement = place(&queue, &mut deadline, &TRANSACTION_QUEUE, entry("acct"), &config(), Some(&stats()))
+        .await
+        .unwrap();
+
+    assert_eq!(placement.position, Some(3));
+    // Two items ahead plus its own slot, at 2 per second
+    assert_eq!(placement.estimated_processing_time_seconds, 2);
+    assert_eq!(deadline.exceeded_in(), Some(SubmitPhase::Estimate));
+}
+
+/// Test the first phase to finish late is the one recorded
+#[tokio::test]
+async fn test_checkpoint_records_first_late_phase() {
+    let mut deadline = Deadline::after(Duration::from_millis(20));
+    deadline.checkpoint(SubmitPhase::RateLimit);
+    assert_eq!(deadline.exceeded_in(), None);
+
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    deadline.checkpoint(SubmitPhase::Insert);
+    deadline.checkpoint(SubmitPhase::Enqueue);
+    assert!(deadline.is_exhausted());
+    assert_eq!(deadline.exceeded_in(), Some(SubmitPhase::Insert));
+}
+
+/// Test the handler answers 202 with a null position when the queue is too slow
+#[tokio::test]
+async fn test_submit_answers_accepted_when_queue_is_slow() {
+    let queue = Arc::new(SlowQueue {
+        position_delay: Duration::from_secs(2),
+        ..Default::default()
+    });
+    let state = TestEnvironment::app_state().await.with_submit_queue(queue.clone());
+    let app = v1::router(state.clone()).with_state(state);
+
+    let request = Request::post("/transactions/submit")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            json!({
+                "account_id": TestData::unique_account_id(),
+                "transaction_data": TestData::sample_transaction_data(),
+            })
+            .to_string(),
+        ))
+        .unwrap();
+    let started = Instant::now();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(1), "took {:?}", started.elapsed());
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert!(response.headers().contains_key("X-RateLimit-Remaining"));
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert!(body["transaction_id"].is_string());
+    assert!(body["queue_position"].is_null());
+    assert!(body["estimated_processing_time_seconds"].is_i64());
+    assert_eq!(body["status"], "pending");
+    assert_eq!(queue.added.lock().unwrap().len(), 1);
+}
@@ -0,0 +1,71 @@
+//! Run with `cargo test --features otel`
+#![cfg(feature = "otel")]
+
+use opentelemetry::trace::{TraceContextExt, TracerProvider};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use redis_cache::QueueEnvelope;
+use serde_json::value::RawValue;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use transaction_queue_api::trace_context::{current_traceparent, processing_span};
+
+/// Subscriber that turns tracing spans into OpenTelemetry spans
+fn otel_subscriber() -> impl tracing::Subscriber + Send + Sync {
+    let tracer = SdkTracerProvider::builder().build().tracer("trace_context_test");
+    tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+fn span_ids(span: &Span) -> (opentelemetry::trace::TraceId, opentelemetry::trace::SpanId) {
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+    (span_context.trace_id(), span_context.span_id())
+}
+
+fn enqueue(traceparent: Option<&str>) -> QueueEnvelope {
+    let data = RawValue::from_string(r#"{"amount":1}"#.to_string()).unwrap();
+    let member = QueueEnvelope::encode_compressed("tx-1", "acct", &data, 16 * 1024, traceparent).unwrap();
+    QueueEnvelope::decode(&member).unwrap()
+}
+
+/// Test the processing span continues the trace submit wrote into the envelope
+#[test]
+fn test_processing_span_continues_submit_trace() {
+    let _guard = tracing::subscriber::set_default(otel_subscriber());
+
+    let submit = tracing::info_span!("submit");
+    let traceparent = submit.in_scope(current_traceparent).expect("submit span has no trace context");
+    let (submit_trace, submit_span) = span_ids(&submit);
+    drop(submit);
+
+    let envelope = enqueue(Some(&traceparent));
+    assert_eq!(envelope.traceparent.as_deref(), Some(traceparent.as_str()));
+    assert!(traceparent.contains(&submit_trace.to_string()));
+
+    let processing = processing_span(&envelope);
+    let (processing_trace, processing_span_id) = span_ids(&processing);
+    assert_eq!(processing_trace, submit_trace);
+    assert_ne!(processing_span_id, submit_span);
+}
+
+/// Test items enqueued without a trace start a new one
+#[test]
+fn test_untraced_item_starts_new_trace() {
+    let _guard = tracing::subscriber::set_default(otel_subscriber());
+
+    let submit = tracing::info_span!("submit");
+    let (submit_trace, _) = span_ids(&submit);
+
+    let processing = processing_span(&enqueue(None));
+    let (processing_trace, _) = span_ids(&processing);
+    assert!(processing_trace != opentelemetry::trace::TraceId::INVALID);
+    assert_ne!(processing_trace, submit_trace);
+}
+
+/// Test no traceparent is produced outside an OpenTelemetry trace
+#[test]
+fn test_no_traceparent_without_otel_layer() {
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry());
+    let span = tracing::info_span!("submit");
+    assert!(span.in_scope(current_traceparent).is_none());
+}
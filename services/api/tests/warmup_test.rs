@@ -0,0 +1,48 @@
+mod common;
+
+use common::*;
+use postgres_models::MIN_IDLE_CONNECTIONS;
+use transaction_queue_api::config::WarmupConfig;
+use transaction_queue_api::warmup;
+
+fn warmup_config(redis_connections: usize, budget_ms: u64) -> WarmupConfig {
+    WarmupConfig {
+        enabled: true,
+        redis_connections,
+        budget_ms,
+        hot_accounts: vec![TestData::enterprise_account_id(), TestData::basic_tier_account_id()],
+    }
+}
+
+/// Test the pools hold the expected idle connections after warm-up
+#[tokio::test]
+async fn test_warmup_fills_pools() {
+    let db_pool = TestEnvironment::db_pool().await;
+    let redis_pool = TestEnvironment::redis_pool().await;
+    let redis_connections = 8.min(redis_pool.status().max_size);
+
+    let report = warmup::run(&db_pool, &redis_pool, &warmup_config(redis_connections, 10_000)).await;
+
+    assert!(!report.timed_out);
+    assert_eq!(report.db_connections, MIN_IDLE_CONNECTIONS as usize);
+    assert_eq!(report.redis_connections, redis_connections);
+    assert_eq!(report.hot_accounts_primed, 2);
+
+    assert!(db_pool.state().idle_connections >= MIN_IDLE_CONNECTIONS);
+    let status = redis_pool.status();
+    assert_eq!(status.size, redis_connections);
+    assert_eq!(status.available, redis_connections);
+}
+
+/// Test warm-up stops at its budget instead of delaying startup
+#[tokio::test]
+async fn test_warmup_respects_budget() {
+    let db_pool = TestEnvironment::db_pool().await;
+    let redis_pool = TestEnvironment::redis_pool().await;
+
+    let started = std::time::Instant::now();
+    let report = warmup::run(&db_pool, &redis_pool, &warmup_config(8, 0)).await;
+
+    assert!(report.timed_out);
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+}
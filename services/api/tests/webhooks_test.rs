@@ -0,0 +1,181 @@
+mod common;
+
+use axum::{body::Bytes, http::HeaderMap, routing::post, Router};
+use common::*;
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use transaction_queue_api::webhooks::{self, EVENT_HEADER, SIGNATURE_HEADER, TEST_EVENT_TYPE};
+
+/// Default MAX_WEBHOOKS_PER_ACCOUNT of the dev server
+const MAX_WEBHOOKS_PER_ACCOUNT: usize = 10;
+
+struct ReceivedEvent {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Start a local endpoint that records every delivery and answers with `status`
+async fn start_listener(status: StatusCode) -> (String, mpsc::UnboundedReceiver<ReceivedEvent>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/hook",
+        post(move |headers: HeaderMap, body: Bytes| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(ReceivedEvent { headers, body });
+                status
+            }
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/hook", addr), rx)
+}
+
+fn webhooks_path(account_id: &str) -> String {
+    format!("/v1/accounts/{}/webhooks", account_id)
+}
+
+async fn create_webhook(client: &TestClient, account_id: &str, url: &str) -> reqwest::Response {
+    client
+        .json_request(
+            Method::POST,
+            &webhooks_path(account_id),
+            Some(json!({ "url": url, "event_types": ["transaction.completed"] })),
+        )
+        .await
+        .expect("Request failed")
+}
+
+/// Test signatures are deterministic and depend on secret, timestamp and body
+#[test]
+fn test_signature_format() {
+    let signature = webhooks::sign("whsec_test", 1_700_000_000, b"{}");
+    assert!(signature.starts_with("t=1700000000,v1="));
+    assert_eq!(signature, webhooks::sign("whsec_test", 1_700_000_000, b"{}"));
+    assert_ne!(signature, webhooks::sign("whsec_other", 1_700_000_000, b"{}"));
+    assert_ne!(signature, webhooks::sign("whsec_test", 1_700_000_001, b"{}"));
+    assert_ne!(signature, webhooks::sign("whsec_test", 1_700_000_000, b"[]"));
+
+    let secret = webhooks::generate_secret();
+    assert!(secret.starts_with("whsec_"));
+    assert_ne!(secret, webhooks::generate_secret());
+}
+
+/// Test create, list, test-fire against a local listener, and delete
+#[tokio::test]
+async fn test_webhook_lifecycle() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+    let (url, mut received) = start_listener(StatusCode::OK).await;
+
+    let response = create_webhook(&client, &account_id, &url).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    let webhook_id = created["id"].as_str().unwrap().to_string();
+    let secret = created["secret"].as_str().unwrap().to_string();
+    assert_eq!(created["url"], url.as_str());
+    assert_eq!(created["event_types"], json!(["transaction.completed"]));
+
+    // Listing never exposes the secret
+    let response = client
+        .json_request(Method::GET, &webhooks_path(&account_id), None)
+        .await
+        .unwrap();
+    let listed: Value = response.json().await.unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["id"], webhook_id.as_str());
+    assert!(listed[0].get("secret").is_none());
+
+    // Test-fire delivers a signed sample event synchronously
+    let test_path = format!("{}/{}/test", webhooks_path(&account_id), webhook_id);
+    let response = client.json_request(Method::POST, &test_path, None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let result: Value = response.json().await.unwrap();
+    assert_eq!(result["delivered"], true);
+    assert_eq!(result["status_code"], 200);
+
+    let event = received.try_recv().expect("Listener should have received the test event");
+    assert_eq!(event.headers[EVENT_HEADER], TEST_EVENT_TYPE);
+    let signature = event.headers[SIGNATURE_HEADER].to_str().unwrap();
+    let timestamp: i64 = signature
+        .strip_prefix("t=")
+        .and_then(|rest| rest.split(',').next())
+        .and_then(|t| t.parse().ok())
+        .expect("Signature should carry a timestamp");
+    assert_eq!(signature, webhooks::sign(&secret, timestamp, &event.body));
+    let payload: Value = serde_json::from_slice(&event.body).unwrap();
+    assert_eq!(payload["type"], TEST_EVENT_TYPE);
+    assert_eq!(payload["data"]["account_id"], account_id.as_str());
+
+    // Delete, then the webhook is gone
+    let webhook_path = format!("{}/{}", webhooks_path(&account_id), webhook_id);
+    let response = client.json_request(Method::DELETE, &webhook_path, None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = client.json_request(Method::DELETE, &webhook_path, None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = client.json_request(Method::POST, &test_path, None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test test-fire reports a failing downstream status
+#[tokio::test]
+async fn test_fire_reports_downstream_failure() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+    let (url, _received) = start_listener(StatusCode::SERVICE_UNAVAILABLE).await;
+
+    let created: Value = create_webhook(&client, &account_id, &url).await.json().await.unwrap();
+    let test_path = format!("{}/{}/test", webhooks_path(&account_id), created["id"].as_str().unwrap());
+
+    let result: Value = client
+        .json_request(Method::POST, &test_path, None)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(result["delivered"], false);
+    assert_eq!(result["status_code"], 503);
+}
+
+/// Test invalid URLs and event types are rejected
+#[tokio::test]
+async fn test_webhook_validation() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let path = webhooks_path(&TestData::unique_account_id());
+
+    let cases = [
+        json!({ "url": "not a url", "event_types": ["transaction.completed"] }),
+        json!({ "url": "ftp://example.com/hook", "event_types": ["transaction.completed"] }),
+        json!({ "url": "https://example.com/hook", "event_types": [] }),
+        json!({ "url": "https://example.com/hook", "event_types": ["transaction.exploded"] }),
+    ];
+    for body in cases {
+        let response = client.json_request(Method::POST, &path, Some(body.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{} should be rejected", body);
+    }
+}
+
+/// Test an account cannot register more than the configured number of endpoints
+#[tokio::test]
+async fn test_webhook_limit_per_account() {
+    TestEnvironment::validate_test_environment().await;
+    let client = TestClient::new();
+    let account_id = TestData::unique_account_id();
+
+    for _ in 0..MAX_WEBHOOKS_PER_ACCOUNT {
+        let response = create_webhook(&client, &account_id, "https://example.com/hook").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    let response = create_webhook(&client, &account_id, "https://example.com/hook").await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
@@ -0,0 +1,187 @@
+//! The worker that drains the transaction queue.
+//!
+//! Up to `concurrency` transactions are in flight at once. Whenever slots
+//! are free the worker pops a batch of that many through
+//! `QueueEngine::dequeue_batch`, trying the configured queues in order, and
+//! executes each popped transaction in a task of its own with
+//! `QueueEngine::process_dequeued`, which stores the result, or retries it
+//! after a backoff while it has retries left. When nothing is ready the
+//! worker sleeps for the poll interval.
+//!
+//! Finished transactions are counted towards the queue's processing rate and
+//! the worker sends heartbeats, so the API's submit estimates reflect the
+//! throughput of the workers actually running.
+//!
+//! On shutdown nothing new is popped. In-flight transactions are given the
+//! drain timeout to finish; any still running after it are abandoned in
+//! "processing", where the API's stale processing check finds them.
+
+use queue_engine::{
+    Dequeued, EngineConfig, EngineError, EnginePools, ProcessError, QueueEngine, RetryBackoff, TransactionProcessor,
+};
+use redis_cache::{KeyLayout, KeySpace, QueueName};
+use service_config::WorkerConfig;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
+
+/// Engine settings for consuming `queue_name` under `config`
+pub fn engine_config(config: &WorkerConfig, queue_name: QueueName) -> EngineConfig {
+    let layout = if config.redis_cluster_keys {
+        KeyLayout::Cluster
+    } else {
+        KeyLayout::Standalone
+    };
+    let keys = KeySpace::new(layout);
+    let keys = match &config.redis_key_prefix {
+        Some(prefix) => keys.with_prefix(prefix).expect("REDIS_KEY_PREFIX is validated with the config"),
+        None => keys,
+    };
+    EngineConfig {
+        queue_name,
+        compress_over_bytes: config.queue_compress_over_bytes,
+        keys,
+        publish_events: config.common.queue_events,
+        retry_backoff: RetryBackoff {
+            base: Duration::from_millis(config.retry_base_delay_ms),
+            max: Duration::from_millis(config.retry_max_delay_ms),
+        },
+        ..EngineConfig::default()
+    }
+}
+
+pub struct Worker<P> {
+    /// One engine per consumed queue, tried in the configured order
+    engines: Vec<QueueEngine>,
+    processor: P,
+    /// Name the worker's heartbeats are recorded under
+    id: String,
+    concurrency: usize,
+    poll_interval: Duration,
+    drain_timeout: Duration,
+    heartbeat_interval: Duration,
+}
+
+impl<P: TransactionProcessor + 'static> Worker<P> {
+    pub fn new(pools: EnginePools, config: &WorkerConfig, processor: P) -> anyhow::Result<Self> {
+        let engines = config
+            .queues
+            .iter()
+            .map(|queue| {
+                let queue_name = QueueName::new(queue.clone())
+                    .map_err(|e| anyhow::anyhow!("Invalid queue name {:?}: {}", queue, e))?;
+                Ok(QueueEngine::new(pools.clone(), engine_config(config, queue_name)))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            engines,
+            processor,
+            id: format!("worker-{}", uuid::Uuid::new_v4().simple()),
+            concurrency: config.concurrency,
+            poll_interval: Duration::from_millis(config.poll_interval_ms),
+            drain_timeout: Duration::from_secs(config.drain_timeout_seconds),
+            heartbeat_interval: Duration::from_secs(config.heartbeat_interval_seconds),
+        })
+    }
+
+    /// Process transactions until `shutdown` resolves, then let the ones in
+    /// flight finish for up to the drain timeout
+    pub async fn run(self, shutdown: impl Future<Output = ()>) {
+        let worker = Arc::new(self);
+        let (stop, stopped) = watch::channel(false);
+        let slots = Arc::new(Semaphore::new(worker.concurrency));
+        let mut dispatcher = tokio::spawn(worker.clone().dispatch(slots.clone(), stopped));
+        let heartbeat = tokio::spawn(worker.clone().heartbeat());
+        info!(worker_id = worker.id, concurrency = worker.concurrency, "Worker started");
+
+        shutdown.await;
+        info!("Finishing in-flight transactions");
+        let _ = stop.send(true);
+        if tokio::time::timeout(worker.drain_timeout, &mut dispatcher).await.is_err() {
+            let in_flight = worker.concurrency - slots.available_permits();
+            warn!(in_flight, "Drain timeout passed, abandoning in-flight transactions");
+            // Dropping the dispatcher's tasks aborts them
+            dispatcher.abort();
+        }
+        heartbeat.abort();
+    }
+
+    /// Fill free slots with batches of ready transactions, sleeping between
+    /// polls when nothing is, until told to stop; then wait for the ones in
+    /// flight
+    async fn dispatch(self: Arc<Self>, slots: Arc<Semaphore>, mut stopped: watch::Receiver<bool>) {
+        let mut in_flight = JoinSet::new();
+        while !*stopped.borrow() {
+            let mut free = tokio::select! {
+                permit = slots.clone().acquire_owned() => permit.expect("worker slots are never closed"),
+                _ = stopped.changed() => continue,
+            };
+            while in_flight.try_join_next().is_some() {}
+            let more = slots.available_permits() as u32;
+            if let Ok(permits) = slots.clone().try_acquire_many_owned(more) {
+                free.merge(permits);
+            }
+            if !self.dispatch_round(&mut free, &mut in_flight).await {
+                drop(free);
+                tokio::select! {
+                    _ = stopped.changed() => {}
+                    _ = tokio::time::sleep(self.poll_interval) => {}
+                }
+            }
+        }
+        while in_flight.join_next().await.is_some() {}
+    }
+
+    /// Pop as many transactions as there are `free` slots, from each queue
+    /// in turn, and start each in a task holding one of the slots; true
+    /// when any was started
+    async fn dispatch_round(self: &Arc<Self>, free: &mut OwnedSemaphorePermit, in_flight: &mut JoinSet<()>) -> bool {
+        let mut started = false;
+        for (index, engine) in self.engines.iter().enumerate() {
+            if free.num_permits() == 0 {
+                break;
+            }
+            let batch = match engine.dequeue_batch(free.num_permits()).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    error!(queue = %engine.config().queue_name, "Failed to dequeue transactions: {}", e);
+                    continue;
+                }
+            };
+            for dequeued in batch {
+                let slot = free.split(1).expect("a batch is no larger than the free slots");
+                in_flight.spawn(self.clone().process(index, dequeued, slot));
+                started = true;
+            }
+        }
+        started
+    }
+
+    /// Execute one dequeued transaction on the engine at `index`, freeing
+    /// its slot when done
+    async fn process(self: Arc<Self>, index: usize, dequeued: Dequeued, _slot: OwnedSemaphorePermit) {
+        let engine = &self.engines[index];
+        match engine.process_dequeued(&self.processor, dequeued).await {
+            Ok(processed) => {
+                debug!(transaction_id = %processed.transaction_id, outcome = ?processed.outcome, "Processed")
+            }
+            // Already retried or failed, and logged, by the engine
+            Err(EngineError::Process(ProcessError::Processor(_))) => {}
+            Err(e) => error!(queue = %engine.config().queue_name, "Failed to process a transaction: {}", e),
+        }
+    }
+
+    /// Record a heartbeat every interval, so the API counts this worker
+    async fn heartbeat(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.heartbeat_interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.engines[0].queue_manager().record_heartbeat(&self.id).await {
+                warn!(worker_id = self.id, "Failed to record a heartbeat: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use dotenvy::dotenv;
+use queue_engine::{EnginePools, NoopProcessor, SimulatedProcessor};
+use redis_cache::RedisOptions;
+use service_config::{LogFormat, ProcessorKind, WorkerConfig};
+use std::time::Duration;
+use tracing::info;
+use transaction_queue_worker::Worker;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Load environment variables
+    dotenv().ok();
+    let config = WorkerConfig::from_env()?;
+
+    // Initialize tracing
+    let subscriber = tracing_subscriber::fmt()
+        .with_target(false)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "transaction_queue_worker=debug,queue_engine=info".into()),
+        );
+    match config.common.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+
+    let redis_options = RedisOptions {
+        db: config.common.redis_db,
+        response_timeout: config.common.redis_response_timeout_ms.map(Duration::from_millis),
+        pool: config.common.redis_pool.clone(),
+    };
+    let pools = EnginePools {
+        db: postgres_models::create_pool_with_config(&config.common.database_url, &config.common.db_pool)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create database pool: {}", e))?,
+        redis: redis_cache::create_pool_with_options(&config.common.redis_url, &redis_options)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create Redis pool: {}", e))?,
+    };
+
+    // The processor is a type parameter of the worker, so each kind gets its own
+    match config.processor {
+        ProcessorKind::Simulated => Worker::new(pools, &config, SimulatedProcessor)?.run(shutdown_signal()).await,
+        ProcessorKind::Noop => Worker::new(pools, &config, NoopProcessor)?.run(shutdown_signal()).await,
+    }
+    info!("Worker stopped");
+
+    Ok(())
+}
+
+/// Resolves on Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutdown signal received");
+}